@@ -0,0 +1,142 @@
+// Auto-generated by Gem compiler
+// Do not edit manually
+
+#![allow(unused)]
+
+#[derive(Debug, Clone)]
+pub struct GameOverLabelProps {
+    pub color: (u8, u8, u8, u8),
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const GAMEOVERLABEL_PROPS: GameOverLabelProps = GameOverLabelProps {
+    color: (179, 51, 204, 255),
+    position: (200.0, 150.0),
+    size: (260.0, 40.0),
+    text: "Game Over!",
+};
+
+#[derive(Debug, Clone)]
+pub struct RetryButtonProps {
+    pub color: (u8, u8, u8, u8),
+    pub link: &'static str,
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const RETRYBUTTON_PROPS: RetryButtonProps = RetryButtonProps {
+    color: (51, 179, 77, 255),
+    link: "#example:retry_button_logic",
+    position: (200.0, 250.0),
+    size: (200.0, 56.0),
+    text: "Retry",
+};
+
+#[derive(Debug, Clone)]
+pub struct MenuButtonProps {
+    pub color: (u8, u8, u8, u8),
+    pub link: &'static str,
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const MENUBUTTON_PROPS: MenuButtonProps = MenuButtonProps {
+    color: (51, 179, 77, 255),
+    link: "#example:menu_button_logic",
+    position: (200.0, 300.0),
+    size: (200.0, 56.0),
+    text: "Main Menu",
+};
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: &'static str,
+    pub class_name: &'static str,
+    pub node_index: usize,
+    pub uid: Option<&'static str>,
+}
+
+pub const GAMEOVER_NODE: Node = Node {
+    name: "GameOver",
+    class_name: "Gem",
+    node_index: 0,
+    uid: None,
+};
+
+pub const GAMEOVERLABEL_NODE: Node = Node {
+    name: "GameOverLabel",
+    class_name: "LabelGem",
+    node_index: 1,
+    uid: None,
+};
+
+pub const RETRYBUTTON_NODE: Node = Node {
+    name: "RetryButton",
+    class_name: "ButtonGem",
+    node_index: 2,
+    uid: None,
+};
+
+pub const MENUBUTTON_NODE: Node = Node {
+    name: "MenuButton",
+    class_name: "ButtonGem",
+    node_index: 3,
+    uid: None,
+};
+
+// Children adjacency lists
+pub const GAMEOVER_CHILDREN: &[usize] = &[1, 2, 3];
+pub const GAMEOVERLABEL_CHILDREN: &[usize] = &[];
+pub const RETRYBUTTON_CHILDREN: &[usize] = &[];
+pub const MENUBUTTON_CHILDREN: &[usize] = &[];
+
+// Signals each node's class emits
+pub const GAMEOVER_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const GAMEOVERLABEL_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const RETRYBUTTON_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const MENUBUTTON_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+
+#[derive(Debug)]
+pub struct Scene {
+    pub root_index: usize,
+    pub node_count: usize,
+}
+
+pub const SCENE: Scene = Scene {
+    root_index: 0,
+    node_count: 4,
+};
+
+pub fn build_scene() -> Scene {
+    SCENE
+}
+
+pub fn find_node_by_name(name: &str) -> Option<usize> {
+    match name {
+        "GameOver" => Some(0),
+        "GameOverLabel" => Some(1),
+        "RetryButton" => Some(2),
+        "MenuButton" => Some(3),
+        _ => None,
+    }
+}
+
+pub fn find_unique_node(name: &str) -> Option<usize> {
+    match name {
+        _ => None,
+    }
+}
+
+pub fn find_node_by_uid(uid: &str) -> Option<usize> {
+    match uid {
+        _ => None,
+    }
+}
+
+// Example: Access typed properties
+// let title_text = TITLE_PROPS.text;
+// let button_pos = STARTBUTTON_PROPS.position;
@@ -0,0 +1,140 @@
+// Auto-generated by Gem compiler
+// Do not edit manually
+
+#![allow(unused)]
+
+#[derive(Debug, Clone)]
+pub struct TitleProps {
+    pub color: (u8, u8, u8, u8),
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const TITLE_PROPS: TitleProps = TitleProps {
+    color: (179, 51, 204, 255),
+    position: (200.0, 100.0),
+    size: (260.0, 40.0),
+    text: "Welcome to Gem Game!",
+};
+
+#[derive(Debug, Clone)]
+pub struct StartButtonProps {
+    pub color: (u8, u8, u8, u8),
+    pub link: &'static str,
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const STARTBUTTON_PROPS: StartButtonProps = StartButtonProps {
+    color: (51, 179, 77, 255),
+    link: "#example:start_button_logic",
+    position: (200.0, 200.0),
+    size: (200.0, 56.0),
+    text: "Start Game",
+};
+
+#[derive(Debug, Clone)]
+pub struct FooterProps {
+    pub color: (u8, u8, u8, u8),
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const FOOTER_PROPS: FooterProps = FooterProps {
+    color: (179, 51, 204, 255),
+    position: (200.0, 300.0),
+    size: (260.0, 40.0),
+    text: "Press Enter to Start",
+};
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: &'static str,
+    pub class_name: &'static str,
+    pub node_index: usize,
+    pub uid: Option<&'static str>,
+}
+
+pub const MAINMENU_NODE: Node = Node {
+    name: "MainMenu",
+    class_name: "Gem",
+    node_index: 0,
+    uid: None,
+};
+
+pub const TITLE_NODE: Node = Node {
+    name: "Title",
+    class_name: "LabelGem",
+    node_index: 1,
+    uid: None,
+};
+
+pub const STARTBUTTON_NODE: Node = Node {
+    name: "StartButton",
+    class_name: "ButtonGem",
+    node_index: 2,
+    uid: None,
+};
+
+pub const FOOTER_NODE: Node = Node {
+    name: "Footer",
+    class_name: "LabelGem",
+    node_index: 3,
+    uid: None,
+};
+
+// Children adjacency lists
+pub const MAINMENU_CHILDREN: &[usize] = &[1, 2, 3];
+pub const TITLE_CHILDREN: &[usize] = &[];
+pub const STARTBUTTON_CHILDREN: &[usize] = &[];
+pub const FOOTER_CHILDREN: &[usize] = &[];
+
+// Signals each node's class emits
+pub const MAINMENU_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const TITLE_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const STARTBUTTON_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const FOOTER_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+
+#[derive(Debug)]
+pub struct Scene {
+    pub root_index: usize,
+    pub node_count: usize,
+}
+
+pub const SCENE: Scene = Scene {
+    root_index: 0,
+    node_count: 4,
+};
+
+pub fn build_scene() -> Scene {
+    SCENE
+}
+
+pub fn find_node_by_name(name: &str) -> Option<usize> {
+    match name {
+        "MainMenu" => Some(0),
+        "Title" => Some(1),
+        "StartButton" => Some(2),
+        "Footer" => Some(3),
+        _ => None,
+    }
+}
+
+pub fn find_unique_node(name: &str) -> Option<usize> {
+    match name {
+        _ => None,
+    }
+}
+
+pub fn find_node_by_uid(uid: &str) -> Option<usize> {
+    match uid {
+        _ => None,
+    }
+}
+
+// Example: Access typed properties
+// let title_text = TITLE_PROPS.text;
+// let button_pos = STARTBUTTON_PROPS.position;
@@ -0,0 +1,182 @@
+// Auto-generated by Gem compiler
+// Do not edit manually
+
+#![allow(unused)]
+
+#[derive(Debug, Clone)]
+pub struct PlayerProps {
+    pub link: &'static str,
+    pub position: (f64, f64),
+    pub sprite: &'static str,
+}
+
+pub const PLAYER_PROPS: PlayerProps = PlayerProps {
+    link: "#example:logic:player_logic",
+    position: (100.0, 200.0),
+    sprite: "#assets:player.png",
+};
+
+#[derive(Debug, Clone)]
+pub struct WeaponProps {
+    pub sprite: &'static str,
+}
+
+pub const WEAPON_PROPS: WeaponProps = WeaponProps {
+    sprite: "#assets:sword.png",
+};
+
+#[derive(Debug, Clone)]
+pub struct EnemyProps {
+    pub link: &'static str,
+    pub position: (f64, f64),
+    pub sprite: &'static str,
+}
+
+pub const ENEMY_PROPS: EnemyProps = EnemyProps {
+    link: "#example:logic:enemy_logic",
+    position: (300.0, 200.0),
+    sprite: "#assets:enemy.png",
+};
+
+#[derive(Debug, Clone)]
+pub struct BackgroundProps {
+    pub sprite: &'static str,
+}
+
+pub const BACKGROUND_PROPS: BackgroundProps = BackgroundProps {
+    sprite: "#assets:background.png",
+};
+
+#[derive(Debug, Clone)]
+pub struct ScoreLabelProps {
+    pub color: (u8, u8, u8, u8),
+    pub position: (f64, f64),
+    pub size: (f64, f64),
+    pub text: &'static str,
+}
+
+pub const SCORELABEL_PROPS: ScoreLabelProps = ScoreLabelProps {
+    color: (179, 51, 204, 255),
+    position: (10.0, 10.0),
+    size: (260.0, 40.0),
+    text: "Score: 0",
+};
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: &'static str,
+    pub class_name: &'static str,
+    pub node_index: usize,
+    pub uid: Option<&'static str>,
+}
+
+pub const MAINSCENE_NODE: Node = Node {
+    name: "MainScene",
+    class_name: "Gem",
+    node_index: 0,
+    uid: None,
+};
+
+pub const PLAYER_NODE: Node = Node {
+    name: "Player",
+    class_name: "SpriteGem",
+    node_index: 1,
+    uid: None,
+};
+
+pub const WEAPON_NODE: Node = Node {
+    name: "Weapon",
+    class_name: "SpriteGem",
+    node_index: 2,
+    uid: None,
+};
+
+pub const ENEMY_NODE: Node = Node {
+    name: "Enemy",
+    class_name: "SpriteGem",
+    node_index: 3,
+    uid: None,
+};
+
+pub const BACKGROUND_NODE: Node = Node {
+    name: "Background",
+    class_name: "SpriteGem",
+    node_index: 4,
+    uid: None,
+};
+
+pub const HUD_NODE: Node = Node {
+    name: "HUD",
+    class_name: "ContainerGem",
+    node_index: 5,
+    uid: None,
+};
+
+pub const SCORELABEL_NODE: Node = Node {
+    name: "ScoreLabel",
+    class_name: "LabelGem",
+    node_index: 6,
+    uid: None,
+};
+
+// Children adjacency lists
+pub const MAINSCENE_CHILDREN: &[usize] = &[1, 3, 4, 5];
+pub const PLAYER_CHILDREN: &[usize] = &[2];
+pub const WEAPON_CHILDREN: &[usize] = &[];
+pub const ENEMY_CHILDREN: &[usize] = &[];
+pub const BACKGROUND_CHILDREN: &[usize] = &[];
+pub const HUD_CHILDREN: &[usize] = &[6];
+pub const SCORELABEL_CHILDREN: &[usize] = &[];
+
+// Signals each node's class emits
+pub const MAINSCENE_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const PLAYER_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const WEAPON_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const ENEMY_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const BACKGROUND_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const HUD_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+pub const SCORELABEL_SIGNALS: &[&str] = &["ready()", "visibility_changed(visible)", "tree_entered()", "tree_exiting()", "child_entered_tree()", "child_exited_tree()", "focus_entered()", "focus_exited()"];
+
+#[derive(Debug)]
+pub struct Scene {
+    pub root_index: usize,
+    pub node_count: usize,
+}
+
+pub const SCENE: Scene = Scene {
+    root_index: 0,
+    node_count: 7,
+};
+
+pub fn build_scene() -> Scene {
+    SCENE
+}
+
+pub fn find_node_by_name(name: &str) -> Option<usize> {
+    match name {
+        "MainScene" => Some(0),
+        "Player" => Some(1),
+        "Weapon" => Some(2),
+        "Enemy" => Some(3),
+        "Background" => Some(4),
+        "HUD" => Some(5),
+        "ScoreLabel" => Some(6),
+        _ => None,
+    }
+}
+
+pub fn find_unique_node(name: &str) -> Option<usize> {
+    match name {
+        _ => None,
+    }
+}
+
+pub fn find_node_by_uid(uid: &str) -> Option<usize> {
+    match uid {
+        _ => None,
+    }
+}
+
+// Example: Access typed properties
+// let title_text = TITLE_PROPS.text;
+// let button_pos = STARTBUTTON_PROPS.position;
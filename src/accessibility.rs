@@ -0,0 +1,300 @@
+//! Accessibility tree for screen readers, in the shape of AccessKit's tree
+//! updates: a flat list of nodes keyed by id, each with a role, the text read
+//! out for it and its bounds, plus the focused node. It is built from the
+//! properties the renderer draws, so controls are described without extra
+//! work: a label or button is named by its `text`, a text field's `text` is
+//! its value, and the global transform and `size` give the bounds. Metadata
+//! refines it: `meta:accessible_name` replaces the name,
+//! `meta:accessible_description` adds a description and
+//! `meta:accessible: false` leaves a subtree out. Hidden subtrees are left
+//! out as well. The preview window hands it to the platform's screen reader
+//! through `accesskit_winit` (see `to_accesskit`).
+
+use std::collections::HashMap;
+
+use crate::gem;
+use crate::line_edit;
+use crate::object::ObjectRef;
+use crate::text::{self, Span, TextStyle};
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Window,
+    GenericContainer,
+    Label,
+    Button,
+    TextInput,
+    MultilineTextInput,
+    Dialog,
+    Pane,
+    Image,
+}
+
+impl Role {
+    fn of(node: &ObjectRef) -> Role {
+        match node.class_name() {
+            "LabelGem" | "RichLabelGem" => Role::Label,
+            "ButtonGem" => Role::Button,
+            "LineEditGem" => Role::TextInput,
+            "TextEditGem" => Role::MultilineTextInput,
+            "DialogGem" => Role::Dialog,
+            "PopupGem" => Role::Pane,
+            "SpriteGem" => Role::Image,
+            _ => Role::GenericContainer,
+        }
+    }
+
+    fn to_accesskit(self) -> accesskit::Role {
+        match self {
+            Role::Window => accesskit::Role::Window,
+            Role::GenericContainer => accesskit::Role::GenericContainer,
+            Role::Label => accesskit::Role::Label,
+            Role::Button => accesskit::Role::Button,
+            Role::TextInput => accesskit::Role::TextInput,
+            Role::MultilineTextInput => accesskit::Role::MultilineTextInput,
+            Role::Dialog => accesskit::Role::Dialog,
+            Role::Pane => accesskit::Role::Pane,
+            Role::Image => accesskit::Role::Image,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Window => "window",
+            Role::GenericContainer => "generic_container",
+            Role::Label => "label",
+            Role::Button => "button",
+            Role::TextInput => "text_input",
+            Role::MultilineTextInput => "multiline_text_input",
+            Role::Dialog => "dialog",
+            Role::Pane => "pane",
+            Role::Image => "image",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessNode {
+    /// The node's object id
+    pub id: u64,
+    pub role: Role,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub value: Option<String>,
+    /// Window pixels (x, y, w, h), for nodes with a `size`
+    pub bounds: Option<(f64, f64, f64, f64)>,
+    pub children: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeUpdate {
+    /// Parents come before their children; the first node is the root
+    pub nodes: Vec<AccessNode>,
+    pub focus: u64,
+}
+
+impl TreeUpdate {
+    /// The whole tree as an AccessKit update, bounds in window pixels
+    pub fn to_accesskit(&self) -> accesskit::TreeUpdate {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|n| {
+                let mut node = accesskit::Node::new(n.role.to_accesskit());
+                if let Some(name) = &n.name {
+                    node.set_label(name.as_str());
+                }
+                if let Some(description) = &n.description {
+                    node.set_description(description.as_str());
+                }
+                if let Some(value) = &n.value {
+                    node.set_value(value.as_str());
+                }
+                if let Some((x, y, w, h)) = n.bounds {
+                    node.set_bounds(accesskit::Rect::new(x, y, x + w, y + h));
+                }
+                node.set_children(
+                    n.children
+                        .iter()
+                        .map(|id| accesskit::NodeId(*id))
+                        .collect::<Vec<_>>(),
+                );
+                (accesskit::NodeId(n.id), node)
+            })
+            .collect();
+        accesskit::TreeUpdate {
+            nodes,
+            tree: self.nodes.first().map(|root| {
+                let mut tree = accesskit::TreeInfo::new(accesskit::NodeId(root.id));
+                tree.toolkit_name = Some("Gem".into());
+                tree
+            }),
+            tree_id: accesskit::TreeId::ROOT,
+            focus: accesskit::NodeId(self.focus),
+        }
+    }
+}
+
+/// Describe `root`'s subtree; the root itself is the window
+pub fn tree_update(root: &ObjectRef) -> TreeUpdate {
+    let mut nodes = Vec::new();
+    collect(root, &mut nodes);
+    if let Some(node) = nodes.first_mut() {
+        node.role = Role::Window;
+    }
+    let focus = line_edit::focused()
+        .map(|f| f.id())
+        .filter(|id| nodes.iter().any(|n| n.id == *id))
+        .unwrap_or(root.id());
+    TreeUpdate { nodes, focus }
+}
+
+fn collect(node: &ObjectRef, out: &mut Vec<AccessNode>) -> bool {
+    if !gem::is_visible(node) || node.get_meta("accessible") == Some(Value::Bool(false)) {
+        return false;
+    }
+    let role = Role::of(node);
+    let text = match node.get_property("text") {
+        Some(Value::String(s)) if !s.is_empty() => Some(s),
+        _ => None,
+    };
+    let (text_name, value) = match role {
+        Role::TextInput | Role::MultilineTextInput => (None, text),
+        _ if node.class_name() == "RichLabelGem" => (text.map(|t| plain_text(&t)), None),
+        _ => (text, None),
+    };
+    let meta_text = |key: &str| match node.get_meta(key) {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    };
+    let bounds = node
+        .get_property("size")
+        .and_then(|s| s.as_vec2())
+        .map(|(w, h)| {
+            let (x, y) = gem::global_transform(node).origin;
+            (x, y, w, h)
+        });
+    let index = out.len();
+    out.push(AccessNode {
+        id: node.id(),
+        role,
+        name: meta_text("accessible_name").or(text_name),
+        description: meta_text("accessible_description"),
+        value,
+        bounds,
+        children: Vec::new(),
+    });
+    for child in gem::children_of(node) {
+        if collect(&child, out) {
+            out[index].children.push(child.id());
+        }
+    }
+    true
+}
+
+/// A rich label's text without its markup tags
+fn plain_text(markup: &str) -> String {
+    text::parse_markup(markup, &TextStyle::default())
+        .into_iter()
+        .filter_map(|span| match span {
+            Span::Text { text, .. } => Some(text),
+            Span::Image { .. } => None,
+        })
+        .collect()
+}
+
+impl AccessNode {
+    pub fn to_value(&self) -> Value {
+        let text = |t: &Option<String>| t.clone().map_or(Value::Null, Value::String);
+        Value::Map(HashMap::from([
+            ("id".to_string(), Value::Int(self.id as i64)),
+            ("role".to_string(), Value::String(self.role.name().into())),
+            ("name".to_string(), text(&self.name)),
+            ("description".to_string(), text(&self.description)),
+            ("value".to_string(), text(&self.value)),
+            (
+                "bounds".to_string(),
+                self.bounds.map_or(Value::Null, |(x, y, w, h)| {
+                    Value::Array(vec![
+                        Value::Float(x),
+                        Value::Float(y),
+                        Value::Float(w),
+                        Value::Float(h),
+                    ])
+                }),
+            ),
+            (
+                "children".to_string(),
+                Value::Array(
+                    self.children
+                        .iter()
+                        .map(|id| Value::Int(*id as i64))
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{self, object_new};
+    use crate::scene_tree;
+
+    #[test]
+    fn controls_are_described_from_their_properties() {
+        scene_tree::init_builtin_classes();
+        let root = object_new("Gem");
+        let add = |class: &str| {
+            let node = if object::is_class_registered(class) {
+                object_new(class)
+            } else {
+                gem::new_gem(class, gem::gem_methods())
+            };
+            root.call_method("add_child", &[Value::Object(node.clone())])
+                .unwrap();
+            node
+        };
+        let title = add("RichLabelGem");
+        title.set_property("text", Value::String("[b]Sign[/b] in".into()));
+        let name = add("LineEditGem");
+        name.set_property("text", Value::String("ada".into()));
+        name.set_meta("accessible_name", Value::String("User name".into()));
+        let ok = add("ButtonGem");
+        ok.set_property("text", Value::String("OK".into()));
+        ok.set_property("position", Value::vec2(10.0, 20.0));
+        ok.set_property("size", Value::vec2(80.0, 30.0));
+        ok.set_meta("accessible_description", Value::String("Signs in".into()));
+        add("PanelGem").set_meta("accessible", Value::Bool(false));
+        add("DialogGem");
+
+        let tree = tree_update(&root);
+        let roles: Vec<Role> = tree.nodes.iter().map(|n| n.role).collect();
+        assert_eq!(
+            roles,
+            [Role::Window, Role::Label, Role::TextInput, Role::Button]
+        );
+        assert_eq!(tree.nodes[0].children, [title.id(), name.id(), ok.id()]);
+        assert_eq!(tree.nodes[1].name.as_deref(), Some("Sign in"));
+        assert_eq!(tree.nodes[2].name.as_deref(), Some("User name"));
+        assert_eq!(tree.nodes[2].value.as_deref(), Some("ada"));
+        assert_eq!(tree.nodes[3].bounds, Some((10.0, 20.0, 80.0, 30.0)));
+        assert_eq!(tree.nodes[3].description.as_deref(), Some("Signs in"));
+        // Dialogs start hidden, and focus falls back to the window
+        assert_eq!(tree.focus, root.id());
+
+        let update = tree.to_accesskit();
+        assert_eq!(update.tree.unwrap().root, accesskit::NodeId(root.id()));
+        assert_eq!(update.focus, accesskit::NodeId(root.id()));
+        let (id, button) = &update.nodes[3];
+        assert_eq!(*id, accesskit::NodeId(ok.id()));
+        assert_eq!(button.role(), accesskit::Role::Button);
+        assert_eq!(button.label(), Some("OK"));
+        assert_eq!(
+            button.bounds(),
+            Some(accesskit::Rect::new(10.0, 20.0, 90.0, 50.0))
+        );
+    }
+}
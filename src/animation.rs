@@ -0,0 +1,167 @@
+//! Animation clips, as imported from glTF models and played by SkeletonGem.
+//! Each channel moves one node's `position`, `rotation` or `scale` through
+//! keyframes; the node is named by its path below the model's root. Rotation
+//! keys are quaternions, interpolated (and blended) the shorter way round.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Position,
+    /// A quaternion `[x, y, z, w]`
+    Rotation,
+    Scale,
+}
+
+impl Target {
+    /// The node property the channel sets
+    pub fn key(self) -> &'static str {
+        match self {
+            Target::Position => "position",
+            Target::Rotation => "rotation",
+            Target::Scale => "scale",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// Path of the animated node below the model's root; empty for the root
+    pub node: String,
+    pub target: Target,
+    /// Hold each key until the next instead of interpolating
+    pub step: bool,
+    /// Seconds, ascending
+    pub times: Vec<f64>,
+    /// One per time; vectors leave the fourth component 0
+    pub values: Vec<[f64; 4]>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+/// A node property's value at one moment
+pub type Pose = Vec<(String, Target, [f64; 4])>;
+
+impl Clip {
+    /// The last key's time
+    pub fn duration(&self) -> f64 {
+        self.channels
+            .iter()
+            .filter_map(|c| c.times.last())
+            .fold(0.0, |a, &b| a.max(b))
+    }
+
+    /// Every channel sampled at `time`
+    pub fn pose(&self, time: f64) -> Pose {
+        self.channels
+            .iter()
+            .filter(|c| !c.values.is_empty())
+            .map(|c| (c.node.clone(), c.target, c.sample(time)))
+            .collect()
+    }
+}
+
+impl Channel {
+    /// The value at `time`, holding the first and last keys outside them
+    pub fn sample(&self, time: f64) -> [f64; 4] {
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.values[0];
+        }
+        if next >= self.values.len() {
+            return self.values[self.values.len() - 1];
+        }
+        let (a, b) = (self.values[next - 1], self.values[next]);
+        if self.step {
+            return a;
+        }
+        let (from, to) = (self.times[next - 1], self.times[next]);
+        mix(self.target, a, b, (time - from) / (to - from))
+    }
+}
+
+/// `a` moved towards `b` by `weight`, from 0 to 1
+pub fn mix(target: Target, a: [f64; 4], b: [f64; 4], weight: f64) -> [f64; 4] {
+    let lerp = |b: [f64; 4]| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * weight);
+    if target != Target::Rotation {
+        return lerp(b);
+    }
+    let dot: f64 = (0..4).map(|i| a[i] * b[i]).sum();
+    let q: [f64; 4] = lerp(if dot < 0.0 { b.map(|c| -c) } else { b });
+    let len = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+    if len == 0.0 { a } else { q.map(|c| c / len) }
+}
+
+/// `to` faded in over `from` by `weight`; what only one of them moves keeps
+/// that value
+pub fn blend(from: &Pose, to: &Pose, weight: f64) -> Pose {
+    let mut out: Pose = from
+        .iter()
+        .filter(|(node, target, _)| !to.iter().any(|(n, t, _)| n == node && t == target))
+        .cloned()
+        .collect();
+    for (node, target, value) in to {
+        let value = match from.iter().find(|(n, t, _)| n == node && t == target) {
+            Some((_, _, old)) => mix(*target, *old, *value, weight),
+            None => *value,
+        };
+        out.push((node.clone(), *target, value));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_interpolate_and_poses_blend() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let clip = Clip {
+            name: "wave".into(),
+            channels: vec![
+                Channel {
+                    node: "Arm".into(),
+                    target: Target::Position,
+                    step: false,
+                    times: vec![0.0, 2.0],
+                    values: vec![[0.0; 4], [4.0, 2.0, 0.0, 0.0]],
+                },
+                Channel {
+                    node: "Arm".into(),
+                    target: Target::Rotation,
+                    step: false,
+                    times: vec![0.0, 1.0],
+                    values: vec![[0.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 0.0]],
+                },
+                Channel {
+                    node: String::new(),
+                    target: Target::Scale,
+                    step: true,
+                    times: vec![0.0, 1.0],
+                    values: vec![[1.0, 1.0, 1.0, 0.0], [2.0, 2.0, 2.0, 0.0]],
+                },
+            ],
+        };
+        assert_eq!(clip.duration(), 2.0);
+        let pose = clip.pose(0.5);
+        assert_eq!(pose[0].2, [1.0, 0.5, 0.0, 0.0]);
+        // Halfway to a half turn about y is a quarter turn
+        let [x, y, z, w] = pose[1].2;
+        assert!([x, y - half, z, w - half].iter().all(|c| c.abs() < 1e-9));
+        assert_eq!(pose[2].2, [1.0, 1.0, 1.0, 0.0]);
+        // Past the last key
+        assert_eq!(clip.pose(5.0)[0].2, [4.0, 2.0, 0.0, 0.0]);
+
+        let idle = vec![
+            ("Arm".to_string(), Target::Position, [0.0, 4.0, 0.0, 0.0]),
+            ("Leg".to_string(), Target::Scale, [3.0, 3.0, 3.0, 0.0]),
+        ];
+        let blended = blend(&idle, &pose, 0.25);
+        assert_eq!(blended[0].0, "Leg");
+        assert_eq!(blended[1].2, [0.25, 3.125, 0.0, 0.0]);
+        assert_eq!(blended.len(), 4);
+    }
+}
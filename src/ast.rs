@@ -1,15 +1,30 @@
 /// AST nodes for Gem scene files and logic scripts
+pub use crate::curve::{Curve, CurvePoint, Easing};
+pub use crate::size_expr::SizeExpr;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct GemFile {
     pub root: GemDecl,
+    /// Malformed properties a recovering parse skipped
+    pub errors: Vec<PropertyError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyError {
+    /// Child indices from the root to the node the property was in
+    pub node: Vec<usize>,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct GemDecl {
     pub name: String,
+    /// Declared as `%Name`: reachable as `%Name` from anywhere in the scene
+    pub unique: bool,
     pub gem_type: String,
     pub properties: Vec<Property>,
+    /// `meta:key: value` entries, kept apart from the declared properties
+    pub meta: Vec<Property>,
     pub children: Vec<GemDecl>,
 }
 
@@ -26,8 +41,56 @@ pub enum Value {
     String(String),
     Bool(bool),
     Tuple(Vec<Value>),
-    Directive(Vec<String>), // e.g., #assets:player.png -> ["assets", "player.png"]
+    Array(Vec<Value>),    // e.g., ["enemy", "flying"]
+    Color([u8; 4]),       // e.g., #112233 or #11223380
+    Directive(Directive), // e.g., #assets:player.png
     Ident(String),
+    Struct(Vec<Property>),     // e.g., { type: "circle", radius: 16 }
+    Map(Vec<(String, Value)>), // e.g., { "fire": 2, "ice": 0.5 }
+    Curve(Curve),              // e.g., Curve((0, 0), (1, 100, ease_out))
+    SizeExpr(SizeExpr),        // e.g., parent.width - 20
+}
+
+/// `#assets:atlas.png:region(0, 0, 32, 32)` or `#scenes:enemy?variant=blue`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    /// The path: `["assets", "atlas.png"]`
+    pub segments: Vec<String>,
+    pub args: Vec<DirectiveArg>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirectiveArg {
+    /// `:region(0, 0, 32, 32)` after the path
+    Call { name: String, values: Vec<Value> },
+    /// `?variant=blue`, further ones joined by `&`
+    Query { key: String, value: Value },
+}
+
+impl Directive {
+    /// A plain path, without arguments
+    pub fn new(segments: Vec<String>) -> Self {
+        Directive {
+            segments,
+            args: Vec::new(),
+        }
+    }
+
+    /// The values of the `name(...)` argument
+    pub fn call(&self, name: &str) -> Option<&[Value]> {
+        self.args.iter().find_map(|arg| match arg {
+            DirectiveArg::Call { name: n, values } if n == name => Some(values.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// `?key=value` pairs, in order
+    pub fn queries(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.args.iter().filter_map(|arg| match arg {
+            DirectiveArg::Query { key, value } => Some((key.as_str(), value)),
+            _ => None,
+        })
+    }
 }
 
 // Logic file AST
@@ -51,6 +114,14 @@ pub struct FunctionDecl {
     pub name: String,
     pub params: Vec<String>,
     pub body: Block,
+    pub annotations: Vec<Annotation>,
+}
+
+/// `@name` or `@name(arg, ...)` before a function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub args: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -60,6 +131,11 @@ pub struct Block {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
+    /// `let name = value`, a local until the end of its block
+    Let {
+        name: String,
+        value: Expr,
+    },
     Assignment {
         target: String,
         value: Expr,
@@ -69,6 +145,18 @@ pub enum Stmt {
         then_block: Block,
         else_block: Option<Block>,
     },
+    /// `for item in items { ... }`, `for i in 0..10 { ... }`
+    For {
+        var: String,
+        iterable: Expr,
+        body: Block,
+    },
+    /// `match value { 1 => { ... } "idle" => { ... } _ => { ... } }`, running
+    /// the first arm whose pattern equals the value
+    Match {
+        value: Expr,
+        arms: Vec<MatchArm>,
+    },
     Call {
         name: String,
         args: Vec<Expr>,
@@ -77,9 +165,25 @@ pub enum Stmt {
         gem_type: String,
         properties: Vec<Property>,
     },
+    /// `return` or `return value`
+    Return(Option<Expr>),
     ExprStmt(Expr),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A number, string or bool the value must equal
+    Literal(Expr),
+    /// `_`, matching anything
+    Wildcard,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Number(f64),
@@ -88,7 +192,7 @@ pub enum Expr {
     Bool(bool),
     Ident(String),
     Tuple(Vec<Expr>),
-    Directive(Vec<String>),
+    Directive(Directive),
     Call {
         name: String,
         args: Vec<Expr>,
@@ -106,6 +210,17 @@ pub enum Expr {
         object: Box<Expr>,
         property: String,
     },
+    /// `object.method(args)`, e.g. `get_parent().get_name()`
+    MethodCall {
+        object: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    /// `start..end`, from `start` up to but not including `end`
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
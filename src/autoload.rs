@@ -0,0 +1,189 @@
+//! Autoload singletons declared in the project manifest.
+//! Each `autoload: Name: #folder:script` entry becomes a Gem that joins the
+//! runtime tree before the entry scene and stays reachable by name from any
+//! script, via `get` or `get_node("/Name")`. Any node can connect to and emit a
+//! singleton's signals, so a singleton without state doubles as an event bus.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use crate::clipboard;
+use crate::color;
+use crate::easing;
+use crate::file_access;
+use crate::gem;
+use crate::multiplayer;
+use crate::navigation;
+use crate::object::{self, ObjectRef};
+use crate::os;
+use crate::performance;
+use crate::physics;
+use crate::pipeline;
+use crate::scene_tree;
+use crate::time;
+use crate::value::Value;
+use crate::vfs;
+use crate::worker_pool;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoloadDecl {
+    pub name: String,
+    pub script: PathBuf,
+}
+
+static TREE_ROOT: OnceLock<ObjectRef> = OnceLock::new();
+static SINGLETONS: OnceLock<RwLock<HashMap<String, ObjectRef>>> = OnceLock::new();
+
+/// Engine singletons are always registered; autoloads are added next to them
+fn singletons() -> &'static RwLock<HashMap<String, ObjectRef>> {
+    SINGLETONS.get_or_init(|| {
+        RwLock::new(HashMap::from([
+            ("PhysicsServer".to_string(), physics::new_physics_server()),
+            ("Clipboard".to_string(), clipboard::new_clipboard()),
+            ("Color".to_string(), color::new_color_singleton()),
+            ("Math".to_string(), easing::new_math()),
+            ("FileAccess".to_string(), file_access::new_file_access()),
+            ("Multiplayer".to_string(), multiplayer::new_multiplayer()),
+            ("Navigation".to_string(), navigation::new_navigation()),
+            ("OS".to_string(), os::new_os()),
+            ("Performance".to_string(), performance::new_performance()),
+            ("SceneTree".to_string(), scene_tree::scene_tree()),
+            ("Time".to_string(), time::new_time()),
+            ("WorkerPool".to_string(), worker_pool::new_worker_pool()),
+        ]))
+    })
+}
+
+/// Root of the runtime tree: autoloads are its first children, the entry scene follows
+pub fn tree_root() -> ObjectRef {
+    TREE_ROOT
+        .get_or_init(|| {
+            scene_tree::init_builtin_classes();
+            let root = object::object_new("Gem");
+            root.set_property("name", Value::String("root".into()));
+            gem::enter_tree(&root);
+            root
+        })
+        .clone()
+}
+
+/// Singleton registered under `name`
+pub fn get(name: &str) -> Option<ObjectRef> {
+    singletons().read().unwrap().get(name).cloned()
+}
+
+/// Every registered singleton
+pub fn all() -> Vec<ObjectRef> {
+    singletons().read().unwrap().values().cloned().collect()
+}
+
+fn create(decl: &AutoloadDecl) -> Result<ObjectRef, String> {
+    let script = decl.script.display();
+    let content = vfs::read_to_string(&decl.script.to_string_lossy())?;
+    let logic = pipeline::compile_logic(&content).map_err(|e| format!("{}: {}", script, e))?;
+    if !object::is_class_registered(&logic.extend_type) {
+        return Err(format!(
+            "{}: extends unknown class {}",
+            script, logic.extend_type
+        ));
+    }
+    let node = object::object_new(&logic.extend_type);
+    multiplayer::configure(&node, &logic).map_err(|e| format!("{}: {}", script, e))?;
+    node.set_property("name", Value::String(decl.name.clone()));
+    node.set_property("script", Value::String(script.to_string()));
+    Ok(node)
+}
+
+/// Create the singletons in declaration order and add them under the tree root.
+/// `ready` is emitted once all of them are in the tree, so they can look each
+/// other up from their handlers.
+pub fn instantiate(decls: &[AutoloadDecl]) -> Result<Vec<ObjectRef>, String> {
+    let root = tree_root();
+    let mut nodes = Vec::new();
+    for decl in decls {
+        if get(&decl.name).is_some() {
+            return Err(format!("Autoload {} is declared twice", decl.name));
+        }
+        let node = create(decl).map_err(|e| format!("Autoload {}: {}", decl.name, e))?;
+        root.call_method("add_child", &[Value::Object(node.clone())])?;
+        singletons()
+            .write()
+            .unwrap()
+            .insert(decl.name.clone(), node.clone());
+        nodes.push(node);
+    }
+    for node in &nodes {
+        gem::propagate_ready(node);
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[test]
+    fn singletons_are_reachable_and_carry_signals() {
+        let dir = PathBuf::from("target").join("gem_autoload_test");
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("event_bus.gem");
+        fs::write(
+            &script,
+            "extend Gem\n\n/// Signal bus\nfn on_ready {\n  score = 0\n}\n",
+        )
+        .unwrap();
+        let decls = vec![AutoloadDecl {
+            name: "TestEventBus".to_string(),
+            script: script.clone(),
+        }];
+        let nodes = instantiate(&decls).unwrap();
+        let bus = get("TestEventBus").unwrap();
+        assert_eq!(bus.id(), nodes[0].id());
+        assert_eq!(
+            bus.get_property("script"),
+            Some(Value::String(script.display().to_string()))
+        );
+
+        // A scene node added after the autoloads finds the singleton by absolute path
+        let level = object::object_new("Gem");
+        level.set_property("name", Value::String("Level".into()));
+        tree_root()
+            .call_method("add_child", &[Value::Object(level.clone())])
+            .unwrap();
+        match level.call_method("get_node", &[Value::String("/TestEventBus".into())]) {
+            Ok(Value::Object(o)) => assert_eq!(o.id(), bus.id()),
+            other => panic!("expected the singleton, got {:?}", other),
+        }
+
+        let total = Arc::new(AtomicI64::new(0));
+        let seen = total.clone();
+        bus.connect(
+            "scored",
+            Arc::new(move |args| {
+                if let Some(Value::Int(n)) = args.first() {
+                    seen.fetch_add(*n, Ordering::SeqCst);
+                }
+            }),
+        );
+        get("TestEventBus")
+            .unwrap()
+            .emit_signal("scored", &[Value::Int(5)]);
+        assert_eq!(total.load(Ordering::SeqCst), 5);
+
+        let Err(e) = instantiate(&decls) else {
+            panic!("redeclaring a singleton should fail");
+        };
+        assert!(e.contains("declared twice"));
+        let missing = AutoloadDecl {
+            name: "TestMissing".to_string(),
+            script: dir.join("missing.gem"),
+        };
+        assert!(instantiate(&[missing]).is_err());
+        assert!(get("TestMissing").is_none());
+        assert!(get("PhysicsServer").is_some());
+    }
+}
@@ -0,0 +1,101 @@
+//! Splits a layer's quads into the calls the renderer issues. A run of at
+//! least `MIN_INSTANCES` consecutive quads that bind the same state (flat, or
+//! the same texture raster, with the same blend mode) is drawn by one
+//! instanced call, each quad's rect, texture coordinates and color coming
+//! from a per-instance buffer; everything else gets a call of its own.
+//! Gradients carry their stops as uniforms, so they are never instanced.
+
+use std::ops::Range;
+
+use crate::draw_list::DrawCmd;
+use crate::renderer::BlendMode;
+use crate::texture::Texture;
+
+/// Shorter runs aren't worth filling an instance buffer for
+pub const MIN_INSTANCES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Batch {
+    Single(usize),
+    Instanced(Range<usize>),
+}
+
+/// What a quad binds. Textures with default options and no region are
+/// rasterized at their display size, so only quads of the same size share one.
+#[derive(PartialEq)]
+struct InstanceKey<'a> {
+    blend: BlendMode,
+    texture: Option<&'a Texture>,
+    raster_size: Option<(u32, u32)>,
+}
+
+fn instance_key(q: &DrawCmd) -> Option<InstanceKey<'_>> {
+    if q.gradient.is_some() && q.texture.is_none() {
+        return None;
+    }
+    let raster_size = q
+        .texture
+        .as_ref()
+        .filter(|t| t.rasterized_at_display_size())
+        .map(|_| (q.w.to_bits(), q.h.to_bits()));
+    Some(InstanceKey {
+        blend: q.blend,
+        texture: q.texture.as_ref(),
+        raster_size,
+    })
+}
+
+/// The calls for `quads`, in draw order
+pub fn batches(quads: &[DrawCmd]) -> Vec<Batch> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < quads.len() {
+        let key = instance_key(&quads[start]);
+        let mut end = start + 1;
+        if key.is_some() {
+            while end < quads.len() && instance_key(&quads[end]) == key {
+                end += 1;
+            }
+        }
+        if end - start >= MIN_INSTANCES {
+            out.push(Batch::Instanced(start..end));
+        } else {
+            out.extend((start..end).map(Batch::Single));
+        }
+        start = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw_list;
+    use crate::pipeline;
+
+    #[test]
+    fn long_runs_of_shared_state_are_instanced() {
+        let mut src = String::from("Root: Gem {\n");
+        for i in 0..20 {
+            src.push_str(&format!(
+                "B{}: PanelGem {{ position: ({}, 0) }}\n",
+                i,
+                i * 4
+            ));
+        }
+        src.push_str("Glow: PanelGem { blend_mode: add }\n");
+        for i in 0..3 {
+            src.push_str(&format!("C{}: PanelGem {{ }}\n", i));
+        }
+        src.push('}');
+        let scene = pipeline::lex_source(&src)
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let list = draw_list::build(&scene);
+        assert_eq!(list.quads.len(), 24);
+
+        let mut expected = vec![Batch::Instanced(0..20)];
+        expected.extend((20..24).map(Batch::Single));
+        assert_eq!(batches(&list.quads), expected);
+    }
+}
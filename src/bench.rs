@@ -0,0 +1,109 @@
+//! `gem bench-draw-list [--threads n] [scene.gem]`: times building a draw
+//! list on one thread and on `n` (by default every CPU), for a scene file or
+//! for generated scenes of growing size. The threaded builds split at any
+//! size, so the sizes where threads start to pay show against
+//! `draw_list::PARALLEL_MIN_NODES`.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::ast;
+use crate::draw_list;
+use crate::font::FontRegistry;
+
+/// Builds timed per scene and thread count; the median is reported
+const RUNS: usize = 9;
+/// Rows of the generated scenes: 16 groups of this many panels and labels
+const ROWS: [usize; 8] = [8, 16, 31, 62, 125, 250, 500, 1000];
+
+/// Rows of panels with labels, `groups` * `per_group` of each. Built
+/// directly, as lexing a source this long would dominate the timing.
+pub fn scene(groups: usize, per_group: usize) -> ast::GemFile {
+    let node = |name: String, gem_type: &str, properties, children| ast::GemDecl {
+        name,
+        unique: false,
+        gem_type: gem_type.into(),
+        properties,
+        meta: Vec::new(),
+        children,
+    };
+    let prop = |key: &str, value| ast::Property {
+        key: key.into(),
+        value,
+    };
+    let pair = |x: usize, y: usize| {
+        ast::Value::Tuple(vec![
+            ast::Value::Integer(x as i64),
+            ast::Value::Integer(y as i64),
+        ])
+    };
+    let groups = (0..groups)
+        .map(|g| {
+            let mut children = Vec::new();
+            for i in 0..per_group {
+                let (x, y) = (i % 40 * 20, g * 10 + i / 40);
+                let panel = vec![prop("position", pair(x, y)), prop("size", pair(16, 8))];
+                children.push(node(format!("P{}", i), "PanelGem", panel, Vec::new()));
+                let label = vec![
+                    prop("position", pair(x, y)),
+                    prop("text", ast::Value::String(i.to_string())),
+                ];
+                children.push(node(format!("L{}", i), "LabelGem", label, Vec::new()));
+            }
+            node(format!("G{}", g), "Gem", Vec::new(), children)
+        })
+        .collect();
+    ast::GemFile {
+        root: node("Root".into(), "Gem", Vec::new(), groups),
+        errors: Vec::new(),
+    }
+}
+
+/// One line per scene: its node count, the median build on one thread and
+/// on `threads`, and the speedup. Without `scene`, the generated ones.
+pub fn report(scene: Option<&ast::GemFile>, threads: usize) -> String {
+    let scenes = match scene {
+        Some(scene) => vec![scene.clone()],
+        None => ROWS.iter().map(|&rows| self::scene(16, rows)).collect(),
+    };
+    let mut out = format!(
+        "{:>7}  {:>10}  {:>10}  speedup (threshold {} nodes)\n",
+        "nodes",
+        "1 thread",
+        format!("{} threads", threads),
+        draw_list::PARALLEL_MIN_NODES
+    );
+    for scene in &scenes {
+        let one = median_build(scene, 1);
+        let many = median_build(scene, threads);
+        let _ = writeln!(
+            out,
+            "{:>7}  {:>10.2?}  {:>10.2?}  {:.2}x",
+            nodes(&scene.root),
+            one,
+            many,
+            one.as_secs_f64() / many.as_secs_f64()
+        );
+    }
+    out
+}
+
+fn median_build(scene: &ast::GemFile, threads: usize) -> Duration {
+    // The first build loads fonts, which every later one reuses
+    let mut fonts = draw_list::rebuild_on(scene, FontRegistry::new(), threads, 0).fonts;
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let list = draw_list::rebuild_on(scene, std::mem::take(&mut fonts), threads, 0);
+            let elapsed = start.elapsed();
+            fonts = list.fonts;
+            elapsed
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn nodes(decl: &ast::GemDecl) -> usize {
+    1 + decl.children.iter().map(nodes).sum::<usize>()
+}
@@ -0,0 +1,209 @@
+//! CheckBoxGem and RadioButtonGem: toggle buttons labelled by `text`.
+//! Clicking one flips its `pressed` property and emits `toggled(pressed)`;
+//! `set_pressed(pressed)` does the same from a script. Toggles that share a
+//! `button_group` name within a tree form a group: pressing one releases
+//! the one pressed before, emitting `toggled(false)` on it, so at most one
+//! is pressed. A pressed radio button stays pressed when clicked again; a
+//! check box in a group can be released, leaving none pressed.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+pub const PRESSED_KEY: &str = "pressed";
+pub const GROUP_KEY: &str = "button_group";
+pub const TYPES: [&str; 2] = ["CheckBoxGem", "RadioButtonGem"];
+
+pub fn init_check_box_classes() {
+    register_class("CheckBoxGem", || new_toggle("CheckBoxGem"));
+    register_class("RadioButtonGem", || new_toggle("RadioButtonGem"));
+}
+
+fn new_toggle(class_name: &str) -> ObjectRef {
+    let obj = gem::new_gem(class_name, toggle_methods());
+    obj.set_property(PRESSED_KEY, Value::Bool(false));
+    obj.set_property("text", Value::String(String::new()));
+    obj.set_property("size", Value::vec2(160.0, 28.0));
+    obj
+}
+
+fn toggle_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = (*gem::gem_methods()).clone();
+            // set_pressed(pressed): emits toggled(pressed) when it changes
+            define_method(
+                &mut methods,
+                "set_pressed",
+                Arc::new(|this, args| {
+                    let Some(Value::Bool(pressed)) = args.first() else {
+                        return Err("set_pressed expects a Bool".into());
+                    };
+                    set_pressed(this, *pressed);
+                    Ok(Value::Null)
+                }),
+            );
+            define_method(
+                &mut methods,
+                "is_pressed",
+                Arc::new(|this, _| Ok(Value::Bool(is_pressed(this)))),
+            );
+            // get_pressed_button() -> the pressed toggle in this one's group, or null
+            define_method(
+                &mut methods,
+                "get_pressed_button",
+                Arc::new(|this, _| {
+                    let pressed = group_of(this).into_iter().find(is_pressed);
+                    Ok(pressed.map_or(Value::Null, Value::Object))
+                }),
+            );
+            // _input(event): a click on the toggle flips it
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let Some(event) = args.first().and_then(Value::as_object) else {
+                        return Err("_input expects an InputEvent".into());
+                    };
+                    if event.class_name() != "InputEventMouseButton"
+                        || !matches!(event.get_property("pressed"), Some(Value::Bool(true)))
+                    {
+                        return Ok(Value::Null);
+                    }
+                    let (w, h) = this
+                        .get_property("size")
+                        .and_then(|s| s.as_vec2())
+                        .unwrap_or((0.0, 0.0));
+                    let inside = event
+                        .get_property("position")
+                        .and_then(|p| p.as_vec2())
+                        .and_then(|p| gem::to_local(this, p))
+                        .is_some_and(|(x, y)| x >= 0.0 && y >= 0.0 && x < w && y < h);
+                    if !inside {
+                        return Ok(Value::Null);
+                    }
+                    let radio = this.class_name() == "RadioButtonGem";
+                    if !(radio && is_pressed(this)) {
+                        set_pressed(this, !is_pressed(this));
+                    }
+                    event.call_method("accept_event", &[])?;
+                    Ok(Value::Null)
+                }),
+            );
+            // signals: toggled(pressed)
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn is_pressed(toggle: &ObjectRef) -> bool {
+    matches!(toggle.get_property(PRESSED_KEY), Some(Value::Bool(true)))
+}
+
+fn set_pressed(toggle: &ObjectRef, pressed: bool) {
+    if is_pressed(toggle) == pressed {
+        return;
+    }
+    if pressed {
+        for other in group_of(toggle) {
+            if other != *toggle && is_pressed(&other) {
+                other.set_property(PRESSED_KEY, Value::Bool(false));
+                other.emit_signal("toggled", &[Value::Bool(false)]);
+            }
+        }
+    }
+    toggle.set_property(PRESSED_KEY, Value::Bool(pressed));
+    toggle.emit_signal("toggled", &[Value::Bool(pressed)]);
+}
+
+/// Every toggle in the tree sharing `toggle`'s `button_group`, itself
+/// included; empty when it has no group
+fn group_of(toggle: &ObjectRef) -> Vec<ObjectRef> {
+    fn visit(node: &ObjectRef, group: &str, out: &mut Vec<ObjectRef>) {
+        if TYPES.contains(&node.class_name())
+            && matches!(node.get_property(GROUP_KEY), Some(Value::String(g)) if g == group)
+        {
+            out.push(node.clone());
+        }
+        for child in gem::children_of(node) {
+            visit(&child, group, out);
+        }
+    }
+    let Some(Value::String(group)) = toggle.get_property(GROUP_KEY) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    visit(&gem::root_of(toggle), &group, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use crate::scene_tree;
+    use std::sync::Mutex;
+
+    #[test]
+    fn grouped_toggles_are_exclusive() {
+        scene_tree::init_builtin_classes();
+        let root = object_new("Gem");
+        let add = |class_name: &str, y: f64, group: Option<&str>| {
+            let toggle = object_new(class_name);
+            toggle.set_property("position", Value::vec2(0.0, y));
+            if let Some(group) = group {
+                toggle.set_property(GROUP_KEY, Value::String(group.into()));
+            }
+            root.call_method("add_child", &[Value::Object(toggle.clone())])
+                .unwrap();
+            toggle
+        };
+        let easy = add("RadioButtonGem", 0.0, Some("difficulty"));
+        let hard = add("RadioButtonGem", 40.0, Some("difficulty"));
+        let music = add("CheckBoxGem", 80.0, None);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for (name, toggle) in [("easy", &easy), ("hard", &hard), ("music", &music)] {
+            let log = log.clone();
+            toggle.connect(
+                "toggled",
+                Arc::new(move |args| log.lock().unwrap().push((name, args[0].clone()))),
+            );
+        }
+        let click = |y: f64| input::mouse_button_event("left", true, (10.0, y));
+        let take = || std::mem::take(&mut *log.lock().unwrap());
+
+        assert!(input::dispatch(&root, &click(10.0)));
+        assert!(input::dispatch(&root, &click(50.0)));
+        assert_eq!(
+            take(),
+            [
+                ("easy", Value::Bool(true)),
+                ("easy", Value::Bool(false)),
+                ("hard", Value::Bool(true)),
+            ]
+        );
+        // A pressed radio button stays pressed
+        assert!(input::dispatch(&root, &click(50.0)));
+        assert_eq!(take(), []);
+        assert_eq!(
+            easy.call_method("get_pressed_button", &[]),
+            Ok(Value::Object(hard.clone()))
+        );
+
+        // Ungrouped check boxes flip on every click
+        assert!(input::dispatch(&root, &click(90.0)));
+        assert!(input::dispatch(&root, &click(90.0)));
+        assert_eq!(
+            take(),
+            [("music", Value::Bool(true)), ("music", Value::Bool(false))]
+        );
+        assert_eq!(
+            music.call_method("get_pressed_button", &[]),
+            Ok(Value::Null)
+        );
+    }
+}
@@ -0,0 +1,583 @@
+//! The built-in Gem classes as the compiler sees them: each class's
+//! properties with their types and defaults, and its signals. Every class
+//! extends `Gem` and has its properties too. The transformer checks scenes
+//! against these and fills in defaults, codegen lists each node's signals,
+//! and the preview draws a node that sets no `color` or `size` with its
+//! class's (after the theme's entry for its type, if any).
+
+use crate::ir::PropValue;
+use crate::property_type::PropertyType as T;
+
+pub struct ClassDef {
+    pub name: &'static str,
+    pub properties: &'static [PropertyDef],
+    /// `name(args)` as emitted
+    pub signals: &'static [&'static str],
+}
+
+pub struct PropertyDef {
+    pub name: &'static str,
+    /// The first is the property's type; values of the others are accepted too
+    pub types: &'static [T],
+    pub default: Option<DefaultValue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Vec2(f64, f64),
+    Color([u8; 4]),
+    Str(&'static str),
+}
+
+impl DefaultValue {
+    pub fn to_prop_value(self) -> PropValue {
+        match self {
+            DefaultValue::Bool(b) => PropValue::Bool(b),
+            DefaultValue::Int(i) => PropValue::Int(i),
+            DefaultValue::Float(f) => PropValue::Float(f),
+            DefaultValue::Vec2(x, y) => PropValue::Vec2(x, y),
+            DefaultValue::Color(c) => PropValue::Color(c),
+            DefaultValue::Str(s) => PropValue::Str(s.to_string()),
+        }
+    }
+}
+
+impl ClassDef {
+    /// The property `key`, declared by this class or by `Gem`
+    pub fn property(&self, key: &str) -> Option<&'static PropertyDef> {
+        self.all_properties().find(|p| p.name == key)
+    }
+
+    /// This class's properties, then `Gem`'s
+    pub fn all_properties(&self) -> impl Iterator<Item = &'static PropertyDef> {
+        let base: &'static [PropertyDef] = if self.name == GEM.name {
+            &[]
+        } else {
+            GEM.properties
+        };
+        self.properties.iter().chain(base)
+    }
+
+    /// This class's signals, then `Gem`'s
+    pub fn all_signals(&self) -> impl Iterator<Item = &'static str> {
+        let base: &'static [&'static str] = if self.name == GEM.name {
+            &[]
+        } else {
+            GEM.signals
+        };
+        self.signals.iter().chain(base).copied()
+    }
+}
+
+impl PropertyDef {
+    /// `value` as this property holds it (an integer becomes a float for a
+    /// float property), or `None` when it's of a type the property doesn't take.
+    /// Two-number vectors may also be given as tuples or size expressions, and
+    /// three-number vectors are tuples.
+    pub fn coerce(&self, value: &PropValue) -> Option<PropValue> {
+        let given = T::of(value);
+        match (&self.types[0], value) {
+            (T::Float, PropValue::Int(i)) => Some(PropValue::Float(*i as f64)),
+            _ if self.types.contains(&given) => Some(value.clone()),
+            (T::Vec2, PropValue::List(_) | PropValue::SizeExpr(_)) => Some(value.clone()),
+            (T::Vec3, PropValue::List(items))
+                if items.len() == 3
+                    && items
+                        .iter()
+                        .all(|v| matches!(v, PropValue::Int(_) | PropValue::Float(_))) =>
+            {
+                Some(value.clone())
+            }
+            (T::Float, PropValue::SizeExpr(_)) => Some(value.clone()),
+            (T::List, PropValue::Array(_)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static ClassDef> {
+    CLASSES.iter().find(|c| c.name == name)
+}
+
+pub fn all() -> &'static [ClassDef] {
+    CLASSES
+}
+
+const fn prop(name: &'static str, types: &'static [T]) -> PropertyDef {
+    PropertyDef {
+        name,
+        types,
+        default: None,
+    }
+}
+
+const fn with_default(
+    name: &'static str,
+    types: &'static [T],
+    default: DefaultValue,
+) -> PropertyDef {
+    PropertyDef {
+        name,
+        types,
+        default: Some(default),
+    }
+}
+
+/// `color` and `size` defaults, for the classes the preview draws
+const fn look(color: u32, (w, h): (f64, f64)) -> [PropertyDef; 2] {
+    let [_, r, g, b] = color.to_be_bytes();
+    [
+        with_default(
+            "color",
+            &[T::Color, T::List],
+            DefaultValue::Color([r, g, b, 255]),
+        ),
+        with_default("size", &[T::Vec2], DefaultValue::Vec2(w, h)),
+    ]
+}
+
+const GEM: ClassDef = ClassDef {
+    name: "Gem",
+    properties: &[
+        prop("position", &[T::Vec2]),
+        prop("size", &[T::Vec2]),
+        prop("min_size", &[T::Vec2]),
+        prop("max_size", &[T::Vec2]),
+        prop("rotation", &[T::Float]),
+        prop("scale", &[T::Vec2]),
+        prop("visible", &[T::Bool]),
+        prop("process_mode", &[T::String]),
+        prop("physics_interpolation", &[T::Bool]),
+        prop("link", &[T::SceneRef]),
+        // Drawing
+        prop("color", &[T::Color, T::List]),
+        prop("blend_mode", &[T::String]),
+        prop("gradient", &[T::List]),
+        prop("gradient_mode", &[T::String]),
+        prop("gradient_angle", &[T::Float]),
+        prop("texture", &[T::SceneRef, T::String]),
+        prop("filter", &[T::String]),
+        prop("repeat", &[T::String, T::Bool]),
+        prop("mipmaps", &[T::Bool, T::String]),
+        prop("occluder", &[T::Bool, T::Vec2]),
+        prop("ambient_light", &[T::Color, T::List]),
+        // Bit n - 1 for render layer n, 1 when unset; cameras draw the layers
+        // in their `cull_mask`
+        prop("visibility_layer", &[T::Int]),
+        // Layout within a container, and around children
+        prop("padding", &[T::Float, T::List, T::Vec2]),
+        prop("margin", &[T::Float, T::List, T::Vec2]),
+        prop("size_flags_horizontal", &[T::String]),
+        prop("size_flags_vertical", &[T::String]),
+        prop("stretch_ratio", &[T::Float]),
+        prop("tab_title", &[T::String]),
+        // Text, which any Gem draws
+        prop("text", &[T::String]),
+        prop("font", &[T::SceneRef, T::List]),
+        prop("fallback_fonts", &[T::List, T::SceneRef]),
+        prop("font_size", &[T::Float]),
+        prop("font_color", &[T::Color, T::List]),
+        prop("align", &[T::String]),
+        prop("autowrap", &[T::Bool]),
+        prop("max_lines", &[T::Int]),
+        prop("line_spacing", &[T::Float]),
+        prop("theme_font", &[T::SceneRef, T::List]),
+        prop("theme_font_size", &[T::Float]),
+        prop("theme_font_color", &[T::Color, T::List]),
+    ],
+    signals: &[
+        "ready()",
+        "visibility_changed(visible)",
+        "tree_entered()",
+        "tree_exiting()",
+        "child_entered_tree()",
+        "child_exited_tree()",
+        "focus_entered()",
+        "focus_exited()",
+    ],
+};
+
+const TOGGLE: &[PropertyDef] = &{
+    let [color, size] = look(0x1f1f26, (160.0, 28.0));
+    [
+        color,
+        size,
+        with_default(
+            "check_color",
+            &[T::Color, T::List],
+            DefaultValue::Color([77, 153, 230, 255]),
+        ),
+        with_default("pressed", &[T::Bool], DefaultValue::Bool(false)),
+        prop("button_group", &[T::String]),
+    ]
+};
+
+const TEXT_CONTROL: [PropertyDef; 5] = [
+    with_default("editable", &[T::Bool], DefaultValue::Bool(true)),
+    with_default("caret", &[T::Int], DefaultValue::Int(0)),
+    prop("selection_anchor", &[T::Int]),
+    prop("preedit", &[T::String]),
+    prop("preedit_cursor", &[T::Int]),
+];
+
+const POPUP: [PropertyDef; 2] = [
+    with_default("visible", &[T::Bool], DefaultValue::Bool(false)),
+    with_default("dim_background", &[T::Bool], DefaultValue::Bool(false)),
+];
+
+const LIST: [PropertyDef; 3] = [
+    prop("items", &[T::List]),
+    with_default("selected", &[T::Int], DefaultValue::Int(-1)),
+    with_default("scroll_offset", &[T::Float], DefaultValue::Float(0.0)),
+];
+
+const CLASSES: &[ClassDef] = &[
+    GEM,
+    ClassDef {
+        name: "SpriteGem",
+        properties: &[prop("sprite", &[T::SceneRef, T::String])],
+        signals: &[],
+    },
+    ClassDef {
+        name: "LabelGem",
+        properties: &look(0xb333cc, (260.0, 40.0)),
+        signals: &[],
+    },
+    ClassDef {
+        name: "RichLabelGem",
+        properties: &[],
+        signals: &[],
+    },
+    ClassDef {
+        name: "ButtonGem",
+        properties: &look(0x33b34d, (200.0, 56.0)),
+        signals: &[],
+    },
+    ClassDef {
+        name: "PanelGem",
+        properties: &[],
+        signals: &[],
+    },
+    ClassDef {
+        name: "ContainerGem",
+        properties: &[],
+        signals: &[],
+    },
+    ClassDef {
+        name: "VBoxGem",
+        properties: &[prop("separation", &[T::Float])],
+        signals: &[],
+    },
+    ClassDef {
+        name: "HBoxGem",
+        properties: &[prop("separation", &[T::Float])],
+        signals: &[],
+    },
+    ClassDef {
+        name: "GridGem",
+        properties: &[prop("separation", &[T::Float]), prop("columns", &[T::Int])],
+        signals: &[],
+    },
+    ClassDef {
+        name: "Light2DGem",
+        properties: &[prop("radius", &[T::Float]), prop("energy", &[T::Float])],
+        signals: &[],
+    },
+    ClassDef {
+        name: "Camera2DGem",
+        properties: &[
+            // All 20 render layers
+            with_default("cull_mask", &[T::Int], DefaultValue::Int(0xF_FFFF)),
+        ],
+        signals: &[],
+    },
+    ClassDef {
+        name: "MeshGem",
+        properties: &[
+            prop("mesh", &[T::SceneRef]),
+            with_default(
+                "color",
+                &[T::Color, T::List],
+                DefaultValue::Color([204, 204, 204, 255]),
+            ),
+            prop("position", &[T::Vec3]),
+            prop("rotation", &[T::Vec3]),
+            prop("scale", &[T::Vec3]),
+        ],
+        signals: &[],
+    },
+    ClassDef {
+        name: "SkeletonGem",
+        properties: &[
+            // The glTF model whose animations it plays
+            prop("model", &[T::SceneRef]),
+            with_default("looping", &[T::Bool], DefaultValue::Bool(true)),
+            prop("position", &[T::Vec3]),
+            prop("rotation", &[T::Vec3]),
+            prop("scale", &[T::Vec3]),
+        ],
+        signals: &["animation_finished(name)"],
+    },
+    ClassDef {
+        name: "BoneAttachmentGem",
+        properties: &[
+            prop("bone", &[T::String]),
+            prop("position", &[T::Vec3]),
+            prop("rotation", &[T::Vec3]),
+            prop("scale", &[T::Vec3]),
+        ],
+        signals: &[],
+    },
+    ClassDef {
+        name: "Camera3DGem",
+        properties: &[
+            prop("position", &[T::Vec3]),
+            prop("rotation", &[T::Vec3]),
+            // Vertical field of view in degrees
+            with_default("fov", &[T::Float], DefaultValue::Float(70.0)),
+            with_default("near", &[T::Float], DefaultValue::Float(0.05)),
+            with_default("far", &[T::Float], DefaultValue::Float(1000.0)),
+        ],
+        signals: &[],
+    },
+    ClassDef {
+        name: "ViewportGem",
+        properties: &[with_default(
+            "size",
+            &[T::Vec2],
+            DefaultValue::Vec2(400.0, 300.0),
+        )],
+        signals: &[],
+    },
+    ClassDef {
+        name: "CanvasLayerGem",
+        properties: &[
+            with_default("layer", &[T::Int], DefaultValue::Int(1)),
+            prop("offset", &[T::Vec2]),
+        ],
+        signals: &[],
+    },
+    ClassDef {
+        name: "Rigidbody2DGem",
+        properties: &[],
+        signals: &[],
+    },
+    ClassDef {
+        name: "Collider2DGem",
+        properties: &[],
+        signals: &[],
+    },
+    ClassDef {
+        name: "LineEditGem",
+        properties: &{
+            let [color, size] = look(0x1f1f26, (240.0, 36.0));
+            let [a, b, c, d, e] = TEXT_CONTROL;
+            [color, size, a, b, c, d, e]
+        },
+        signals: &["text_changed(text)", "text_submitted(text)"],
+    },
+    ClassDef {
+        name: "TextEditGem",
+        properties: &{
+            let [color, size] = look(0x1f1f26, (320.0, 160.0));
+            let [a, b, c, d, e] = TEXT_CONTROL;
+            [color, size, a, b, c, d, e]
+        },
+        signals: &["text_changed(text)"],
+    },
+    ClassDef {
+        name: "PopupGem",
+        properties: &{
+            let [color, size] = look(0x383845, (320.0, 180.0));
+            let [visible, dim] = POPUP;
+            [color, size, visible, dim]
+        },
+        signals: &["popup_hide()"],
+    },
+    ClassDef {
+        name: "DialogGem",
+        properties: &{
+            let [color, size] = look(0x383845, (320.0, 180.0));
+            let [visible, dim] = POPUP;
+            [color, size, visible, dim]
+        },
+        signals: &["popup_hide()"],
+    },
+    ClassDef {
+        name: "PopupMenuGem",
+        properties: &{
+            let [color, size] = look(0x383845, (160.0, 0.0));
+            let [visible, dim] = POPUP;
+            [
+                color,
+                size,
+                visible,
+                dim,
+                prop("items", &[T::List]),
+                with_default("focused_item", &[T::Int], DefaultValue::Int(-1)),
+            ]
+        },
+        signals: &["item_selected(index)", "popup_hide()"],
+    },
+    ClassDef {
+        name: "OptionButtonGem",
+        properties: &{
+            let [color, size] = look(0x33334d, (200.0, 36.0));
+            [
+                color,
+                size,
+                prop("items", &[T::List]),
+                with_default("selected", &[T::Int], DefaultValue::Int(-1)),
+            ]
+        },
+        signals: &["item_selected(index)"],
+    },
+    ClassDef {
+        name: "CheckBoxGem",
+        properties: TOGGLE,
+        signals: &["toggled(pressed)"],
+    },
+    ClassDef {
+        name: "RadioButtonGem",
+        properties: TOGGLE,
+        signals: &["toggled(pressed)"],
+    },
+    ClassDef {
+        name: "ColorPickerGem",
+        properties: &{
+            let [color, size] = look(0xffffff, (256.0, 200.0));
+            [color, size, prop("hue", &[T::Float])]
+        },
+        signals: &["color_changed(color)"],
+    },
+    ClassDef {
+        name: "TabContainerGem",
+        properties: &{
+            let [color, size] = look(0x2a2a33, (400.0, 300.0));
+            [
+                color,
+                size,
+                with_default("current_tab", &[T::Int], DefaultValue::Int(0)),
+            ]
+        },
+        signals: &["tab_changed(index)"],
+    },
+    ClassDef {
+        name: "AccordionGem",
+        properties: &{
+            let [color, size] = look(0x2a2a33, (300.0, 360.0));
+            [
+                color,
+                size,
+                with_default("current_tab", &[T::Int], DefaultValue::Int(0)),
+            ]
+        },
+        signals: &["tab_changed(index)"],
+    },
+    ClassDef {
+        name: "ItemListGem",
+        properties: &{
+            let [color, size] = look(0x1f1f26, (200.0, 240.0));
+            let [items, selected, scroll] = LIST;
+            [color, size, items, selected, scroll]
+        },
+        signals: &["item_selected(index)"],
+    },
+    ClassDef {
+        name: "TreeGem",
+        properties: &{
+            let [color, size] = look(0x1f1f26, (240.0, 320.0));
+            let [items, selected, scroll] = LIST;
+            [
+                color,
+                size,
+                items,
+                selected,
+                scroll,
+                prop("item_parents", &[T::List]),
+                prop("item_collapsed", &[T::List]),
+            ]
+        },
+        signals: &["item_selected(index)"],
+    },
+    ClassDef {
+        name: "StateMachineGem",
+        properties: &[
+            prop("initial_state", &[T::String]),
+            prop("current_state", &[T::String]),
+        ],
+        signals: &["state_changed(from, to)"],
+    },
+    ClassDef {
+        name: "TimerGem",
+        properties: &[
+            with_default("wait_time", &[T::Float], DefaultValue::Float(1.0)),
+            with_default("one_shot", &[T::Bool], DefaultValue::Bool(false)),
+            prop("time_left", &[T::Float]),
+        ],
+        signals: &["timeout()"],
+    },
+    ClassDef {
+        name: "WebSocketGem",
+        properties: &[with_default(
+            "state",
+            &[T::String],
+            DefaultValue::Str("closed"),
+        )],
+        signals: &[
+            "connected()",
+            "message_received(data)",
+            "closed(code, reason)",
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+    use crate::transformer::Transformer;
+
+    #[test]
+    fn classes_inherit_gem_and_coerce_values() {
+        let check_box = find("CheckBoxGem").unwrap();
+        assert_eq!(
+            check_box.property("size").unwrap().default,
+            Some(DefaultValue::Vec2(160.0, 28.0))
+        );
+        // Gem's own `size` has no default; the class's comes first
+        assert!(check_box.property("position").is_some());
+        assert!(check_box.property("wait_time").is_none());
+        assert_eq!(check_box.all_signals().next(), Some("toggled(pressed)"));
+        assert!(check_box.all_signals().any(|s| s == "ready()"));
+        assert_eq!(
+            find("Gem").unwrap().all_properties().count(),
+            GEM.properties.len()
+        );
+        assert!(find("Sprite").is_none());
+
+        let wait_time = find("TimerGem").unwrap().property("wait_time").unwrap();
+        assert_eq!(
+            wait_time.coerce(&PropValue::Int(2)),
+            Some(PropValue::Float(2.0))
+        );
+        assert_eq!(wait_time.coerce(&PropValue::Str("2".into())), None);
+        let occluder = GEM.property("occluder").unwrap();
+        assert!(occluder.coerce(&PropValue::Vec2(4.0, 4.0)).is_some());
+        assert!(occluder.coerce(&PropValue::Bool(true)).is_some());
+
+        // The transformer converts what the scene sets and fills in the rest
+        let scene = pipeline::lex_source("Tick: TimerGem { wait_time: 2 }")
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let ir = Transformer::new().transform(scene).unwrap();
+        let timer = &ir.nodes[&ir.root.unwrap()].properties;
+        assert_eq!(timer["wait_time"].value, PropValue::Float(2.0));
+        assert_eq!(timer["wait_time"].prop_type, T::Float);
+        assert_eq!(timer["one_shot"].value, PropValue::Bool(false));
+        assert!(!timer.contains_key("time_left"));
+    }
+}
@@ -0,0 +1,120 @@
+//! The system clipboard, for text controls and the `Clipboard` singleton.
+//! Text goes through the platform's clipboard tool: wl-copy/wl-paste on
+//! Wayland, xclip or xsel on X11, pbcopy/pbpaste on macOS and PowerShell on
+//! Windows. When none of them works the text is kept in the process, so copy
+//! and paste still work inside the game.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+/// Last text set from this process; the fallback when the system can't be reached
+static LOCAL: Mutex<String> = Mutex::new(String::new());
+
+/// (program, args) pairs: readers print the clipboard, writers take it on stdin
+fn readers() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(test) {
+        // Tests never touch the user's clipboard
+        Vec::new()
+    } else if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![(
+            "powershell",
+            &["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+        )]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        vec![("wl-paste", &["--no-newline"])]
+    } else {
+        vec![
+            ("xclip", &["-selection", "clipboard", "-out"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    }
+}
+
+fn writers() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(test) {
+        Vec::new()
+    } else if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![("clip", &[])]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        vec![("wl-copy", &[])]
+    } else {
+        vec![
+            ("xclip", &["-selection", "clipboard", "-in"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    }
+}
+
+pub fn get_text() -> String {
+    for (program, args) in readers() {
+        if let Ok(out) = Command::new(program)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            && out.status.success()
+        {
+            return String::from_utf8_lossy(&out.stdout).into_owned();
+        }
+    }
+    LOCAL.lock().unwrap().clone()
+}
+
+pub fn set_text(text: &str) {
+    *LOCAL.lock().unwrap() = text.to_string();
+    for (program, args) in writers() {
+        if write_with(program, args, text).is_ok() {
+            return;
+        }
+    }
+}
+
+fn write_with(program: &str, args: &[&str], text: &str) -> std::io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Closing stdin lets the tool take ownership of the selection and exit
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )))
+    }
+}
+
+/// The script-facing `Clipboard` singleton
+pub fn new_clipboard() -> ObjectRef {
+    let obj = ObjectInner::base("Clipboard");
+    // get_text() -> String, set_text(text)
+    ObjectInner::insert_method(
+        &obj,
+        "get_text",
+        Arc::new(|_, _| Ok(Value::String(get_text()))),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "set_text",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::String(text)) => {
+                set_text(text);
+                Ok(Value::Null)
+            }
+            _ => Err("set_text expects a String".into()),
+        }),
+    );
+    obj
+}
@@ -1,12 +1,28 @@
 //! Rust Code Generator
 //! Emits zero-heap Rust source from SceneIR using typed property structs
 
-use crate::ir::{NodeId, SceneIR};
+use crate::class_defs;
+use crate::ir::{NodeId, PropValue, SceneIR, TypedProperty};
+use crate::printer;
+use crate::property_type::PropertyType;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NO_STD: AtomicBool = AtomicBool::new(false);
+
+/// Generate no_std output for every scene compiled from now on
+pub fn set_no_std(no_std: bool) {
+    NO_STD.store(no_std, Ordering::Relaxed);
+}
+
+pub fn no_std() -> bool {
+    NO_STD.load(Ordering::Relaxed)
+}
 
 pub struct RustCodegen {
     output: String,
     indent_level: usize,
+    no_std: bool,
 }
 
 impl RustCodegen {
@@ -14,9 +30,18 @@ impl RustCodegen {
         Self {
             output: String::new(),
             indent_level: 0,
+            no_std: false,
         }
     }
 
+    /// Output for no_std and embedded projects: only `core` items, no inner
+    /// attributes so the file can be `include!`d, and the nodes as one const
+    /// table with their children and signals
+    pub fn with_no_std(mut self, no_std: bool) -> Self {
+        self.no_std = no_std;
+        self
+    }
+
     pub fn generate(mut self, scene: &SceneIR) -> String {
         self.emit_header();
         self.emit_property_structs(scene);
@@ -29,52 +54,137 @@ impl RustCodegen {
     fn emit_header(&mut self) {
         self.writeln("// Auto-generated by Gem compiler");
         self.writeln("// Do not edit manually\n");
-        self.writeln("#![allow(unused)]\n");
+        if self.no_std {
+            self.writeln("// no_std: uses only core, with no allocation\n");
+        } else {
+            self.writeln("#![allow(unused)]\n");
+        }
     }
 
-    /// Generate a struct for each node's properties with typed fields
+    /// Generate a struct for each node's properties with typed fields, and
+    /// one for its metadata
     fn emit_property_structs(&mut self, scene: &SceneIR) {
         let mut ids: Vec<NodeId> = scene.nodes.keys().cloned().collect();
         ids.sort_by_key(|id| id.0);
 
         for id in ids.iter() {
             let node = &scene.nodes[id];
-            if node.properties.is_empty() {
-                continue;
-            }
+            let upper = node.name.to_uppercase();
+            self.emit_values_struct(
+                &format!("{}Props", node.name),
+                &format!("{}_PROPS", upper),
+                &node.name,
+                &node.properties,
+            );
+            self.emit_values_struct(
+                &format!("{}Meta", node.name),
+                &format!("{}_META", upper),
+                &format!("{}Meta", node.name),
+                &node.meta,
+            );
+        }
+    }
 
-            // Generate struct name from node name
-            let struct_name = format!("{}Props", node.name);
+    /// `struct_name` with a typed field per value, and its `const_name`
+    /// instance. Struct values are named `prefix` + the PascalCase key.
+    fn emit_values_struct(
+        &mut self,
+        struct_name: &str,
+        const_name: &str,
+        prefix: &str,
+        values: &HashMap<String, TypedProperty>,
+    ) {
+        if values.is_empty() {
+            return;
+        }
+        // By key, so the same scene always generates the same code
+        let mut values: Vec<(&String, &TypedProperty)> = values.iter().collect();
+        values.sort_by_key(|(key, _)| *key);
 
-            self.writeln(&format!("#[derive(Debug, Clone)]"));
-            self.writeln(&format!("pub struct {} {{", struct_name));
-            self.indent();
+        // Struct values (also inside lists) get their own named types first
+        for &(key, typed_prop) in &values {
+            let name = format!("{}{}", prefix, pascal_case(key));
+            self.emit_value_types(&name, &typed_prop.value);
+        }
 
-            // Emit fields with inferred types
-            for (key, typed_prop) in node.properties.iter() {
-                let rust_type = typed_prop.prop_type.to_rust_type();
-                self.writeln(&format!("pub {}: {},", key, rust_type));
-            }
+        self.writeln("#[derive(Debug, Clone)]");
+        self.writeln(&format!("pub struct {} {{", struct_name));
+        self.indent();
 
-            self.dedent();
-            self.writeln("}\n");
+        // Emit fields with their value types
+        for &(key, typed_prop) in &values {
+            let rust_type = match typed_prop.prop_type {
+                PropertyType::List
+                | PropertyType::Array
+                | PropertyType::Map
+                | PropertyType::Struct => rust_type(
+                    &format!("{}{}", prefix, pascal_case(key)),
+                    &typed_prop.value,
+                ),
+                ref scalar => scalar.to_rust_type().to_string(),
+            };
+            self.writeln(&format!("pub {}: {},", rust_field(key), rust_type));
+        }
 
-            // Emit const instance
-            self.writeln(&format!(
-                "pub const {}_PROPS: {} = {} {{",
-                node.name.to_uppercase(),
-                struct_name,
-                struct_name
-            ));
-            self.indent();
+        self.dedent();
+        self.writeln("}\n");
 
-            for (key, typed_prop) in node.properties.iter() {
-                let const_value = typed_prop.prop_type.parse_to_rust_const(&typed_prop.value);
-                self.writeln(&format!("{}: {},", key, const_value));
-            }
+        // Emit const instance
+        self.writeln(&format!(
+            "pub const {}: {} = {} {{",
+            const_name, struct_name, struct_name
+        ));
+        self.indent();
 
-            self.dedent();
-            self.writeln("};\n");
+        for &(key, typed_prop) in &values {
+            let const_value = const_expr(
+                &format!("{}{}", prefix, pascal_case(key)),
+                &typed_prop.value,
+            );
+            self.writeln(&format!("{}: {},", rust_field(key), const_value));
+        }
+
+        self.dedent();
+        self.writeln("};\n");
+    }
+
+    /// Named struct types for every struct within `value`, innermost first.
+    /// A struct is named `name`; its fields extend the name with their key,
+    /// list elements with their index, and array elements and map values
+    /// with `Item` and `Value`.
+    fn emit_value_types(&mut self, name: &str, value: &PropValue) {
+        match value {
+            PropValue::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.emit_value_types(&format!("{}{}", name, i), item);
+                }
+            }
+            // Elements share one type, so the first stands for all
+            PropValue::Array(items) => {
+                if let Some(first) = items.first() {
+                    self.emit_value_types(&format!("{}Item", name), first);
+                }
+            }
+            PropValue::Map(entries) => {
+                if let Some((_, first)) = entries.first() {
+                    self.emit_value_types(&format!("{}Value", name), first);
+                }
+            }
+            PropValue::Struct(fields) => {
+                for (key, field) in fields {
+                    self.emit_value_types(&format!("{}{}", name, pascal_case(key)), field);
+                }
+                self.writeln("#[derive(Debug, Clone)]");
+                self.writeln(&format!("pub struct {} {{", name));
+                self.indent();
+                for (key, field) in fields {
+                    let rust_type = rust_type(&format!("{}{}", name, pascal_case(key)), field);
+                    self.writeln(&format!("pub {}: {},", rust_field(key), rust_type));
+                }
+                self.dedent();
+                self.writeln("}\n");
+            }
+            _ => {}
         }
     }
 
@@ -89,6 +199,11 @@ impl RustCodegen {
         self.writeln("pub name: &'static str,");
         self.writeln("pub class_name: &'static str,");
         self.writeln("pub node_index: usize,");
+        self.writeln("pub uid: Option<&'static str>,");
+        if self.no_std {
+            self.writeln("pub children: &'static [usize],");
+            self.writeln("pub signals: &'static [&'static str],");
+        }
         self.dedent();
         self.writeln("}\n");
 
@@ -102,6 +217,15 @@ impl RustCodegen {
             self.writeln(&format!("name: \"{}\",", node.name));
             self.writeln(&format!("class_name: \"{}\",", node.class_name));
             self.writeln(&format!("node_index: {},", idx));
+            match &node.uid {
+                Some(uid) => self.writeln(&format!("uid: Some({:?}),", uid)),
+                None => self.writeln("uid: None,"),
+            }
+            if self.no_std {
+                let upper = node.name.to_uppercase();
+                self.writeln(&format!("children: {}_CHILDREN,", upper));
+                self.writeln(&format!("signals: {}_SIGNALS,", upper));
+            }
             self.dedent();
             self.writeln("};\n");
         }
@@ -119,6 +243,35 @@ impl RustCodegen {
             ));
         }
         self.writeln("");
+
+        self.writeln("// Signals each node's class emits");
+        for id in ids.iter() {
+            let node = &scene.nodes[id];
+            let signals: Vec<String> = class_defs::find(&node.class_name)
+                .into_iter()
+                .flat_map(|class| class.all_signals())
+                .map(|signal| format!("{:?}", signal))
+                .collect();
+            self.writeln(&format!(
+                "pub const {}_SIGNALS: &[&str] = &[{}];",
+                node.name.to_uppercase(),
+                signals.join(", ")
+            ));
+        }
+        self.writeln("");
+
+        if self.no_std {
+            let nodes: Vec<String> = ids
+                .iter()
+                .map(|id| format!("{}_NODE", scene.nodes[id].name.to_uppercase()))
+                .collect();
+            self.writeln("// Every node, by node_index");
+            self.writeln(&format!(
+                "pub const NODES: [Node; {}] = [{}];\n",
+                nodes.len(),
+                nodes.join(", ")
+            ));
+        }
     }
 
     fn emit_scene_struct(&mut self, scene: &SceneIR) {
@@ -130,6 +283,9 @@ impl RustCodegen {
         self.indent();
         self.writeln("pub root_index: usize,");
         self.writeln("pub node_count: usize,");
+        if self.no_std {
+            self.writeln("pub nodes: &'static [Node],");
+        }
         self.dedent();
         self.writeln("}\n");
 
@@ -138,6 +294,9 @@ impl RustCodegen {
         let root_idx = scene.root.map(|r| r.0).unwrap_or(0);
         self.writeln(&format!("root_index: {},", root_idx));
         self.writeln(&format!("node_count: {},", ids.len()));
+        if self.no_std {
+            self.writeln("nodes: &NODES,");
+        }
         self.dedent();
         self.writeln("};\n");
     }
@@ -172,6 +331,46 @@ impl RustCodegen {
         self.dedent();
         self.writeln("}\n");
 
+        // `%Name` lookups, by bare name
+        let mut unique: Vec<(&String, usize)> = scene
+            .unique_names
+            .iter()
+            .filter_map(|(name, id)| Some((name, ids.iter().position(|i| i == id)?)))
+            .collect();
+        unique.sort();
+        self.writeln("pub fn find_unique_node(name: &str) -> Option<usize> {");
+        self.indent();
+        self.writeln("match name {");
+        self.indent();
+        for (name, idx) in unique {
+            self.writeln(&format!("\"{}\" => Some({}),", name, idx));
+        }
+        self.writeln("_ => None,");
+        self.dedent();
+        self.writeln("}");
+        self.dedent();
+        self.writeln("}\n");
+
+        // Lookups by stable uid, which survive edits that renumber nodes
+        let mut uids: Vec<(&String, usize)> = scene
+            .uids
+            .iter()
+            .filter_map(|(uid, id)| Some((uid, ids.iter().position(|i| i == id)?)))
+            .collect();
+        uids.sort();
+        self.writeln("pub fn find_node_by_uid(uid: &str) -> Option<usize> {");
+        self.indent();
+        self.writeln("match uid {");
+        self.indent();
+        for (uid, idx) in uids {
+            self.writeln(&format!("{:?} => Some({}),", uid, idx));
+        }
+        self.writeln("_ => None,");
+        self.dedent();
+        self.writeln("}");
+        self.dedent();
+        self.writeln("}\n");
+
         // Generate typed property accessor example
         self.writeln("// Example: Access typed properties");
         self.writeln("// let title_text = TITLE_PROPS.text;");
@@ -195,6 +394,125 @@ impl RustCodegen {
     }
 }
 
+/// Rust type of a value, with struct types named as in `emit_value_types`
+fn rust_type(name: &str, value: &PropValue) -> String {
+    match value {
+        PropValue::List(items) => {
+            let types: Vec<String> = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| rust_type(&format!("{}{}", name, i), item))
+                .collect();
+            tuple(types)
+        }
+        PropValue::Array(items) => {
+            let item = items.first().map_or("()".to_string(), |first| {
+                rust_type(&format!("{}Item", name), first)
+            });
+            format!("&'static [{}]", item)
+        }
+        PropValue::Map(entries) => {
+            let value = entries.first().map_or("()".to_string(), |(_, first)| {
+                rust_type(&format!("{}Value", name), first)
+            });
+            format!("&'static [(&'static str, {})]", value)
+        }
+        PropValue::Struct(_) => name.to_string(),
+        _ => PropertyType::of(value).to_rust_type().to_string(),
+    }
+}
+
+/// Rust const expression for a value
+fn const_expr(name: &str, value: &PropValue) -> String {
+    match value {
+        PropValue::Int(i) => i.to_string(),
+        // Debug keeps the decimal point (`1.0`) and full precision
+        PropValue::Float(f) => format!("{:?}", f),
+        PropValue::Bool(b) => b.to_string(),
+        PropValue::Str(s) => format!("{:?}", s),
+        PropValue::Vec2(x, y) => format!("({:?}, {:?})", x, y),
+        PropValue::Color([r, g, b, a]) => format!("({}, {}, {}, {})", r, g, b, a),
+        PropValue::SceneRef(directive) => format!("{:?}", printer::print_directive(directive)),
+        PropValue::List(items) => tuple(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| const_expr(&format!("{}{}", name, i), item))
+                .collect(),
+        ),
+        PropValue::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| const_expr(&format!("{}Item", name), item))
+                .collect();
+            format!("&[{}]", items.join(", "))
+        }
+        PropValue::Map(entries) => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, v)| {
+                    format!("({:?}, {})", key, const_expr(&format!("{}Value", name), v))
+                })
+                .collect();
+            format!("&[{}]", entries.join(", "))
+        }
+        PropValue::Struct(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, field)| {
+                    let nested = format!("{}{}", name, pascal_case(key));
+                    format!("{}: {}", rust_field(key), const_expr(&nested, field))
+                })
+                .collect();
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        PropValue::Curve(curve) => {
+            let points: Vec<String> = curve
+                .points
+                .iter()
+                .map(|p| format!("({:?}, {:?}, {:?})", p.t, p.value, p.easing.as_str()))
+                .collect();
+            format!("&[{}]", points.join(", "))
+        }
+        PropValue::SizeExpr(expr) => format!("{:?}", expr.to_string()),
+    }
+}
+
+/// `(a, b)`; one element needs a trailing comma to stay a tuple
+fn tuple(items: Vec<String>) -> String {
+    match items.len() {
+        1 => format!("({},)", items[0]),
+        _ => format!("({})", items.join(", ")),
+    }
+}
+
+/// `hit_box` -> `HitBox`
+fn pascal_case(key: &str) -> String {
+    key.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Property keys that are Rust keywords (e.g. `type`) become raw identifiers
+fn rust_field(key: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern",
+        "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+        "ref", "return", "static", "struct", "trait", "type", "unsafe", "use", "where", "while",
+    ];
+    if KEYWORDS.contains(&key) {
+        format!("r#{}", key)
+    } else {
+        key.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,16 +524,23 @@ mod tests {
         let r = scene.add_node("Root", "Gem");
         let c = scene.add_node("Child", "Sprite");
         scene.add_child(r, c);
-        scene.set_property(r, "position", "(0, 0)");
+        scene.set_property(r, "position", PropValue::Vec2(0.0, 0.0));
+        scene.set_uid(c, "c0ffee01").unwrap();
 
         let code = RustCodegen::new().generate(&scene);
         // Check for typed property structs
         assert!(code.contains("pub struct RootProps"));
         assert!(code.contains("pub const ROOT_PROPS"));
-        assert!(code.contains("pub position: (i32, i32)"));
+        assert!(code.contains("pub position: (f64, f64)"));
+        assert!(code.contains("position: (0.0, 0.0),"));
         // Check for node constants
         assert!(code.contains("pub const ROOT_NODE: Node"));
         assert!(code.contains("pub const CHILD_NODE: Node"));
+        assert!(code.contains("uid: Some(\"c0ffee01\"),"));
+        assert!(code.contains("\"c0ffee01\" => Some(1),"));
+        // Signals come from the class definitions; Sprite isn't a class
+        assert!(code.contains("pub const ROOT_SIGNALS: &[&str] = &[\"ready()\", "));
+        assert!(code.contains("pub const CHILD_SIGNALS: &[&str] = &[];"));
         // Check for scene and builder
         assert!(code.contains("pub const SCENE: Scene"));
         assert!(code.contains("pub fn build_scene() -> Scene"));
@@ -223,4 +548,155 @@ mod tests {
         assert!(code.contains("Root"));
         assert!(code.contains("Child"));
     }
+
+    #[test]
+    fn values_keep_their_types_and_precision() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::transformer::Transformer;
+
+        let src = r#"Hud: Gem {
+            position: (10, 2.5)
+            speed: 0.1234567890123
+            count: 9000000000
+            label: "say \"hi\""
+            tint: #ff800080
+            next: #example:main_scene.gem
+            path: (1, 2, (3, "x"))
+            fade: Curve((0, 1), (1, 0, ease_in))
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse_scene().unwrap();
+        let scene = Transformer::new().transform(ast).unwrap();
+        let code = RustCodegen::new().generate(&scene);
+        assert!(code.contains("position: (10.0, 2.5),"));
+        assert!(code.contains("speed: 0.1234567890123,"));
+        assert!(code.contains("count: 9000000000,"));
+        assert!(code.contains("label: \"say \\\"hi\\\"\","));
+        assert!(code.contains("pub tint: (u8, u8, u8, u8),"));
+        assert!(code.contains("tint: (255, 128, 0, 128),"));
+        assert!(code.contains("next: \"#example:main_scene.gem\","));
+        assert!(code.contains("pub path: (i64, i64, (i64, &'static str)),"));
+        assert!(code.contains("pub fade: &'static [(f64, f64, &'static str)],"));
+        assert!(code.contains("fade: &[(0.0, 1.0, \"linear\"), (1.0, 0.0, \"ease_in\")],"));
+    }
+
+    #[test]
+    fn struct_properties_get_named_types() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::transformer::Transformer;
+
+        let src = r#"Body: Gem { shape: { type: "circle", radius: 16, hit_box: { layer: 2 } } }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse_scene().unwrap();
+        let scene = Transformer::new().transform(ast).unwrap();
+        let shape = &scene.nodes[&scene.root.unwrap()].properties["shape"];
+        let PropValue::Struct(fields) = &shape.value else {
+            panic!("expected a struct value");
+        };
+        assert_eq!(fields[1], ("radius".to_string(), PropValue::Int(16)));
+
+        let code = RustCodegen::new().generate(&scene);
+        assert!(code.contains("pub struct BodyShapeHitBox {"));
+        assert!(code.contains("pub r#type: &'static str,"));
+        assert!(code.contains("pub shape: BodyShape,"));
+        assert!(code.contains(
+            "shape: BodyShape { r#type: \"circle\", radius: 16, hit_box: BodyShapeHitBox { layer: 2 } },"
+        ));
+    }
+
+    #[test]
+    fn metadata_gets_its_own_struct() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::transformer::Transformer;
+
+        let src = "Spawn: Gem { position: (1, 2) meta:spawn_point: true }";
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse_scene().unwrap();
+        let scene = Transformer::new().transform(ast).unwrap();
+        let spawn = &scene.nodes[&scene.root.unwrap()];
+        assert!(!spawn.properties.contains_key("spawn_point"));
+        assert_eq!(spawn.meta["spawn_point"].value, PropValue::Bool(true));
+
+        let code = RustCodegen::new().generate(&scene);
+        assert!(code.contains("pub struct SpawnMeta {\n    pub spawn_point: bool,\n}"));
+        assert!(code.contains("pub const SPAWN_META: SpawnMeta = SpawnMeta {"));
+        assert!(code.contains("pub struct SpawnProps {\n    pub position: (f64, f64),\n}"));
+    }
+
+    #[test]
+    fn arrays_become_static_slices() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::transformer::Transformer;
+
+        let scene = |src: &str| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse_scene().unwrap();
+            Transformer::new().transform(ast)
+        };
+        let squad =
+            scene(r#"Squad: Gem { meta:tags: ["enemy", "flying"] meta:weights: [1, 2.5] }"#)
+                .unwrap();
+        let root = &squad.nodes[&squad.root.unwrap()];
+        assert_eq!(
+            root.meta["weights"].value,
+            PropValue::Array(vec![PropValue::Float(1.0), PropValue::Float(2.5)])
+        );
+
+        let code = RustCodegen::new().generate(&squad);
+        assert!(code.contains("pub tags: &'static [&'static str],"));
+        assert!(code.contains("tags: &[\"enemy\", \"flying\"],"));
+        assert!(code.contains("weights: &[1.0, 2.5],"));
+
+        let err = scene(r#"Squad: Gem { meta:tags: ["enemy", 2] }"#).unwrap_err();
+        assert!(
+            err.contains("array elements must all have the same type"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn maps_become_key_value_slices() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+        use crate::transformer::Transformer;
+
+        let scene = |src: &str| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse_scene().unwrap();
+            Transformer::new().transform(ast)
+        };
+        let loot =
+            scene(r#"Loot: Gem { meta:odds: { "Sword of Fire": 0.5, "gold": 2 } }"#).unwrap();
+        let code = RustCodegen::new().generate(&loot);
+        assert!(code.contains("pub odds: &'static [(&'static str, f64)],"));
+        assert!(code.contains("odds: &[(\"Sword of Fire\", 0.5), (\"gold\", 2.0)],"));
+
+        let err = scene(r#"Loot: Gem { meta:odds: { "gold": 2, "name": "x" } }"#).unwrap_err();
+        assert!(
+            err.contains("map values must all have the same type"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn no_std_output_tables_the_nodes() {
+        let mut scene = SceneIR::new();
+        let r = scene.add_node("Root", "Gem");
+        let c = scene.add_node("Child", "TimerGem");
+        scene.add_child(r, c);
+
+        let code = RustCodegen::new().with_no_std(true).generate(&scene);
+        assert!(!code.contains("#!["));
+        assert!(!code.contains("String") && !code.contains("HashMap"));
+        assert!(code.contains("pub const NODES: [Node; 2] = [ROOT_NODE, CHILD_NODE];"));
+        assert!(code.contains("children: ROOT_CHILDREN,\n    signals: ROOT_SIGNALS,"));
+        assert!(code.contains("nodes: &NODES,"));
+        assert!(!RustCodegen::new().generate(&scene).contains("NODES"));
+    }
 }
@@ -0,0 +1,284 @@
+//! RGBA colors: the runtime `Value::Color` and the renderer's color math.
+//! Channels are floats in 0..=1, as the renderer draws them; scene files
+//! write colors as `#rrggbb` or `#rrggbbaa`. Scripts call the methods in
+//! `call_method` on color values, or through the `Color` singleton with the
+//! color first, and build colors with the singleton too.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::easing;
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn from_rgba8(c: [u8; 4]) -> Self {
+        let [r, g, b, a] = c.map(|ch| ch as f32 / 255.0);
+        Self { r, g, b, a }
+    }
+
+    pub fn to_rgba8(self) -> [u8; 4] {
+        self.to_array()
+            .map(|ch| (ch.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    pub fn from_array(c: [f32; 4]) -> Self {
+        Self::rgba(c[0], c[1], c[2], c[3])
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// `#rrggbb` or `#rrggbbaa`
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let hex = s.strip_prefix('#')?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        let alpha = if hex.len() == 8 { channel(6) } else { 255 };
+        Some(Self::from_rgba8([
+            channel(0),
+            channel(2),
+            channel(4),
+            alpha,
+        ]))
+    }
+
+    /// `#rrggbb`, with `aa` appended unless opaque, as scene files spell it
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+
+    /// Hue, saturation and value in 0..=1; hue wraps around
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: f32) -> Self {
+        let h = h.rem_euclid(1.0) * 6.0;
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Self::rgba(r + m, g + m, b + m, a)
+    }
+
+    /// Hue, saturation and value in 0..1, the inverse of `from_hsv`; grays
+    /// have hue 0
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let d = max - min;
+        let h = if d == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / d).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+        let s = if max == 0.0 { 0.0 } else { d / max };
+        (h / 6.0, s, max)
+    }
+
+    /// Moved `amount` of the way toward white, keeping alpha
+    pub fn lightened(self, amount: f32) -> Self {
+        let mut out = self.lerp(Color::WHITE, amount);
+        out.a = self.a;
+        out
+    }
+
+    /// Moved `amount` of the way toward black, keeping alpha
+    pub fn darkened(self, amount: f32) -> Self {
+        let mut out = self.lerp(Color::BLACK, amount);
+        out.a = self.a;
+        out
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Every channel, alpha included, `t` of the way to `other`
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let mix = |a: f32, b: f32| easing::lerp(a as f64, b as f64, t as f64) as f32;
+        Self::rgba(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// A color method called from a script
+    pub fn call_method(self, name: &str, args: &[Value]) -> Result<Value, String> {
+        let number = |i: usize| {
+            args.get(i)
+                .and_then(Value::as_f64)
+                .map(|n| n as f32)
+                .ok_or_else(|| format!("Color.{} expects a number", name))
+        };
+        let color = match name {
+            "lightened" => self.lightened(number(0)?),
+            "darkened" => self.darkened(number(0)?),
+            "with_alpha" => self.with_alpha(number(0)?),
+            "lerp" => {
+                let other = args
+                    .first()
+                    .and_then(Value::as_color)
+                    .ok_or("Color.lerp expects a color and t")?;
+                self.lerp(other, number(1)?)
+            }
+            "to_hex" => return Ok(Value::String(self.to_hex())),
+            "to_hsv" => {
+                let (h, s, v) = self.to_hsv();
+                let hsv = [h, s, v].map(|c| Value::Float(c as f64));
+                return Ok(Value::Array(hsv.to_vec()));
+            }
+            "to_array" => {
+                let channels = self.to_array().map(|c| Value::Float(c as f64));
+                return Ok(Value::Array(channels.to_vec()));
+            }
+            _ => return Err(format!("Color has no method '{}'", name)),
+        };
+        Ok(Value::Color(color))
+    }
+}
+
+impl fmt::Debug for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Color({})", self.to_hex())
+    }
+}
+
+/// The script-facing `Color` singleton, which builds colors
+pub fn new_color_singleton() -> ObjectRef {
+    let obj = ObjectInner::base("Color");
+    // from_hsv(h, s, v, a?), each in 0..1
+    ObjectInner::insert_method(
+        &obj,
+        "from_hsv",
+        Arc::new(|_, args| {
+            let n = |i: usize, default: Option<f64>| {
+                args.get(i)
+                    .map(Value::as_f64)
+                    .unwrap_or(default)
+                    .map(|n| n as f32)
+                    .ok_or("from_hsv expects (h, s, v, a?)")
+            };
+            let color = Color::from_hsv(n(0, None)?, n(1, None)?, n(2, None)?, n(3, Some(1.0))?);
+            Ok(Value::Color(color))
+        }),
+    );
+    // lightened(color, f) and the other color methods, with the color first
+    for name in [
+        "lightened",
+        "darkened",
+        "with_alpha",
+        "lerp",
+        "to_hex",
+        "to_hsv",
+        "to_array",
+    ] {
+        ObjectInner::insert_method(
+            &obj,
+            name,
+            Arc::new(move |_, args| {
+                let color = args
+                    .first()
+                    .and_then(Value::as_color)
+                    .ok_or_else(|| format!("Color.{} expects a color first", name))?;
+                color.call_method(name, &args[1..])
+            }),
+        );
+    }
+    // from_hex("#rrggbb" or "#rrggbbaa")
+    ObjectInner::insert_method(
+        &obj,
+        "from_hex",
+        Arc::new(|_, args| match args {
+            [Value::String(s)] => Color::from_hex(s)
+                .map(Value::Color)
+                .ok_or_else(|| format!("'{}' is not a #rrggbb or #rrggbbaa color", s)),
+            _ => Err("from_hex expects a string".to_string()),
+        }),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_math_and_script_methods() {
+        let red = Color::from_hex("#ff0000").unwrap();
+        assert_eq!(red.to_hex(), "#ff0000");
+        assert_eq!(red.lightened(0.5).to_hex(), "#ff8080");
+        assert_eq!(red.darkened(0.5).to_hex(), "#800000");
+        assert_eq!(red.with_alpha(0.5).to_hex(), "#ff000080");
+        assert_eq!(
+            red.lerp(Color::from_hex("#0000ff").unwrap(), 0.5).to_hex(),
+            "#800080"
+        );
+        assert_eq!(
+            Color::from_hsv(1.0 / 3.0, 1.0, 1.0, 1.0).to_hex(),
+            "#00ff00"
+        );
+        assert_eq!(Color::from_hsv(0.5, 0.5, 1.0, 1.0).to_hex(), "#80ffff");
+        assert_eq!(Color::from_hex("#80ffff").unwrap().to_hsv().0, 0.5);
+        assert_eq!(
+            Color::from_hex("#ff00ff").unwrap().to_hsv(),
+            (5.0 / 6.0, 1.0, 1.0)
+        );
+        assert_eq!(Color::from_hex("#12345"), None);
+
+        assert_eq!(
+            red.call_method("darkened", &[Value::Float(0.5)]),
+            Ok(Value::Color(red.darkened(0.5)))
+        );
+        assert!(red.call_method("saturate", &[]).is_err());
+        let singleton = new_color_singleton();
+        assert_eq!(
+            singleton.call_method("to_hex", &[Value::String("#FF0000".into())]),
+            Ok(Value::String("#ff0000".into()))
+        );
+        assert_eq!(
+            singleton.call_method("from_hex", &[Value::String("#00ff0080".into())]),
+            Ok(Value::Color(Color::rgba(0.0, 1.0, 0.0, 128.0 / 255.0)))
+        );
+        assert_eq!(
+            singleton
+                .call_method("from_hsv", &[Value::Int(0), Value::Int(1), Value::Int(1)])
+                .unwrap(),
+            Value::Color(red)
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! ColorPickerGem: edits its `color` property. The left of its rect is a
+//! saturation/value square for the current `hue`, right of it a hue bar and
+//! then an alpha bar; pressing in a part and dragging sets that part of the
+//! color and emits `color_changed(color)` on every change. The last hue
+//! picked is kept in `hue`, for when the color is a gray or black and so has
+//! none of its own. The layout is shared with the preview's draw list.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::color::Color;
+use crate::container::Rect;
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+pub const COLOR_KEY: &str = "color";
+pub const HUE_KEY: &str = "hue";
+/// The part being dragged, while the button is held
+const DRAG_KEY: &str = "dragging";
+/// Width of the hue and alpha bars, and the gap before each
+pub const BAR_WIDTH: f32 = 20.0;
+pub const GAP: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Part {
+    Square,
+    Hue,
+    Alpha,
+}
+
+impl Part {
+    const ALL: [Part; 3] = [Part::Square, Part::Hue, Part::Alpha];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Part::Square => "square",
+            Part::Hue => "hue",
+            Part::Alpha => "alpha",
+        }
+    }
+}
+
+/// Where each part sits in a picker of `size`, relative to its corner
+pub fn layout(size: (f32, f32)) -> [(Part, Rect); 3] {
+    let (w, h) = size;
+    let square = (w - 2.0 * (BAR_WIDTH + GAP)).max(0.0);
+    let hue_x = square + GAP;
+    let alpha_x = hue_x + BAR_WIDTH + GAP;
+    [
+        (Part::Square, ((0.0, 0.0), (square, h))),
+        (Part::Hue, ((hue_x, 0.0), (BAR_WIDTH, h))),
+        (Part::Alpha, ((alpha_x, 0.0), (BAR_WIDTH, h))),
+    ]
+}
+
+/// The color after pointing at `(x, y)` in `part`, given the current hue:
+/// the square sets saturation (left to right) and value (top to bottom),
+/// the bars hue and alpha (top to bottom). Returns the new color and hue.
+pub fn pick(part: Part, rect: Rect, (x, y): (f32, f32), color: Color, hue: f32) -> (Color, f32) {
+    let ((rx, ry), (rw, rh)) = rect;
+    let fx = ((x - rx) / rw.max(1.0)).clamp(0.0, 1.0);
+    let fy = ((y - ry) / rh.max(1.0)).clamp(0.0, 1.0);
+    let (_, s, v) = color.to_hsv();
+    match part {
+        Part::Square => (Color::from_hsv(hue, fx, 1.0 - fy, color.a), hue),
+        // A full turn would wrap back to 0, so the bottom stops just short
+        Part::Hue => {
+            let hue = fy.min(0.9999);
+            (Color::from_hsv(hue, s, v, color.a), hue)
+        }
+        Part::Alpha => (color.with_alpha(1.0 - fy), hue),
+    }
+}
+
+pub fn init_color_picker_class() {
+    register_class("ColorPickerGem", || {
+        let obj = gem::new_gem("ColorPickerGem", color_picker_methods());
+        obj.set_property(COLOR_KEY, Value::Color(Color::WHITE));
+        obj.set_property("size", Value::vec2(256.0, 200.0));
+        obj
+    });
+}
+
+fn color_picker_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = (*gem::gem_methods()).clone();
+            // _input(event): press in a part to start dragging it, move to
+            // keep picking, release to stop
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let Some(event) = args.first().and_then(Value::as_object) else {
+                        return Err("_input expects an InputEvent".into());
+                    };
+                    let Some((x, y)) = event
+                        .get_property("position")
+                        .and_then(|p| p.as_vec2())
+                        .and_then(|p| gem::to_local(this, p))
+                    else {
+                        return Ok(Value::Null);
+                    };
+                    let at = (x as f32, y as f32);
+                    let parts = layout(size_of(this));
+                    let dragging = match this.get_property(DRAG_KEY) {
+                        Some(Value::String(name)) => {
+                            Part::ALL.into_iter().find(|p| p.as_str() == name)
+                        }
+                        _ => None,
+                    };
+                    let part = match event.class_name() {
+                        "InputEventMouseButton" => {
+                            if !matches!(event.get_property("pressed"), Some(Value::Bool(true))) {
+                                this.set_property(DRAG_KEY, Value::Null);
+                                return Ok(Value::Null);
+                            }
+                            let Some(&(part, _)) = parts.iter().find(|(_, r)| contains(*r, at))
+                            else {
+                                return Ok(Value::Null);
+                            };
+                            this.set_property(DRAG_KEY, Value::String(part.as_str().into()));
+                            part
+                        }
+                        "InputEventMouseMotion" => match dragging {
+                            Some(part) => part,
+                            None => return Ok(Value::Null),
+                        },
+                        _ => return Ok(Value::Null),
+                    };
+                    let rect = parts.iter().find(|(p, _)| *p == part).unwrap().1;
+                    let color = this
+                        .get_property(COLOR_KEY)
+                        .and_then(|c| c.as_color())
+                        .unwrap_or(Color::WHITE);
+                    // Only grays and black need the remembered hue
+                    let hue = match color.to_hsv() {
+                        (h, s, v) if s > 0.0 && v > 0.0 => h,
+                        _ => this
+                            .get_property(HUE_KEY)
+                            .and_then(|h| h.as_f64())
+                            .map_or(0.0, |h| h as f32),
+                    };
+                    let (picked, hue) = pick(part, rect, at, color, hue);
+                    this.set_property(HUE_KEY, Value::Float(hue as f64));
+                    if picked != color {
+                        this.set_property(COLOR_KEY, Value::Color(picked));
+                        this.emit_signal("color_changed", &[Value::Color(picked)]);
+                    }
+                    event.call_method("accept_event", &[])?;
+                    Ok(Value::Null)
+                }),
+            );
+            // signals: color_changed(color)
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn size_of(picker: &ObjectRef) -> (f32, f32) {
+    let (w, h) = picker
+        .get_property("size")
+        .and_then(|s| s.as_vec2())
+        .unwrap_or((0.0, 0.0));
+    (w as f32, h as f32)
+}
+
+fn contains(((x, y), (w, h)): Rect, (px, py): (f32, f32)) -> bool {
+    px >= x && py >= y && px < x + w && py < y + h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use crate::scene_tree;
+    use std::sync::Mutex;
+
+    #[test]
+    fn dragging_picks_hue_saturation_value_and_alpha() {
+        scene_tree::init_builtin_classes();
+        let picker = object_new("ColorPickerGem");
+        // A 156px square, then bars at x 164 and 192
+        picker.set_property("size", Value::vec2(212.0, 100.0));
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        picker.connect(
+            "color_changed",
+            Arc::new(move |args| seen.lock().unwrap().push(args[0].clone())),
+        );
+        let press = |x, y| input::mouse_button_event("left", true, (x, y));
+        let color = || picker.get_property(COLOR_KEY).and_then(|c| c.as_color());
+
+        // Hue a third of the way down is green; white keeps its hue-less look
+        assert!(input::dispatch(&picker, &press(170.0, 100.0 / 3.0)));
+        assert_eq!(color(), Some(Color::WHITE));
+        // The square's top-right corner is the pure hue
+        assert!(input::dispatch(&picker, &press(155.9, 0.0)));
+        assert_eq!(color().unwrap().to_hex(), "#00ff00");
+        // Dragging past the bottom clamps value to black, keeping the hue
+        let drag = input::mouse_motion_event((155.9, 300.0), (0.0, 300.0));
+        assert!(input::dispatch(&picker, &drag));
+        assert_eq!(color().unwrap().to_hex(), "#000000");
+        assert!(!input::dispatch(
+            &picker,
+            &input::mouse_button_event("left", false, (155.9, 300.0))
+        ));
+        assert!(input::dispatch(&picker, &press(155.9, 0.0)));
+        assert_eq!(color().unwrap().to_hex(), "#00ff00");
+
+        assert!(input::dispatch(&picker, &press(200.0, 50.0)));
+        assert_eq!(color().unwrap().to_hex(), "#00ff0080");
+        // Motion without a held button picks nothing
+        assert!(!input::dispatch(
+            &picker,
+            &input::mouse_button_event("left", false, (200.0, 50.0))
+        ));
+        assert!(!input::dispatch(
+            &picker,
+            &input::mouse_motion_event((10.0, 10.0), (0.0, 0.0))
+        ));
+        assert_eq!(changes.lock().unwrap().len(), 4);
+    }
+}
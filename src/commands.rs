@@ -0,0 +1,267 @@
+//! Reversible editor operations and the undo/redo history.
+//! Every edit the preview makes to the scene AST goes through a `Command`, so
+//! it can be reverted and re-applied in order.
+
+use crate::ast::{GemDecl, GemFile, Property, Value};
+use crate::draw_list::NodePath;
+
+/// Walk child indices from the root
+pub fn find_node<'a>(root: &'a GemDecl, path: &[usize]) -> Option<&'a GemDecl> {
+    path.iter().try_fold(root, |decl, i| decl.children.get(*i))
+}
+
+pub fn find_node_mut<'a>(root: &'a mut GemDecl, path: &[usize]) -> Option<&'a mut GemDecl> {
+    path.iter()
+        .try_fold(root, |decl, i| decl.children.get_mut(*i))
+}
+
+/// Replace a property's value, appending it when the node doesn't have it yet.
+/// `None` removes the property.
+pub fn set_property(decl: &mut GemDecl, key: &str, value: Option<Value>) {
+    let existing = decl.properties.iter().position(|p| p.key == key);
+    match (existing, value) {
+        (Some(i), Some(value)) => decl.properties[i].value = value,
+        (Some(i), None) => {
+            decl.properties.remove(i);
+        }
+        (None, Some(value)) => decl.properties.push(Property {
+            key: key.to_string(),
+            value,
+        }),
+        (None, None) => {}
+    }
+}
+
+pub fn get_property<'a>(decl: &'a GemDecl, key: &str) -> Option<&'a Value> {
+    decl.properties
+        .iter()
+        .find(|p| p.key == key)
+        .map(|p| &p.value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `None` on either side means the property is absent
+    SetProperty {
+        node: NodePath,
+        key: String,
+        old: Option<Value>,
+        new: Option<Value>,
+    },
+    /// A gizmo drag: `position` goes from `from` to `to`
+    MoveNode {
+        node: NodePath,
+        from: Option<Value>,
+        to: Value,
+    },
+    AddChild {
+        parent: NodePath,
+        index: usize,
+        child: GemDecl,
+    },
+    /// `child` is captured when the command is first applied
+    RemoveChild {
+        parent: NodePath,
+        index: usize,
+        child: Option<GemDecl>,
+    },
+}
+
+fn node_mut<'a>(scene: &'a mut GemFile, path: &[usize]) -> Result<&'a mut GemDecl, String> {
+    find_node_mut(&mut scene.root, path).ok_or_else(|| format!("No node at {:?}", path))
+}
+
+fn insert_child(
+    scene: &mut GemFile,
+    parent: &[usize],
+    index: usize,
+    child: GemDecl,
+) -> Result<(), String> {
+    let decl = node_mut(scene, parent)?;
+    if index > decl.children.len() {
+        return Err(format!(
+            "Child index {} out of range for {}",
+            index, decl.name
+        ));
+    }
+    decl.children.insert(index, child);
+    Ok(())
+}
+
+fn take_child(scene: &mut GemFile, parent: &[usize], index: usize) -> Result<GemDecl, String> {
+    let decl = node_mut(scene, parent)?;
+    if index >= decl.children.len() {
+        return Err(format!("{} has no child {}", decl.name, index));
+    }
+    Ok(decl.children.remove(index))
+}
+
+impl Command {
+    pub fn apply(&mut self, scene: &mut GemFile) -> Result<(), String> {
+        match self {
+            Command::SetProperty { node, key, new, .. } => {
+                set_property(node_mut(scene, node)?, key, new.clone());
+            }
+            Command::MoveNode { node, to, .. } => {
+                set_property(node_mut(scene, node)?, "position", Some(to.clone()));
+            }
+            Command::AddChild {
+                parent,
+                index,
+                child,
+            } => insert_child(scene, parent, *index, child.clone())?,
+            Command::RemoveChild {
+                parent,
+                index,
+                child,
+            } => *child = Some(take_child(scene, parent, *index)?),
+        }
+        Ok(())
+    }
+
+    pub fn revert(&mut self, scene: &mut GemFile) -> Result<(), String> {
+        match self {
+            Command::SetProperty { node, key, old, .. } => {
+                set_property(node_mut(scene, node)?, key, old.clone());
+            }
+            Command::MoveNode { node, from, .. } => {
+                set_property(node_mut(scene, node)?, "position", from.clone());
+            }
+            Command::AddChild { parent, index, .. } => {
+                take_child(scene, parent, *index)?;
+            }
+            Command::RemoveChild {
+                parent,
+                index,
+                child,
+            } => {
+                let child = child
+                    .clone()
+                    .ok_or_else(|| "RemoveChild was never applied".to_string())?;
+                insert_child(scene, parent, *index, child)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Linear history: executing a new command drops everything that was undone
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    done: Vec<Command>,
+    undone: Vec<Command>,
+}
+
+impl UndoStack {
+    /// Apply `cmd` and record it
+    pub fn execute(&mut self, scene: &mut GemFile, mut cmd: Command) -> Result<(), String> {
+        cmd.apply(scene)?;
+        self.record(cmd);
+        Ok(())
+    }
+
+    /// Record a command whose effect is already in the scene (e.g. a finished drag)
+    pub fn record(&mut self, cmd: Command) {
+        self.done.push(cmd);
+        self.undone.clear();
+    }
+
+    /// Returns whether anything was undone
+    pub fn undo(&mut self, scene: &mut GemFile) -> Result<bool, String> {
+        let Some(mut cmd) = self.done.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = cmd.revert(scene) {
+            self.done.push(cmd);
+            return Err(e);
+        }
+        self.undone.push(cmd);
+        Ok(true)
+    }
+
+    /// Returns whether anything was redone
+    pub fn redo(&mut self, scene: &mut GemFile) -> Result<bool, String> {
+        let Some(mut cmd) = self.undone.pop() else {
+            return Ok(false);
+        };
+        if let Err(e) = cmd.apply(scene) {
+            self.undone.push(cmd);
+            return Err(e);
+        }
+        self.done.push(cmd);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn undo_and_redo_each_command() {
+        let src = r#"Root: Gem {
+            A: LabelGem { text: "a" }
+            B: LabelGem { text: "b" }
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let mut scene = Parser::new(tokens).parse_scene().unwrap();
+        let original = scene.clone();
+        let mut history = UndoStack::default();
+
+        let commands = vec![
+            Command::SetProperty {
+                node: vec![0],
+                key: "text".to_string(),
+                old: Some(Value::String("a".to_string())),
+                new: Some(Value::String("changed".to_string())),
+            },
+            Command::MoveNode {
+                node: vec![1],
+                from: None,
+                to: Value::Tuple(vec![Value::Integer(5), Value::Integer(6)]),
+            },
+            Command::RemoveChild {
+                parent: vec![],
+                index: 0,
+                child: None,
+            },
+            Command::AddChild {
+                parent: vec![],
+                index: 1,
+                child: scene.root.children[0].clone(),
+            },
+        ];
+        for cmd in commands {
+            history.execute(&mut scene, cmd).unwrap();
+        }
+        let edited = scene.clone();
+        let names: Vec<&str> = edited
+            .root
+            .children
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["B", "A"]);
+        assert_eq!(
+            get_property(&edited.root.children[1], "text"),
+            Some(&Value::String("a".to_string()))
+        );
+
+        while history.undo(&mut scene).unwrap() {}
+        assert_eq!(scene, original);
+        while history.redo(&mut scene).unwrap() {}
+        assert_eq!(scene, edited);
+
+        // A new edit clears the redo side
+        history.undo(&mut scene).unwrap();
+        history.record(Command::SetProperty {
+            node: vec![],
+            key: "tag".to_string(),
+            old: None,
+            new: None,
+        });
+        assert!(!history.redo(&mut scene).unwrap());
+    }
+}
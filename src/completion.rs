@@ -0,0 +1,246 @@
+//! Completions for editors, for `gem complete <file> <offset>`.
+//! Given a scene's source and a byte offset, suggests what fits there: Gem
+//! classes after a node's `Name:`, the enclosing class's properties (those
+//! not yet set) where a property can start, and the folders and files of
+//! `res://` inside a directive. The word under the cursor narrows them down.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::class_defs;
+use crate::lexer::{self, Class, Lexeme};
+use crate::token::Token;
+use crate::vfs;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Class,
+    Property,
+    Folder,
+    File,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Class => "class",
+            Kind::Property => "property",
+            Kind::Folder => "folder",
+            Kind::File => "file",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub label: String,
+    pub kind: Kind,
+    /// A property's types and default, e.g. `Vec2, default (260.0, 40.0)`
+    pub detail: String,
+}
+
+/// What a brace opened
+enum Frame {
+    /// A node of `class`, with the keys it sets so far
+    Node { class: String, keys: Vec<String> },
+    /// A struct, map, tuple or array value
+    Value,
+}
+
+/// Suggestions at byte `offset` of `source`; none where nothing is known to
+/// fit, such as in a property's value or a file that doesn't lex
+pub fn at(source: &str, offset: usize) -> Vec<Suggestion> {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let Ok(lexemes) = lexer::lex_with_trivia(&source[..offset]) else {
+        return Vec::new();
+    };
+    // The word the cursor is at the end of, if any
+    let typed = lexemes
+        .last()
+        .filter(|l| is_word(l))
+        .map_or(0, |l| l.range.len());
+    let prefix = &source[offset - typed..offset];
+    let before = &lexemes[..lexemes.len() - usize::from(typed > 0)];
+
+    if lexemes.last().is_some_and(|l| l.class == Class::Directive) {
+        return paths(source, &lexemes, offset);
+    }
+
+    let tokens: Vec<&Token> = before
+        .iter()
+        .filter(|l| !matches!(l.class, Class::Whitespace | Class::Comment))
+        .filter_map(|l| l.token.as_ref())
+        .collect();
+    let mut frames = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LBrace => frames.push(match i.checked_sub(1).map(|j| tokens[j]) {
+                Some(Token::Ident(class)) if class.starts_with(char::is_uppercase) => Frame::Node {
+                    class: class.clone(),
+                    keys: Vec::new(),
+                },
+                _ => Frame::Value,
+            }),
+            Token::LParen | Token::LBracket => frames.push(Frame::Value),
+            Token::RBrace | Token::RParen | Token::RBracket => {
+                frames.pop();
+            }
+            Token::Ident(key) if tokens.get(i + 1) == Some(&&Token::Colon) => {
+                if let Some(Frame::Node { keys, .. }) = frames.last_mut() {
+                    keys.push(key.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let last = tokens.last().copied();
+    let name_before = matches!(
+        tokens.len().checked_sub(2).map(|i| tokens[i]),
+        Some(Token::Ident(name)) if name.starts_with(char::is_uppercase)
+    );
+    if last == Some(&Token::Colon) && name_before {
+        return classes(prefix);
+    }
+    let at_entry = !matches!(
+        last,
+        Some(
+            Token::Colon
+                | Token::Dot
+                | Token::Hash
+                | Token::Dollar
+                | Token::Percent
+                | Token::At
+                | Token::Plus
+                | Token::Minus
+                | Token::Multiply
+                | Token::Divide
+        )
+    );
+    match frames.last() {
+        Some(Frame::Node { class, keys }) if at_entry => properties(class, keys, prefix),
+        _ => Vec::new(),
+    }
+}
+
+/// Names, keywords and the segments of a directive
+fn is_word(lexeme: &Lexeme) -> bool {
+    match &lexeme.token {
+        Some(Token::Ident(_) | Token::Bool(_)) => true,
+        Some(token) => token.keyword().is_some(),
+        None => false,
+    }
+}
+
+fn classes(prefix: &str) -> Vec<Suggestion> {
+    class_defs::all()
+        .iter()
+        .filter(|c| c.name.starts_with(prefix))
+        .map(|c| Suggestion {
+            label: c.name.to_string(),
+            kind: Kind::Class,
+            detail: String::new(),
+        })
+        .collect()
+}
+
+/// `class`'s properties other than those in `set`; every class that isn't
+/// built in is at least a `Gem`
+fn properties(class: &str, set: &[String], prefix: &str) -> Vec<Suggestion> {
+    let def = class_defs::find(class).or_else(|| class_defs::find("Gem"));
+    let mut seen = BTreeSet::new();
+    def.into_iter()
+        .flat_map(|def| def.all_properties())
+        // A class's own declaration comes before the `Gem` one it overrides
+        .filter(|p| seen.insert(p.name))
+        .filter(|p| p.name.starts_with(prefix) && !set.iter().any(|k| k == p.name))
+        .map(|p| {
+            let types: Vec<String> = p.types.iter().map(|t| format!("{:?}", t)).collect();
+            let mut detail = types.join(" or ");
+            if let Some(default) = p.default {
+                detail.push_str(&format!(", default {}", default.to_prop_value()));
+            }
+            Suggestion {
+                label: p.name.to_string(),
+                kind: Kind::Property,
+                detail,
+            }
+        })
+        .collect()
+}
+
+/// The folders and files that can follow the directive ending at `offset`
+fn paths(source: &str, lexemes: &[Lexeme], offset: usize) -> Vec<Suggestion> {
+    let Some(hash) = lexemes.iter().rposition(|l| l.token == Some(Token::Hash)) else {
+        return Vec::new();
+    };
+    let written = &source[lexemes[hash].range.end..offset];
+    let mut segments: Vec<&str> = written.split(':').collect();
+    let prefix = segments.pop().unwrap_or_default();
+    let dir = segments.join("/");
+    let Ok(files) = vfs::list(&dir) else {
+        return Vec::new();
+    };
+    let mut entries = BTreeMap::new();
+    for file in &files {
+        let Ok(rest) = file.strip_prefix(Path::new(&dir)) else {
+            continue;
+        };
+        let mut parts = rest.iter();
+        if let Some(name) = parts.next().and_then(|n| n.to_str())
+            && name.starts_with(prefix)
+            && !name.starts_with('.')
+        {
+            let kind = match parts.next() {
+                Some(_) => Kind::Folder,
+                None => Kind::File,
+            };
+            entries.insert(name.to_string(), kind);
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(label, kind)| Suggestion {
+            label,
+            kind,
+            detail: String::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(source: &str, at_marker: &str) -> Vec<String> {
+        let offset = source.find(at_marker).unwrap() + at_marker.len();
+        at(source, offset).into_iter().map(|s| s.label).collect()
+    }
+
+    #[test]
+    fn suggestions_follow_the_context() {
+        let src = "Root: Gem {\n    visible: true\n    Title: LabelGem { te }\n    Menu: Pan\n    b\n    pos: #example:ma\n}";
+        assert_eq!(labels(src, "{ te"), ["texture", "text"]);
+        assert_eq!(labels(src, "Menu: Pan"), ["PanelGem"]);
+        let root = labels(src, "\n    b");
+        assert!(root.contains(&"blend_mode".to_string()));
+        assert!(!root.iter().any(|l| !l.starts_with('b')));
+        assert!(labels(src, "Root: Gem {\n").contains(&"position".to_string()));
+        assert_eq!(
+            labels("A: Gem { visible: true vi", "true vi"),
+            ["visibility_layer"]
+        );
+        // Nothing fits a property's value
+        assert!(labels(src, "visible: ").is_empty());
+        assert!(labels(src, "#example:ma").contains(&"main_scene.gem".to_string()));
+
+        let label = at("A: LabelGem { color", 19).remove(0);
+        assert_eq!(label.detail, "Color or List, default #b333cc");
+        let dirs = at("A: Gem { link: #exa }", 19);
+        assert_eq!(dirs[0].label, "example");
+        assert_eq!(dirs[0].kind, Kind::Folder);
+    }
+}
@@ -0,0 +1,413 @@
+//! Box and grid containers: `VBoxGem`, `HBoxGem` and `GridGem` place their
+//! children, replacing the children's own `position` and `size`.
+//! Along the container's axis each child first gets its own size, clamped
+//! to `min_size` / `max_size`; the space left over goes to children flagged
+//! `expand`, in proportion to their `stretch_ratio`, without growing any
+//! past its `max_size`. Within its slot a child fills the space unless a
+//! `shrink_*` flag keeps its own size and aligns it. A child's `margin` is
+//! kept clear around it inside its slot; the container's own `padding` is
+//! applied by the caller before arranging.
+
+/// Gem types whose children are laid out here
+pub const TYPES: [&str; 3] = ["VBoxGem", "HBoxGem", "GridGem"];
+
+/// Gap between children when the container has no `separation`
+pub const DEFAULT_SEPARATION: f32 = 4.0;
+
+/// Top-left corner and size
+pub type Rect = ((f32, f32), (f32, f32));
+
+/// Space inside a rect's edges: `padding` and `margin`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Insets {
+    pub fn uniform(v: f32) -> Self {
+        Self {
+            top: v,
+            right: v,
+            bottom: v,
+            left: v,
+        }
+    }
+
+    /// `rect` with the insets taken off its edges, never below zero size
+    pub fn shrink(self, rect: Rect) -> Rect {
+        let ((x, y), (w, h)) = rect;
+        (
+            (x + self.left, y + self.top),
+            (
+                (w - self.left - self.right).max(0.0),
+                (h - self.top - self.bottom).max(0.0),
+            ),
+        )
+    }
+
+    /// `size` with room for the insets added
+    fn grow(self, size: (f32, f32)) -> (f32, f32) {
+        (
+            size.0 + self.left + self.right,
+            size.1 + self.top + self.bottom,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Align {
+    #[default]
+    Begin,
+    Center,
+    End,
+}
+
+/// One axis of a child's `size_flags_horizontal` / `size_flags_vertical`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeFlags {
+    /// Grow to the slot's size (up to `max_size`)
+    pub fill: bool,
+    /// Take a share of the container's leftover space
+    pub expand: bool,
+    /// Where the child sits when it is smaller than its slot
+    pub align: Align,
+}
+
+impl Default for SizeFlags {
+    fn default() -> Self {
+        Self {
+            fill: true,
+            expand: false,
+            align: Align::Begin,
+        }
+    }
+}
+
+impl SizeFlags {
+    /// From flag names: `fill`, `expand`, `shrink_begin`, `shrink_center`,
+    /// `shrink_end`. A child fills its slot unless only `shrink_*` is given.
+    pub fn parse(names: &[&str]) -> Result<Self, String> {
+        let mut flags = Self {
+            fill: false,
+            ..Self::default()
+        };
+        let mut shrink = false;
+        for name in names {
+            match *name {
+                "fill" => flags.fill = true,
+                "expand" => flags.expand = true,
+                "shrink_begin" | "shrink_center" | "shrink_end" => {
+                    shrink = true;
+                    flags.align = match *name {
+                        "shrink_begin" => Align::Begin,
+                        "shrink_center" => Align::Center,
+                        _ => Align::End,
+                    };
+                }
+                other => {
+                    return Err(format!(
+                        "unknown size flag {}, expected fill, expand, shrink_begin, shrink_center or shrink_end",
+                        other
+                    ));
+                }
+            }
+        }
+        flags.fill |= !shrink;
+        Ok(flags)
+    }
+}
+
+/// A child as its container sees it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Item {
+    /// Its own size, already clamped to `min_size` / `max_size`
+    pub size: (f32, f32),
+    /// `max_size`, or infinity on an axis without one
+    pub max: (f32, f32),
+    pub horizontal: SizeFlags,
+    pub vertical: SizeFlags,
+    pub stretch_ratio: f32,
+    pub margin: Insets,
+}
+
+impl Item {
+    fn flags(&self, axis: usize) -> SizeFlags {
+        if axis == 0 {
+            self.horizontal
+        } else {
+            self.vertical
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    VBox,
+    HBox,
+    /// Children fill rows left to right, `columns` per row
+    Grid {
+        columns: usize,
+    },
+}
+
+/// Rects for `items` inside the container rect `(origin, size)`
+pub fn arrange(layout: Layout, rect: Rect, separation: f32, items: &[Item]) -> Vec<Rect> {
+    // Slots are laid out with each child's margin included
+    let outer: Vec<Item> = items
+        .iter()
+        .map(|i| Item {
+            size: i.margin.grow(i.size),
+            max: i.margin.grow(i.max),
+            ..*i
+        })
+        .collect();
+    let slots = match layout {
+        Layout::VBox => arrange_box(1, rect, separation, &outer),
+        Layout::HBox => arrange_box(0, rect, separation, &outer),
+        Layout::Grid { columns } => arrange_grid(columns.max(1), rect, separation, &outer),
+    };
+    slots
+        .into_iter()
+        .zip(items)
+        .map(|(slot, item)| item.margin.shrink(slot))
+        .collect()
+}
+
+fn get(v: (f32, f32), axis: usize) -> f32 {
+    if axis == 0 { v.0 } else { v.1 }
+}
+
+fn pair(axis: usize, along: f32, across: f32) -> (f32, f32) {
+    if axis == 0 {
+        (along, across)
+    } else {
+        (across, along)
+    }
+}
+
+fn arrange_box(axis: usize, rect: Rect, separation: f32, items: &[Item]) -> Vec<Rect> {
+    let (origin, size) = rect;
+    let cross = 1 - axis;
+    let mut slots: Vec<f32> = items.iter().map(|i| get(i.size, axis)).collect();
+    let gaps = separation * items.len().saturating_sub(1) as f32;
+    let extra = get(size, axis) - slots.iter().sum::<f32>() - gaps;
+    let shares: Vec<Option<(f32, f32)>> = items
+        .iter()
+        .map(|i| {
+            i.flags(axis)
+                .expand
+                .then_some((i.stretch_ratio, get(i.max, axis)))
+        })
+        .collect();
+    distribute(&mut slots, &shares, extra);
+
+    let mut at = get(origin, axis);
+    items
+        .iter()
+        .zip(slots)
+        .map(|(item, slot)| {
+            let (along_at, along) = place(
+                item.flags(axis),
+                slot,
+                get(item.size, axis),
+                get(item.max, axis),
+            );
+            let (across_at, across) = place(
+                item.flags(cross),
+                get(size, cross),
+                get(item.size, cross),
+                get(item.max, cross),
+            );
+            let placed = (
+                pair(axis, at + along_at, get(origin, cross) + across_at),
+                pair(axis, along, across),
+            );
+            at += slot + separation;
+            placed
+        })
+        .collect()
+}
+
+fn arrange_grid(columns: usize, rect: Rect, separation: f32, items: &[Item]) -> Vec<Rect> {
+    let (origin, size) = rect;
+    let rows = items.len().div_ceil(columns);
+    let cell = |i: usize| (i % columns, i / columns);
+    // Each track is as big as its biggest child, and expands if any child
+    // in it does, by the largest of their ratios
+    let mut tracks = [vec![0.0f32; columns], vec![0.0f32; rows]];
+    let mut shares: [Vec<Option<(f32, f32)>>; 2] = [vec![None; columns], vec![None; rows]];
+    for (i, item) in items.iter().enumerate() {
+        let (col, row) = cell(i);
+        for (axis, track) in [(0, col), (1, row)] {
+            tracks[axis][track] = tracks[axis][track].max(get(item.size, axis));
+            if item.flags(axis).expand {
+                let ratio = shares[axis][track].map_or(0.0, |s| s.0);
+                shares[axis][track] = Some((ratio.max(item.stretch_ratio), f32::INFINITY));
+            }
+        }
+    }
+    for axis in 0..2 {
+        let gaps = separation * tracks[axis].len().saturating_sub(1) as f32;
+        let extra = get(size, axis) - tracks[axis].iter().sum::<f32>() - gaps;
+        distribute(&mut tracks[axis], &shares[axis], extra);
+    }
+    let starts = |axis: usize| {
+        let mut at = get(origin, axis);
+        tracks[axis]
+            .iter()
+            .map(|t| {
+                let start = at;
+                at += t + separation;
+                start
+            })
+            .collect::<Vec<f32>>()
+    };
+    let (xs, ys) = (starts(0), starts(1));
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let (col, row) = cell(i);
+            let (x, w) = place(item.horizontal, tracks[0][col], item.size.0, item.max.0);
+            let (y, h) = place(item.vertical, tracks[1][row], item.size.1, item.max.1);
+            ((xs[col] + x, ys[row] + y), (w, h))
+        })
+        .collect()
+}
+
+/// Share `extra` among the slots with a `(stretch_ratio, max)`, in
+/// proportion to their ratios; a slot that reaches its max passes the rest
+/// of its share on to the others
+fn distribute(slots: &mut [f32], shares: &[Option<(f32, f32)>], mut extra: f32) {
+    let mut open: Vec<usize> = (0..slots.len())
+        .filter(|&i| shares[i].is_some_and(|(ratio, max)| ratio > 0.0 && slots[i] < max))
+        .collect();
+    while extra > 0.0 && !open.is_empty() {
+        let total: f32 = open.iter().filter_map(|&i| shares[i]).map(|s| s.0).sum();
+        let mut given = 0.0;
+        for &i in &open {
+            let (ratio, max) = shares[i].unwrap_or_default();
+            let add = (extra * ratio / total).min(max - slots[i]);
+            slots[i] += add;
+            given += add;
+        }
+        extra -= given;
+        let before = open.len();
+        open.retain(|&i| shares[i].is_some_and(|(_, max)| slots[i] < max));
+        if open.len() == before {
+            break;
+        }
+    }
+}
+
+/// Offset and extent of a child of natural size `size` in a slot
+fn place(flags: SizeFlags, slot: f32, size: f32, max: f32) -> (f32, f32) {
+    let extent = if flags.fill {
+        slot.min(max).max(size)
+    } else {
+        size
+    };
+    let offset = match flags.align {
+        Align::Begin => 0.0,
+        Align::Center => (slot - extent) / 2.0,
+        Align::End => slot - extent,
+    };
+    (offset.max(0.0), extent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(w: f32, h: f32) -> Item {
+        Item {
+            size: (w, h),
+            max: (f32::INFINITY, f32::INFINITY),
+            horizontal: SizeFlags::default(),
+            vertical: SizeFlags::default(),
+            stretch_ratio: 1.0,
+            margin: Insets::default(),
+        }
+    }
+
+    fn expanding(w: f32, h: f32, ratio: f32) -> Item {
+        Item {
+            vertical: SizeFlags::parse(&["expand"]).unwrap(),
+            stretch_ratio: ratio,
+            ..item(w, h)
+        }
+    }
+
+    #[test]
+    fn boxes_share_leftover_space_by_ratio_and_max() {
+        // 300 tall: 20 + 10 gap + 20 + 10 gap + 20 leaves 220 for the expanders
+        let capped = Item {
+            max: (f32::INFINITY, 60.0),
+            ..expanding(50.0, 20.0, 3.0)
+        };
+        let rects = arrange(
+            Layout::VBox,
+            ((10.0, 0.0), (100.0, 300.0)),
+            10.0,
+            &[item(50.0, 20.0), expanding(50.0, 20.0, 1.0), capped],
+        );
+        assert_eq!(rects[0], ((10.0, 0.0), (100.0, 20.0)));
+        // The capped child takes 40 of its 165 share; the rest goes to the other
+        assert_eq!(rects[1], ((10.0, 30.0), (100.0, 200.0)));
+        assert_eq!(rects[2], ((10.0, 240.0), (100.0, 60.0)));
+
+        let centered = Item {
+            horizontal: SizeFlags::parse(&["expand", "shrink_center"]).unwrap(),
+            ..item(40.0, 10.0)
+        };
+        let rects = arrange(
+            Layout::HBox,
+            ((0.0, 0.0), (200.0, 30.0)),
+            0.0,
+            &[item(60.0, 10.0), centered],
+        );
+        assert_eq!(rects[0], ((0.0, 0.0), (60.0, 30.0)));
+        assert_eq!(rects[1], ((110.0, 0.0), (40.0, 30.0)));
+        assert!(SizeFlags::parse(&["grow"]).is_err());
+
+        // Margins are kept clear inside the slot, which grows to hold them
+        let spaced = Item {
+            margin: Insets {
+                top: 5.0,
+                right: 0.0,
+                bottom: 5.0,
+                left: 10.0,
+            },
+            ..item(40.0, 10.0)
+        };
+        let rects = arrange(
+            Layout::VBox,
+            ((0.0, 0.0), (100.0, 100.0)),
+            0.0,
+            &[spaced, item(40.0, 10.0)],
+        );
+        assert_eq!(rects[0], ((10.0, 5.0), (90.0, 10.0)));
+        assert_eq!(rects[1], ((0.0, 20.0), (100.0, 10.0)));
+    }
+
+    #[test]
+    fn grids_size_tracks_by_their_largest_child() {
+        let wide = Item {
+            horizontal: SizeFlags::parse(&["expand"]).unwrap(),
+            ..item(30.0, 10.0)
+        };
+        let rects = arrange(
+            Layout::Grid { columns: 2 },
+            ((0.0, 0.0), (100.0, 100.0)),
+            0.0,
+            &[item(20.0, 10.0), wide, item(40.0, 30.0)],
+        );
+        // Column 0 is 40 wide (its widest child); column 1 expands to the rest
+        assert_eq!(rects[0], ((0.0, 0.0), (40.0, 10.0)));
+        assert_eq!(rects[1], ((40.0, 0.0), (60.0, 10.0)));
+        assert_eq!(rects[2], ((0.0, 10.0), (40.0, 30.0)));
+    }
+}
@@ -0,0 +1,184 @@
+//! Recovery layer for the preview runtime.
+//! Frame updates, input delivery and script callbacks run inside `guard`: a
+//! panic is caught instead of unwinding out of the event loop, and comes back
+//! as a `Crash` naming the node whose handler was running and, through the
+//! scene's source map, the line that declares it. The preview shows it in an
+//! `ErrorOverlay` and stops updating the scene until it is reloaded.
+
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::gem;
+use crate::object::ObjectRef;
+use crate::scene_tree;
+use crate::source_map::SourceMap;
+use crate::value::Value;
+
+/// A failure to show in the preview instead of exiting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crash {
+    /// What failed, e.g. "Script panic" or "Shader error"
+    pub title: String,
+    pub message: String,
+    /// Tree path of the node whose handler was running
+    pub node: Option<String>,
+    /// `file:line` declaring that node, when the scene's source map has it
+    pub location: Option<String>,
+}
+
+impl Crash {
+    pub fn new(title: &str, message: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            node: None,
+            location: None,
+        }
+    }
+
+    /// The whole report, as printed to stderr and drawn in the overlay
+    pub fn report(&self) -> String {
+        let mut out = format!("{}: {}", self.title, self.message);
+        if let Some(node) = &self.node {
+            out.push_str(&format!("\n  in {}", node));
+        }
+        if let Some(location) = &self.location {
+            out.push_str(&format!("\n  at {}", location));
+        }
+        out
+    }
+}
+
+thread_local! {
+    /// How many `guard` calls are running on this thread
+    static GUARDS: Cell<usize> = const { Cell::new(0) };
+    /// Node whose handler is running, innermost last
+    static RUNNING: RefCell<Vec<ObjectRef>> = const { RefCell::new(Vec::new()) };
+    /// Message and Rust location of the last panic caught on this thread
+    static PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// Replace the default panic output: panics inside `guard` are reported as
+/// crashes, so only the message and where it was raised are kept
+pub fn install_panic_hook() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if GUARDS.get() == 0 {
+            return default(info);
+        }
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        PANIC.with(|p| *p.borrow_mut() = Some((message, location)));
+    }));
+}
+
+/// Run `f`, turning a panic into a `Crash` located with `map`
+pub fn guard<R>(map: &SourceMap, f: impl FnOnce() -> R) -> Result<R, Crash> {
+    let depth = RUNNING.with(|r| r.borrow().len());
+    GUARDS.set(GUARDS.get() + 1);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    GUARDS.set(GUARDS.get() - 1);
+    let node = RUNNING.with(|r| {
+        let mut running = r.borrow_mut();
+        let node = running.last().cloned();
+        running.truncate(depth);
+        node
+    });
+    result.map_err(|payload| {
+        let (message, origin) = PANIC.with(|p| p.borrow_mut().take()).unwrap_or_else(|| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "panic".to_string());
+            (message, String::new())
+        });
+        let mut crash = Crash::new("Script panic", &message);
+        if !origin.is_empty() {
+            crash.message.push_str(&format!(" (raised at {})", origin));
+        }
+        if let Some(node) = node {
+            crash.node = Some(gem::get_path(&node));
+            crash.location = scene_names(&node).and_then(|names| map.locate(&names));
+        }
+        crash
+    })
+}
+
+/// Mark `node` as running for the duration of `f`, so a panic inside it is
+/// blamed on the node
+pub fn running<R>(node: &ObjectRef, f: impl FnOnce() -> R) -> R {
+    RUNNING.with(|r| r.borrow_mut().push(node.clone()));
+    let result = f();
+    RUNNING.with(|r| r.borrow_mut().pop());
+    result
+}
+
+/// Names from the current scene's root down to `node`
+fn scene_names(node: &ObjectRef) -> Option<Vec<String>> {
+    let scene = scene_tree::current_scene()?;
+    let mut names = Vec::new();
+    let mut cur = node.clone();
+    loop {
+        match cur.get_property("name") {
+            Some(Value::String(name)) => names.push(name),
+            _ => return None,
+        }
+        if cur.id() == scene.id() {
+            break;
+        }
+        cur = gem::parent_of(&cur)?;
+    }
+    names.reverse();
+    Some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    #[test]
+    fn panics_in_handlers_become_located_crashes() {
+        let _scene = scene_tree::CURRENT_SCENE_TESTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        install_panic_hook();
+        let source = "Main: Gem {\n    Player: Gem {}\n}";
+        let scene = pipeline::lex_source(source)
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let map = SourceMap::new("main.gem", source, &scene);
+        let root = scene_tree::change_scene(&scene).unwrap();
+        let player = gem::children_of(&root)[0].clone();
+
+        assert_eq!(guard(&map, || 7), Ok(7));
+        let crash = guard(&map, || {
+            running(&player, || -> i32 { panic!("speed is {}", "NaN") })
+        })
+        .unwrap_err();
+        assert_eq!(crash.title, "Script panic");
+        assert!(crash.message.starts_with("speed is NaN"));
+        assert!(
+            crash
+                .node
+                .as_deref()
+                .is_some_and(|p| p.ends_with("/Main/Player"))
+        );
+        assert_eq!(crash.location.as_deref(), Some("main.gem:2"));
+        // The running stack was unwound with the panic
+        assert!(
+            guard(&map, || -> i32 { panic!("again") })
+                .unwrap_err()
+                .node
+                .is_none()
+        );
+    }
+}
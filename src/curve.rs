@@ -0,0 +1,187 @@
+//! Animation curves: `Curve((t, value, easing), ...)` property values.
+//! Points are kept in ascending `t`; a point's easing shapes the segment
+//! from it to the next point, and sampling outside the points holds the
+//! first or last value. At runtime a curve is a `Curve` object whose
+//! `sample(t)` method evaluates it.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::easing;
+use crate::object::{MethodTable, ObjectInner, ObjectRef, define_method};
+use crate::value::Value;
+
+/// Shape of the segment after a curve point
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Holds the point's value until the next point
+    Constant,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Easing::Linear),
+            "constant" => Some(Easing::Constant),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::Constant => "constant",
+            Easing::EaseIn => "ease_in",
+            Easing::EaseOut => "ease_out",
+            Easing::EaseInOut => "ease_in_out",
+        }
+    }
+
+    /// Progress through a segment, `x` in 0..=1, mapped to 0..=1; the
+    /// eased shapes are the quadratic ones from `easing`
+    pub fn apply(self, x: f64) -> f64 {
+        let name = match self {
+            Easing::Constant => return if x < 1.0 { 0.0 } else { 1.0 },
+            Easing::Linear => "linear",
+            Easing::EaseIn => "in_quad",
+            Easing::EaseOut => "out_quad",
+            Easing::EaseInOut => "in_out_quad",
+        };
+        easing::ease(x, name).unwrap_or(x)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurvePoint {
+    pub t: f64,
+    pub value: f64,
+    pub easing: Easing,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Curve {
+    pub points: Vec<CurvePoint>,
+}
+
+impl Curve {
+    /// A curve through `points`, which must be non-empty and in ascending `t`
+    pub fn new(points: Vec<CurvePoint>) -> Result<Self, String> {
+        if points.is_empty() {
+            return Err("Curve needs at least one point".to_string());
+        }
+        if points.windows(2).any(|w| w[1].t < w[0].t) {
+            return Err("Curve points must be in ascending t order".to_string());
+        }
+        Ok(Self { points })
+    }
+
+    pub fn sample(&self, t: f64) -> f64 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0.0;
+        };
+        if t <= first.t {
+            return first.value;
+        }
+        if t >= last.t {
+            return last.value;
+        }
+        let i = self.points.partition_point(|p| p.t <= t) - 1;
+        let (a, b) = (&self.points[i], &self.points[i + 1]);
+        let x = (t - a.t) / (b.t - a.t);
+        a.value + (b.value - a.value) * a.easing.apply(x)
+    }
+}
+
+/// The runtime `Curve` object holding `curve`
+pub fn handle(curve: Curve) -> ObjectRef {
+    let obj = ObjectInner::with_methods("Curve", curve_methods());
+    obj.extension::<OnceLock<Curve>>().get_or_init(|| curve);
+    obj
+}
+
+/// The curve held by a `Curve` object
+pub fn of(handle: &ObjectRef) -> Option<&Curve> {
+    if handle.class_name() != "Curve" {
+        return None;
+    }
+    handle.extension::<OnceLock<Curve>>().get()
+}
+
+fn curve_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = MethodTable::new();
+            // sample(t): the curve's value at `t`
+            define_method(
+                &mut methods,
+                "sample",
+                Arc::new(|this, args| {
+                    let t = args
+                        .first()
+                        .and_then(Value::as_f64)
+                        .ok_or("sample expects a number")?;
+                    let curve = of(this).ok_or("sample called on an empty Curve")?;
+                    Ok(Value::Float(curve.sample(t)))
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_point_count",
+                Arc::new(|this, _| Ok(Value::Int(of(this).map_or(0, |c| c.points.len()) as i64))),
+            );
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(t: f64, value: f64, easing: Easing) -> CurvePoint {
+        CurvePoint { t, value, easing }
+    }
+
+    #[test]
+    fn samples_between_points_with_easing() {
+        let curve = Curve::new(vec![
+            point(0.0, 0.0, Easing::Linear),
+            point(0.5, 1.0, Easing::EaseIn),
+            point(1.0, 0.0, Easing::Constant),
+        ])
+        .unwrap();
+        assert_eq!(curve.sample(-1.0), 0.0);
+        assert_eq!(curve.sample(0.25), 0.5);
+        assert_eq!(curve.sample(0.5), 1.0);
+        // Halfway through an ease_in segment is a quarter of the way
+        assert_eq!(curve.sample(0.75), 0.75);
+        assert_eq!(curve.sample(2.0), 0.0);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(
+            Curve::new(vec![
+                point(1.0, 0.0, Easing::Linear),
+                point(0.0, 1.0, Easing::Linear)
+            ])
+            .is_err()
+        );
+
+        let obj = handle(curve);
+        assert_eq!(
+            obj.call_method("sample", &[Value::Float(0.25)]).unwrap(),
+            Value::Float(0.5)
+        );
+        assert_eq!(
+            obj.call_method("get_point_count", &[]).unwrap(),
+            Value::Int(3)
+        );
+    }
+}
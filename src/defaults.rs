@@ -0,0 +1,83 @@
+//! Assets built into the engine: a default font, a "missing texture"
+//! placeholder and the default theme. They are served as `res://engine/...`
+//! files below the project directory, so a project can override any of them
+//! by shipping a file at the same path. When a scene's font or texture can't
+//! be found, the draw list warns and substitutes these instead of drawing
+//! nothing.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::ast;
+use crate::pipeline;
+use crate::vfs::{self, Source};
+
+/// Cantarell Regular (SIL Open Font License; the license is in its name table)
+pub const FONT_PATH: &str = "engine/Cantarell-Regular.ttf";
+/// Magenta and black checkerboard
+pub const MISSING_TEXTURE_PATH: &str = "engine/missing_texture.svg";
+/// See `defaults/theme.gem`
+pub const THEME_PATH: &str = "engine/theme.gem";
+
+const FILES: [(&str, &[u8]); 3] = [
+    (FONT_PATH, include_bytes!("defaults/Cantarell-Regular.ttf")),
+    (
+        MISSING_TEXTURE_PATH,
+        include_bytes!("defaults/missing_texture.svg"),
+    ),
+    (THEME_PATH, include_bytes!("defaults/theme.gem")),
+];
+
+/// The built-in files, as the last `res://` source
+pub struct EmbeddedSource;
+
+impl Source for EmbeddedSource {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        FILES
+            .iter()
+            .find(|(p, _)| Path::new(p) == path)
+            .map(|(_, data)| data.to_vec())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        FILES.iter().any(|(p, _)| Path::new(p) == path)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        FILES
+            .iter()
+            .map(|(p, _)| PathBuf::from(p))
+            .filter(|p| p.starts_with(dir))
+            .collect()
+    }
+}
+
+/// The theme's root Gem, read once; a broken project theme falls back to the
+/// built-in one
+pub fn theme() -> &'static ast::GemDecl {
+    static THEME: OnceLock<ast::GemFile> = OnceLock::new();
+    let parse = |src: &str| pipeline::lex_source(src).and_then(pipeline::parse_scene);
+    &THEME
+        .get_or_init(|| {
+            vfs::read_to_string(THEME_PATH)
+                .and_then(|src| parse(&src))
+                .unwrap_or_else(|e| {
+                    eprintln!("[WARN] {}: {}; using the built-in theme", THEME_PATH, e);
+                    let builtin = include_str!("defaults/theme.gem");
+                    parse(builtin).expect("built-in theme parses")
+                })
+        })
+        .root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_files_are_served_below_the_project() {
+        assert!(vfs::is_file(FONT_PATH));
+        assert!(vfs::read_to_string(MISSING_TEXTURE_PATH).is_ok_and(|s| s.contains("<svg")));
+        assert!(theme().children.iter().any(|c| c.name == "Default"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Preprocessor defines, given on the command line as `--define debug`
+//! (true) or `--define platform=web`. Every source is expanded with them as
+//! it's lexed, so scenes and logic files see the same ones:
+//!
+//! ```text
+//! #if platform == "web" && !debug {
+//!     Touch: TouchPadGem { }
+//! } #else #if platform == "console" {
+//!     Pad: GamepadHintGem { }
+//! } #else {
+//!     Keys: KeyHintGem { }
+//! }
+//! ```
+//!
+//! An `#if` keeps the tokens of the first block whose condition holds, or
+//! none. A condition is a constant expression over defines and literals, in
+//! which a name that isn't defined is false. `$name` is replaced by a
+//! define's value anywhere (`title: $platform`, `width: $tile * 4`); naming
+//! one that isn't defined is an error.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::ast::{BinOp, Expr, UnOp};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Token;
+
+/// Each define's value, as the literal token `$name` becomes
+pub type Defines = BTreeMap<String, Token>;
+
+static DEFINES: RwLock<Defines> = RwLock::new(BTreeMap::new());
+
+/// Make `defines` visible to every source lexed from now on
+pub fn set(defines: Defines) {
+    *DEFINES.write().unwrap() = defines;
+}
+
+/// `tokens` expanded with the defines last `set`
+pub fn apply(tokens: &[Token]) -> Result<Vec<Token>, String> {
+    expand(tokens, &DEFINES.read().unwrap())
+}
+
+/// Remove the `--define name[=value]` pairs from `args`, returning the
+/// defines they give. A value is a bool, a number or else a string.
+pub fn take_flags(args: &mut Vec<String>) -> Result<Defines, String> {
+    let mut defines = Defines::new();
+    let mut rest = Vec::new();
+    let mut args_iter = std::mem::take(args).into_iter();
+    while let Some(arg) = args_iter.next() {
+        if arg != "--define" {
+            rest.push(arg);
+            continue;
+        }
+        let Some(define) = args_iter.next() else {
+            return Err("--define expects name or name=value".into());
+        };
+        let (name, value) = match define.split_once('=') {
+            Some((name, value)) => (name, parse_value(value)),
+            None => (define.as_str(), Token::Bool(true)),
+        };
+        let tokens = Lexer::new(name.to_string()).tokenize();
+        if !matches!(tokens.as_deref(), Ok([Token::Ident(_)])) {
+            return Err(format!("--define: '{}' isn't a name", name));
+        }
+        defines.insert(name.to_string(), value);
+    }
+    *args = rest;
+    Ok(defines)
+}
+
+fn parse_value(text: &str) -> Token {
+    let numeric = text.starts_with(|c: char| c.is_ascii_digit() || c == '-');
+    match text {
+        "true" => Token::Bool(true),
+        "false" => Token::Bool(false),
+        _ if numeric && text.parse::<i64>().is_ok() => Token::Integer(text.parse().unwrap()),
+        _ if numeric && text.parse::<f64>().is_ok() => Token::Float(text.parse().unwrap()),
+        _ => Token::String(text.to_string()),
+    }
+}
+
+/// Whether `source` has `#if` blocks, which printing its parsed form would
+/// flatten to the chosen branches
+pub fn has_conditionals(source: &str) -> bool {
+    Lexer::new(source.to_string())
+        .tokenize()
+        .is_ok_and(|tokens| {
+            tokens
+                .windows(2)
+                .any(|pair| matches!(pair, [Token::Hash, Token::If]))
+        })
+}
+
+/// `tokens` with each `$name` replaced and each `#if` chain reduced to the
+/// block it keeps
+pub fn expand(tokens: &[Token], defines: &Defines) -> Result<Vec<Token>, String> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        match (&tokens[i], tokens.get(i + 1)) {
+            (Token::Dollar, Some(Token::Ident(name))) => {
+                let value = defines.get(name).ok_or_else(|| {
+                    format!("'${}' isn't defined; pass --define {}=value", name, name)
+                })?;
+                out.push(value.clone());
+                i += 2;
+            }
+            (Token::Dollar, _) => return Err("Expected a define name after '$'".into()),
+            (Token::Hash, Some(Token::If)) => {
+                let (block, next) = conditional(tokens, i + 2, defines)?;
+                out.extend(expand(block, defines)?);
+                i = next;
+            }
+            (Token::Hash, Some(Token::Else)) => {
+                return Err("#else without an #if block before it".into());
+            }
+            (token, _) => {
+                out.push(token.clone());
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The block kept by the `#if` whose condition starts at `start` (empty
+/// when none is), and where the tokens after its `#else` chain begin
+fn conditional<'a>(
+    tokens: &'a [Token],
+    start: usize,
+    defines: &Defines,
+) -> Result<(&'a [Token], usize), String> {
+    let open = tokens[start..]
+        .iter()
+        .position(|t| *t == Token::LBrace)
+        .map(|offset| start + offset)
+        .ok_or("Expected '{' after the #if condition")?;
+    let condition = expand(&tokens[start..open], defines)?;
+    if condition.is_empty() {
+        return Err("Expected a condition after #if".into());
+    }
+    let expr = Parser::new(condition)
+        .parse_standalone_expression()
+        .map_err(|e| format!("#if condition: {}", e.message))?;
+    let holds = match eval(&expr, defines)? {
+        Token::Bool(b) => b,
+        other => {
+            return Err(format!(
+                "#if condition must be true or false, got {}",
+                describe(&other)
+            ));
+        }
+    };
+    let close = matching_brace(tokens, open)?;
+    let then_block = &tokens[open + 1..close];
+    let mut else_block: &[Token] = &[];
+    let mut next = close + 1;
+    if let (Some(Token::Hash), Some(Token::Else)) = (tokens.get(next), tokens.get(next + 1)) {
+        match (tokens.get(next + 2), tokens.get(next + 3)) {
+            (Some(Token::LBrace), _) => {
+                let close = matching_brace(tokens, next + 2)?;
+                else_block = &tokens[next + 3..close];
+                next = close + 1;
+            }
+            (Some(Token::Hash), Some(Token::If)) => {
+                (else_block, next) = conditional(tokens, next + 4, defines)?;
+            }
+            _ => return Err("Expected '{' or #if after #else".into()),
+        }
+    }
+    Ok((if holds { then_block } else { else_block }, next))
+}
+
+fn matching_brace(tokens: &[Token], open: usize) -> Result<usize, String> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LBrace => depth += 1,
+            Token::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("Unclosed #if block".into())
+}
+
+fn eval(expr: &Expr, defines: &Defines) -> Result<Token, String> {
+    match expr {
+        Expr::Bool(b) => Ok(Token::Bool(*b)),
+        Expr::Integer(i) => Ok(Token::Integer(*i)),
+        Expr::Number(n) => Ok(Token::Float(*n)),
+        Expr::String(s) => Ok(Token::String(s.clone())),
+        Expr::Ident(name) => Ok(defines.get(name).cloned().unwrap_or(Token::Bool(false))),
+        Expr::UnaryOp { op, expr } => match (op, eval(expr, defines)?) {
+            (UnOp::Not, Token::Bool(b)) => Ok(Token::Bool(!b)),
+            (UnOp::Minus, Token::Integer(i)) => Ok(Token::Integer(-i)),
+            (UnOp::Minus, Token::Float(f)) => Ok(Token::Float(-f)),
+            (op, value) => Err(format!("Can't apply {:?} to {}", op, describe(&value))),
+        },
+        Expr::BinaryOp { op, left, right } => {
+            binary(op, eval(left, defines)?, eval(right, defines)?)
+        }
+        _ => Err(format!(
+            "#if conditions take defines and literals, not {:?}",
+            expr
+        )),
+    }
+}
+
+fn binary(op: &BinOp, left: Token, right: Token) -> Result<Token, String> {
+    let result = match (op, &left, &right) {
+        (BinOp::And, Token::Bool(a), Token::Bool(b)) => Some(Token::Bool(*a && *b)),
+        (BinOp::Or, Token::Bool(a), Token::Bool(b)) => Some(Token::Bool(*a || *b)),
+        (BinOp::Add, Token::String(a), Token::String(b)) => {
+            Some(Token::String(format!("{}{}", a, b)))
+        }
+        (BinOp::Eq | BinOp::NotEq, ..) => {
+            let equal = match (number(&left), number(&right)) {
+                (Some(a), Some(b)) => a == b,
+                _ => left == right,
+            };
+            Some(Token::Bool(equal == (*op == BinOp::Eq)))
+        }
+        (_, Token::Integer(a), Token::Integer(b)) => match op {
+            BinOp::Add => a.checked_add(*b).map(Token::Integer),
+            BinOp::Sub => a.checked_sub(*b).map(Token::Integer),
+            BinOp::Mul => a.checked_mul(*b).map(Token::Integer),
+            BinOp::Div => a.checked_div(*b).map(Token::Integer),
+            _ => compare(op, *a as f64, *b as f64),
+        },
+        _ => match (number(&left), number(&right)) {
+            (Some(a), Some(b)) => match op {
+                BinOp::Add => Some(Token::Float(a + b)),
+                BinOp::Sub => Some(Token::Float(a - b)),
+                BinOp::Mul => Some(Token::Float(a * b)),
+                BinOp::Div => Some(Token::Float(a / b)),
+                _ => compare(op, a, b),
+            },
+            _ => None,
+        },
+    };
+    result.ok_or_else(|| {
+        format!(
+            "Can't apply {:?} to {} and {}",
+            op,
+            describe(&left),
+            describe(&right)
+        )
+    })
+}
+
+fn compare(op: &BinOp, a: f64, b: f64) -> Option<Token> {
+    match op {
+        BinOp::Less => Some(Token::Bool(a < b)),
+        BinOp::Greater => Some(Token::Bool(a > b)),
+        BinOp::LessEq => Some(Token::Bool(a <= b)),
+        BinOp::GreaterEq => Some(Token::Bool(a >= b)),
+        _ => None,
+    }
+}
+
+fn number(value: &Token) -> Option<f64> {
+    match value {
+        Token::Integer(i) => Some(*i as f64),
+        Token::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn describe(value: &Token) -> String {
+    match value {
+        Token::Bool(b) => b.to_string(),
+        Token::Integer(i) => i.to_string(),
+        Token::Float(f) => f.to_string(),
+        Token::String(s) => format!("{:?}", s),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Value;
+
+    #[test]
+    fn conditionals_and_substitutions_follow_the_defines() {
+        let mut args: Vec<String> = ["gem", "--define", "debug", "compile", "--define"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        args.push("platform=web".into());
+        let defines = take_flags(&mut args).unwrap();
+        assert_eq!(args, ["gem", "compile"]);
+        assert_eq!(defines["debug"], Token::Bool(true));
+        assert_eq!(defines["platform"], Token::String("web".into()));
+        assert!(take_flags(&mut vec!["--define".into(), "a-b".into()]).is_err());
+
+        let scene = |src: &str| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            expand(&tokens, &defines)
+                .and_then(|tokens| Parser::new(tokens).parse_scene().map_err(|e| e.message))
+        };
+        let src = r#"Root: Gem {
+            title: $platform
+            #if platform == "web" && !release {
+                Touch: Gem { size: (2 * 8, 4) }
+                #if debug { Fps: Gem { } }
+            } #else #if platform == "console" {
+                Pad: Gem { }
+            } #else {
+                Keys: Gem { }
+            }
+        }"#;
+        let root = scene(src).unwrap().root;
+        assert_eq!(root.properties[0].value, Value::String("web".into()));
+        let names: Vec<&str> = root.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Touch", "Fps"]);
+        assert!(has_conditionals(src));
+
+        let err = |src: &str| scene(src).unwrap_err();
+        assert!(err("Root: Gem { x: $missing }").contains("pass --define missing=value"));
+        assert!(err("Root: Gem { #if platform { } }").contains("must be true or false"));
+        assert!(err("#if debug { Root: Gem { }").contains("Unclosed #if block"));
+        assert!(err("Root: Gem { #else { } }").contains("without an #if"));
+    }
+}
@@ -0,0 +1,121 @@
+//! `gem diagnostics scene.gem`, a diagnostics server for editors. It prints
+//! the properties a scene's parse skips, then reads edits from stdin, one
+//! `start end text` line each, and prints them again after every edit. The
+//! offsets are bytes of the text as edited so far and `text` is escaped as in
+//! a string literal. An edit inside one node only reparses that node (see
+//! `parser::parse_incremental`), so this keeps up with every keystroke. Each
+//! report is a line per skipped property followed by an empty line.
+
+use std::io::{BufRead, Write};
+
+use crate::ast::GemFile;
+use crate::parser::{self, TextEdit};
+use crate::pipeline;
+
+/// Report on `source`, then again after each edit line of `input`
+pub fn serve(
+    mut source: String,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    let mut scene = parse(&source);
+    report(&scene, &mut output)?;
+    for line in input.lines() {
+        let line = line?;
+        let edit = parse_edit(&line).filter(|e| source.get(e.range.clone()).is_some());
+        let Some(edit) = edit else {
+            eprintln!(
+                "[WARN] diagnostics: expected `start end text` within the file, got {:?}",
+                line
+            );
+            report(&scene, &mut output)?;
+            continue;
+        };
+        let edits = [edit];
+        let edited = parser::apply_edits(&source, &edits, 0..source.len());
+        scene = match &scene {
+            Ok(old) => parser::parse_incremental(old, &source, &edits).map_err(|e| e.message),
+            // Nothing to reuse from a file that didn't parse
+            Err(_) => parse(&edited),
+        };
+        source = edited;
+        report(&scene, &mut output)?;
+    }
+    Ok(())
+}
+
+fn parse(source: &str) -> Result<GemFile, String> {
+    pipeline::lex_source(source).and_then(pipeline::parse_scene_recovering)
+}
+
+fn report(scene: &Result<GemFile, String>, output: &mut impl Write) -> std::io::Result<()> {
+    match scene {
+        Ok(scene) => {
+            for error in pipeline::describe_errors(scene) {
+                writeln!(output, "{}", error)?;
+            }
+        }
+        Err(e) => writeln!(output, "{}", e)?,
+    }
+    writeln!(output)?;
+    output.flush()
+}
+
+/// `start end text`, with `\n`, `\t`, `\"` and `\\` in `text` unescaped
+fn parse_edit(line: &str) -> Option<TextEdit> {
+    let mut parts = line.splitn(3, ' ');
+    let start = parts.next()?.parse().ok()?;
+    let end = parts.next()?.parse().ok()?;
+    let mut text = String::new();
+    let mut chars = parts.next().unwrap_or("").chars();
+    while let Some(c) = chars.next() {
+        text.push(match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                other @ ('"' | '\\') => other,
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(TextEdit {
+        range: start..end,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_skipped_properties_after_each_edit() {
+        let src = "Hud: Gem {\n    Score: LabelGem { text: (1, }\n    Lives: LabelGem {}\n}\n";
+        let fix = src.find("(1,").unwrap();
+        let close = src.rfind('}').unwrap();
+        let edits = format!(
+            "{} {} \\\"0\\\"\n{} {} \nbogus\n",
+            fix,
+            fix + 3,
+            close - 1,
+            close + 1
+        );
+        let mut out = Vec::new();
+        serve(src.to_string(), edits.as_bytes(), &mut out).unwrap();
+        let mut reports = vec![Vec::new()];
+        for line in std::str::from_utf8(&out).unwrap().lines() {
+            match line {
+                "" => reports.push(Vec::new()),
+                line => reports.last_mut().unwrap().push(line),
+            }
+        }
+        reports.pop();
+        assert_eq!(reports.len(), 4, "{:?}", reports);
+        assert!(reports[0][0].starts_with("Score: "), "{:?}", reports[0]);
+        // Fixed, then the root left unclosed, which a bad edit line doesn't change
+        assert!(reports[1].is_empty());
+        assert_eq!(reports[2].len(), 1);
+        assert_eq!(reports[3], reports[2]);
+    }
+}
@@ -13,6 +13,8 @@ use winit::{
     window::{Window, WindowAttributes},
 };
 
+use crate::log::verbose;
+
 pub struct GemDisplay {
     pub window: Window,
     pub gl_context: PossiblyCurrentContext,
@@ -20,11 +22,14 @@ pub struct GemDisplay {
 }
 
 impl GemDisplay {
-    pub fn new(event_loop: &EventLoop<()>, width: u32, height: u32, title: &str) -> Self {
+    /// The window starts hidden: screen readers attach before it's first
+    /// shown (see `run_renderer`)
+    pub fn new<T>(event_loop: &EventLoop<T>, width: u32, height: u32, title: &str) -> Self {
         let window_attrs = WindowAttributes::default()
             .with_title(title)
             .with_inner_size(PhysicalSize::new(width, height))
-            .with_resizable(true);
+            .with_resizable(true)
+            .with_visible(false);
 
         let config_template = ConfigTemplateBuilder::new()
             .with_alpha_size(8)
@@ -48,7 +53,7 @@ impl GemDisplay {
 
         let window = window.expect("Failed to create window");
 
-        println!(
+        verbose!(
             "[GemDisplay] Picked config with {} samples",
             gl_config.num_samples()
         );
@@ -78,7 +83,7 @@ impl GemDisplay {
             gl_display
                 .create_context(&gl_config, &context_attributes)
                 .unwrap_or_else(|_| {
-                    println!("[GemDisplay] OpenGL 3.3 failed, falling back to GLES 2.0");
+                    verbose!("[GemDisplay] OpenGL 3.3 failed, falling back to GLES 2.0");
                     gl_display
                         .create_context(&gl_config, &fallback_context_attributes)
                         .expect("Failed to create OpenGL context")
@@ -107,7 +112,7 @@ impl GemDisplay {
             .make_current(&gl_surface)
             .expect("Failed to make context current");
 
-        println!("[GemDisplay] OpenGL context created and made current");
+        verbose!("[GemDisplay] OpenGL context created and made current");
 
         Self {
             window,
@@ -0,0 +1,2474 @@
+//! Draw list extraction for the preview renderer.
+//! Walks the scene AST and produces pixel-space draw commands, lights and occluders.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::ast;
+use crate::check_box;
+use crate::class_defs::{self, DefaultValue};
+use crate::color::Color;
+use crate::color_picker;
+use crate::container::{self, Insets, Layout, SizeFlags};
+use crate::defaults;
+use crate::font::{self, FontChain, FontRegistry};
+use crate::item_list::{self, ListItem};
+use crate::line_edit;
+use crate::mesh::{self, MeshRef};
+use crate::option_button;
+use crate::popup;
+use crate::renderer::{BlendMode, Gradient, GradientMode, MAX_GRADIENT_STOPS};
+use crate::scene_tree;
+use crate::tab_container;
+use crate::text::{self, Align, LaidOut, LayoutParams, Span, TextLayout, TextStyle};
+use crate::texture::{self, Texture, TextureOptions};
+use crate::transform::Transform2D;
+use crate::transform3d::Mat4;
+use crate::vfs;
+
+/// Simple draw command representing a quad to render
+#[derive(Clone, Debug)]
+pub struct DrawCmd {
+    pub x: f32, // pixels
+    pub y: f32, // pixels
+    pub w: f32, // pixels
+    pub h: f32, // pixels
+    pub color: [f32; 4],
+    pub blend: BlendMode,
+    /// Replaces `color` when the node declares a `gradient`
+    pub gradient: Option<Gradient>,
+    /// SVG drawn over the rect (see `texture` for how it's sampled)
+    pub texture: Option<Texture>,
+    /// Node that produced this quad
+    pub node: NodePath,
+}
+
+/// Location of a node in the scene: child indices starting from the root
+pub type NodePath = Vec<usize>;
+
+/// A laid-out block of text drawn with its top-left corner at (x, y)
+#[derive(Clone, Debug)]
+pub struct TextCmd {
+    pub x: f32,
+    pub y: f32,
+    pub layout: TextLayout,
+    pub node: NodePath,
+}
+
+/// A point light contributed by a `Light2DGem`
+#[derive(Clone, Copy, Debug)]
+pub struct LightCmd {
+    pub x: f32, // pixels (center)
+    pub y: f32, // pixels (center)
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub energy: f32,
+}
+
+/// A `MeshGem`, drawn by the 3D pass under everything 2D
+#[derive(Clone, Debug)]
+pub struct MeshCmd {
+    pub mesh: MeshRef,
+    pub model: Mat4,
+    pub color: [f32; 4],
+}
+
+/// An axis-aligned shadow-casting rectangle (pixels, top-left origin)
+#[derive(Clone, Copy, Debug)]
+pub struct OccluderCmd {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct DrawList {
+    pub quads: Vec<DrawCmd>,
+    pub texts: Vec<TextCmd>,
+    pub lights: Vec<LightCmd>,
+    pub occluders: Vec<OccluderCmd>,
+    pub meshes: Vec<MeshCmd>,
+    /// Perspective and view of the scene's `Camera3DGem`, for the meshes
+    pub view_projection: Mat4,
+    /// Light level of unlit areas; only used when the scene contains lights
+    pub ambient: [f32; 4],
+    /// Faces referenced by the scene; text layouts index into it
+    pub fonts: FontRegistry,
+    /// Where canvas layers in front of the world and popup windows start in
+    /// `quads` and `texts`, when there are any
+    pub overlay: Option<Overlay>,
+    /// Split-screen regions, each drawing the world through its own camera;
+    /// the world fills the window when there are none
+    pub views: Vec<View>,
+}
+
+/// A ViewportGem: its rect of the window, and the commands drawn clipped to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub rect: container::Rect,
+    pub quads: Range<usize>,
+    pub texts: Range<usize>,
+    pub lights: Range<usize>,
+    pub occluders: Range<usize>,
+}
+
+/// CanvasLayerGem subtrees with a `layer` of 0 or more, then PopupGem/DialogGem
+/// subtrees, drawn after everything else (and after lighting) so they sit on
+/// top of the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overlay {
+    pub quads_from: usize,
+    pub texts_from: usize,
+    /// Where the popups start, after the canvas layers
+    pub popup_quads_from: usize,
+    pub popup_texts_from: usize,
+    /// Fullscreen tint under the popups, set by `dim_background: true`
+    pub dim: Option<[f32; 4]>,
+}
+
+const POPUP_TYPES: [&str; 3] = ["PopupGem", "DialogGem", "PopupMenuGem"];
+const CAMERA_TYPE: &str = "Camera2DGem";
+/// A camera's default `cull_mask`: all 20 render layers
+const ALL_LAYERS: i64 = 0xF_FFFF;
+const CANVAS_LAYER_TYPE: &str = "CanvasLayerGem";
+const VIEWPORT_TYPE: &str = "ViewportGem";
+/// Nodes placed in 3D; only MeshGems draw, the others just group
+const SPATIAL_TYPES: [&str; 3] = ["MeshGem", "SkeletonGem", "BoneAttachmentGem"];
+const CAMERA_3D_TYPE: &str = "Camera3DGem";
+/// How much of the scene a popup's `dim_background` hides
+const DIM_ALPHA: f32 = 0.5;
+const TAB_TYPES: [&str; 2] = ["TabContainerGem", "AccordionGem"];
+const TAB_COLOR: [f32; 4] = [0.2, 0.2, 0.24, 1.0];
+/// The current tab is the tab color lightened this much
+const CURRENT_TAB_LIGHTEN: f32 = 0.15;
+/// Side of a check box or radio button's indicator, and the gap before its text
+const TOGGLE_BOX: f32 = 20.0;
+const TOGGLE_GAP: f32 = 8.0;
+const LIST_TYPES: [&str; 2] = ["ItemListGem", "TreeGem"];
+const SELECTED_ROW_COLOR: [f32; 4] = [0.26, 0.4, 0.62, 1.0];
+/// Types whose own properties are running state that nothing draws
+const UNDRAWN_TYPES: [&str; 3] = ["StateMachineGem", "TimerGem", "WebSocketGem"];
+/// Trees smaller than this are gathered on one thread; `gem bench-draw-list`
+/// times both sides of it
+pub const PARALLEL_MIN_NODES: usize = 2000;
+
+/// A popup, canvas layer or viewport put aside by the main pass, with the
+/// path, theme and frame it had there
+type Deferred<'a> = (&'a ast::GemDecl, NodePath, Theme, Frame);
+
+/// Where a node is laid out: the sizes that size expressions
+/// (`parent.width - 20`) resolve against, and the rect a container gave it
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    /// The enclosing node's top-left corner
+    origin: (f32, f32),
+    /// The enclosing node's size; the viewport's for the root
+    parent: (f32, f32),
+    viewport: (f32, f32),
+    /// Set for children of `container::TYPES`, replacing their own position and size
+    slot: Option<container::Rect>,
+    /// Threads the subtree may be gathered on; 1 once the work is split
+    workers: usize,
+    /// Subtrees of fewer nodes are gathered on one thread
+    parallel_min_nodes: usize,
+    /// Render layers drawn: the camera's `cull_mask` in the world
+    cull_mask: i64,
+    /// The enclosing MeshGems' 3D transform
+    model: Mat4,
+}
+
+impl DrawList {
+    /// Indices of the first overlay quad and text (the lengths without popups)
+    pub fn overlay_start(&self) -> (usize, usize) {
+        self.overlay
+            .map_or((self.quads.len(), self.texts.len()), |o| {
+                (o.quads_from, o.texts_from)
+            })
+    }
+}
+
+/// Text defaults cascaded from `theme_font`, `theme_font_size` and
+/// `theme_font_color` on any ancestor (or the node itself)
+#[derive(Clone, Debug, Default)]
+struct Theme {
+    font: FontChain,
+    font_size: Option<f32>,
+    font_color: Option<[f32; 4]>,
+}
+
+impl Theme {
+    fn inherit(&self, decl: &ast::GemDecl, fonts: &FontRegistry) -> Theme {
+        let mut theme = self.clone();
+        for p in &decl.properties {
+            match p.key.as_str() {
+                "theme_font" => theme.font = font_chain(&p.value, fonts),
+                "theme_font_size" => theme.font_size = value_f32(&p.value).or(theme.font_size),
+                "theme_font_color" => theme.font_color = value_color(&p.value).or(theme.font_color),
+                _ => {}
+            }
+        }
+        theme
+    }
+}
+
+const FONT_KEYS: [&str; 3] = ["font", "theme_font", "fallback_fonts"];
+
+/// Build the draw list for a whole scene. Size expressions resolve against
+/// the current viewport size, so rebuild after a resize.
+pub fn build(scene: &ast::GemFile) -> DrawList {
+    rebuild(scene, FontRegistry::new())
+}
+
+/// Build again after an edit, keeping faces and glyph pages already loaded in `fonts`
+pub fn rebuild(scene: &ast::GemFile, fonts: FontRegistry) -> DrawList {
+    let workers = thread::available_parallelism().map_or(1, usize::from);
+    rebuild_on(scene, fonts, workers, PARALLEL_MIN_NODES)
+}
+
+/// `rebuild`, gathering trees of `parallel_min_nodes` or more on up to
+/// `workers` threads
+pub fn rebuild_on(
+    scene: &ast::GemFile,
+    mut fonts: FontRegistry,
+    workers: usize,
+    parallel_min_nodes: usize,
+) -> DrawList {
+    let mut list = DrawList {
+        ambient: [0.2, 0.2, 0.2, 1.0],
+        ..Default::default()
+    };
+    if let Some(p) = scene
+        .root
+        .properties
+        .iter()
+        .find(|p| p.key == "ambient_light")
+        && let Some(c) = value_color(&p.value)
+    {
+        list.ambient = c;
+    }
+
+    load_fonts(&scene.root, &mut fonts);
+    if let Some(p) = scene
+        .root
+        .properties
+        .iter()
+        .find(|p| p.key == "fallback_fonts")
+    {
+        fonts.fallbacks = font_chain(&p.value, &fonts).faces;
+    }
+    let (vw, vh) = scene_tree::viewport_size();
+    let viewport = (vw as f32, vh as f32);
+    let screen = Frame {
+        origin: (0.0, 0.0),
+        parent: viewport,
+        viewport,
+        slot: None,
+        workers,
+        parallel_min_nodes,
+        cull_mask: ALL_LAYERS,
+        model: Mat4::IDENTITY,
+    };
+    let root_theme = Theme::default().inherit(defaults::theme(), &fonts);
+    let mut deferred = Vec::new();
+    let world = gather_world(
+        &scene.root,
+        &root_theme,
+        screen,
+        find_camera(&scene.root),
+        ((0.0, 0.0), viewport),
+        &fonts,
+        &mut deferred,
+    );
+
+    // Each viewport draws the world again through its own camera, then its
+    // other children in its own coordinates
+    let mut views = Vec::new();
+    // Each canvas layer is drawn in window coordinates, moved by its own
+    // `offset` rather than the camera
+    let mut layers = Vec::new();
+    let mut popups = Vec::new();
+    let mut i = 0;
+    while let Some((decl, mut path, theme, frame)) = deferred.get(i).cloned() {
+        i += 1;
+        if decl.gem_type == VIEWPORT_TYPE {
+            let rect = (node_position(decl), own_size(decl, frame));
+            let mut part = gather_world(
+                &scene.root,
+                &root_theme,
+                screen,
+                find_camera(decl),
+                rect,
+                &fonts,
+                &mut Vec::new(),
+            );
+            let mut own = DrawList::default();
+            let frame = Frame {
+                parent: rect.1,
+                ..screen
+            };
+            let theme = theme.inherit(decl, &fonts);
+            gather_children(
+                decl,
+                &mut path,
+                &theme,
+                frame,
+                &fonts,
+                &mut own,
+                &mut deferred,
+            );
+            project(
+                &mut own,
+                &Transform2D::from_parts(to_f64(rect.0), 0.0, (1.0, 1.0)),
+            );
+            append(&mut part, own);
+            // The 3D pass draws the world's meshes once, across the window
+            part.meshes.clear();
+            views.push((rect, part));
+            continue;
+        }
+        if decl.gem_type != CANVAS_LAYER_TYPE {
+            popups.push((decl, path, theme, frame));
+            continue;
+        }
+        let mut part = DrawList::default();
+        gather_draws(
+            decl,
+            &mut path,
+            &theme,
+            screen,
+            &fonts,
+            &mut part,
+            &mut deferred,
+        );
+        project(&mut part, &layer_projection(decl));
+        layers.push((int_prop(decl, "layer", 1), part));
+    }
+    // Layers below 0 go behind the world, the rest in front of it
+    layers.sort_by_key(|&(layer, _)| layer);
+    let front = layers.split_off(layers.partition_point(|&(layer, _)| layer < 0));
+    for (_, part) in layers {
+        append(&mut list, part);
+    }
+    if views.is_empty() {
+        append(&mut list, world);
+    } else {
+        list.meshes.extend(world.meshes);
+    }
+    for (rect, part) in views {
+        let starts = (
+            list.quads.len(),
+            list.texts.len(),
+            list.lights.len(),
+            list.occluders.len(),
+        );
+        append(&mut list, part);
+        list.views.push(View {
+            rect,
+            quads: starts.0..list.quads.len(),
+            texts: starts.1..list.texts.len(),
+            lights: starts.2..list.lights.len(),
+            occluders: starts.3..list.occluders.len(),
+        });
+    }
+
+    if !front.is_empty() || !popups.is_empty() {
+        let mut overlay = Overlay {
+            quads_from: list.quads.len(),
+            texts_from: list.texts.len(),
+            popup_quads_from: 0,
+            popup_texts_from: 0,
+            dim: None,
+        };
+        for (_, part) in front {
+            append(&mut list, part);
+        }
+        overlay.popup_quads_from = list.quads.len();
+        overlay.popup_texts_from = list.texts.len();
+        // Popups nested in popups are appended while drawing their parent
+        let mut i = 0;
+        while let Some((decl, mut path, theme, frame)) = popups.get(i).cloned() {
+            if decl
+                .properties
+                .iter()
+                .any(|p| p.key == popup::DIM_BACKGROUND_KEY && p.value == ast::Value::Bool(true))
+            {
+                overlay.dim = Some(Color::BLACK.with_alpha(DIM_ALPHA).to_array());
+            }
+            gather_draws(
+                decl,
+                &mut path,
+                &theme,
+                frame,
+                &fonts,
+                &mut list,
+                &mut popups,
+            );
+            i += 1;
+        }
+        list.overlay = Some(overlay);
+    }
+    list.view_projection = camera_3d(&scene.root, viewport);
+    list.fonts = fonts;
+    if scene_tree::is_pixel_snap() {
+        snap_to_pixels(&mut list);
+    }
+    list
+}
+
+fn append(list: &mut DrawList, part: DrawList) {
+    list.quads.extend(part.quads);
+    list.texts.extend(part.texts);
+    list.lights.extend(part.lights);
+    list.occluders.extend(part.occluders);
+    list.meshes.extend(part.meshes);
+}
+
+/// The scene outside canvas layers and viewports, seen through `camera` and
+/// drawn into `rect` of the window. Whatever it puts aside goes in `deferred`.
+fn gather_world<'a>(
+    root: &'a ast::GemDecl,
+    theme: &Theme,
+    screen: Frame,
+    camera: Option<&ast::GemDecl>,
+    rect: container::Rect,
+    fonts: &FontRegistry,
+    deferred: &mut Vec<Deferred<'a>>,
+) -> DrawList {
+    let mut world = DrawList::default();
+    let frame = Frame {
+        cull_mask: camera.map_or(ALL_LAYERS, |c| int_prop(c, "cull_mask", ALL_LAYERS)),
+        ..screen
+    };
+    gather_draws(
+        root,
+        &mut Vec::new(),
+        theme,
+        frame,
+        fonts,
+        &mut world,
+        deferred,
+    );
+    project(&mut world, &camera_projection(camera, rect));
+    world
+}
+
+/// The camera showing the world from `decl`: the first Camera2DGem in it
+/// outside a canvas layer or another viewport
+fn find_camera(decl: &ast::GemDecl) -> Option<&ast::GemDecl> {
+    if decl.gem_type == CAMERA_TYPE {
+        return Some(decl);
+    }
+    decl.children
+        .iter()
+        .filter(|c| ![CANVAS_LAYER_TYPE, VIEWPORT_TYPE].contains(&c.gem_type.as_str()))
+        .find_map(find_camera)
+}
+
+/// The world's projection into `rect`: the camera shows its `position` at
+/// the middle of the rect. Without one the world's origin is the rect's corner.
+fn camera_projection(
+    camera: Option<&ast::GemDecl>,
+    ((x, y), (w, h)): container::Rect,
+) -> Transform2D {
+    let offset = match camera.map(node_position) {
+        Some((cx, cy)) => (x + w / 2.0 - cx, y + h / 2.0 - cy),
+        None => (x, y),
+    };
+    Transform2D::from_parts(to_f64(offset), 0.0, (1.0, 1.0))
+}
+
+fn to_f64((x, y): (f32, f32)) -> (f64, f64) {
+    (x as f64, y as f64)
+}
+
+/// The first Camera3DGem's perspective times its view, or a camera at the
+/// origin looking down -z with the default `fov`, `near` and `far`
+fn camera_3d(root: &ast::GemDecl, (vw, vh): (f32, f32)) -> Mat4 {
+    fn find(decl: &ast::GemDecl) -> Option<&ast::GemDecl> {
+        if decl.gem_type == CAMERA_3D_TYPE {
+            return Some(decl);
+        }
+        decl.children.iter().find_map(find)
+    }
+    let camera = find(root);
+    let float = |key: &str| {
+        let set = camera.and_then(|c| c.properties.iter().find(|p| p.key == key));
+        let default = match class_default(CAMERA_3D_TYPE, key) {
+            Some(DefaultValue::Float(v)) => v as f32,
+            _ => 1.0,
+        };
+        set.and_then(|p| value_f32(&p.value)).unwrap_or(default)
+    };
+    let projection = Mat4::perspective(
+        float("fov").to_radians(),
+        vw / vh.max(1.0),
+        float("near"),
+        float("far"),
+    );
+    let vec3 = |key| camera.and_then(|c| vec3_prop(c, key)).unwrap_or([0.0; 3]);
+    projection * Mat4::view(vec3("position"), vec3("rotation"))
+}
+
+/// A canvas layer's projection: window coordinates moved by its `offset`
+fn layer_projection(decl: &ast::GemDecl) -> Transform2D {
+    let (x, y) = decl
+        .properties
+        .iter()
+        .find(|p| p.key == "offset")
+        .and_then(|p| value_f32s(&p.value))
+        .filter(|v| v.len() >= 2)
+        .map_or((0.0, 0.0), |v| (v[0], v[1]));
+    Transform2D::from_parts(to_f64((x, y)), 0.0, (1.0, 1.0))
+}
+
+/// An Int property such as a canvas layer's `layer`, or `default`
+fn int_prop(decl: &ast::GemDecl, key: &str, default: i64) -> i64 {
+    decl.properties
+        .iter()
+        .find(|p| p.key == key)
+        .and_then(|p| match p.value {
+            ast::Value::Integer(i) => Some(i),
+            _ => None,
+        })
+        .unwrap_or(default)
+}
+
+/// Move every command in `part` from its canvas into the window
+fn project(part: &mut DrawList, projection: &Transform2D) {
+    let at = |x: &mut f32, y: &mut f32| {
+        let (px, py) = projection.apply((*x as f64, *y as f64));
+        (*x, *y) = (px as f32, py as f32);
+    };
+    for q in &mut part.quads {
+        at(&mut q.x, &mut q.y);
+    }
+    for t in &mut part.texts {
+        at(&mut t.x, &mut t.y);
+    }
+    for l in &mut part.lights {
+        at(&mut l.x, &mut l.y);
+    }
+    for o in &mut part.occluders {
+        at(&mut o.x, &mut o.y);
+    }
+}
+
+/// Round every rect's edges and every text and light position to whole
+/// pixels and sample textures with nearest filtering, so pixel art doesn't
+/// shimmer as it moves by fractions of a pixel
+fn snap_to_pixels(list: &mut DrawList) {
+    fn snap_rect(x: &mut f32, y: &mut f32, w: &mut f32, h: &mut f32) {
+        let (right, bottom) = ((*x + *w).round(), (*y + *h).round());
+        (*x, *y) = (x.round(), y.round());
+        (*w, *h) = (right - *x, bottom - *y);
+    }
+    for q in &mut list.quads {
+        snap_rect(&mut q.x, &mut q.y, &mut q.w, &mut q.h);
+        if let Some(texture) = &mut q.texture {
+            texture.options.filter = texture::Filter::Nearest;
+        }
+    }
+    for o in &mut list.occluders {
+        snap_rect(&mut o.x, &mut o.y, &mut o.w, &mut o.h);
+    }
+    for t in &mut list.texts {
+        (t.x, t.y) = (t.x.round(), t.y.round());
+    }
+    for l in &mut list.lights {
+        (l.x, l.y) = (l.x.round(), l.y.round());
+    }
+}
+
+/// Load every face referenced by a directive in a font property; a face that
+/// can't be loaded is replaced by the engine's default font
+fn load_fonts(decl: &ast::GemDecl, fonts: &mut FontRegistry) {
+    fn visit(v: &ast::Value, fonts: &mut FontRegistry) {
+        match v {
+            ast::Value::Directive(directive) => {
+                let path = font::directive_path(&directive.segments);
+                if let Err(e) = fonts.load(&path) {
+                    eprintln!("[WARN] Failed to load font {}; using the default font", e);
+                    let default = Path::new(defaults::FONT_PATH);
+                    if let Err(e) = fonts.substitute(&path, default) {
+                        eprintln!("[WARN] Failed to load the default font {}", e);
+                    }
+                }
+            }
+            ast::Value::Tuple(vals) | ast::Value::Array(vals) => {
+                vals.iter().for_each(|v| visit(v, fonts))
+            }
+            _ => {}
+        }
+    }
+    for p in &decl.properties {
+        if FONT_KEYS.contains(&p.key.as_str()) {
+            visit(&p.value, fonts);
+        }
+    }
+    for c in &decl.children {
+        load_fonts(c, fonts);
+    }
+}
+
+/// `font: #assets:fonts:Inter.ttf`, `font: "Inter"` (a loaded face's file stem)
+/// or a tuple of those, tried in order for each character.
+fn font_chain(v: &ast::Value, fonts: &FontRegistry) -> FontChain {
+    let mut chain = FontChain::default();
+    let entries = match v {
+        ast::Value::Tuple(vals) | ast::Value::Array(vals) => vals.as_slice(),
+        v => std::slice::from_ref(v),
+    };
+    for entry in entries {
+        let face = match entry {
+            ast::Value::Directive(directive) => {
+                fonts.loaded(&font::directive_path(&directive.segments))
+            }
+            ast::Value::String(name) | ast::Value::Ident(name) => {
+                let face = fonts.find(name);
+                if face.is_none() {
+                    eprintln!("[WARN] Unknown font {}", name);
+                }
+                face
+            }
+            _ => None,
+        };
+        chain.faces.extend(face);
+    }
+    chain
+}
+
+/// A node's `position`, or the placeholder default when it has none
+pub fn node_position(decl: &ast::GemDecl) -> (f32, f32) {
+    decl.properties
+        .iter()
+        .find(|p| p.key == "position")
+        .and_then(|p| value_f32s(&p.value))
+        .filter(|v| v.len() >= 2)
+        .map(|v| (v[0], v[1]))
+        .unwrap_or((100.0, 100.0))
+}
+
+// Extract draw commands from AST nodes (placeholder visuals)
+fn gather_draws<'a>(
+    decl: &'a ast::GemDecl,
+    path: &mut NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+    out: &mut DrawList,
+    popups: &mut Vec<Deferred<'a>>,
+) {
+    if is_hidden(decl) {
+        return;
+    }
+    let theme = &theme.inherit(decl, fonts);
+
+    let (pos, size) = frame
+        .slot
+        .unwrap_or_else(|| (node_position(decl), own_size(decl, frame)));
+    let (mut color, _) = type_defaults(&decl.gem_type);
+    let mut blend = BlendMode::Normal;
+
+    for p in &decl.properties {
+        if p.key == "blend_mode" {
+            let mode = match &p.value {
+                ast::Value::Ident(s) | ast::Value::String(s) => BlendMode::parse(s),
+                _ => None,
+            };
+            match mode {
+                Some(m) => blend = m,
+                None => eprintln!(
+                    "[WARN] {}: unknown blend_mode {:?}, using normal",
+                    decl.name, p.value
+                ),
+            }
+        }
+    }
+
+    // A node on no layer the camera draws still holds its children, which
+    // have layers of their own
+    let shown = int_prop(decl, "visibility_layer", 1) & frame.cull_mask != 0;
+    if decl.gem_type == "Light2DGem" {
+        if shown {
+            out.lights.push(light_from_decl(decl, pos));
+        }
+        let frame = Frame {
+            slot: None,
+            ..frame
+        };
+        gather_children(decl, path, theme, frame, fonts, out, popups);
+        return;
+    }
+
+    // An explicit color overrides the theme's per-type default
+    if let Some(c) = decl
+        .properties
+        .iter()
+        .find(|p| p.key == "color")
+        .and_then(|p| value_color(&p.value))
+    {
+        color = c;
+    }
+
+    if SPATIAL_TYPES.contains(&decl.gem_type.as_str()) {
+        // Nested 3D nodes are placed relative to their parent, as a glTF
+        // model's nodes are
+        let vec3 = |key, default| vec3_prop(decl, key).unwrap_or(default);
+        let model = frame.model
+            * Mat4::from_parts(
+                vec3("position", [0.0; 3]),
+                vec3("rotation", [0.0; 3]),
+                vec3("scale", [1.0; 3]),
+            );
+        if shown && let Some(cmd) = mesh_from_decl(decl, model, color) {
+            out.meshes.push(cmd);
+        }
+        let frame = Frame {
+            slot: None,
+            model,
+            ..frame
+        };
+        gather_children(decl, path, theme, frame, fonts, out, popups);
+        return;
+    }
+
+    // occluder: true (use the node's rect) or occluder: (w, h)
+    if shown && let Some(p) = decl.properties.iter().find(|p| p.key == "occluder") {
+        let occ_size = match &p.value {
+            ast::Value::Bool(true) => Some(size),
+            v => value_f32s(v).filter(|v| v.len() >= 2).map(|v| (v[0], v[1])),
+        };
+        if let Some((w, h)) = occ_size {
+            out.occluders.push(OccluderCmd {
+                x: pos.0,
+                y: pos.1,
+                w,
+                h,
+            });
+        }
+    }
+
+    let is_text_only = matches!(decl.gem_type.as_str(), "LabelGem" | "RichLabelGem");
+    if !shown {
+    } else if check_box::TYPES.contains(&decl.gem_type.as_str()) {
+        draw_toggle(decl, path, (pos, size), color, out);
+    } else if decl.gem_type == "ColorPickerGem" {
+        draw_color_picker(decl, path, (pos, size), color, out);
+    } else if !is_group(&decl.gem_type) && !is_text_only {
+        out.quads.push(DrawCmd {
+            x: pos.0,
+            y: pos.1,
+            w: size.0,
+            h: size.1,
+            color,
+            blend,
+            gradient: gradient_from_decl(decl),
+            texture: texture_from_decl(decl),
+            node: path.clone(),
+        });
+    }
+
+    if shown && let Some(mut cmd) = text_from_decl(decl, pos, size, theme, fonts) {
+        cmd.node = path.clone();
+        out.texts.push(cmd);
+    }
+
+    // Plain `Gem` groups without a size pass their parent's frame through;
+    // other nodes hold their children inside their padding
+    let has_size = decl.properties.iter().any(|p| p.key == "size");
+    let frame = if has_size || !is_group(&decl.gem_type) || frame.slot.is_some() {
+        let (origin, parent) = insets_prop(decl, "padding").shrink((pos, size));
+        Frame {
+            origin,
+            parent,
+            viewport: frame.viewport,
+            slot: None,
+            workers: frame.workers,
+            parallel_min_nodes: frame.parallel_min_nodes,
+            cull_mask: frame.cull_mask,
+            model: frame.model,
+        }
+    } else {
+        Frame {
+            slot: None,
+            ..frame
+        }
+    };
+    gather_children(decl, path, theme, frame, fonts, out, popups);
+}
+
+/// Types that draw nothing themselves, only their children
+fn is_group(gem_type: &str) -> bool {
+    [
+        CAMERA_TYPE,
+        CAMERA_3D_TYPE,
+        CANVAS_LAYER_TYPE,
+        VIEWPORT_TYPE,
+        "Gem",
+    ]
+    .contains(&gem_type)
+}
+
+/// `visible: false` hides the node and everything under it
+fn is_hidden(decl: &ast::GemDecl) -> bool {
+    decl.properties
+        .iter()
+        .any(|p| p.key == "visible" && p.value == ast::Value::Bool(false))
+}
+
+/// A node's size when no container places it: `size` or the theme's
+/// default for its type, clamped to `min_size` and `max_size`. A
+/// PopupMenuGem is as tall as its rows.
+fn own_size(decl: &ast::GemDecl, frame: Frame) -> (f32, f32) {
+    let (w, mut h) =
+        size_prop(decl, "size", frame).unwrap_or_else(|| type_defaults(&decl.gem_type).1);
+    if decl.gem_type == "PopupMenuGem" {
+        let rows = decl.properties.iter().find_map(|p| match &p.value {
+            ast::Value::Tuple(items) | ast::Value::Array(items) if p.key == popup::ITEMS_KEY => {
+                Some(items.len())
+            }
+            _ => None,
+        });
+        h = rows.unwrap_or(0) as f32 * item_list::ROW_HEIGHT;
+    }
+    let (min_w, min_h) = size_prop(decl, "min_size", frame).unwrap_or_default();
+    let (max_w, max_h) = max_size(decl, frame);
+    (w.min(max_w).max(min_w), h.min(max_h).max(min_h))
+}
+
+/// `max_size`, where a 0 (or a missing `max_size`) leaves that axis unbounded
+fn max_size(decl: &ast::GemDecl, frame: Frame) -> (f32, f32) {
+    let unbounded = |v: f32| if v > 0.0 { v } else { f32::INFINITY };
+    size_prop(decl, "max_size", frame).map_or((f32::INFINITY, f32::INFINITY), |(w, h)| {
+        (unbounded(w), unbounded(h))
+    })
+}
+
+fn size_prop(decl: &ast::GemDecl, key: &str, frame: Frame) -> Option<(f32, f32)> {
+    decl.properties
+        .iter()
+        .find(|p| p.key == key)
+        .and_then(|p| value_size(&p.value, frame))
+}
+
+/// Where a container puts each of its children; hidden children and those
+/// put aside take no space
+fn arrange_children(decl: &ast::GemDecl, frame: Frame) -> Vec<Option<container::Rect>> {
+    let prop = |key: &str| {
+        decl.properties
+            .iter()
+            .find(|p| p.key == key)
+            .and_then(|p| value_f32(&p.value))
+    };
+    let layout = match decl.gem_type.as_str() {
+        "VBoxGem" => Layout::VBox,
+        "HBoxGem" => Layout::HBox,
+        _ => Layout::Grid {
+            columns: prop("columns").map_or(1, |c| c.max(1.0) as usize),
+        },
+    };
+    let separation = prop("separation").unwrap_or(container::DEFAULT_SEPARATION);
+    let placed: Vec<usize> = (0..decl.children.len())
+        .filter(|&i| {
+            let c = &decl.children[i];
+            !is_hidden(c) && !is_deferred(c)
+        })
+        .collect();
+    let items: Vec<container::Item> = placed
+        .iter()
+        .map(|&i| container_item(&decl.children[i], frame))
+        .collect();
+    let rects = container::arrange(layout, (frame.origin, frame.parent), separation, &items);
+    let mut slots = vec![None; decl.children.len()];
+    for (i, rect) in placed.into_iter().zip(rects) {
+        slots[i] = Some(rect);
+    }
+    slots
+}
+
+/// A container child's size, limits and `size_flags_horizontal` /
+/// `size_flags_vertical` (a flag name or a tuple of them)
+fn container_item(decl: &ast::GemDecl, frame: Frame) -> container::Item {
+    let flags = |key: &str| {
+        let Some(p) = decl.properties.iter().find(|p| p.key == key) else {
+            return SizeFlags::default();
+        };
+        let name = |v: &ast::Value| match v {
+            ast::Value::Ident(n) | ast::Value::String(n) => Some(n.clone()),
+            _ => None,
+        };
+        let names: Vec<String> = match &p.value {
+            ast::Value::Tuple(vals) | ast::Value::Array(vals) => {
+                vals.iter().filter_map(name).collect()
+            }
+            v => name(v).into_iter().collect(),
+        };
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        SizeFlags::parse(&names).unwrap_or_else(|e| {
+            eprintln!("[WARN] {}: {}", decl.name, e);
+            SizeFlags::default()
+        })
+    };
+    let stretch_ratio = decl
+        .properties
+        .iter()
+        .find(|p| p.key == "stretch_ratio")
+        .and_then(|p| value_f32(&p.value))
+        .unwrap_or(1.0);
+    container::Item {
+        size: own_size(decl, frame),
+        max: max_size(decl, frame),
+        horizontal: flags("size_flags_horizontal"),
+        vertical: flags("size_flags_vertical"),
+        stretch_ratio: stretch_ratio.max(0.0),
+        margin: insets_prop(decl, "margin"),
+    }
+}
+
+/// `padding` / `margin`: one number for every side, `(vertical, horizontal)`
+/// or `(top, right, bottom, left)`
+fn insets_prop(decl: &ast::GemDecl, key: &str) -> Insets {
+    let Some(p) = decl.properties.iter().find(|p| p.key == key) else {
+        return Insets::default();
+    };
+    if let Some(v) = value_f32(&p.value) {
+        return Insets::uniform(v);
+    }
+    match value_f32s(&p.value).as_deref() {
+        Some(&[v, h]) => Insets {
+            top: v,
+            right: h,
+            bottom: v,
+            left: h,
+        },
+        Some(&[top, right, bottom, left]) => Insets {
+            top,
+            right,
+            bottom,
+            left,
+        },
+        _ => {
+            eprintln!(
+                "[WARN] {}: {} expects a number, (vertical, horizontal) or (top, right, bottom, left)",
+                decl.name, key
+            );
+            Insets::default()
+        }
+    }
+}
+
+/// Color and size for `gem_type`: the theme's entry for it, else its
+/// class's defaults, else the theme's `Default` entry
+fn type_defaults(gem_type: &str) -> ([f32; 4], (f32, f32)) {
+    let color = default_color(gem_type, "color").unwrap_or([0.6, 0.6, 0.6, 1.0]);
+    let theme_size = |entry: &str| {
+        theme_value(entry, "size")
+            .and_then(value_f32s)
+            .filter(|v| v.len() >= 2)
+            .map(|v| (v[0], v[1]))
+    };
+    let size = theme_size(gem_type)
+        .or_else(|| match class_default(gem_type, "size")? {
+            DefaultValue::Vec2(w, h) => Some((w as f32, h as f32)),
+            _ => None,
+        })
+        .or_else(|| theme_size("Default"))
+        .unwrap_or((160.0, 48.0));
+    (color, size)
+}
+
+/// The color `key` of a `gem_type` node that sets none, looked up like
+/// `type_defaults`
+fn default_color(gem_type: &str, key: &str) -> Option<[f32; 4]> {
+    theme_value(gem_type, key)
+        .and_then(value_color)
+        .or_else(|| match class_default(gem_type, key)? {
+            DefaultValue::Color(c) => Some(Color::from_rgba8(c).to_array()),
+            _ => None,
+        })
+        .or_else(|| theme_value("Default", key).and_then(value_color))
+}
+
+fn class_default(gem_type: &str, key: &str) -> Option<DefaultValue> {
+    class_defs::find(gem_type)?.property(key)?.default
+}
+
+/// Whether `key` on a `gem_type` node can change what's drawn: its `name`,
+/// which live nodes are matched by, and the properties its class declares
+/// (`Gem`'s for script-only and undrawn types). Values scripts keep on a
+/// node, like a Timer's `time_left` or a player's `hp`, are not drawn.
+pub fn draws_property(gem_type: &str, key: &str) -> bool {
+    let class = class_defs::find(gem_type)
+        .filter(|_| !UNDRAWN_TYPES.contains(&gem_type))
+        .or_else(|| class_defs::find("Gem"));
+    key == "name" || class.is_some_and(|c| c.property(key).is_some())
+}
+
+/// A property of the theme's entry named `entry`
+fn theme_value(entry: &str, key: &str) -> Option<&'static ast::Value> {
+    defaults::theme()
+        .children
+        .iter()
+        .find(|c| c.name == entry)?
+        .properties
+        .iter()
+        .find(|p| p.key == key)
+        .map(|p| &p.value)
+}
+
+/// A check box or radio button's indicator: a `color` box at the left,
+/// filled with `check_color` (its class's by default) while `pressed`
+fn draw_toggle(
+    decl: &ast::GemDecl,
+    path: &NodePath,
+    ((x, y), (_, h)): container::Rect,
+    color: [f32; 4],
+    out: &mut DrawList,
+) {
+    let side = TOGGLE_BOX.min(h);
+    let quad = |x, y, side, color| DrawCmd {
+        x,
+        y,
+        w: side,
+        h: side,
+        color,
+        blend: BlendMode::Normal,
+        gradient: None,
+        texture: None,
+        node: path.clone(),
+    };
+    let top = y + (h - side) * 0.5;
+    out.quads.push(quad(x, top, side, color));
+    let prop = |key: &str| {
+        decl.properties
+            .iter()
+            .find(|p| p.key == key)
+            .map(|p| &p.value)
+    };
+    if prop(check_box::PRESSED_KEY) == Some(&ast::Value::Bool(true)) {
+        let check = prop("check_color")
+            .and_then(value_color)
+            .or_else(|| default_color(&decl.gem_type, "check_color"))
+            .unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        // Radio marks are smaller, to tell the two apart
+        let inset = if decl.gem_type == "RadioButtonGem" {
+            side * 0.3
+        } else {
+            side * 0.2
+        };
+        out.quads
+            .push(quad(x + inset, top + inset, side - 2.0 * inset, check));
+    }
+}
+
+fn current_tab_color() -> [f32; 4] {
+    Color::from_array(TAB_COLOR)
+        .lightened(CURRENT_TAB_LIGHTEN)
+        .to_array()
+}
+
+/// A color picker's parts for its `color`: the saturation/value square as
+/// a white-to-hue gradient under a clear-to-black one, the hue bar, and the
+/// alpha bar over a dark backing, each with a marker at the current value
+fn draw_color_picker(
+    decl: &ast::GemDecl,
+    path: &NodePath,
+    ((x, y), size): container::Rect,
+    color: [f32; 4],
+    out: &mut DrawList,
+) {
+    let picked = Color::from_array(color);
+    let (h, s, v) = picked.to_hsv();
+    let hue = match decl
+        .properties
+        .iter()
+        .find(|p| p.key == color_picker::HUE_KEY)
+    {
+        Some(p) if s == 0.0 || v == 0.0 => value_f32(&p.value).unwrap_or(h),
+        _ => h,
+    };
+    let quad = |((qx, qy), (w, h)): container::Rect, color, gradient| DrawCmd {
+        x: x + qx,
+        y: y + qy,
+        w,
+        h,
+        color,
+        blend: BlendMode::Normal,
+        gradient,
+        texture: None,
+        node: path.clone(),
+    };
+    let linear = |angle, stops| {
+        Some(Gradient {
+            mode: GradientMode::Linear,
+            angle,
+            stops,
+        })
+    };
+    let opaque = |c: Color| c.with_alpha(1.0).to_array();
+    let marker = [1.0, 1.0, 1.0, 1.0];
+    for (part, rect) in color_picker::layout(size) {
+        let ((rx, ry), (rw, rh)) = rect;
+        match part {
+            color_picker::Part::Square => {
+                let pure = opaque(Color::from_hsv(hue, 1.0, 1.0, 1.0));
+                out.quads.push(quad(
+                    rect,
+                    pure,
+                    linear(0.0, vec![(0.0, [1.0; 4]), (1.0, pure)]),
+                ));
+                let black = vec![(0.0, [0.0, 0.0, 0.0, 0.0]), (1.0, [0.0, 0.0, 0.0, 1.0])];
+                out.quads.push(quad(rect, pure, linear(90.0, black)));
+                let (mx, my) = (rx + s * rw, ry + (1.0 - v) * rh);
+                out.quads
+                    .push(quad(((mx - 3.0, my - 3.0), (6.0, 6.0)), marker, None));
+            }
+            color_picker::Part::Hue => {
+                let stops = (0..=6)
+                    .map(|i| {
+                        let t = i as f32 / 6.0;
+                        (t, opaque(Color::from_hsv(t.min(0.9999), 1.0, 1.0, 1.0)))
+                    })
+                    .collect();
+                out.quads.push(quad(rect, marker, linear(90.0, stops)));
+                out.quads
+                    .push(quad(((rx, ry + hue * rh - 1.0), (rw, 2.0)), marker, None));
+            }
+            color_picker::Part::Alpha => {
+                out.quads.push(quad(rect, [0.15, 0.15, 0.15, 1.0], None));
+                let stops = vec![
+                    (0.0, opaque(picked)),
+                    (1.0, picked.with_alpha(0.0).to_array()),
+                ];
+                out.quads.push(quad(rect, marker, linear(90.0, stops)));
+                let my = ry + (1.0 - picked.a) * rh;
+                out.quads
+                    .push(quad(((rx, my - 1.0), (rw, 2.0)), marker, None));
+            }
+        }
+    }
+}
+
+fn gather_children<'a>(
+    decl: &'a ast::GemDecl,
+    path: &mut NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+    out: &mut DrawList,
+    popups: &mut Vec<Deferred<'a>>,
+) {
+    // A list's children are its items, drawn as rows
+    if LIST_TYPES.contains(&decl.gem_type.as_str()) || decl.gem_type == "PopupMenuGem" {
+        draw_rows(decl, path, theme, frame, fonts, out);
+        return;
+    }
+    let is_tabs = TAB_TYPES.contains(&decl.gem_type.as_str());
+    let slots = if container::TYPES.contains(&decl.gem_type.as_str()) {
+        arrange_children(decl, frame)
+    } else if is_tabs {
+        draw_tabs(decl, path, theme, frame, fonts, out)
+    } else {
+        vec![None; decl.children.len()]
+    };
+    // Only the current page of a tab container is drawn
+    let drawn: Vec<(usize, Option<container::Rect>)> = (0..decl.children.len())
+        .filter(|&i| !is_tabs || slots[i].is_some())
+        .map(|i| (i, slots[i]))
+        .collect();
+    // Smaller trees aren't worth the threads, so they stop looking
+    let workers = if frame.workers > 1 && node_count(decl) >= frame.parallel_min_nodes {
+        frame.workers
+    } else {
+        1
+    };
+    let frame = Frame { workers, ..frame };
+    if workers > 1 && drawn.len() > 1 {
+        for (part, deferred) in gather_parallel(decl, &drawn, path, theme, frame, fonts) {
+            out.quads.extend(part.quads);
+            out.texts.extend(part.texts);
+            out.lights.extend(part.lights);
+            out.occluders.extend(part.occluders);
+            popups.extend(deferred);
+        }
+        return;
+    }
+    for (i, slot) in drawn {
+        path.push(i);
+        let frame = Frame { slot, ..frame };
+        gather_child(&decl.children[i], path, theme, frame, fonts, out, popups);
+        path.pop();
+    }
+}
+
+/// Popups, canvas layers and viewports, which the main pass puts aside
+fn is_deferred(decl: &ast::GemDecl) -> bool {
+    POPUP_TYPES.contains(&decl.gem_type.as_str())
+        || [CANVAS_LAYER_TYPE, VIEWPORT_TYPE].contains(&decl.gem_type.as_str())
+}
+
+/// Gather a child into `out`, or put it aside (outside any slot) if it's a
+/// popup, canvas layer or viewport
+fn gather_child<'a>(
+    decl: &'a ast::GemDecl,
+    path: &mut NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+    out: &mut DrawList,
+    popups: &mut Vec<Deferred<'a>>,
+) {
+    // What's put aside is drawn in window coordinates, on every layer
+    if is_deferred(decl) {
+        let frame = Frame {
+            slot: None,
+            cull_mask: ALL_LAYERS,
+            ..frame
+        };
+        popups.push((decl, path.clone(), theme.clone(), frame));
+    } else {
+        gather_draws(decl, path, theme, frame, fonts, out, popups);
+    }
+}
+
+/// Gather the `drawn` children of a large node on `frame.workers` threads,
+/// each taking a run of neighbouring subtrees of about the same node count
+/// into its own buffers. The buffers come back in child order, so appending
+/// them draws in the same order as gathering on one thread.
+fn gather_parallel<'a>(
+    decl: &'a ast::GemDecl,
+    drawn: &[(usize, Option<container::Rect>)],
+    path: &NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+) -> Vec<(DrawList, Vec<Deferred<'a>>)> {
+    let sizes: Vec<usize> = drawn
+        .iter()
+        .map(|&(i, _)| node_count(&decl.children[i]))
+        .collect();
+    let share = sizes.iter().sum::<usize>().div_ceil(frame.workers);
+    let mut runs = vec![Vec::new()];
+    let mut taken = 0;
+    for (&child, size) in drawn.iter().zip(sizes) {
+        if taken >= share {
+            runs.push(Vec::new());
+            taken = 0;
+        }
+        runs.last_mut().unwrap().push(child);
+        taken += size;
+    }
+    thread::scope(|s| {
+        let handles: Vec<_> = runs
+            .into_iter()
+            .map(|run| {
+                let mut path = path.clone();
+                s.spawn(move || {
+                    let mut part = DrawList::default();
+                    let mut popups = Vec::new();
+                    for (i, slot) in run {
+                        path.push(i);
+                        let frame = Frame {
+                            slot,
+                            workers: 1,
+                            ..frame
+                        };
+                        let child = &decl.children[i];
+                        gather_child(
+                            child,
+                            &mut path,
+                            theme,
+                            frame,
+                            fonts,
+                            &mut part,
+                            &mut popups,
+                        );
+                        path.pop();
+                    }
+                    (part, popups)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+fn node_count(decl: &ast::GemDecl) -> usize {
+    1 + decl.children.iter().map(node_count).sum::<usize>()
+}
+
+/// The tab bar of a TabContainerGem or the headers of an AccordionGem,
+/// each titled by its page's `tab_title` or name. Returns the current
+/// page's rect; the other pages get none.
+fn draw_tabs(
+    decl: &ast::GemDecl,
+    path: &NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+    out: &mut DrawList,
+) -> Vec<Option<container::Rect>> {
+    let count = decl.children.len();
+    // The first page is current by default; -1 collapses an accordion
+    let current = match decl
+        .properties
+        .iter()
+        .find(|p| p.key == tab_container::CURRENT_TAB_KEY)
+    {
+        Some(p) => match p.value {
+            ast::Value::Integer(i) => usize::try_from(i).ok(),
+            _ => None,
+        },
+        None => Some(0),
+    };
+    let ((x, y), (w, h)) = (frame.origin, frame.parent);
+    let bar = tab_container::BAR_HEIGHT;
+    let (headers, page) = if decl.gem_type == "AccordionGem" {
+        let (tops, page) = tab_container::accordion_layout(h, count, current);
+        let headers: Vec<_> = tops.into_iter().map(|top| (x, y + top, w)).collect();
+        (
+            headers,
+            page.map(|(top, height)| ((x, y + top), (w, height))),
+        )
+    } else {
+        let headers = tab_container::tab_spans(w, count)
+            .into_iter()
+            .map(|(left, width)| (x + left, y, width))
+            .collect();
+        (headers, Some(((x, y + bar), (w, (h - bar).max(0.0)))))
+    };
+    let mut slots = vec![None; count];
+    for (i, (page_decl, (hx, hy, hw))) in decl.children.iter().zip(headers).enumerate() {
+        let is_current = current == Some(i);
+        out.quads.push(DrawCmd {
+            x: hx,
+            y: hy,
+            w: hw,
+            h: bar,
+            color: if is_current {
+                current_tab_color()
+            } else {
+                TAB_COLOR
+            },
+            blend: BlendMode::Normal,
+            gradient: None,
+            texture: None,
+            node: path.clone(),
+        });
+        let title = page_decl
+            .properties
+            .iter()
+            .find_map(|p| match (&p.key, &p.value) {
+                (key, ast::Value::String(t)) if key == tab_container::TITLE_KEY => Some(t.as_str()),
+                _ => None,
+            })
+            .unwrap_or(&page_decl.name);
+        let mut text = line_text(title, ((hx, hy), (hw, bar)), Align::Center, theme, fonts);
+        text.node = path.clone();
+        out.texts.push(text);
+        if is_current {
+            slots[i] = page;
+        }
+    }
+    slots
+}
+
+/// The rows of an ItemListGem or TreeGem: its `items` tuple, then its
+/// children titled by `text` or name (a tree's nested), scrolled by
+/// `scroll_offset`. Only rows wholly inside the list are drawn. A
+/// PopupMenuGem's rows are just its `items`, highlighting `focused_item`.
+fn draw_rows(
+    decl: &ast::GemDecl,
+    path: &NodePath,
+    theme: &Theme,
+    frame: Frame,
+    fonts: &FontRegistry,
+    out: &mut DrawList,
+) {
+    fn collect(decl: &ast::GemDecl, parent: Option<usize>, tree: bool, out: &mut Vec<ListItem>) {
+        let text = decl
+            .properties
+            .iter()
+            .find_map(|p| match (&p.key, &p.value) {
+                (key, ast::Value::String(t)) if key == "text" => Some(t.as_str()),
+                _ => None,
+            })
+            .unwrap_or(&decl.name);
+        out.push(ListItem::new(text, parent));
+        if tree {
+            let index = out.len() - 1;
+            for c in &decl.children {
+                collect(c, Some(index), tree, out);
+            }
+        }
+    }
+    let prop = |key: &str| {
+        decl.properties
+            .iter()
+            .find(|p| p.key == key)
+            .map(|p| &p.value)
+    };
+    let tree = decl.gem_type == "TreeGem";
+    let mut items = Vec::new();
+    match prop(item_list::ITEMS_KEY) {
+        Some(ast::Value::Tuple(texts) | ast::Value::Array(texts)) => {
+            for t in texts {
+                match t {
+                    ast::Value::String(t) => items.push(ListItem::new(t, None)),
+                    other => {
+                        eprintln!("[WARN] {}: skipping non-string item {:?}", decl.name, other)
+                    }
+                }
+            }
+        }
+        Some(other) => eprintln!(
+            "[WARN] {}: items should be a tuple of strings, got {:?}",
+            decl.name, other
+        ),
+        None => {}
+    }
+    let is_menu = decl.gem_type == "PopupMenuGem";
+    if !is_menu {
+        for c in &decl.children {
+            collect(c, None, tree, &mut items);
+        }
+    }
+    let highlight = if is_menu {
+        popup::FOCUSED_ITEM_KEY
+    } else {
+        item_list::SELECTED_KEY
+    };
+    let selected = match prop(highlight) {
+        Some(ast::Value::Integer(i)) => usize::try_from(*i).ok(),
+        _ => None,
+    };
+    let ((x, y), (w, h)) = (frame.origin, frame.parent);
+    let rows = item_list::rows(&items);
+    let max = item_list::max_scroll(rows.len(), h);
+    let scroll = prop(item_list::SCROLL_KEY)
+        .and_then(value_f32)
+        .unwrap_or(0.0)
+        .clamp(0.0, max);
+    let row_h = item_list::ROW_HEIGHT;
+    for (row, &(i, depth)) in rows.iter().enumerate() {
+        let top = row as f32 * row_h - scroll;
+        if top < 0.0 || top + row_h > h {
+            continue;
+        }
+        if selected == Some(i) {
+            out.quads.push(DrawCmd {
+                x,
+                y: y + top,
+                w,
+                h: row_h,
+                color: SELECTED_ROW_COLOR,
+                blend: BlendMode::Normal,
+                gradient: None,
+                texture: None,
+                node: path.clone(),
+            });
+        }
+        let indent = if tree {
+            (depth + 1) as f32 * item_list::INDENT
+        } else {
+            item_list::INDENT * 0.5
+        };
+        if tree && item_list::has_children(&items, i) {
+            let marker = if items[i].collapsed { "+" } else { "-" };
+            let rect = (
+                (x + indent - item_list::INDENT, y + top),
+                (item_list::INDENT, row_h),
+            );
+            let mut text = line_text(marker, rect, Align::Center, theme, fonts);
+            text.node = path.clone();
+            out.texts.push(text);
+        }
+        let rect = ((x + indent, y + top), ((w - indent).max(0.0), row_h));
+        let mut text = line_text(&items[i].text, rect, Align::Left, theme, fonts);
+        text.node = path.clone();
+        out.texts.push(text);
+    }
+}
+
+/// One line of `content` in the theme's font, vertically centered in `rect`
+fn line_text(
+    content: &str,
+    rect: container::Rect,
+    align: Align,
+    theme: &Theme,
+    fonts: &FontRegistry,
+) -> TextCmd {
+    let ((x, y), (w, h)) = rect;
+    let mut style = TextStyle::default();
+    style.color = theme.font_color.unwrap_or(style.color);
+    let params = LayoutParams {
+        font_size: theme.font_size.unwrap_or(LayoutParams::default().font_size),
+        max_width: Some(w),
+        align,
+        max_lines: Some(1),
+        ..LayoutParams::default()
+    };
+    let spans = [Span::Text {
+        text: content.to_string(),
+        style,
+    }];
+    let layout = text::layout(&spans, &params, &fonts.metrics(&theme.font));
+    prerender(&layout, fonts);
+    TextCmd {
+        x,
+        y: y + (h - layout.height) * 0.5,
+        layout,
+        node: NodePath::new(),
+    }
+}
+
+/// Rasterize a layout's glyphs up front so the first frame only has to
+/// upload the pages
+fn prerender(layout: &TextLayout, fonts: &FontRegistry) {
+    for item in &layout.items {
+        if let LaidOut::Glyph {
+            ch,
+            font_size,
+            face: Some(face),
+            ..
+        } = item
+        {
+            fonts.glyph(*face, *ch, *font_size);
+        }
+    }
+}
+
+/// The text of a dropdown's `selected` item, 0 when unset
+fn option_text(decl: &ast::GemDecl) -> Option<String> {
+    let prop = |key: &str| {
+        decl.properties
+            .iter()
+            .find(|p| p.key == key)
+            .map(|p| &p.value)
+    };
+    let selected = match prop(option_button::SELECTED_KEY) {
+        Some(ast::Value::Integer(i)) => usize::try_from(*i).ok()?,
+        _ => 0,
+    };
+    match prop(option_button::ITEMS_KEY) {
+        Some(ast::Value::Tuple(items) | ast::Value::Array(items)) => match items.get(selected)? {
+            ast::Value::String(t) => Some(t.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Text for LabelGem and the text edits (plain), RichLabelGem (markup) and
+/// ButtonGem and OptionButtonGem (centered in the button rect), and the
+/// toggles (after their indicator). Wrapping measures against the node's `size` rect; `font`
+/// overrides the theme's font chain.
+fn text_from_decl(
+    decl: &ast::GemDecl,
+    pos: (f32, f32),
+    size: (f32, f32),
+    theme: &Theme,
+    fonts: &FontRegistry,
+) -> Option<TextCmd> {
+    let kind = decl.gem_type.as_str();
+    if !matches!(
+        kind,
+        "LabelGem"
+            | "RichLabelGem"
+            | "ButtonGem"
+            | "OptionButtonGem"
+            | "CheckBoxGem"
+            | "RadioButtonGem"
+            | "LineEditGem"
+            | "TextEditGem"
+    ) {
+        return None;
+    }
+
+    let mut content = None;
+    let mut style = TextStyle::default();
+    let mut params = LayoutParams::default();
+    let mut chain = theme.font.clone();
+    style.color = theme.font_color.unwrap_or(style.color);
+    params.font_size = theme.font_size.unwrap_or(params.font_size);
+    let mut has_size = false;
+    let mut preedit = None;
+    let mut caret = None;
+    // Rich labels wrap by default; plain labels opt in
+    let mut autowrap = kind == "RichLabelGem";
+    for p in &decl.properties {
+        match (p.key.as_str(), &p.value) {
+            ("text", ast::Value::String(s)) => content = Some(s.clone()),
+            ("font_size", v) => params.font_size = value_f32(v).unwrap_or(params.font_size),
+            ("font_color", v) => style.color = value_color(v).unwrap_or(style.color),
+            ("align", ast::Value::Ident(a) | ast::Value::String(a)) => match Align::parse(a) {
+                Some(align) => params.align = align,
+                None => eprintln!("[WARN] {}: unknown align {}", decl.name, a),
+            },
+            ("size", _) => has_size = true,
+            ("font", v) => chain = font_chain(v, fonts),
+            ("autowrap", ast::Value::Bool(b)) => autowrap = *b,
+            ("max_lines", ast::Value::Integer(n)) if *n > 0 => params.max_lines = Some(*n as usize),
+            (line_edit::PREEDIT_KEY, ast::Value::String(s)) if !s.is_empty() => {
+                preedit = Some(s.clone())
+            }
+            ("caret", ast::Value::Integer(n)) => caret = Some((*n).max(0) as usize),
+            ("line_spacing", v) => {
+                params.line_spacing = value_f32(v).unwrap_or(params.line_spacing)
+            }
+            _ => {}
+        }
+    }
+    // A dropdown shows its selected item, the first by default
+    if kind == "OptionButtonGem" {
+        content = option_text(decl);
+    }
+    let content = content?;
+
+    let spans = if kind == "RichLabelGem" {
+        text::parse_markup(&content, &style)
+    } else if let Some(preedit) = preedit {
+        // Text being composed shows at the caret, underlined
+        let at = content
+            .char_indices()
+            .nth(caret.unwrap_or(usize::MAX))
+            .map_or(content.len(), |(i, _)| i);
+        let (before, after) = content.split_at(at);
+        let composing = TextStyle {
+            underline: true,
+            ..style.clone()
+        };
+        [(before, &style), (&preedit, &composing), (after, &style)]
+            .into_iter()
+            .filter(|(text, _)| !text.is_empty())
+            .map(|(text, style)| Span::Text {
+                text: text.to_string(),
+                style: style.clone(),
+            })
+            .collect()
+    } else {
+        vec![Span::Text {
+            text: content,
+            style,
+        }]
+    };
+    let is_button = matches!(kind, "ButtonGem" | "OptionButtonGem");
+    if is_button {
+        params.max_width = Some(size.0);
+        params.align = Align::Center;
+    } else if autowrap && has_size {
+        params.max_width = Some(size.0);
+    } else if autowrap {
+        eprintln!(
+            "[WARN] {}: autowrap needs a size to wrap against",
+            decl.name
+        );
+    }
+
+    let layout = text::layout(&spans, &params, &fonts.metrics(&chain));
+    prerender(&layout, fonts);
+    let is_toggle = check_box::TYPES.contains(&kind);
+    let y = if is_button || is_toggle {
+        pos.1 + (size.1 - layout.height) * 0.5
+    } else {
+        pos.1
+    };
+    // A toggle's label follows its indicator
+    let x = if is_toggle {
+        pos.0 + TOGGLE_BOX.min(size.1) + TOGGLE_GAP
+    } else {
+        pos.0
+    };
+    Some(TextCmd {
+        x,
+        y,
+        layout,
+        node: NodePath::new(),
+    })
+}
+
+/// `texture: #assets:icon.svg`; raster formats aren't decoded yet. A missing
+/// file draws the "missing texture" placeholder. Sampling options come from
+/// the asset's import file, then the node's own `filter`/`repeat`/`mipmaps`.
+fn texture_from_decl(decl: &ast::GemDecl) -> Option<Texture> {
+    let prop = decl.properties.iter().find(|p| p.key == "texture")?;
+    let ast::Value::Directive(directive) = &prop.value else {
+        eprintln!("[WARN] {}: texture must be an asset directive", decl.name);
+        return None;
+    };
+    let path = font::directive_path(&directive.segments);
+    if !vfs::is_file(&path.to_string_lossy()) {
+        eprintln!(
+            "[WARN] {}: texture {} not found, using a placeholder",
+            decl.name,
+            path.display()
+        );
+        Some(Texture {
+            path: PathBuf::from(defaults::MISSING_TEXTURE_PATH),
+            options: TextureOptions::default(),
+            region: None,
+        })
+    } else if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"))
+    {
+        let mut options = texture::import_options(&path);
+        options.apply(&decl.properties, &decl.name);
+        let region = texture::region(directive, &decl.name);
+        Some(Texture {
+            path,
+            options,
+            region,
+        })
+    } else {
+        eprintln!(
+            "[WARN] {}: only .svg textures are supported, skipping {}",
+            decl.name,
+            path.display()
+        );
+        None
+    }
+}
+
+/// A MeshGem's `mesh`, placed by `model`; a MeshGem without one only groups
+/// its children
+fn mesh_from_decl(decl: &ast::GemDecl, model: Mat4, color: [f32; 4]) -> Option<MeshCmd> {
+    let prop = decl.properties.iter().find(|p| p.key == "mesh")?;
+    let ast::Value::Directive(directive) = &prop.value else {
+        eprintln!("[WARN] {}: mesh must be an asset directive", decl.name);
+        return None;
+    };
+    Some(MeshCmd {
+        mesh: mesh::reference(directive, &decl.name),
+        model,
+        color,
+    })
+}
+
+/// A three-number property such as a MeshGem's `position`
+fn vec3_prop(decl: &ast::GemDecl, key: &str) -> Option<[f32; 3]> {
+    let p = decl.properties.iter().find(|p| p.key == key)?;
+    match value_f32s(&p.value)?[..] {
+        [x, y, z] => Some([x, y, z]),
+        _ => None,
+    }
+}
+
+fn light_from_decl(decl: &ast::GemDecl, pos: (f32, f32)) -> LightCmd {
+    let mut light = LightCmd {
+        x: pos.0,
+        y: pos.1,
+        radius: 128.0,
+        color: [1.0, 1.0, 1.0, 1.0],
+        energy: 1.0,
+    };
+    for p in &decl.properties {
+        match p.key.as_str() {
+            "radius" => light.radius = value_f32(&p.value).unwrap_or(light.radius),
+            "energy" => light.energy = value_f32(&p.value).unwrap_or(light.energy),
+            "color" => light.color = value_color(&p.value).unwrap_or(light.color),
+            _ => {}
+        }
+    }
+    light
+}
+
+/// `gradient: ((0, #112233), (1, #334455))` plus optional `gradient_mode` and
+/// `gradient_angle` properties.
+fn gradient_from_decl(decl: &ast::GemDecl) -> Option<Gradient> {
+    let prop = decl.properties.iter().find(|p| p.key == "gradient")?;
+    let (ast::Value::Tuple(entries) | ast::Value::Array(entries)) = &prop.value else {
+        eprintln!("[WARN] {}: gradient must be a tuple of stops", decl.name);
+        return None;
+    };
+
+    let mut stops = Vec::new();
+    for entry in entries {
+        let stop = match entry {
+            ast::Value::Tuple(pair) if pair.len() == 2 => {
+                value_f32(&pair[0]).zip(value_color(&pair[1]))
+            }
+            _ => None,
+        };
+        match stop {
+            Some((offset, color)) => stops.push((offset.clamp(0.0, 1.0), color)),
+            None => eprintln!(
+                "[WARN] {}: ignoring malformed gradient stop {:?}",
+                decl.name, entry
+            ),
+        }
+    }
+    if stops.is_empty() {
+        return None;
+    }
+    if stops.len() > MAX_GRADIENT_STOPS {
+        eprintln!(
+            "[WARN] {}: gradient has {} stops, only the first {} are used",
+            decl.name,
+            stops.len(),
+            MAX_GRADIENT_STOPS
+        );
+        stops.truncate(MAX_GRADIENT_STOPS);
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut gradient = Gradient {
+        mode: GradientMode::Linear,
+        angle: 90.0,
+        stops,
+    };
+    for p in &decl.properties {
+        match (p.key.as_str(), &p.value) {
+            ("gradient_mode", ast::Value::Ident(m) | ast::Value::String(m)) => {
+                match GradientMode::parse(m) {
+                    Some(mode) => gradient.mode = mode,
+                    None => eprintln!("[WARN] {}: unknown gradient_mode {}", decl.name, m),
+                }
+            }
+            ("gradient_angle", v) => gradient.angle = value_f32(v).unwrap_or(gradient.angle),
+            _ => {}
+        }
+    }
+    Some(gradient)
+}
+
+fn value_f32(v: &ast::Value) -> Option<f32> {
+    match v {
+        ast::Value::Integer(i) => Some(*i as f32),
+        ast::Value::Number(n) => Some(*n as f32),
+        _ => None,
+    }
+}
+
+fn value_f32s(v: &ast::Value) -> Option<Vec<f32>> {
+    match v {
+        ast::Value::Tuple(vals) => vals.iter().map(value_f32).collect(),
+        _ => None,
+    }
+}
+
+/// `(w, h)`, either of which may be a size expression; negative sizes clamp to 0
+fn value_size(v: &ast::Value, frame: Frame) -> Option<(f32, f32)> {
+    let ast::Value::Tuple(vals) = v else {
+        return None;
+    };
+    let component = |v: &ast::Value| match v {
+        ast::Value::SizeExpr(e) => Some(e.eval(frame.parent, frame.viewport)),
+        v => value_f32(v),
+    };
+    match vals.as_slice() {
+        [w, h, ..] => Some((component(w)?.max(0.0), component(h)?.max(0.0))),
+        _ => None,
+    }
+}
+
+/// Colors are written as #rrggbb[aa], (r, g, b) or (r, g, b, a) with 0-255 channels.
+fn value_color(v: &ast::Value) -> Option<[f32; 4]> {
+    if let ast::Value::Color(c) = v {
+        return Some(Color::from_rgba8(*c).to_array());
+    }
+    let c = value_f32s(v)?;
+    if c.len() < 3 {
+        return None;
+    }
+    let a = c.get(3).copied().unwrap_or(255.0);
+    Some([c[0] / 255.0, c[1] / 255.0, c[2] / 255.0, a / 255.0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn build_from(src: &str) -> DrawList {
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse_scene().unwrap();
+        build(&ast)
+    }
+
+    #[test]
+    fn gradient_and_light_extraction() {
+        let list = build_from(
+            r#"Root: Gem {
+                Bg: PanelGem {
+                    gradient: ((1, #334455), (0, #112233))
+                    gradient_mode: radial
+                    occluder: true
+                    texture: #assets:icons:play.svg
+                }
+                Lamp: Light2DGem { position: (10, 20) radius: 64 energy: 2.0 }
+            }"#,
+        );
+
+        assert_eq!(list.quads.len(), 1);
+        let g = list.quads[0].gradient.as_ref().expect("gradient");
+        assert_eq!(g.mode, GradientMode::Radial);
+        assert_eq!(g.stops.len(), 2);
+        assert_eq!(g.stops[0].0, 0.0);
+        assert_eq!(g.stops[0].1[0], 0x11 as f32 / 255.0);
+        // The icon doesn't exist, so the placeholder stands in for it
+        assert_eq!(
+            list.quads[0].texture.as_ref().map(|t| t.path.as_path()),
+            Some(Path::new(defaults::MISSING_TEXTURE_PATH))
+        );
+
+        assert_eq!(list.occluders.len(), 1);
+        assert!(list.texts.is_empty());
+        assert_eq!(list.lights.len(), 1);
+        assert_eq!(list.lights[0].radius, 64.0);
+        assert_eq!(list.lights[0].energy, 2.0);
+    }
+
+    #[test]
+    fn texture_options_come_from_import_files_and_nodes() {
+        let dir = Path::new("target").join("gem_texture_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tile.svg"), "<svg/>").unwrap();
+        std::fs::write(
+            dir.join("tile.svg.import"),
+            "Import: Gem { filter: nearest mipmaps: true }",
+        )
+        .unwrap();
+        let list = build_from(
+            r#"Root: Gem {
+                Floor: PanelGem { texture: #target:gem_texture_test:tile.svg repeat: enabled }
+                Wall: PanelGem { texture: #target:gem_texture_test:tile.svg mipmaps: false }
+                Door: PanelGem { texture: #target:gem_texture_test:tile.svg:region(8, 0, 8, 16) }
+            }"#,
+        );
+        let options: Vec<_> = list
+            .quads
+            .iter()
+            .map(|q| q.texture.as_ref().unwrap().options)
+            .collect();
+        assert_eq!(
+            options,
+            [
+                TextureOptions {
+                    filter: texture::Filter::Nearest,
+                    repeat: true,
+                    mipmaps: true,
+                },
+                TextureOptions {
+                    filter: texture::Filter::Nearest,
+                    ..TextureOptions::default()
+                },
+                TextureOptions {
+                    filter: texture::Filter::Nearest,
+                    mipmaps: true,
+                    ..TextureOptions::default()
+                },
+            ]
+        );
+        let door = list.quads[2].texture.as_ref().unwrap();
+        assert_eq!(door.region, Some([8.0, 0.0, 8.0, 16.0]));
+        assert!(!door.rasterized_at_display_size());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn pixel_snap_rounds_edges_and_filters_nearest() {
+        let mut list = build_from(
+            r#"Root: Gem {
+                Hero: PanelGem {
+                    position: (10.4, 20.6) size: (15.3, 8)
+                    texture: #assets:hero.svg
+                }
+                Name: LabelGem { position: (10.6, 12.2) text: "Hero" }
+            }"#,
+        );
+        snap_to_pixels(&mut list);
+        let q = &list.quads[0];
+        // The right edge at 25.7 rounds up, so the width grows to 16
+        assert_eq!((q.x, q.y, q.w, q.h), (10.0, 21.0, 16.0, 8.0));
+        assert_eq!(
+            q.texture.as_ref().unwrap().options.filter,
+            texture::Filter::Nearest
+        );
+        assert_eq!((list.texts[0].x, list.texts[0].y), (11.0, 12.0));
+    }
+
+    #[test]
+    fn parallel_gathering_keeps_draw_order() {
+        let scene = crate::bench::scene(5, 300);
+        let one = rebuild_on(&scene, FontRegistry::new(), 1, PARALLEL_MIN_NODES);
+        let four = rebuild_on(&scene, FontRegistry::new(), 4, PARALLEL_MIN_NODES);
+        assert_eq!(one.quads.len(), 1500);
+        let quads = |l: &DrawList| -> Vec<_> {
+            l.quads.iter().map(|q| (q.node.clone(), q.x, q.y)).collect()
+        };
+        let texts = |l: &DrawList| -> Vec<_> {
+            l.texts.iter().map(|t| (t.node.clone(), t.x, t.y)).collect()
+        };
+        assert_eq!(quads(&four), quads(&one));
+        assert_eq!(texts(&four), texts(&one));
+    }
+
+    #[test]
+    fn invisible_subtrees_are_skipped() {
+        let list = build_from(
+            r#"Root: Gem {
+                Menu: PanelGem {
+                    visible: false
+                    Title: LabelGem { text: "Hi" }
+                }
+                Hud: PanelGem { visible: true position: (0, 0) size: (10, 10) }
+            }"#,
+        );
+        assert!(list.texts.is_empty());
+        assert_eq!(list.quads.len(), 1);
+        assert_eq!(list.quads[0].node, vec![1]);
+    }
+
+    #[test]
+    fn popups_draw_on_the_overlay() {
+        let list = build_from(
+            r#"Root: Gem {
+                Quit: DialogGem {
+                    dim_background: true
+                    Ask: LabelGem { text: "Quit?" }
+                    Yes: ButtonGem { text: "Yes" }
+                }
+                Hud: PanelGem {}
+                Score: LabelGem { text: "0" }
+            }"#,
+        );
+        let overlay = list.overlay.expect("overlay");
+        assert_eq!((overlay.quads_from, overlay.texts_from), (1, 1));
+        assert_eq!(overlay.dim, Some([0.0, 0.0, 0.0, 0.5]));
+        let nodes = |from: usize| {
+            list.quads[from..]
+                .iter()
+                .map(|q| q.node.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(nodes(0), [vec![1], vec![0], vec![0, 1]]);
+        assert_eq!(list.texts[1].node, vec![0, 0]);
+        assert_eq!(build_from("Root: Gem { Hud: PanelGem {} }").overlay, None);
+    }
+
+    #[test]
+    fn canvas_layers_ignore_the_camera() {
+        let list = build_from(
+            r#"Root: Gem {
+                View: Camera2DGem { position: (500, 400) }
+                Player: PanelGem { position: (500, 400) }
+                Hud: CanvasLayerGem {
+                    Score: LabelGem { position: (8, 8) text: "0" }
+                }
+                Sky: CanvasLayerGem {
+                    layer: -1
+                    offset: (0, 10)
+                    Clouds: PanelGem { position: (0, 0) }
+                }
+                Quit: DialogGem {}
+            }"#,
+        );
+        let at = |q: &DrawCmd| (q.node.clone(), q.x, q.y);
+        // The sky behind the world, the world centered on the camera
+        assert_eq!(at(&list.quads[0]), (vec![3, 0], 0.0, 10.0));
+        assert_eq!(at(&list.quads[1]), (vec![1], 400.0, 300.0));
+        let overlay = list.overlay.expect("overlay");
+        assert_eq!((overlay.quads_from, overlay.texts_from), (2, 0));
+        // The HUD in front, in window coordinates, under the popups
+        assert_eq!((list.texts[0].x, list.texts[0].y), (8.0, 8.0));
+        assert_eq!((overlay.popup_quads_from, overlay.popup_texts_from), (2, 1));
+        assert_eq!(list.quads[2].node, vec![4]);
+    }
+
+    #[test]
+    fn cameras_draw_the_layers_in_their_cull_mask() {
+        let list = build_from(
+            r#"Root: Gem {
+                Map: Camera2DGem { cull_mask: 5 }
+                Ground: PanelGem {}
+                Debug: PanelGem {
+                    visibility_layer: 2
+                    Path: LabelGem { text: "path" }
+                    Marker: PanelGem { visibility_layer: 4 }
+                }
+                Hud: CanvasLayerGem { Flags: PanelGem { visibility_layer: 2 } }
+            }"#,
+        );
+        let nodes: Vec<NodePath> = list.quads.iter().map(|q| q.node.clone()).collect();
+        // Culled nodes still hold their children; canvas layers aren't culled
+        assert_eq!(nodes, [vec![1], vec![2, 1], vec![3, 0]]);
+        assert_eq!(list.texts[0].node, vec![2, 0]);
+    }
+
+    #[test]
+    fn meshes_are_placed_in_3d_and_seen_by_the_camera() {
+        let list = build_from(
+            r#"Root: Gem {
+                Eye: Camera3DGem { position: (0, 1, 5) fov: 60 }
+                Ship: MeshGem {
+                    mesh: #assets:models:ship.obj
+                    position: (1, 2, 3)
+                    color: #ff0000
+                    Mount: BoneAttachmentGem {
+                        position: (0, 0, -1)
+                        Turret: MeshGem { mesh: #assets:models:ship.glb:mesh(1, 0) scale: (2, 2, 2) }
+                    }
+                }
+            }"#,
+        );
+        assert!(list.quads.is_empty());
+        let [ship, turret] = &list.meshes[..] else {
+            panic!("two meshes");
+        };
+        // Nested meshes are placed relative to their parent, through groups
+        assert_eq!(
+            turret.model,
+            Mat4::translation([1.0, 2.0, 2.0]) * Mat4::scaling([2.0; 3])
+        );
+        assert_eq!((turret.mesh.index, turret.mesh.primitive), (1, Some(0)));
+        assert!(ship.mesh.path.ends_with("ship.obj"));
+        assert_eq!(ship.model, Mat4::translation([1.0, 2.0, 3.0]));
+        assert_eq!(ship.color, [1.0, 0.0, 0.0, 1.0]);
+        let (vw, vh) = scene_tree::viewport_size();
+        let projection = Mat4::perspective(60f32.to_radians(), (vw / vh) as f32, 0.05, 1000.0);
+        assert_eq!(
+            list.view_projection,
+            projection * Mat4::view([0.0, 1.0, 5.0], [0.0; 3])
+        );
+    }
+
+    #[test]
+    fn viewports_draw_the_world_through_their_own_cameras() {
+        let list = build_from(
+            r#"Root: Gem {
+                Ball: PanelGem { position: (100, 100) size: (10, 10) }
+                Left: ViewportGem {
+                    position: (0, 0)
+                    size: (400, 600)
+                    Cam: Camera2DGem { position: (100, 100) }
+                }
+                Right: ViewportGem {
+                    position: (400, 0)
+                    size: (400, 600)
+                    Cam: Camera2DGem { position: (0, 0) }
+                    Lives: LabelGem { position: (8, 8) text: "3" }
+                }
+            }"#,
+        );
+        let [left, right] = &list.views[..] else {
+            panic!("two views");
+        };
+        assert_eq!(right.rect, ((400.0, 0.0), (400.0, 600.0)));
+        // The ball once per view, centered under the left camera
+        let at = |i: usize| (list.quads[i].x, list.quads[i].y);
+        assert_eq!((left.quads.clone(), right.quads.clone()), (0..1, 1..2));
+        assert_eq!((at(0), at(1)), ((200.0, 300.0), (700.0, 400.0)));
+        // Its other children in its own coordinates
+        assert_eq!(right.texts, 0..1);
+        assert_eq!((list.texts[0].x, list.texts[0].y), (408.0, 8.0));
+    }
+
+    #[test]
+    fn preedit_is_drawn_underlined_at_the_caret() {
+        let list = build_from(
+            r#"Root: Gem {
+                Name: LineEditGem { text: "ab" caret: 1 preedit: "niho" }
+            }"#,
+        );
+        let items = &list.texts[0].layout.items;
+        let text: String = items
+            .iter()
+            .filter_map(|item| match item {
+                LaidOut::Glyph { ch, .. } => Some(*ch),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "anihob");
+        // One underline, starting after the first glyph
+        let underlines: Vec<f32> = items
+            .iter()
+            .filter_map(|item| match item {
+                LaidOut::Underline { x, .. } => Some(*x),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(underlines.len(), 1);
+        assert!(underlines[0] > 0.0);
+    }
+
+    #[test]
+    fn labels_produce_text() {
+        let list = build_from(
+            r#"Root: Gem {
+                Title: LabelGem { text: "Hi" position: (5, 6) }
+                Info: RichLabelGem { text: "[b]a[/b] b" size: (12, 40) }
+            }"#,
+        );
+        assert!(list.quads.is_empty());
+        assert_eq!(list.texts.len(), 2);
+        assert_eq!((list.texts[0].x, list.texts[0].y), (5.0, 6.0));
+        // 12px only fits one 12px-wide glyph per line at 16px font size
+        assert_eq!(list.texts[1].layout.line_count, 2);
+    }
+
+    #[test]
+    fn theme_cascades_to_labels() {
+        let list = build_from(
+            r#"Root: Gem {
+                theme_font: #assets:fonts:missing.ttf
+                theme_font_size: 32
+                Menu: Gem {
+                    theme_font_color: #ff0000
+                    Title: LabelGem { text: "Hi" }
+                    Small: LabelGem { text: "Hi" font_size: 8 }
+                }
+            }"#,
+        );
+        // The missing face is replaced by the default font
+        assert_eq!(list.fonts.face_count(), 1);
+        assert!(list.texts[0].layout.height > 4.0 * list.texts[1].layout.height - 1.0);
+        match &list.texts[0].layout.items[0] {
+            LaidOut::Glyph { color, face, .. } => {
+                assert_eq!(*color, [1.0, 0.0, 0.0, 1.0]);
+                assert_eq!(*face, Some(0));
+            }
+            other => panic!("expected a glyph, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn size_expressions_resolve_against_parent_and_viewport() {
+        let list = build_from(
+            r#"Root: Gem {
+                Bar: PanelGem {
+                    size: (viewport.width * 0.5, 100)
+                    Group: Gem {
+                        Fill: PanelGem { size: (parent.width - 20, parent.height / 4) }
+                    }
+                }
+                Tiny: PanelGem { size: (parent.width - viewport.width - 1, 10) }
+            }"#,
+        );
+        let (vw, _) = scene_tree::viewport_size();
+        let size = |i: usize| (list.quads[i].w, list.quads[i].h);
+        assert_eq!(size(0), (vw as f32 * 0.5, 100.0));
+        // `Group` has no size, so `Fill`'s parent is `Bar`
+        assert_eq!(size(1), (vw as f32 * 0.5 - 20.0, 25.0));
+        assert_eq!(size(2), (0.0, 10.0));
+    }
+
+    #[test]
+    fn containers_place_children_with_min_max_and_flags() {
+        let list = build_from(
+            r#"Root: Gem {
+                Menu: VBoxGem {
+                    position: (10, 20)
+                    size: (200, 300)
+                    separation: 10
+                    Title: PanelGem { size: (50, 20) position: (999, 999) }
+                    Body: PanelGem { size: (50, 20) size_flags_vertical: expand }
+                    Hidden: PanelGem { visible: false }
+                    Ok: ButtonGem {
+                        min_size: (80, 40)
+                        max_size: (120, 0)
+                        size_flags_horizontal: shrink_end
+                    }
+                }
+                Loose: PanelGem { size: (500, 10) max_size: (100, 0) min_size: (0, 30) }
+            }"#,
+        );
+        let rect = |i: usize| {
+            let q = &list.quads[i];
+            (q.x, q.y, q.w, q.h)
+        };
+        assert_eq!(rect(0), (10.0, 20.0, 200.0, 300.0));
+        assert_eq!(rect(1), (10.0, 20.0, 200.0, 20.0));
+        // Body takes what Title, the 56px button and two gaps leave
+        assert_eq!(rect(2), (10.0, 50.0, 200.0, 204.0));
+        // The button keeps its theme width, capped at 120, at the right edge
+        assert_eq!(rect(3), (90.0, 264.0, 120.0, 56.0));
+        assert_eq!((rect(4).2, rect(4).3), (100.0, 30.0));
+    }
+
+    #[test]
+    fn padding_and_margins_inset_children() {
+        let list = build_from(
+            r#"Root: Gem {
+                Card: PanelGem {
+                    position: (0, 0)
+                    size: (200, 100)
+                    padding: (10, 20)
+                    Body: PanelGem { position: (20, 10) size: (parent.width, parent.height) }
+                }
+                List: HBoxGem {
+                    position: (0, 200)
+                    size: (300, 50)
+                    padding: (1, 2, 3, 4)
+                    separation: 0
+                    A: PanelGem { size: (40, 10) margin: 5 }
+                    B: PanelGem { size: (40, 10) }
+                }
+            }"#,
+        );
+        let rect = |i: usize| {
+            let q = &list.quads[i];
+            (q.x, q.y, q.w, q.h)
+        };
+        // The panel's background covers its padding; its child sees the inside
+        assert_eq!(rect(0), (0.0, 0.0, 200.0, 100.0));
+        assert_eq!(rect(1), (20.0, 10.0, 160.0, 80.0));
+        assert_eq!(rect(3), (9.0, 206.0, 40.0, 36.0));
+        assert_eq!(rect(4), (54.0, 201.0, 40.0, 46.0));
+    }
+
+    #[test]
+    fn tab_containers_draw_their_bar_and_current_page() {
+        let list = build_from(
+            r#"Root: Gem {
+                Settings: TabContainerGem {
+                    position: (0, 0)
+                    size: (300, 200)
+                    current_tab: 1
+                    Video: PanelGem {}
+                    Audio: PanelGem { tab_title: "Sound" }
+                    Keys: PanelGem {}
+                }
+                Faq: AccordionGem {
+                    position: (400, 0)
+                    size: (100, 200)
+                    current_tab: -1
+                    One: PanelGem {}
+                    Two: PanelGem {}
+                }
+            }"#,
+        );
+        // Container, three tabs, the current page, the accordion and its headers
+        assert_eq!(list.quads.len(), 8);
+        let tab = &list.quads[2];
+        assert_eq!(
+            (tab.x, tab.w, tab.color),
+            (100.0, 100.0, current_tab_color())
+        );
+        let page = &list.quads[4];
+        assert_eq!(page.node, vec![0, 1]);
+        assert_eq!((page.x, page.y, page.w, page.h), (0.0, 28.0, 300.0, 172.0));
+        assert_eq!((list.quads[7].y, list.quads[7].color), (28.0, TAB_COLOR));
+        assert_eq!(list.texts.len(), 5);
+    }
+
+    #[test]
+    fn lists_draw_scrolled_rows_from_items_and_children() {
+        let list = build_from(
+            r#"Root: Gem {
+                Bag: ItemListGem {
+                    position: (0, 0)
+                    size: (200, 48)
+                    items: ("Sword", "Shield")
+                    selected: 2
+                    scroll_offset: 24
+                    Bow: Gem { text: "Longbow" }
+                }
+                Files: TreeGem {
+                    position: (300, 0)
+                    size: (200, 100)
+                    Scenes: Gem { Level: Gem {} }
+                }
+            }"#,
+        );
+        // Each list, then the selected row; "Sword" scrolled out of view
+        assert_eq!(list.quads.len(), 3);
+        let row = &list.quads[1];
+        assert_eq!((row.y, row.h, row.color), (24.0, 24.0, SELECTED_ROW_COLOR));
+        assert_eq!(list.texts[0].x, item_list::INDENT * 0.5);
+        // Shield, Longbow, then the tree's fold marker and its two rows
+        assert_eq!(list.texts.len(), 5);
+        assert_eq!(list.texts[2].x, 300.0);
+        assert_eq!(list.texts[4].x, 300.0 + 2.0 * item_list::INDENT);
+    }
+
+    #[test]
+    fn dropdowns_show_their_selection_and_menus_fit_their_items() {
+        let list = build_from(
+            r#"Root: Gem {
+                Quality: OptionButtonGem {
+                    position: (0, 0)
+                    items: ("Low", "High")
+                    selected: 1
+                }
+                Actions: PopupMenuGem {
+                    position: (0, 100)
+                    size: (120, 10)
+                    items: ("Copy", "Paste", "Delete")
+                    focused_item: 1
+                }
+            }"#,
+        );
+        let menu = &list.quads[list.overlay.unwrap().quads_from];
+        assert_eq!((menu.y, menu.w, menu.h), (100.0, 120.0, 72.0));
+        assert_eq!(list.quads.last().unwrap().y, 124.0);
+        let content = |t: &TextCmd| {
+            t.layout
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    LaidOut::Glyph { ch, .. } => Some(*ch),
+                    _ => None,
+                })
+                .collect::<String>()
+        };
+        let texts: Vec<String> = list.texts.iter().map(content).collect();
+        assert_eq!(texts, ["High", "Copy", "Paste", "Delete"]);
+    }
+
+    #[test]
+    fn toggles_draw_an_indicator_before_their_text() {
+        let list = build_from(
+            r#"Root: Gem {
+                Music: CheckBoxGem { position: (0, 0) text: "Music" pressed: true }
+                Easy: RadioButtonGem {
+                    position: (0, 40)
+                    size: (160, 28)
+                    text: "Easy"
+                    color: #000000
+                }
+            }"#,
+        );
+        // The check box's box and mark, then the unpressed radio's box
+        assert_eq!(list.quads.len(), 3);
+        let (bx, mark) = (&list.quads[0], &list.quads[1]);
+        assert_eq!((bx.y, bx.w), (4.0, 20.0));
+        assert_eq!((mark.x, mark.w), (4.0, 12.0));
+        assert_eq!(
+            mark.color,
+            value_color(&ast::Value::Color([0x4d, 0x99, 0xe6, 0xff])).unwrap()
+        );
+        assert_eq!(list.quads[2].color, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(list.texts[0].x, 28.0);
+    }
+
+    #[test]
+    fn color_pickers_draw_square_bars_and_markers() {
+        let list = build_from(
+            r#"Root: Gem {
+                Tint: ColorPickerGem { position: (0, 0) size: (212, 100) color: #00ff0080 }
+            }"#,
+        );
+        // Square (two layers, marker), hue bar (bar, marker), alpha (backing, bar, marker)
+        assert_eq!(list.quads.len(), 8);
+        let base = &list.quads[0];
+        assert_eq!((base.w, base.h), (156.0, 100.0));
+        assert_eq!(
+            base.gradient.as_ref().unwrap().stops[1].1,
+            [0.0, 1.0, 0.0, 1.0]
+        );
+        // Pure green sits at the top-right of the square, a third down the hue bar
+        assert_eq!((list.quads[2].x, list.quads[2].y), (153.0, -3.0));
+        assert!((list.quads[4].y - (100.0 / 3.0 - 1.0)).abs() < 0.01);
+        let alpha_marker = &list.quads[7];
+        assert_eq!(alpha_marker.x, 192.0);
+        assert!((alpha_marker.y - 48.8).abs() < 0.01);
+    }
+}
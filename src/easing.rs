@@ -0,0 +1,269 @@
+//! Easing and interpolation library shared by curves, gradients and scripts.
+//! Easings map progress `t` in 0..=1 to 0..=1 and are named `<in|out|in_out>_<kind>`
+//! (e.g. `out_cubic`), or `linear`. Scripts reach them, with `smoothstep`,
+//! `remap`, `lerp` and gradient sampling, through the `Math` singleton.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+/// Easing kinds, each available as `in_`, `out_` and `in_out_`
+pub const KINDS: [&str; 10] = [
+    "quad", "cubic", "quart", "quint", "sine", "expo", "circ", "back", "elastic", "bounce",
+];
+
+/// `t` eased by the easing called `name`
+pub fn ease(t: f64, name: &str) -> Result<f64, String> {
+    let t = t.clamp(0.0, 1.0);
+    if name == "linear" {
+        return Ok(t);
+    }
+    let (dir, kind) = if let Some(kind) = name.strip_prefix("in_out_") {
+        ("in_out", kind)
+    } else if let Some(kind) = name.strip_prefix("in_") {
+        ("in", kind)
+    } else if let Some(kind) = name.strip_prefix("out_") {
+        ("out", kind)
+    } else {
+        ("", name)
+    };
+    if dir.is_empty() || !KINDS.contains(&kind) {
+        return Err(format!(
+            "Unknown easing '{}'; expected linear or in_/out_/in_out_ followed by one of {}",
+            name,
+            KINDS.join(", ")
+        ));
+    }
+    Ok(match dir {
+        "in" => ease_in(kind, t),
+        "out" => 1.0 - ease_in(kind, 1.0 - t),
+        _ if t < 0.5 => ease_in(kind, 2.0 * t) / 2.0,
+        _ => 1.0 - ease_in(kind, 2.0 - 2.0 * t) / 2.0,
+    })
+}
+
+/// The `in_` form of each kind; `out_` and `in_out_` are built from it
+fn ease_in(kind: &str, t: f64) -> f64 {
+    match kind {
+        "quad" => t * t,
+        "cubic" => t.powi(3),
+        "quart" => t.powi(4),
+        "quint" => t.powi(5),
+        "sine" => 1.0 - (t * PI / 2.0).cos(),
+        "expo" if t == 0.0 => 0.0,
+        "expo" => 2f64.powf(10.0 * t - 10.0),
+        "circ" => 1.0 - (1.0 - t * t).sqrt(),
+        "back" => {
+            const C1: f64 = 1.70158;
+            (C1 + 1.0) * t.powi(3) - C1 * t * t
+        }
+        "elastic" if t == 0.0 || t == 1.0 => t,
+        "elastic" => -(2f64.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin(),
+        "bounce" => 1.0 - bounce_out(1.0 - t),
+        _ => t,
+    }
+}
+
+fn bounce_out(t: f64) -> f64 {
+    const N: f64 = 7.5625;
+    const D: f64 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Hermite step from 0 at `edge0` to 1 at `edge1`
+pub fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    if edge0 == edge1 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// `value` moved from the range `in_min..in_max` to `out_min..out_max`,
+/// without clamping
+pub fn remap(value: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64 {
+    if in_min == in_max {
+        return out_min;
+    }
+    lerp(out_min, out_max, (value - in_min) / (in_max - in_min))
+}
+
+/// Color at `t` along (offset, rgba) stops sorted by offset, blending
+/// linearly between neighbours and holding the end colors beyond them;
+/// matches the gradient shader
+pub fn sample_stops(stops: &[(f32, [f32; 4])], t: f32) -> [f32; 4] {
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return [0.0; 4];
+    };
+    if t <= first.0 {
+        return first.1;
+    }
+    if t >= last.0 {
+        return last.1;
+    }
+    let i = stops.partition_point(|s| s.0 <= t) - 1;
+    let ((a, ca), (b, cb)) = (stops[i], stops[i + 1]);
+    let x = if b > a { (t - a) / (b - a) } else { 1.0 };
+    std::array::from_fn(|c| ca[c] + (cb[c] - ca[c]) * x)
+}
+
+/// The script-facing `Math` singleton
+pub fn new_math() -> ObjectRef {
+    let obj = ObjectInner::base("Math");
+    // ease(t, name)
+    ObjectInner::insert_method(
+        &obj,
+        "ease",
+        Arc::new(|_, args| {
+            let [t, Value::String(name)] = args else {
+                return Err("ease expects (t, name)".to_string());
+            };
+            let t = t.as_f64().ok_or("ease expects a number t")?;
+            ease(t, name).map(Value::Float)
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "smoothstep",
+        Arc::new(|_, args| {
+            let [e0, e1, x] = numbers::<3>(args, "smoothstep(edge0, edge1, x)")?;
+            Ok(Value::Float(smoothstep(e0, e1, x)))
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "remap",
+        Arc::new(|_, args| {
+            let [v, a, b, c, d] =
+                numbers::<5>(args, "remap(value, in_min, in_max, out_min, out_max)")?;
+            Ok(Value::Float(remap(v, a, b, c, d)))
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "lerp",
+        Arc::new(|_, args| {
+            let [a, b, t] = numbers::<3>(args, "lerp(a, b, t)")?;
+            Ok(Value::Float(lerp(a, b, t)))
+        }),
+    );
+    // sample_gradient(((offset, color), ...), t): the color at t
+    ObjectInner::insert_method(
+        &obj,
+        "sample_gradient",
+        Arc::new(|_, args| {
+            let usage = "sample_gradient expects ((offset, color), ...) and t";
+            let [Value::Array(entries), t] = args else {
+                return Err(usage.to_string());
+            };
+            let t = t.as_f64().ok_or(usage)?;
+            let mut stops = Vec::new();
+            for entry in entries {
+                let stop = match entry {
+                    Value::Array(pair) if pair.len() == 2 => {
+                        pair[0].as_f64().zip(color_arg(&pair[1]))
+                    }
+                    _ => None,
+                };
+                let (offset, color) = stop.ok_or(usage)?;
+                stops.push((offset as f32, color));
+            }
+            stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let rgba = sample_stops(&stops, t as f32);
+            Ok(Value::Color(Color::from_array(rgba)))
+        }),
+    );
+    obj
+}
+
+fn numbers<const N: usize>(args: &[Value], usage: &str) -> Result<[f64; N], String> {
+    let mut out = [0.0; N];
+    if args.len() != N {
+        return Err(format!("expected {}", usage));
+    }
+    for (slot, arg) in out.iter_mut().zip(args) {
+        *slot = arg.as_f64().ok_or_else(|| format!("expected {}", usage))?;
+    }
+    Ok(out)
+}
+
+/// A color, a `#rrggbb`/`#rrggbbaa` string, or 3-4 channels in 0..=255
+fn color_arg(value: &Value) -> Option<[f32; 4]> {
+    if let Some(color) = value.as_color() {
+        return Some(color.to_array());
+    }
+    match value {
+        Value::Array(items) if items.len() == 3 || items.len() == 4 => {
+            let mut out = [1.0; 4];
+            for (slot, item) in out.iter_mut().zip(items) {
+                *slot = item.as_f64()? as f32 / 255.0;
+            }
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easings_interpolation_and_gradients() {
+        for kind in KINDS {
+            for dir in ["in", "out", "in_out"] {
+                let name = format!("{}_{}", dir, kind);
+                assert!(ease(0.0, &name).unwrap().abs() < 1e-9, "{}", name);
+                assert!((ease(1.0, &name).unwrap() - 1.0).abs() < 1e-9, "{}", name);
+            }
+        }
+        assert_eq!(ease(0.5, "in_quad"), Ok(0.25));
+        assert_eq!(ease(0.5, "out_cubic"), Ok(0.875));
+        assert_eq!(ease(0.5, "in_out_cubic"), Ok(0.5));
+        assert!(ease(0.5, "sideways").is_err());
+        assert_eq!(smoothstep(0.0, 10.0, 5.0), 0.5);
+        assert_eq!(smoothstep(0.0, 10.0, -1.0), 0.0);
+        assert_eq!(remap(5.0, 0.0, 10.0, 100.0, 200.0), 150.0);
+
+        let stops = [(0.0, [0.0, 0.0, 0.0, 1.0]), (1.0, [1.0, 0.5, 0.0, 1.0])];
+        assert_eq!(sample_stops(&stops, 0.5), [0.5, 0.25, 0.0, 1.0]);
+        assert_eq!(sample_stops(&stops, 2.0), stops[1].1);
+
+        let math = new_math();
+        let call = |name: &str, args: &[Value]| math.call_method(name, args).unwrap();
+        assert_eq!(
+            call(
+                "ease",
+                &[Value::Float(0.5), Value::String("in_quad".into())]
+            ),
+            Value::Float(0.25)
+        );
+        let gradient = Value::Array(vec![
+            Value::Array(vec![Value::Int(1), Value::String("#ffffff".into())]),
+            Value::Array(vec![Value::Int(0), Value::String("#000000".into())]),
+        ]);
+        assert_eq!(
+            call("sample_gradient", &[gradient, Value::Float(0.5)]),
+            Value::Color(Color::rgba(0.5, 0.5, 0.5, 1.0))
+        );
+        assert!(math.call_method("remap", &[Value::Int(1)]).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+//! Editing interactions for the preview window.
+//! Dragging a node rewrites its `position` in the in-memory AST; saving prints
+//! the AST back into the scene's .gem file. Drags can snap to a grid and to
+//! the edges and centers of the node's siblings. Nodes with properties the
+//! parser had to skip are outlined in red, and such a scene isn't saved, as
+//! printing it would drop them.
+
+use std::fs;
+use std::path::Path;
+
+use crate::ast::{GemFile, PropertyError, Value};
+use crate::commands::{self, Command, UndoStack, find_node, find_node_mut};
+use crate::defines;
+use crate::draw_list::{self, DrawCmd, DrawList, NodePath, TextCmd};
+use crate::printer;
+use crate::renderer::GemRenderer;
+use crate::uid;
+
+const OUTLINE_WIDTH: f32 = 2.0;
+const OUTLINE_COLOR: [f32; 4] = [1.0, 0.8, 0.1, 1.0];
+const ERROR_COLOR: [f32; 4] = [0.9, 0.15, 0.15, 1.0];
+const DUPLICATE_OFFSET: i64 = 16;
+
+/// How close (in pixels) an edge has to come to a sibling's before it snaps
+const GUIDE_DISTANCE: f32 = 6.0;
+const GUIDE_COLOR: [f32; 4] = [1.0, 0.2, 0.8, 0.9];
+const GRID_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.08];
+/// Grid lines closer together than this are thinned out when drawn
+const GRID_MIN_SPACING: f32 = 8.0;
+const GRID_MIN: f32 = 4.0;
+const GRID_MAX: f32 = 128.0;
+
+type Rect = (f32, f32, f32, f32);
+
+struct Drag {
+    node: NodePath,
+    /// Cursor position minus the node position when the drag started
+    grab: (f32, f32),
+    /// `position` before the drag, for undo
+    from: Option<Value>,
+    /// The node's drawn bounds relative to its position
+    extent: Option<Rect>,
+    /// Sibling bounds captured at the start of the drag
+    siblings: Vec<Rect>,
+}
+
+/// Drag snapping settings; they survive scene reloads
+#[derive(Clone, Copy, Debug)]
+pub struct Snap {
+    pub grid: bool,
+    pub grid_size: f32,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            grid: false,
+            grid_size: 16.0,
+        }
+    }
+}
+
+impl Snap {
+    /// Double (or halve) the grid spacing within its limits
+    pub fn scale_grid(&mut self, larger: bool) {
+        let size = if larger {
+            self.grid_size * 2.0
+        } else {
+            self.grid_size / 2.0
+        };
+        self.grid_size = size.clamp(GRID_MIN, GRID_MAX);
+    }
+}
+
+/// An alignment line shown while a dragged edge or center is snapped to a sibling's
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Guide {
+    Vertical(f32),
+    Horizontal(f32),
+}
+
+#[derive(Default)]
+pub struct Editor {
+    drag: Option<Drag>,
+    pub selected: Option<NodePath>,
+    /// Unsaved edits since the last load or save
+    pub dirty: bool,
+    pub history: UndoStack,
+    pub snap: Snap,
+    guides: Vec<Guide>,
+}
+
+/// Topmost node drawn under `(x, y)`: text is drawn after quads, later after earlier
+pub fn node_at(list: &DrawList, x: f32, y: f32) -> Option<NodePath> {
+    let inside =
+        |rx: f32, ry: f32, rw: f32, rh: f32| x >= rx && y >= ry && x < rx + rw && y < ry + rh;
+    let hit = |quads: &[DrawCmd], texts: &[TextCmd]| {
+        texts
+            .iter()
+            .rev()
+            .find(|t| inside(t.x, t.y, t.layout.width, t.layout.height))
+            .map(|t| t.node.clone())
+            .or_else(|| {
+                quads
+                    .iter()
+                    .rev()
+                    .find(|q| inside(q.x, q.y, q.w, q.h))
+                    .map(|q| q.node.clone())
+            })
+    };
+    // Popups are drawn over the rest, so they are picked first
+    let (quads_from, texts_from) = list.overlay_start();
+    hit(&list.quads[quads_from..], &list.texts[texts_from..])
+        .or_else(|| hit(&list.quads[..quads_from], &list.texts[..texts_from]))
+}
+
+/// Union of everything `node` draws, in pixels (x, y, w, h)
+pub fn node_bounds(list: &DrawList, node: &[usize]) -> Option<(f32, f32, f32, f32)> {
+    let rects = list
+        .quads
+        .iter()
+        .filter(|q| q.node == node)
+        .map(|q| (q.x, q.y, q.w, q.h))
+        .chain(
+            list.texts
+                .iter()
+                .filter(|t| t.node == node)
+                .map(|t| (t.x, t.y, t.layout.width, t.layout.height)),
+        );
+    rects.reduce(|a, b| {
+        let (x0, y0) = (a.0.min(b.0), a.1.min(b.1));
+        let (x1, y1) = ((a.0 + a.2).max(b.0 + b.2), (a.1 + a.3).max(b.1 + b.3));
+        (x0, y0, x1 - x0, y1 - y0)
+    })
+}
+
+/// Snap one axis of a node position. `start` and `len` are the node's drawn
+/// extent relative to `pos`; `targets` are sibling edges and centers. A sibling
+/// match wins over the grid and is returned so it can be drawn as a guide.
+fn snap_axis(
+    pos: f32,
+    start: f32,
+    len: f32,
+    targets: &[f32],
+    grid: Option<f32>,
+) -> (f32, Option<f32>) {
+    let edges = [start, start + len / 2.0, start + len];
+    let nearest = targets
+        .iter()
+        .flat_map(|t| edges.iter().map(move |e| (*t, *t - (pos + e))))
+        .filter(|(_, d)| d.abs() <= GUIDE_DISTANCE)
+        .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()));
+    match (nearest, grid) {
+        (Some((target, d)), _) => (pos + d, Some(target)),
+        (None, Some(g)) => ((pos / g).round() * g, None),
+        (None, None) => (pos, None),
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select and start dragging the node under the cursor; clicking empty
+    /// space clears the selection. Returns whether a node was grabbed.
+    pub fn press(&mut self, scene: &GemFile, list: &DrawList, cursor: (f32, f32)) -> bool {
+        self.selected = node_at(list, cursor.0, cursor.1);
+        self.drag = self.selected.as_ref().and_then(|node| {
+            let decl = find_node(&scene.root, node)?;
+            let pos = draw_list::node_position(decl);
+            let extent = node_bounds(list, node).map(|(x, y, w, h)| (x - pos.0, y - pos.1, w, h));
+            let siblings = node.split_last().map_or_else(Vec::new, |(index, parent)| {
+                let count = find_node(&scene.root, parent).map_or(0, |p| p.children.len());
+                (0..count)
+                    .filter(|i| i != index)
+                    .filter_map(|i| {
+                        let mut sibling = parent.to_vec();
+                        sibling.push(i);
+                        node_bounds(list, &sibling)
+                    })
+                    .collect()
+            });
+            Some(Drag {
+                node: node.clone(),
+                grab: (cursor.0 - pos.0, cursor.1 - pos.1),
+                from: commands::get_property(decl, "position").cloned(),
+                extent,
+                siblings,
+            })
+        });
+        self.guides.clear();
+        self.drag.is_some()
+    }
+
+    /// Move the dragged node so it stays under the cursor. Returns whether the
+    /// scene changed and the draw list needs rebuilding.
+    pub fn drag_to(&mut self, scene: &mut GemFile, cursor: (f32, f32)) -> bool {
+        let Some(drag) = &self.drag else {
+            return false;
+        };
+        let Some(decl) = find_node_mut(&mut scene.root, &drag.node) else {
+            return false;
+        };
+        let grid = self.snap.grid.then_some(self.snap.grid_size);
+        let (ex, ey, ew, eh) = drag.extent.unwrap_or_default();
+        let xs: Vec<f32> = drag
+            .siblings
+            .iter()
+            .flat_map(|r| [r.0, r.0 + r.2 / 2.0, r.0 + r.2])
+            .collect();
+        let ys: Vec<f32> = drag
+            .siblings
+            .iter()
+            .flat_map(|r| [r.1, r.1 + r.3 / 2.0, r.1 + r.3])
+            .collect();
+        let (x, vertical) = snap_axis(cursor.0 - drag.grab.0, ex, ew, &xs, grid);
+        let (y, horizontal) = snap_axis(cursor.1 - drag.grab.1, ey, eh, &ys, grid);
+        self.guides = vertical
+            .map(Guide::Vertical)
+            .into_iter()
+            .chain(horizontal.map(Guide::Horizontal))
+            .collect();
+        // Whole pixels, and no negatives until the parser accepts them
+        let x = x.round().max(0.0) as i64;
+        let y = y.round().max(0.0) as i64;
+        let value = Value::Tuple(vec![Value::Integer(x), Value::Integer(y)]);
+        if decl
+            .properties
+            .iter()
+            .any(|p| p.key == "position" && p.value == value)
+        {
+            return false;
+        }
+        commands::set_property(decl, "position", Some(value));
+        self.dirty = true;
+        true
+    }
+
+    /// Finish a drag, recording it as one undoable move
+    pub fn release(&mut self, scene: &GemFile) {
+        self.guides.clear();
+        let Some(drag) = self.drag.take() else {
+            return;
+        };
+        let to = find_node(&scene.root, &drag.node)
+            .and_then(|decl| commands::get_property(decl, "position"))
+            .cloned();
+        if let Some(to) = to
+            && drag.from.as_ref() != Some(&to)
+        {
+            self.history.record(Command::MoveNode {
+                node: drag.node,
+                from: drag.from,
+                to,
+            });
+        }
+    }
+
+    /// Delete the selected node. Returns whether the scene changed.
+    pub fn delete_selected(&mut self, scene: &mut GemFile) -> Result<bool, String> {
+        let Some((index, parent)) = self.selected.as_ref().and_then(|n| n.split_last()) else {
+            return Ok(false);
+        };
+        let cmd = Command::RemoveChild {
+            parent: parent.to_vec(),
+            index: *index,
+            child: None,
+        };
+        self.history.execute(scene, cmd)?;
+        self.selected = None;
+        self.drag = None;
+        self.dirty = true;
+        Ok(true)
+    }
+
+    /// Insert a copy of the selected node right after it, offset so both stay
+    /// visible, and select the copy. Returns whether the scene changed.
+    pub fn duplicate_selected(&mut self, scene: &mut GemFile) -> Result<bool, String> {
+        let Some(node) = self.selected.clone() else {
+            return Ok(false);
+        };
+        let Some((index, parent)) = node.split_last() else {
+            return Ok(false);
+        };
+        let Some(mut child) = find_node(&scene.root, &node).cloned() else {
+            return Ok(false);
+        };
+        let (x, y) = draw_list::node_position(&child);
+        commands::set_property(
+            &mut child,
+            "position",
+            Some(Value::Tuple(vec![
+                Value::Integer(x as i64 + DUPLICATE_OFFSET),
+                Value::Integer(y as i64 + DUPLICATE_OFFSET),
+            ])),
+        );
+        child.name = format!("{}Copy", child.name);
+        uid::clear(&mut child);
+        let cmd = Command::AddChild {
+            parent: parent.to_vec(),
+            index: index + 1,
+            child,
+        };
+        self.history.execute(scene, cmd)?;
+        let mut copy = parent.to_vec();
+        copy.push(index + 1);
+        self.selected = Some(copy);
+        self.dirty = true;
+        Ok(true)
+    }
+
+    /// Set a property of the selected node as one undoable edit; `None`
+    /// removes it. Returns whether the scene changed.
+    pub fn set_selected_property(
+        &mut self,
+        scene: &mut GemFile,
+        key: &str,
+        value: Option<Value>,
+    ) -> Result<bool, String> {
+        let Some(node) = self.selected.clone() else {
+            return Ok(false);
+        };
+        let Some(decl) = find_node(&scene.root, &node) else {
+            return Ok(false);
+        };
+        let old = commands::get_property(decl, key).cloned();
+        if old == value {
+            return Ok(false);
+        }
+        let cmd = Command::SetProperty {
+            node,
+            key: key.to_string(),
+            old,
+            new: value,
+        };
+        self.history.execute(scene, cmd)?;
+        self.dirty = true;
+        Ok(true)
+    }
+
+    /// Hide the selected node with `visible: false`, or show it again by
+    /// dropping that. Returns whether the scene changed.
+    pub fn toggle_selected_visible(&mut self, scene: &mut GemFile) -> Result<bool, String> {
+        let hidden = self
+            .selected
+            .as_ref()
+            .and_then(|node| find_node(&scene.root, node))
+            .is_some_and(|decl| {
+                commands::get_property(decl, "visible") == Some(&Value::Bool(false))
+            });
+        self.set_selected_property(scene, "visible", (!hidden).then_some(Value::Bool(false)))
+    }
+
+    /// Undo (or with `redo`, re-apply) the last edit. Returns whether the scene changed.
+    pub fn step_history(&mut self, scene: &mut GemFile, redo: bool) -> Result<bool, String> {
+        self.drag = None;
+        let changed = if redo {
+            self.history.redo(scene)?
+        } else {
+            self.history.undo(scene)?
+        };
+        if changed {
+            self.dirty = true;
+            // Removing or re-adding children can invalidate the selected path
+            if let Some(node) = &self.selected
+                && find_node(&scene.root, node).is_none()
+            {
+                self.selected = None;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Write the scene back to its source file
+    pub fn save(&mut self, scene: &GemFile, path: &Path) -> Result<(), String> {
+        if !scene.errors.is_empty() {
+            return Err(format!(
+                "{} malformed properties would be lost; fix them in the file and reload",
+                scene.errors.len()
+            ));
+        }
+        if fs::read_to_string(path).is_ok_and(|source| defines::has_conditionals(&source)) {
+            return Err(
+                "the file has #if blocks, which saving would flatten; edit it as text".into(),
+            );
+        }
+        fs::write(path, printer::print_scene(scene))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Grid (when snapping to it), active guides, error markers and the
+    /// selection outline
+    pub fn draw(&self, renderer: &GemRenderer, list: &DrawList, errors: &[PropertyError]) {
+        let (vw, vh) = renderer.viewport();
+        let (vw, vh) = (vw as f32, vh as f32);
+        if self.snap.grid {
+            let step =
+                self.snap.grid_size * (GRID_MIN_SPACING / self.snap.grid_size).ceil().max(1.0);
+            let mut x = step;
+            while x < vw {
+                renderer.render_line((x, 0.0), (x, vh), 1.0, GRID_COLOR);
+                x += step;
+            }
+            let mut y = step;
+            while y < vh {
+                renderer.render_line((0.0, y), (vw, y), 1.0, GRID_COLOR);
+                y += step;
+            }
+        }
+        for guide in &self.guides {
+            match *guide {
+                Guide::Vertical(x) => renderer.render_line((x, 0.0), (x, vh), 1.0, GUIDE_COLOR),
+                Guide::Horizontal(y) => renderer.render_line((0.0, y), (vw, y), 1.0, GUIDE_COLOR),
+            }
+        }
+
+        for bounds in errors.iter().filter_map(|e| node_bounds(list, &e.node)) {
+            outline(renderer, bounds, ERROR_COLOR);
+        }
+        if let Some(bounds) = self.selected.as_ref().and_then(|n| node_bounds(list, n)) {
+            outline(renderer, bounds, OUTLINE_COLOR);
+        }
+    }
+}
+
+fn outline(renderer: &GemRenderer, (x, y, w, h): Rect, color: [f32; 4]) {
+    let t = OUTLINE_WIDTH;
+    renderer.render_px_quad(x - t, y - t, w + t * 2.0, t, color);
+    renderer.render_px_quad(x - t, y + h, w + t * 2.0, t, color);
+    renderer.render_px_quad(x - t, y, t, h, color);
+    renderer.render_px_quad(x + w, y, t, h, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn drag_moves_and_prints_position() {
+        let src = r#"Root: Gem {
+            Panel: PanelGem { position: (10, 10) size: (50, 50) }
+            Title: LabelGem { text: "Hi" }
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let mut scene = Parser::new(tokens).parse_scene().unwrap();
+        let list = draw_list::build(&scene);
+        let mut editor = Editor::new();
+
+        assert!(editor.press(&scene, &list, (20.0, 30.0)));
+        assert_eq!(editor.selected, Some(vec![0]));
+        assert!(editor.drag_to(&mut scene, (25.0, 40.0)));
+        assert!(!editor.drag_to(&mut scene, (25.0, 40.0)));
+        editor.release(&scene);
+        assert!(editor.dirty);
+        assert_eq!(
+            draw_list::node_position(&scene.root.children[0]),
+            (15.0, 20.0)
+        );
+
+        // The label has no position yet: dragging adds one
+        assert!(editor.press(&scene, &list, (101.0, 101.0)));
+        assert!(editor.drag_to(&mut scene, (111.0, 101.0)));
+        let printed = printer::print_scene(&scene);
+        assert!(printed.contains("position: (15, 20)"));
+        assert!(printed.contains("position: (110, 100)"));
+        editor.release(&scene);
+
+        // Each drag is a single undo step; undoing the label's drops the added property
+        assert!(editor.step_history(&mut scene, false).unwrap());
+        assert!(!printer::print_scene(&scene).contains("(110, 100)"));
+        assert!(editor.step_history(&mut scene, false).unwrap());
+        assert_eq!(
+            draw_list::node_position(&scene.root.children[0]),
+            (10.0, 10.0)
+        );
+        assert!(editor.step_history(&mut scene, true).unwrap());
+        assert_eq!(
+            draw_list::node_position(&scene.root.children[0]),
+            (15.0, 20.0)
+        );
+
+        editor.selected = Some(vec![1]);
+        assert!(editor.duplicate_selected(&mut scene).unwrap());
+        assert_eq!(scene.root.children[2].name, "TitleCopy");
+        assert_eq!(editor.selected, Some(vec![2]));
+        assert!(editor.delete_selected(&mut scene).unwrap());
+        assert_eq!(scene.root.children.len(), 2);
+        assert!(editor.step_history(&mut scene, false).unwrap());
+        assert_eq!(scene.root.children.len(), 3);
+
+        // Hiding sets `visible: false`; undo takes the property away again
+        editor.selected = Some(vec![0]);
+        assert!(editor.toggle_selected_visible(&mut scene).unwrap());
+        assert!(printer::print_scene(&scene).contains("visible: false"));
+        assert!(editor.step_history(&mut scene, false).unwrap());
+        assert!(!printer::print_scene(&scene).contains("visible"));
+
+        assert!(!editor.press(&scene, &list, (500.0, 500.0)));
+        assert_eq!(editor.selected, None);
+    }
+
+    #[test]
+    fn drags_snap_to_grid_and_sibling_guides() {
+        let src = r#"Root: Gem {
+            Anchor: PanelGem { position: (200, 40) size: (50, 50) }
+            Box: PanelGem { position: (10, 10) size: (20, 20) }
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let mut scene = Parser::new(tokens).parse_scene().unwrap();
+        let list = draw_list::build(&scene);
+        let mut editor = Editor::new();
+        editor.snap.grid = true;
+
+        // Far from the anchor: position rounds to the 16px grid
+        assert!(editor.press(&scene, &list, (15.0, 15.0)));
+        editor.drag_to(&mut scene, (26.0, 130.0));
+        assert_eq!(
+            draw_list::node_position(&scene.root.children[1]),
+            (16.0, 128.0)
+        );
+        assert!(editor.guides.is_empty());
+
+        // Box's center lands 3px off the anchor's center (225): the guide wins
+        editor.drag_to(&mut scene, (217.0, 133.0));
+        assert_eq!(
+            draw_list::node_position(&scene.root.children[1]),
+            (215.0, 128.0)
+        );
+        assert_eq!(editor.guides, [Guide::Vertical(225.0)]);
+        editor.release(&scene);
+        assert!(editor.guides.is_empty());
+
+        editor.snap.scale_grid(true);
+        assert_eq!(editor.snap.grid_size, 32.0);
+        for _ in 0..8 {
+            editor.snap.scale_grid(false);
+        }
+        assert_eq!(editor.snap.grid_size, GRID_MIN);
+    }
+}
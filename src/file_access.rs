@@ -0,0 +1,306 @@
+//! File I/O for scripts, through the virtual filesystem. Script paths must
+//! name their root: `res://` (the project, and any mounted packs) can only be
+//! read, and `user://` (the per-user data directory) is where games keep saves
+//! and settings. `FileAccess.open(path, mode)` returns a handle with
+//! `read_string`, `read_bytes`, `store_string`, `store_bytes` and `close`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor, Read, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::object::{MethodTable, ObjectInner, ObjectRef, define_method, register_class};
+use crate::value::Value;
+use crate::vfs::{self, VPath};
+
+pub const READ: i64 = 1;
+/// Create or truncate the file
+pub const WRITE: i64 = 2;
+pub const READ_WRITE: i64 = 3;
+
+/// What an open handle reads from: `res://` files are read whole through the
+/// virtual filesystem, `user://` files are opened on disk
+enum Opened {
+    Res(Cursor<Vec<u8>>),
+    User(File),
+}
+
+impl Opened {
+    fn read_to_end(&mut self, buf: &mut Vec<u8>, limit: Option<u64>) -> io::Result<usize> {
+        match (self, limit) {
+            (Opened::Res(c), Some(n)) => c.take(n).read_to_end(buf),
+            (Opened::Res(c), None) => c.read_to_end(buf),
+            (Opened::User(f), Some(n)) => f.take(n).read_to_end(buf),
+            (Opened::User(f), None) => f.read_to_end(buf),
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Opened::Res(_) => Err(io::Error::other("res:// is read-only")),
+            Opened::User(f) => f.write_all(data),
+        }
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        match self {
+            Opened::Res(c) => Ok(c.get_ref().len() as u64),
+            Opened::User(f) => f.metadata().map(|m| m.len()),
+        }
+    }
+}
+
+fn checked(path: &str) -> Result<VPath, String> {
+    if !path.starts_with("res://") && !path.starts_with("user://") {
+        return Err(format!("{}: paths must start with res:// or user://", path));
+    }
+    vfs::resolve(path)
+}
+
+pub fn file_exists(path: &str) -> bool {
+    checked(path).is_ok() && vfs::is_file(path)
+}
+
+/// Open `path` with READ, WRITE or READ_WRITE
+pub fn open(path: &str, mode: i64) -> Result<ObjectRef, String> {
+    let (read, write) = match mode {
+        READ => (true, false),
+        WRITE => (false, true),
+        READ_WRITE => (true, true),
+        _ => return Err(format!("FileAccess.open: unknown mode {}", mode)),
+    };
+    let opened = match checked(path)? {
+        VPath::Res(_) if write => return Err(format!("{}: res:// is read-only", path)),
+        VPath::Res(_) => Opened::Res(Cursor::new(vfs::read(path)?)),
+        VPath::User(_) => {
+            let real = vfs::user_file(path)?;
+            if write && let Some(dir) = real.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", path, e))?;
+            }
+            let file = OpenOptions::new()
+                .read(read)
+                .write(write)
+                .create(write)
+                .truncate(mode == WRITE)
+                .open(&real)
+                .map_err(|e| format!("{}: {}", path, e))?;
+            Opened::User(file)
+        }
+    };
+    let handle = new_file_access();
+    handle.set_property("path", Value::String(path.to_string()));
+    *handle.extension::<Mutex<Option<Opened>>>().lock().unwrap() = Some(opened);
+    Ok(handle)
+}
+
+fn with_file<R>(
+    handle: &ObjectRef,
+    f: impl FnOnce(&mut Opened) -> io::Result<R>,
+) -> Result<R, String> {
+    let mut file = handle.extension::<Mutex<Option<Opened>>>().lock().unwrap();
+    let file = file.as_mut().ok_or("file is closed")?;
+    f(file).map_err(|e| e.to_string())
+}
+
+pub fn init_file_access_class() {
+    register_class("FileAccess", new_file_access);
+}
+
+/// The script-facing `FileAccess` singleton, or with a file in its extension,
+/// a handle `open` returned
+pub fn new_file_access() -> ObjectRef {
+    let obj = ObjectInner::with_methods("FileAccess", file_access_methods());
+    obj.set_property("READ", Value::Int(READ));
+    obj.set_property("WRITE", Value::Int(WRITE));
+    obj.set_property("READ_WRITE", Value::Int(READ_WRITE));
+    obj
+}
+
+fn file_access_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_file_access_methods()))
+        .clone()
+}
+
+fn build_file_access_methods() -> MethodTable {
+    let mut methods = MethodTable::new();
+
+    // open(path, mode?) -> handle; READ by default
+    define_method(
+        &mut methods,
+        "open",
+        Arc::new(|_, args| {
+            let (Some(Value::String(path)), mode) = (args.first(), args.get(1)) else {
+                return Err("open expects (path, mode)".into());
+            };
+            let mode = match mode {
+                None => READ,
+                Some(Value::Int(mode)) => *mode,
+                Some(_) => return Err("open: mode must be READ, WRITE or READ_WRITE".into()),
+            };
+            open(path, mode).map(Value::Object)
+        }),
+    );
+    // file_exists(path) -> Bool
+    define_method(
+        &mut methods,
+        "file_exists",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::String(path)) => Ok(Value::Bool(file_exists(path))),
+            _ => Err("file_exists expects a path".into()),
+        }),
+    );
+    // read_string() -> the rest of the file as text
+    define_method(
+        &mut methods,
+        "read_string",
+        Arc::new(|this, _| {
+            let mut bytes = Vec::new();
+            with_file(this, |f| f.read_to_end(&mut bytes, None))?;
+            String::from_utf8(bytes)
+                .map(Value::String)
+                .map_err(|_| "read_string: the file is not UTF-8 text".into())
+        }),
+    );
+    // read_bytes(count?) -> Array of byte Ints; the rest of the file by default
+    define_method(
+        &mut methods,
+        "read_bytes",
+        Arc::new(|this, args| {
+            let count = match args.first() {
+                None => None,
+                Some(Value::Int(n)) if *n >= 0 => Some(*n as u64),
+                Some(_) => return Err("read_bytes expects a byte count".into()),
+            };
+            let mut bytes = Vec::new();
+            with_file(this, |f| f.read_to_end(&mut bytes, count))?;
+            Ok(Value::Array(
+                bytes.into_iter().map(|b| Value::Int(b.into())).collect(),
+            ))
+        }),
+    );
+    // store_string(text), store_bytes(bytes)
+    define_method(
+        &mut methods,
+        "store_string",
+        Arc::new(|this, args| match args.first() {
+            Some(Value::String(text)) => {
+                with_file(this, |f| f.write_all(text.as_bytes()))?;
+                Ok(Value::Null)
+            }
+            _ => Err("store_string expects a String".into()),
+        }),
+    );
+    define_method(
+        &mut methods,
+        "store_bytes",
+        Arc::new(|this, args| {
+            let Some(Value::Array(items)) = args.first() else {
+                return Err("store_bytes expects an Array of bytes".into());
+            };
+            let bytes = items
+                .iter()
+                .map(|b| match b {
+                    Value::Int(b) if (0..=255).contains(b) => Ok(*b as u8),
+                    _ => Err("store_bytes: every item must be a byte (0-255)".to_string()),
+                })
+                .collect::<Result<Vec<u8>, _>>()?;
+            with_file(this, |f| f.write_all(&bytes))?;
+            Ok(Value::Null)
+        }),
+    );
+    // get_length() -> size in bytes
+    define_method(
+        &mut methods,
+        "get_length",
+        Arc::new(|this, _| with_file(this, |f| f.len()).map(|n| Value::Int(n as i64))),
+    );
+    // close(): flush and release the file; later calls fail
+    define_method(
+        &mut methods,
+        "close",
+        Arc::new(|this, _| {
+            if let Some(file) = this
+                .extension::<Mutex<Option<Opened>>>()
+                .lock()
+                .unwrap()
+                .take()
+                && let Opened::User(mut file) = file
+            {
+                file.flush().map_err(|e| e.to_string())?;
+            }
+            Ok(Value::Null)
+        }),
+    );
+    methods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn scripts_stay_inside_res_and_user() {
+        let dir = std::env::temp_dir().join("gem_file_access_test");
+        let _ = fs::remove_dir_all(&dir);
+        vfs::set_user_dir(Some(dir.clone()));
+        let files = new_file_access();
+        let open = |path: &str, mode: i64| {
+            files.call_method("open", &[Value::String(path.into()), Value::Int(mode)])
+        };
+        let string = |s: &str| Value::String(s.into());
+
+        let save = open("user://saves/slot1.json", WRITE)
+            .unwrap()
+            .as_object()
+            .unwrap();
+        save.call_method("store_string", &[string("{\"level\": 3}")])
+            .unwrap();
+        save.call_method("store_bytes", &[Value::Array(vec![Value::Int(10)])])
+            .unwrap();
+        save.call_method("close", &[]).unwrap();
+        assert!(save.call_method("store_string", &[string("x")]).is_err());
+        assert!(dir.join("saves/slot1.json").is_file());
+
+        let save = open("user://saves/slot1.json", READ)
+            .unwrap()
+            .as_object()
+            .unwrap();
+        assert_eq!(save.call_method("get_length", &[]).unwrap(), Value::Int(13));
+        assert_eq!(
+            save.call_method("read_bytes", &[Value::Int(2)]).unwrap(),
+            Value::Array(vec![Value::Int(b'{'.into()), Value::Int(b'"'.into())])
+        );
+        assert_eq!(
+            save.call_method("read_string", &[]).unwrap(),
+            string("level\": 3}\n")
+        );
+        assert!(save.call_method("store_string", &[string("x")]).is_err());
+
+        // res:// reads the project but never writes it
+        let manifest = open("res://Cargo.toml", READ).unwrap().as_object().unwrap();
+        let text = manifest.call_method("read_string", &[]).unwrap();
+        assert!(matches!(text, Value::String(s) if s.contains("[package]")));
+        assert!(
+            open("res://Cargo.toml", WRITE)
+                .unwrap_err()
+                .contains("read-only")
+        );
+
+        for escape in [
+            "user://../escape.txt",
+            "res://../x",
+            "/etc/passwd",
+            "user:///etc/passwd",
+        ] {
+            assert!(open(escape, READ).is_err(), "{} was allowed", escape);
+        }
+        assert!(!file_exists(
+            "user://../gem_file_access_test/saves/slot1.json"
+        ));
+        assert!(file_exists("user://saves/slot1.json"));
+        vfs::set_user_dir(None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
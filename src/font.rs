@@ -0,0 +1,300 @@
+//! Font registry for TTF/OTF assets.
+//! Faces are loaded from `#assets:fonts:...` directives, looked up through a
+//! fallback chain per character, and rasterized lazily into one atlas page per
+//! (face, pixel size). The built-in bitmap font is the last link of every chain.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+
+use crate::bitmap_font::BitmapFont;
+use crate::text::GlyphMetrics;
+use crate::vfs;
+
+/// Index of a loaded face inside a `FontRegistry`
+pub type FaceId = usize;
+
+/// Edge length of a glyph atlas page
+pub const PAGE_SIZE: u32 = 1024;
+
+const GLYPH_PADDING: u32 = 1;
+
+struct Face {
+    name: String,
+    font: FontVec,
+}
+
+/// Where a rasterized glyph lives in its page, relative to the pen position
+/// at the top-left of the line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphSlot {
+    pub uv: [f32; 4],
+    pub offset: (f32, f32),
+    pub size: (f32, f32),
+}
+
+/// One atlas page of rasterized glyphs for a single face and pixel size.
+/// `generation` bumps whenever pixels change so the renderer knows to re-upload.
+pub struct GlyphPage {
+    pub pixels: Vec<u8>,
+    pub generation: u32,
+    glyphs: HashMap<char, Option<GlyphSlot>>,
+    cursor: (u32, u32),
+    row_h: u32,
+}
+
+impl GlyphPage {
+    fn new() -> Self {
+        Self {
+            pixels: vec![0; (PAGE_SIZE * PAGE_SIZE * 4) as usize],
+            generation: 0,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_h: 0,
+        }
+    }
+
+    /// Shelf-pack a `w` x `h` region, returning its top-left corner
+    fn allocate(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        let (w, h) = (w + GLYPH_PADDING, h + GLYPH_PADDING);
+        if self.cursor.0 + w > PAGE_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_h);
+            self.row_h = 0;
+        }
+        if w > PAGE_SIZE || self.cursor.1 + h > PAGE_SIZE {
+            return None;
+        }
+        let at = self.cursor;
+        self.cursor.0 += w;
+        self.row_h = self.row_h.max(h);
+        Some(at)
+    }
+}
+
+/// Resolved list of faces tried in order; falls through to the bitmap font
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontChain {
+    pub faces: Vec<FaceId>,
+}
+
+#[derive(Default)]
+pub struct FontRegistry {
+    faces: Vec<Face>,
+    by_path: HashMap<PathBuf, FaceId>,
+    /// Appended to every chain before the bitmap font
+    pub fallbacks: Vec<FaceId>,
+    /// Behind a lock so draw lists can be gathered on several threads
+    pages: Mutex<HashMap<(FaceId, u32), GlyphPage>>,
+}
+
+/// Turn `["assets", "fonts", "Inter.ttf"]` into `assets/fonts/Inter.ttf`
+pub fn directive_path(parts: &[String]) -> PathBuf {
+    parts.iter().collect()
+}
+
+/// Cache key for a font size: glyphs are rasterized at whole pixel sizes
+pub fn size_key(font_size: f32) -> u32 {
+    font_size.round().max(1.0) as u32
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a face from the project, reusing it when the same path was loaded before
+    pub fn load(&mut self, path: &Path) -> Result<FaceId, String> {
+        if let Some(id) = self.by_path.get(path) {
+            return Ok(*id);
+        }
+        let data = vfs::read(&path.to_string_lossy())?;
+        let font = FontVec::try_from_vec(data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let id = self.faces.len();
+        self.faces.push(Face { name, font });
+        self.by_path.insert(path.to_path_buf(), id);
+        Ok(id)
+    }
+
+    /// Make `path` stand for the face at `with`, e.g. the default font when
+    /// `path` itself couldn't be loaded
+    pub fn substitute(&mut self, path: &Path, with: &Path) -> Result<FaceId, String> {
+        let id = self.load(with)?;
+        self.by_path.insert(path.to_path_buf(), id);
+        Ok(id)
+    }
+
+    /// Face previously loaded from `path`
+    pub fn loaded(&self, path: &Path) -> Option<FaceId> {
+        self.by_path.get(path).copied()
+    }
+
+    /// Find a loaded face by its file stem (e.g. "Inter" for Inter.ttf)
+    pub fn find(&self, name: &str) -> Option<FaceId> {
+        self.faces.iter().position(|f| f.name == name)
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Metrics for laying out text with `chain`, followed by the registry fallbacks
+    pub fn metrics<'r>(&'r self, chain: &FontChain) -> ChainMetrics<'r> {
+        let mut faces = chain.faces.clone();
+        for id in &self.fallbacks {
+            if !faces.contains(id) {
+                faces.push(*id);
+            }
+        }
+        ChainMetrics {
+            registry: self,
+            faces,
+        }
+    }
+
+    fn covers(&self, face: FaceId, ch: char) -> bool {
+        self.faces[face].font.glyph_id(ch).0 != 0
+    }
+
+    /// Rasterize `ch` (if not cached yet) and return its slot in the page for
+    /// `(face, font_size)`. `None` means the glyph has no outline, e.g. a space.
+    pub fn glyph(&self, face: FaceId, ch: char, font_size: f32) -> Option<GlyphSlot> {
+        let px = size_key(font_size);
+        let mut pages = self.pages.lock().unwrap();
+        let page = pages.entry((face, px)).or_insert_with(GlyphPage::new);
+        if let Some(slot) = page.glyphs.get(&ch) {
+            return *slot;
+        }
+
+        let font = &self.faces[face].font;
+        let scale = PxScale::from(px as f32);
+        let ascent = font.as_scaled(scale).ascent();
+        let glyph = font
+            .glyph_id(ch)
+            .with_scale_and_position(scale, point(0.0, ascent));
+        let slot = font.outline_glyph(glyph).and_then(|outlined| {
+            let bounds = outlined.px_bounds();
+            let (w, h) = (bounds.width() as u32, bounds.height() as u32);
+            let Some((ax, ay)) = page.allocate(w, h) else {
+                eprintln!(
+                    "[WARN] Glyph atlas for {} at {}px is full; dropping '{}'",
+                    self.faces[face].name, px, ch
+                );
+                return None;
+            };
+            outlined.draw(|x, y, coverage| {
+                let i = (((ay + y) * PAGE_SIZE + ax + x) * 4) as usize;
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+                page.pixels[i..i + 4].copy_from_slice(&[255, 255, 255, alpha]);
+            });
+            page.generation += 1;
+            let size = PAGE_SIZE as f32;
+            Some(GlyphSlot {
+                uv: [
+                    ax as f32 / size,
+                    ay as f32 / size,
+                    (ax + w) as f32 / size,
+                    (ay + h) as f32 / size,
+                ],
+                offset: (bounds.min.x, bounds.min.y),
+                size: (w as f32, h as f32),
+            })
+        });
+        page.glyphs.insert(ch, slot);
+        slot
+    }
+
+    /// Visit the page for `(face, font_size)` if any glyph was rasterized into it
+    pub fn with_page<R>(
+        &self,
+        face: FaceId,
+        font_size: f32,
+        f: impl FnOnce(&GlyphPage) -> R,
+    ) -> Option<R> {
+        self.pages
+            .lock()
+            .unwrap()
+            .get(&(face, size_key(font_size)))
+            .map(f)
+    }
+}
+
+impl fmt::Debug for FontRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FontRegistry")
+            .field(
+                "faces",
+                &self.faces.iter().map(|f| &f.name).collect::<Vec<_>>(),
+            )
+            .field("fallbacks", &self.fallbacks)
+            .finish()
+    }
+}
+
+/// `GlyphMetrics` over a fallback chain: each character is measured with the
+/// first face that has it, then the bitmap font.
+pub struct ChainMetrics<'r> {
+    registry: &'r FontRegistry,
+    faces: Vec<FaceId>,
+}
+
+impl GlyphMetrics for ChainMetrics<'_> {
+    fn advance(&self, ch: char, font_size: f32) -> f32 {
+        match self.face(ch) {
+            Some(id) => {
+                let font = &self.registry.faces[id].font;
+                let scale = PxScale::from(size_key(font_size) as f32);
+                font.as_scaled(scale).h_advance(font.glyph_id(ch))
+            }
+            None => BitmapFont.advance(ch, font_size),
+        }
+    }
+
+    fn line_height(&self, font_size: f32) -> f32 {
+        match self.faces.first() {
+            Some(id) => {
+                let font = &self.registry.faces[*id].font;
+                let scaled = font.as_scaled(PxScale::from(size_key(font_size) as f32));
+                scaled.height() + scaled.line_gap()
+            }
+            None => BitmapFont.line_height(font_size),
+        }
+    }
+
+    fn face(&self, ch: char) -> Option<FaceId> {
+        self.faces
+            .iter()
+            .copied()
+            .find(|id| self.registry.covers(*id, ch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_chain_falls_back_to_bitmap() {
+        let registry = FontRegistry::new();
+        let metrics = registry.metrics(&FontChain::default());
+        assert_eq!(metrics.face('a'), None);
+        assert_eq!(metrics.advance('a', 16.0), BitmapFont.advance('a', 16.0));
+        assert_eq!(metrics.line_height(16.0), BitmapFont.line_height(16.0));
+        assert!(FontRegistry::new().load(Path::new("missing.ttf")).is_err());
+    }
+
+    #[test]
+    fn page_packs_rows() {
+        let mut page = GlyphPage::new();
+        assert_eq!(page.allocate(10, 20), Some((0, 0)));
+        assert_eq!(page.allocate(10, 5), Some((11, 0)));
+        assert_eq!(page.allocate(PAGE_SIZE - 5, 5), Some((0, 21)));
+        assert_eq!(page.allocate(PAGE_SIZE + 1, 1), None);
+    }
+}
@@ -1,328 +1,1005 @@
-use std::collections::HashMap;
+use std::any::Any;
 use std::sync::{Arc, OnceLock, RwLock};
 
-use crate::object::{ObjectInner, ObjectRef, register_class};
+use crate::multiplayer;
+use crate::object::{
+    self, MethodTable, ObjectInner, ObjectRef, WeakObjectRef, define_method, register_class,
+};
+use crate::resource;
+use crate::scene_tree::{self, PROCESS_MODE_KEY, ProcessMode};
+use crate::transform::{Pose, Transform2D};
 use crate::value::Value;
 
 const NAME_KEY: &str = "name";
+const POSITION_KEY: &str = "position";
+const VISIBLE_KEY: &str = "visible";
+/// `false` draws the node where physics left it instead of blending
+const PHYSICS_INTERPOLATION_KEY: &str = "physics_interpolation";
 
+/// Tree links, stored on each node through its object extension
 #[derive(Clone, Default)]
 struct GemPrivate {
-    parent: Option<ObjectRef>,
+    parent: Option<WeakObjectRef>,
     children: Vec<ObjectRef>,
     in_tree: bool,
     groups: Vec<String>,
+    /// Root of the scene that declared this node; `None` for scene roots and
+    /// nodes created at runtime
+    owner: Option<WeakObjectRef>,
+    /// Reachable as `%name` from anywhere in its owner's scene
+    unique_name: bool,
+    /// The unique-named nodes this node owns
+    unique_nodes: Vec<WeakObjectRef>,
+    /// Its pose before the latest physics step, for interpolation
+    previous_pose: Option<Pose>,
+    /// What a derived class keeps on the node, such as a WebSocketGem's
+    /// connection; see `class_state`
+    class_state: Option<Arc<dyn Any + Send + Sync>>,
 }
 
-static GEM_PRIV: OnceLock<RwLock<HashMap<u64, GemPrivate>>> = OnceLock::new();
-
-fn priv_map() -> &'static RwLock<HashMap<u64, GemPrivate>> {
-    GEM_PRIV.get_or_init(|| RwLock::new(HashMap::new()))
-}
-fn init_priv_for(node: &ObjectRef) {
-    let mut m = priv_map().write().unwrap();
-    m.entry(node.id()).or_insert_with(GemPrivate::default);
-}
 fn with_priv<R>(node: &ObjectRef, f: impl FnOnce(&mut GemPrivate) -> R) -> R {
-    let mut m = priv_map().write().unwrap();
-    let p = m.entry(node.id()).or_insert_with(GemPrivate::default);
-    f(p)
+    f(&mut node.extension::<RwLock<GemPrivate>>().write().unwrap())
 }
 fn read_priv<R>(node: &ObjectRef, f: impl FnOnce(&GemPrivate) -> R) -> R {
-    let m = priv_map().read().unwrap();
-    let p = m.get(&node.id()).unwrap();
-    f(p)
+    f(&node.extension::<RwLock<GemPrivate>>().read().unwrap())
+}
+/// A derived class's own state on `node`, created on first use. Like the
+/// object extension, every caller on one node must ask for the same type.
+pub(crate) fn class_state<T: Default + Send + Sync + 'static>(node: &ObjectRef) -> Arc<T> {
+    let state = with_priv(node, |p| {
+        p.class_state
+            .get_or_insert_with(|| Arc::new(T::default()))
+            .clone()
+    });
+    state
+        .downcast()
+        .unwrap_or_else(|_| panic!("{} class state has a different type", node.class_name()))
+}
+pub(crate) fn parent_of(node: &ObjectRef) -> Option<ObjectRef> {
+    read_priv(node, |p| p.parent.as_ref().and_then(WeakObjectRef::upgrade))
+}
+pub fn owner_of(node: &ObjectRef) -> Option<ObjectRef> {
+    read_priv(node, |p| p.owner.as_ref().and_then(WeakObjectRef::upgrade))
+}
+/// Fails, changing nothing, when `node` is unique-named and `owner` already
+/// has a unique node by that name
+pub fn set_owner(node: &ObjectRef, owner: Option<&ObjectRef>) -> Result<(), String> {
+    let unique = read_priv(node, |p| p.unique_name);
+    if unique && let Some(owner) = owner {
+        check_unique_free(owner, node)?;
+    }
+    let previous = with_priv(node, |p| {
+        std::mem::replace(&mut p.owner, owner.map(ObjectRef::downgrade))
+    });
+    if unique {
+        if let Some(previous) = previous.as_ref().and_then(WeakObjectRef::upgrade) {
+            unregister_unique(&previous, node);
+        }
+        if let Some(owner) = owner {
+            with_priv(owner, |p| p.unique_nodes.push(node.downgrade()));
+        }
+    }
+    Ok(())
+}
+pub fn is_unique_name_in_owner(node: &ObjectRef) -> bool {
+    read_priv(node, |p| p.unique_name)
+}
+/// Mark `node` as reachable through `get_node("%name")` from its owner's
+/// scene. Fails when the owner already has a unique node by that name.
+pub fn set_unique_name_in_owner(node: &ObjectRef, unique: bool) -> Result<(), String> {
+    if unique == is_unique_name_in_owner(node) {
+        return Ok(());
+    }
+    let owner = owner_of(node);
+    if unique && let Some(owner) = &owner {
+        check_unique_free(owner, node)?;
+    }
+    with_priv(node, |p| p.unique_name = unique);
+    if let Some(owner) = owner {
+        if unique {
+            with_priv(&owner, |p| p.unique_nodes.push(node.downgrade()));
+        } else {
+            unregister_unique(&owner, node);
+        }
+    }
+    Ok(())
+}
+fn check_unique_free(owner: &ObjectRef, node: &ObjectRef) -> Result<(), String> {
+    let name = node_name(node);
+    match find_unique(owner, &name) {
+        Some(other) if other != *node => Err(format!(
+            "%{} is already used in the scene of {}",
+            name,
+            node_name(owner)
+        )),
+        _ => Ok(()),
+    }
+}
+fn unregister_unique(owner: &ObjectRef, node: &ObjectRef) {
+    with_priv(owner, |p| {
+        p.unique_nodes
+            .retain(|n| n.upgrade().is_some_and(|n| n != *node))
+    });
+}
+/// The unique-named node called `name` that `owner` owns. Names are compared
+/// at lookup time, so renames and moves within the scene are picked up.
+fn find_unique(owner: &ObjectRef, name: &str) -> Option<ObjectRef> {
+    read_priv(owner, |p| {
+        p.unique_nodes
+            .iter()
+            .filter_map(WeakObjectRef::upgrade)
+            .find(|n| {
+                n.with_property(
+                    NAME_KEY,
+                    |v| matches!(v, Some(Value::String(s)) if s == name),
+                )
+            })
+    })
+}
+fn node_name(node: &ObjectRef) -> String {
+    match node.get_property(NAME_KEY) {
+        Some(Value::String(name)) => name,
+        _ => node.class_name().to_string(),
+    }
+}
+pub(crate) fn children_of(node: &ObjectRef) -> Vec<ObjectRef> {
+    read_priv(node, |p| p.children.clone())
 }
 
 pub fn init_gem_class() {
-    register_class("Gem", || {
-        let obj = ObjectInner::base("Gem");
-        obj.set_property(NAME_KEY, Value::String("Gem".into()));
-        init_priv_for(&obj);
-
-        // methods
-        ObjectInner::insert_method(
-            &obj,
-            "get_name",
-            Arc::new(|this, _| {
-                Ok(this
-                    .get_property(NAME_KEY)
-                    .unwrap_or(Value::String("".into())))
-            }),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "set_name",
-            Arc::new(|this, args| {
-                if let Some(Value::String(s)) = args.get(0).cloned() {
-                    this.set_property(NAME_KEY, Value::String(s));
+    register_class("Gem", || new_gem("Gem", gem_methods()));
+}
+
+/// A fresh node of `class_name` sharing `methods`, which derived gem classes
+/// build by extending `gem_methods`
+pub fn new_gem(class_name: &str, methods: Arc<MethodTable>) -> ObjectRef {
+    let obj = ObjectInner::with_methods(class_name, methods);
+    obj.set_property(NAME_KEY, Value::String(class_name.into()));
+    // Created up front, so `is_gem` tells nodes from other objects
+    obj.extension::<RwLock<GemPrivate>>();
+    obj
+}
+
+/// Whether `node` is a Gem, rather than an object of another class such as
+/// a Curve or a FileAccess
+pub(crate) fn is_gem(node: &ObjectRef) -> bool {
+    node.try_extension::<RwLock<GemPrivate>>().is_some()
+}
+
+/// Tree operations take nodes only: another class's object keeps its own
+/// state in the extension slot
+fn check_gem(node: &ObjectRef, op: &str) -> Result<(), String> {
+    if is_gem(node) {
+        Ok(())
+    } else {
+        Err(format!("{}: {} is not a Gem", op, node.class_name()))
+    }
+}
+
+/// The Gem class's method table, built once
+pub fn gem_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_gem_methods()))
+        .clone()
+}
+
+fn build_gem_methods() -> MethodTable {
+    let mut methods = MethodTable::new();
+    define_method(
+        &mut methods,
+        "get_name",
+        Arc::new(|this, _| {
+            Ok(this
+                .get_property(NAME_KEY)
+                .unwrap_or(Value::String("".into())))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "set_name",
+        Arc::new(|this, args| {
+            if let Some(Value::String(s)) = args.get(0).cloned() {
+                this.set_property(NAME_KEY, Value::String(s));
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // add_child(child)
+    define_method(
+        &mut methods,
+        "add_child",
+        Arc::new(|this, args| {
+            let child = match args.get(0) {
+                Some(Value::Object(o)) => o.clone(),
+                _ => return Err("add_child expects Object".into()),
+            };
+            check_gem(&child, "add_child")?;
+            check_tree_thread(this, "add_child")?;
+            check_orphan(&child, "add_child")?;
+            check_not_ancestor(&child, this, "add_child")?;
+            attach_child(this, &child, None);
+            Ok(Value::Null)
+        }),
+    );
+    // add_child_at(child, index): insert before the child now at `index`
+    define_method(
+        &mut methods,
+        "add_child_at",
+        Arc::new(|this, args| {
+            let (Some(Value::Object(child)), Some(Value::Int(index))) = (args.first(), args.get(1))
+            else {
+                return Err("add_child_at expects (child, index)".into());
+            };
+            let index = usize::try_from(*index).map_err(|_| "add_child_at: negative index")?;
+            check_gem(child, "add_child_at")?;
+            check_tree_thread(this, "add_child_at")?;
+            check_orphan(child, "add_child_at")?;
+            check_not_ancestor(child, this, "add_child_at")?;
+            attach_child(this, child, Some(index));
+            Ok(Value::Null)
+        }),
+    );
+    // add_sibling(node): insert right after this node in its parent
+    define_method(
+        &mut methods,
+        "add_sibling",
+        Arc::new(|this, args| {
+            let Some(Value::Object(sibling)) = args.first() else {
+                return Err("add_sibling expects Object".into());
+            };
+            check_gem(sibling, "add_sibling")?;
+            let parent = parent_of(this).ok_or("add_sibling: node has no parent")?;
+            check_tree_thread(&parent, "add_sibling")?;
+            check_orphan(sibling, "add_sibling")?;
+            check_not_ancestor(sibling, &parent, "add_sibling")?;
+            let index = read_priv(&parent, |p| p.children.iter().position(|c| c == this))
+                .map_or(0, |i| i + 1);
+            attach_child(&parent, sibling, Some(index));
+            Ok(Value::Null)
+        }),
+    );
+    // reparent(new_parent, keep_global_position = true)
+    define_method(
+        &mut methods,
+        "reparent",
+        Arc::new(|this, args| {
+            let Some(Value::Object(new_parent)) = args.first() else {
+                return Err("reparent expects (new_parent, keep_global_position?)".into());
+            };
+            let keep_global = !matches!(args.get(1), Some(Value::Bool(false)));
+            reparent(this, new_parent, keep_global)?;
+            Ok(Value::Null)
+        }),
+    );
+    // remove_child(child)
+    define_method(
+        &mut methods,
+        "remove_child",
+        Arc::new(|this, args| {
+            let target = match args.get(0) {
+                Some(Value::Object(o)) => o.clone(),
+                _ => return Err("remove_child expects Object".into()),
+            };
+            check_tree_thread(this, "remove_child")?;
+            let drawn = scene_tree::is_drawn_if_visible(&target);
+            let mut removed = false;
+            with_priv(this, |p| {
+                if let Some(pos) = p.children.iter().position(|o| o.id() == target.id()) {
+                    p.children.remove(pos);
+                    removed = true;
                 }
-                Ok(Value::Null)
-            }),
-        );
-        // add_child(child)
-        ObjectInner::insert_method(
-            &obj,
-            "add_child",
-            Arc::new(|this, args| {
-                let child = match args.get(0) {
-                    Some(Value::Object(o)) => o.clone(),
-                    _ => return Err("add_child expects Object".into()),
-                };
-                with_priv(this, |p| p.children.push(child.clone()));
-                with_priv(&child, |cp| cp.parent = Some(this.clone()));
+            });
+            if removed {
+                with_priv(&target, |cp| cp.parent = None);
+                mark_drawn_changed_if(drawn);
                 if is_in_tree(this) {
-                    enter_tree_recursive(&child);
+                    exit_tree_recursive(&target);
                 }
-                this.emit_signal("child_entered_tree", &[]);
-                Ok(Value::Null)
-            }),
-        );
-        // remove_child(child)
-        ObjectInner::insert_method(
-            &obj,
-            "remove_child",
-            Arc::new(|this, args| {
-                let target = match args.get(0) {
-                    Some(Value::Object(o)) => o.clone(),
-                    _ => return Err("remove_child expects Object".into()),
-                };
-                let mut removed = false;
-                with_priv(this, |p| {
-                    if let Some(pos) = p.children.iter().position(|o| o.id() == target.id()) {
-                        p.children.remove(pos);
-                        removed = true;
-                    }
-                });
-                if removed {
-                    with_priv(&target, |cp| cp.parent = None);
-                    if is_in_tree(this) {
-                        exit_tree_recursive(&target);
-                    }
-                    this.emit_signal("child_exited_tree", &[]);
+                this.emit_signal("child_exited_tree", &[]);
+            }
+            Ok(Value::Bool(removed))
+        }),
+    );
+    // spawn(scene): add a fresh instance of a PackedScene as a child, and return it
+    define_method(
+        &mut methods,
+        "spawn",
+        Arc::new(|this, args| {
+            let node = scene_tree::instantiate(&resource::scene_arg(args, "spawn")?)?;
+            this.call_method("add_child", &[Value::Object(node.clone())])?;
+            if is_in_tree(this) {
+                propagate_ready(&node);
+            }
+            Ok(Value::Object(node))
+        }),
+    );
+    // get_parent()
+    define_method(
+        &mut methods,
+        "get_parent",
+        Arc::new(|this, _| {
+            let p = parent_of(this);
+            Ok(p.map(Value::Object).unwrap_or(Value::Null))
+        }),
+    );
+    // get_owner(), set_owner(node or Null)
+    define_method(
+        &mut methods,
+        "get_owner",
+        Arc::new(|this, _| Ok(owner_of(this).map(Value::Object).unwrap_or(Value::Null))),
+    );
+    define_method(
+        &mut methods,
+        "set_owner",
+        Arc::new(|this, args| {
+            match args.first() {
+                Some(Value::Object(o)) => {
+                    check_gem(o, "set_owner")?;
+                    set_owner(this, Some(o))?
                 }
-                Ok(Value::Bool(removed))
-            }),
-        );
-        // get_parent()
-        ObjectInner::insert_method(
-            &obj,
-            "get_parent",
-            Arc::new(|this, _| {
-                let p = read_priv(this, |pr| pr.parent.clone());
-                Ok(p.map(Value::Object).unwrap_or(Value::Null))
-            }),
-        );
-        // get_children()
-        ObjectInner::insert_method(
-            &obj,
-            "get_children",
-            Arc::new(|this, _| {
-                let arr = read_priv(this, |p| {
-                    p.children
+                Some(Value::Null) => set_owner(this, None)?,
+                _ => return Err("set_owner expects Object or Null".into()),
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // set_unique_name_in_owner(bool), is_unique_name_in_owner()
+    define_method(
+        &mut methods,
+        "set_unique_name_in_owner",
+        Arc::new(|this, args| match args.first() {
+            Some(Value::Bool(unique)) => {
+                set_unique_name_in_owner(this, *unique)?;
+                Ok(Value::Null)
+            }
+            _ => Err("set_unique_name_in_owner expects Bool".into()),
+        }),
+    );
+    define_method(
+        &mut methods,
+        "is_unique_name_in_owner",
+        Arc::new(|this, _| Ok(Value::Bool(is_unique_name_in_owner(this)))),
+    );
+    // get_children()
+    define_method(
+        &mut methods,
+        "get_children",
+        Arc::new(|this, _| {
+            let arr = read_priv(this, |p| {
+                p.children
+                    .iter()
+                    .cloned()
+                    .map(Value::Object)
+                    .collect::<Vec<_>>()
+            });
+            Ok(Value::Array(arr))
+        }),
+    );
+    // get_child_count()
+    define_method(
+        &mut methods,
+        "get_child_count",
+        Arc::new(|this, _| {
+            let len = read_priv(this, |p| p.children.len() as i64);
+            Ok(Value::Int(len))
+        }),
+    );
+    // get_child(index)
+    define_method(
+        &mut methods,
+        "get_child",
+        Arc::new(|this, args| {
+            let idx = match args.get(0) {
+                Some(Value::Int(i)) => *i as usize,
+                _ => return Err("get_child expects index (int)".into()),
+            };
+            if let Some(o) = read_priv(this, |p| p.children.get(idx).cloned()) {
+                return Ok(Value::Object(o));
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // get_node(path), has_node(path)
+    define_method(
+        &mut methods,
+        "has_node",
+        Arc::new(|this, args| {
+            let path = match args.get(0) {
+                Some(Value::String(s)) => s,
+                _ => return Err("has_node expects path string".into()),
+            };
+            Ok(Value::Bool(get_node_by_path(this, path).is_some()))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_node",
+        Arc::new(|this, args| {
+            let path = match args.get(0) {
+                Some(Value::String(s)) => s,
+                _ => return Err("get_node expects path string".into()),
+            };
+            if let Some(node) = get_node_by_path(this, path) {
+                Ok(Value::Object(node))
+            } else {
+                Ok(Value::Null)
+            }
+        }),
+    );
+
+    // get_index()
+    define_method(
+        &mut methods,
+        "get_index",
+        Arc::new(|this, _| {
+            let parent = parent_of(this);
+            if let Some(p) = parent {
+                let idx = read_priv(&p, |pp| {
+                    pp.children
                         .iter()
-                        .cloned()
-                        .map(Value::Object)
-                        .collect::<Vec<_>>()
+                        .position(|o| o.id() == this.id())
+                        .map(|i| i as i64)
+                        .unwrap_or(-1)
                 });
-                Ok(Value::Array(arr))
-            }),
-        );
-        // get_child_count()
-        ObjectInner::insert_method(
-            &obj,
-            "get_child_count",
-            Arc::new(|this, _| {
-                let len = read_priv(this, |p| p.children.len() as i64);
-                Ok(Value::Int(len))
-            }),
-        );
-        // get_child(index)
-        ObjectInner::insert_method(
-            &obj,
-            "get_child",
-            Arc::new(|this, args| {
-                let idx = match args.get(0) {
-                    Some(Value::Int(i)) => *i as usize,
-                    _ => return Err("get_child expects index (int)".into()),
-                };
-                if let Some(o) = read_priv(this, |p| p.children.get(idx).cloned()) {
-                    return Ok(Value::Object(o));
-                }
-                Ok(Value::Null)
-            }),
-        );
-        // get_node(path), has_node(path)
-        ObjectInner::insert_method(
-            &obj,
-            "has_node",
-            Arc::new(|this, args| {
-                let path = match args.get(0) {
-                    Some(Value::String(s)) => s,
-                    _ => return Err("has_node expects path string".into()),
-                };
-                Ok(Value::Bool(get_node_by_path(this, path).is_some()))
-            }),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "get_node",
-            Arc::new(|this, args| {
-                let path = match args.get(0) {
-                    Some(Value::String(s)) => s,
-                    _ => return Err("get_node expects path string".into()),
-                };
-                if let Some(node) = get_node_by_path(this, path) {
-                    Ok(Value::Object(node))
-                } else {
-                    Ok(Value::Null)
-                }
-            }),
-        );
+                return Ok(Value::Int(idx));
+            }
+            Ok(Value::Int(-1))
+        }),
+    );
 
-        // get_index()
-        ObjectInner::insert_method(
-            &obj,
-            "get_index",
-            Arc::new(|this, _| {
-                let parent = read_priv(this, |p| p.parent.clone());
-                if let Some(p) = parent {
-                    let idx = read_priv(&p, |pp| {
-                        pp.children
-                            .iter()
-                            .position(|o| o.id() == this.id())
-                            .map(|i| i as i64)
-                            .unwrap_or(-1)
-                    });
-                    return Ok(Value::Int(idx));
+    // move_child(child, to_position)
+    define_method(
+        &mut methods,
+        "move_child",
+        Arc::new(|this, args| {
+            let child_id = match args.get(0) {
+                Some(Value::Object(o)) => o.id(),
+                _ => return Err("move_child expects (child, to_position)".into()),
+            };
+            let to_pos = match args.get(1) {
+                Some(Value::Int(i)) => *i as usize,
+                _ => return Err("move_child expects (child, to_position)".into()),
+            };
+            let mut moved = false;
+            with_priv(this, |p| {
+                if let Some(pos) = p.children.iter().position(|o| o.id() == child_id) {
+                    let val = p.children.remove(pos);
+                    let insert_at = to_pos.min(p.children.len());
+                    p.children.insert(insert_at, val);
+                    moved = true;
                 }
-                Ok(Value::Int(-1))
-            }),
-        );
+            });
+            if moved {
+                mark_drawn_changed_if(scene_tree::is_drawn(this));
+                return Ok(Value::Bool(true));
+            }
+            Ok(Value::Bool(false))
+        }),
+    );
 
-        // move_child(child, to_position)
-        ObjectInner::insert_method(
-            &obj,
-            "move_child",
-            Arc::new(|this, args| {
-                let child_id = match args.get(0) {
-                    Some(Value::Object(o)) => o.id(),
-                    _ => return Err("move_child expects (child, to_position)".into()),
-                };
-                let to_pos = match args.get(1) {
-                    Some(Value::Int(i)) => *i as usize,
-                    _ => return Err("move_child expects (child, to_position)".into()),
-                };
-                let mut moved = false;
-                with_priv(this, |p| {
-                    if let Some(pos) = p.children.iter().position(|o| o.id() == child_id) {
-                        let val = p.children.remove(pos);
-                        let insert_at = to_pos.min(p.children.len());
-                        p.children.insert(insert_at, val);
-                        moved = true;
-                    }
-                });
-                if moved {
-                    return Ok(Value::Bool(true));
-                }
-                Ok(Value::Bool(false))
-            }),
-        );
+    // get_tree_string(), print_tree(): the subtree as an indented listing
+    define_method(
+        &mut methods,
+        "get_tree_string",
+        Arc::new(|this, _| Ok(Value::String(tree_string(this)))),
+    );
+    define_method(
+        &mut methods,
+        "print_tree",
+        Arc::new(|this, _| {
+            print!("{}", tree_string(this));
+            Ok(Value::Null)
+        }),
+    );
 
-        // is_inside_tree()
-        ObjectInner::insert_method(
-            &obj,
-            "is_inside_tree",
-            Arc::new(|this, _| Ok(Value::Bool(is_in_tree(this)))),
-        );
+    // is_inside_tree()
+    define_method(
+        &mut methods,
+        "is_inside_tree",
+        Arc::new(|this, _| Ok(Value::Bool(is_in_tree(this)))),
+    );
 
-        // get_path(), get_path_to(node)
-        ObjectInner::insert_method(
-            &obj,
-            "get_path",
-            Arc::new(|this, _| Ok(Value::String(get_path(this)))),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "get_path_to",
-            Arc::new(|this, args| {
-                let target = match args.get(0) {
-                    Some(Value::Object(o)) => o,
-                    _ => return Err("get_path_to expects node".into()),
-                };
-                Ok(Value::String(get_path_to(this, target)))
-            }),
-        );
+    // get_path(), get_path_to(node)
+    define_method(
+        &mut methods,
+        "get_path",
+        Arc::new(|this, _| Ok(Value::String(get_path(this)))),
+    );
+    define_method(
+        &mut methods,
+        "get_path_to",
+        Arc::new(|this, args| {
+            let target = match args.get(0) {
+                Some(Value::Object(o)) => o,
+                _ => return Err("get_path_to expects node".into()),
+            };
+            check_gem(target, "get_path_to")?;
+            Ok(Value::String(get_path_to(this, target)))
+        }),
+    );
 
-        // Groups API: add_to_group(name), is_in_group(name), remove_from_group(name), get_groups()
-        ObjectInner::insert_method(
-            &obj,
-            "add_to_group",
-            Arc::new(|this, args| {
-                let name = match args.get(0) {
-                    Some(Value::String(s)) => s.clone(),
-                    _ => return Err("add_to_group expects name".into()),
-                };
-                with_priv(this, |p| {
-                    if !p.groups.iter().any(|g| g == &name) {
-                        p.groups.push(name);
-                    }
-                });
+    // Groups API: add_to_group(name), is_in_group(name), remove_from_group(name), get_groups()
+    define_method(
+        &mut methods,
+        "add_to_group",
+        Arc::new(|this, args| {
+            let name = match args.get(0) {
+                Some(Value::String(s)) => s.clone(),
+                _ => return Err("add_to_group expects name".into()),
+            };
+            with_priv(this, |p| {
+                if !p.groups.iter().any(|g| g == &name) {
+                    p.groups.push(name);
+                }
+            });
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "is_in_group",
+        Arc::new(|this, args| {
+            let name = match args.get(0) {
+                Some(Value::String(s)) => s,
+                _ => return Err("is_in_group expects name".into()),
+            };
+            let found = read_priv(this, |p| p.groups.iter().any(|g| g == name));
+            Ok(Value::Bool(found))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "remove_from_group",
+        Arc::new(|this, args| {
+            let name = match args.get(0) {
+                Some(Value::String(s)) => s.clone(),
+                _ => return Err("remove_from_group expects name".into()),
+            };
+            with_priv(this, |p| p.groups.retain(|g| g != &name));
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_groups",
+        Arc::new(|this, _| {
+            let v = read_priv(this, |p| {
+                p.groups
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect::<Vec<_>>()
+            });
+            Ok(Value::Array(v))
+        }),
+    );
+
+    // Coordinate helpers: get_global_position(), set_global_position(p),
+    // get_interpolated_global_position(), to_global(local_point),
+    // to_local(global_point)
+    define_method(
+        &mut methods,
+        "get_global_position",
+        Arc::new(|this, _| {
+            let (x, y) = global_transform(this).origin;
+            Ok(Value::vec2(x, y))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_interpolated_global_position",
+        Arc::new(|this, _| {
+            let alpha = scene_tree::physics_interpolation_fraction();
+            let (x, y) = interpolated_global_transform(this, alpha).origin;
+            Ok(Value::vec2(x, y))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "set_global_position",
+        Arc::new(|this, args| {
+            let p = args
+                .first()
+                .and_then(Value::as_vec2)
+                .ok_or("set_global_position expects a point")?;
+            let parent = parent_of(this).map(|p| global_transform(&p));
+            let (x, y) = match parent {
+                Some(t) => t
+                    .inverse()
+                    .ok_or("set_global_position: parent transform has zero scale")?
+                    .apply(p),
+                None => p,
+            };
+            this.set_property(POSITION_KEY, Value::vec2(x, y));
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "to_global",
+        Arc::new(|this, args| {
+            let p = args
+                .first()
+                .and_then(Value::as_vec2)
+                .ok_or("to_global expects a point")?;
+            let (x, y) = global_transform(this).apply(p);
+            Ok(Value::vec2(x, y))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "to_local",
+        Arc::new(|this, args| {
+            let p = args
+                .first()
+                .and_then(Value::as_vec2)
+                .ok_or("to_local expects a point")?;
+            let (x, y) = to_local(this, p).ok_or("to_local: node transform has zero scale")?;
+            Ok(Value::vec2(x, y))
+        }),
+    );
+
+    // Notifications: notification(code) for this node, propagate_notification(code)
+    // for its whole subtree
+    define_method(
+        &mut methods,
+        "notification",
+        Arc::new(object::notification_method),
+    );
+    object::define_meta_methods(&mut methods);
+    multiplayer::define_rpc_methods(&mut methods);
+    define_method(
+        &mut methods,
+        "propagate_notification",
+        Arc::new(|this, args| match args.first() {
+            Some(Value::Int(code)) => {
+                propagate_notification(this, *code);
                 Ok(Value::Null)
-            }),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "is_in_group",
-            Arc::new(|this, args| {
-                let name = match args.get(0) {
-                    Some(Value::String(s)) => s,
-                    _ => return Err("is_in_group expects name".into()),
-                };
-                let found = read_priv(this, |p| p.groups.iter().any(|g| g == name));
-                Ok(Value::Bool(found))
-            }),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "remove_from_group",
-            Arc::new(|this, args| {
-                let name = match args.get(0) {
-                    Some(Value::String(s)) => s.clone(),
-                    _ => return Err("remove_from_group expects name".into()),
-                };
-                with_priv(this, |p| p.groups.retain(|g| g != &name));
+            }
+            _ => Err("propagate_notification expects an Int code".into()),
+        }),
+    );
+
+    // Visibility: show(), hide(), set_visible(bool), is_visible(), is_visible_in_tree()
+    define_method(
+        &mut methods,
+        "show",
+        Arc::new(|this, _| {
+            set_visible(this, true);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "hide",
+        Arc::new(|this, _| {
+            set_visible(this, false);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "set_visible",
+        Arc::new(|this, args| match args.first() {
+            Some(Value::Bool(b)) => {
+                set_visible(this, *b);
                 Ok(Value::Null)
-            }),
-        );
-        ObjectInner::insert_method(
-            &obj,
-            "get_groups",
-            Arc::new(|this, _| {
-                let v = read_priv(this, |p| {
-                    p.groups
-                        .iter()
-                        .cloned()
-                        .map(Value::String)
-                        .collect::<Vec<_>>()
-                });
-                Ok(Value::Array(v))
-            }),
-        );
+            }
+            _ => Err("set_visible expects Bool".into()),
+        }),
+    );
+    define_method(
+        &mut methods,
+        "is_visible",
+        Arc::new(|this, _| Ok(Value::Bool(is_visible(this)))),
+    );
+    define_method(
+        &mut methods,
+        "is_visible_in_tree",
+        Arc::new(|this, _| Ok(Value::Bool(is_visible_in_tree(this)))),
+    );
+
+    // Pausing: set_process_mode(mode), get_process_mode(), can_process()
+    define_method(
+        &mut methods,
+        "set_process_mode",
+        Arc::new(|this, args| {
+            let mode = match args.first() {
+                Some(Value::String(s)) => {
+                    ProcessMode::parse(s).ok_or_else(|| format!("unknown process_mode {}", s))?
+                }
+                _ => return Err("set_process_mode expects a mode name".into()),
+            };
+            this.set_property(PROCESS_MODE_KEY, Value::String(mode.as_str().into()));
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_process_mode",
+        Arc::new(|this, _| {
+            Ok(Value::String(
+                scene_tree::process_mode(this).as_str().into(),
+            ))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "can_process",
+        Arc::new(|this, _| Ok(Value::Bool(scene_tree::can_process(this)))),
+    );
+
+    // signals set is dynamic; document: child_entered_tree, child_exited_tree, tree_entered, tree_exiting, ready, visibility_changed
+    methods
+}
+
+/// Transform from `position`, `rotation` and `scale`, relative to the parent
+pub fn local_transform(node: &ObjectRef) -> Transform2D {
+    pose(node).transform()
+}
+
+pub fn pose(node: &ObjectRef) -> Pose {
+    let position = node
+        .with_property(POSITION_KEY, |v| v.and_then(Value::as_vec2))
+        .unwrap_or((0.0, 0.0));
+    let rotation = node
+        .with_property("rotation", |v| v.and_then(Value::as_f64))
+        .unwrap_or(0.0);
+    let scale = node
+        .with_property("scale", |v| v.and_then(Value::as_vec2))
+        .unwrap_or((1.0, 1.0));
+    Pose {
+        position,
+        rotation,
+        scale,
+    }
+}
+
+pub fn global_transform(node: &ObjectRef) -> Transform2D {
+    let local = local_transform(node);
+    match parent_of(node) {
+        Some(parent) => global_transform(&parent).compose(&local),
+        None => local,
+    }
+}
+
+/// Remember the pose of every node under `root`, before a physics step
+pub fn record_poses(root: &ObjectRef) {
+    let pose = pose(root);
+    with_priv(root, |p| p.previous_pose = Some(pose));
+    for child in children_of(root) {
+        record_poses(&child);
+    }
+}
+
+/// The local transform `alpha` (0 to 1) of the way from the pose before the
+/// latest physics step to the current one, unless `physics_interpolation` is
+/// off
+pub fn interpolated_transform(node: &ObjectRef, alpha: f64) -> Transform2D {
+    let current = pose(node);
+    let enabled = node.with_property(PHYSICS_INTERPOLATION_KEY, |v| {
+        !matches!(v, Some(Value::Bool(false)))
+    });
+    match read_priv(node, |p| p.previous_pose) {
+        Some(previous) if enabled => previous.lerp(&current, alpha).transform(),
+        _ => current.transform(),
+    }
+}
+
+pub fn interpolated_global_transform(node: &ObjectRef, alpha: f64) -> Transform2D {
+    let local = interpolated_transform(node, alpha);
+    match parent_of(node) {
+        Some(parent) => interpolated_global_transform(&parent, alpha).compose(&local),
+        None => local,
+    }
+}
+
+/// A global (window) point in `node`'s local space, e.g. for hit tests
+pub fn to_local(node: &ObjectRef, point: (f64, f64)) -> Option<(f64, f64)> {
+    Some(global_transform(node).inverse()?.apply(point))
+}
+
+/// The node's own `visible` flag; nodes without one are visible
+pub fn is_visible(node: &ObjectRef) -> bool {
+    node.with_property(VISIBLE_KEY, |v| !matches!(v, Some(Value::Bool(false))))
+}
+
+/// Visible itself and under visible ancestors, i.e. actually drawn
+pub fn is_visible_in_tree(node: &ObjectRef) -> bool {
+    is_visible(node) && parent_of(node).is_none_or(|p| is_visible_in_tree(&p))
+}
+
+/// Set `visible`, emitting `visibility_changed(visible)` when it flips
+pub fn set_visible(node: &ObjectRef, visible: bool) {
+    if is_visible(node) == visible {
+        return;
+    }
+    node.set_property(VISIBLE_KEY, Value::Bool(visible));
+    node.emit_signal("visibility_changed", &[Value::Bool(visible)]);
+}
+
+/// Send `code` to `node` and then its descendants, parents before children
+pub fn propagate_notification(node: &ObjectRef, code: i64) {
+    node.notification(code);
+    for child in children_of(node) {
+        propagate_notification(&child, code);
+    }
+}
 
-        // signals set is dynamic; document: child_entered_tree, child_exited_tree, tree_entered, tree_exiting, ready
-        obj
+/// Mark a subtree ready, children before parents so a parent's handler can
+/// rely on its children: each node gets `NOTIFICATION_READY`, then `ready`
+pub fn propagate_ready(node: &ObjectRef) {
+    for child in children_of(node) {
+        propagate_ready(&child);
+    }
+    node.notification(object::NOTIFICATION_READY);
+    node.emit_signal("ready", &[]);
+}
+
+/// Make `node` the root of a live tree: it and its descendants enter the tree
+pub fn enter_tree(node: &ObjectRef) {
+    enter_tree_recursive(node);
+}
+
+/// Structural changes to a live tree touch several nodes and are not atomic
+/// across them, so they must come from the main thread
+/// Properties `tree_string` lists after a node's name and class
+const TREE_STRING_KEYS: [&str; 5] = ["position", "size", "text", "visible", PROCESS_MODE_KEY];
+
+/// Indented listing of `node`'s subtree: one `Name (Class)` line per node,
+/// `%` marking scene-unique names, followed by the key properties it has
+pub fn tree_string(node: &ObjectRef) -> String {
+    let mut out = String::new();
+    write_tree(node, 0, &mut out);
+    out
+}
+
+fn write_tree(node: &ObjectRef, depth: usize, out: &mut String) {
+    let marker = if is_unique_name_in_owner(node) {
+        "%"
+    } else {
+        ""
+    };
+    out.push_str(&format!(
+        "{}{}{} ({})",
+        "  ".repeat(depth),
+        marker,
+        node_name(node),
+        node.class_name()
+    ));
+    for key in TREE_STRING_KEYS {
+        if let Some(value) = node.get_property(key) {
+            out.push_str(&format!(" {}={}", key, short_value(&value)));
+        }
+    }
+    out.push('\n');
+    for child in children_of(node) {
+        write_tree(&child, depth + 1, out);
+    }
+}
+
+fn short_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(x) => format!("{:?}", x),
+        Value::String(s) => format!("{:?}", s),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(short_value).collect();
+            format!("({})", items.join(", "))
+        }
+        Value::Map(map) => format!("{{{} fields}}", map.len()),
+        Value::Object(o) => format!("<{}:{}>", o.class_name(), o.id()),
+        Value::Color(c) => c.to_hex(),
+    }
+}
+
+fn check_tree_thread(node: &ObjectRef, op: &str) -> Result<(), String> {
+    if is_in_tree(node) && !object::is_main_thread() {
+        return Err(format!(
+            "{} on a node inside the tree must run on the main thread",
+            op
+        ));
+    }
+    Ok(())
+}
+
+/// Link `child` under `parent` at `index` (clamped; `None` appends), entering
+/// the tree with it
+fn attach_child(parent: &ObjectRef, child: &ObjectRef, index: Option<usize>) {
+    with_priv(parent, |p| {
+        let index = index.map_or(p.children.len(), |i| i.min(p.children.len()));
+        p.children.insert(index, child.clone());
     });
+    with_priv(child, |cp| cp.parent = Some(parent.downgrade()));
+    mark_drawn_changed_if(scene_tree::is_drawn_if_visible(child));
+    if is_in_tree(parent) {
+        enter_tree_recursive(child);
+    }
+    parent.emit_signal("child_entered_tree", &[]);
+}
+
+/// Count a change to a node's children towards `object::draw_revision`
+/// when the draw list showed, or now shows, the nodes involved
+fn mark_drawn_changed_if(drawn: bool) {
+    if drawn {
+        object::mark_drawn_changed();
+    }
+}
+
+fn check_orphan(node: &ObjectRef, op: &str) -> Result<(), String> {
+    match parent_of(node) {
+        Some(_) => Err(format!("{}: node already has a parent; use reparent", op)),
+        None => Ok(()),
+    }
+}
+
+/// Fails when `node` is `parent` or above it, which would make a cycle
+fn check_not_ancestor(node: &ObjectRef, parent: &ObjectRef, op: &str) -> Result<(), String> {
+    let mut ancestor = Some(parent.clone());
+    while let Some(a) = ancestor {
+        if a == *node {
+            return Err(format!("{}: can't move a node under itself", op));
+        }
+        ancestor = parent_of(&a);
+    }
+    Ok(())
+}
+
+/// Move `node` to the end of `new_parent`'s children. A node that stays in
+/// the tree doesn't leave and re-enter it: its subtree gets no EXIT_TREE /
+/// ENTER_TREE notifications or tree signals, only the two parents'
+/// `child_exited_tree` / `child_entered_tree`.
+pub fn reparent(
+    node: &ObjectRef,
+    new_parent: &ObjectRef,
+    keep_global_position: bool,
+) -> Result<(), String> {
+    check_gem(node, "reparent")?;
+    check_gem(new_parent, "reparent")?;
+    check_not_ancestor(node, new_parent, "reparent")?;
+    check_tree_thread(node, "reparent")?;
+    check_tree_thread(new_parent, "reparent")?;
+    let position = if keep_global_position {
+        let inverse = global_transform(new_parent)
+            .inverse()
+            .ok_or("reparent: new parent transform has zero scale")?;
+        Some(inverse.apply(global_transform(node).origin))
+    } else {
+        None
+    };
+
+    let was_in_tree = is_in_tree(node);
+    let was_drawn = scene_tree::is_drawn_if_visible(node);
+    if let Some(old) = parent_of(node) {
+        with_priv(&old, |p| p.children.retain(|c| c != node));
+        old.emit_signal("child_exited_tree", &[]);
+    }
+    with_priv(new_parent, |p| p.children.push(node.clone()));
+    with_priv(node, |p| p.parent = Some(new_parent.downgrade()));
+    mark_drawn_changed_if(was_drawn || scene_tree::is_drawn_if_visible(node));
+    match (was_in_tree, is_in_tree(new_parent)) {
+        (false, true) => enter_tree_recursive(node),
+        (true, false) => exit_tree_recursive(node),
+        _ => {}
+    }
+    new_parent.emit_signal("child_entered_tree", &[]);
+    if let Some((x, y)) = position {
+        node.set_property(POSITION_KEY, Value::vec2(x, y));
+    }
+    Ok(())
 }
 
 fn is_in_tree(node: &ObjectRef) -> bool {
     read_priv(node, |p| p.in_tree)
 }
 
-fn root_of(node: &ObjectRef) -> ObjectRef {
+pub(crate) fn root_of(node: &ObjectRef) -> ObjectRef {
     let mut cur = node.clone();
     loop {
-        let parent = read_priv(&cur, |p| p.parent.clone());
+        let parent = parent_of(&cur);
         match parent {
             Some(p) => cur = p,
             None => break,
@@ -331,7 +1008,7 @@ fn root_of(node: &ObjectRef) -> ObjectRef {
     cur
 }
 
-fn get_path(node: &ObjectRef) -> String {
+pub fn get_path(node: &ObjectRef) -> String {
     // absolute path from root using names
     let mut names = vec![];
     let mut cur = node.clone();
@@ -341,7 +1018,7 @@ fn get_path(node: &ObjectRef) -> String {
             _ => "".to_string(),
         };
         names.push(name);
-        let parent = read_priv(&cur, |p| p.parent.clone());
+        let parent = parent_of(&cur);
         match parent {
             Some(p) => cur = p,
             None => break,
@@ -366,27 +1043,34 @@ fn get_node_by_path(from: &ObjectRef, path: &str) -> Option<ObjectRef> {
     if path.is_empty() {
         return None;
     }
-    let start = if path.starts_with('/') {
-        root_of(from)
+    // `%Name/rest`: a unique node owned by `from` or by `from`'s owner
+    let (start, path) = if let Some(unique) = path.strip_prefix('%') {
+        let (name, rest) = unique.split_once('/').unwrap_or((unique, ""));
+        let found = find_unique(from, name)
+            .or_else(|| owner_of(from).and_then(|owner| find_unique(&owner, name)))?;
+        (found, rest)
+    } else if path.starts_with('/') {
+        (root_of(from), path)
     } else {
-        from.clone()
+        (from.clone(), path)
     };
     let mut cur = start;
     for seg in path.split('/') {
         if seg.is_empty() {
             continue;
         }
-        // find child by name
-        let children = read_priv(&cur, |p| p.children.clone());
-        let mut found: Option<ObjectRef> = None;
-        for o in children {
-            if let Some(Value::String(n)) = o.get_property(NAME_KEY) {
-                if n == seg {
-                    found = Some(o);
-                    break;
-                }
-            }
-        }
+        // find child by name, without copying the child list or names
+        let found = read_priv(&cur, |p| {
+            p.children
+                .iter()
+                .find(|o| {
+                    o.with_property(
+                        NAME_KEY,
+                        |v| matches!(v, Some(Value::String(n)) if n == seg),
+                    )
+                })
+                .cloned()
+        });
         if let Some(n) = found {
             cur = n;
         } else {
@@ -398,6 +1082,7 @@ fn get_node_by_path(from: &ObjectRef, path: &str) -> Option<ObjectRef> {
 
 fn enter_tree_recursive(node: &ObjectRef) {
     with_priv(node, |p| p.in_tree = true);
+    node.notification(object::NOTIFICATION_ENTER_TREE);
     node.emit_signal("tree_entered", &[]);
     let children = read_priv(node, |p| p.children.clone());
     for o in children {
@@ -410,6 +1095,7 @@ fn exit_tree_recursive(node: &ObjectRef) {
     for o in children {
         exit_tree_recursive(&o);
     }
+    node.notification(object::NOTIFICATION_EXIT_TREE);
     node.emit_signal("tree_exiting", &[]);
     with_priv(node, |p| p.in_tree = false);
 }
@@ -449,5 +1135,409 @@ mod tests {
             Value::Object(o) => assert_eq!(o.id(), child.id()),
             _ => panic!("expected object"),
         }
+
+        // Another class's object isn't a node, and keeps its own state
+        let curve = crate::curve::handle(crate::curve::Curve::default());
+        let err = root
+            .call_method("add_child", &[Value::Object(curve.clone())])
+            .unwrap_err();
+        assert!(err.contains("Curve is not a Gem"), "{}", err);
+        assert!(crate::curve::of(&curve).is_some());
+    }
+
+    #[test]
+    fn global_positions_follow_parents() {
+        init_object_class();
+        init_gem_class();
+        let parent = object_new("Gem");
+        parent.set_property("position", Value::vec2(100.0, 50.0));
+        parent.set_property("scale", Value::vec2(2.0, 2.0));
+        let child = object_new("Gem");
+        child.set_property(
+            "position",
+            Value::Array(vec![Value::Int(10), Value::Int(0)]),
+        );
+        parent
+            .call_method("add_child", &[Value::Object(child.clone())])
+            .unwrap();
+
+        let global = |node: &ObjectRef| node.call_method("get_global_position", &[]).unwrap();
+        assert_eq!(global(&child), Value::vec2(120.0, 50.0));
+        assert_eq!(
+            child
+                .call_method("to_global", &[Value::vec2(1.0, 1.0)])
+                .unwrap(),
+            Value::vec2(122.0, 52.0)
+        );
+        assert_eq!(
+            child
+                .call_method("to_local", &[Value::vec2(122.0, 52.0)])
+                .unwrap(),
+            Value::vec2(1.0, 1.0)
+        );
+
+        child
+            .call_method("set_global_position", &[Value::vec2(0.0, 0.0)])
+            .unwrap();
+        assert_eq!(
+            child.get_property("position"),
+            Some(Value::vec2(-50.0, -25.0))
+        );
+        assert_eq!(global(&child), Value::vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn hiding_a_parent_hides_its_subtree() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        init_object_class();
+        init_gem_class();
+        let parent = object_new("Gem");
+        let child = object_new("Gem");
+        parent
+            .call_method("add_child", &[Value::Object(child.clone())])
+            .unwrap();
+        let changes = Arc::new(AtomicUsize::new(0));
+        let seen = changes.clone();
+        parent.connect(
+            "visibility_changed",
+            Arc::new(move |_| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+
+        assert!(is_visible_in_tree(&child));
+        parent.call_method("hide", &[]).unwrap();
+        // Hiding again is not a change
+        parent.call_method("hide", &[]).unwrap();
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            child.call_method("is_visible", &[]).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            child.call_method("is_visible_in_tree", &[]).unwrap(),
+            Value::Bool(false)
+        );
+        parent.call_method("show", &[]).unwrap();
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+        assert!(is_visible_in_tree(&child));
+    }
+
+    /// Tree throughput across threads; run with
+    /// `cargo test --release tree_throughput -- --ignored --nocapture`
+    #[test]
+    fn notifications_follow_tree_order() {
+        use std::sync::{Arc, Mutex};
+
+        init_object_class();
+        init_gem_class();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let node = |name: &'static str| {
+            let node = object_new("Gem");
+            for handler in ["_notification", "on_notification"] {
+                let log = log.clone();
+                ObjectInner::insert_method(
+                    &node,
+                    handler,
+                    Arc::new(move |_, args| {
+                        log.lock()
+                            .unwrap()
+                            .push(format!("{} {} {:?}", name, handler, args[0]));
+                        Ok(Value::Null)
+                    }),
+                );
+            }
+            node
+        };
+        let root = node("Root");
+        let child = node("Child");
+        root.call_method("add_child", &[Value::Object(child.clone())])
+            .unwrap();
+        let take = || std::mem::take(&mut *log.lock().unwrap());
+
+        enter_tree(&root);
+        propagate_ready(&root);
+        assert_eq!(
+            take(),
+            [
+                "Root _notification Int(10)",
+                "Root on_notification Int(10)",
+                "Child _notification Int(10)",
+                "Child on_notification Int(10)",
+                "Child _notification Int(13)",
+                "Child on_notification Int(13)",
+                "Root _notification Int(13)",
+                "Root on_notification Int(13)",
+            ]
+        );
+        root.call_method(
+            "propagate_notification",
+            &[Value::Int(object::NOTIFICATION_WM_CLOSE_REQUEST)],
+        )
+        .unwrap();
+        assert_eq!(take().len(), 4);
+        // Leaving the tree notifies children first
+        root.call_method("remove_child", &[Value::Object(child.clone())])
+            .unwrap();
+        assert_eq!(
+            take(),
+            [
+                "Child _notification Int(11)",
+                "Child on_notification Int(11)"
+            ]
+        );
+        child
+            .call_method("notification", &[Value::Int(object::NOTIFICATION_PAUSED)])
+            .unwrap();
+        assert_eq!(take().len(), 2);
+    }
+
+    #[test]
+    fn restructuring_keeps_order_and_tree_state() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        init_object_class();
+        init_gem_class();
+        let named = |name: &str| {
+            let node = object_new("Gem");
+            node.set_property(NAME_KEY, Value::String(name.into()));
+            node
+        };
+        let names =
+            |node: &ObjectRef| -> Vec<String> { children_of(node).iter().map(node_name).collect() };
+        let root = named("Root");
+        let (a, c) = (named("A"), named("C"));
+        for child in [&a, &c] {
+            root.call_method("add_child", &[Value::Object(child.clone())])
+                .unwrap();
+        }
+        root.call_method(
+            "add_child_at",
+            &[Value::Object(named("Top")), Value::Int(0)],
+        )
+        .unwrap();
+        a.call_method("add_sibling", &[Value::Object(named("B"))])
+            .unwrap();
+        assert_eq!(names(&root), ["Top", "A", "B", "C"]);
+        assert!(
+            root.call_method("add_child_at", &[Value::Object(a.clone()), Value::Int(0)])
+                .is_err()
+        );
+        // add_child takes orphans too, and none that would make a cycle
+        let err = c
+            .call_method("add_child", &[Value::Object(a.clone())])
+            .unwrap_err();
+        assert!(err.contains("already has a parent"), "{}", err);
+        assert!(
+            root.call_method("add_child", &[Value::Object(root.clone())])
+                .is_err()
+        );
+        let loose = named("Loose");
+        loose
+            .call_method("add_child", &[Value::Object(named("Leaf"))])
+            .unwrap();
+        let leaf = children_of(&loose)[0].clone();
+        let err = leaf
+            .call_method("add_child", &[Value::Object(loose.clone())])
+            .unwrap_err();
+        assert!(err.contains("under itself"), "{}", err);
+        assert_eq!(names(&c), Vec::<String>::new());
+
+        enter_tree(&root);
+        let entered = Arc::new(AtomicUsize::new(0));
+        let seen = entered.clone();
+        a.connect(
+            "tree_entered",
+            Arc::new(move |_| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        a.set_property(POSITION_KEY, Value::vec2(10.0, 0.0));
+        c.set_property(POSITION_KEY, Value::vec2(100.0, 0.0));
+        a.call_method("reparent", &[Value::Object(c.clone())])
+            .unwrap();
+        assert_eq!(names(&root), ["Top", "B", "C"]);
+        assert_eq!(names(&c), ["A"]);
+        // Stayed in the tree and where it was on screen
+        assert_eq!(entered.load(Ordering::SeqCst), 0);
+        assert!(is_in_tree(&a));
+        assert_eq!(a.get_property(POSITION_KEY), Some(Value::vec2(-90.0, 0.0)));
+        assert!(
+            c.call_method("reparent", &[Value::Object(a.clone())])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tree_string_lists_the_subtree() {
+        init_object_class();
+        init_gem_class();
+        let root = object_new("Gem");
+        root.set_property(NAME_KEY, Value::String("Hud".into()));
+        let label = object_new("Gem");
+        label.set_property(NAME_KEY, Value::String("Score".into()));
+        label.set_property("text", Value::String("0".into()));
+        label.set_property(POSITION_KEY, Value::vec2(4.0, 8.0));
+        root.call_method("add_child", &[Value::Object(label.clone())])
+            .unwrap();
+        set_owner(&label, Some(&root)).unwrap();
+        set_unique_name_in_owner(&label, true).unwrap();
+        assert_eq!(
+            root.call_method("get_tree_string", &[]).unwrap(),
+            Value::String("Hud (Gem)\n  %Score (Gem) position=(4.0, 8.0) text=\"0\"\n".into())
+        );
+    }
+
+    #[test]
+    fn unique_names_resolve_across_restructuring() {
+        init_object_class();
+        init_gem_class();
+        let named = |name: &str| {
+            let node = object_new("Gem");
+            node.set_property(NAME_KEY, Value::String(name.into()));
+            node
+        };
+        let add = |parent: &ObjectRef, child: &ObjectRef| {
+            parent
+                .call_method("add_child", &[Value::Object(child.clone())])
+                .unwrap();
+        };
+        let scene = named("Hud");
+        let panel = named("Panel");
+        let score = named("Score");
+        let deep = named("Deep");
+        add(&scene, &panel);
+        add(&panel, &score);
+        add(&panel, &deep);
+        for node in [&panel, &score, &deep] {
+            set_owner(node, Some(&scene)).unwrap();
+        }
+        set_unique_name_in_owner(&score, true).unwrap();
+
+        let find = |from: &ObjectRef, path: &str| get_node_by_path(from, path);
+        assert_eq!(find(&deep, "%Score"), Some(score.clone()));
+        assert_eq!(find(&scene, "%Score"), Some(score.clone()));
+        // Moving the node within its scene keeps the lookup working
+        panel
+            .call_method("remove_child", &[Value::Object(score.clone())])
+            .unwrap();
+        add(&deep, &score);
+        add(&score, &named("Icon"));
+        assert_eq!(find(&panel, "%Score"), Some(score.clone()));
+        assert!(find(&panel, "%Score/Icon").is_some());
+
+        // A second %Score in the same scene is refused
+        let other = named("Score");
+        add(&scene, &other);
+        set_owner(&other, Some(&scene)).unwrap();
+        assert!(set_unique_name_in_owner(&other, true).is_err());
+        set_unique_name_in_owner(&score, false).unwrap();
+        assert_eq!(find(&deep, "%Score"), None);
+        set_unique_name_in_owner(&other, true).unwrap();
+        assert_eq!(find(&deep, "%Score"), Some(other));
+    }
+
+    /// add_child / get_node throughput across threads, against the single
+    /// global table of every node's links that per-node storage replaced. Run
+    /// with `cargo test --release tree_throughput -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn tree_throughput() {
+        use std::collections::HashMap;
+        use std::sync::LazyLock;
+        use std::time::{Duration, Instant};
+        init_object_class();
+        init_gem_class();
+        const THREADS: usize = 4;
+        const CHILDREN: usize = 2000;
+        const LOOKUPS: usize = 200;
+
+        type Add = fn(&ObjectRef, &ObjectRef);
+        type Find = fn(&ObjectRef, &str) -> Option<ObjectRef>;
+        // The slowest thread's add_child and get_node times
+        let measure = |enter: fn(&ObjectRef), add: Add, find: Find| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    std::thread::spawn(move || {
+                        let root = object_new("Gem");
+                        enter(&root);
+                        let children: Vec<_> = (0..CHILDREN)
+                            .map(|i| {
+                                let child = object_new("Gem");
+                                child.set_property(NAME_KEY, Value::String(format!("N{}", i)));
+                                child
+                            })
+                            .collect();
+                        let start = Instant::now();
+                        for child in &children {
+                            add(&root, child);
+                        }
+                        let added = start.elapsed();
+                        let start = Instant::now();
+                        for i in (0..CHILDREN).step_by(CHILDREN / LOOKUPS) {
+                            assert!(find(&root, &format!("N{}", i)).is_some());
+                        }
+                        (added, start.elapsed())
+                    })
+                })
+                .collect();
+            let times: Vec<(Duration, Duration)> =
+                handles.into_iter().map(|h| h.join().unwrap()).collect();
+            (
+                times.iter().map(|t| t.0).max().unwrap(),
+                times.iter().map(|t| t.1).max().unwrap(),
+            )
+        };
+
+        let per_node = measure(
+            enter_tree,
+            |root, child| {
+                root.call_method("add_child", &[Value::Object(child.clone())])
+                    .unwrap();
+            },
+            |root, name| match root.call_method("get_node", &[Value::String(name.into())]) {
+                Ok(Value::Object(node)) => Some(node),
+                _ => None,
+            },
+        );
+
+        // The table as add_child and get_node used it; without method
+        // dispatch, which errs in its favour
+        static TABLE: LazyLock<RwLock<HashMap<u64, GemPrivate>>> = LazyLock::new(Default::default);
+        let table = measure(
+            |root| TABLE.write().unwrap().entry(root.id()).or_default().in_tree = true,
+            |root, child| {
+                TABLE
+                    .write()
+                    .unwrap()
+                    .entry(root.id())
+                    .or_default()
+                    .children
+                    .push(child.clone());
+                TABLE.write().unwrap().entry(child.id()).or_default().parent =
+                    Some(root.downgrade());
+                if TABLE.read().unwrap()[&root.id()].in_tree {
+                    TABLE.write().unwrap().get_mut(&child.id()).unwrap().in_tree = true;
+                    child.notification(object::NOTIFICATION_ENTER_TREE);
+                    child.emit_signal("tree_entered", &[]);
+                }
+                root.emit_signal("child_entered_tree", &[]);
+            },
+            |root, name| {
+                let children = TABLE.read().unwrap()[&root.id()].children.clone();
+                children
+                    .into_iter()
+                    .find(|c| c.get_property(NAME_KEY) == Some(Value::String(name.into())))
+            },
+        );
+
+        for (storage, (added, found)) in [("per-node", per_node), ("global table", table)] {
+            println!(
+                "{}: {} threads, {} add_child in {:?}, {} get_node in {:?} (slowest thread)",
+                storage, THREADS, CHILDREN, added, LOOKUPS, found,
+            );
+        }
     }
 }
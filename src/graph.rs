@@ -0,0 +1,322 @@
+//! Dependency graph export for `gem graph`.
+//! Draws the project's reference graph (see `project`) as Graphviz DOT or a
+//! self-contained HTML page. Scenes the registry names are scenes; scenes
+//! only reached from other scenes or scripts are prefabs. Reference cycles
+//! are found and drawn in red.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::project::{FileKind, ProjectGraph};
+
+/// Column spacing of the HTML layout, in px
+const COLUMN: usize = 260;
+/// Row spacing of the HTML layout, in px
+const ROW: usize = 44;
+const NODE_W: usize = 200;
+const NODE_H: usize = 28;
+
+/// How a file is shown in the graph
+fn label(graph: &ProjectGraph, path: &Path) -> &'static str {
+    match graph.files.get(path) {
+        Some(FileKind::Scene) => {
+            let from_registry = graph
+                .edges
+                .iter()
+                .any(|(from, to)| to == path && graph.files.get(from) == Some(&FileKind::Registry));
+            if from_registry { "scene" } else { "prefab" }
+        }
+        Some(kind) => kind.as_str(),
+        None => "asset",
+    }
+}
+
+fn color(label: &str) -> &'static str {
+    match label {
+        "registry" => "#9e9e9e",
+        "scene" => "#64b5f6",
+        "prefab" => "#4db6ac",
+        "logic" => "#ffb74d",
+        _ => "#e0e0e0",
+    }
+}
+
+fn adjacency(graph: &ProjectGraph) -> BTreeMap<&PathBuf, Vec<&PathBuf>> {
+    let mut out: BTreeMap<&PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for (from, to) in &graph.edges {
+        out.entry(from).or_default().push(to);
+    }
+    out
+}
+
+/// Every reference cycle, as the files along it starting from the smallest
+/// path
+pub fn cycles(graph: &ProjectGraph) -> Vec<Vec<PathBuf>> {
+    let next = adjacency(graph);
+    let mut out = Vec::new();
+    let mut done = BTreeSet::new();
+    for start in graph.files.keys() {
+        walk(&next, start, &mut Vec::new(), &mut done, &mut out);
+    }
+    out
+}
+
+fn walk<'g>(
+    next: &BTreeMap<&'g PathBuf, Vec<&'g PathBuf>>,
+    node: &'g PathBuf,
+    stack: &mut Vec<&'g PathBuf>,
+    done: &mut BTreeSet<&'g PathBuf>,
+    out: &mut Vec<Vec<PathBuf>>,
+) {
+    if let Some(at) = stack.iter().position(|p| *p == node) {
+        let mut cycle: Vec<PathBuf> = stack[at..].iter().map(|p| p.to_path_buf()).collect();
+        let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or(0);
+        cycle.rotate_left(first);
+        if !out.contains(&cycle) {
+            out.push(cycle);
+        }
+        return;
+    }
+    if done.contains(node) {
+        return;
+    }
+    stack.push(node);
+    for to in next.get(node).into_iter().flatten() {
+        walk(next, to, stack, done, out);
+    }
+    stack.pop();
+    done.insert(node);
+}
+
+/// Edges that lie on a cycle
+fn cycle_edges(cycles: &[Vec<PathBuf>]) -> BTreeSet<(PathBuf, PathBuf)> {
+    let mut out = BTreeSet::new();
+    for cycle in cycles {
+        for (i, from) in cycle.iter().enumerate() {
+            out.insert((from.clone(), cycle[(i + 1) % cycle.len()].clone()));
+        }
+    }
+    out
+}
+
+/// The graph in Graphviz DOT
+pub fn to_dot(graph: &ProjectGraph) -> String {
+    let looped = cycle_edges(&cycles(graph));
+    let mut out =
+        String::from("digraph gem {\n    rankdir=LR;\n    node [shape=box, style=filled];\n");
+    for path in graph.files.keys() {
+        let kind = label(graph, path);
+        out.push_str(&format!(
+            "    \"{}\" [label=\"{}\\n{}\", fillcolor=\"{}\"];\n",
+            dot_escape(&path.display().to_string()),
+            dot_escape(&file_name(path)),
+            kind,
+            color(kind)
+        ));
+    }
+    for (from, to) in &graph.edges {
+        let attrs = if looped.contains(&(from.clone(), to.clone())) {
+            " [color=red, penwidth=2]"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "    \"{}\" -> \"{}\"{};\n",
+            dot_escape(&from.display().to_string()),
+            dot_escape(&to.display().to_string()),
+            attrs
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The graph as a standalone HTML page: an SVG drawing laid out in columns
+/// by distance from the registry, then a list of cycles
+pub fn to_html(graph: &ProjectGraph) -> String {
+    let cycles = cycles(graph);
+    let looped = cycle_edges(&cycles);
+    let positions = layout(graph);
+    let width = positions.values().map(|p| p.0).max().unwrap_or(0) + NODE_W + 20;
+    let height = positions.values().map(|p| p.1).max().unwrap_or(0) + NODE_H + 20;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\
+         <path d=\"M0,0 L10,5 L0,10 z\" fill=\"#555\"/></marker></defs>\n",
+        w = width,
+        h = height
+    );
+    for (from, to) in &graph.edges {
+        let (Some(a), Some(b)) = (positions.get(from), positions.get(to)) else {
+            continue;
+        };
+        let stroke = if looped.contains(&(from.clone(), to.clone())) {
+            "red"
+        } else {
+            "#555"
+        };
+        svg.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" marker-end=\"url(#arrow)\"/>\n",
+            a.0 + NODE_W,
+            a.1 + NODE_H / 2,
+            b.0,
+            b.1 + NODE_H / 2,
+            stroke
+        ));
+    }
+    for (path, (x, y)) in &positions {
+        let kind = label(graph, path);
+        svg.push_str(&format!(
+            "<g><title>{}</title><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\" fill=\"{}\" stroke=\"#333\"/>\
+             <text x=\"{}\" y=\"{}\" font-size=\"12\">{} ({})</text></g>\n",
+            html_escape(&path.display().to_string()),
+            x,
+            y,
+            NODE_W,
+            NODE_H,
+            color(kind),
+            x + 8,
+            y + 18,
+            html_escape(&file_name(path)),
+            kind
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    let mut list = String::new();
+    if cycles.is_empty() {
+        list.push_str("<p>No cycles.</p>\n");
+    } else {
+        list.push_str("<h2>Cycles</h2>\n<ul>\n");
+        for cycle in &cycles {
+            let names: Vec<String> = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|p| html_escape(&p.display().to_string()))
+                .collect();
+            list.push_str(&format!("<li>{}</li>\n", names.join(" &rarr; ")));
+        }
+        list.push_str("</ul>\n");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Project graph</title>\n\
+         <style>body {{ font-family: sans-serif; }} li {{ color: red; }}</style>\n</head>\n<body>\n\
+         <h1>Project graph</h1>\n{}{}</body>\n</html>\n",
+        svg, list
+    )
+}
+
+/// Top-left corner of each file's box: the column is its distance from the
+/// registry, the row its order within the column
+fn layout(graph: &ProjectGraph) -> BTreeMap<PathBuf, (usize, usize)> {
+    let next = adjacency(graph);
+    let mut column: BTreeMap<&PathBuf, usize> = BTreeMap::new();
+    let mut queue: VecDeque<&PathBuf> = graph
+        .files
+        .iter()
+        .filter(|(_, kind)| **kind == FileKind::Registry)
+        .map(|(path, _)| path)
+        .collect();
+    for path in &queue {
+        column.insert(path, 0);
+    }
+    while let Some(path) = queue.pop_front() {
+        let depth = column[path];
+        for to in next.get(path).into_iter().flatten() {
+            if !column.contains_key(to) {
+                column.insert(to, depth + 1);
+                queue.push_back(to);
+            }
+        }
+    }
+    let mut rows: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut out = BTreeMap::new();
+    for path in graph.files.keys() {
+        let col = column.get(path).copied().unwrap_or(0);
+        let row = rows.entry(col).or_default();
+        out.insert(path.clone(), (10 + col * COLUMN, 10 + *row * ROW));
+        *row += 1;
+    }
+    out
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(files: &[(&str, FileKind)], edges: &[(&str, &str)]) -> ProjectGraph {
+        ProjectGraph {
+            files: files.iter().map(|(p, k)| (PathBuf::from(p), *k)).collect(),
+            edges: edges
+                .iter()
+                .map(|(a, b)| (PathBuf::from(a), PathBuf::from(b)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exports_kinds_and_cycles() {
+        let g = graph(
+            &[
+                ("p/scenes.registry.gem", FileKind::Registry),
+                ("p/menu.gem", FileKind::Scene),
+                ("p/level.gem", FileKind::Scene),
+                ("p/enemy.gem", FileKind::Scene),
+                ("p/logic/enemy.gem", FileKind::Logic),
+                ("p/enemy.png", FileKind::Asset),
+            ],
+            &[
+                ("p/scenes.registry.gem", "p/menu.gem"),
+                ("p/scenes.registry.gem", "p/level.gem"),
+                ("p/level.gem", "p/enemy.gem"),
+                ("p/enemy.gem", "p/logic/enemy.gem"),
+                ("p/enemy.gem", "p/enemy.png"),
+                ("p/logic/enemy.gem", "p/level.gem"),
+            ],
+        );
+        assert_eq!(label(&g, Path::new("p/level.gem")), "scene");
+        assert_eq!(label(&g, Path::new("p/enemy.gem")), "prefab");
+        assert_eq!(
+            cycles(&g),
+            [vec![
+                PathBuf::from("p/enemy.gem"),
+                PathBuf::from("p/logic/enemy.gem"),
+                PathBuf::from("p/level.gem"),
+            ]]
+        );
+
+        let dot = to_dot(&g);
+        assert!(dot.starts_with("digraph gem {"));
+        assert!(dot.contains("\"p/enemy.gem\" [label=\"enemy.gem\\nprefab\""));
+        assert!(dot.contains("\"p/logic/enemy.gem\" -> \"p/level.gem\" [color=red"));
+        assert!(dot.contains("\"p/enemy.gem\" -> \"p/enemy.png\";"));
+
+        let html = to_html(&g);
+        assert!(html.contains("<svg"));
+        assert!(html.contains(
+            "p/enemy.gem &rarr; p/logic/enemy.gem &rarr; p/level.gem &rarr; p/enemy.gem"
+        ));
+        let none = graph(&[("p/scenes.registry.gem", FileKind::Registry)], &[]);
+        assert!(cycles(&none).is_empty());
+        assert!(to_html(&none).contains("No cycles."));
+    }
+}
@@ -0,0 +1,4 @@
+//! Scenes made from other tools' files, so a directive to one can be used
+//! wherever a `.gem` scene can.
+
+pub mod gltf;
@@ -0,0 +1,709 @@
+//! glTF 2.0 models: a `.gltf` file with its buffers (separate files or base64
+//! `data:` URIs), or one binary `.glb`. `import` turns the default scene's
+//! node hierarchy into a scene of MeshGems, which `spawn` and `change_scene`
+//! take like any other (`spawn(#models:ship.glb)`). Each node keeps its name
+//! and its translation, rotation and scale, or its matrix; a node with a mesh
+//! draws it through `mesh: #models:ship.glb:mesh(i)` in its material's base
+//! color. A mesh whose primitives differ in color becomes one child per
+//! primitive (`mesh(i, p)`). A model with animations or skins is rooted in a
+//! SkeletonGem, which plays the clips `animations` reads on those nodes;
+//! meshes are not deformed by their skins. Textures, morph targets, cameras
+//! and lights are ignored.
+
+use std::path::Path;
+
+use crate::animation::{Channel, Clip, Target};
+use crate::ast;
+use crate::json::{self, Json};
+use crate::mesh::{self, Mesh};
+use crate::transform3d::{self, NO_ROTATION};
+use crate::vfs;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+/// A primitive's `mode` for triangle lists, the default
+const TRIANGLES: usize = 4;
+const MESH_TYPE: &str = "MeshGem";
+const SKELETON_TYPE: &str = "SkeletonGem";
+
+/// `.gltf` or `.glb`
+pub fn is_model(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+}
+
+/// The model at `path` as a scene: a MeshGem named after the file holding
+/// the default scene's nodes
+pub fn import(path: &Path) -> Result<ast::GemFile, String> {
+    let doc = read(path)?;
+    let source = ast::Directive::new(
+        path.iter()
+            .map(|s| s.to_string_lossy().into_owned())
+            .collect(),
+    );
+    let mut placed = vec![false; doc.list("nodes").len()];
+    let children = scene_roots(&doc)
+        .into_iter()
+        .map(|i| node_decl(&doc, i, &source, &mut placed))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let name = path
+        .file_stem()
+        .map_or("Model".into(), |s| s.to_string_lossy().into_owned());
+    let mut root = decl(name, Vec::new(), children);
+    if !doc.list("animations").is_empty() || !doc.list("skins").is_empty() {
+        root.gem_type = SKELETON_TYPE.to_string();
+        root.properties.push(ast::Property {
+            key: "model".to_string(),
+            value: ast::Value::Directive(source),
+        });
+    }
+    Ok(ast::GemFile {
+        root,
+        errors: Vec::new(),
+    })
+}
+
+/// The model's animations, moving the nodes `import` makes of it
+pub fn animations(path: &Path) -> Result<Vec<Clip>, String> {
+    let doc = read(path)?;
+    let paths = node_paths(&doc);
+    let mut clips = Vec::new();
+    for (a, animation) in doc.list("animations").iter().enumerate() {
+        let name = animation
+            .get("name")
+            .and_then(Json::as_str)
+            .filter(|n| !n.is_empty())
+            .map_or_else(|| format!("Animation{}", a), str::to_string);
+        let samplers = animation.get("samplers").map_or(&[][..], Json::items);
+        let mut channels = Vec::new();
+        for (c, channel) in animation
+            .get("channels")
+            .map_or(&[][..], Json::items)
+            .iter()
+            .enumerate()
+        {
+            let at = |e: String| {
+                format!(
+                    "{}: animations[{}].channels[{}]: {}",
+                    path.display(),
+                    a,
+                    c,
+                    e
+                )
+            };
+            let target = channel.get("target");
+            let property = target.and_then(|t| t.get("path")).and_then(Json::as_str);
+            let target = match property {
+                Some("translation") => Target::Position,
+                Some("rotation") => Target::Rotation,
+                Some("scale") => Target::Scale,
+                // Morph target weights
+                _ => continue,
+            };
+            let node = channel
+                .get("target")
+                .and_then(|t| t.get("node"))
+                .and_then(Json::as_usize)
+                .and_then(|n| paths.get(n).cloned().flatten());
+            // Nodes outside the default scene aren't imported
+            let Some(node) = node else {
+                continue;
+            };
+            let sampler = channel
+                .get("sampler")
+                .and_then(Json::as_usize)
+                .and_then(|s| samplers.get(s))
+                .ok_or_else(|| at("its sampler does not exist".into()))?;
+            let accessor_of = |key| {
+                sampler
+                    .get(key)
+                    .and_then(Json::as_usize)
+                    .ok_or_else(|| at(format!("the sampler has no {}", key)))
+            };
+            let times = accessor(&doc, accessor_of("input")?, 1).map_err(at)?;
+            let width = if target == Target::Rotation { 4 } else { 3 };
+            let flat = accessor(&doc, accessor_of("output")?, width).map_err(at)?;
+            let mut values: Vec<[f64; 4]> = flat
+                .chunks_exact(width)
+                .map(|v| std::array::from_fn(|i| v.get(i).copied().unwrap_or(0.0)))
+                .collect();
+            let interpolation = sampler.get("interpolation").and_then(Json::as_str);
+            if interpolation == Some("CUBICSPLINE") {
+                // In-tangent, value, out-tangent per key; the curve is
+                // approximated by straight lines between the values
+                values = values.chunks_exact(3).map(|key| key[1]).collect();
+            }
+            if values.len() != times.len() {
+                return Err(at(format!(
+                    "{} keys but {} values",
+                    times.len(),
+                    values.len()
+                )));
+            }
+            channels.push(Channel {
+                node,
+                target,
+                step: interpolation == Some("STEP"),
+                times,
+                values,
+            });
+        }
+        clips.push(Clip { name, channels });
+    }
+    Ok(clips)
+}
+
+/// Mesh `index` of the model at `path`: its triangles from every primitive,
+/// or from just `primitive`
+pub fn mesh(path: &Path, index: usize, primitive: Option<usize>) -> Result<Mesh, String> {
+    let doc = read(path)?;
+    let at = |e: String| format!("{}: {}", path.display(), e);
+    let primitives = doc.item("meshes", index).map_err(at)?.get("primitives");
+    let primitives = primitives.map_or(&[][..], Json::items);
+    if primitive.is_some_and(|p| p >= primitives.len()) {
+        return Err(at(format!(
+            "meshes[{}] has {} primitives",
+            index,
+            primitives.len()
+        )));
+    }
+    let mut mesh = Mesh::default();
+    for (p, prim) in primitives.iter().enumerate() {
+        if primitive.is_none_or(|only| only == p) {
+            append_primitive(&doc, prim, &mut mesh)
+                .map_err(|e| at(format!("meshes[{}].primitives[{}]: {}", index, p, e)))?;
+        }
+    }
+    Ok(mesh)
+}
+
+/// A model's JSON and the bytes of its buffers
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document {
+    /// A top-level array such as `nodes`
+    fn list(&self, key: &str) -> &[Json] {
+        self.json.get(key).map_or(&[], Json::items)
+    }
+
+    fn item(&self, key: &str, index: usize) -> Result<&Json, String> {
+        self.list(key)
+            .get(index)
+            .ok_or_else(|| format!("{}[{}] does not exist", key, index))
+    }
+}
+
+fn read(path: &Path) -> Result<Document, String> {
+    let bytes = vfs::read(&path.to_string_lossy())?;
+    let at = |e: String| format!("{}: {}", path.display(), e);
+    let (text, bin) = if bytes.starts_with(GLB_MAGIC) {
+        glb_chunks(&bytes).map_err(at)?
+    } else {
+        (&bytes[..], None)
+    };
+    let text = std::str::from_utf8(text).map_err(|_| at("the JSON is not UTF-8".into()))?;
+    let json = json::parse(text).map_err(at)?;
+    let version = json
+        .get("asset")
+        .and_then(|a| a.get("version"))
+        .and_then(Json::as_str);
+    if !version.is_some_and(|v| v.starts_with("2.")) {
+        return Err(at(format!(
+            "only glTF 2.0 is supported, found version {}",
+            version.unwrap_or("none")
+        )));
+    }
+    let mut buffers = Vec::new();
+    for (i, buffer) in json
+        .get("buffers")
+        .map_or(&[][..], Json::items)
+        .iter()
+        .enumerate()
+    {
+        let data = match buffer.get("uri").and_then(Json::as_str) {
+            Some(uri) if uri.starts_with("data:") => data_uri(uri).map_err(at)?,
+            // Relative to the model
+            Some(uri) => {
+                let file = path.parent().unwrap_or(Path::new("")).join(uri);
+                vfs::read(&file.to_string_lossy())?
+            }
+            // A .glb's first buffer is its binary chunk
+            None => match (i, bin) {
+                (0, Some(bin)) => bin.to_vec(),
+                _ => return Err(at(format!("buffers[{}] has no uri", i))),
+            },
+        };
+        buffers.push(data);
+    }
+    Ok(Document { json, buffers })
+}
+
+/// The JSON chunk of a `.glb`, and its binary chunk if it has one
+fn glb_chunks(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .and_then(|b| <[u8; 4]>::try_from(b).ok())
+            .map(u32::from_le_bytes)
+    };
+    if u32_at(4) != Some(2) {
+        return Err("only version 2 .glb files are supported".into());
+    }
+    let (mut at, mut text, mut bin) = (12, None, None);
+    while let (Some(len), Some(kind)) = (u32_at(at), u32_at(at + 4)) {
+        let end = at + 8 + len as usize;
+        let data = bytes
+            .get(at + 8..end)
+            .ok_or("a chunk runs past the end of the file")?;
+        match kind {
+            CHUNK_JSON if text.is_none() => text = Some(data),
+            CHUNK_BIN if bin.is_none() => bin = Some(data),
+            _ => {}
+        }
+        at = end;
+    }
+    Ok((text.ok_or("the file has no JSON chunk")?, bin))
+}
+
+/// The bytes of a base64 `data:` URI
+fn data_uri(uri: &str) -> Result<Vec<u8>, String> {
+    let (_, data) = uri
+        .split_once(";base64,")
+        .ok_or("only base64 data: URIs are supported")?;
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in data.bytes().filter(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("'{}' is not base64", c as char)),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// The default scene's top nodes; without scenes, every node no other holds
+fn scene_roots(doc: &Document) -> Vec<usize> {
+    let scene = doc.json.get("scene").and_then(Json::as_usize).unwrap_or(0);
+    if let Some(scene) = doc.list("scenes").get(scene) {
+        return indices(scene.get("nodes"));
+    }
+    let held: Vec<usize> = doc
+        .list("nodes")
+        .iter()
+        .flat_map(|n| indices(n.get("children")))
+        .collect();
+    (0..doc.list("nodes").len())
+        .filter(|i| !held.contains(i))
+        .collect()
+}
+
+/// Each node's path below the imported root, as `get_node` takes it; `None`
+/// for nodes outside the default scene
+fn node_paths(doc: &Document) -> Vec<Option<String>> {
+    let mut paths = vec![None; doc.list("nodes").len()];
+    let mut todo: Vec<(usize, String)> = scene_roots(doc)
+        .into_iter()
+        .map(|i| (i, String::new()))
+        .collect();
+    while let Some((index, parent)) = todo.pop() {
+        let Some(node) = doc.list("nodes").get(index) else {
+            continue;
+        };
+        if paths[index].is_some() {
+            continue;
+        }
+        let name = node_name(node, index);
+        let path = if parent.is_empty() {
+            name
+        } else {
+            format!("{}/{}", parent, name)
+        };
+        for child in indices(node.get("children")) {
+            todo.push((child, path.clone()));
+        }
+        paths[index] = Some(path);
+    }
+    paths
+}
+
+/// A node's name, or `NodeN` without one; `/` would split its path
+fn node_name(node: &Json, index: usize) -> String {
+    node.get("name")
+        .and_then(Json::as_str)
+        .filter(|n| !n.is_empty())
+        .map_or_else(|| format!("Node{}", index), |n| n.replace('/', "_"))
+}
+
+fn indices(list: Option<&Json>) -> Vec<usize> {
+    list.map_or(&[][..], Json::items)
+        .iter()
+        .filter_map(Json::as_usize)
+        .collect()
+}
+
+/// Node `index` and everything under it
+fn node_decl(
+    doc: &Document,
+    index: usize,
+    source: &ast::Directive,
+    placed: &mut [bool],
+) -> Result<ast::GemDecl, String> {
+    let node = doc.item("nodes", index)?;
+    if std::mem::replace(&mut placed[index], true) {
+        return Err(format!("nodes[{}] appears twice in the hierarchy", index));
+    }
+    let name = node_name(node, index);
+    let mut properties = transform(node).map_err(|e| format!("nodes[{}]: {}", index, e))?;
+    let mut children = Vec::new();
+    if let Some(m) = node.get("mesh").and_then(Json::as_usize) {
+        let primitives = doc.item("meshes", m)?.get("primitives");
+        let colors = primitives
+            .map_or(&[][..], Json::items)
+            .iter()
+            .map(|p| base_color(doc, p))
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.windows(2).all(|w| w[0] == w[1]) {
+            properties.push(mesh_prop(source, &[m]));
+            properties.extend(colors.into_iter().flatten().next().map(color_prop));
+        } else {
+            for (p, color) in colors.into_iter().enumerate() {
+                let mut props = vec![mesh_prop(source, &[m, p])];
+                props.extend(color.map(color_prop));
+                children.push(decl(format!("Primitive{}", p), props, Vec::new()));
+            }
+        }
+    }
+    for child in indices(node.get("children")) {
+        children.push(node_decl(doc, child, source, placed)?);
+    }
+    Ok(decl(name, properties, children))
+}
+
+fn decl(name: String, properties: Vec<ast::Property>, children: Vec<ast::GemDecl>) -> ast::GemDecl {
+    ast::GemDecl {
+        name,
+        unique: false,
+        gem_type: MESH_TYPE.to_string(),
+        properties,
+        meta: Vec::new(),
+        children,
+    }
+}
+
+/// `mesh: #models:ship.glb:mesh(m)`, or `mesh(m, p)` for one primitive
+fn mesh_prop(source: &ast::Directive, indices: &[usize]) -> ast::Property {
+    let mut directive = source.clone();
+    directive.args.push(ast::DirectiveArg::Call {
+        name: "mesh".to_string(),
+        values: indices
+            .iter()
+            .map(|&i| ast::Value::Integer(i as i64))
+            .collect(),
+    });
+    ast::Property {
+        key: "mesh".to_string(),
+        value: ast::Value::Directive(directive),
+    }
+}
+
+fn color_prop(rgba: [u8; 4]) -> ast::Property {
+    ast::Property {
+        key: "color".to_string(),
+        value: ast::Value::Color(rgba),
+    }
+}
+
+/// A node's `position`, `rotation` and `scale`, each left out at its default
+fn transform(node: &Json) -> Result<Vec<ast::Property>, String> {
+    let numbers = |key: &str, len: usize| -> Result<Option<Vec<f64>>, String> {
+        let Some(value) = node.get(key) else {
+            return Ok(None);
+        };
+        let numbers: Vec<f64> = value.items().iter().filter_map(Json::as_f64).collect();
+        if numbers.len() != len {
+            return Err(format!("{} takes {} numbers", key, len));
+        }
+        Ok(Some(numbers))
+    };
+    let (translation, rotation, scale) = match numbers("matrix", 16)? {
+        Some(m) => transform3d::decompose(&std::array::from_fn(|i| m[i])),
+        None => (
+            numbers("translation", 3)?.map_or([0.0; 3], |t| [t[0], t[1], t[2]]),
+            numbers("rotation", 4)?.map_or(NO_ROTATION, |q| {
+                transform3d::quaternion_rotation([q[0], q[1], q[2], q[3]])
+            }),
+            numbers("scale", 3)?.map_or([1.0; 3], |s| [s[0], s[1], s[2]]),
+        ),
+    };
+    let mut properties = Vec::new();
+    for (key, value, default) in [
+        ("position", translation, 0.0),
+        ("rotation", transform3d::angles(&rotation), 0.0),
+        ("scale", scale, 1.0),
+    ] {
+        if value.iter().any(|&v| v != default) {
+            properties.push(ast::Property {
+                key: key.to_string(),
+                value: ast::Value::Tuple(value.map(ast::Value::Number).to_vec()),
+            });
+        }
+    }
+    Ok(properties)
+}
+
+/// The sRGB base color of a primitive's material, if it has one
+fn base_color(doc: &Document, primitive: &Json) -> Result<Option<[u8; 4]>, String> {
+    let Some(material) = primitive.get("material").and_then(Json::as_usize) else {
+        return Ok(None);
+    };
+    let factor = doc
+        .item("materials", material)?
+        .get("pbrMetallicRoughness")
+        .and_then(|p| p.get("baseColorFactor"));
+    let mut rgba = [1.0; 4];
+    for (c, v) in rgba.iter_mut().zip(factor.map_or(&[][..], Json::items)) {
+        *c = v.as_f64().unwrap_or(1.0).clamp(0.0, 1.0);
+    }
+    // Factors are linear; alpha stays as it is
+    for c in &mut rgba[..3] {
+        *c = if *c <= 0.003_130_8 {
+            *c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+    }
+    Ok(Some(rgba.map(|c| (c * 255.0).round() as u8)))
+}
+
+fn append_primitive(doc: &Document, primitive: &Json, mesh: &mut Mesh) -> Result<(), String> {
+    let mode = primitive
+        .get("mode")
+        .and_then(Json::as_usize)
+        .unwrap_or(TRIANGLES);
+    if mode != TRIANGLES {
+        eprintln!(
+            "[WARN] glTF: only triangle primitives are drawn, skipping mode {}",
+            mode
+        );
+        return Ok(());
+    }
+    let attribute = |name| {
+        primitive
+            .get("attributes")
+            .and_then(|a| a.get(name))
+            .and_then(Json::as_usize)
+    };
+    let positions = accessor(
+        doc,
+        attribute("POSITION").ok_or("no POSITION attribute")?,
+        3,
+    )?;
+    let normals = match attribute("NORMAL") {
+        Some(n) => Some(accessor(doc, n, 3)?).filter(|n| n.len() == positions.len()),
+        None => None,
+    };
+    let count = positions.len() / 3;
+    let corners: Vec<usize> = match primitive.get("indices").and_then(Json::as_usize) {
+        Some(i) => accessor(doc, i, 1)?
+            .into_iter()
+            .map(|i| i as usize)
+            .collect(),
+        None => (0..count).collect(),
+    };
+    let vec3 = |v: &[f64], i: usize| [v[i * 3] as f32, v[i * 3 + 1] as f32, v[i * 3 + 2] as f32];
+    for triangle in corners.chunks_exact(3) {
+        let mut points = [([0.0; 3], None); 3];
+        for (point, &i) in points.iter_mut().zip(triangle) {
+            if i >= count {
+                return Err(format!("index {} is past the {} vertices", i, count));
+            }
+            *point = (vec3(&positions, i), normals.as_deref().map(|n| vec3(n, i)));
+        }
+        mesh::triangle(points, mesh);
+    }
+    Ok(())
+}
+
+/// Accessor `index`'s `width`-component elements, flattened
+fn accessor(doc: &Document, index: usize, width: usize) -> Result<Vec<f64>, String> {
+    let acc = doc.item("accessors", index)?;
+    let at = |e: &str| format!("accessors[{}]: {}", index, e);
+    let kind = ["SCALAR", "VEC2", "VEC3", "VEC4"][width - 1];
+    if acc.get("type").and_then(Json::as_str) != Some(kind) {
+        return Err(at(&format!("expected a {} accessor", kind)));
+    }
+    let count = acc
+        .get("count")
+        .and_then(Json::as_usize)
+        .ok_or_else(|| at("no count"))?;
+    // Component size, reader and the largest integer, for normalized ones
+    type Read = fn(&[u8]) -> f64;
+    let (size, read, max): (usize, Read, f64) =
+        match acc.get("componentType").and_then(Json::as_usize) {
+            Some(5120) => (1, |b| b[0] as i8 as f64, 127.0),
+            Some(5121) => (1, |b| b[0] as f64, 255.0),
+            Some(5122) => (2, |b| i16::from_le_bytes([b[0], b[1]]) as f64, 32767.0),
+            Some(5123) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as f64, 65535.0),
+            Some(5125) => (
+                4,
+                |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                u32::MAX as f64,
+            ),
+            Some(5126) => (
+                4,
+                |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                1.0,
+            ),
+            other => return Err(at(&format!("unknown componentType {:?}", other))),
+        };
+    let normalized = acc.get("normalized").and_then(Json::as_bool) == Some(true);
+    // Sparse-only accessors start from zeros; their substitutions are ignored
+    let Some(view) = acc.get("bufferView").and_then(Json::as_usize) else {
+        return Ok(vec![0.0; count * width]);
+    };
+    let view = doc.item("bufferViews", view)?;
+    let buffer = view
+        .get("buffer")
+        .and_then(Json::as_usize)
+        .and_then(|b| doc.buffers.get(b))
+        .ok_or_else(|| at("its buffer does not exist"))?;
+    let offset = |j: &Json| j.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+    let start = offset(view) + offset(acc);
+    let stride = view
+        .get("byteStride")
+        .and_then(Json::as_usize)
+        .unwrap_or(size * width);
+    let mut values = Vec::with_capacity(count * width);
+    for element in 0..count {
+        for component in 0..width {
+            let from = start + element * stride + component * size;
+            let bytes = buffer
+                .get(from..from + size)
+                .ok_or_else(|| at("runs past the end of its buffer"))?;
+            let value = read(bytes);
+            values.push(if normalized {
+                (value / max).max(-1.0)
+            } else {
+                value
+            });
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn models_become_mesh_gem_scenes() {
+        let dir = Path::new("target").join("gem_gltf_test");
+        fs::create_dir_all(&dir).unwrap();
+        // One triangle facing +z
+        let triangle: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        fs::write(dir.join("tri.bin"), &triangle).unwrap();
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0]}],
+            "nodes": [
+                {"name": "Hull", "translation": [0, 2, 0], "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476], "children": [1]},
+                {"mesh": 0, "scale": [2, 2, 2]}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}],
+            "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1]}}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [BUFFER]
+        }"#;
+        let gltf = dir.join("ship.gltf");
+        fs::write(
+            &gltf,
+            json.replace("BUFFER", r#"{"uri": "tri.bin", "byteLength": 36}"#),
+        )
+        .unwrap();
+
+        let scene = import(&gltf).unwrap();
+        assert_eq!(scene.root.name, "ship");
+        let hull = &scene.root.children[0];
+        assert_eq!(
+            (hull.name.as_str(), hull.gem_type.as_str()),
+            ("Hull", "MeshGem")
+        );
+        let prop = |decl: &ast::GemDecl, key: &str| {
+            decl.properties
+                .iter()
+                .find(|p| p.key == key)
+                .map(|p| p.value.clone())
+        };
+        let Some(ast::Value::Tuple(rotation)) = prop(hull, "rotation") else {
+            panic!("expected a rotation");
+        };
+        let ast::Value::Number(yaw) = rotation[1] else {
+            panic!("expected a number");
+        };
+        assert!((yaw - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(prop(hull, "scale"), None);
+        let part = &hull.children[0];
+        assert_eq!(part.name, "Node1");
+        assert_eq!(
+            prop(part, "color"),
+            Some(ast::Value::Color([255, 0, 0, 255]))
+        );
+        let Some(ast::Value::Directive(directive)) = prop(part, "mesh") else {
+            panic!("expected a mesh directive");
+        };
+        assert_eq!(mesh::reference(&directive, "Node1").index, 0);
+        assert!(directive.segments.ends_with(&["ship.gltf".to_string()]));
+
+        // A .glb carries the same model with its buffer in a binary chunk
+        let mut text = json.replace("BUFFER", r#"{"byteLength": 36}"#).into_bytes();
+        text.resize(text.len().next_multiple_of(4), b' ');
+        let mut glb = b"glTF".to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + 8 + text.len() as u32 + 8 + 36).to_le_bytes());
+        for (kind, data) in [(CHUNK_JSON, &text), (CHUNK_BIN, &triangle)] {
+            glb.extend((data.len() as u32).to_le_bytes());
+            glb.extend(kind.to_le_bytes());
+            glb.extend(data);
+        }
+        fs::write(dir.join("ship.glb"), glb).unwrap();
+        for path in [gltf, dir.join("ship.glb")] {
+            let mesh = mesh(&path, 0, None).unwrap();
+            assert_eq!(mesh.vertices.len(), 3);
+            assert_eq!(mesh.vertices[1].position, [1.0, 0.0, 0.0]);
+            assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+        }
+
+        assert_eq!(
+            data_uri("data:application/octet-stream;base64,AAECAw==").unwrap(),
+            [0, 1, 2, 3]
+        );
+        let err = mesh(&dir.join("ship.gltf"), 0, Some(1)).unwrap_err();
+        assert!(err.ends_with("meshes[0] has 1 primitives"));
+        assert!(
+            mesh(&dir.join("ship.gltf"), 3, None)
+                .unwrap_err()
+                .contains("meshes[3] does not exist")
+        );
+    }
+}
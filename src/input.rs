@@ -0,0 +1,243 @@
+//! Input events delivered through the node tree.
+//! An event is an `InputEvent*` object passed to each node in two phases,
+//! like DOM events: on the way down every node gets `on_input_capture(event)`
+//! before its children, and on the way back up `_input(event)` (built-in
+//! behavior) then `on_input(event)` after them. Children are visited last
+//! first, so the node drawn on top sees an event before its siblings. A
+//! handler that calls `event.accept_event()` stops delivery, so a button
+//! inside a scroll container inside a dialog consumes a click before the
+//! container can scroll. Hidden subtrees and nodes that can't process (see
+//! `process_mode`) are skipped, and an open popup keeps events to itself.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::crash;
+use crate::gem;
+use crate::object::{MethodTable, ObjectInner, ObjectRef, define_method};
+use crate::popup;
+use crate::scene_tree::{self, ProcessMode};
+use crate::value::Value;
+
+const CAPTURE_HOOKS: [&str; 1] = ["on_input_capture"];
+const BUBBLE_HOOKS: [&str; 2] = ["_input", "on_input"];
+
+/// A key press or release; `keycode` names the physical key (`"KeyA"`,
+/// `"ArrowLeft"`, `"Space"`)
+pub fn key_event(keycode: &str, pressed: bool, echo: bool) -> ObjectRef {
+    let event = new_event("InputEventKey");
+    event.set_property("keycode", Value::String(keycode.into()));
+    event.set_property("pressed", Value::Bool(pressed));
+    event.set_property("echo", Value::Bool(echo));
+    event.set_property("text", Value::String(String::new()));
+    set_modifiers(&event, false, false, false);
+    event
+}
+
+/// The characters a key press typed, for text entry (empty for arrows etc.)
+pub fn set_key_text(event: &ObjectRef, text: &str) {
+    event.set_property("text", Value::String(text.into()));
+}
+
+/// Modifier keys held; `ctrl` is Command on macOS, as shortcuts expect
+pub fn set_modifiers(event: &ObjectRef, ctrl: bool, shift: bool, alt: bool) {
+    event.set_property("ctrl", Value::Bool(ctrl));
+    event.set_property("shift", Value::Bool(shift));
+    event.set_property("alt", Value::Bool(alt));
+}
+
+/// A mouse button change at `position` (window pixels); `button` is
+/// `"left"`, `"right"` or `"middle"`
+pub fn mouse_button_event(button: &str, pressed: bool, position: (f64, f64)) -> ObjectRef {
+    let event = new_event("InputEventMouseButton");
+    event.set_property("button", Value::String(button.into()));
+    event.set_property("pressed", Value::Bool(pressed));
+    event.set_property("position", Value::vec2(position.0, position.1));
+    event
+}
+
+/// The pointer moved to `position`, by `relative` since the last motion
+pub fn mouse_motion_event(position: (f64, f64), relative: (f64, f64)) -> ObjectRef {
+    let event = new_event("InputEventMouseMotion");
+    event.set_property("position", Value::vec2(position.0, position.1));
+    event.set_property("relative", Value::vec2(relative.0, relative.1));
+    event
+}
+
+/// The wheel turned at `position`; `delta` is in lines, positive y away
+/// from the user (scrolling up)
+pub fn mouse_wheel_event(position: (f64, f64), delta: (f64, f64)) -> ObjectRef {
+    let event = new_event("InputEventMouseWheel");
+    event.set_property("position", Value::vec2(position.0, position.1));
+    event.set_property("delta", Value::vec2(delta.0, delta.1));
+    event
+}
+
+/// Input method composition: while composing, `kind` is `"preedit"` and
+/// `text` the unfinished text, with `cursor` a char index into it when the
+/// IME shows one; `"commit"` delivers the finished text
+pub fn ime_event(kind: &str, text: &str, cursor: Option<usize>) -> ObjectRef {
+    let event = new_event("InputEventIme");
+    event.set_property("kind", Value::String(kind.into()));
+    event.set_property("text", Value::String(text.into()));
+    if let Some(cursor) = cursor {
+        event.set_property("cursor", Value::Int(cursor as i64));
+    }
+    event
+}
+
+fn new_event(class_name: &str) -> ObjectRef {
+    ObjectInner::with_methods(class_name, event_methods())
+}
+
+fn event_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = MethodTable::new();
+            // accept_event(): stop delivery after the current handler
+            define_method(
+                &mut methods,
+                "accept_event",
+                Arc::new(|this, _| {
+                    accepted(this).store(true, Ordering::Relaxed);
+                    Ok(Value::Null)
+                }),
+            );
+            define_method(
+                &mut methods,
+                "is_accepted",
+                Arc::new(|this, _| Ok(Value::Bool(is_accepted(this)))),
+            );
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn accepted(event: &ObjectRef) -> &AtomicBool {
+    event.extension::<AtomicBool>()
+}
+
+pub fn is_accepted(event: &ObjectRef) -> bool {
+    accepted(event).load(Ordering::Relaxed)
+}
+
+/// Deliver `event` through `root`'s subtree; true when a handler accepted it.
+/// While a popup inside the subtree is open, only that popup gets the event,
+/// and it counts as accepted.
+pub fn dispatch(root: &ObjectRef, event: &ObjectRef) -> bool {
+    if let Some(popup) = popup::modal_within(root) {
+        let inherited = gem::parent_of(&popup)
+            .map(|p| scene_tree::effective_process_mode(&p))
+            .unwrap_or(ProcessMode::Inherit);
+        deliver(&popup, inherited, scene_tree::is_paused(), event);
+        return true;
+    }
+    let inherited = gem::parent_of(root)
+        .map(|p| scene_tree::effective_process_mode(&p))
+        .unwrap_or(ProcessMode::Inherit);
+    deliver(root, inherited, scene_tree::is_paused(), event)
+}
+
+/// Returns true once the event is accepted
+fn deliver(node: &ObjectRef, inherited: ProcessMode, paused: bool, event: &ObjectRef) -> bool {
+    if !gem::is_visible(node) {
+        return false;
+    }
+    let mode = scene_tree::process_mode(node).resolve(inherited);
+    let active = mode.can_process(paused);
+    if active && call_hooks(node, &CAPTURE_HOOKS, event) {
+        return true;
+    }
+    for child in gem::children_of(node).iter().rev() {
+        if deliver(child, mode, paused, event) {
+            return true;
+        }
+    }
+    active && call_hooks(node, &BUBBLE_HOOKS, event)
+}
+
+fn call_hooks(node: &ObjectRef, hooks: &[&str], event: &ObjectRef) -> bool {
+    let args = [Value::Object(event.clone())];
+    for hook in hooks {
+        if node.has_method(hook)
+            && let Err(e) = crash::running(node, || node.call_method(hook, &args))
+        {
+            eprintln!("[ERR] {}.{}: {}", node.class_name(), hook, e);
+        }
+        if is_accepted(event) {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_new;
+    use std::sync::Mutex;
+
+    #[test]
+    fn events_capture_down_bubble_up_and_stop_when_accepted() {
+        scene_tree::init_builtin_classes();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let node = |name: &'static str, accept_in: Option<&'static str>| {
+            let node = object_new("Gem");
+            for hook in ["on_input_capture", "on_input"] {
+                let log = log.clone();
+                ObjectInner::insert_method(
+                    &node,
+                    hook,
+                    Arc::new(move |_, args| {
+                        log.lock().unwrap().push(format!("{} {}", name, hook));
+                        if accept_in == Some(hook)
+                            && let Some(Value::Object(event)) = args.first()
+                        {
+                            event.call_method("accept_event", &[])?;
+                        }
+                        Ok(Value::Null)
+                    }),
+                );
+            }
+            node
+        };
+        let add = |parent: &ObjectRef, child: &ObjectRef| {
+            parent
+                .call_method("add_child", &[Value::Object(child.clone())])
+                .unwrap();
+        };
+        let dialog = node("Dialog", None);
+        let scroll = node("Scroll", Some("on_input"));
+        let button = node("Button", None);
+        let hidden = node("Hidden", None);
+        add(&dialog, &scroll);
+        add(&scroll, &button);
+        add(&dialog, &hidden);
+        hidden.set_property("visible", Value::Bool(false));
+        let take = || std::mem::take(&mut *log.lock().unwrap());
+
+        let click = mouse_button_event("left", true, (5.0, 5.0));
+        assert!(dispatch(&dialog, &click));
+        assert_eq!(
+            take(),
+            [
+                "Dialog on_input_capture",
+                "Scroll on_input_capture",
+                "Button on_input_capture",
+                "Button on_input",
+                "Scroll on_input",
+            ]
+        );
+
+        // Accepting during capture keeps the event from the children
+        let modal = node("Modal", Some("on_input_capture"));
+        add(&modal, &node("Inner", None));
+        assert!(dispatch(&modal, &key_event("Escape", true, false)));
+        assert_eq!(take(), ["Modal on_input_capture"]);
+
+        let motion = mouse_motion_event((1.0, 1.0), (1.0, 0.0));
+        assert!(!dispatch(&button, &motion));
+        assert_eq!(motion.class_name(), "InputEventMouseMotion");
+    }
+}
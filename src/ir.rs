@@ -2,8 +2,13 @@
 //! These structures are compile-time only: no runtime mutation, no Arc/RwLock.
 //! They model classes (Object/Gem), nodes, and scenes similar to Godot's Node tree.
 
+use crate::ast::Directive;
+use crate::curve::Curve;
+use crate::printer;
 use crate::property_type::PropertyType;
+use crate::size_expr::SizeExpr;
 use std::collections::HashMap;
+use std::fmt::{self, Write};
 
 #[derive(Debug, Clone)]
 pub struct MethodSig {
@@ -35,18 +40,106 @@ pub struct ClassDecl {
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct NodeId(pub u32);
 
+/// A property value as written in the scene, without going through strings
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),                      // strings and bare identifiers
+    Vec2(f64, f64),                   // two-number tuples
+    Color([u8; 4]),                   // rgba
+    SceneRef(Directive),              // #a:b.gem, with any arguments
+    List(Vec<PropValue>),             // any other tuple
+    Array(Vec<PropValue>),            // [a, b], elements of one type
+    Struct(Vec<(String, PropValue)>), // fields in declared order
+    Map(Vec<(String, PropValue)>),    // { "key": value }, values of one type
+    Curve(Curve),                     // (t, value, easing) points
+    SizeExpr(SizeExpr),               // resolved by the layout pass
+}
+
+/// Scene-file spelling, as in dumps
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |items: Vec<String>| items.join(", ");
+        match self {
+            PropValue::Int(i) => write!(f, "{}", i),
+            PropValue::Float(x) => write!(f, "{:?}", x),
+            PropValue::Bool(b) => write!(f, "{}", b),
+            PropValue::Str(s) => write!(f, "{:?}", s),
+            PropValue::Vec2(x, y) => write!(f, "({:?}, {:?})", x, y),
+            PropValue::Color([r, g, b, 255]) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            PropValue::Color([r, g, b, a]) => write!(f, "#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+            PropValue::SceneRef(directive) => write!(f, "{}", printer::print_directive(directive)),
+            PropValue::List(items) => {
+                write!(
+                    f,
+                    "({})",
+                    join(items.iter().map(|v| v.to_string()).collect())
+                )
+            }
+            PropValue::Array(items) => {
+                write!(
+                    f,
+                    "[{}]",
+                    join(items.iter().map(|v| v.to_string()).collect())
+                )
+            }
+            PropValue::Curve(curve) => write!(
+                f,
+                "{}",
+                crate::printer::print_value(&crate::ast::Value::Curve(curve.clone()))
+            ),
+            PropValue::SizeExpr(expr) => write!(f, "{}", expr),
+            PropValue::Struct(fields) => write!(
+                f,
+                "{{ {} }}",
+                join(
+                    fields
+                        .iter()
+                        .map(|(k, v)| format!("{}: {}", k, v))
+                        .collect()
+                )
+            ),
+            PropValue::Map(entries) => write!(
+                f,
+                "{{ {} }}",
+                join(
+                    entries
+                        .iter()
+                        .map(|(k, v)| format!("{:?}: {}", k, v))
+                        .collect()
+                )
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TypedProperty {
-    pub value: String, // literal value
+    pub value: PropValue,
     pub prop_type: PropertyType,
 }
 
+impl TypedProperty {
+    pub fn new(value: PropValue) -> Self {
+        Self {
+            prop_type: PropertyType::of(&value),
+            value,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeIR {
     pub id: NodeId,
+    /// Stable identifier from `meta:uid`; unlike `id` it survives edits that
+    /// add, remove or reorder nodes
+    pub uid: Option<String>,
     pub name: String,
     pub class_name: String, // e.g. "Gem" or future specialized classes
     pub properties: HashMap<String, TypedProperty>, // typed properties
+    pub meta: HashMap<String, TypedProperty>, // `meta:key: value` entries
     pub parent: Option<NodeId>,
     pub children: Vec<NodeId>,
 }
@@ -55,9 +148,11 @@ impl NodeIR {
     pub fn new(id: NodeId, name: impl Into<String>, class_name: impl Into<String>) -> Self {
         Self {
             id,
+            uid: None,
             name: name.into(),
             class_name: class_name.into(),
             properties: HashMap::new(),
+            meta: HashMap::new(),
             parent: None,
             children: Vec::new(),
         }
@@ -68,6 +163,10 @@ impl NodeIR {
 pub struct SceneIR {
     pub nodes: HashMap<NodeId, NodeIR>,
     pub root: Option<NodeId>,
+    /// `%Name` nodes, reachable by name from anywhere in the scene
+    pub unique_names: HashMap<String, NodeId>,
+    /// Nodes by their stable `uid`
+    pub uids: HashMap<String, NodeId>,
     next_id: u32,
 }
 
@@ -76,6 +175,8 @@ impl SceneIR {
         Self {
             nodes: HashMap::new(),
             root: None,
+            unique_names: HashMap::new(),
+            uids: HashMap::new(),
             next_id: 0,
         }
     }
@@ -105,35 +206,85 @@ impl SceneIR {
         }
     }
 
-    pub fn set_property(&mut self, node: NodeId, key: impl Into<String>, value: impl Into<String>) {
-        let value_str = value.into();
-        let prop_type = PropertyType::infer(&value_str);
+    /// Register `node` under its name as scene-unique; names may be used once per scene
+    pub fn set_unique(&mut self, node: NodeId) -> Result<(), String> {
+        let name = match self.nodes.get(&node) {
+            Some(n) => n.name.clone(),
+            None => return Err(format!("Unknown node {:?}", node)),
+        };
+        if self.unique_names.contains_key(&name) {
+            return Err(format!("Unique name %{} is used more than once", name));
+        }
+        self.unique_names.insert(name, node);
+        Ok(())
+    }
+
+    /// Give `node` its stable identifier; identifiers may be used once per scene
+    pub fn set_uid(&mut self, node: NodeId, uid: &str) -> Result<(), String> {
+        if let Some(other) = self.uids.get(uid) {
+            let name = |id| self.nodes.get(id).map_or("?", |n: &NodeIR| n.name.as_str());
+            return Err(format!(
+                "uid \"{}\" is used by both {} and {}",
+                uid,
+                name(other),
+                name(&node)
+            ));
+        }
+        let Some(n) = self.nodes.get_mut(&node) else {
+            return Err(format!("Unknown node {:?}", node));
+        };
+        n.uid = Some(uid.to_string());
+        self.uids.insert(uid.to_string(), node);
+        Ok(())
+    }
+
+    pub fn set_property(&mut self, node: NodeId, key: impl Into<String>, value: PropValue) {
         if let Some(n) = self.nodes.get_mut(&node) {
-            n.properties.insert(
-                key.into(),
-                TypedProperty {
-                    value: value_str,
-                    prop_type,
-                },
-            );
-        }
-    }
-
-    pub fn set_typed_property(
-        &mut self,
-        node: NodeId,
-        key: impl Into<String>,
-        value: impl Into<String>,
-        prop_type: PropertyType,
-    ) {
+            n.properties.insert(key.into(), TypedProperty::new(value));
+        }
+    }
+
+    pub fn set_meta(&mut self, node: NodeId, key: impl Into<String>, value: PropValue) {
         if let Some(n) = self.nodes.get_mut(&node) {
-            n.properties.insert(
-                key.into(),
-                TypedProperty {
-                    value: value.into(),
-                    prop_type,
-                },
-            );
+            n.meta.insert(key.into(), TypedProperty::new(value));
+        }
+    }
+
+    /// Indented listing of the tree from the root: one `Name (Class)` line per
+    /// node, `%` marking unique names, followed by its properties by key
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        if let Some(root) = self.root {
+            self.dump_node(root, 0, &mut out);
+        }
+        out
+    }
+
+    fn dump_node(&self, id: NodeId, depth: usize, out: &mut String) {
+        let Some(node) = self.nodes.get(&id) else {
+            return;
+        };
+        let marker = if self.unique_names.get(&node.name) == Some(&id) {
+            "%"
+        } else {
+            ""
+        };
+        let _ = write!(
+            out,
+            "{}{}{} ({})",
+            "  ".repeat(depth),
+            marker,
+            node.name,
+            node.class_name
+        );
+        let mut keys: Vec<&String> = node.properties.keys().collect();
+        keys.sort();
+        for key in keys {
+            let _ = write!(out, " {}={}", key, node.properties[key].value);
+        }
+        out.push('\n');
+        for child in &node.children {
+            self.dump_node(*child, depth + 1, out);
         }
     }
 
@@ -186,3 +337,101 @@ impl SceneIR {
         Some(current)
     }
 }
+
+/// How a node differs between two compiles of a scene
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeChange {
+    Added {
+        path: String,
+    },
+    Removed {
+        path: String,
+    },
+    /// Same node under a new path: renamed, reparented or both
+    Moved {
+        from: String,
+        to: String,
+    },
+    /// Keys whose values differ; `class` stands for the node's class
+    Changed {
+        path: String,
+        keys: Vec<String>,
+    },
+}
+
+/// Changes from `old` to `new`, for the hot-reload differ. Nodes are matched
+/// by `uid` when both have one and by path otherwise, so a node with a uid
+/// is recognised after being renamed or moved.
+pub fn diff(old: &SceneIR, new: &SceneIR) -> Vec<NodeChange> {
+    let path = |scene: &SceneIR, id: NodeId| scene.get_path(id).unwrap_or_default();
+    let mut old_ids: Vec<NodeId> = old.nodes.keys().cloned().collect();
+    old_ids.sort_by_key(|id| id.0);
+    let mut new_ids: Vec<NodeId> = new.nodes.keys().cloned().collect();
+    new_ids.sort_by_key(|id| id.0);
+
+    let mut matched = std::collections::HashSet::new();
+    let mut changes = Vec::new();
+    for id in old_ids {
+        let node = &old.nodes[&id];
+        let from = path(old, id);
+        let counterpart = match &node.uid {
+            Some(uid) => new.uids.get(uid).copied(),
+            None => new
+                .find_by_path(&from)
+                .filter(|n| new.nodes[n].uid.is_none()),
+        };
+        let Some(other) = counterpart else {
+            changes.push(NodeChange::Removed { path: from });
+            continue;
+        };
+        matched.insert(other);
+        let to = path(new, other);
+        if to != from {
+            changes.push(NodeChange::Moved {
+                from,
+                to: to.clone(),
+            });
+        }
+        let keys = changed_keys(node, &new.nodes[&other]);
+        if !keys.is_empty() {
+            changes.push(NodeChange::Changed { path: to, keys });
+        }
+    }
+    for id in new_ids {
+        if !matched.contains(&id) {
+            changes.push(NodeChange::Added {
+                path: path(new, id),
+            });
+        }
+    }
+    changes
+}
+
+fn changed_keys(old: &NodeIR, new: &NodeIR) -> Vec<String> {
+    let mut keys: Vec<String> = old
+        .properties
+        .keys()
+        .chain(new.properties.keys())
+        .filter(|k| {
+            old.properties.get(*k).map(|p| &p.value) != new.properties.get(*k).map(|p| &p.value)
+        })
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    if old.class_name != new.class_name {
+        keys.insert(0, "class".to_string());
+    }
+    keys
+}
+
+impl fmt::Display for NodeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeChange::Added { path } => write!(f, "+ {}", path),
+            NodeChange::Removed { path } => write!(f, "- {}", path),
+            NodeChange::Moved { from, to } => write!(f, "~ {} -> {}", from, to),
+            NodeChange::Changed { path, keys } => write!(f, "* {} ({})", path, keys.join(", ")),
+        }
+    }
+}
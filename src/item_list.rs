@@ -0,0 +1,572 @@
+//! ItemListGem and TreeGem: scrollable lists of text rows.
+//! A scene fills one with an `items: ("Sword", "Shield")` tuple or with
+//! child nodes, each an item titled by its `text` property or else its
+//! name; a TreeGem nests a child's own children under its row. Item
+//! children are data, not drawn: once the list is ready they are read into
+//! its items and hidden. Scripts change items with `add_item(text)` (on a
+//! TreeGem `add_item(text, parent)`), `set_item_text`, `remove_item` and
+//! `clear`. Clicking a row selects it and emits `item_selected(index)`;
+//! clicking left of a tree row's text folds or unfolds its children. The
+//! wheel scrolls `scroll_offset` by rows. The items live in the `items`
+//! property (on a tree also `item_parents` and `item_collapsed`), so scripts
+//! can read or assign them whole. Row layout is shared with the preview's
+//! draw list.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{self, MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+/// Item texts, in index order
+pub const ITEMS_KEY: &str = "items";
+/// On a tree, each item's parent index, -1 at the top level
+pub const PARENTS_KEY: &str = "item_parents";
+/// On a tree, whether each item's children are folded away
+pub const COLLAPSED_KEY: &str = "item_collapsed";
+pub const SELECTED_KEY: &str = "selected";
+pub const SCROLL_KEY: &str = "scroll_offset";
+/// Height of a row, in px
+pub const ROW_HEIGHT: f32 = 24.0;
+/// Indent per tree level, in px; the fold marker sits in the last one
+pub const INDENT: f32 = 16.0;
+/// Rows scrolled per wheel line
+const WHEEL_ROWS: f64 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub text: String,
+    /// Always before the item; `None` at the top level
+    pub parent: Option<usize>,
+    pub collapsed: bool,
+}
+
+impl ListItem {
+    pub fn new(text: &str, parent: Option<usize>) -> Self {
+        Self {
+            text: text.to_string(),
+            parent,
+            collapsed: false,
+        }
+    }
+}
+
+/// The shown rows, top to bottom: each item's index and depth, children
+/// after their parent and none under a collapsed item
+pub fn rows(items: &[ListItem]) -> Vec<(usize, usize)> {
+    fn visit(
+        items: &[ListItem],
+        parent: Option<usize>,
+        depth: usize,
+        out: &mut Vec<(usize, usize)>,
+    ) {
+        for (i, item) in items.iter().enumerate() {
+            if item.parent != parent {
+                continue;
+            }
+            out.push((i, depth));
+            if !item.collapsed {
+                visit(items, Some(i), depth + 1, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    visit(items, None, 0, &mut out);
+    out
+}
+
+pub fn has_children(items: &[ListItem], index: usize) -> bool {
+    items.iter().any(|item| item.parent == Some(index))
+}
+
+/// Largest `scroll_offset` for `row_count` rows in a `height`-tall list
+pub fn max_scroll(row_count: usize, height: f32) -> f32 {
+    (row_count as f32 * ROW_HEIGHT - height).max(0.0)
+}
+
+pub fn init_item_list_classes() {
+    register_class("ItemListGem", || {
+        new_list("ItemListGem", item_list_methods())
+    });
+    register_class("TreeGem", || new_list("TreeGem", tree_methods()));
+}
+
+fn new_list(class_name: &str, methods: Arc<MethodTable>) -> ObjectRef {
+    let obj = gem::new_gem(class_name, methods);
+    obj.set_property(SELECTED_KEY, Value::Int(-1));
+    obj.set_property(SCROLL_KEY, Value::Float(0.0));
+    obj.set_property("size", Value::vec2(200.0, 240.0));
+    obj
+}
+
+fn item_list_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_list_methods(false)))
+        .clone()
+}
+
+fn tree_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_list_methods(true)))
+        .clone()
+}
+
+/// The items from the list's properties; a parent that doesn't come before
+/// its child counts as none
+fn load(list: &ObjectRef) -> Vec<ListItem> {
+    let array = |key| match list.get_property(key) {
+        Some(Value::Array(values)) => values,
+        _ => Vec::new(),
+    };
+    let (parents, collapsed) = (array(PARENTS_KEY), array(COLLAPSED_KEY));
+    array(ITEMS_KEY)
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| ListItem {
+            text: match text {
+                Value::String(t) => t,
+                _ => String::new(),
+            },
+            parent: match parents.get(i) {
+                Some(Value::Int(p)) => usize::try_from(*p).ok().filter(|&p| p < i),
+                _ => None,
+            },
+            collapsed: matches!(collapsed.get(i), Some(Value::Bool(true))),
+        })
+        .collect()
+}
+
+fn store(list: &ObjectRef, items: &[ListItem]) {
+    let texts = items.iter().map(|item| Value::String(item.text.clone()));
+    list.set_property(ITEMS_KEY, Value::Array(texts.collect()));
+    if list.class_name() == "TreeGem" {
+        let parents = items
+            .iter()
+            .map(|item| Value::Int(item.parent.map_or(-1, |p| p as i64)));
+        let collapsed = items.iter().map(|item| Value::Bool(item.collapsed));
+        list.set_property(PARENTS_KEY, Value::Array(parents.collect()));
+        list.set_property(COLLAPSED_KEY, Value::Array(collapsed.collect()));
+    }
+}
+
+fn index_arg(list: &ObjectRef, args: &[Value], method: &str) -> Result<usize, String> {
+    let count = load(list).len();
+    match args.first() {
+        Some(Value::Int(i)) if (0..count as i64).contains(i) => Ok(*i as usize),
+        Some(Value::Int(i)) => Err(format!(
+            "{}: {} is out of range for {} items",
+            method, i, count
+        )),
+        _ => Err(format!("{} expects an Int index", method)),
+    }
+}
+
+fn text_arg(args: &[Value], at: usize, method: &str) -> Result<String, String> {
+    match args.get(at) {
+        Some(Value::String(s)) => Ok(s.clone()),
+        _ => Err(format!("{} expects a text string", method)),
+    }
+}
+
+/// Gem's methods plus the list's; a tree's items may have parents
+fn build_list_methods(tree: bool) -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+    // add_item(text) -> index; on a tree add_item(text, parent = -1)
+    define_method(
+        &mut methods,
+        "add_item",
+        Arc::new(move |this, args| {
+            let text = text_arg(args, 0, "add_item")?;
+            let parent = match args.get(1) {
+                None | Some(Value::Int(-1)) => None,
+                Some(_) if !tree => return Err("add_item on an ItemListGem takes only text".into()),
+                Some(_) => Some(index_arg(this, &args[1..], "add_item")?),
+            };
+            let mut items = load(this);
+            items.push(ListItem::new(&text, parent));
+            store(this, &items);
+            Ok(Value::Int(items.len() as i64 - 1))
+        }),
+    );
+    // set_item_text(index, text)
+    define_method(
+        &mut methods,
+        "set_item_text",
+        Arc::new(|this, args| {
+            let i = index_arg(this, args, "set_item_text")?;
+            let text = text_arg(args, 1, "set_item_text")?;
+            let mut items = load(this);
+            items[i].text = text;
+            store(this, &items);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_item_text",
+        Arc::new(|this, args| {
+            let i = index_arg(this, args, "get_item_text")?;
+            Ok(Value::String(load(this).swap_remove(i).text))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_item_count",
+        Arc::new(|this, _| Ok(Value::Int(load(this).len() as i64))),
+    );
+    // remove_item(index): later items move up one; a tree item's children go with it
+    define_method(
+        &mut methods,
+        "remove_item",
+        Arc::new(|this, args| {
+            let i = index_arg(this, args, "remove_item")?;
+            remove(this, i);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "clear",
+        Arc::new(|this, _| {
+            store(this, &[]);
+            this.set_property(SELECTED_KEY, Value::Int(-1));
+            this.set_property(SCROLL_KEY, Value::Float(0.0));
+            Ok(Value::Null)
+        }),
+    );
+    // select(index): emits item_selected(index) when the selection changes
+    define_method(
+        &mut methods,
+        "select",
+        Arc::new(|this, args| {
+            let i = index_arg(this, args, "select")?;
+            select(this, i);
+            Ok(Value::Null)
+        }),
+    );
+    // get_selected() -> index, or -1
+    define_method(
+        &mut methods,
+        "get_selected",
+        Arc::new(|this, _| Ok(Value::Int(selected(this)))),
+    );
+    // ensure_item_visible(index): scroll just enough to show the item's row
+    define_method(
+        &mut methods,
+        "ensure_item_visible",
+        Arc::new(|this, args| {
+            let i = index_arg(this, args, "ensure_item_visible")?;
+            let Some(row) = rows(&load(this)).iter().position(|&(item, _)| item == i) else {
+                return Ok(Value::Null);
+            };
+            let top = row as f64 * ROW_HEIGHT as f64;
+            let height = size_of(this).1;
+            let scroll = scroll_of(this);
+            if top < scroll {
+                set_scroll(this, top);
+            } else if top + ROW_HEIGHT as f64 > scroll + height {
+                set_scroll(this, top + ROW_HEIGHT as f64 - height);
+            }
+            Ok(Value::Null)
+        }),
+    );
+    if tree {
+        // set_item_collapsed(index, collapsed)
+        define_method(
+            &mut methods,
+            "set_item_collapsed",
+            Arc::new(|this, args| {
+                let i = index_arg(this, args, "set_item_collapsed")?;
+                let Some(Value::Bool(collapsed)) = args.get(1) else {
+                    return Err("set_item_collapsed expects (index, collapsed)".into());
+                };
+                let mut items = load(this);
+                items[i].collapsed = *collapsed;
+                store(this, &items);
+                set_scroll(this, scroll_of(this));
+                Ok(Value::Null)
+            }),
+        );
+        define_method(
+            &mut methods,
+            "is_item_collapsed",
+            Arc::new(|this, args| {
+                let i = index_arg(this, args, "is_item_collapsed")?;
+                Ok(Value::Bool(load(this)[i].collapsed))
+            }),
+        );
+        // get_item_parent(index) -> index, or -1 at the top level
+        define_method(
+            &mut methods,
+            "get_item_parent",
+            Arc::new(|this, args| {
+                let i = index_arg(this, args, "get_item_parent")?;
+                Ok(Value::Int(load(this)[i].parent.map_or(-1, |p| p as i64)))
+            }),
+        );
+    }
+    // _notification(code): once ready, read in `items` and item children, hiding them
+    define_method(
+        &mut methods,
+        "_notification",
+        Arc::new(move |this, args| {
+            if let Some(Value::Int(object::NOTIFICATION_READY)) = args.first() {
+                let mut items = load(this);
+                for child in gem::children_of(this) {
+                    collect_child(&child, None, tree, &mut items);
+                    gem::set_visible(&child, false);
+                }
+                store(this, &items);
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // _input(event): clicks select rows or fold tree items, the wheel scrolls
+    define_method(
+        &mut methods,
+        "_input",
+        Arc::new(|this, args| {
+            let Some(event) = args.first().and_then(Value::as_object) else {
+                return Err("_input expects an InputEvent".into());
+            };
+            let Some((x, y)) = event
+                .get_property("position")
+                .and_then(|p| p.as_vec2())
+                .and_then(|p| gem::to_local(this, p))
+            else {
+                return Ok(Value::Null);
+            };
+            let (w, h) = size_of(this);
+            if x < 0.0 || y < 0.0 || x >= w || y >= h {
+                return Ok(Value::Null);
+            }
+            match event.class_name() {
+                "InputEventMouseWheel" => {
+                    let lines = event
+                        .get_property("delta")
+                        .and_then(|d| d.as_vec2())
+                        .map_or(0.0, |d| d.1);
+                    set_scroll(
+                        this,
+                        scroll_of(this) - lines * WHEEL_ROWS * ROW_HEIGHT as f64,
+                    );
+                }
+                "InputEventMouseButton"
+                    if matches!(event.get_property("pressed"), Some(Value::Bool(true))) =>
+                {
+                    let row = ((y + scroll_of(this)) / ROW_HEIGHT as f64) as usize;
+                    let mut items = load(this);
+                    let hit = rows(&items).get(row).map(|&(i, depth)| {
+                        let on_marker =
+                            has_children(&items, i) && x < (depth + 1) as f64 * INDENT as f64;
+                        (i, on_marker)
+                    });
+                    match hit {
+                        Some((i, true)) => {
+                            items[i].collapsed = !items[i].collapsed;
+                            store(this, &items);
+                            set_scroll(this, scroll_of(this));
+                        }
+                        Some((i, false)) => select(this, i),
+                        None => return Ok(Value::Null),
+                    }
+                }
+                _ => return Ok(Value::Null),
+            }
+            event.call_method("accept_event", &[])?;
+            Ok(Value::Null)
+        }),
+    );
+    // signals: item_selected(index)
+    methods
+}
+
+/// A child node's item, then its children's under it on a tree
+fn collect_child(node: &ObjectRef, parent: Option<usize>, tree: bool, out: &mut Vec<ListItem>) {
+    let text = match (node.get_property("text"), node.get_property("name")) {
+        (Some(Value::String(t)), _) | (_, Some(Value::String(t))) => t,
+        _ => node.class_name().to_string(),
+    };
+    out.push(ListItem::new(&text, parent));
+    if tree {
+        let index = out.len() - 1;
+        for child in gem::children_of(node) {
+            collect_child(&child, Some(index), tree, out);
+        }
+    }
+}
+
+fn remove(list: &ObjectRef, index: usize) {
+    let mut items = load(list);
+    // The item and its descendants, which always come after it
+    let mut gone = vec![false; items.len()];
+    gone[index] = true;
+    for i in index + 1..items.len() {
+        gone[i] = items[i].parent.is_some_and(|p| gone[p]);
+    }
+    let mut new_index = Vec::with_capacity(items.len());
+    let mut next = 0;
+    for &g in &gone {
+        new_index.push(next);
+        if !g {
+            next += 1;
+        }
+    }
+    let mut i = 0;
+    items.retain(|_| {
+        i += 1;
+        !gone[i - 1]
+    });
+    for item in items.iter_mut() {
+        item.parent = item.parent.map(|p| new_index[p]);
+    }
+    store(list, &items);
+    let sel = selected(list);
+    let sel = match usize::try_from(sel) {
+        Ok(s) if gone[s] => -1,
+        Ok(s) => new_index[s] as i64,
+        Err(_) => -1,
+    };
+    list.set_property(SELECTED_KEY, Value::Int(sel));
+    set_scroll(list, scroll_of(list));
+}
+
+fn selected(list: &ObjectRef) -> i64 {
+    match list.get_property(SELECTED_KEY) {
+        Some(Value::Int(i)) => i,
+        _ => -1,
+    }
+}
+
+fn select(list: &ObjectRef, index: usize) {
+    if selected(list) == index as i64 {
+        return;
+    }
+    list.set_property(SELECTED_KEY, Value::Int(index as i64));
+    list.emit_signal("item_selected", &[Value::Int(index as i64)]);
+}
+
+fn size_of(list: &ObjectRef) -> (f64, f64) {
+    list.get_property("size")
+        .and_then(|s| s.as_vec2())
+        .unwrap_or((0.0, 0.0))
+}
+
+fn scroll_of(list: &ObjectRef) -> f64 {
+    list.get_property(SCROLL_KEY)
+        .and_then(|s| s.as_f64())
+        .unwrap_or(0.0)
+}
+
+/// Set `scroll_offset`, kept within the rows
+fn set_scroll(list: &ObjectRef, scroll: f64) {
+    let row_count = rows(&load(list)).len();
+    let max = max_scroll(row_count, size_of(list).1 as f32) as f64;
+    list.set_property(SCROLL_KEY, Value::Float(scroll.clamp(0.0, max)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use crate::scene_tree;
+    use std::sync::Mutex;
+
+    fn call(node: &ObjectRef, method: &str, args: &[Value]) -> Value {
+        node.call_method(method, args).unwrap()
+    }
+
+    #[test]
+    fn lists_select_scroll_and_remove() {
+        scene_tree::init_builtin_classes();
+        let list = object_new("ItemListGem");
+        list.set_property("size", Value::vec2(100.0, 48.0));
+        let sword = object_new("Gem");
+        sword.set_property("name", Value::String("Sword".into()));
+        list.call_method("add_child", &[Value::Object(sword.clone())])
+            .unwrap();
+        gem::propagate_ready(&list);
+        assert!(!gem::is_visible(&sword));
+        for text in ["Shield", "Bow", "Staff"] {
+            call(&list, "add_item", &[Value::String(text.into())]);
+        }
+        call(
+            &list,
+            "set_item_text",
+            &[Value::Int(2), Value::String("Longbow".into())],
+        );
+        assert_eq!(
+            call(&list, "get_item_text", &[Value::Int(2)]),
+            Value::String("Longbow".into())
+        );
+        assert!(
+            list.call_method("add_item", &[Value::String("x".into()), Value::Int(0)])
+                .is_err()
+        );
+
+        let picked = Arc::new(Mutex::new(Vec::new()));
+        let seen = picked.clone();
+        list.connect(
+            "item_selected",
+            Arc::new(move |args| seen.lock().unwrap().push(args[0].clone())),
+        );
+        // Two 24px rows show; scrolling down a wheel line stops at the last row
+        let wheel = input::mouse_wheel_event((10.0, 10.0), (0.0, -1.0));
+        assert!(input::dispatch(&list, &wheel));
+        assert_eq!(list.get_property(SCROLL_KEY), Some(Value::Float(48.0)));
+        let click = input::mouse_button_event("left", true, (10.0, 30.0));
+        assert!(input::dispatch(&list, &click));
+        assert_eq!(call(&list, "get_selected", &[]), Value::Int(3));
+        assert_eq!(*picked.lock().unwrap(), [Value::Int(3)]);
+
+        call(&list, "remove_item", &[Value::Int(0)]);
+        assert_eq!(call(&list, "get_selected", &[]), Value::Int(2));
+        assert_eq!(list.get_property(SCROLL_KEY), Some(Value::Float(24.0)));
+        call(&list, "ensure_item_visible", &[Value::Int(0)]);
+        assert_eq!(list.get_property(SCROLL_KEY), Some(Value::Float(0.0)));
+    }
+
+    #[test]
+    fn trees_nest_fold_and_remove_subtrees() {
+        scene_tree::init_builtin_classes();
+        let tree = object_new("TreeGem");
+        let add = |text: &str, parent: i64| match call(
+            &tree,
+            "add_item",
+            &[Value::String(text.into()), Value::Int(parent)],
+        ) {
+            Value::Int(i) => i,
+            other => panic!("expected an index, got {:?}", other),
+        };
+        let scenes = add("scenes", -1);
+        let level = add("level.gem", scenes);
+        add("enemy.gem", level);
+        let assets = add("assets", -1);
+        let list = |tree: &ObjectRef| rows(&load(tree));
+        assert_eq!(list(&tree), [(0, 0), (1, 1), (2, 2), (3, 0)]);
+
+        // A click on the marker column of "scenes" folds it
+        tree.set_property("size", Value::vec2(200.0, 200.0));
+        assert!(input::dispatch(
+            &tree,
+            &input::mouse_button_event("left", true, (4.0, 4.0))
+        ));
+        assert_eq!(list(&tree), [(0, 0), (3, 0)]);
+        assert_eq!(
+            call(&tree, "is_item_collapsed", &[Value::Int(scenes)]),
+            Value::Bool(true)
+        );
+
+        call(&tree, "select", &[Value::Int(assets)]);
+        call(&tree, "remove_item", &[Value::Int(scenes)]);
+        assert_eq!(call(&tree, "get_item_count", &[]), Value::Int(1));
+        assert_eq!(call(&tree, "get_selected", &[]), Value::Int(0));
+        assert_eq!(
+            call(&tree, "get_item_parent", &[Value::Int(0)]),
+            Value::Int(-1)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! The shutdown leak report, for `gem --leak-report`.
+//! Once the preview window closes, every object still alive that neither the
+//! runtime tree nor a singleton can reach is a leak: something kept an
+//! `ObjectRef` to it (a removed node held by a script, say) so it was never
+//! freed. Reachability follows tree children and objects held in properties
+//! and metadata, including inside arrays and maps.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::autoload;
+use crate::gem;
+use crate::object::{self, ClassCount, ObjectRef};
+use crate::value::Value;
+
+/// Remove `--leak-report` from `args`, returning whether it was there
+pub fn take_flag(args: &mut Vec<String>) -> bool {
+    let before = args.len();
+    args.retain(|a| a != "--leak-report");
+    args.len() != before
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leak {
+    pub id: u64,
+    pub class: String,
+    /// Path from the root of the detached subtree it is in, for Gems
+    pub path: Option<String>,
+}
+
+/// Live objects the runtime tree and the singletons can't reach
+pub fn find() -> Vec<Leak> {
+    let mut reached = HashSet::new();
+    let mut pending = autoload::all();
+    pending.push(autoload::tree_root());
+    while let Some(obj) = pending.pop() {
+        if !reached.insert(obj.id()) {
+            continue;
+        }
+        if is_gem(&obj) {
+            pending.extend(gem::children_of(&obj));
+        }
+        let props = obj
+            .property_keys()
+            .into_iter()
+            .filter_map(|k| obj.get_property(&k));
+        let meta = obj.meta_keys().into_iter().filter_map(|k| obj.get_meta(&k));
+        for value in props.chain(meta) {
+            held(&value, &mut pending);
+        }
+    }
+    object::live()
+        .into_iter()
+        .filter(|obj| !reached.contains(&obj.id()))
+        .map(|obj| Leak {
+            id: obj.id(),
+            class: obj.class_name().to_string(),
+            path: is_gem(&obj).then(|| gem::get_path(&obj)),
+        })
+        .collect()
+}
+
+fn is_gem(obj: &ObjectRef) -> bool {
+    obj.has_method("add_child")
+}
+
+/// Push the objects in `value` onto `out`
+fn held(value: &Value, out: &mut Vec<ObjectRef>) {
+    match value {
+        Value::Object(obj) => out.push(obj.clone()),
+        Value::Array(items) => items.iter().for_each(|v| held(v, out)),
+        Value::Map(entries) => entries.values().for_each(|v| held(v, out)),
+        _ => {}
+    }
+}
+
+/// The leaks, with each class's creation and destruction counts
+pub struct Report {
+    pub leaks: Vec<Leak>,
+    pub classes: BTreeMap<String, ClassCount>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report {
+            leaks: find(),
+            classes: object::class_counts(),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Leaked objects: {}", self.leaks.len())?;
+        for (class, count) in &self.classes {
+            let leaked = self.leaks.iter().filter(|l| &l.class == class).count();
+            if leaked > 0 {
+                writeln!(
+                    f,
+                    "  {}: {} leaked ({} created, {} destroyed)",
+                    class, leaked, count.created, count.destroyed
+                )?;
+            }
+        }
+        for leak in &self.leaks {
+            match &leak.path {
+                Some(path) => writeln!(f, "  #{} {} at {}", leak.id, leak.class, path)?,
+                None => writeln!(f, "  #{} {}", leak.id, leak.class)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::ObjectInner;
+
+    #[test]
+    fn unreachable_objects_are_reported_with_their_paths() {
+        let root = autoload::tree_root();
+        let add = |parent: &ObjectRef, name: &str| {
+            let node = object::object_new("Gem");
+            node.set_property("name", Value::String(name.into()));
+            parent
+                .call_method("add_child", &[Value::Object(node.clone())])
+                .unwrap();
+            node
+        };
+        let kept = add(&root, "LeakKept");
+        let enemy = add(&root, "LeakEnemy");
+        let sprite = add(&enemy, "Sprite");
+        let texture = ObjectInner::base("Texture");
+        sprite.set_property(
+            "texture",
+            Value::Array(vec![Value::Object(texture.clone())]),
+        );
+        root.call_method("remove_child", &[Value::Object(enemy.clone())])
+            .unwrap();
+
+        let leaks = find();
+        let leaked = |obj: &ObjectRef| leaks.iter().find(|l| l.id == obj.id()).cloned();
+        assert_eq!(leaked(&kept), None);
+        // The removed subtree lives on, and holds the texture
+        assert_eq!(
+            leaked(&enemy).and_then(|l| l.path),
+            Some("/LeakEnemy".to_string())
+        );
+        assert_eq!(
+            leaked(&sprite).and_then(|l| l.path),
+            Some("/LeakEnemy/Sprite".to_string())
+        );
+        assert_eq!(leaked(&texture).map(|l| l.path), Some(None));
+
+        let report = Report::new().to_string();
+        assert!(report.contains(&format!("#{} Gem at /LeakEnemy/Sprite", sprite.id())));
+        let class = &object::class_counts()["Gem"];
+        assert!(class.created > class.destroyed);
+    }
+}
@@ -1,26 +1,173 @@
+use std::ops::Range;
+
+use unicode_ident::{is_xid_continue, is_xid_start};
+
 use crate::error::LexError;
 use crate::token::Token;
 
+/// What a highlighter colors a piece of source as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Keyword,     // a logic keyword (see `Token::keyword`), `true` or `false`
+    TypeIdent,   // a capitalized name not followed by `:`, e.g. `LabelGem`
+    PropertyKey, // a lowercase name followed by `:`
+    Ident,
+    Number,
+    String,
+    Color,
+    Directive, // `#` and the path right after it
+    Punctuation,
+    Comment,
+    Whitespace,
+}
+
+impl Class {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword",
+            Class::TypeIdent => "type-ident",
+            Class::PropertyKey => "property-key",
+            Class::Ident => "ident",
+            Class::Number => "number",
+            Class::String => "string",
+            Class::Color => "color",
+            Class::Directive => "directive",
+            Class::Punctuation => "punctuation",
+            Class::Comment => "comment",
+            Class::Whitespace => "whitespace",
+        }
+    }
+}
+
+/// A token or a run of trivia, with its byte range in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lexeme {
+    pub class: Class,
+    pub range: Range<usize>,
+    /// `None` for whitespace and comments other than `///` doc comments
+    pub token: Option<Token>,
+}
+
+/// Every token of `content` along with the comments and whitespace between
+/// them, covering the source end to end, for syntax highlighting
+pub fn lex_with_trivia(content: &str) -> Result<Vec<Lexeme>, LexError> {
+    let mut lexer = Lexer::new(content.to_string());
+    // Byte offset of each character, then of the end
+    let offsets: Vec<usize> = content
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([content.len()])
+        .collect();
+    let mut out = Vec::new();
+    while lexer.position < lexer.input.len() {
+        let start = lexer.position;
+        let (class, token) = match (lexer.current_char(), lexer.peek_char(), lexer.peek_n(2)) {
+            (ch, ..) if ch.is_whitespace() => {
+                lexer.skip_whitespace();
+                (Class::Whitespace, None)
+            }
+            ('/', Some('/'), next) if next != Some('/') => {
+                lexer.skip_rest_of_line();
+                (Class::Comment, None)
+            }
+            ('/', Some('#'), _) => {
+                lexer.advance();
+                lexer.advance();
+                lexer.skip_multiline_comment()?;
+                (Class::Comment, None)
+            }
+            _ => match lexer.next_token()? {
+                Some(token @ Token::DocComment(_)) => (Class::Comment, Some(token)),
+                token => (Class::Punctuation, token),
+            },
+        };
+        out.push(Lexeme {
+            class,
+            range: offsets[start]..offsets[lexer.position],
+            token,
+        });
+    }
+    classify(&mut out);
+    Ok(out)
+}
+
+/// Class of each token, from the token and those around it
+fn classify(lexemes: &mut [Lexeme]) {
+    let tokens: Vec<usize> = (0..lexemes.len())
+        .filter(|&i| lexemes[i].token.is_some() && lexemes[i].class != Class::Comment)
+        .collect();
+    let mut in_directive = false;
+    for (n, &i) in tokens.iter().enumerate() {
+        let next = tokens.get(n + 1).and_then(|&j| lexemes[j].token.as_ref());
+        let before_colon = next == Some(&Token::Colon);
+        // A directive's path runs on from `#` until a space or other token
+        let touching = n > 0 && lexemes[tokens[n - 1]].range.end == lexemes[i].range.start;
+        let lexeme = &mut lexemes[i];
+        let token = lexeme.token.as_ref().expect("only tokens are classified");
+        in_directive = match token {
+            Token::Hash => true,
+            Token::Ident(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::Colon
+            | Token::Dot
+            | Token::Minus
+            | Token::Question
+            | Token::Amp
+            | Token::Eq => in_directive && touching,
+            _ => false,
+        };
+        lexeme.class = match token {
+            _ if in_directive => Class::Directive,
+            Token::Integer(_) | Token::Float(_) => Class::Number,
+            Token::String(_) => Class::String,
+            Token::Color(_) => Class::Color,
+            _ if token.keyword().is_some() && before_colon => Class::PropertyKey,
+            Token::Bool(_) => Class::Keyword,
+            _ if token.keyword().is_some() => Class::Keyword,
+            Token::Ident(name) if name.starts_with(char::is_uppercase) && !before_colon => {
+                Class::TypeIdent
+            }
+            Token::Ident(name) if before_colon && !name.starts_with(char::is_uppercase) => {
+                Class::PropertyKey
+            }
+            Token::Ident(_) => Class::Ident,
+            _ => Class::Punctuation,
+        };
+    }
+}
+
 pub struct Lexer {
-    input: String,
+    /// Indexed by `position`, so a multi-byte character is one step
+    input: Vec<char>,
     position: usize,
     line: usize,
     column: usize,
+    /// Line the last token lexed starts on
+    token_line: usize,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Self {
         Self {
-            input,
+            input: input.chars().collect(),
             position: 0,
             line: 1,
             column: 1,
+            token_line: 1,
         }
     }
 
+    /// Every token of the input. A `return` that ends its line is followed
+    /// by a `;`, so the statement on the next line isn't read as its value.
     pub fn tokenize(&mut self) -> Result<Vec<Token>, LexError> {
         let mut tokens = Vec::new();
+        let mut line = 1;
         while let Some(token) = self.next_token()? {
+            if tokens.last() == Some(&Token::Return) && self.token_line > line {
+                tokens.push(Token::Semi);
+            }
+            line = self.token_line;
             tokens.push(token);
         }
         Ok(tokens)
@@ -28,6 +175,7 @@ impl Lexer {
 
     fn next_token(&mut self) -> Result<Option<Token>, LexError> {
         self.skip_whitespace();
+        self.token_line = self.line;
 
         if self.position >= self.input.len() {
             return Ok(None);
@@ -37,6 +185,9 @@ impl Lexer {
 
         match ch {
             '#' => {
+                if let Some(color) = self.read_hex_color() {
+                    return Ok(Some(Token::Color(color)));
+                }
                 // directive marker
                 self.advance();
                 return Ok(Some(Token::Hash));
@@ -78,6 +229,14 @@ impl Lexer {
                 self.advance();
                 Ok(Some(Token::RParen))
             }
+            '[' => {
+                self.advance();
+                Ok(Some(Token::LBracket))
+            }
+            ']' => {
+                self.advance();
+                Ok(Some(Token::RBracket))
+            }
             '{' => {
                 self.advance();
                 Ok(Some(Token::LBrace))
@@ -94,6 +253,11 @@ impl Lexer {
                 self.advance();
                 Ok(Some(Token::Colon))
             }
+            '.' if self.peek_char() == Some('.') => {
+                self.advance();
+                self.advance();
+                Ok(Some(Token::DotDot))
+            }
             '.' => {
                 self.advance();
                 Ok(Some(Token::Dot))
@@ -102,6 +266,22 @@ impl Lexer {
                 self.advance();
                 Ok(Some(Token::Semi))
             }
+            '%' => {
+                self.advance();
+                Ok(Some(Token::Percent))
+            }
+            '@' => {
+                self.advance();
+                Ok(Some(Token::At))
+            }
+            '?' => {
+                self.advance();
+                Ok(Some(Token::Question))
+            }
+            '$' => {
+                self.advance();
+                Ok(Some(Token::Dollar))
+            }
             '+' => {
                 self.advance();
                 Ok(Some(Token::Plus))
@@ -119,6 +299,10 @@ impl Lexer {
                     self.advance();
                     self.advance();
                     Ok(Some(Token::EqEq))
+                } else if self.peek_char() == Some('>') {
+                    self.advance();
+                    self.advance();
+                    Ok(Some(Token::FatArrow))
                 } else {
                     self.advance();
                     Ok(Some(Token::Eq))
@@ -160,11 +344,8 @@ impl Lexer {
                     self.advance();
                     Ok(Some(Token::And))
                 } else {
-                    Err(LexError {
-                        message: "Expected '&&'".to_string(),
-                        line: self.line,
-                        column: self.column,
-                    })
+                    self.advance();
+                    Ok(Some(Token::Amp))
                 }
             }
             '|' => {
@@ -182,7 +363,7 @@ impl Lexer {
             }
             '"' => self.read_string(),
             _ if ch.is_ascii_digit() => self.read_number(),
-            _ if ch.is_ascii_alphabetic() || ch == '_' => self.read_identifier(),
+            _ if is_xid_start(ch) || ch == '_' => self.read_identifier(),
             _ => Err(LexError {
                 message: format!("Unexpected character: '{}'", ch),
                 line: self.line,
@@ -195,13 +376,11 @@ impl Lexer {
         while self.position < self.input.len() {
             let ch = self.current_char();
             if ch.is_whitespace() {
+                self.advance();
                 if ch == '\n' {
                     self.line += 1;
                     self.column = 1;
-                } else {
-                    self.column += 1;
                 }
-                self.advance();
             } else {
                 break;
             }
@@ -209,15 +388,15 @@ impl Lexer {
     }
 
     fn current_char(&self) -> char {
-        self.input.chars().nth(self.position).unwrap_or('\0')
+        self.input.get(self.position).copied().unwrap_or('\0')
     }
 
     fn peek_char(&self) -> Option<char> {
-        self.input.chars().nth(self.position + 1)
+        self.peek_n(1)
     }
 
     fn peek_n(&self, n: usize) -> Option<char> {
-        self.input.chars().nth(self.position + n)
+        self.input.get(self.position + n).copied()
     }
 
     fn advance(&mut self) {
@@ -324,7 +503,7 @@ impl Lexer {
 
         while self.position < self.input.len() {
             let ch = self.current_char();
-            if ch.is_ascii_alphanumeric() || ch == '_' {
+            if is_xid_continue(ch) {
                 value.push(ch);
                 self.advance();
             } else {
@@ -345,6 +524,13 @@ impl Lexer {
             "spawn" => return Ok(Some(Token::Spawn)),
             "extend" => return Ok(Some(Token::Extend)),
             "fn" => return Ok(Some(Token::Fn)),
+            "if" => return Ok(Some(Token::If)),
+            "else" => return Ok(Some(Token::Else)),
+            "for" => return Ok(Some(Token::For)),
+            "in" => return Ok(Some(Token::In)),
+            "return" => return Ok(Some(Token::Return)),
+            "let" => return Ok(Some(Token::Let)),
+            "match" => return Ok(Some(Token::Match)),
             _ => {}
         }
 
@@ -353,6 +539,38 @@ impl Lexer {
         Ok(Some(token))
     }
 
+    /// Try to read `#rrggbb` / `#rrggbbaa` at the current '#'. Anything else
+    /// (e.g. `#assets:player.png`) is left alone and lexed as a directive.
+    fn read_hex_color(&mut self) -> Option<[u8; 4]> {
+        let mut digits = String::new();
+        let mut n = 1;
+        while let Some(c) = self.peek_n(n) {
+            if !c.is_ascii_hexdigit() {
+                break;
+            }
+            digits.push(c);
+            n += 1;
+        }
+        let terminated = match self.peek_n(n) {
+            Some(c) => !(is_xid_continue(c) || c == ':' || c == '.'),
+            None => true,
+        };
+        if !terminated || (digits.len() != 6 && digits.len() != 8) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        let color = [
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            if digits.len() == 8 { channel(6)? } else { 255 },
+        ];
+        for _ in 0..n {
+            self.advance();
+        }
+        Some(color)
+    }
+
     fn skip_rest_of_line(&mut self) {
         while self.position < self.input.len() {
             let ch = self.current_char();
@@ -400,3 +618,90 @@ impl Lexer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn lex(src: &str) -> Vec<Token> {
+        Lexer::new(src.to_string()).tokenize().unwrap()
+    }
+
+    #[test]
+    fn unicode_names_and_strings() {
+        assert_eq!(
+            lex(r#"título: "🎮 ゲーム\n" π_2 _x"#),
+            [
+                Token::Ident("título".into()),
+                Token::Colon,
+                Token::String("🎮 ゲーム\n".into()),
+                Token::Ident("π_2".into()),
+                Token::Ident("_x".into()),
+            ]
+        );
+        // Columns count characters, not bytes
+        let err = Lexer::new("\"ü\" 🎮".to_string()).tokenize().unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.column),
+            ("Unexpected character: '🎮'", 5)
+        );
+
+        // A caseless name is a node in full `Name: Type {` form, else a key
+        let src = "Root: Gem { 名前: \"勇者\" 敵: LabelGem { 文字: \"⚔️\" } }";
+        let scene = Parser::new(lex(src)).parse_scene().unwrap();
+        assert_eq!(scene.root.properties[0].key, "名前");
+        assert_eq!(scene.root.children[0].name, "敵");
+        assert_eq!(
+            scene.root.children[0].properties[0].value,
+            crate::ast::Value::String("⚔️".into())
+        );
+    }
+
+    #[test]
+    fn trivia_lexing_classifies_and_covers_the_source() {
+        let src =
+            "// hud\nHud: LabelGem {\n    text: \"hé\" font: #assets:ui.ttf /# x #/ on: true\n}";
+        let lexemes = lex_with_trivia(src).unwrap();
+        assert_eq!(lexemes[0].range, 0..6);
+        assert!(
+            lexemes
+                .windows(2)
+                .all(|w| w[0].range.end == w[1].range.start)
+        );
+        assert_eq!(lexemes.last().unwrap().range.end, src.len());
+
+        let classes: Vec<(&str, Class)> = lexemes
+            .iter()
+            .filter(|l| l.class != Class::Whitespace)
+            .map(|l| (&src[l.range.clone()], l.class))
+            .collect();
+        use Class::*;
+        assert_eq!(
+            classes,
+            [
+                ("// hud", Comment),
+                ("Hud", Ident),
+                (":", Punctuation),
+                ("LabelGem", TypeIdent),
+                ("{", Punctuation),
+                ("text", PropertyKey),
+                (":", Punctuation),
+                ("\"hé\"", String),
+                ("font", PropertyKey),
+                (":", Punctuation),
+                ("#", Directive),
+                ("assets", Directive),
+                (":", Directive),
+                ("ui", Directive),
+                (".", Directive),
+                ("ttf", Directive),
+                ("/# x #/", Comment),
+                ("on", PropertyKey),
+                (":", Punctuation),
+                ("true", Keyword),
+                ("}", Punctuation),
+            ]
+        );
+    }
+}
@@ -0,0 +1,524 @@
+//! LineEditGem and TextEditGem: editable text fields.
+//! A click inside the control's `size` rect gives it keyboard focus (see
+//! `grab_focus`); only the focused control takes key events. Typed text is
+//! inserted at `caret` (a char index into `text`), and `selection_anchor`,
+//! when set, marks the other end of the selection. Ctrl+C/X/V copy, cut and
+//! paste through the system clipboard and Ctrl+A selects everything. A
+//! LineEditGem emits `text_submitted(text)` on Enter and never holds a line
+//! break; a TextEditGem inserts one instead. Every edit emits
+//! `text_changed(text)`, and `editable: false` allows selecting and copying only.
+//! Input method composition is kept in `preedit` (shown at the caret,
+//! underlined, until the IME commits it) and doesn't change `text`.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::clipboard;
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, WeakObjectRef, define_method, register_class};
+use crate::value::Value;
+
+const TEXT_KEY: &str = "text";
+const CARET_KEY: &str = "caret";
+const ANCHOR_KEY: &str = "selection_anchor";
+const EDITABLE_KEY: &str = "editable";
+pub const PREEDIT_KEY: &str = "preedit";
+const PREEDIT_CURSOR_KEY: &str = "preedit_cursor";
+
+/// The control that takes key events
+static FOCUS: Mutex<Option<WeakObjectRef>> = Mutex::new(None);
+
+pub fn init_text_edit_classes() {
+    register_class("LineEditGem", || {
+        new_text_control("LineEditGem", line_edit_methods(), (240.0, 36.0))
+    });
+    register_class("TextEditGem", || {
+        new_text_control("TextEditGem", text_edit_methods(), (320.0, 160.0))
+    });
+}
+
+fn new_text_control(class_name: &str, methods: Arc<MethodTable>, size: (f64, f64)) -> ObjectRef {
+    let obj = gem::new_gem(class_name, methods);
+    obj.set_property(TEXT_KEY, Value::String(String::new()));
+    obj.set_property(CARET_KEY, Value::Int(0));
+    obj.set_property(EDITABLE_KEY, Value::Bool(true));
+    obj.set_property("size", Value::vec2(size.0, size.1));
+    obj
+}
+
+fn line_edit_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_text_control_methods(false)))
+        .clone()
+}
+
+fn text_edit_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_text_control_methods(true)))
+        .clone()
+}
+
+fn build_text_control_methods(multiline: bool) -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+    // grab_focus(), release_focus(), has_focus()
+    define_method(
+        &mut methods,
+        "grab_focus",
+        Arc::new(|this, _| {
+            grab_focus(this);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "release_focus",
+        Arc::new(|this, _| {
+            if has_focus(this) {
+                release_focus();
+            }
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "has_focus",
+        Arc::new(|this, _| Ok(Value::Bool(has_focus(this)))),
+    );
+    // get_selected_text(), select_all(), deselect()
+    define_method(
+        &mut methods,
+        "get_selected_text",
+        Arc::new(|this, _| Ok(Value::String(EditState::of(this).selected_text()))),
+    );
+    define_method(
+        &mut methods,
+        "select_all",
+        Arc::new(|this, _| {
+            let mut state = EditState::of(this);
+            state.select_all();
+            state.store(this);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "deselect",
+        Arc::new(|this, _| {
+            let mut state = EditState::of(this);
+            state.anchor = None;
+            state.store(this);
+            Ok(Value::Null)
+        }),
+    );
+    // _input(event): focus on click; edit and handle shortcuts while focused
+    define_method(
+        &mut methods,
+        "_input",
+        Arc::new(move |this, args| {
+            let Some(event) = args.first().and_then(Value::as_object) else {
+                return Err("_input expects an InputEvent".into());
+            };
+            if handle_input(this, &event, multiline) {
+                event.call_method("accept_event", &[])?;
+            }
+            Ok(Value::Null)
+        }),
+    );
+    methods
+}
+
+pub fn focused() -> Option<ObjectRef> {
+    FOCUS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(WeakObjectRef::upgrade)
+}
+
+fn has_focus(node: &ObjectRef) -> bool {
+    focused().is_some_and(|f| f == *node)
+}
+
+fn grab_focus(node: &ObjectRef) {
+    let previous = FOCUS.lock().unwrap().replace(node.downgrade());
+    match previous.and_then(|p| p.upgrade()) {
+        Some(p) if p == *node => return,
+        Some(p) => p.emit_signal("focus_exited", &[]),
+        None => {}
+    }
+    node.emit_signal("focus_entered", &[]);
+}
+
+fn release_focus() {
+    let previous = FOCUS.lock().unwrap().take();
+    if let Some(p) = previous.and_then(|p| p.upgrade()) {
+        p.emit_signal("focus_exited", &[]);
+    }
+}
+
+/// Returns true when the event was used
+fn handle_input(node: &ObjectRef, event: &ObjectRef, multiline: bool) -> bool {
+    let flag = |key: &str| matches!(event.get_property(key), Some(Value::Bool(true)));
+    match event.class_name() {
+        "InputEventMouseButton" if flag("pressed") => {
+            let inside = event
+                .get_property("position")
+                .and_then(|p| p.as_vec2())
+                .and_then(|p| gem::to_local(node, p))
+                .is_some_and(|(x, y)| {
+                    let (w, h) = node
+                        .get_property("size")
+                        .and_then(|s| s.as_vec2())
+                        .unwrap_or((0.0, 0.0));
+                    x >= 0.0 && y >= 0.0 && x < w && y < h
+                });
+            if inside {
+                grab_focus(node);
+            } else if has_focus(node) {
+                release_focus();
+            }
+            inside
+        }
+        "InputEventKey" if flag("pressed") && has_focus(node) => {
+            let Some(Value::String(keycode)) = event.get_property("keycode") else {
+                return false;
+            };
+            let typed = match event.get_property("text") {
+                Some(Value::String(t)) => t,
+                _ => String::new(),
+            };
+            let editable = !matches!(node.get_property(EDITABLE_KEY), Some(Value::Bool(false)));
+            let mut state = EditState::of(node);
+            let before = state.text.clone();
+            let used = state.apply_key(
+                &keycode,
+                &typed,
+                KeyFlags {
+                    ctrl: flag("ctrl"),
+                    shift: flag("shift"),
+                    multiline,
+                    editable,
+                },
+            );
+            let submitted = !multiline && matches!(keycode.as_str(), "Enter" | "NumpadEnter");
+            state.store(node);
+            if state.text != before {
+                node.emit_signal("text_changed", &[Value::String(state.text_string())]);
+            }
+            if submitted {
+                node.emit_signal("text_submitted", &[Value::String(state.text_string())]);
+            }
+            used || submitted
+        }
+        "InputEventIme"
+            if has_focus(node)
+                && !matches!(node.get_property(EDITABLE_KEY), Some(Value::Bool(false))) =>
+        {
+            let text = match event.get_property("text") {
+                Some(Value::String(t)) => t,
+                _ => String::new(),
+            };
+            let committed = event.get_property("kind") == Some(Value::String("commit".into()));
+            if committed {
+                node.set_property(PREEDIT_KEY, Value::String(String::new()));
+                node.set_property(PREEDIT_CURSOR_KEY, Value::Null);
+                let mut state = EditState::of(node);
+                state.insert(&text, multiline);
+                state.store(node);
+                node.emit_signal("text_changed", &[Value::String(state.text_string())]);
+            } else {
+                node.set_property(PREEDIT_KEY, Value::String(text));
+                node.set_property(
+                    PREEDIT_CURSOR_KEY,
+                    event.get_property("cursor").unwrap_or(Value::Null),
+                );
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyFlags {
+    ctrl: bool,
+    shift: bool,
+    multiline: bool,
+    editable: bool,
+}
+
+/// A control's text, caret and selection anchor, in chars
+#[derive(Debug, Clone, PartialEq)]
+struct EditState {
+    text: Vec<char>,
+    caret: usize,
+    anchor: Option<usize>,
+}
+
+impl EditState {
+    fn of(node: &ObjectRef) -> EditState {
+        let text: Vec<char> = match node.get_property(TEXT_KEY) {
+            Some(Value::String(s)) => s.chars().collect(),
+            _ => Vec::new(),
+        };
+        let index = |key: &str| match node.get_property(key) {
+            Some(Value::Int(i)) => Some((i.max(0) as usize).min(text.len())),
+            _ => None,
+        };
+        EditState {
+            caret: index(CARET_KEY).unwrap_or(text.len()),
+            anchor: index(ANCHOR_KEY),
+            text,
+        }
+    }
+
+    fn store(&self, node: &ObjectRef) {
+        node.set_property(TEXT_KEY, Value::String(self.text_string()));
+        node.set_property(CARET_KEY, Value::Int(self.caret as i64));
+        let anchor = self
+            .selection()
+            .map_or(Value::Null, |_| Value::Int(self.anchor.unwrap() as i64));
+        node.set_property(ANCHOR_KEY, anchor);
+    }
+
+    fn text_string(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    /// Selected char range, when it isn't empty
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor.filter(|&a| a != self.caret)?;
+        Some((anchor.min(self.caret), anchor.max(self.caret)))
+    }
+
+    fn selected_text(&self) -> String {
+        self.selection()
+            .map(|(from, to)| self.text[from..to].iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.caret = self.text.len();
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let Some((from, to)) = self.selection() else {
+            return false;
+        };
+        self.text.drain(from..to);
+        self.caret = from;
+        self.anchor = None;
+        true
+    }
+
+    fn insert(&mut self, text: &str, multiline: bool) {
+        self.delete_selection();
+        let chars = text
+            .chars()
+            .filter(|c| !c.is_control() || (multiline && *c == '\n'));
+        let before = self.text.len();
+        self.text.splice(self.caret..self.caret, chars);
+        self.caret += self.text.len() - before;
+    }
+
+    /// Move the caret, extending the selection when `extend`
+    fn move_to(&mut self, caret: usize, extend: bool) {
+        if extend {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = caret.min(self.text.len());
+    }
+
+    fn line_start(&self, at: usize) -> usize {
+        self.text[..at]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, at: usize) -> usize {
+        self.text[at..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(self.text.len(), |i| at + i)
+    }
+
+    /// The caret one line up or down, keeping its column where the line allows
+    fn vertical(&self, down: bool) -> usize {
+        let start = self.line_start(self.caret);
+        let column = self.caret - start;
+        if down {
+            let end = self.line_end(self.caret);
+            if end == self.text.len() {
+                return end;
+            }
+            (end + 1 + column).min(self.line_end(end + 1))
+        } else if start == 0 {
+            0
+        } else {
+            let above = self.line_start(start - 1);
+            (above + column).min(start - 1)
+        }
+    }
+
+    /// Returns true when the key was used
+    fn apply_key(&mut self, keycode: &str, typed: &str, flags: KeyFlags) -> bool {
+        let collapse = |state: &Self, to_start: bool| {
+            state
+                .selection()
+                .map(|(from, to)| if to_start { from } else { to })
+        };
+        match keycode {
+            "KeyA" if flags.ctrl => self.select_all(),
+            "KeyC" if flags.ctrl => {
+                if self.selection().is_some() {
+                    clipboard::set_text(&self.selected_text());
+                }
+            }
+            "KeyX" if flags.ctrl => {
+                if self.selection().is_some() {
+                    clipboard::set_text(&self.selected_text());
+                    if flags.editable {
+                        self.delete_selection();
+                    }
+                }
+            }
+            "KeyV" if flags.ctrl => {
+                if flags.editable {
+                    self.insert(&clipboard::get_text(), flags.multiline);
+                }
+            }
+            "ArrowLeft" => match collapse(self, true) {
+                Some(from) if !flags.shift => self.move_to(from, false),
+                _ => self.move_to(self.caret.saturating_sub(1), flags.shift),
+            },
+            "ArrowRight" => match collapse(self, false) {
+                Some(to) if !flags.shift => self.move_to(to, false),
+                _ => self.move_to(self.caret + 1, flags.shift),
+            },
+            "ArrowUp" | "ArrowDown" if flags.multiline => {
+                self.move_to(self.vertical(keycode == "ArrowDown"), flags.shift)
+            }
+            "Home" => self.move_to(self.line_start(self.caret), flags.shift),
+            "End" => self.move_to(self.line_end(self.caret), flags.shift),
+            "Backspace" if flags.editable => {
+                if !self.delete_selection() && self.caret > 0 {
+                    self.caret -= 1;
+                    self.text.remove(self.caret);
+                }
+            }
+            "Delete" if flags.editable => {
+                if !self.delete_selection() && self.caret < self.text.len() {
+                    self.text.remove(self.caret);
+                }
+            }
+            "Enter" | "NumpadEnter" if flags.multiline && flags.editable => self.insert("\n", true),
+            _ if typed.chars().any(|c| !c.is_control()) && !flags.ctrl && flags.editable => {
+                self.insert(typed, flags.multiline)
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use crate::scene_tree;
+
+    fn key(node: &ObjectRef, keycode: &str, text: &str, ctrl: bool, shift: bool) {
+        let event = input::key_event(keycode, true, false);
+        input::set_key_text(&event, text);
+        input::set_modifiers(&event, ctrl, shift, false);
+        input::dispatch(node, &event);
+    }
+
+    fn text_of(node: &ObjectRef) -> Value {
+        node.get_property(TEXT_KEY).unwrap()
+    }
+
+    #[test]
+    fn fields_edit_compose_and_use_the_clipboard() {
+        scene_tree::init_builtin_classes();
+        let name = object_new("LineEditGem");
+        let notes = object_new("TextEditGem");
+        // Unfocused fields ignore typing; a click inside focuses
+        key(&name, "KeyH", "h", false, false);
+        assert_eq!(text_of(&name), Value::String(String::new()));
+        input::dispatch(&name, &input::mouse_button_event("left", true, (5.0, 5.0)));
+        assert_eq!(focused(), Some(name.clone()));
+
+        for (code, text) in [("KeyH", "h"), ("KeyI", "i"), ("Space", " "), ("KeyY", "y")] {
+            key(&name, code, text, false, false);
+        }
+        key(&name, "ArrowLeft", "", false, true);
+        key(&name, "ArrowLeft", "", false, true);
+        assert_eq!(
+            name.call_method("get_selected_text", &[]).unwrap(),
+            Value::String(" y".into())
+        );
+        key(&name, "KeyX", "", true, false);
+        assert_eq!(text_of(&name), Value::String("hi".into()));
+        assert_eq!(clipboard::get_text(), " y");
+        key(&name, "Home", "", false, false);
+        key(&name, "KeyV", "", true, false);
+        assert_eq!(text_of(&name), Value::String(" yhi".into()));
+        assert_eq!(name.get_property(CARET_KEY), Some(Value::Int(2)));
+
+        // Line edits drop pasted line breaks; text edits keep them
+        clipboard::set_text("a\nb");
+        key(&name, "KeyV", "", true, false);
+        assert_eq!(text_of(&name), Value::String(" yabhi".into()));
+        notes.call_method("grab_focus", &[]).unwrap();
+        assert_eq!(
+            name.call_method("has_focus", &[]).unwrap(),
+            Value::Bool(false)
+        );
+        key(&notes, "KeyV", "", true, false);
+        key(&notes, "Enter", "\r", false, false);
+        key(&notes, "KeyC", "c", false, false);
+        assert_eq!(text_of(&notes), Value::String("a\nb\nc".into()));
+        key(&notes, "ArrowUp", "", false, false);
+        key(&notes, "ArrowUp", "", false, false);
+        key(&notes, "Delete", "", false, false);
+        assert_eq!(text_of(&notes), Value::String("ab\nc".into()));
+        assert_eq!(notes.get_property(CARET_KEY), Some(Value::Int(1)));
+
+        // Input method composition is held until committed
+        let field = object_new("LineEditGem");
+        field.set_property(TEXT_KEY, Value::String("ab".into()));
+        field.set_property(CARET_KEY, Value::Int(1));
+        let ime = |kind: &str, text: &str| {
+            input::dispatch(
+                &field,
+                &input::ime_event(kind, text, Some(text.chars().count())),
+            )
+        };
+        // Only the focused field composes
+        assert!(!ime("preedit", "に"));
+        field.call_method("grab_focus", &[]).unwrap();
+        assert!(ime("preedit", "に"));
+        assert!(ime("preedit", "にほん"));
+        assert_eq!(
+            field.get_property(PREEDIT_KEY),
+            Some(Value::String("にほん".into()))
+        );
+        assert_eq!(field.get_property(PREEDIT_CURSOR_KEY), Some(Value::Int(3)));
+        assert_eq!(text_of(&field), Value::String("ab".into()));
+
+        assert!(ime("commit", "日本"));
+        assert_eq!(text_of(&field), Value::String("a日本b".into()));
+        assert_eq!(field.get_property(CARET_KEY), Some(Value::Int(3)));
+        assert_eq!(
+            field.get_property(PREEDIT_KEY),
+            Some(Value::String(String::new()))
+        );
+    }
+}
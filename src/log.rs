@@ -0,0 +1,106 @@
+//! Verbosity-filtered console output.
+//! The compile pipeline and the preview report progress through `info!`,
+//! `verbose!` and `trace!` instead of printing directly. Nothing is printed
+//! until a level is set, so the pipeline stays silent when used as a library;
+//! the CLI sets one from `-q`, `-v` and `-vv`. Warnings and errors still go
+//! straight to stderr.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Only warnings and errors
+    Quiet = 0,
+    /// One line per step, the CLI default
+    Info = 1,
+    /// Adds pipeline stage banners, counts and GL setup details (`-v`)
+    Verbose = 2,
+    /// Adds the IR dump and every draw command (`-vv`)
+    Trace = 3,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Quiet as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Remove the verbosity flags from `args`, returning the level they ask for.
+/// `-v` may be repeated; `-q` wins over any number of them.
+pub fn take_flags(args: &mut Vec<String>) -> Level {
+    let mut quiet = false;
+    let mut verbosity = 0;
+    args.retain(|arg| match arg.as_str() {
+        "-q" | "--quiet" => {
+            quiet = true;
+            false
+        }
+        "-v" | "--verbose" => {
+            verbosity += 1;
+            false
+        }
+        "-vv" => {
+            verbosity += 2;
+            false
+        }
+        _ => true,
+    });
+    match (quiet, verbosity) {
+        (true, _) => Level::Quiet,
+        (false, 0) => Level::Info,
+        (false, 1) => Level::Verbose,
+        _ => Level::Trace,
+    }
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Info) {
+            println!($($arg)*);
+        }
+    };
+}
+
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            println!($($arg)*);
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Trace) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {info, trace, verbose};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(flags: &[&str]) -> (Level, Vec<String>) {
+        let mut args: Vec<String> = flags.iter().map(|s| s.to_string()).collect();
+        let level = take_flags(&mut args);
+        (level, args)
+    }
+
+    #[test]
+    fn flags_pick_a_level_and_are_removed() {
+        assert_eq!(level(&["gem"]), (Level::Info, vec!["gem".to_string()]));
+        assert_eq!(level(&["gem", "-v"]).0, Level::Verbose);
+        assert_eq!(level(&["gem", "-v", "-v"]).0, Level::Trace);
+        assert_eq!(level(&["gem", "-vv", "-q"]).0, Level::Quiet);
+        let (level, args) = level(&["gem", "-vv", "--pack", "out.gempack"]);
+        assert_eq!(level, Level::Trace);
+        assert_eq!(args, ["gem", "--pack", "out.gempack"]);
+    }
+}
@@ -1,71 +1,469 @@
+mod accessibility;
+mod animation;
 mod ast;
+mod autoload;
+mod batch;
+mod bench;
+mod bitmap_font;
+mod check_box;
+mod class_defs;
+mod clipboard;
 mod codegen;
+mod color;
+mod color_picker;
+mod commands;
+mod completion;
+mod container;
+mod crash;
+mod curve;
+mod defaults;
+mod defines;
+mod diagnostics;
 mod display;
+mod draw_list;
+mod easing;
+mod editor;
 mod error;
+mod file_access;
+mod font;
 mod gem;
+mod graph;
+mod importers;
+mod input;
 mod ir;
+mod item_list;
+mod json;
+mod leaks;
 mod lexer;
+mod line_edit;
+mod log;
+mod mesh;
+mod multiplayer;
+mod navigation;
 mod object;
+mod option_button;
+mod os;
+mod outliner;
+mod overlay;
 mod parser;
+mod performance;
+mod physics;
 mod pipeline;
+mod popup;
+mod printer;
+mod profile;
+mod project;
 mod property_type;
 mod renderer;
+mod resource;
+mod scene_tree;
+mod scope;
+mod size_expr;
+mod skeleton;
+mod snapshot;
+mod source_map;
+mod state_machine;
+mod stats;
+mod svg;
+mod tab_container;
+mod text;
+mod texture;
+mod time;
+mod timer;
 mod token;
+mod transform;
+mod transform3d;
 mod transformer;
+mod uid;
 mod value;
+mod vfs;
+mod websocket;
+mod worker_pool;
 
 use display::GemDisplay;
-use pipeline::compile_scene;
-use renderer::GemRenderer;
+use editor::Editor;
+use ir::SceneIR;
+use log::{info, trace, verbose};
+use outliner::{Outliner, OutlinerClick};
+use overlay::{ErrorOverlay, Toast, ToastKind};
+use pipeline::compile_preview;
+use renderer::{BlendMode, GemRenderer};
+use source_map::SourceMap;
 use std::collections::HashMap;
-use std::fs;
 use std::path::{Path, PathBuf};
-use winit::event::{Event, WindowEvent};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    ElementState, Event, Ime, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+};
 use winit::event_loop::{ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::window::UserAttentionType;
+
+/// The project manifest, a `res://` path
+const REGISTRY_PATH: &str = "example/scenes.registry.gem";
+/// How often an idle window still runs the scene's frame
+const IDLE_FRAME: Duration = Duration::from_millis(16);
 
 fn main() {
-    println!("Gem Engine - Parser & Renderer Demo");
+    // `-q` keeps only warnings and errors; `-v`/`-vv` add pipeline detail
+    let mut args: Vec<String> = std::env::args().collect();
+    log::set_level(log::take_flags(&mut args));
+    // `--strict` makes unknown Gem types and properties errors
+    let strict = transformer::take_flag(&mut args);
+    // `--leak-report` lists the objects still alive once the preview closes
+    let leak_report = leaks::take_flag(&mut args);
+    // `--define name[=value]` is seen by `#if` and `$name` in every source,
+    // and `--profile name` picks the manifest's build profile
+    let (cli_defines, profile_name) = match defines::take_flags(&mut args)
+        .and_then(|d| Ok((d, profile::take_flag(&mut args)?)))
+    {
+        Ok(flags) => flags,
+        Err(e) => {
+            eprintln!("[ERR] {}", e);
+            std::process::exit(1);
+        }
+    };
+    object::set_main_thread();
+    crash::install_panic_hook();
+
+    // `gem --pack out.gempack dir...` packs project folders for shipping
+    if let [_, flag, out, dirs @ ..] = args.as_slice()
+        && flag == "--pack"
+    {
+        let dirs: Vec<PathBuf> = dirs.iter().map(PathBuf::from).collect();
+        match vfs::write_pack(&dirs, Path::new(out)) {
+            Ok(count) => info!("[INFO] Packed {} file(s) into {}", count, out),
+            Err(e) => eprintln!("[ERR] Packing: {}", e),
+        }
+        return;
+    }
+    mount_pack();
+    load_profile(&profile_name, cli_defines);
+    load_strict(strict);
+
+    // `gem check` lists unused files and directives naming missing ones
+    if let [_, command] = args.as_slice()
+        && command == "check"
+    {
+        match project::build(REGISTRY_PATH) {
+            Ok(graph) => {
+                let report = project::Report {
+                    unreferenced: graph.unreferenced(),
+                    graph: &graph,
+                };
+                print!("{}", report);
+                if !graph.missing.is_empty() || !graph.errors.is_empty() {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem graph [--html]` prints the project's dependency graph
+    if let [_, command, format @ ..] = args.as_slice()
+        && command == "graph"
+    {
+        let html = match format {
+            [] => false,
+            [flag] if flag == "--html" => true,
+            _ => {
+                eprintln!("[ERR] usage: gem graph [--html]");
+                std::process::exit(1);
+            }
+        };
+        match project::build(REGISTRY_PATH) {
+            Ok(project) => {
+                for cycle in graph::cycles(&project) {
+                    let names: Vec<String> =
+                        cycle.iter().map(|p| p.display().to_string()).collect();
+                    eprintln!("[WARN] Reference cycle: {}", names.join(" -> "));
+                }
+                if html {
+                    print!("{}", graph::to_html(&project));
+                } else {
+                    print!("{}", graph::to_dot(&project));
+                }
+            }
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem uids scene.gem` gives every node a stable uid and saves the scene
+    if let [_, command, scene] = args.as_slice()
+        && command == "uids"
+    {
+        let parsed = read_arg(scene).and_then(|content| {
+            if defines::has_conditionals(&content) {
+                return Err(format!(
+                    "{} has #if blocks, which writing it back would flatten",
+                    scene
+                ));
+            }
+            pipeline::lex_source(&content).and_then(pipeline::parse_scene)
+        });
+        let result = parsed.and_then(|mut file| {
+            let added = uid::assign(&mut file);
+            if added > 0 {
+                std::fs::write(scene, printer::print_scene(&file))
+                    .map_err(|e| format!("{}: {}", scene, e))?;
+            }
+            Ok(added)
+        });
+        match result {
+            Ok(added) => info!("[INFO] Added {} uid(s) to {}", added, scene),
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
+    // `gem stats scene.gem` reports what a scene costs instead of running it
+    if let [_, command, scene] = args.as_slice()
+        && command == "stats"
+    {
+        let scene = read_arg(scene)
+            .and_then(|content| pipeline::lex_source(&content))
+            .and_then(pipeline::parse_scene);
+        match scene {
+            Ok(scene) => print!("{}", stats::collect(&scene)),
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem bench-draw-list [--threads n] [scene.gem]` times draw-list builds
+    // on one thread and on several
+    if let [_, command, rest @ ..] = args.as_slice()
+        && command == "bench-draw-list"
+    {
+        let (threads, scene) = match rest {
+            [flag, n, scene @ ..] if flag == "--threads" => {
+                (n.parse().ok().filter(|n| *n > 0), scene)
+            }
+            scene => (
+                std::thread::available_parallelism().ok().map(usize::from),
+                scene,
+            ),
+        };
+        let usage = "usage: gem bench-draw-list [--threads n] [scene.gem]";
+        let scene = match (threads, scene) {
+            (None, _) => Err(usage.to_string()),
+            (_, []) => Ok(None),
+            (_, [path]) => read_arg(path)
+                .and_then(|content| pipeline::lex_source(&content))
+                .and_then(pipeline::parse_scene)
+                .map(Some),
+            _ => Err(usage.to_string()),
+        };
+        let threads = threads.unwrap_or(1);
+        if threads == 1 {
+            eprintln!(
+                "[WARN] bench-draw-list: one thread times the same build twice; pass --threads n to time the split anyway"
+            );
+        }
+        match scene {
+            Ok(scene) => print!("{}", bench::report(scene.as_ref(), threads)),
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem tokens file.gem` prints `start end class` for each token, comment
+    // and run of whitespace, byte ranges included, for editors to highlight
+    if let [_, command, file] = args.as_slice()
+        && command == "tokens"
+    {
+        let lexemes = read_arg(file).and_then(|content| {
+            lexer::lex_with_trivia(&content).map_err(|e| format!("{}: {}", file, e))
+        });
+        match lexemes {
+            Ok(lexemes) => {
+                for l in lexemes {
+                    println!("{} {} {}", l.range.start, l.range.end, l.class.as_str());
+                }
+            }
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem complete scene.gem offset` prints what could be typed at a byte
+    // offset, one `kind label detail` line each, for editor plugins
+    if let [_, command, file, offset] = args.as_slice()
+        && command == "complete"
+    {
+        let Ok(offset) = offset.parse::<usize>() else {
+            eprintln!("[ERR] usage: gem complete file.gem byte-offset");
+            std::process::exit(1);
+        };
+        match read_arg(file) {
+            Ok(content) => {
+                for s in completion::at(&content, offset) {
+                    println!("{}\t{}\t{}", s.kind.as_str(), s.label, s.detail);
+                }
+            }
+            Err(e) => {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `gem diagnostics scene.gem` reports a scene's skipped properties again
+    // after each edit read from stdin, for editors checking as the user types
+    if let [_, command, file] = args.as_slice()
+        && command == "diagnostics"
+    {
+        let served = read_arg(file).and_then(|content| {
+            diagnostics::serve(content, std::io::stdin().lock(), std::io::stdout().lock())
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = served {
+            eprintln!("[ERR] {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `gem compile scene.gem` generates a scene's code without previewing it;
+    // `--no-std` makes that code usable from no_std projects
+    if let [_, command, flags @ .., scene] = args.as_slice()
+        && command == "compile"
+        && flags.iter().all(|f| f == "--no-std")
+    {
+        codegen::set_no_std(!flags.is_empty());
+        let scene = project_relative(scene);
+        let scene = &*scene.to_string_lossy();
+        let out_path = generated_path(scene);
+        let result = read_arg(scene)
+            .and_then(|content| pipeline::compile_scene(&content, &out_path.to_string_lossy()));
+        if let Err(e) = result {
+            eprintln!("[ERR] Compile error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `gem verify-codegen [scene.gem...]` compares generated code with the
+    // snapshots of the given scenes, or of every scene in the project
+    if let [_, command, rest @ ..] = args.as_slice()
+        && command == "verify-codegen"
+    {
+        let update = rest.iter().any(|a| a == "--update");
+        let mut scenes: Vec<PathBuf> = rest
+            .iter()
+            .filter(|a| *a != "--update")
+            .map(|a| project_relative(a))
+            .collect();
+        if scenes.is_empty() {
+            scenes = snapshot::project_scenes(REGISTRY_PATH).unwrap_or_else(|e| {
+                eprintln!("[ERR] {}", e);
+                std::process::exit(1);
+            });
+        }
+        let root = find_root_dir().unwrap_or_else(|| PathBuf::from("."));
+        let mut failed = 0;
+        for scene in &scenes {
+            let result = if update {
+                snapshot::update(&root, scene).map(|path| info!("[INFO] Wrote {}", path.display()))
+            } else {
+                match snapshot::verify(&root, scene) {
+                    Ok(snapshot::Outcome::Matches) => Ok(()),
+                    Ok(snapshot::Outcome::Differs(path, diff)) => Err(format!(
+                        "{}'s generated code differs from {}:\n{}",
+                        scene.display(),
+                        path.display(),
+                        diff.trim_end()
+                    )),
+                    Ok(snapshot::Outcome::Missing(path)) => Err(format!(
+                        "{} has no snapshot; `gem verify-codegen --update` writes {}",
+                        scene.display(),
+                        path.display()
+                    )),
+                    Err(e) => Err(e),
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("[ERR] {}", e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            eprintln!("[ERR] {} of {} scene(s) failed", failed, scenes.len());
+            std::process::exit(1);
+        }
+        if !update {
+            info!("[INFO] {} scene(s) match their snapshots", scenes.len());
+        }
+        return;
+    }
+
+    // Only the preview has a banner; the subcommands' output is for tools
+    info!("Gem Engine - Parser & Renderer Demo");
     let chosen_path = resolve_entry_scene_path();
+    load_permissions();
+    load_pixel_snap();
+    load_autoloads();
 
-    match fs::read_to_string(&chosen_path) {
+    match vfs::read_to_string(&chosen_path) {
         Ok(content) => {
-            println!("\n=== Lexing: {} ===", chosen_path);
+            verbose!("\n=== Lexing: {} ===", chosen_path);
             let is_logic_file =
                 chosen_path.contains("logic") || content.trim_start().starts_with("extend");
 
             if is_logic_file {
-                match pipeline::lex_source(&content).and_then(pipeline::parse_logic) {
+                match pipeline::compile_logic(&content) {
                     Ok(ast) => {
-                        println!("[INFO] Parsed logic file successfully!");
-                        println!("\nAST:\n{:#?}", ast);
-                        println!("\n[INFO] Logic files don't launch renderer - parse only.");
+                        info!("[INFO] Parsed logic file successfully!");
+                        trace!("\nAST:\n{:#?}", ast);
+                        info!("[INFO] Logic files don't launch renderer - parse only.");
                     }
                     Err(e) => eprintln!("[ERR] Logic parse error: {}", e),
                 }
             } else {
-                // Determine root directory (folder containing scenes.registry.gem if present), then write to <root>/gen/<relative>.rs
-                let root_dir = find_root_dir().unwrap_or_else(|| {
-                    Path::new(&chosen_path)
-                        .parent()
-                        .map(|p| p.to_path_buf())
-                        .unwrap_or_else(|| PathBuf::from("."))
-                });
-                let relative = Path::new(&chosen_path)
-                    .strip_prefix(&root_dir)
-                    .unwrap_or_else(|_| Path::new(&chosen_path))
-                    .to_path_buf();
-                let mut out_path = root_dir.join(".gen").join(&relative);
-                out_path.set_extension("rs");
-                if let Some(parent) = out_path.parent() {
-                    std::fs::create_dir_all(parent).ok();
-                }
+                let root_dir = scene_root_dir(&chosen_path);
+                let out_path = generated_path(&chosen_path);
 
-                match compile_scene(&content, &out_path.to_string_lossy()) {
+                match compile_preview(&content, &out_path.to_string_lossy()) {
                     Ok(result) => {
+                        for e in pipeline::describe_errors(&result.ast) {
+                            eprintln!("[ERR] Skipped property in {}", e);
+                        }
                         // Optionally launch renderer for preview
-                        println!("\n[INFO] Launching renderer for preview...");
-                        run_renderer(result.ast);
+                        info!("[INFO] Launching renderer for preview...");
+                        let source = PreviewSource {
+                            scene_path: PathBuf::from(&chosen_path),
+                            out_path,
+                            shader_dir: root_dir.join("shaders"),
+                        };
+                        let map = SourceMap::new(&chosen_path, &content, &result.ast);
+                        run_renderer(result.ast, result.ir, map, source);
+                        if leak_report {
+                            print!("{}", leaks::Report::new());
+                        }
                     }
                     Err(e) => eprintln!("[ERR] Compile error: {}", e),
                 }
@@ -80,169 +478,549 @@ fn main() {
     }
 }
 
+/// A file named on the command line. It is a path on disk as the user typed
+/// it, absolute or with `..`, not a `res://` path.
+fn read_arg(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// A path typed on the command line, made relative to the working directory
+/// when it is inside it, so `.gen` and `.snapshots` mirror it as they do
+/// project paths
+fn project_relative(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
 // Return the directory containing scenes.registry.gem if it exists.
 fn find_root_dir() -> Option<PathBuf> {
-    let registry_path = Path::new("example/scenes.registry.gem");
-    if registry_path.exists() {
-        return registry_path.parent().map(|p| p.to_path_buf());
+    if vfs::is_file(REGISTRY_PATH) {
+        return Path::new(REGISTRY_PATH).parent().map(|p| p.to_path_buf());
     }
     None
 }
 
-fn run_renderer(scene_ast: ast::GemFile) {
-    println!("\n=== Initializing Renderer ===");
+/// The folder containing scenes.registry.gem if present, else the scene's own
+fn scene_root_dir(scene: &str) -> PathBuf {
+    find_root_dir().unwrap_or_else(|| {
+        Path::new(scene)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."))
+    })
+}
 
-    let event_loop = EventLoop::new().expect("Failed to create event loop");
-    let display = GemDisplay::new(&event_loop, 800, 600, "Gem Engine - Scene Viewer");
+/// `<root>/.gen/<relative>.rs` for `scene`, creating its folder
+fn generated_path(scene: &str) -> PathBuf {
+    let root_dir = scene_root_dir(scene);
+    let relative = snapshot::mirrored(&root_dir, Path::new(scene));
+    let mut out_path = root_dir.join(".gen").join(&relative);
+    out_path.set_extension("rs");
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    out_path
+}
 
-    let mut renderer = GemRenderer::new(&display);
+/// Where the previewed scene came from, so F5 can rebuild it
+struct PreviewSource {
+    scene_path: PathBuf,
+    out_path: PathBuf,
+    shader_dir: PathBuf,
+}
 
-    // Simple draw command representing a quad to render
-    #[derive(Clone, Copy, Debug)]
-    struct DrawCmd {
-        x: f32, // pixels
-        y: f32, // pixels
-        w: f32, // pixels
-        h: f32, // pixels
-        color: [f32; 4],
-    }
-
-    // Extract draw commands from AST nodes (placeholder visuals)
-    fn gather_draws(decl: &ast::GemDecl, out: &mut Vec<DrawCmd>) {
-        // Defaults
-        let mut pos: (f32, f32) = (100.0, 100.0);
-        let mut size: (f32, f32) = (160.0, 48.0);
-        let mut color: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
-
-        // Basic property parsing for position
-        for p in &decl.properties {
-            if p.key == "position" {
-                if let ast::Value::Tuple(vals) = &p.value {
-                    if vals.len() >= 2 {
-                        let x = match &vals[0] {
-                            ast::Value::Integer(i) => *i as f32,
-                            ast::Value::Number(n) => *n as f32,
-                            _ => 0.0,
-                        };
-                        let y = match &vals[1] {
-                            ast::Value::Integer(i) => *i as f32,
-                            ast::Value::Number(n) => *n as f32,
-                            _ => 0.0,
-                        };
-                        pos = (x, y);
-                    }
-                }
-            }
-        }
+impl PreviewSource {
+    /// Re-run the compile pipeline on the scene file and rebuild its draw list
+    /// and source map
+    fn reload_scene(
+        &self,
+    ) -> Result<(ast::GemFile, SceneIR, draw_list::DrawList, SourceMap), String> {
+        let path = self.scene_path.to_string_lossy();
+        let content = vfs::read_to_string(&path)?;
+        let result = compile_preview(&content, &self.out_path.to_string_lossy())?;
+        let list = draw_list::build(&result.ast);
+        let map = SourceMap::new(&path, &content, &result.ast);
+        Ok((result.ast, result.ir, list, map))
+    }
+}
 
-        // Color/size by gem type (temporary placeholders)
-        match decl.gem_type.as_str() {
-            "LabelGem" => {
-                color = [0.7, 0.2, 0.8, 1.0];
-                size = (260.0, 40.0);
-            }
-            "ButtonGem" => {
-                color = [0.2, 0.7, 0.3, 1.0];
-                size = (200.0, 56.0);
-            }
-            _ => { /* keep defaults */ }
-        }
+fn run_renderer(
+    mut scene_ast: ast::GemFile,
+    mut scene_ir: SceneIR,
+    mut source_map: SourceMap,
+    source: PreviewSource,
+) {
+    verbose!("\n=== Initializing Renderer ===");
 
-        if decl.gem_type != "Gem" {
-            out.push(DrawCmd {
-                x: pos.0,
-                y: pos.1,
-                w: size.0,
-                h: size.1,
-                color,
-            });
-        }
+    // User events are AccessKit's, for screen readers
+    let event_loop = EventLoop::<accesskit_winit::Event>::with_user_event()
+        .build()
+        .expect("Failed to create event loop");
+    let display = GemDisplay::new(&event_loop, 800, 600, "Gem Engine - Scene Viewer");
+    let proxy = event_loop.create_proxy();
+    // Attached on the first Resumed, when the window is shown
+    let mut access: Option<accesskit_winit::Adapter> = None;
 
-        for c in &decl.children {
-            gather_draws(c, out);
-        }
+    let mut renderer = GemRenderer::new(&display);
+    renderer.set_pixel_ratio(display.window.scale_factor());
+    // Composed text (CJK, dead keys) arrives as Ime events instead of key text
+    display.window.set_ime_allowed(true);
+    let mut toast = None;
+    // Set by a crash or shader error; the scene doesn't update while it's up
+    let mut crashed = None;
+    if let Err(e) = renderer.reload_shaders(&source.shader_dir) {
+        crashed = Some(report_crash(crash::Crash::new("Shader error", &e)));
     }
 
-    // Precompute draw list from AST (static for now)
-    let mut draws: Vec<DrawCmd> = Vec::new();
-    gather_draws(&scene_ast.root, &mut draws);
+    // Precompute draw list from AST; rebuilt on F5, while dragging nodes and
+    // as the running scene changes
+    let mut draw_list = draw_list::build(&scene_ast);
+    let mut editor = Editor::new();
+    let mut cursor = (0.0f32, 0.0f32);
+    let mut modifiers = ModifiersState::empty();
+    let mut outliner = Outliner::new();
+    outliner.refresh(&scene_ast, None, 800.0);
+    let draws = &draw_list.quads;
 
     // Track framebuffer size for pixel-space to NDC conversion
     let mut fb_w: f32 = 800.0;
     let mut fb_h: f32 = 600.0;
+    // Redraw only after a window event or a change to what the running
+    // scene draws
+    let mut dirty = true;
+    let mut drawn_revision = object::draw_revision();
+    // Set while the latest physics step moved something, which is drawn
+    // between its poses until the next step
+    let mut interpolating = false;
+    time::begin_frame();
+    if let Err(c) = crash::guard(&source_map, || enter_scene(&scene_ast)) {
+        crashed = Some(report_crash(c));
+    }
+    // The scene as it was entered, to tell scripts' changes from the editor's
+    let mut entered_ast = scene_ast.clone();
 
-    println!(
+    info!(
         "[INFO] Scene root: {} : {}",
         scene_ast.root.name, scene_ast.root.gem_type
     );
-    println!(
+    verbose!(
         "[INFO] Rendering scene with {} children",
         scene_ast.root.children.len()
     );
-    println!("[INFO] Draw commands: {} quads", draws.len());
+    verbose!(
+        "[INFO] Draw commands: {} quads, {} texts, {} lights, {} occluders, {} fonts",
+        draws.len(),
+        draw_list.texts.len(),
+        draw_list.lights.len(),
+        draw_list.occluders.len(),
+        draw_list.fonts.face_count()
+    );
     for (i, d) in draws.iter().enumerate() {
-        println!(
-            "  [{}] pos=({:.1},{:.1}) size=({:.1}x{:.1}) color={:?}",
-            i, d.x, d.y, d.w, d.h, d.color
+        trace!(
+            "  [{}] pos=({:.1},{:.1}) size=({:.1}x{:.1}) color={:?} blend={:?}",
+            i, d.x, d.y, d.w, d.h, d.color, d.blend
         );
     }
 
     #[allow(deprecated)]
-    let _ = event_loop.run(move |event, elwt| {
-        elwt.set_control_flow(ControlFlow::Poll);
-
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    println!("[INFO] Window close requested");
-                    elwt.exit();
+    let _ =
+        event_loop.run(move |event, elwt| {
+            if let Event::WindowEvent { event, .. } = &event
+                && !matches!(event, WindowEvent::RedrawRequested)
+            {
+                dirty = true;
+            }
+            if let Event::WindowEvent { event, .. } = &event
+                && let Some(access) = &mut access
+            {
+                access.process_event(&display.window, event);
+            }
+
+            // The running scene sees input before the editor
+            if let Event::WindowEvent {
+                event: window_event,
+                ..
+            } = &event
+                && crashed.is_none()
+                && let Some(input) = input_event(window_event, cursor, modifiers)
+            {
+                let delivered = crash::guard(&source_map, || {
+                    input::dispatch(&autoload::tree_root(), &input)
+                });
+                if let Err(c) = delivered {
+                    crashed = Some(report_crash(c));
                 }
-                WindowEvent::Resized(size) => {
-                    display.resize(size.width, size.height);
-                    renderer.set_viewport(size.width, size.height);
-                    fb_w = size.width as f32;
-                    fb_h = size.height as f32;
+                // Keep the IME's candidate window next to the focused field
+                if let Some(field) = line_edit::focused() {
+                    let (x, y) = gem::global_transform(&field).origin;
+                    let height = field
+                        .get_property("size")
+                        .and_then(|s| s.as_vec2())
+                        .map_or(0.0, |s| s.1);
+                    display.window.set_ime_cursor_area(
+                        PhysicalPosition::new(x, y),
+                        PhysicalSize::new(1.0, height),
+                    );
                 }
-                WindowEvent::RedrawRequested => {
-                    renderer.begin_frame();
+            }
 
-                    for d in &draws {
-                        // Convert pixel coordinates to NDC (-1 to 1)
-                        // Center position in pixels
-                        let cx_px = d.x + d.w * 0.5;
-                        let cy_px = d.y + d.h * 0.5;
+            match event {
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => {
+                        info!("[INFO] Window close requested");
+                        gem::propagate_notification(
+                            &autoload::tree_root(),
+                            object::NOTIFICATION_WM_CLOSE_REQUEST,
+                        );
+                        elwt.exit();
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::F5),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => {
+                        info!("[INFO] Reloading {}", source.scene_path.display());
+                        let mut errors = Vec::new();
+                        match source.reload_scene() {
+                            Ok((ast, ir, list, map)) => {
+                                info!("[INFO] Reloaded {} : {}", ast.root.name, ast.root.gem_type);
+                                if editor.dirty {
+                                    info!("[INFO] Discarded unsaved edits");
+                                }
+                                let changes = ir::diff(&scene_ir, &ir);
+                                info!("[INFO] {} node(s) changed", changes.len());
+                                for change in &changes {
+                                    verbose!("  {}", change);
+                                }
+                                // Keep the selection on the same node if it has a uid
+                                let selected_uid = editor
+                                    .selected
+                                    .as_ref()
+                                    .and_then(|n| commands::find_node(&scene_ast.root, n))
+                                    .and_then(uid::of)
+                                    .map(str::to_string);
+                                errors.extend(
+                                    pipeline::describe_errors(&ast)
+                                        .into_iter()
+                                        .map(|e| format!("Skipped property in {}", e)),
+                                );
+                                scene_ast = ast;
+                                scene_ir = ir;
+                                draw_list = list;
+                                source_map = map;
+                                crashed = None;
+                                if let Err(c) =
+                                    crash::guard(&source_map, || enter_scene(&scene_ast))
+                                {
+                                    crashed = Some(report_crash(c));
+                                }
+                                entered_ast = scene_ast.clone();
+                                let snap = editor.snap;
+                                editor = Editor::new();
+                                editor.snap = snap;
+                                editor.selected =
+                                    selected_uid.and_then(|u| uid::find(&scene_ast.root, &u));
+                                outliner.refresh(&scene_ast, editor.selected.as_ref(), fb_w);
+                            }
+                            Err(e) => errors.push(format!("Scene: {}", e)),
+                        }
+                        if let Err(e) = renderer.reload_shaders(&source.shader_dir) {
+                            crashed = Some(report_crash(crash::Crash::new("Shader error", &e)));
+                        }
+                        toast = Some(if errors.is_empty() {
+                            Toast::new(ToastKind::Info, "Reloaded")
+                        } else {
+                            let message = errors.join("\n");
+                            eprintln!("[ERR] Reload failed:\n{}", message);
+                            Toast::new(ToastKind::Error, &message)
+                        });
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } if crashed.is_some() => crashed = None,
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::KeyS),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } if modifiers.control_key() => {
+                        toast = Some(match editor.save(&scene_ast, &source.scene_path) {
+                            Ok(()) => {
+                                info!("[INFO] Saved {}", source.scene_path.display());
+                                Toast::new(ToastKind::Info, "Saved")
+                            }
+                            Err(e) => {
+                                eprintln!("[ERR] Save failed: {}", e);
+                                Toast::new(ToastKind::Error, &format!("Save failed: {}", e))
+                            }
+                        });
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::Tab),
+                                state: ElementState::Pressed,
+                                repeat: false,
+                                ..
+                            },
+                        ..
+                    } => outliner.visible = !outliner.visible,
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::KeyZ),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } if modifiers.control_key() => {
+                        match editor.step_history(&mut scene_ast, modifiers.shift_key()) {
+                            Ok(true) => {
+                                let fonts = std::mem::take(&mut draw_list.fonts);
+                                draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                                outliner.refresh(&scene_ast, editor.selected.as_ref(), fb_w);
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                eprintln!("[ERR] Undo failed: {}", e);
+                                toast = Some(Toast::new(ToastKind::Error, &e));
+                            }
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key:
+                                    PhysicalKey::Code(key @ (KeyCode::Delete | KeyCode::KeyD)),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } if key == KeyCode::Delete || modifiers.control_key() => {
+                        let result = if key == KeyCode::Delete {
+                            editor.delete_selected(&mut scene_ast)
+                        } else {
+                            editor.duplicate_selected(&mut scene_ast)
+                        };
+                        match result {
+                            Ok(true) => {
+                                let fonts = std::mem::take(&mut draw_list.fonts);
+                                draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                                outliner.refresh(&scene_ast, editor.selected.as_ref(), fb_w);
+                            }
+                            Ok(false) => {}
+                            Err(e) => toast = Some(Toast::new(ToastKind::Error, &e)),
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key: PhysicalKey::Code(KeyCode::KeyH),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } if modifiers.control_key() => {
+                        // Ctrl+H hides the selected node, or shows it again
+                        match editor.toggle_selected_visible(&mut scene_ast) {
+                            Ok(true) => {
+                                let fonts = std::mem::take(&mut draw_list.fonts);
+                                draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                            }
+                            Ok(false) => {}
+                            Err(e) => toast = Some(Toast::new(ToastKind::Error, &e)),
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                physical_key:
+                                    PhysicalKey::Code(
+                                        key @ (KeyCode::KeyG
+                                        | KeyCode::BracketLeft
+                                        | KeyCode::BracketRight),
+                                    ),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        match key {
+                            KeyCode::KeyG => editor.snap.grid = !editor.snap.grid,
+                            _ => editor.snap.scale_grid(key == KeyCode::BracketRight),
+                        }
+                        let message = if editor.snap.grid {
+                            format!("Grid snap: {}px", editor.snap.grid_size)
+                        } else {
+                            "Grid snap: off".to_string()
+                        };
+                        toast = Some(Toast::new(ToastKind::Info, &message));
+                    }
+                    WindowEvent::ModifiersChanged(m) => modifiers = m.state(),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        cursor = (position.x as f32, position.y as f32);
+                        if editor.drag_to(&mut scene_ast, cursor) {
+                            let fonts = std::mem::take(&mut draw_list.fonts);
+                            draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => match state {
+                        ElementState::Pressed => {
+                            match outliner.click(&scene_ast, cursor.0, cursor.1) {
+                                Some(OutlinerClick::Select(node)) => editor.selected = Some(node),
+                                Some(_) => {}
+                                None => {
+                                    editor.press(&scene_ast, &draw_list, cursor);
+                                }
+                            }
+                            outliner.refresh(&scene_ast, editor.selected.as_ref(), fb_w);
+                        }
+                        ElementState::Released => editor.release(&scene_ast),
+                    },
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        renderer.set_pixel_ratio(scale_factor);
+                    }
+                    WindowEvent::Resized(size) => {
+                        display.resize(size.width, size.height);
+                        renderer.set_viewport(size.width, size.height);
+                        scene_tree::set_viewport_size(size.width as f64, size.height as f64);
+                        // Size expressions depend on the viewport
+                        let fonts = std::mem::take(&mut draw_list.fonts);
+                        draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                        fb_w = size.width as f32;
+                        fb_h = size.height as f32;
+                        outliner.refresh(&scene_ast, editor.selected.as_ref(), fb_w);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        if object::draw_revision() != drawn_revision || interpolating {
+                            let fonts = std::mem::take(&mut draw_list.fonts);
+                            draw_list = live_draw_list(&scene_ast, &entered_ast, fonts);
+                        }
+                        if object::draw_revision() != drawn_revision
+                            && let Some(access) = &mut access
+                        {
+                            access.update_if_active(accessibility_update);
+                        }
+                        dirty = false;
+                        drawn_revision = object::draw_revision();
+                        renderer.begin_frame();
 
-                        // Convert to NDC: map [0, fb_w] to [-1, 1] and [0, fb_h] to [1, -1] (Y inverted)
-                        let cx_ndc = (cx_px / fb_w) * 2.0 - 1.0;
-                        let cy_ndc = -((cy_px / fb_h) * 2.0 - 1.0);
+                        renderer.render_draw_list(&draw_list);
 
-                        // Convert size to NDC scale
-                        let w_ndc = d.w / fb_w * 2.0;
-                        let h_ndc = d.h / fb_h * 2.0;
+                        renderer.set_blend_mode(BlendMode::Normal);
+                        editor.draw(&renderer, &draw_list, &scene_ast.errors);
+                        outliner.draw(&mut renderer);
 
-                        renderer.render_quad(cx_ndc, cy_ndc, w_ndc, h_ndc, d.color);
-                    }
+                        if toast.as_ref().is_some_and(Toast::expired) {
+                            toast = None;
+                        }
+                        if let Some(t) = &toast {
+                            t.draw(&mut renderer, fb_h, &draw_list.fonts);
+                        }
+                        if let Some(overlay) = &crashed {
+                            overlay.draw(&mut renderer, (fb_w, fb_h), &draw_list.fonts);
+                        }
 
-                    display.swap_buffers();
+                        display.swap_buffers();
+                    }
+                    _ => {}
+                },
+                Event::AboutToWait => {
+                    // The clock keeps running while crashed, so resuming
+                    // doesn't see one huge frame
+                    let dt = time::begin_frame();
+                    if crashed.is_none() {
+                        let root = autoload::tree_root();
+                        let frame = crash::guard(&source_map, || {
+                            let before = object::draw_revision();
+                            let stepped = scene_tree::step_physics(&root, dt) > 0;
+                            let moved = object::draw_revision() != before;
+                            scene_tree::process_frame(&root, dt);
+                            multiplayer::poll_current(&root);
+                            object::flush_deferred_signals();
+                            stepped.then_some(moved)
+                        });
+                        match frame {
+                            Ok(Some(moved)) => interpolating = moved,
+                            Ok(None) => {}
+                            Err(c) => crashed = Some(report_crash(c)),
+                        }
+                    }
+                    if os::take_attention_request() {
+                        display
+                            .window
+                            .request_user_attention(Some(UserAttentionType::Informational));
+                    }
+                    // A toast is taken down by the redraw after it expires
+                    if dirty
+                        || toast.is_some()
+                        || interpolating
+                        || object::draw_revision() != drawn_revision
+                    {
+                        display.window.request_redraw();
+                        elwt.set_control_flow(ControlFlow::Poll);
+                    } else {
+                        elwt.set_control_flow(ControlFlow::WaitUntil(Instant::now() + IDLE_FRAME));
+                    }
+                }
+                // A screen reader has to attach before the window is shown
+                Event::Resumed if access.is_none() => {
+                    access = Some(accesskit_winit::Adapter::with_event_loop_proxy(
+                        elwt,
+                        &display.window,
+                        proxy.clone(),
+                    ));
+                    display.window.set_visible(true);
+                }
+                Event::UserEvent(accesskit_winit::Event {
+                    window_event: accesskit_winit::WindowEvent::InitialTreeRequested,
+                    ..
+                }) => {
+                    if let Some(access) = &mut access {
+                        access.update_if_active(accessibility_update);
+                    }
                 }
                 _ => {}
-            },
-            Event::AboutToWait => {
-                display.window.request_redraw();
             }
-            _ => {}
-        }
-    });
+        });
+}
+
+/// The running tree as screen readers get it
+fn accessibility_update() -> accesskit::TreeUpdate {
+    accessibility::tree_update(&autoload::tree_root()).to_accesskit()
 }
 
 fn resolve_entry_scene_path() -> String {
-    let registry_path = "example/scenes.registry.gem";
-    if Path::new(registry_path).exists() {
-        match fs::read_to_string(registry_path) {
+    if vfs::is_file(REGISTRY_PATH) {
+        match vfs::read_to_string(REGISTRY_PATH) {
             Ok(registry) => {
                 if let Some(directive) = parse_registry_for_entry(&registry) {
-                    let resolved = directive_to_path(&directive);
-                    println!(
+                    let parts: Vec<String> = directive.split(':').map(str::to_string).collect();
+                    let resolved = resource::resolve(&parts).to_string_lossy().into_owned();
+                    info!(
                         "Resolved entry from scenes.registry.gem => {} -> {}",
                         directive, resolved
                     );
@@ -254,16 +1032,254 @@ fn resolve_entry_scene_path() -> String {
                 }
             }
             Err(e) => {
-                eprintln!(
-                    "Warning: Failed to read {}: {}. Falling back to example/main_scene.gem",
-                    registry_path, e
-                );
+                eprintln!("Warning: {}. Falling back to example/main_scene.gem", e);
             }
         }
     }
     "example/main_scene.gem".to_string()
 }
 
+/// The runtime InputEvent for a window event, if it is input. Runs before
+/// `cursor` takes a motion's new position, so the motion's `relative` is
+/// measured from it.
+fn input_event(
+    event: &WindowEvent,
+    cursor: (f32, f32),
+    modifiers: ModifiersState,
+) -> Option<object::ObjectRef> {
+    let at = (cursor.0 as f64, cursor.1 as f64);
+    match event {
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(code),
+                    state,
+                    repeat,
+                    text,
+                    ..
+                },
+            ..
+        } => {
+            let key = input::key_event(
+                &format!("{:?}", code),
+                *state == ElementState::Pressed,
+                *repeat,
+            );
+            input::set_key_text(&key, text.as_deref().unwrap_or(""));
+            let command = cfg!(target_os = "macos") && modifiers.super_key();
+            input::set_modifiers(
+                &key,
+                modifiers.control_key() || command,
+                modifiers.shift_key(),
+                modifiers.alt_key(),
+            );
+            Some(key)
+        }
+        WindowEvent::MouseInput { state, button, .. } => {
+            let button = match button {
+                MouseButton::Left => "left",
+                MouseButton::Right => "right",
+                MouseButton::Middle => "middle",
+                _ => return None,
+            };
+            Some(input::mouse_button_event(
+                button,
+                *state == ElementState::Pressed,
+                at,
+            ))
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let lines = match delta {
+                MouseScrollDelta::LineDelta(x, y) => (*x as f64, *y as f64),
+                // Touchpads report pixels; call a line 20px
+                MouseScrollDelta::PixelDelta(p) => (p.x / 20.0, p.y / 20.0),
+            };
+            Some(input::mouse_wheel_event(at, lines))
+        }
+        WindowEvent::Ime(Ime::Preedit(text, cursor)) => {
+            // winit reports byte offsets; events count chars
+            let cursor = cursor.map(|(start, _)| text[..start].chars().count());
+            Some(input::ime_event("preedit", text, cursor))
+        }
+        WindowEvent::Ime(Ime::Commit(text)) => Some(input::ime_event("commit", text, None)),
+        WindowEvent::CursorMoved { position, .. } => Some(input::mouse_motion_event(
+            (position.x, position.y),
+            (position.x - at.0, position.y - at.1),
+        )),
+        _ => None,
+    }
+}
+
+/// Log a crash and build the overlay that shows it
+fn report_crash(crash: crash::Crash) -> ErrorOverlay {
+    eprintln!("[ERR] {}", crash.report());
+    ErrorOverlay::new(&crash)
+}
+
+/// Replace the running scene with live nodes for `scene`
+/// The draw list for the running scene, or for `scene` as declared before it
+/// has been entered
+fn live_draw_list(
+    scene: &ast::GemFile,
+    entered: &ast::GemFile,
+    fonts: font::FontRegistry,
+) -> draw_list::DrawList {
+    match scene_tree::current_scene() {
+        Some(node) => {
+            let alpha = scene_tree::physics_interpolation_fraction();
+            draw_list::rebuild(&scene_tree::live_scene(scene, entered, &node, alpha), fonts)
+        }
+        None => draw_list::rebuild(scene, fonts),
+    }
+}
+
+fn enter_scene(scene: &ast::GemFile) {
+    if let Err(e) = scene_tree::change_scene(scene) {
+        eprintln!("[ERR] Entering scene: {}", e);
+    }
+}
+
+/// Serve project files from `game.gempack` next to the executable, if present
+fn mount_pack() {
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    let pack = exe.with_file_name("game.gempack");
+    if !pack.is_file() {
+        return;
+    }
+    match vfs::PackSource::open(&pack) {
+        Ok(source) => {
+            vfs::mount(Arc::new(source));
+            info!("[INFO] Mounted {}", pack.display());
+        }
+        Err(e) => eprintln!("[ERR] {}", e),
+    }
+}
+
+/// Grant the OS permissions listed on the manifest's `permissions:` line
+fn load_permissions() {
+    let Ok(registry) = vfs::read_to_string(REGISTRY_PATH) else {
+        return;
+    };
+    let mut permissions = Vec::new();
+    for line in registry.lines() {
+        let Some(rest) = line.trim().strip_prefix("permissions:") else {
+            continue;
+        };
+        for name in rest.split([',', ' ']).filter(|n| !n.is_empty()) {
+            match os::Permission::parse(name) {
+                Some(p) => permissions.push(p),
+                None => eprintln!("[WARN] Unknown permission: {}", name),
+            }
+        }
+    }
+    os::set_permissions(permissions);
+}
+
+/// Apply the manifest's `pixel_snap: true` line, if any
+fn load_pixel_snap() {
+    let Ok(registry) = vfs::read_to_string(REGISTRY_PATH) else {
+        return;
+    };
+    for line in registry.lines() {
+        let Some(rest) = line.trim().strip_prefix("pixel_snap:") else {
+            continue;
+        };
+        match rest.trim() {
+            "true" => scene_tree::set_pixel_snap(true),
+            "false" => scene_tree::set_pixel_snap(false),
+            other => eprintln!("[WARN] pixel_snap expects true or false, got {}", other),
+        }
+    }
+}
+
+/// Use strict mode with `--strict` or the manifest's `strict: true` line
+fn load_strict(flag: bool) {
+    let mut strict = flag;
+    if !flag && let Ok(registry) = vfs::read_to_string(REGISTRY_PATH) {
+        for line in registry.lines() {
+            let Some(rest) = line.trim().strip_prefix("strict:") else {
+                continue;
+            };
+            match rest.trim() {
+                "true" => strict = true,
+                "false" => strict = false,
+                other => eprintln!("[WARN] strict expects true or false, got {}", other),
+            }
+        }
+    }
+    transformer::set_strict(strict);
+}
+
+/// Use the manifest's build profile `name` and the CLI's defines, which win
+/// over the profile's
+fn load_profile(name: &str, cli_defines: defines::Defines) {
+    let mut all = defines::Defines::new();
+    if let Ok(registry) = vfs::read_to_string(REGISTRY_PATH) {
+        match profile::parse(&registry, name) {
+            Ok(Some(profile)) => {
+                info!("[INFO] Using the {} profile", name);
+                all = profile.defines;
+                profile::set_overrides(profile.overrides);
+            }
+            Ok(None) if name != profile::DEFAULT => {
+                eprintln!("[WARN] The manifest has no {} profile", name)
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[ERR] {}: {}", REGISTRY_PATH, e),
+        }
+    }
+    all.extend(cli_defines);
+    defines::set(all);
+}
+
+/// Instantiate the manifest's autoload singletons ahead of the entry scene
+fn load_autoloads() {
+    let Ok(registry) = vfs::read_to_string(REGISTRY_PATH) else {
+        return;
+    };
+    let decls = parse_registry_autoloads(&registry);
+    if decls.is_empty() {
+        return;
+    }
+    match autoload::instantiate(&decls) {
+        Ok(nodes) => info!("[INFO] Autoloaded {} singleton(s)", nodes.len()),
+        Err(e) => eprintln!("[ERR] {}", e),
+    }
+}
+
+/// `autoload: Name: #folder:script` lines, in order
+fn parse_registry_autoloads(contents: &str) -> Vec<autoload::AutoloadDecl> {
+    let mut decls = Vec::new();
+    for line in contents.lines() {
+        let Some(rest) = line.trim().strip_prefix("autoload:") else {
+            continue;
+        };
+        let Some((name, target)) = rest.split_once(':') else {
+            eprintln!("[WARN] Malformed autoload entry: {}", line.trim());
+            continue;
+        };
+        let directive = target
+            .trim()
+            .strip_prefix('#')
+            .and_then(|d| d.split_whitespace().next())
+            .map(|d| d.trim_end_matches(','));
+        match (name.trim(), directive) {
+            (name, Some(directive)) if !name.is_empty() && !directive.is_empty() => {
+                decls.push(autoload::AutoloadDecl {
+                    name: name.to_string(),
+                    script: resource::resolve(
+                        &directive.split(':').map(str::to_string).collect::<Vec<_>>(),
+                    ),
+                })
+            }
+            _ => eprintln!("[WARN] Malformed autoload entry: {}", line.trim()),
+        }
+    }
+    decls
+}
+
 fn parse_registry_for_entry(contents: &str) -> Option<String> {
     let mut entry_name: Option<String> = None;
     let mut map: HashMap<String, String> = HashMap::new();
@@ -298,21 +1314,3 @@ fn parse_registry_for_entry(contents: &str) -> Option<String> {
     let name = entry_name?;
     map.get(&name).cloned()
 }
-
-fn directive_to_path(directive: &str) -> String {
-    let parts: Vec<&str> = directive.split(':').collect();
-    let mut pb = PathBuf::new();
-    for (i, part) in parts.iter().enumerate() {
-        if i == parts.len() - 1 {
-            // last segment: add .gem if missing an extension
-            if !part.contains('.') {
-                pb.push(format!("{}.gem", part));
-            } else {
-                pb.push(part);
-            }
-        } else {
-            pb.push(part);
-        }
-    }
-    pb.to_string_lossy().to_string()
-}
@@ -0,0 +1,211 @@
+//! Triangle meshes for MeshGem, loaded from Wavefront OBJ files or from
+//! glTF models (see `importers::gltf`).
+//! Reads `v` positions, `vn` normals and `f` faces (`v`, `v/vt`, `v//vn` or
+//! `v/vt/vn` corners, counted from 1 or from the end when negative); larger
+//! polygons are split into a fan of triangles. Faces without normals get
+//! their flat face normal. Materials, texture coordinates and groups are
+//! ignored.
+
+use std::path::PathBuf;
+
+use crate::ast;
+use crate::importers::gltf;
+use crate::vfs;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+/// Every three vertices are a triangle, counter-clockwise from the front
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+}
+
+/// Which mesh a MeshGem draws: a file and, in a glTF model, the mesh and
+/// optionally just one of its primitives
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshRef {
+    pub path: PathBuf,
+    pub index: usize,
+    pub primitive: Option<usize>,
+}
+
+/// `#models:ship.glb:mesh(2)` or `mesh(2, 0)` for one primitive; mesh 0 of
+/// a file without the argument
+pub fn reference(directive: &ast::Directive, owner: &str) -> MeshRef {
+    let mut source = MeshRef {
+        path: directive.segments.iter().collect(),
+        index: 0,
+        primitive: None,
+    };
+    let Some(values) = directive.call("mesh") else {
+        return source;
+    };
+    let indices: Option<Vec<usize>> = values
+        .iter()
+        .map(|v| match v {
+            ast::Value::Integer(i) => usize::try_from(*i).ok(),
+            _ => None,
+        })
+        .collect();
+    match indices.as_deref() {
+        Some(&[index]) => source.index = index,
+        Some(&[index, primitive]) => (source.index, source.primitive) = (index, Some(primitive)),
+        _ => eprintln!(
+            "[WARN] {}: mesh takes a mesh index and optionally a primitive index, got {:?}",
+            owner, values
+        ),
+    }
+    source
+}
+
+pub fn load(source: &MeshRef) -> Result<Mesh, String> {
+    let path = &source.path;
+    if gltf::is_model(path) {
+        return gltf::mesh(path, source.index, source.primitive);
+    }
+    let is_obj = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("obj"));
+    if !is_obj {
+        return Err(format!(
+            "{}: only .obj, .gltf and .glb meshes are supported",
+            path.display()
+        ));
+    }
+    let src = vfs::read_to_string(&path.to_string_lossy())?;
+    parse_obj(&src).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse_obj(src: &str) -> Result<Mesh, String> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut mesh = Mesh::default();
+    for (n, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let at = |e: String| format!("line {}: {}", n + 1, e);
+        match words.next() {
+            Some("v") => positions.push(vector(words).map_err(at)?),
+            Some("vn") => normals.push(vector(words).map_err(at)?),
+            Some("f") => {
+                let corners = words
+                    .map(|w| corner(w, &positions, &normals))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(at)?;
+                if corners.len() < 3 {
+                    return Err(at("a face needs at least 3 corners".into()));
+                }
+                for i in 1..corners.len() - 1 {
+                    triangle([corners[0], corners[i], corners[i + 1]], &mut mesh);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+fn vector<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<[f32; 3], String> {
+    let mut v = [0.0; 3];
+    for c in &mut v {
+        let word = words.next().ok_or("expected 3 numbers")?;
+        *c = word
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", word))?;
+    }
+    Ok(v)
+}
+
+/// A face corner's position and, when it names one, normal
+fn corner(
+    word: &str,
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+) -> Result<([f32; 3], Option<[f32; 3]>), String> {
+    let mut parts = word.split('/');
+    let position = lookup(parts.next().unwrap_or(""), positions, "vertex")?;
+    let normal = match parts.nth(1) {
+        Some(i) if !i.is_empty() => Some(lookup(i, normals, "normal")?),
+        _ => None,
+    };
+    Ok((position, normal))
+}
+
+/// The `index`th of `items`, counting from 1, or back from the last when negative
+fn lookup(index: &str, items: &[[f32; 3]], what: &str) -> Result<[f32; 3], String> {
+    let i: i64 = index
+        .parse()
+        .map_err(|_| format!("'{}' is not a {} index", index, what))?;
+    let resolved = if i < 0 { items.len() as i64 + i } else { i - 1 };
+    usize::try_from(resolved)
+        .ok()
+        .and_then(|i| items.get(i).copied())
+        .ok_or_else(|| format!("there is no {} {}", what, i))
+}
+
+/// Appends a triangle, giving corners without a normal the face's own
+pub fn triangle(corners: [([f32; 3], Option<[f32; 3]>); 3], mesh: &mut Mesh) {
+    let [a, b, c] = corners.map(|(p, _)| p);
+    let sub = |p: [f32; 3], q: [f32; 3]| [p[0] - q[0], p[1] - q[1], p[2] - q[2]];
+    let (u, v) = (sub(b, a), sub(c, a));
+    let cross = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let len = cross
+        .iter()
+        .map(|c| c * c)
+        .sum::<f32>()
+        .sqrt()
+        .max(f32::EPSILON);
+    let flat = cross.map(|c| c / len);
+    for (position, normal) in corners {
+        mesh.vertices.push(Vertex {
+            position,
+            normal: normal.unwrap_or(flat),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_become_triangles_with_normals() {
+        let mesh = parse_obj(
+            "# a unit square facing +z\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vn 0 0 -1\n\
+             f 1 2 3 4\n\
+             f -4//1 -3//1 -2//1\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.vertices.len(), 9);
+        assert_eq!(mesh.vertices[5].position, [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.vertices[8].normal, [0.0, 0.0, -1.0]);
+
+        let err = parse_obj("v 0 0 0\nf 1 2 3").unwrap_err();
+        assert_eq!(err, "line 2: there is no vertex 2");
+        assert!(
+            parse_obj("v 0 x 0")
+                .unwrap_err()
+                .contains("'x' is not a number")
+        );
+        assert!(
+            load(&MeshRef {
+                path: "ship.fbx".into(),
+                index: 0,
+                primitive: None,
+            })
+            .unwrap_err()
+            .contains("only .obj")
+        );
+    }
+}
@@ -0,0 +1,667 @@
+//! Remote procedure calls between peers.
+//! A logic function marked `@rpc` can be called on the other peers with
+//! `node.rpc("name", args...)`, or on one peer with `rpc_id(peer, ...)`.
+//! Calls are routed by the node's path under the tree root, so every peer
+//! must build the same tree. Each node has a multiplayer authority (a peer
+//! id; inherited from the parent, and peer 1, the server, at the root). By
+//! default only the authority's calls are accepted; `@rpc(any_peer)` takes
+//! them from anyone, and `call_local` also runs the function on the caller.
+//! The transport is a `MultiplayerPeer`; `WebSocketPeer` goes through a
+//! WebSocketGem connected to a relay that forwards every binary message to
+//! the other clients.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::ast::{Annotation, LogicFile};
+use crate::color::Color;
+use crate::gem;
+use crate::object::{MethodTable, ObjectInner, ObjectRef, define_method};
+use crate::value::Value;
+
+/// The server's peer id, and the default authority
+pub const SERVER_ID: i64 = 1;
+/// `put_packet` target meaning every other peer
+pub const ALL_PEERS: i64 = 0;
+
+pub trait MultiplayerPeer: Send + Sync {
+    fn unique_id(&self) -> i64;
+    /// Send `packet` to peer `to`, or to every other peer with `ALL_PEERS`
+    fn put_packet(&self, to: i64, packet: Vec<u8>);
+    /// Packets received since the last call, with their senders' ids
+    fn take_packets(&self) -> Vec<(i64, Vec<u8>)>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcConfig {
+    /// Accept calls from any peer, not just the node's authority
+    pub any_peer: bool,
+    /// Run on the calling peer too
+    pub call_local: bool,
+}
+
+impl RpcConfig {
+    fn from_annotation(a: &Annotation) -> Result<RpcConfig, String> {
+        let mut config = RpcConfig {
+            any_peer: false,
+            call_local: false,
+        };
+        for arg in &a.args {
+            match arg.as_str() {
+                "any_peer" => config.any_peer = true,
+                "authority" => config.any_peer = false,
+                "call_local" => config.call_local = true,
+                "call_remote" => config.call_local = false,
+                other => return Err(format!("@rpc: unknown option {}", other)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Per-node network state, by object id
+#[derive(Default)]
+struct NodeNet {
+    rpcs: HashMap<String, RpcConfig>,
+    authority: Option<i64>,
+}
+
+static NODES: RwLock<Option<HashMap<u64, NodeNet>>> = RwLock::new(None);
+static PEER: RwLock<Option<Arc<dyn MultiplayerPeer>>> = RwLock::new(None);
+/// Who sent the call being handled; 0 outside remote calls
+static REMOTE_SENDER: AtomicI64 = AtomicI64::new(0);
+
+fn with_net<R>(node: &ObjectRef, f: impl FnOnce(&mut NodeNet) -> R) -> R {
+    let mut nodes = NODES.write().unwrap();
+    f(nodes.get_or_insert_default().entry(node.id()).or_default())
+}
+
+fn rpc_config(node: &ObjectRef, method: &str) -> Option<RpcConfig> {
+    let nodes = NODES.read().unwrap();
+    nodes.as_ref()?.get(&node.id())?.rpcs.get(method).copied()
+}
+
+/// The `@rpc` functions of a logic file, checking their options
+pub fn rpc_configs(logic: &LogicFile) -> Result<Vec<(String, RpcConfig)>, String> {
+    let mut configs = Vec::new();
+    for function in &logic.functions {
+        for a in &function.annotations {
+            if a.name != "rpc" {
+                return Err(format!("{}: unknown annotation @{}", function.name, a.name));
+            }
+            let config =
+                RpcConfig::from_annotation(a).map_err(|e| format!("{}: {}", function.name, e))?;
+            configs.push((function.name.clone(), config));
+        }
+    }
+    Ok(configs)
+}
+
+/// Make `method` callable remotely on `node`
+pub fn set_rpc_config(node: &ObjectRef, method: &str, config: RpcConfig) {
+    with_net(node, |net| net.rpcs.insert(method.to_string(), config));
+}
+
+/// Register the `@rpc` functions of the script attached to `node`
+pub fn configure(node: &ObjectRef, logic: &LogicFile) -> Result<(), String> {
+    for (method, config) in rpc_configs(logic)? {
+        set_rpc_config(node, &method, config);
+    }
+    Ok(())
+}
+
+pub fn authority(node: &ObjectRef) -> i64 {
+    let own = NODES
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|nodes| nodes.get(&node.id())?.authority);
+    own.unwrap_or_else(|| gem::parent_of(node).map_or(SERVER_ID, |p| authority(&p)))
+}
+
+pub fn set_peer(peer: Option<Arc<dyn MultiplayerPeer>>) {
+    *PEER.write().unwrap() = peer;
+}
+
+fn peer() -> Option<Arc<dyn MultiplayerPeer>> {
+    PEER.read().unwrap().clone()
+}
+
+/// This process's peer id; the server's when there is no peer
+pub fn unique_id() -> i64 {
+    peer().map_or(SERVER_ID, |p| p.unique_id())
+}
+
+/// Send `method(args)` on `node` to peer `to` (or `ALL_PEERS`), running it
+/// here as well when the function is `call_local`
+pub fn rpc(node: &ObjectRef, to: i64, method: &str, args: &[Value]) -> Result<(), String> {
+    let config = rpc_config(node, method)
+        .ok_or_else(|| format!("{} isn't an @rpc function of {}", method, node.class_name()))?;
+    let me = unique_id();
+    if to != me
+        && let Some(peer) = peer()
+    {
+        let path = path_from_root(node);
+        peer.put_packet(to, encode_call(&path, method, args)?);
+    }
+    if to == me || (config.call_local && to == ALL_PEERS) {
+        call_as(node, me, method, args);
+    }
+    Ok(())
+}
+
+/// Handle the calls `peer` received, routing them under `root`; returns how
+/// many were run
+pub fn poll(peer: &dyn MultiplayerPeer, root: &ObjectRef) -> usize {
+    let mut handled = 0;
+    for (sender, packet) in peer.take_packets() {
+        let (path, method, args) = match decode_call(&packet) {
+            Ok(call) => call,
+            Err(e) => {
+                eprintln!("[WARN] Bad rpc packet from peer {}: {}", sender, e);
+                continue;
+            }
+        };
+        let node = if path.is_empty() {
+            Some(root.clone())
+        } else {
+            root.call_method("get_node", &[Value::String(path.clone())])
+                .ok()
+                .and_then(|n| n.as_object())
+        };
+        let Some(node) = node else {
+            eprintln!(
+                "[WARN] rpc {} from peer {}: no node at {}",
+                method, sender, path
+            );
+            continue;
+        };
+        let Some(config) = rpc_config(&node, &method) else {
+            eprintln!(
+                "[WARN] rpc {} from peer {}: not an @rpc function",
+                method, sender
+            );
+            continue;
+        };
+        if !config.any_peer && sender != authority(&node) {
+            eprintln!(
+                "[WARN] rpc {} from peer {}: only the authority of {} may call it",
+                method, sender, path
+            );
+            continue;
+        }
+        call_as(&node, sender, &method, &args);
+        handled += 1;
+    }
+    handled
+}
+
+/// Poll the current peer against the tree; called once per frame
+pub fn poll_current(root: &ObjectRef) -> usize {
+    peer().map_or(0, |p| poll(p.as_ref(), root))
+}
+
+fn call_as(node: &ObjectRef, sender: i64, method: &str, args: &[Value]) {
+    let previous = REMOTE_SENDER.swap(sender, Ordering::Relaxed);
+    if let Err(e) = node.call_method(method, args) {
+        eprintln!("[ERR] rpc {}.{}: {}", node.class_name(), method, e);
+    }
+    REMOTE_SENDER.store(previous, Ordering::Relaxed);
+}
+
+/// Names from just below the tree root down to `node`
+fn path_from_root(node: &ObjectRef) -> String {
+    let mut names = Vec::new();
+    let mut cur = node.clone();
+    while let Some(parent) = gem::parent_of(&cur) {
+        names.push(match cur.get_property("name") {
+            Some(Value::String(name)) => name,
+            _ => String::new(),
+        });
+        cur = parent;
+    }
+    names.reverse();
+    names.join("/")
+}
+
+/// rpc(method, args...), rpc_id(peer, method, args...),
+/// set_multiplayer_authority(id), get_multiplayer_authority(),
+/// is_multiplayer_authority(); shared by every Gem's method table
+pub(crate) fn define_rpc_methods(methods: &mut MethodTable) {
+    define_method(
+        methods,
+        "rpc",
+        Arc::new(|this, args| {
+            let Some(Value::String(method)) = args.first() else {
+                return Err("rpc expects a method name".into());
+            };
+            rpc(this, ALL_PEERS, method, &args[1..]).map(|_| Value::Null)
+        }),
+    );
+    define_method(
+        methods,
+        "rpc_id",
+        Arc::new(|this, args| {
+            let (Some(Value::Int(to)), Some(Value::String(method))) = (args.first(), args.get(1))
+            else {
+                return Err("rpc_id expects (peer_id, method, args...)".into());
+            };
+            rpc(this, *to, method, &args[2..]).map(|_| Value::Null)
+        }),
+    );
+    define_method(
+        methods,
+        "set_multiplayer_authority",
+        Arc::new(|this, args| match args.first() {
+            Some(Value::Int(id)) => {
+                with_net(this, |net| net.authority = Some(*id));
+                Ok(Value::Null)
+            }
+            _ => Err("set_multiplayer_authority expects a peer id".into()),
+        }),
+    );
+    define_method(
+        methods,
+        "get_multiplayer_authority",
+        Arc::new(|this, _| Ok(Value::Int(authority(this)))),
+    );
+    define_method(
+        methods,
+        "is_multiplayer_authority",
+        Arc::new(|this, _| Ok(Value::Bool(authority(this) == unique_id()))),
+    );
+}
+
+/// The script-facing `Multiplayer` singleton
+pub fn new_multiplayer() -> ObjectRef {
+    let obj = ObjectInner::base("Multiplayer");
+    // use_websocket(socket, peer_id): talk through a WebSocketGem's relay
+    ObjectInner::insert_method(
+        &obj,
+        "use_websocket",
+        Arc::new(|_, args| {
+            let (Some(Value::Object(socket)), Some(Value::Int(id))) = (args.first(), args.get(1))
+            else {
+                return Err("use_websocket expects (socket, peer_id)".into());
+            };
+            set_peer(Some(Arc::new(WebSocketPeer::new(socket, *id))));
+            Ok(Value::Null)
+        }),
+    );
+    // get_unique_id(), is_server(), get_remote_sender_id()
+    ObjectInner::insert_method(
+        &obj,
+        "get_unique_id",
+        Arc::new(|_, _| Ok(Value::Int(unique_id()))),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "is_server",
+        Arc::new(|_, _| Ok(Value::Bool(unique_id() == SERVER_ID))),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "get_remote_sender_id",
+        Arc::new(|_, _| Ok(Value::Int(REMOTE_SENDER.load(Ordering::Relaxed)))),
+    );
+    obj
+}
+
+type Inbox = Arc<Mutex<Vec<(i64, Vec<u8>)>>>;
+
+/// Packets as binary WebSocket messages: sender id, target id (both 8 bytes,
+/// big-endian), then the payload. The relay sends every message on to the
+/// other clients; each keeps those addressed to it.
+pub struct WebSocketPeer {
+    id: i64,
+    socket: ObjectRef,
+    inbox: Inbox,
+}
+
+impl WebSocketPeer {
+    pub fn new(socket: &ObjectRef, id: i64) -> WebSocketPeer {
+        let inbox = Inbox::default();
+        let received = inbox.clone();
+        socket.connect(
+            "message_received",
+            Arc::new(move |args| {
+                let Some(Value::Array(bytes)) = args.first() else {
+                    return;
+                };
+                let bytes: Vec<u8> = bytes
+                    .iter()
+                    .filter_map(|b| match b {
+                        Value::Int(b) => Some(*b as u8),
+                        _ => None,
+                    })
+                    .collect();
+                if bytes.len() < 16 {
+                    return;
+                }
+                let from = i64::from_be_bytes(bytes[..8].try_into().unwrap());
+                let to = i64::from_be_bytes(bytes[8..16].try_into().unwrap());
+                if from != id && (to == ALL_PEERS || to == id) {
+                    received.lock().unwrap().push((from, bytes[16..].to_vec()));
+                }
+            }),
+        );
+        WebSocketPeer {
+            id,
+            socket: socket.clone(),
+            inbox,
+        }
+    }
+}
+
+impl MultiplayerPeer for WebSocketPeer {
+    fn unique_id(&self) -> i64 {
+        self.id
+    }
+
+    fn put_packet(&self, to: i64, packet: Vec<u8>) {
+        let message = self
+            .id
+            .to_be_bytes()
+            .into_iter()
+            .chain(to.to_be_bytes())
+            .chain(packet)
+            .map(|b| Value::Int(b as i64))
+            .collect();
+        if let Err(e) = self.socket.call_method("send", &[Value::Array(message)]) {
+            eprintln!("[WARN] Multiplayer: {}", e);
+        }
+    }
+
+    fn take_packets(&self) -> Vec<(i64, Vec<u8>)> {
+        std::mem::take(&mut *self.inbox.lock().unwrap())
+    }
+}
+
+fn encode_call(path: &str, method: &str, args: &[Value]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    encode(&Value::String(path.into()), &mut out)?;
+    encode(&Value::String(method.into()), &mut out)?;
+    encode(&Value::Array(args.to_vec()), &mut out)?;
+    Ok(out)
+}
+
+fn decode_call(packet: &[u8]) -> Result<(String, String, Vec<Value>), String> {
+    let mut input = packet;
+    match (
+        decode(&mut input)?,
+        decode(&mut input)?,
+        decode(&mut input)?,
+    ) {
+        (Value::String(path), Value::String(method), Value::Array(args)) if input.is_empty() => {
+            Ok((path, method, args))
+        }
+        _ => Err("not an rpc call".into()),
+    }
+}
+
+/// One tag byte per value, then its data; lengths and numbers little-endian
+fn encode(value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    match value {
+        Value::Null => out.push(0),
+        Value::Bool(b) => out.extend([1, *b as u8]),
+        Value::Int(i) => {
+            out.push(2);
+            out.extend(i.to_le_bytes());
+        }
+        Value::Float(f) => {
+            out.push(3);
+            out.extend(f.to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(4);
+            out.extend((s.len() as u32).to_le_bytes());
+            out.extend(s.as_bytes());
+        }
+        Value::Array(items) => {
+            out.push(5);
+            out.extend((items.len() as u32).to_le_bytes());
+            for item in items {
+                encode(item, out)?;
+            }
+        }
+        Value::Map(map) => {
+            out.push(6);
+            out.extend((map.len() as u32).to_le_bytes());
+            for (key, item) in map {
+                encode(&Value::String(key.clone()), out)?;
+                encode(item, out)?;
+            }
+        }
+        Value::Color(c) => {
+            out.push(7);
+            for channel in c.to_array() {
+                out.extend(channel.to_le_bytes());
+            }
+        }
+        Value::Object(o) => {
+            return Err(format!("can't send a {} to another peer", o.class_name()));
+        }
+    }
+    Ok(())
+}
+
+fn decode(input: &mut &[u8]) -> Result<Value, String> {
+    fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+        if input.len() < n {
+            return Err("packet cut short".into());
+        }
+        let (head, rest) = input.split_at(n);
+        *input = rest;
+        Ok(head)
+    }
+    fn len(input: &mut &[u8]) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(take(input, 4)?.try_into().unwrap()) as usize)
+    }
+    Ok(match take(input, 1)?[0] {
+        0 => Value::Null,
+        1 => Value::Bool(take(input, 1)?[0] != 0),
+        2 => Value::Int(i64::from_le_bytes(take(input, 8)?.try_into().unwrap())),
+        3 => Value::Float(f64::from_le_bytes(take(input, 8)?.try_into().unwrap())),
+        4 => {
+            let n = len(input)?;
+            let bytes = take(input, n)?;
+            Value::String(String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())?)
+        }
+        5 => {
+            let n = len(input)?;
+            let mut items = Vec::new();
+            for _ in 0..n {
+                items.push(decode(input)?);
+            }
+            Value::Array(items)
+        }
+        6 => {
+            let n = len(input)?;
+            let mut map = HashMap::new();
+            for _ in 0..n {
+                let Value::String(key) = decode(input)? else {
+                    return Err("map key isn't a string".into());
+                };
+                map.insert(key, decode(input)?);
+            }
+            Value::Map(map)
+        }
+        7 => {
+            let mut channels = [0.0; 4];
+            for channel in &mut channels {
+                *channel = f32::from_le_bytes(take(input, 4)?.try_into().unwrap());
+            }
+            Value::Color(Color::from_array(channels))
+        }
+        tag => return Err(format!("unknown value tag {}", tag)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_new;
+    use crate::pipeline;
+    use crate::scene_tree;
+
+    /// Peers connected in memory
+    struct LocalPeer {
+        id: i64,
+        inboxes: Arc<Mutex<HashMap<i64, Inbox>>>,
+    }
+
+    /// Connected local peers with the given ids
+    fn local_peers(ids: &[i64]) -> Vec<Arc<LocalPeer>> {
+        let inboxes: Arc<Mutex<HashMap<i64, Inbox>>> = Arc::default();
+        ids.iter()
+            .map(|&id| {
+                inboxes.lock().unwrap().insert(id, Inbox::default());
+                Arc::new(LocalPeer {
+                    id,
+                    inboxes: inboxes.clone(),
+                })
+            })
+            .collect()
+    }
+
+    impl MultiplayerPeer for LocalPeer {
+        fn unique_id(&self) -> i64 {
+            self.id
+        }
+
+        fn put_packet(&self, to: i64, packet: Vec<u8>) {
+            for (id, inbox) in self.inboxes.lock().unwrap().iter() {
+                if *id != self.id && (to == ALL_PEERS || to == *id) {
+                    inbox.lock().unwrap().push((self.id, packet.clone()));
+                }
+            }
+        }
+
+        fn take_packets(&self) -> Vec<(i64, Vec<u8>)> {
+            let inbox = self.inboxes.lock().unwrap()[&self.id].clone();
+            std::mem::take(&mut *inbox.lock().unwrap())
+        }
+    }
+
+    /// World/Player with `sync_position` and `chat` recording what they receive
+    fn world(log: &Arc<Mutex<Vec<String>>>) -> (ObjectRef, ObjectRef) {
+        let logic = pipeline::compile_logic(
+            "extend Gem\n@rpc\nfn sync_position(pos) {}\n\
+             @rpc(any_peer, call_local)\nfn chat(text) {}\n",
+        )
+        .unwrap();
+        let root = object_new("Gem");
+        let player = object_new("Gem");
+        player.set_property("name", Value::String("Player".into()));
+        for method in ["sync_position", "chat"] {
+            let log = log.clone();
+            ObjectInner::insert_method(
+                &player,
+                method,
+                Arc::new(move |_, args| {
+                    let sender = REMOTE_SENDER.load(Ordering::Relaxed);
+                    log.lock()
+                        .unwrap()
+                        .push(format!("{} {:?} from {}", method, args, sender));
+                    Ok(Value::Null)
+                }),
+            );
+        }
+        configure(&player, &logic).unwrap();
+        root.call_method("add_child", &[Value::Object(player.clone())])
+            .unwrap();
+        (root, player)
+    }
+
+    #[test]
+    fn rpcs_route_by_path_and_respect_authority() {
+        scene_tree::init_builtin_classes();
+        let server_log = Arc::new(Mutex::new(Vec::new()));
+        let client_log = Arc::new(Mutex::new(Vec::new()));
+        let (server_root, server_player) = world(&server_log);
+        let (client_root, client_player) = world(&client_log);
+        let [server, client] = local_peers(&[SERVER_ID, 2]).try_into().ok().unwrap();
+        let take = |log: &Arc<Mutex<Vec<String>>>| std::mem::take(&mut *log.lock().unwrap());
+
+        // The client plays as peer 2, but the server still owns the player
+        set_peer(Some(client.clone()));
+        let pos = Value::vec2(3.0, 4.0);
+        client_player
+            .call_method("rpc", &[Value::String("sync_position".into()), pos.clone()])
+            .unwrap();
+        assert_eq!(poll(server.as_ref(), &server_root), 0);
+
+        client_player
+            .call_method("set_multiplayer_authority", &[Value::Int(2)])
+            .unwrap();
+        client_player
+            .call_method("rpc", &[Value::String("sync_position".into()), pos])
+            .unwrap();
+        client_player
+            .call_method(
+                "rpc",
+                &[Value::String("chat".into()), Value::String("hi".into())],
+            )
+            .unwrap();
+        // The server's copy of the player must agree on who owns it
+        server_root
+            .call_method("get_node", &[Value::String("Player".into())])
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .call_method("set_multiplayer_authority", &[Value::Int(2)])
+            .unwrap();
+        assert_eq!(poll(server.as_ref(), &server_root), 2);
+        assert_eq!(
+            take(&server_log),
+            [
+                "sync_position [Array(len=2)] from 2",
+                "chat [String(\"hi\")] from 2",
+            ]
+        );
+        // call_local ran chat on the client as well
+        assert_eq!(take(&client_log), ["chat [String(\"hi\")] from 2"]);
+
+        // Clients check authority too: the server no longer owns the player
+        set_peer(Some(server.clone()));
+        server_player
+            .call_method(
+                "rpc",
+                &[Value::String("sync_position".into()), Value::vec2(0.0, 0.0)],
+            )
+            .unwrap();
+        assert_eq!(poll(client.as_ref(), &client_root), 0);
+        assert!(take(&client_log).is_empty());
+        set_peer(Some(client.clone()));
+
+        let err = client_player
+            .call_method("rpc", &[Value::String("plain".into())])
+            .unwrap_err();
+        assert!(err.contains("isn't an @rpc function"));
+        set_peer(None);
+    }
+
+    #[test]
+    fn values_survive_the_wire_and_options_are_checked() {
+        let args = vec![
+            Value::Null,
+            Value::Bool(true),
+            Value::Int(-7),
+            Value::Color(Color::rgba(1.0, 0.25, 0.0, 0.5)),
+            Value::Map(HashMap::from([(
+                "hp".to_string(),
+                Value::Array(vec![Value::Float(0.5), Value::String("ok".into())]),
+            )])),
+        ];
+        let packet = encode_call("Level/Player", "hit", &args).unwrap();
+        assert_eq!(
+            decode_call(&packet).unwrap(),
+            ("Level/Player".into(), "hit".into(), args)
+        );
+        assert!(decode_call(&packet[..packet.len() - 1]).is_err());
+
+        let err = pipeline::compile_logic("extend Gem\n@rpc(reliable)\nfn a() {}\n").unwrap_err();
+        assert!(err.contains("unknown option reliable"));
+    }
+}
@@ -0,0 +1,308 @@
+//! Grid navigation: A* over a grid of walkable and blocked cells.
+//! The grid comes from tile rows (`#` blocks a cell) or from explicit obstacle
+//! rects, in world units of `cell_size` per cell with the origin at (0, 0).
+//! Scripts use it through the `Navigation` singleton.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, RwLock};
+
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+/// Step costs scaled so diagonals stay integral (10 ≈ 1, 14 ≈ √2)
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavGrid {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: f64,
+    blocked: Vec<bool>,
+}
+
+impl NavGrid {
+    pub fn new(width: usize, height: usize, cell_size: f64) -> Self {
+        Self {
+            width,
+            height,
+            cell_size,
+            blocked: vec![false; width * height],
+        }
+    }
+
+    /// One string per row of tiles; `#` is a wall, anything else is walkable.
+    /// Shorter rows are padded with walkable cells.
+    pub fn from_rows<S: AsRef<str>>(rows: &[S], cell_size: f64) -> Self {
+        let width = rows
+            .iter()
+            .map(|r| r.as_ref().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut grid = Self::new(width, rows.len(), cell_size);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.as_ref().chars().enumerate() {
+                grid.set_blocked(x, y, ch == '#');
+            }
+        }
+        grid
+    }
+
+    pub fn set_blocked(&mut self, x: usize, y: usize, blocked: bool) {
+        if x < self.width && y < self.height {
+            self.blocked[y * self.width + x] = blocked;
+        }
+    }
+
+    pub fn is_blocked(&self, x: usize, y: usize) -> bool {
+        x >= self.width || y >= self.height || self.blocked[y * self.width + x]
+    }
+
+    /// Block every cell the world-space rect overlaps
+    pub fn block_rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        let s = self.cell_size;
+        let (x0, y0) = ((x / s).floor().max(0.0), (y / s).floor().max(0.0));
+        let (x1, y1) = (((x + w) / s).ceil(), ((y + h) / s).ceil());
+        for cy in y0 as usize..(y1.max(0.0) as usize).min(self.height) {
+            for cx in x0 as usize..(x1.max(0.0) as usize).min(self.width) {
+                self.set_blocked(cx, cy, true);
+            }
+        }
+    }
+
+    pub fn cell_of(&self, (x, y): (f64, f64)) -> Option<(usize, usize)> {
+        let (cx, cy) = ((x / self.cell_size).floor(), (y / self.cell_size).floor());
+        (cx >= 0.0 && cy >= 0.0 && (cx as usize) < self.width && (cy as usize) < self.height)
+            .then_some((cx as usize, cy as usize))
+    }
+
+    pub fn cell_center(&self, (x, y): (usize, usize)) -> (f64, f64) {
+        (
+            (x as f64 + 0.5) * self.cell_size,
+            (y as f64 + 0.5) * self.cell_size,
+        )
+    }
+
+    /// Walkable neighbors with their step cost. Diagonals may not cut a
+    /// blocked corner.
+    fn neighbors(&self, (x, y): (usize, usize)) -> impl Iterator<Item = ((usize, usize), u32)> {
+        let mut out = Vec::with_capacity(8);
+        for dy in -1i64..=1 {
+            for dx in -1i64..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || ny < 0 || self.is_blocked(nx as usize, ny as usize) {
+                    continue;
+                }
+                if dx != 0 && dy != 0 {
+                    if self.is_blocked(nx as usize, y) || self.is_blocked(x, ny as usize) {
+                        continue;
+                    }
+                    out.push(((nx as usize, ny as usize), DIAGONAL_COST));
+                } else {
+                    out.push(((nx as usize, ny as usize), STRAIGHT_COST));
+                }
+            }
+        }
+        out.into_iter()
+    }
+
+    /// Octile distance, consistent with the step costs
+    fn heuristic(a: (usize, usize), b: (usize, usize)) -> u32 {
+        let dx = a.0.abs_diff(b.0) as u32;
+        let dy = a.1.abs_diff(b.1) as u32;
+        STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+    }
+
+    /// Shortest path between the cells holding `from` and `to`, as world-space
+    /// cell centers including both ends. `None` when either end is off the
+    /// grid or blocked, or no path exists.
+    pub fn find_path(&self, from: (f64, f64), to: (f64, f64)) -> Option<Vec<(f64, f64)>> {
+        let start = self.cell_of(from)?;
+        let goal = self.cell_of(to)?;
+        if self.is_blocked(start.0, start.1) || self.is_blocked(goal.0, goal.1) {
+            return None;
+        }
+        let index = |(x, y): (usize, usize)| y * self.width + x;
+        let mut cost = vec![u32::MAX; self.width * self.height];
+        let mut came_from = vec![usize::MAX; self.width * self.height];
+        let mut open = BinaryHeap::new();
+        cost[index(start)] = 0;
+        open.push(Reverse((Self::heuristic(start, goal), start)));
+
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if cell == goal {
+                let mut path = vec![self.cell_center(goal)];
+                let mut i = index(goal);
+                while i != index(start) {
+                    i = came_from[i];
+                    path.push(self.cell_center((i % self.width, i / self.width)));
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let base = cost[index(cell)];
+            for (next, step) in self.neighbors(cell) {
+                let next_cost = base + step;
+                if next_cost < cost[index(next)] {
+                    cost[index(next)] = next_cost;
+                    came_from[index(next)] = index(cell);
+                    open.push(Reverse((next_cost + Self::heuristic(next, goal), next)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The script-facing `Navigation` object; it owns one grid, empty until set
+pub fn new_navigation() -> ObjectRef {
+    let obj = ObjectInner::base("Navigation");
+    let grid = Arc::new(RwLock::new(NavGrid::new(0, 0, 1.0)));
+
+    // set_tiles(rows, cell_size)
+    let g = grid.clone();
+    ObjectInner::insert_method(
+        &obj,
+        "set_tiles",
+        Arc::new(move |_, args| {
+            let (Some(Value::Array(rows)), Some(cell_size)) =
+                (args.first(), args.get(1).and_then(Value::as_f64))
+            else {
+                return Err("set_tiles expects (rows, cell_size)".into());
+            };
+            *g.write().unwrap() = NavGrid::from_rows(&tile_rows(rows)?, cell_size);
+            Ok(Value::Null)
+        }),
+    );
+    // set_grid(size, cell_size): an empty grid of size.x by size.y cells
+    let g = grid.clone();
+    ObjectInner::insert_method(
+        &obj,
+        "set_grid",
+        Arc::new(move |_, args| {
+            let (Some((w, h)), Some(cell_size)) = (
+                args.first().and_then(Value::as_vec2),
+                args.get(1).and_then(Value::as_f64),
+            ) else {
+                return Err("set_grid expects (size, cell_size)".into());
+            };
+            *g.write().unwrap() = NavGrid::new(w.max(0.0) as usize, h.max(0.0) as usize, cell_size);
+            Ok(Value::Null)
+        }),
+    );
+    // add_obstacle(position, size) in world units
+    let g = grid.clone();
+    ObjectInner::insert_method(
+        &obj,
+        "add_obstacle",
+        Arc::new(move |_, args| {
+            let (Some((x, y)), Some((w, h))) = (
+                args.first().and_then(Value::as_vec2),
+                args.get(1).and_then(Value::as_vec2),
+            ) else {
+                return Err("add_obstacle expects (position, size)".into());
+            };
+            g.write().unwrap().block_rect(x, y, w, h);
+            Ok(Value::Null)
+        }),
+    );
+    // find_path(from, to) -> array of points, empty when unreachable
+    ObjectInner::insert_method(
+        &obj,
+        "find_path",
+        Arc::new(move |_, args| {
+            let (Some(from), Some(to)) = (
+                args.first().and_then(Value::as_vec2),
+                args.get(1).and_then(Value::as_vec2),
+            ) else {
+                return Err("find_path expects (from, to) points".into());
+            };
+            Ok(path_value(grid.read().unwrap().find_path(from, to)))
+        }),
+    );
+    obj
+}
+
+/// `find_path(rows, cell_size, from, to)` for `WorkerPool.run`. A job can't
+/// reach the singleton's grid, so it gets the tile rows to build its own.
+pub fn find_path_job(args: &[Value]) -> Result<Value, String> {
+    let (Some(Value::Array(rows)), Some(cell_size), Some(from), Some(to)) = (
+        args.first(),
+        args.get(1).and_then(Value::as_f64),
+        args.get(2).and_then(Value::as_vec2),
+        args.get(3).and_then(Value::as_vec2),
+    ) else {
+        return Err("find_path expects (rows, cell_size, from, to)".into());
+    };
+    let grid = NavGrid::from_rows(&tile_rows(rows)?, cell_size);
+    Ok(path_value(grid.find_path(from, to)))
+}
+
+fn tile_rows(rows: &[Value]) -> Result<Vec<&str>, String> {
+    rows.iter()
+        .map(|r| match r {
+            Value::String(s) => Ok(s.as_str()),
+            _ => Err("tile rows must be strings".to_string()),
+        })
+        .collect()
+}
+
+/// The points of `path`, or an empty array when there is none
+fn path_value(path: Option<Vec<(f64, f64)>>) -> Value {
+    Value::Array(
+        path.unwrap_or_default()
+            .into_iter()
+            .map(|(x, y)| Value::vec2(x, y))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_route_around_walls() {
+        let grid = NavGrid::from_rows(&["....", ".##.", "..#.", "...."], 10.0);
+        assert!(grid.is_blocked(2, 1));
+        // From the top-left cell to the one under the wall: around the left side
+        let path = grid.find_path((5.0, 5.0), (15.0, 25.0)).unwrap();
+        assert_eq!(path, [(5.0, 5.0), (5.0, 15.0), (5.0, 25.0), (15.0, 25.0)]);
+        // Diagonals never cut a wall's corner, so either way around takes six steps
+        let path = grid.find_path((35.0, 5.0), (15.0, 25.0)).unwrap();
+        assert_eq!(path.first(), Some(&(35.0, 5.0)));
+        assert_eq!(path.last(), Some(&(15.0, 25.0)));
+        assert_eq!(path.len(), 7);
+        assert!(grid.find_path((25.0, 15.0), (5.0, 5.0)).is_none());
+        assert!(grid.find_path((5.0, 5.0), (500.0, 5.0)).is_none());
+
+        let nav = new_navigation();
+        nav.call_method(
+            "set_grid",
+            &[
+                Value::Array(vec![Value::Int(3), Value::Int(3)]),
+                Value::Int(10),
+            ],
+        )
+        .unwrap();
+        // A wall down the middle column except its bottom cell
+        nav.call_method(
+            "add_obstacle",
+            &[
+                Value::Array(vec![Value::Int(12), Value::Int(0)]),
+                Value::Array(vec![Value::Int(5), Value::Int(15)]),
+            ],
+        )
+        .unwrap();
+        let from = Value::Array(vec![Value::Float(5.0), Value::Float(5.0)]);
+        let to = Value::Array(vec![Value::Float(25.0), Value::Float(5.0)]);
+        let Value::Array(points) = nav.call_method("find_path", &[from, to]).unwrap() else {
+            panic!("expected an array of points");
+        };
+        assert_eq!(points.len(), 7);
+    }
+}
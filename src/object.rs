@@ -1,13 +1,33 @@
-use std::collections::HashMap;
+//! Runtime objects: properties, methods and signals behind shared handles.
+//!
+//! Threading: an `ObjectRef` is `Send + Sync` and every per-object operation
+//! (properties, methods, connect/emit) locks only that object, so any thread
+//! may use it. Signal callbacks run on the emitting thread; background work
+//! that must notify main-thread listeners uses `emit_signal_deferred`, which
+//! queues the emission for `flush_deferred_signals` in the main loop. Changes
+//! to a live tree touch several nodes at once and are main-thread only
+//! (`is_main_thread`), once `set_main_thread` has been called.
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread::{self, ThreadId};
 
+use crate::crash;
 use crate::value::Value;
 
 #[derive(Clone)]
 pub struct ObjectRef(Arc<ObjectInner>);
 
+// Handles are shared with background threads; keep them Send + Sync
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ObjectRef>();
+};
+
 impl PartialEq for ObjectRef {
     fn eq(&self, other: &Self) -> bool {
         self.id() == other.id()
@@ -16,10 +36,126 @@ impl PartialEq for ObjectRef {
 
 impl Eq for ObjectRef {}
 
+/// Non-owning handle, for back-references such as a child's parent
+#[derive(Clone)]
+pub struct WeakObjectRef(Weak<ObjectInner>);
+
+impl WeakObjectRef {
+    pub fn upgrade(&self) -> Option<ObjectRef> {
+        self.0.upgrade().map(ObjectRef)
+    }
+}
+
+impl fmt::Debug for ObjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ObjectRef(class={}, id={})",
+            self.class_name(),
+            self.id()
+        )
+    }
+}
+
 type MethodFn = dyn Fn(&ObjectRef, &[Value]) -> Result<Value, String> + Send + Sync + 'static;
+/// Methods by name. A class builds its table once and every instance shares it.
+pub type MethodTable = HashMap<String, Arc<MethodFn>>;
 type SignalFn = dyn Fn(&[Value]) + Send + Sync + 'static;
 
+// Lifecycle notification codes, delivered through `ObjectRef::notification`
+pub const NOTIFICATION_ENTER_TREE: i64 = 10;
+pub const NOTIFICATION_EXIT_TREE: i64 = 11;
+pub const NOTIFICATION_READY: i64 = 13;
+pub const NOTIFICATION_PAUSED: i64 = 14;
+pub const NOTIFICATION_UNPAUSED: i64 = 15;
+pub const NOTIFICATION_WM_CLOSE_REQUEST: i64 = 1006;
+
 static OBJECT_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+static CENSUS: Mutex<Census> = Mutex::new(Census {
+    classes: BTreeMap::new(),
+    live: BTreeMap::new(),
+});
+
+/// Every object alive, and how many of each class were created and dropped
+struct Census {
+    classes: BTreeMap<String, ClassCount>,
+    live: BTreeMap<u64, WeakObjectRef>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassCount {
+    pub created: u64,
+    pub destroyed: u64,
+}
+static MAIN_THREAD: OnceLock<ThreadId> = OnceLock::new();
+static DEFERRED: Mutex<Vec<(ObjectRef, String, Vec<Value>)>> = Mutex::new(Vec::new());
+/// Held by tests that queue deferred signals and flush them: the queue is
+/// global, so one test's flush would deliver another's emissions
+#[cfg(test)]
+pub(crate) static DEFERRED_TESTS: Mutex<()> = Mutex::new(());
+/// Bumped by the changes that can alter what the running scene draws
+static DRAW_REVISION: AtomicU64 = AtomicU64::new(0);
+/// Whether a property change counts towards `DRAW_REVISION`
+static DRAW_FILTER: OnceLock<fn(&ObjectRef, &str) -> bool> = OnceLock::new();
+
+/// Objects alive right now, of every class
+pub fn live_objects() -> u64 {
+    CENSUS.lock().unwrap().live.len() as u64
+}
+
+/// Handles to the objects alive right now, oldest first
+pub fn live() -> Vec<ObjectRef> {
+    let weak: Vec<WeakObjectRef> = CENSUS.lock().unwrap().live.values().cloned().collect();
+    // Upgraded outside the lock: dropping the last handle locks it again
+    weak.iter().filter_map(WeakObjectRef::upgrade).collect()
+}
+
+/// Objects created and dropped so far, by class
+pub fn class_counts() -> BTreeMap<String, ClassCount> {
+    CENSUS.lock().unwrap().classes.clone()
+}
+
+/// Changes whenever something the running scene draws does, so a frame that
+/// remembers it can skip rebuilding an unchanged draw list
+pub fn draw_revision() -> u64 {
+    DRAW_REVISION.load(Ordering::Relaxed)
+}
+
+pub(crate) fn mark_drawn_changed() {
+    DRAW_REVISION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count only the property changes `filter` accepts towards
+/// `draw_revision`; until then, every change counts
+pub fn set_draw_filter(filter: fn(&ObjectRef, &str) -> bool) {
+    let _ = DRAW_FILTER.set(filter);
+}
+
+/// Mark the calling thread as the one running the main loop
+pub fn set_main_thread() {
+    MAIN_THREAD.get_or_init(|| thread::current().id());
+}
+
+/// True on the main thread, and everywhere until `set_main_thread` is called
+pub fn is_main_thread() -> bool {
+    MAIN_THREAD
+        .get()
+        .is_none_or(|id| *id == thread::current().id())
+}
+
+/// Emit every queued deferred signal, in the order queued; signals deferred
+/// by these callbacks wait for the next flush. Returns how many were emitted.
+pub fn flush_deferred_signals() -> usize {
+    assert!(
+        is_main_thread(),
+        "deferred signals must be flushed on the main thread"
+    );
+    let queued = std::mem::take(&mut *DEFERRED.lock().unwrap());
+    for (obj, signal, args) in &queued {
+        obj.emit_signal(signal, args);
+    }
+    queued.len()
+}
 static CLASS_REGISTRY: OnceLock<RwLock<HashMap<String, fn() -> ObjectRef>>> = OnceLock::new();
 
 fn registry() -> &'static RwLock<HashMap<String, fn() -> ObjectRef>> {
@@ -31,6 +167,10 @@ pub fn register_class(name: &str, ctor: fn() -> ObjectRef) {
     reg.write().unwrap().insert(name.to_string(), ctor);
 }
 
+pub fn is_class_registered(class_name: &str) -> bool {
+    registry().read().unwrap().contains_key(class_name)
+}
+
 pub fn object_new(class_name: &str) -> ObjectRef {
     let reg = registry();
     let map = reg.read().unwrap();
@@ -44,19 +184,64 @@ pub struct ObjectInner {
     id: u64,
     class_name: String,
     properties: RwLock<HashMap<String, Value>>,
-    methods: RwLock<HashMap<String, Arc<MethodFn>>>,
+    /// Metadata (`set_meta`): tags for editors and gameplay, kept apart from
+    /// the declared properties
+    meta: RwLock<HashMap<String, Value>>,
+    /// Shared with every instance of the class
+    class_methods: Arc<MethodTable>,
+    /// Per-instance additions and overrides, consulted first
+    methods: RwLock<MethodTable>,
     signals: RwLock<HashMap<String, Vec<Arc<SignalFn>>>>,
+    /// State a subclass keeps on the instance itself (e.g. Gem tree links), so
+    /// operations on different objects never contend on a shared table
+    extension: OnceLock<Box<dyn Any + Send + Sync>>,
+}
+
+fn empty_methods() -> Arc<MethodTable> {
+    static EMPTY: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    EMPTY.get_or_init(|| Arc::new(MethodTable::new())).clone()
+}
+
+pub(crate) fn define_method(table: &mut MethodTable, name: &str, f: Arc<MethodFn>) {
+    table.insert(name.to_string(), f);
 }
 
 impl ObjectInner {
     pub fn base(class_name: &str) -> ObjectRef {
-        ObjectRef(Arc::new(ObjectInner {
-            id: OBJECT_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+        Self::with_methods(class_name, empty_methods())
+    }
+
+    /// An instance whose methods come from the class's shared table
+    pub fn with_methods(class_name: &str, class_methods: Arc<MethodTable>) -> ObjectRef {
+        let id = OBJECT_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let obj = ObjectRef(Arc::new(ObjectInner {
+            id,
             class_name: class_name.to_string(),
             properties: RwLock::new(HashMap::new()),
-            methods: RwLock::new(HashMap::new()),
+            meta: RwLock::new(HashMap::new()),
+            class_methods,
+            methods: RwLock::new(MethodTable::new()),
             signals: RwLock::new(HashMap::new()),
-        }))
+            extension: OnceLock::new(),
+        }));
+        let mut census = CENSUS.lock().unwrap();
+        census
+            .classes
+            .entry(class_name.to_string())
+            .or_default()
+            .created += 1;
+        census.live.insert(id, obj.downgrade());
+        obj
+    }
+}
+
+impl Drop for ObjectInner {
+    fn drop(&mut self) {
+        let mut census = CENSUS.lock().unwrap();
+        census.live.remove(&self.id);
+        if let Some(count) = census.classes.get_mut(&self.class_name) {
+            count.destroyed += 1;
+        }
     }
 }
 
@@ -70,6 +255,7 @@ impl ObjectInner {
 }
 
 impl ObjectInner {
+    /// Add or override a method on this instance only
     pub(crate) fn insert_method(this: &ObjectRef, name: &str, f: Arc<MethodFn>) {
         this.0.methods.write().unwrap().insert(name.to_string(), f);
     }
@@ -84,24 +270,81 @@ impl ObjectRef {
     pub fn id(&self) -> u64 {
         self.0.id()
     }
+    pub fn downgrade(&self) -> WeakObjectRef {
+        WeakObjectRef(Arc::downgrade(&self.0))
+    }
+    /// The instance's extension state, created on first use. Each object has a
+    /// single extension slot, so every caller must ask for the same type.
+    pub(crate) fn extension<T: Default + Send + Sync + 'static>(&self) -> &T {
+        self.0
+            .extension
+            .get_or_init(|| Box::new(T::default()))
+            .downcast_ref()
+            .unwrap_or_else(|| panic!("{} extension has a different type", self.class_name()))
+    }
+    /// The instance's extension state if it has been created as a `T`
+    pub(crate) fn try_extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.0.extension.get()?.downcast_ref()
+    }
     pub fn class_name(&self) -> &str {
         self.0.class_name()
     }
 
+    /// Setting a property to the value it has is not a change (see `draw_revision`)
     pub fn set_property(&self, key: &str, value: Value) {
-        self.0
-            .properties
-            .write()
-            .unwrap()
-            .insert(key.to_string(), value);
+        {
+            let mut properties = self.0.properties.write().unwrap();
+            if properties.get(key) == Some(&value) {
+                return;
+            }
+            properties.insert(key.to_string(), value);
+        }
+        // The filter reads the node's properties, so the lock is let go first
+        if DRAW_FILTER.get().is_none_or(|drawn| drawn(self, key)) {
+            mark_drawn_changed();
+        }
     }
     pub fn get_property(&self, key: &str) -> Option<Value> {
         self.0.properties.read().unwrap().get(key).cloned()
     }
+    pub fn property_keys(&self) -> Vec<String> {
+        self.0.properties.read().unwrap().keys().cloned().collect()
+    }
+    /// Inspect a property in place instead of cloning it
+    pub fn with_property<R>(&self, key: &str, f: impl FnOnce(Option<&Value>) -> R) -> R {
+        f(self.0.properties.read().unwrap().get(key))
+    }
+    pub fn set_meta(&self, key: &str, value: Value) {
+        self.0.meta.write().unwrap().insert(key.to_string(), value);
+    }
+    pub fn get_meta(&self, key: &str) -> Option<Value> {
+        self.0.meta.read().unwrap().get(key).cloned()
+    }
+    pub fn has_meta(&self, key: &str) -> bool {
+        self.0.meta.read().unwrap().contains_key(key)
+    }
+    pub fn remove_meta(&self, key: &str) -> Option<Value> {
+        self.0.meta.write().unwrap().remove(key)
+    }
+    /// Metadata keys, sorted
+    pub fn meta_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.0.meta.read().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+    pub fn has_method(&self, name: &str) -> bool {
+        self.0.class_methods.contains_key(name) || self.0.methods.read().unwrap().contains_key(name)
+    }
     pub fn call_method(&self, name: &str, args: &[Value]) -> Result<Value, String> {
-        let methods = self.0.methods.read().unwrap();
-        let m = methods
+        // Instance overrides take precedence; no lock is held while the method runs
+        let m = self
+            .0
+            .methods
+            .read()
+            .unwrap()
             .get(name)
+            .or_else(|| self.0.class_methods.get(name))
+            .cloned()
             .ok_or_else(|| format!("Method '{}' not found on {}", name, self.class_name()))?;
         m(self, args)
     }
@@ -109,32 +352,130 @@ impl ObjectRef {
         let mut sigs = self.0.signals.write().unwrap();
         sigs.entry(signal.to_string()).or_default().push(callback);
     }
+    /// Run the callbacks now, on this thread. The lock is released first, so
+    /// callbacks may connect or emit on the same object.
     pub fn emit_signal(&self, signal: &str, args: &[Value]) {
-        if let Some(list) = self.0.signals.read().unwrap().get(signal) {
-            for cb in list {
-                cb(args);
+        let callbacks = self.0.signals.read().unwrap().get(signal).cloned();
+        for cb in callbacks.iter().flatten() {
+            cb(args);
+        }
+    }
+    /// Deliver a notification: the class's built-in `_notification(code)` runs
+    /// first, then the script's `on_notification(code)`. Errors are logged, so
+    /// one failing handler doesn't stop a broadcast.
+    pub fn notification(&self, code: i64) {
+        for handler in ["_notification", "on_notification"] {
+            let args = [Value::Int(code)];
+            if self.has_method(handler)
+                && let Err(e) = crash::running(self, || self.call_method(handler, &args))
+            {
+                eprintln!("[ERR] {}.{}({}): {}", self.class_name(), handler, code, e);
             }
         }
     }
+    /// Queue the emission for the main loop's `flush_deferred_signals`; safe
+    /// from any thread
+    pub fn emit_signal_deferred(&self, signal: &str, args: &[Value]) {
+        DEFERRED
+            .lock()
+            .unwrap()
+            .push((self.clone(), signal.to_string(), args.to_vec()));
+    }
+}
+
+/// `notification(code)`, shared by every class's method table
+pub(crate) fn notification_method(this: &ObjectRef, args: &[Value]) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::Int(code)) => {
+            this.notification(*code);
+            Ok(Value::Null)
+        }
+        _ => Err("notification expects an Int code".into()),
+    }
+}
+
+/// set_meta(key, value), get_meta(key, default?), has_meta(key),
+/// remove_meta(key), get_meta_list(); shared by every class's method table
+pub(crate) fn define_meta_methods(methods: &mut MethodTable) {
+    fn key(args: &[Value], method: &str) -> Result<String, String> {
+        match args.first() {
+            Some(Value::String(key)) => Ok(key.clone()),
+            _ => Err(format!("{} expects a String key", method)),
+        }
+    }
+    define_method(
+        methods,
+        "set_meta",
+        Arc::new(|this, args| {
+            let key = key(args, "set_meta")?;
+            match args.get(1) {
+                // Like Godot, setting Null removes the entry
+                Some(Value::Null) | None => {
+                    this.remove_meta(&key);
+                }
+                Some(value) => this.set_meta(&key, value.clone()),
+            }
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        methods,
+        "get_meta",
+        Arc::new(|this, args| {
+            let key = key(args, "get_meta")?;
+            this.get_meta(&key)
+                .or_else(|| args.get(1).cloned())
+                .ok_or_else(|| format!("{} has no metadata '{}'", this.class_name(), key))
+        }),
+    );
+    define_method(
+        methods,
+        "has_meta",
+        Arc::new(|this, args| Ok(Value::Bool(this.has_meta(&key(args, "has_meta")?)))),
+    );
+    define_method(
+        methods,
+        "remove_meta",
+        Arc::new(|this, args| {
+            Ok(Value::Bool(
+                this.remove_meta(&key(args, "remove_meta")?).is_some(),
+            ))
+        }),
+    );
+    define_method(
+        methods,
+        "get_meta_list",
+        Arc::new(|this, _| {
+            Ok(Value::Array(
+                this.meta_keys().into_iter().map(Value::String).collect(),
+            ))
+        }),
+    );
 }
 
 pub fn init_object_class() {
     static ONCE: OnceLock<()> = OnceLock::new();
     ONCE.get_or_init(|| {
         register_class("Object", || {
-            let obj = ObjectInner::base("Object");
-            ObjectInner::insert_method(
-                &obj,
-                "to_string",
-                Arc::new(|this, _| {
-                    Ok(Value::String(format!(
-                        "<Object {}:{}>",
-                        this.class_name(),
-                        this.id()
-                    )))
-                }),
-            );
-            obj
+            static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+            let methods = METHODS.get_or_init(|| {
+                let mut methods = MethodTable::new();
+                define_method(&mut methods, "notification", Arc::new(notification_method));
+                define_meta_methods(&mut methods);
+                define_method(
+                    &mut methods,
+                    "to_string",
+                    Arc::new(|this, _| {
+                        Ok(Value::String(format!(
+                            "<Object {}:{}>",
+                            this.class_name(),
+                            this.id()
+                        )))
+                    }),
+                );
+                Arc::new(methods)
+            });
+            ObjectInner::with_methods("Object", methods.clone())
         });
     });
 }
@@ -155,4 +496,84 @@ mod tests {
             panic!("Expected string");
         }
     }
+
+    #[test]
+    fn metadata_is_separate_from_properties() {
+        init_object_class();
+        let o = object_new("Object");
+        let s = |v: &str| Value::String(v.into());
+        o.call_method("set_meta", &[s("spawn_point"), Value::Bool(true)])
+            .unwrap();
+        assert_eq!(o.get_property("spawn_point"), None);
+        assert_eq!(
+            o.call_method("has_meta", &[s("spawn_point")]),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            o.call_method("get_meta_list", &[]),
+            Ok(Value::Array(vec![s("spawn_point")]))
+        );
+        // Missing keys fall back to the default, or fail without one
+        assert_eq!(
+            o.call_method("get_meta", &[s("team"), Value::Int(2)]),
+            Ok(Value::Int(2))
+        );
+        assert!(o.call_method("get_meta", &[s("team")]).is_err());
+        o.call_method("set_meta", &[s("spawn_point"), Value::Null])
+            .unwrap();
+        assert!(!o.has_meta("spawn_point"));
+    }
+
+    #[test]
+    fn class_methods_are_shared_and_overridable() {
+        init_object_class();
+        let a = object_new("Object");
+        let b = object_new("Object");
+        assert!(Arc::ptr_eq(&a.0.class_methods, &b.0.class_methods));
+
+        ObjectInner::insert_method(
+            &a,
+            "to_string",
+            Arc::new(|_, _| Ok(Value::String("custom".into()))),
+        );
+        assert_eq!(
+            a.call_method("to_string", &[]),
+            Ok(Value::String("custom".into()))
+        );
+        assert_ne!(
+            b.call_method("to_string", &[]),
+            Ok(Value::String("custom".into()))
+        );
+        assert!(b.has_method("to_string"));
+        assert!(b.call_method("missing", &[]).is_err());
+    }
+
+    #[test]
+    fn deferred_signals_wait_for_the_flush() {
+        let _queue = DEFERRED_TESTS.lock().unwrap_or_else(|e| e.into_inner());
+        init_object_class();
+        let obj = object_new("Object");
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let seen = received.clone();
+        obj.connect(
+            "loaded",
+            Arc::new(move |args| seen.lock().unwrap().extend_from_slice(args)),
+        );
+
+        let worker = obj.clone();
+        thread::spawn(move || {
+            for i in 0..3 {
+                worker.emit_signal_deferred("loaded", &[Value::Int(i)]);
+            }
+        })
+        .join()
+        .unwrap();
+        assert!(received.lock().unwrap().is_empty());
+
+        assert!(flush_deferred_signals() >= 3);
+        assert_eq!(
+            *received.lock().unwrap(),
+            [Value::Int(0), Value::Int(1), Value::Int(2)]
+        );
+    }
 }
@@ -0,0 +1,268 @@
+//! OptionButtonGem: a dropdown. It shows the text of its `selected` item
+//! from `items`; clicking it opens a PopupMenuGem child just below it,
+//! listing the items with the selected one focused. Picking one there (by
+//! click, or arrows and Enter) selects it and emits `item_selected(index)`
+//! on the button. Scripts manage the items with `add_item(text)`, `clear()`
+//! and `select(index)`, which doesn't emit. Items from the scene start with
+//! the first one selected.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{self, MethodTable, ObjectRef, define_method, object_new, register_class};
+use crate::popup;
+use crate::value::Value;
+
+pub const ITEMS_KEY: &str = "items";
+pub const SELECTED_KEY: &str = "selected";
+
+pub fn init_option_button_class() {
+    register_class("OptionButtonGem", || {
+        let obj = gem::new_gem("OptionButtonGem", option_button_methods());
+        obj.set_property(ITEMS_KEY, Value::Array(Vec::new()));
+        obj.set_property(SELECTED_KEY, Value::Int(-1));
+        obj.set_property("size", Value::vec2(200.0, 36.0));
+        obj
+    });
+}
+
+fn option_button_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = (*gem::gem_methods()).clone();
+            // add_item(text) -> index; the first item added is selected
+            define_method(
+                &mut methods,
+                "add_item",
+                Arc::new(|this, args| {
+                    let Some(Value::String(text)) = args.first() else {
+                        return Err("add_item expects a text string".into());
+                    };
+                    let mut items = items(this);
+                    items.push(text.clone());
+                    set_items(this, &items);
+                    if selected(this) < 0 {
+                        this.set_property(SELECTED_KEY, Value::Int(0));
+                    }
+                    Ok(Value::Int(items.len() as i64 - 1))
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_item_text",
+                Arc::new(|this, args| {
+                    let i = index_arg(this, args, "get_item_text")?;
+                    Ok(Value::String(items(this).swap_remove(i)))
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_item_count",
+                Arc::new(|this, _| Ok(Value::Int(items(this).len() as i64))),
+            );
+            define_method(
+                &mut methods,
+                "clear",
+                Arc::new(|this, _| {
+                    set_items(this, &[]);
+                    this.set_property(SELECTED_KEY, Value::Int(-1));
+                    Ok(Value::Null)
+                }),
+            );
+            // select(index): -1 selects nothing
+            define_method(
+                &mut methods,
+                "select",
+                Arc::new(|this, args| {
+                    let index = match args.first() {
+                        Some(Value::Int(-1)) => -1,
+                        _ => index_arg(this, args, "select")? as i64,
+                    };
+                    this.set_property(SELECTED_KEY, Value::Int(index));
+                    Ok(Value::Null)
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_selected",
+                Arc::new(|this, _| Ok(Value::Int(selected(this)))),
+            );
+            // _notification(code): scene `items` start with the first selected
+            define_method(
+                &mut methods,
+                "_notification",
+                Arc::new(|this, args| {
+                    if let Some(Value::Int(object::NOTIFICATION_READY)) = args.first()
+                        && selected(this) < 0
+                        && !items(this).is_empty()
+                    {
+                        this.set_property(SELECTED_KEY, Value::Int(0));
+                    }
+                    Ok(Value::Null)
+                }),
+            );
+            // _input(event): a click on the button opens the menu
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let Some(event) = args.first().and_then(Value::as_object) else {
+                        return Err("_input expects an InputEvent".into());
+                    };
+                    if event.class_name() != "InputEventMouseButton"
+                        || !matches!(event.get_property("pressed"), Some(Value::Bool(true)))
+                    {
+                        return Ok(Value::Null);
+                    }
+                    let (w, h) = this
+                        .get_property("size")
+                        .and_then(|s| s.as_vec2())
+                        .unwrap_or((0.0, 0.0));
+                    let inside = event
+                        .get_property("position")
+                        .and_then(|p| p.as_vec2())
+                        .and_then(|p| gem::to_local(this, p))
+                        .is_some_and(|(x, y)| x >= 0.0 && y >= 0.0 && x < w && y < h);
+                    if inside {
+                        open_menu(this)?;
+                        event.call_method("accept_event", &[])?;
+                    }
+                    Ok(Value::Null)
+                }),
+            );
+            // signals: item_selected(index)
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn items(button: &ObjectRef) -> Vec<String> {
+    match button.get_property(ITEMS_KEY) {
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(t) => t,
+                _ => String::new(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn set_items(button: &ObjectRef, items: &[String]) {
+    let values = items.iter().map(|t| Value::String(t.clone())).collect();
+    button.set_property(ITEMS_KEY, Value::Array(values));
+}
+
+fn selected(button: &ObjectRef) -> i64 {
+    match button.get_property(SELECTED_KEY) {
+        Some(Value::Int(i)) => i,
+        _ => -1,
+    }
+}
+
+fn index_arg(button: &ObjectRef, args: &[Value], method: &str) -> Result<usize, String> {
+    let count = items(button).len();
+    match args.first() {
+        Some(Value::Int(i)) if (0..count as i64).contains(i) => Ok(*i as usize),
+        Some(Value::Int(i)) => Err(format!(
+            "{}: {} is out of range for {} items",
+            method, i, count
+        )),
+        _ => Err(format!("{} expects an Int index", method)),
+    }
+}
+
+/// Open the button's menu, made on first use, under the button
+fn open_menu(button: &ObjectRef) -> Result<(), String> {
+    let menu = match gem::children_of(button)
+        .into_iter()
+        .find(|c| c.class_name() == "PopupMenuGem")
+    {
+        Some(menu) => menu,
+        None => {
+            let menu = object_new("PopupMenuGem");
+            button.call_method("add_child", &[Value::Object(menu.clone())])?;
+            let owner = button.downgrade();
+            menu.connect(
+                "item_selected",
+                Arc::new(move |args| {
+                    if let (Some(button), Some(index)) = (owner.upgrade(), args.first()) {
+                        button.set_property(SELECTED_KEY, index.clone());
+                        button.emit_signal("item_selected", args);
+                    }
+                }),
+            );
+            menu
+        }
+    };
+    let (w, h) = button
+        .get_property("size")
+        .and_then(|s| s.as_vec2())
+        .unwrap_or((0.0, 0.0));
+    let (x, y) = gem::global_transform(button).origin;
+    popup::set_menu_items(&menu, &items(button));
+    menu.set_property("size", Value::vec2(w, 0.0));
+    menu.set_property(popup::FOCUSED_ITEM_KEY, Value::Int(selected(button)));
+    menu.call_method("popup_at", &[Value::vec2(x, y + h)])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::scene_tree;
+    use std::sync::Mutex;
+
+    #[test]
+    fn dropdowns_pick_by_click_and_keys() {
+        scene_tree::init_builtin_classes();
+        let root = object_new("Gem");
+        let button = object_new("OptionButtonGem");
+        root.call_method("add_child", &[Value::Object(button.clone())])
+            .unwrap();
+        button.set_property("position", Value::vec2(10.0, 10.0));
+        for text in ["Low", "Medium", "High"] {
+            button
+                .call_method("add_item", &[Value::String(text.into())])
+                .unwrap();
+        }
+        assert_eq!(button.call_method("get_selected", &[]), Ok(Value::Int(0)));
+        let picked = Arc::new(Mutex::new(Vec::new()));
+        let seen = picked.clone();
+        button.connect(
+            "item_selected",
+            Arc::new(move |args| seen.lock().unwrap().push(args[0].clone())),
+        );
+        let click = |x: f64, y: f64| input::mouse_button_event("left", true, (x, y));
+        let key = |code: &str| input::key_event(code, true, false);
+
+        // The 36px button opens a menu of 24px rows under itself
+        assert!(input::dispatch(&root, &click(20.0, 20.0)));
+        let menu = popup::modal_within(&root).expect("the menu is open");
+        assert_eq!(menu.get_property("size"), Some(Value::vec2(200.0, 72.0)));
+        assert!(input::dispatch(
+            &root,
+            &click(20.0, 46.0 + 24.0 * 2.0 + 4.0)
+        ));
+        assert_eq!(button.call_method("get_selected", &[]), Ok(Value::Int(2)));
+        assert!(!gem::is_visible(&menu));
+
+        // Arrows wrap from the selected item; Enter picks
+        assert!(input::dispatch(&root, &click(20.0, 20.0)));
+        for code in ["ArrowDown", "ArrowDown", "Enter"] {
+            assert!(input::dispatch(&root, &key(code)));
+        }
+        assert_eq!(button.call_method("get_selected", &[]), Ok(Value::Int(1)));
+        assert_eq!(*picked.lock().unwrap(), [Value::Int(2), Value::Int(1)]);
+
+        // Escape closes without picking, and the menu is reused
+        assert!(input::dispatch(&root, &click(20.0, 20.0)));
+        assert!(input::dispatch(&root, &key("Escape")));
+        assert_eq!(popup::modal_within(&root), None);
+        assert_eq!(gem::children_of(&button).len(), 1);
+        assert_eq!(picked.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,260 @@
+//! The `OS` singleton: the platform's name, environment and desktop shell.
+//! Everything that reaches outside the game needs a permission granted by the
+//! project manifest's `permissions:` line, so a script can't open links or read
+//! the environment unless the project opted in. URLs and folders are handed to
+//! the platform's launcher (xdg-open, open, explorer); `request_attention`
+//! is picked up by the main loop, which owns the window.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// `open_url`
+    OpenUrl,
+    /// `get_env`
+    Env,
+    /// `shell_show_in_file_manager`
+    FileManager,
+    /// `request_attention`
+    Attention,
+}
+
+impl Permission {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open_url" => Some(Self::OpenUrl),
+            "env" => Some(Self::Env),
+            "file_manager" => Some(Self::FileManager),
+            "attention" => Some(Self::Attention),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::OpenUrl => "open_url",
+            Self::Env => "env",
+            Self::FileManager => "file_manager",
+            Self::Attention => "attention",
+        }
+    }
+}
+
+static GRANTED: RwLock<Vec<Permission>> = RwLock::new(Vec::new());
+/// Set by `request_attention`, cleared by the main loop
+static ATTENTION: AtomicBool = AtomicBool::new(false);
+
+/// Replace the granted permissions, as read from the manifest
+pub fn set_permissions(permissions: Vec<Permission>) {
+    *GRANTED.write().unwrap() = permissions;
+}
+
+fn require(permission: Permission, method: &str) -> Result<(), String> {
+    if GRANTED.read().unwrap().contains(&permission) {
+        Ok(())
+    } else {
+        Err(format!(
+            "OS.{} needs `permissions: {}` in scenes.registry",
+            method,
+            permission.name()
+        ))
+    }
+}
+
+pub fn name() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macOS"
+    } else if cfg!(windows) {
+        "Windows"
+    } else if cfg!(target_os = "android") {
+        "Android"
+    } else if cfg!(target_os = "ios") {
+        "iOS"
+    } else if cfg!(target_family = "wasm") {
+        "Web"
+    } else if cfg!(any(
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )) {
+        "BSD"
+    } else {
+        "Linux"
+    }
+}
+
+/// Open `url` in the user's browser (or mail client, for `mailto:`)
+pub fn open_url(url: &str) -> Result<(), String> {
+    require(Permission::OpenUrl, "open_url")?;
+    if !["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+    {
+        return Err(format!(
+            "open_url: only http, https and mailto links, not {}",
+            url
+        ));
+    }
+    launch(url, false)
+}
+
+/// Open a file manager at `path`, with the file selected where supported
+pub fn show_in_file_manager(path: &str) -> Result<(), String> {
+    require(Permission::FileManager, "shell_show_in_file_manager")?;
+    if !Path::new(path).exists() {
+        return Err(format!(
+            "shell_show_in_file_manager: {} doesn't exist",
+            path
+        ));
+    }
+    launch(path, true)
+}
+
+fn launch(target: &str, select: bool) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut c = Command::new("open");
+        if select {
+            c.arg("-R");
+        }
+        c.arg(target);
+        c
+    } else if cfg!(windows) {
+        if select {
+            let mut c = Command::new("explorer");
+            c.arg(format!("/select,{}", target));
+            c
+        } else {
+            let mut c = Command::new("cmd");
+            c.args(["/C", "start", "", target]);
+            c
+        }
+    } else {
+        // xdg-open can't select a file, so open the folder holding it
+        let path = Path::new(target);
+        let target = match path.parent() {
+            Some(parent) if select && !path.is_dir() => parent.as_os_str(),
+            _ => path.as_os_str(),
+        };
+        let mut c = Command::new("xdg-open");
+        c.arg(target);
+        c
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|mut child| {
+            // Reap the launcher once it hands off to the browser or file manager
+            std::thread::spawn(move || child.wait());
+        })
+        .map_err(|e| format!("Couldn't open {}: {}", target, e))
+}
+
+/// Whether a script asked for the user's attention since the last call
+pub fn take_attention_request() -> bool {
+    ATTENTION.swap(false, Ordering::Relaxed)
+}
+
+/// The script-facing `OS` singleton
+pub fn new_os() -> ObjectRef {
+    let obj = ObjectInner::base("OS");
+    // get_name() -> "Linux", "macOS", "Windows", ...
+    ObjectInner::insert_method(
+        &obj,
+        "get_name",
+        Arc::new(|_, _| Ok(Value::String(name().into()))),
+    );
+    // get_env(var) -> String, or null when unset
+    ObjectInner::insert_method(
+        &obj,
+        "get_env",
+        Arc::new(|_, args| {
+            let Some(Value::String(var)) = args.first() else {
+                return Err("get_env expects a variable name".into());
+            };
+            require(Permission::Env, "get_env")?;
+            Ok(std::env::var(var).map_or(Value::Null, Value::String))
+        }),
+    );
+    // open_url(url), shell_show_in_file_manager(path)
+    ObjectInner::insert_method(
+        &obj,
+        "open_url",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::String(url)) => open_url(url).map(|_| Value::Null),
+            _ => Err("open_url expects a URL".into()),
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "shell_show_in_file_manager",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::String(path)) => show_in_file_manager(path).map(|_| Value::Null),
+            _ => Err("shell_show_in_file_manager expects a path".into()),
+        }),
+    );
+    // request_attention(): flash the window or taskbar entry
+    ObjectInner::insert_method(
+        &obj,
+        "request_attention",
+        Arc::new(|_, _| {
+            require(Permission::Attention, "request_attention")?;
+            ATTENTION.store(true, Ordering::Relaxed);
+            Ok(Value::Null)
+        }),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_calls_need_manifest_permissions() {
+        let os = new_os();
+        let call = |method: &str, args: &[Value]| os.call_method(method, args);
+        let path = Value::String("PATH".into());
+        assert_eq!(call("get_name", &[]).unwrap(), Value::String(name().into()));
+
+        set_permissions(Vec::new());
+        let err = call("get_env", std::slice::from_ref(&path)).unwrap_err();
+        assert!(err.contains("permissions: env"));
+        assert!(call("request_attention", &[]).is_err());
+        assert!(!take_attention_request());
+
+        set_permissions(vec![
+            Permission::Env,
+            Permission::Attention,
+            Permission::OpenUrl,
+        ]);
+        assert_eq!(
+            call("get_env", &[path]).unwrap(),
+            std::env::var("PATH").map_or(Value::Null, Value::String)
+        );
+        let unset = Value::String("GEM_SURELY_UNSET_VARIABLE".into());
+        assert_eq!(call("get_env", &[unset]).unwrap(), Value::Null);
+        call("request_attention", &[]).unwrap();
+        assert!(take_attention_request());
+        assert!(!take_attention_request());
+        // Links are checked before anything is launched
+        assert!(
+            open_url("file:///etc/passwd")
+                .unwrap_err()
+                .contains("only http")
+        );
+        assert!(
+            show_in_file_manager("/tmp")
+                .unwrap_err()
+                .contains("file_manager")
+        );
+        set_permissions(Vec::new());
+    }
+}
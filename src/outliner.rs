@@ -0,0 +1,259 @@
+//! Scene outliner panel for the preview.
+//! Lists every node as `name : type` in a collapsible tree. The panel itself is
+//! a generated scene of PanelGem/LabelGem nodes drawn through the regular draw
+//! list, so it exercises the same controls games use.
+
+use std::collections::HashSet;
+
+use crate::ast::{GemDecl, GemFile, Property, Value};
+use crate::commands::find_node;
+use crate::draw_list::{self, DrawList, NodePath};
+use crate::renderer::GemRenderer;
+
+const PANEL_W: f32 = 260.0;
+const PADDING: f32 = 8.0;
+const ROW_H: f32 = 18.0;
+const INDENT: f32 = 14.0;
+const FONT_SIZE: i64 = 14;
+/// Width of the `+`/`-` marker that toggles a row
+const MARKER_W: f32 = 12.0;
+
+const PANEL_COLOR: [u8; 4] = [24, 26, 34, 230];
+const SELECTED_COLOR: [u8; 4] = [70, 90, 150, 255];
+const TEXT_COLOR: [u8; 4] = [220, 220, 230, 255];
+
+struct Row {
+    node: NodePath,
+    depth: usize,
+    has_children: bool,
+}
+
+/// Result of a click inside the panel
+#[derive(Debug, PartialEq)]
+pub enum OutlinerClick {
+    Select(NodePath),
+    Toggled,
+    /// Inside the panel but not on a row
+    Panel,
+}
+
+pub struct Outliner {
+    pub visible: bool,
+    collapsed: HashSet<NodePath>,
+    rows: Vec<Row>,
+    selected: Option<NodePath>,
+    viewport_w: f32,
+    origin: (f32, f32),
+    height: f32,
+    panel: DrawList,
+}
+
+fn prop(key: &str, value: Value) -> Property {
+    Property {
+        key: key.to_string(),
+        value,
+    }
+}
+
+fn pair(x: f32, y: f32) -> Value {
+    Value::Tuple(vec![
+        Value::Integer(x.round() as i64),
+        Value::Integer(y.round() as i64),
+    ])
+}
+
+fn node(name: String, gem_type: &str, properties: Vec<Property>) -> GemDecl {
+    GemDecl {
+        name,
+        unique: false,
+        gem_type: gem_type.to_string(),
+        properties,
+        meta: Vec::new(),
+        children: Vec::new(),
+    }
+}
+
+impl Outliner {
+    pub fn new() -> Self {
+        Self {
+            visible: true,
+            collapsed: HashSet::new(),
+            rows: Vec::new(),
+            selected: None,
+            viewport_w: 0.0,
+            origin: (0.0, 0.0),
+            height: 0.0,
+            panel: DrawList::default(),
+        }
+    }
+
+    fn collect_rows(&mut self, decl: &GemDecl, path: &mut NodePath) {
+        self.rows.push(Row {
+            node: path.clone(),
+            depth: path.len(),
+            has_children: !decl.children.is_empty(),
+        });
+        if self.collapsed.contains(path) {
+            return;
+        }
+        for (i, child) in decl.children.iter().enumerate() {
+            path.push(i);
+            self.collect_rows(child, path);
+            path.pop();
+        }
+    }
+
+    /// Rebuild the panel for `scene`, highlighting `selected`, docked to the
+    /// right edge of a `viewport_w` pixel wide window
+    pub fn refresh(&mut self, scene: &GemFile, selected: Option<&NodePath>, viewport_w: f32) {
+        self.rows.clear();
+        self.collect_rows(&scene.root, &mut NodePath::new());
+        self.selected = selected.cloned();
+        self.viewport_w = viewport_w;
+        self.origin = ((viewport_w - PANEL_W).max(0.0), 0.0);
+        self.height = self.rows.len() as f32 * ROW_H + PADDING * 2.0;
+
+        let (x0, y0) = self.origin;
+        let mut root = node(
+            "Outliner".to_string(),
+            "Gem",
+            vec![prop("theme_font_color", Value::Color(TEXT_COLOR))],
+        );
+        root.children.push(node(
+            "Background".to_string(),
+            "PanelGem",
+            vec![
+                prop("position", pair(x0, y0)),
+                prop("size", pair(PANEL_W, self.height)),
+                prop("color", Value::Color(PANEL_COLOR)),
+            ],
+        ));
+        for (i, row) in self.rows.iter().enumerate() {
+            let y = y0 + PADDING + i as f32 * ROW_H;
+            if self.selected.as_ref() == Some(&row.node) {
+                root.children.push(node(
+                    "Selection".to_string(),
+                    "PanelGem",
+                    vec![
+                        prop("position", pair(x0, y)),
+                        prop("size", pair(PANEL_W, ROW_H)),
+                        prop("color", Value::Color(SELECTED_COLOR)),
+                    ],
+                ));
+            }
+            let Some(decl) = find_node(&scene.root, &row.node) else {
+                continue;
+            };
+            let marker = match (row.has_children, self.collapsed.contains(&row.node)) {
+                (false, _) => " ",
+                (true, true) => "+",
+                (true, false) => "-",
+            };
+            let x = x0 + PADDING + row.depth as f32 * INDENT;
+            root.children.push(node(
+                format!("Row{}", i),
+                "LabelGem",
+                vec![
+                    prop("position", pair(x, y + 2.0)),
+                    prop(
+                        "text",
+                        Value::String(format!("{} {} : {}", marker, decl.name, decl.gem_type)),
+                    ),
+                    prop("font_size", Value::Integer(FONT_SIZE)),
+                    prop("max_lines", Value::Integer(1)),
+                    prop("size", pair(x0 + PANEL_W - x - PADDING, ROW_H)),
+                    prop("autowrap", Value::Bool(true)),
+                ],
+            ));
+        }
+        self.panel = draw_list::build(&GemFile {
+            root,
+            errors: Vec::new(),
+        });
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.visible
+            && x >= self.origin.0
+            && x < self.origin.0 + PANEL_W
+            && y >= self.origin.1
+            && y < self.origin.1 + self.height
+    }
+
+    /// Handle a click; `None` when it landed outside the panel
+    pub fn click(&mut self, scene: &GemFile, x: f32, y: f32) -> Option<OutlinerClick> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let index = ((y - self.origin.1 - PADDING) / ROW_H).floor();
+        let Some(row) = (index >= 0.0)
+            .then(|| self.rows.get(index as usize))
+            .flatten()
+        else {
+            return Some(OutlinerClick::Panel);
+        };
+        let marker_x = self.origin.0 + PADDING + row.depth as f32 * INDENT;
+        if row.has_children && x >= marker_x && x < marker_x + MARKER_W {
+            let node = row.node.clone();
+            if !self.collapsed.remove(&node) {
+                self.collapsed.insert(node);
+            }
+            let selected = self.selected.clone();
+            self.refresh(scene, selected.as_ref(), self.viewport_w);
+            return Some(OutlinerClick::Toggled);
+        }
+        Some(OutlinerClick::Select(row.node.clone()))
+    }
+
+    pub fn draw(&self, renderer: &mut GemRenderer) {
+        if self.visible {
+            renderer.render_draw_list(&self.panel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn rows_select_and_collapse() {
+        let src = r#"Root: Gem {
+            Menu: ContainerGem {
+                Title: LabelGem { text: "Hi" }
+                Start: ButtonGem { text: "Go" }
+            }
+            Footer: LabelGem { text: "Bye" }
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let scene = Parser::new(tokens).parse_scene().unwrap();
+        let mut outliner = Outliner::new();
+        outliner.refresh(&scene, Some(&vec![0, 1]), 800.0);
+
+        // Root, Menu, Title, Start, Footer; background + selection highlight
+        assert_eq!(outliner.panel.texts.len(), 5);
+        assert_eq!(outliner.panel.quads.len(), 2);
+
+        let row_y = |i: usize| PADDING + i as f32 * ROW_H + 1.0;
+        let x0 = 800.0 - PANEL_W;
+        assert_eq!(outliner.click(&scene, 10.0, row_y(0)), None);
+        assert_eq!(
+            outliner.click(&scene, x0 + 100.0, row_y(4)),
+            Some(OutlinerClick::Select(vec![1]))
+        );
+
+        // Menu's marker sits one indent in
+        let marker = x0 + PADDING + INDENT + 1.0;
+        assert_eq!(
+            outliner.click(&scene, marker, row_y(1)),
+            Some(OutlinerClick::Toggled)
+        );
+        assert_eq!(outliner.panel.texts.len(), 3);
+        assert_eq!(
+            outliner.click(&scene, x0 + 100.0, row_y(2)),
+            Some(OutlinerClick::Select(vec![1]))
+        );
+    }
+}
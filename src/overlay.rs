@@ -0,0 +1,127 @@
+//! On-screen messages for the preview window.
+//! Toasts report reload results and errors without interrupting the scene;
+//! the error overlay covers it after a crash until it is reloaded.
+
+use std::time::{Duration, Instant};
+
+use crate::bitmap_font::BitmapFont;
+use crate::crash::Crash;
+use crate::font::FontRegistry;
+use crate::renderer::GemRenderer;
+use crate::text::{self, LayoutParams, Span, TextLayout, TextStyle};
+
+const TOAST_MARGIN: f32 = 12.0;
+const TOAST_PADDING: f32 = 8.0;
+const TOAST_MAX_WIDTH: f32 = 560.0;
+const ERROR_MAX_WIDTH: f32 = 640.0;
+const ERROR_HINT: &str = "F5 reloads the scene, Esc dismisses";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+/// A message pinned to the bottom-left corner until it expires
+pub struct Toast {
+    pub kind: ToastKind,
+    layout: TextLayout,
+    expires: Instant,
+}
+
+impl Toast {
+    pub fn new(kind: ToastKind, message: &str) -> Self {
+        let lifetime = match kind {
+            ToastKind::Info => Duration::from_secs(2),
+            ToastKind::Error => Duration::from_secs(8),
+        };
+        let spans = [Span::Text {
+            text: message.to_string(),
+            style: TextStyle::default(),
+        }];
+        let params = LayoutParams {
+            font_size: 16.0,
+            max_width: Some(TOAST_MAX_WIDTH),
+            max_lines: Some(6),
+            ..Default::default()
+        };
+        Self {
+            kind,
+            layout: text::layout(&spans, &params, &BitmapFont),
+            expires: Instant::now() + lifetime,
+        }
+    }
+
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.expires
+    }
+
+    /// Draw over everything else; `viewport_h` is the framebuffer height in pixels
+    pub fn draw(&self, renderer: &mut GemRenderer, viewport_h: f32, fonts: &FontRegistry) {
+        let w = self.layout.width + TOAST_PADDING * 2.0;
+        let h = self.layout.height + TOAST_PADDING * 2.0;
+        let x = TOAST_MARGIN;
+        let y = viewport_h - TOAST_MARGIN - h;
+        let color = match self.kind {
+            ToastKind::Info => [0.1, 0.35, 0.2, 0.9],
+            ToastKind::Error => [0.55, 0.1, 0.1, 0.9],
+        };
+        renderer.render_px_quad(x, y, w, h, color);
+        renderer.render_text((x + TOAST_PADDING, y + TOAST_PADDING), &self.layout, fonts);
+    }
+}
+
+/// A crash report drawn over the whole window; the scene stops updating
+/// while it is up
+pub struct ErrorOverlay {
+    title: TextLayout,
+    body: TextLayout,
+}
+
+impl ErrorOverlay {
+    pub fn new(crash: &Crash) -> Self {
+        let mut body = crash.message.clone();
+        if let Some(node) = &crash.node {
+            body.push_str(&format!("\nin {}", node));
+        }
+        if let Some(location) = &crash.location {
+            body.push_str(&format!("\nat {}", location));
+        }
+        body.push_str(&format!("\n\n{}", ERROR_HINT));
+        let layout = |text: &str, font_size: f32| {
+            let spans = [Span::Text {
+                text: text.to_string(),
+                style: TextStyle::default(),
+            }];
+            let params = LayoutParams {
+                font_size,
+                max_width: Some(ERROR_MAX_WIDTH),
+                max_lines: Some(16),
+                ..Default::default()
+            };
+            text::layout(&spans, &params, &BitmapFont)
+        };
+        Self {
+            title: layout(&crash.title, 24.0),
+            body: layout(&body, 16.0),
+        }
+    }
+
+    /// Dim the scene and center the report in a `viewport` (pixels)
+    pub fn draw(&self, renderer: &mut GemRenderer, viewport: (f32, f32), fonts: &FontRegistry) {
+        renderer.render_px_quad(0.0, 0.0, viewport.0, viewport.1, [0.0, 0.0, 0.0, 0.6]);
+        let w = self.title.width.max(self.body.width) + TOAST_PADDING * 2.0;
+        let title_h = self.title.height + TOAST_PADDING * 2.0;
+        let h = title_h + self.body.height + TOAST_PADDING * 2.0;
+        let x = ((viewport.0 - w) * 0.5).max(TOAST_MARGIN);
+        let y = ((viewport.1 - h) * 0.5).max(TOAST_MARGIN);
+        renderer.render_px_quad(x, y, w, h, [0.12, 0.12, 0.14, 0.95]);
+        renderer.render_px_quad(x, y, w, title_h, [0.55, 0.1, 0.1, 1.0]);
+        renderer.render_text((x + TOAST_PADDING, y + TOAST_PADDING), &self.title, fonts);
+        renderer.render_text(
+            (x + TOAST_PADDING, y + title_h + TOAST_PADDING),
+            &self.body,
+            fonts,
+        );
+    }
+}
@@ -1,9 +1,23 @@
+use std::ops::Range;
+
 use crate::ast::*;
+use crate::defines;
+use crate::lexer::Lexer;
+use crate::size_expr::{Axis, Op as SizeOp};
 use crate::token::Token;
 
+/// Deepest nesting of tuples and structs inside one property value
+const MAX_VALUE_DEPTH: usize = 32;
+
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
+    /// Tuples and structs currently open in `parse_value`
+    value_depth: usize,
+    /// Child indices from the root to the node being parsed
+    node_path: Vec<usize>,
+    /// Malformed properties skipped so far
+    recovered: Vec<PropertyError>,
 }
 
 #[derive(Debug)]
@@ -24,6 +38,9 @@ impl Parser {
         Self {
             tokens,
             position: 0,
+            value_depth: 0,
+            node_path: Vec::new(),
+            recovered: Vec::new(),
         }
     }
 
@@ -63,20 +80,133 @@ impl Parser {
         }
     }
 
-    fn is_lowercase_ident(&self, token: &Token) -> bool {
+    /// Starts with a letter that has no case (CJK, for one), so it can name
+    /// either a node or a property
+    fn is_caseless_ident(&self, token: &Token) -> bool {
         match token {
             Token::Ident(name) => name
                 .chars()
                 .next()
-                .map_or(false, |c| c.is_lowercase() || c == '_'),
+                .is_some_and(|c| !c.is_uppercase() && !c.is_lowercase() && c != '_'),
             _ => false,
         }
     }
 
-    /// Parse a scene file: expect one root GemDecl
+    /// A caseless name opening a child in full: `名前: Type {`
+    fn is_caseless_child(&self) -> bool {
+        self.current().is_some_and(|t| self.is_caseless_ident(t))
+            && self.peek(1) == Some(&Token::Colon)
+            && matches!(self.peek(2), Some(Token::Ident(_)))
+            && self.peek(3) == Some(&Token::LBrace)
+    }
+
+    /// `meta` `:` key `:`, the start of a metadata entry rather than a
+    /// property named `meta`
+    fn is_meta_entry(&self) -> bool {
+        matches!(self.current(), Some(Token::Ident(m)) if m == "meta")
+            && self.peek(1) == Some(&Token::Colon)
+            && matches!(self.peek(2), Some(Token::Ident(_)))
+            && self.peek(3) == Some(&Token::Colon)
+    }
+
+    /// An identifier's name, or the word a keyword was lexed from, for places
+    /// only a name can be: a property key before `:`, a property after `.`
+    fn name_of(token: Option<Token>) -> Option<String> {
+        match token? {
+            Token::Ident(name) => Some(name),
+            token => token.keyword().map(str::to_string),
+        }
+    }
+
+    /// "Expected `what`", explaining the conflict when a reserved word was
+    /// `found` instead of a name
+    fn name_error(what: &str, found: Option<&Token>) -> ParseError {
+        let message = match found.and_then(Token::keyword) {
+            Some(word) => format!(
+                "Expected {}, found '{}', a reserved word in logic files; rename it (e.g. '{}_')",
+                what, word, word
+            ),
+            None => format!("Expected {}", what),
+        };
+        ParseError { message }
+    }
+
+    /// Starts with a lowercase letter, `_` or a letter without case
+    fn is_lowercase_ident(&self, token: &Token) -> bool {
+        match token {
+            Token::Ident(name) => name.chars().next().map_or(false, |c| !c.is_uppercase()),
+            _ => false,
+        }
+    }
+
+    /// Parse a scene file: expect one root GemDecl. A malformed property
+    /// fails the parse.
     pub fn parse_scene(&mut self) -> Result<GemFile, ParseError> {
+        self.unreserve_keywords();
+        let root = self.parse_gem_decl();
+        // Anything recovered from came before whatever stopped the parse
+        if let Some(error) = self.recovered.drain(..).next() {
+            return Err(ParseError {
+                message: error.message,
+            });
+        }
+        Ok(GemFile {
+            root: root?,
+            errors: Vec::new(),
+        })
+    }
+
+    /// Parse a scene file, skipping malformed properties: each is listed in
+    /// the file's `errors` and the rest of its node is kept. Errors in the
+    /// structure (a child's header, unbalanced braces) still fail the parse.
+    pub fn parse_scene_recovering(&mut self) -> Result<GemFile, ParseError> {
+        self.unreserve_keywords();
         let root = self.parse_gem_decl()?;
-        Ok(GemFile { root })
+        Ok(GemFile {
+            root,
+            errors: std::mem::take(&mut self.recovered),
+        })
+    }
+
+    /// Scenes have no keywords: `on: true` is a property like any other
+    fn unreserve_keywords(&mut self) {
+        // Along with the `;` the lexer puts after a `return` ending its line
+        let mut after_return = false;
+        self.tokens.retain(|token| {
+            let keep = !(after_return && *token == Token::Semi);
+            after_return = *token == Token::Return;
+            keep
+        });
+        for token in &mut self.tokens {
+            if let Some(word) = token.keyword() {
+                *token = Token::Ident(word.to_string());
+            }
+        }
+    }
+
+    /// Record `error` for the property starting at token `start` and skip to
+    /// the next entry of the node: a `key:`, a child, or its closing `}`.
+    /// Tokens carry no lines, so bracketed values are stepped over whole.
+    fn recover(&mut self, start: usize, error: ParseError) {
+        self.recovered.push(PropertyError {
+            node: self.node_path.clone(),
+            message: error.message,
+        });
+        if self.position == start {
+            self.advance();
+        }
+        let mut depth = 0usize;
+        while let Some(token) = self.current() {
+            match token {
+                Token::LParen | Token::LBrace => depth += 1,
+                Token::RParen | Token::RBrace if depth > 0 => depth -= 1,
+                Token::RBrace => return,
+                Token::Percent if depth == 0 => return,
+                Token::Ident(_) if depth == 0 && self.peek(1) == Some(&Token::Colon) => return,
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
     /// Parse GemName: GemType { ... }
@@ -86,8 +216,17 @@ impl Parser {
             self.advance();
         }
 
+        let unique = self.current() == Some(&Token::Percent);
+        if unique {
+            self.advance();
+        }
         let name = match self.advance() {
-            Some(Token::Ident(n)) if self.is_uppercase_ident(&Token::Ident(n.clone())) => n,
+            Some(Token::Ident(n))
+                if self.is_uppercase_ident(&Token::Ident(n.clone()))
+                    || self.is_caseless_ident(&Token::Ident(n.clone())) =>
+            {
+                n
+            }
             _ => {
                 return Err(ParseError {
                     message: "Expected Gem name (Uppercase identifier)".to_string(),
@@ -109,6 +248,7 @@ impl Parser {
         self.expect(Token::LBrace)?;
 
         let mut properties = Vec::new();
+        let mut meta = Vec::new();
         let mut children = Vec::new();
 
         while let Some(token) = self.current() {
@@ -116,26 +256,54 @@ impl Parser {
                 break;
             }
 
-            // Check if it's a child Gem (Uppercase) or a property (lowercase)
-            if self.is_uppercase_ident(token) {
-                children.push(self.parse_gem_decl()?);
+            // Check if it's a child Gem (Uppercase or %Unique) or a property (lowercase)
+            let start = self.position;
+            if self.is_uppercase_ident(token)
+                || token == &Token::Percent
+                || self.is_caseless_child()
+            {
+                self.node_path.push(children.len());
+                let child = self.parse_gem_decl();
+                self.node_path.pop();
+                children.push(child?);
+            } else if self.is_meta_entry() {
+                // meta:key: value
+                self.advance();
+                self.advance();
+                match self.parse_property() {
+                    Ok(entry) if meta.iter().any(|m: &Property| m.key == entry.key) => {
+                        self.recovered.push(PropertyError {
+                            node: self.node_path.clone(),
+                            message: format!("Duplicate metadata 'meta:{}'", entry.key),
+                        });
+                    }
+                    Ok(entry) => meta.push(entry),
+                    Err(e) => self.recover(start, e),
+                }
             } else if self.is_lowercase_ident(token) {
-                properties.push(self.parse_property()?);
+                match self.parse_property() {
+                    Ok(property) => properties.push(property),
+                    Err(e) => self.recover(start, e),
+                }
             } else if token == &Token::Hash {
                 // Standalone directive (e.g., link or resource in older style)
                 // For now, treat as a special property "link"
-                let directive = self.parse_directive()?;
-                properties.push(Property {
-                    key: "link".to_string(),
-                    value: Value::Directive(directive),
-                });
-            } else if let Token::DocComment(_) = token {
-                // skip doc comments inside blocks
+                match self.parse_directive() {
+                    Ok(directive) => properties.push(Property {
+                        key: "link".to_string(),
+                        value: Value::Directive(directive),
+                    }),
+                    Err(e) => self.recover(start, e),
+                }
+            } else if let Token::DocComment(_) | Token::Comma = token {
+                // skip doc comments inside blocks, and commas between
+                // entries: lines need no separator, but may have one
                 self.advance();
             } else {
-                return Err(ParseError {
+                let error = ParseError {
                     message: format!("Unexpected token in Gem body: {:?}", token),
-                });
+                };
+                self.recover(start, error);
             }
         }
 
@@ -143,20 +311,19 @@ impl Parser {
 
         Ok(GemDecl {
             name,
+            unique,
             gem_type,
             properties,
+            meta,
             children,
         })
     }
 
     fn parse_property(&mut self) -> Result<Property, ParseError> {
-        let key = match self.advance() {
-            Some(Token::Ident(k)) => k,
-            _ => {
-                return Err(ParseError {
-                    message: "Expected property key".to_string(),
-                });
-            }
+        let Some(key) = Self::name_of(self.advance()) else {
+            return Err(ParseError {
+                message: "Expected property key".to_string(),
+            });
         };
 
         self.expect(Token::Colon)?;
@@ -167,7 +334,29 @@ impl Parser {
     }
 
     fn parse_value(&mut self) -> Result<Value, ParseError> {
+        if self.at_size_expr() {
+            return self.parse_size_value();
+        }
         match self.current() {
+            Some(Token::LParen) | Some(Token::LBracket) | Some(Token::LBrace) => {
+                if self.value_depth == MAX_VALUE_DEPTH {
+                    return Err(ParseError {
+                        message: format!("Value nested deeper than {} levels", MAX_VALUE_DEPTH),
+                    });
+                }
+                self.value_depth += 1;
+                let value = match self.current() {
+                    Some(Token::LParen) => self.parse_group_or_tuple(),
+                    Some(Token::LBracket) => self.parse_array(),
+                    // A quoted first key makes a map, and so does `{}`
+                    _ if matches!(self.peek(1), Some(Token::String(_) | Token::RBrace)) => {
+                        self.parse_map()
+                    }
+                    _ => self.parse_struct(),
+                };
+                self.value_depth -= 1;
+                value
+            }
             Some(Token::Integer(_)) => {
                 if let Some(Token::Integer(i)) = self.advance() {
                     Ok(Value::Integer(i))
@@ -194,27 +383,19 @@ impl Parser {
                     unreachable!()
                 }
             }
-            Some(Token::LParen) => {
+            Some(Token::Color(c)) => {
+                let c = *c;
                 self.advance();
-                let mut elements = Vec::new();
-                loop {
-                    if let Some(Token::RParen) = self.current() {
-                        break;
-                    }
-                    elements.push(self.parse_value()?);
-                    if let Some(Token::Comma) = self.current() {
-                        self.advance();
-                    } else {
-                        break;
-                    }
-                }
-                self.expect(Token::RParen)?;
-                Ok(Value::Tuple(elements))
+                Ok(Value::Color(c))
             }
             Some(Token::Hash) => {
                 let directive = self.parse_directive()?;
                 Ok(Value::Directive(directive))
             }
+            Some(Token::Ident(id)) if id == "Curve" && self.peek(1) == Some(&Token::LParen) => {
+                self.advance();
+                self.parse_curve()
+            }
             Some(Token::Ident(_)) => {
                 if let Some(Token::Ident(id)) = self.advance() {
                     Ok(Value::Ident(id))
@@ -228,14 +409,325 @@ impl Parser {
         }
     }
 
-    fn parse_directive(&mut self) -> Result<Vec<String>, ParseError> {
+    /// A value that starts `parent.`, `viewport.` or `-`, or a number or
+    /// parenthesized group followed by an operator
+    fn at_size_expr(&self) -> bool {
+        let is_op = |t: Option<&Token>| {
+            matches!(
+                t,
+                Some(Token::Plus | Token::Minus | Token::Multiply | Token::Divide)
+            )
+        };
+        match self.current() {
+            Some(Token::Ident(id)) => {
+                (id == "parent" || id == "viewport") && self.peek(1) == Some(&Token::Dot)
+            }
+            Some(Token::Minus) => true,
+            Some(Token::Integer(_) | Token::Float(_)) => is_op(self.peek(1)),
+            Some(Token::LParen) => {
+                let mut depth = 0;
+                for (i, token) in self.tokens[self.position..].iter().enumerate() {
+                    match token {
+                        Token::LParen => depth += 1,
+                        Token::RParen if depth == 1 => return is_op(self.peek(i + 1)),
+                        Token::RParen => depth -= 1,
+                        _ => {}
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// A size expression; one without `parent` or `viewport` folds to a
+    /// number, an integer when it is made of integers and divides evenly
+    fn parse_size_value(&mut self) -> Result<Value, ParseError> {
+        let expr = self.parse_size_sum()?;
+        Ok(match (expr.integer(), expr.constant()) {
+            (Some(i), _) => Value::Integer(i),
+            (None, Some(n)) => Value::Number(n),
+            (None, None) => Value::SizeExpr(expr),
+        })
+    }
+
+    fn parse_size_sum(&mut self) -> Result<SizeExpr, ParseError> {
+        let mut left = self.parse_size_product()?;
+        loop {
+            let op = match self.current() {
+                Some(Token::Plus) => SizeOp::Add,
+                Some(Token::Minus) => SizeOp::Sub,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_size_product()?;
+            left = SizeExpr::Op(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_size_product(&mut self) -> Result<SizeExpr, ParseError> {
+        let mut left = self.parse_size_atom()?;
+        loop {
+            let op = match self.current() {
+                Some(Token::Multiply) => SizeOp::Mul,
+                Some(Token::Divide) => SizeOp::Div,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_size_atom()?;
+            left = SizeExpr::Op(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_size_atom(&mut self) -> Result<SizeExpr, ParseError> {
+        match self.advance() {
+            Some(Token::Integer(i)) => Ok(SizeExpr::Integer(i)),
+            Some(Token::Float(f)) => Ok(SizeExpr::Number(f)),
+            Some(Token::Minus) => match self.current() {
+                Some(Token::Integer(_) | Token::Float(_) | Token::Minus | Token::LParen) => {
+                    Ok(SizeExpr::Neg(Box::new(self.parse_size_atom()?)))
+                }
+                Some(Token::Ident(id)) if id == "parent" || id == "viewport" => {
+                    Ok(SizeExpr::Neg(Box::new(self.parse_size_atom()?)))
+                }
+                token => Err(ParseError {
+                    message: format!("Expected a number after unary '-', got {:?}", token),
+                }),
+            },
+            Some(Token::LParen) => {
+                if self.value_depth == MAX_VALUE_DEPTH {
+                    return Err(ParseError {
+                        message: format!("Value nested deeper than {} levels", MAX_VALUE_DEPTH),
+                    });
+                }
+                self.value_depth += 1;
+                let expr = self.parse_size_sum()?;
+                self.value_depth -= 1;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(frame)) if frame == "parent" || frame == "viewport" => {
+                self.expect(Token::Dot)?;
+                let axis = match self.advance() {
+                    Some(Token::Ident(axis)) => Axis::parse(&axis),
+                    _ => None,
+                };
+                match (frame.as_str(), axis) {
+                    ("parent", Some(axis)) => Ok(SizeExpr::Parent(axis)),
+                    (_, Some(axis)) => Ok(SizeExpr::Viewport(axis)),
+                    _ => Err(ParseError {
+                        message: format!("Expected {}.width or {}.height", frame, frame),
+                    }),
+                }
+            }
+            token => Err(ParseError {
+                message: format!(
+                    "Expected a number, parent.width/height or viewport.width/height in a size expression, got {:?}",
+                    token
+                ),
+            }),
+        }
+    }
+
+    /// `(a, b, ...)`; a trailing comma is allowed
+    fn parse_tuple(&mut self) -> Result<Value, ParseError> {
+        let elements = self.parse_elements(Token::LParen, Token::RParen, "tuple")?;
+        Ok(Value::Tuple(elements))
+    }
+
+    /// `[a, b, ...]`; a trailing comma is allowed
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        let elements = self.parse_elements(Token::LBracket, Token::RBracket, "array")?;
+        Ok(Value::Array(elements))
+    }
+
+    /// Comma-separated values between `open` and `close`
+    fn parse_elements(
+        &mut self,
+        open: Token,
+        close: Token,
+        what: &str,
+    ) -> Result<Vec<Value>, ParseError> {
+        self.expect(open)?;
+        let mut elements = Vec::new();
+        while self.current().is_some() && self.current() != Some(&close) {
+            elements.push(self.parse_value()?);
+            if let Some(Token::Comma) = self.current() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        if self.current().is_none() {
+            return Err(ParseError {
+                message: format!("Unterminated {}, expected {:?}", what, close),
+            });
+        }
+        self.expect(close)?;
+        Ok(elements)
+    }
+
+    /// `(a, b)` and `(a,)` are tuples; `(a)` is just `a`, grouped
+    fn parse_group_or_tuple(&mut self) -> Result<Value, ParseError> {
+        let value = self.parse_tuple()?;
+        let trailing_comma = self.tokens[self.position - 2] == Token::Comma;
+        match value {
+            Value::Tuple(mut items) if items.len() == 1 && !trailing_comma => Ok(items.remove(0)),
+            value => Ok(value),
+        }
+    }
+
+    /// `Curve((t, value), (t, value, easing), ...)`, after the `Curve` keyword
+    fn parse_curve(&mut self) -> Result<Value, ParseError> {
+        let Value::Tuple(entries) = self.parse_tuple()? else {
+            unreachable!()
+        };
+        let number = |v: &Value| match v {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Number(n) => Some(*n),
+            _ => None,
+        };
+        let mut points = Vec::new();
+        for entry in &entries {
+            let point = match entry {
+                Value::Tuple(items) if items.len() == 2 || items.len() == 3 => {
+                    let easing = match items.get(2) {
+                        None => Some(Easing::Linear),
+                        Some(Value::Ident(e)) => Easing::parse(e),
+                        Some(_) => None,
+                    };
+                    match (number(&items[0]), number(&items[1]), easing) {
+                        (Some(t), Some(value), Some(easing)) => {
+                            Some(CurvePoint { t, value, easing })
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match point {
+                Some(point) => points.push(point),
+                None => {
+                    return Err(ParseError {
+                        message: format!(
+                            "Invalid curve point {:?}, expected (t, value) or (t, value, easing) with easing one of linear, constant, ease_in, ease_out, ease_in_out",
+                            entry
+                        ),
+                    });
+                }
+            }
+        }
+        Curve::new(points)
+            .map(Value::Curve)
+            .map_err(|message| ParseError { message })
+    }
+
+    /// `{ key: value ... }`, with fields separated by whitespace or commas.
+    /// Keys are lowercase like property keys and may not repeat.
+    fn parse_struct(&mut self) -> Result<Value, ParseError> {
+        self.expect(Token::LBrace)?;
+        let mut fields: Vec<Property> = Vec::new();
+        loop {
+            match self.current() {
+                Some(Token::RBrace) => break,
+                Some(Token::Comma) => {
+                    self.advance();
+                }
+                Some(token) if self.is_lowercase_ident(token) || token.keyword().is_some() => {
+                    let field = self.parse_property()?;
+                    if fields.iter().any(|f| f.key == field.key) {
+                        return Err(ParseError {
+                            message: format!("Duplicate field '{}' in struct value", field.key),
+                        });
+                    }
+                    fields.push(field);
+                }
+                None => {
+                    return Err(ParseError {
+                        message: "Unterminated struct value, expected '}'".to_string(),
+                    });
+                }
+                Some(token) => {
+                    return Err(ParseError {
+                        message: format!("Expected a lowercase field name, got {:?}", token),
+                    });
+                }
+            }
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Value::Struct(fields))
+    }
+
+    /// `{ "key": value ... }`, with entries separated by whitespace or
+    /// commas. Keys are any string but may not repeat.
+    fn parse_map(&mut self) -> Result<Value, ParseError> {
+        self.expect(Token::LBrace)?;
+        let mut entries: Vec<(String, Value)> = Vec::new();
+        loop {
+            match self.advance() {
+                Some(Token::RBrace) => break,
+                Some(Token::Comma) => {}
+                Some(Token::String(key)) => {
+                    if entries.iter().any(|(k, _)| *k == key) {
+                        return Err(ParseError {
+                            message: format!("Duplicate key {:?} in map value", key),
+                        });
+                    }
+                    self.expect(Token::Colon)?;
+                    entries.push((key, self.parse_value()?));
+                }
+                None => {
+                    return Err(ParseError {
+                        message: "Unterminated map value, expected '}'".to_string(),
+                    });
+                }
+                Some(token) => {
+                    return Err(ParseError {
+                        message: format!("Expected a quoted map key, got {:?}", token),
+                    });
+                }
+            }
+        }
+        Ok(Value::Map(entries))
+    }
+
+    /// `#path:segments`, then any `:name(values)` arguments and a
+    /// `?key=value&key=value` query
+    fn parse_directive(&mut self) -> Result<Directive, ParseError> {
         self.expect(Token::Hash)?;
-        let mut segments = Vec::new();
+        let mut directive = Directive::new(Vec::new());
         loop {
             match self.current() {
                 Some(Token::Ident(_)) => {
-                    if let Some(Token::Ident(seg)) = self.advance() {
-                        segments.push(seg);
+                    if let Some(Token::Ident(mut seg)) = self.advance() {
+                        // File names: `player.png` lexes as Ident Dot Ident
+                        while let (Some(Token::Dot), Some(Token::Ident(ext))) =
+                            (self.current(), self.peek(1))
+                        {
+                            seg.push('.');
+                            seg.push_str(ext);
+                            self.advance();
+                            self.advance();
+                        }
+                        if self.current() == Some(&Token::LParen) {
+                            let Value::Tuple(values) = self.parse_tuple()? else {
+                                unreachable!()
+                            };
+                            directive
+                                .args
+                                .push(DirectiveArg::Call { name: seg, values });
+                        } else if directive.args.is_empty() {
+                            directive.segments.push(seg);
+                        } else {
+                            return Err(ParseError {
+                                message: format!(
+                                    "Directive path segment '{}' after an argument",
+                                    seg
+                                ),
+                            });
+                        }
                         if let Some(Token::Colon) = self.current() {
                             self.advance();
                         } else {
@@ -246,12 +738,50 @@ impl Parser {
                 _ => break,
             }
         }
-        if segments.is_empty() {
+        if directive.segments.is_empty() {
             return Err(ParseError {
                 message: "Empty directive".to_string(),
             });
         }
-        Ok(segments)
+        if self.current() == Some(&Token::Question) {
+            loop {
+                self.advance();
+                let Some(Token::Ident(key)) = self.advance() else {
+                    return Err(ParseError {
+                        message: "Expected a query key in directive".to_string(),
+                    });
+                };
+                self.expect(Token::Eq)?;
+                let value = self.parse_value()?;
+                directive.args.push(DirectiveArg::Query { key, value });
+                if self.current() != Some(&Token::Amp) {
+                    break;
+                }
+            }
+        }
+        Ok(directive)
+    }
+
+    /// A directive making up the whole input, as in directive text kept in a string
+    pub fn parse_standalone_directive(&mut self) -> Result<Directive, ParseError> {
+        let directive = self.parse_directive()?;
+        match self.current() {
+            None => Ok(directive),
+            Some(token) => Err(ParseError {
+                message: format!("Unexpected {:?} after directive", token),
+            }),
+        }
+    }
+
+    /// An expression making up the whole input, as in an `#if` condition
+    pub fn parse_standalone_expression(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_expression()?;
+        match self.current() {
+            None => Ok(expr),
+            Some(token) => Err(ParseError {
+                message: format!("Unexpected {:?} after expression", token),
+            }),
+        }
     }
 
     /// Parse a logic file: extend header + events/functions
@@ -282,27 +812,41 @@ impl Parser {
 
         let mut events = Vec::new();
         let mut functions = Vec::new();
+        // Annotations seen since the last function
+        let mut annotations = Vec::new();
 
         while let Some(token) = self.current() {
             match token {
                 Token::DocComment(_) => {
                     self.advance();
                 }
+                Token::At => annotations.push(self.parse_annotation()?),
                 Token::Fn => {
                     self.advance();
                     // Check if it's an event handler (on_ready, on_update, etc.) or a regular function
                     if let Some(Token::Ident(name)) = self.current() {
                         if name.starts_with("on_") {
+                            if let Some(a) = annotations.first() {
+                                return Err(ParseError {
+                                    message: format!(
+                                        "@{} can't annotate event handler {}",
+                                        a.name, name
+                                    ),
+                                });
+                            }
                             // Event handler
                             events.push(self.parse_event_handler()?);
                         } else {
                             // Regular function
-                            functions.push(self.parse_function()?);
+                            let mut function = self.parse_function()?;
+                            function.annotations = std::mem::take(&mut annotations);
+                            functions.push(function);
                         }
                     } else {
-                        return Err(ParseError {
-                            message: "Expected function or event name after 'fn'".to_string(),
-                        });
+                        return Err(Self::name_error(
+                            "function or event name after 'fn'",
+                            self.current(),
+                        ));
                     }
                 }
                 _ => {
@@ -313,6 +857,12 @@ impl Parser {
             }
         }
 
+        if let Some(a) = annotations.first() {
+            return Err(ParseError {
+                message: format!("@{} must be followed by a function", a.name),
+            });
+        }
+
         Ok(LogicFile {
             extend_type,
             doc_comment,
@@ -321,15 +871,43 @@ impl Parser {
         })
     }
 
-    fn parse_event_handler(&mut self) -> Result<Event, ParseError> {
+    /// `@name` or `@name(arg, ...)`; arguments are identifiers or strings
+    fn parse_annotation(&mut self) -> Result<Annotation, ParseError> {
+        self.expect(Token::At)?;
         let name = match self.advance() {
             Some(Token::Ident(n)) => n,
             _ => {
                 return Err(ParseError {
-                    message: "Expected event name".to_string(),
+                    message: "Expected annotation name after '@'".to_string(),
                 });
             }
         };
+        let mut args = Vec::new();
+        if let Some(Token::LParen) = self.current() {
+            self.advance();
+            while self.current() != Some(&Token::RParen) {
+                match self.advance() {
+                    Some(Token::Ident(arg) | Token::String(arg)) => args.push(arg),
+                    other => {
+                        return Err(ParseError {
+                            message: format!("Expected @{} argument, got {:?}", name, other),
+                        });
+                    }
+                }
+                if let Some(Token::Comma) = self.current() {
+                    self.advance();
+                }
+            }
+            self.advance();
+        }
+        Ok(Annotation { name, args })
+    }
+
+    fn parse_event_handler(&mut self) -> Result<Event, ParseError> {
+        let name = match self.advance() {
+            Some(Token::Ident(n)) => n,
+            other => return Err(Self::name_error("event name", other.as_ref())),
+        };
 
         let params = if let Some(Token::LParen) = self.current() {
             self.parse_param_list()?
@@ -345,17 +923,18 @@ impl Parser {
     fn parse_function(&mut self) -> Result<FunctionDecl, ParseError> {
         let name = match self.advance() {
             Some(Token::Ident(n)) => n,
-            _ => {
-                return Err(ParseError {
-                    message: "Expected function name".to_string(),
-                });
-            }
+            other => return Err(Self::name_error("function name", other.as_ref())),
         };
 
         let params = self.parse_param_list()?;
         let body = self.parse_block()?;
 
-        Ok(FunctionDecl { name, params, body })
+        Ok(FunctionDecl {
+            name,
+            params,
+            body,
+            annotations: Vec::new(),
+        })
     }
 
     fn parse_param_list(&mut self) -> Result<Vec<String>, ParseError> {
@@ -367,11 +946,7 @@ impl Parser {
             }
             match self.advance() {
                 Some(Token::Ident(p)) => params.push(p),
-                _ => {
-                    return Err(ParseError {
-                        message: "Expected parameter name".to_string(),
-                    });
-                }
+                other => return Err(Self::name_error("parameter name", other.as_ref())),
             }
             if let Some(Token::Comma) = self.current() {
                 self.advance();
@@ -411,10 +986,23 @@ impl Parser {
                     Ok(Stmt::ExprStmt(expr))
                 }
             }
+            // `spawn(#scene)` instances a scene; `spawn Type { ... }` builds a node
+            Some(Token::Spawn) if self.peek(1) == Some(&Token::LParen) => {
+                let expr = self.parse_expression()?;
+                Ok(Stmt::ExprStmt(expr))
+            }
             Some(Token::Spawn) => {
                 self.advance();
                 self.parse_spawn()
             }
+            Some(Token::If) => self.parse_if(),
+            Some(Token::Else) => Err(ParseError {
+                message: "'else' without an 'if' block before it".to_string(),
+            }),
+            Some(Token::For) => self.parse_for(),
+            Some(Token::Return) => self.parse_return(),
+            Some(Token::Let) => self.parse_let(),
+            Some(Token::Match) => self.parse_match(),
             _ => {
                 let expr = self.parse_expression()?;
                 Ok(Stmt::ExprStmt(expr))
@@ -422,6 +1010,135 @@ impl Parser {
         }
     }
 
+    /// `if condition { ... }`, then `else { ... }` or `else if ...`, which
+    /// becomes an else block holding just that `if`
+    fn parse_if(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::If)?;
+        let condition = self.parse_expression()?;
+        let then_block = self.parse_block()?;
+        let else_block = if let Some(Token::Else) = self.current() {
+            self.advance();
+            Some(match self.current() {
+                Some(Token::If) => Block {
+                    statements: vec![self.parse_if()?],
+                },
+                _ => self.parse_block()?,
+            })
+        } else {
+            None
+        };
+        Ok(Stmt::If {
+            condition,
+            then_block,
+            else_block,
+        })
+    }
+
+    /// `for name in iterable { ... }`
+    fn parse_for(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::For)?;
+        let var = match self.current() {
+            Some(Token::Ident(name)) => name.clone(),
+            found => return Err(Self::name_error("a loop variable name after 'for'", found)),
+        };
+        self.advance();
+        if self.current() != Some(&Token::In) {
+            return Err(ParseError {
+                message: format!("Expected 'in' after 'for {}'", var),
+            });
+        }
+        self.advance();
+        let iterable = self.parse_expression()?;
+        let body = self.parse_block()?;
+        Ok(Stmt::For {
+            var,
+            iterable,
+            body,
+        })
+    }
+
+    /// `let name = value`
+    fn parse_let(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::Let)?;
+        let name = match self.current() {
+            Some(Token::Ident(name)) => name.clone(),
+            found => return Err(Self::name_error("a variable name after 'let'", found)),
+        };
+        self.advance();
+        if self.current() != Some(&Token::Eq) {
+            return Err(ParseError {
+                message: format!("Expected '=' after 'let {}'", name),
+            });
+        }
+        self.advance();
+        let value = self.parse_expression()?;
+        Ok(Stmt::Let { name, value })
+    }
+
+    /// `match value { pattern => { ... } ... }`, arms optionally separated
+    /// by commas
+    fn parse_match(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::Match)?;
+        let value = self.parse_expression()?;
+        self.expect(Token::LBrace)?;
+        let mut arms = Vec::new();
+        while !matches!(self.current(), Some(Token::RBrace) | None) {
+            let pattern = self.parse_pattern()?;
+            if self.current() != Some(&Token::FatArrow) {
+                return Err(ParseError {
+                    message: "Expected '=>' after a match pattern".to_string(),
+                });
+            }
+            self.advance();
+            let body = self.parse_block()?;
+            arms.push(MatchArm { pattern, body });
+            if self.current() == Some(&Token::Comma) {
+                self.advance();
+            }
+        }
+        self.expect(Token::RBrace)?;
+        Ok(Stmt::Match { value, arms })
+    }
+
+    /// A literal, maybe negated, or `_`
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let negate = self.current() == Some(&Token::Minus);
+        if negate {
+            self.advance();
+        }
+        let literal = match (self.advance(), negate) {
+            (Some(Token::Ident(name)), false) if name == "_" => return Ok(Pattern::Wildcard),
+            (Some(Token::Integer(i)), _) => Expr::Integer(if negate { -i } else { i }),
+            (Some(Token::Float(f)), _) => Expr::Number(if negate { -f } else { f }),
+            (Some(Token::String(s)), false) => Expr::String(s),
+            (Some(Token::Bool(b)), false) => Expr::Bool(b),
+            (found, _) => {
+                return Err(ParseError {
+                    message: format!(
+                        "Expected a number, string, bool or '_' as a match pattern, got {:?}",
+                        found
+                    ),
+                });
+            }
+        };
+        Ok(Pattern::Literal(literal))
+    }
+
+    /// `return value`, or a bare `return` at the end of a block or before
+    /// the `;` the lexer ends its line with
+    fn parse_return(&mut self) -> Result<Stmt, ParseError> {
+        self.expect(Token::Return)?;
+        let value = match self.current() {
+            Some(Token::Semi) => {
+                self.advance();
+                None
+            }
+            Some(Token::RBrace) | None => None,
+            _ => Some(self.parse_expression()?),
+        };
+        Ok(Stmt::Return(value))
+    }
+
     fn parse_spawn(&mut self) -> Result<Stmt, ParseError> {
         let gem_type = match self.advance() {
             Some(Token::Ident(t)) => t,
@@ -435,10 +1152,13 @@ impl Parser {
         self.expect(Token::LBrace)?;
         let mut properties = Vec::new();
         while let Some(token) = self.current() {
-            if token == &Token::RBrace {
-                break;
+            match token {
+                Token::RBrace => break,
+                Token::Comma => {
+                    self.advance();
+                }
+                _ => properties.push(self.parse_property()?),
             }
-            properties.push(self.parse_property()?);
         }
         self.expect(Token::RBrace)?;
 
@@ -449,7 +1169,16 @@ impl Parser {
     }
 
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
-        self.parse_logical_or()
+        let start = self.parse_logical_or()?;
+        if let Some(Token::DotDot) = self.current() {
+            self.advance();
+            let end = self.parse_logical_or()?;
+            return Ok(Expr::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+            });
+        }
+        Ok(start)
     }
 
     fn parse_logical_or(&mut self) -> Result<Expr, ParseError> {
@@ -610,27 +1339,37 @@ impl Parser {
                     unreachable!()
                 }
             }
+            // `(a, b)` and `(a,)` are tuples; `(a)` groups
             Some(Token::LParen) => {
                 self.advance();
                 let mut elements = Vec::new();
+                let mut trailing_comma = false;
                 loop {
                     if let Some(Token::RParen) = self.current() {
                         break;
                     }
                     elements.push(self.parse_expression()?);
-                    if let Some(Token::Comma) = self.current() {
+                    trailing_comma = self.current() == Some(&Token::Comma);
+                    if trailing_comma {
                         self.advance();
                     } else {
                         break;
                     }
                 }
                 self.expect(Token::RParen)?;
+                if elements.len() == 1 && !trailing_comma {
+                    return Ok(elements.remove(0));
+                }
                 Ok(Expr::Tuple(elements))
             }
             Some(Token::Hash) => {
                 let directive = self.parse_directive()?;
                 Ok(Expr::Directive(directive))
             }
+            Some(Token::Spawn) if self.peek(1) == Some(&Token::LParen) => {
+                self.advance();
+                self.parse_call_or_property("spawn".to_string())
+            }
             Some(Token::Ident(_)) => {
                 if let Some(Token::Ident(name)) = self.advance() {
                     self.parse_call_or_property(name)
@@ -638,64 +1377,694 @@ impl Parser {
                     unreachable!()
                 }
             }
+            Some(token) if token.keyword().is_some() => {
+                Err(Self::name_error("an expression", Some(token)))
+            }
             _ => Err(ParseError {
                 message: format!("Unexpected token in expression: {:?}", self.current()),
             }),
         }
     }
 
+    /// `name`, `name(args)`, then any chain of `.property` and
+    /// `.method(args)`: `a.b.c`, `a.b.method(x)`, `get_parent().get_name()`
     fn parse_call_or_property(&mut self, name: String) -> Result<Expr, ParseError> {
-        if let Some(Token::LParen) = self.current() {
-            // Function call
+        let mut expr = if let Some(Token::LParen) = self.current() {
+            let args = self.parse_args()?;
+            Expr::Call { name, args }
+        } else {
+            Expr::Ident(name)
+        };
+        while let Some(Token::Dot) = self.current() {
             self.advance();
-            let mut args = Vec::new();
-            loop {
-                if let Some(Token::RParen) = self.current() {
-                    break;
-                }
-                args.push(self.parse_expression()?);
-                if let Some(Token::Comma) = self.current() {
-                    self.advance();
-                } else {
-                    break;
+            let Some(property) = Self::name_of(self.advance()) else {
+                return Err(ParseError {
+                    message: "Expected property name after '.'".to_string(),
+                });
+            };
+            expr = if let Some(Token::LParen) = self.current() {
+                Expr::MethodCall {
+                    object: Box::new(expr),
+                    method: property,
+                    args: self.parse_args()?,
                 }
-            }
-            self.expect(Token::RParen)?;
-            Ok(Expr::Call { name, args })
-        } else if let Some(Token::Dot) = self.current() {
-            // Property access
-            self.advance();
-            let property = match self.advance() {
-                Some(Token::Ident(p)) => p,
-                _ => {
-                    return Err(ParseError {
-                        message: "Expected property name after '.'".to_string(),
-                    });
+            } else {
+                Expr::PropertyAccess {
+                    object: Box::new(expr),
+                    property,
                 }
             };
-            let mut expr = Expr::PropertyAccess {
-                object: Box::new(Expr::Ident(name)),
-                property,
-            };
-            // Chain property accesses
-            while let Some(Token::Dot) = self.current() {
+        }
+        Ok(expr)
+    }
+
+    /// `(a, b, ...)` after a function or method name
+    fn parse_args(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        loop {
+            if let Some(Token::RParen) = self.current() {
+                break;
+            }
+            args.push(self.parse_expression()?);
+            if let Some(Token::Comma) = self.current() {
                 self.advance();
-                let prop = match self.advance() {
-                    Some(Token::Ident(p)) => p,
-                    _ => {
-                        return Err(ParseError {
-                            message: "Expected property name after '.'".to_string(),
-                        });
-                    }
-                };
-                expr = Expr::PropertyAccess {
-                    object: Box::new(expr),
-                    property: prop,
-                };
+            } else {
+                break;
             }
-            Ok(expr)
-        } else {
-            Ok(Expr::Ident(name))
         }
+        self.expect(Token::RParen)?;
+        Ok(args)
+    }
+}
+
+/// Source text replacing the bytes `range` of a previous version
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// Reparse a scene after `edits` to `old_source`, from which `old` was parsed
+/// (recovering, as the preview does). Edit ranges are byte offsets into
+/// `old_source` and don't overlap. When they all fall inside one child of the
+/// root, only that child is lexed and parsed again and the rest of `old` is
+/// reused, so an editor can reparse large files on every keystroke; any other
+/// edit, or a file with `#if` blocks, reparses the whole file.
+pub fn parse_incremental(
+    old: &GemFile,
+    old_source: &str,
+    edits: &[TextEdit],
+) -> Result<GemFile, ParseError> {
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|e| e.range.start);
+    let mut end = 0;
+    for edit in &edits {
+        let range = &edit.range;
+        if range.start < end
+            || range.start > range.end
+            || !old_source.is_char_boundary(range.start)
+            || !old_source.is_char_boundary(range.end)
+        {
+            return Err(ParseError {
+                message: format!("Edit {:?} overlaps another or is out of range", range),
+            });
+        }
+        end = range.end;
+    }
+    if let Some(file) = reparse_child(old, old_source, &edits) {
+        return Ok(file);
+    }
+    let source = apply_edits(old_source, &edits, 0..old_source.len());
+    Parser::new(lex(&source)?).parse_scene_recovering()
+}
+
+/// `old` with the one child of the root that `edits` fall in parsed again,
+/// if they do
+fn reparse_child(old: &GemFile, source: &str, edits: &[TextEdit]) -> Option<GemFile> {
+    let (first, last) = (edits.first()?, edits.last()?);
+    if defines::has_conditionals(source) || edits.iter().any(|e| e.text.contains("#if")) {
+        return None;
+    }
+    let spans = child_spans(source)?;
+    if spans.len() != old.root.children.len() {
+        return None;
+    }
+    let index = spans
+        .iter()
+        .position(|s| s.start <= first.range.start && last.range.end <= s.end)?;
+    let text = apply_edits(source, edits, spans[index].clone());
+    let mut parser = Parser::new(lex(&text).ok()?);
+    let child = parser.parse_scene_recovering().ok()?;
+    // The edit must leave exactly one node there
+    if parser.position != parser.tokens.len() {
+        return None;
+    }
+
+    let mut file = old.clone();
+    file.root.children[index] = child.root;
+    file.errors.retain(|e| e.node.first() != Some(&index));
+    file.errors.extend(child.errors.into_iter().map(|mut e| {
+        e.node.insert(0, index);
+        e
+    }));
+    file.errors.sort_by(|a, b| a.node.cmp(&b.node));
+    Some(file)
+}
+
+/// The text of `span` of `source` with `edits` (sorted, and within it) applied
+pub fn apply_edits(source: &str, edits: &[TextEdit], span: Range<usize>) -> String {
+    let mut out = String::new();
+    let mut at = span.start;
+    for edit in edits {
+        out.push_str(&source[at..edit.range.start]);
+        out.push_str(&edit.text);
+        at = edit.range.end;
+    }
+    out.push_str(&source[at..span.end]);
+    out
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, ParseError> {
+    let tokens = Lexer::new(source.to_string())
+        .tokenize()
+        .map_err(|e| ParseError {
+            message: e.to_string(),
+        })?;
+    defines::apply(&tokens).map_err(|message| ParseError { message })
+}
+
+/// Byte ranges of the root's children, from their name to their closing
+/// brace, found without lexing: strings and comments are skipped and a child
+/// is a `Name: Type {` directly inside the root's braces
+fn child_spans(source: &str) -> Option<Vec<Range<usize>>> {
+    let bytes = source.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80;
+    let skip_spaces = |mut j: usize| {
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        j
+    };
+    let ident_end = |mut j: usize| {
+        while j < bytes.len() && is_ident(bytes[j]) {
+            j += 1;
+        }
+        j
+    };
+    // The `{` of a `%Name: Type {` starting at `i`
+    let decl_brace = |i: usize| {
+        let name = if bytes[i] == b'%' { i + 1 } else { i };
+        let colon = skip_spaces(ident_end(name));
+        if colon == name || bytes.get(colon) != Some(&b':') {
+            return None;
+        }
+        let gem_type = skip_spaces(colon + 1);
+        let brace = skip_spaces(ident_end(gem_type));
+        (brace > gem_type && bytes.get(brace) == Some(&b'{')).then_some(brace)
+    };
+
+    let mut spans = Vec::new();
+    let mut open = None;
+    let mut depth = 0i32;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'"', _) => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            (b'/', Some(b'/')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            (b'/', Some(b'#')) => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'#' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            (b'{', _) => {
+                depth += 1;
+                i += 1;
+            }
+            (b'}', _) => {
+                depth -= 1;
+                if depth < 0 {
+                    return None;
+                }
+                if depth == 1
+                    && let Some(start) = open.take()
+                {
+                    spans.push(start..i + 1);
+                }
+                i += 1;
+            }
+            (b, _) if depth == 1 && (b == b'%' || is_ident(b)) => match decl_brace(i) {
+                Some(brace) => {
+                    open = Some(i);
+                    i = brace;
+                }
+                None => i = ident_end(i + 1),
+            },
+            _ => i += 1,
+        }
+    }
+    (depth == 0).then_some(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(src: &str) -> Result<GemFile, ParseError> {
+        Parser::new(Lexer::new(src.to_string()).tokenize().unwrap()).parse_scene()
+    }
+
+    #[test]
+    fn struct_values_nest_and_reject_bad_input() {
+        let scene =
+            parse(r#"Body: Gem { shape: { type: "circle", radius: 16, hit: { layer: 2 } } }"#)
+                .unwrap();
+        let Value::Struct(fields) = &scene.root.properties[0].value else {
+            panic!("expected a struct value");
+        };
+        assert_eq!(fields[0].key, "type");
+        assert_eq!(fields[1].value, Value::Integer(16));
+        assert!(matches!(&fields[2].value, Value::Struct(inner) if inner.len() == 1));
+
+        let err = |src: &str| parse(src).unwrap_err().message;
+        assert!(err("A: Gem { s: { r: 1 r: 2 } }").contains("Duplicate field 'r'"));
+        assert!(err("A: Gem { s: { r: 1 ").contains("Unterminated struct"));
+        assert!(err("A: Gem { s: (1, 2").contains("Unterminated tuple"));
+        assert!(err("A: Gem { s: { Child: Gem {} } }").contains("lowercase field name"));
+        let deep = format!("A: Gem {{ s: {}1{} }}", "(".repeat(40), ")".repeat(40));
+        assert!(err(&deep).contains("nested deeper"));
+    }
+
+    #[test]
+    fn negative_numbers_in_values() {
+        let scene = parse(
+            "A: Gem { position: (-10, 20) offset: -2.5 path: ((-1, 2), [-3, -4]) fade: Curve((0, -1), (1, 0)) }",
+        )
+        .unwrap();
+        let values: Vec<&Value> = scene.root.properties.iter().map(|p| &p.value).collect();
+        assert_eq!(
+            values[0],
+            &Value::Tuple(vec![Value::Integer(-10), Value::Integer(20)])
+        );
+        assert_eq!(values[1], &Value::Number(-2.5));
+        assert_eq!(
+            values[2],
+            &Value::Tuple(vec![
+                Value::Tuple(vec![Value::Integer(-1), Value::Integer(2)]),
+                Value::Array(vec![Value::Integer(-3), Value::Integer(-4)]),
+            ])
+        );
+        let Value::Curve(fade) = values[3] else {
+            panic!("expected a curve");
+        };
+        assert_eq!(fade.points[0].value, -1.0);
+
+        // Negating twice keeps an integer, and only numbers can be negated
+        let scene = parse("A: Gem { z: --5 }").unwrap();
+        assert_eq!(scene.root.properties[0].value, Value::Integer(5));
+        let err = parse("A: Gem { x: -\"a\" }").unwrap_err().message;
+        assert!(err.contains("after unary '-'"), "{}", err);
+    }
+
+    #[test]
+    fn map_values_take_quoted_keys() {
+        let scene = parse(r#"Loot: Gem { odds: { "Sword of Fire": 0.5, "gold": 2 } }"#).unwrap();
+        assert_eq!(
+            scene.root.properties[0].value,
+            Value::Map(vec![
+                ("Sword of Fire".to_string(), Value::Number(0.5)),
+                ("gold".to_string(), Value::Integer(2)),
+            ])
+        );
+        assert_eq!(
+            parse("A: Gem { style: {} }").unwrap().root.properties[0].value,
+            Value::Map(vec![])
+        );
+
+        let err = |src: &str| parse(src).unwrap_err().message;
+        assert!(err(r#"A: Gem { m: { "a": 1 "a": 2 } }"#).contains("Duplicate key \"a\""));
+        assert!(err(r#"A: Gem { m: { "a": 1 b: 2 } }"#).contains("quoted map key"));
+        assert!(err(r#"A: Gem { m: { "a": 1 "#).contains("Unterminated map"));
+    }
+
+    #[test]
+    fn malformed_properties_are_skipped_with_errors() {
+        let src = r#"Root: Gem {
+            title: "Menu"
+            size: (10, 20
+            color: #ff0000
+            Panel: PanelGem {
+                position: (4, 4)
+                size: : 3
+                meta:tag: 1
+                meta:tag: 2
+                visible: true
+            }
+            scale: 2
+        }"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let scene = Parser::new(tokens.clone())
+            .parse_scene_recovering()
+            .unwrap();
+        let keys = |decl: &GemDecl| {
+            decl.properties
+                .iter()
+                .map(|p| p.key.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&scene.root), ["title", "color", "scale"]);
+        let panel = &scene.root.children[0];
+        assert_eq!(keys(panel), ["position", "visible"]);
+        assert_eq!(panel.meta.len(), 1);
+
+        let nodes: Vec<&[usize]> = scene.errors.iter().map(|e| e.node.as_slice()).collect();
+        assert_eq!(nodes, [&[][..], &[0], &[0]]);
+        assert!(scene.errors[2].message.contains("Duplicate metadata"));
+        // A strict parse fails on the first of them
+        let strict = Parser::new(tokens).parse_scene().unwrap_err();
+        assert_eq!(strict.message, scene.errors[0].message);
+    }
+
+    #[test]
+    fn reserved_words_are_names_in_scenes_and_explained_in_logic() {
+        let scene = parse("A: Gem { on: true spawn: { fn: 1 } mode: extend }").unwrap();
+        let keys: Vec<&str> = scene
+            .root
+            .properties
+            .iter()
+            .map(|p| p.key.as_str())
+            .collect();
+        assert_eq!(keys, ["on", "spawn", "mode"]);
+        assert_eq!(
+            scene.root.properties[2].value,
+            Value::Ident("extend".into())
+        );
+
+        let logic =
+            |src: &str| Parser::new(Lexer::new(src.to_string()).tokenize().unwrap()).parse_logic();
+        // After `.` and before `:` only a name can follow
+        let src = "extend Gem\nfn on_ready() { x = self.on spawn Sprite { on: true } }";
+        assert!(logic(src).is_ok());
+        let err = |src: &str| logic(src).unwrap_err().message;
+        assert!(err("extend Gem\nfn toggle(on) {}").contains("found 'on', a reserved word"));
+        assert!(err("extend Gem\nfn on_ready() { fn = 1 }").contains("rename it (e.g. 'fn_')"));
+    }
+
+    #[test]
+    fn trailing_commas_and_optional_separators() {
+        let scene = parse("A: Gem { size: (1, 2,), tags: {a: 1,}, B: Gem {}, c: 3, }").unwrap();
+        assert_eq!(
+            scene.root.properties[0].value,
+            Value::Tuple(vec![Value::Integer(1), Value::Integer(2)])
+        );
+        assert_eq!(scene.root.properties.len(), 3);
+        assert_eq!(scene.root.children.len(), 1);
+
+        let src = "extend Gem\nfn f(a, b,) { g(a, (b, 1,),) spawn Sprite { x: 1, y: 2, } }";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let f = &logic.functions[0];
+        assert_eq!(f.params, ["a", "b"]);
+        let Stmt::ExprStmt(Expr::Call { args, .. }) = &f.body.statements[0] else {
+            panic!("expected a call");
+        };
+        assert!(matches!(&args[1], Expr::Tuple(items) if items.len() == 2));
+        assert!(
+            matches!(&f.body.statements[1], Stmt::Spawn { properties, .. } if properties.len() == 2)
+        );
+    }
+
+    #[test]
+    fn parentheses_group_unless_a_comma_makes_a_tuple() {
+        let src = "A: Gem { a: (5) b: (5,) c: (10 + 5) * 2 d: ((parent.width - 20)) e: () }";
+        let values: Vec<Value> = parse(src)
+            .unwrap()
+            .root
+            .properties
+            .into_iter()
+            .map(|p| p.value)
+            .collect();
+        assert_eq!(values[0], Value::Integer(5));
+        assert_eq!(values[1], Value::Tuple(vec![Value::Integer(5)]));
+        assert_eq!(values[2], Value::Integer(30));
+        assert!(matches!(values[3], Value::SizeExpr(_)));
+        assert_eq!(values[4], Value::Tuple(Vec::new()));
+
+        let src = "extend Gem\nfn f() { x = (a + 1) * 2 y = (a,) }";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let statements = &logic.functions[0].body.statements;
+        let Stmt::Assignment { value, .. } = &statements[0] else {
+            panic!("expected an assignment");
+        };
+        let Expr::BinaryOp { left, .. } = value else {
+            panic!("expected a product");
+        };
+        assert!(matches!(**left, Expr::BinaryOp { .. }));
+        let Stmt::Assignment { value, .. } = &statements[1] else {
+            panic!("expected an assignment");
+        };
+        assert!(matches!(value, Expr::Tuple(items) if items.len() == 1));
+    }
+
+    #[test]
+    fn directives_take_arguments_and_queries() {
+        let scene = parse(
+            "A: Gem { t: #assets:atlas.png:region(0, 0, 32, 32) e: #enemy?variant=blue&hp=3 }",
+        )
+        .unwrap();
+        let directive = |i: usize| match &scene.root.properties[i].value {
+            Value::Directive(d) => d.clone(),
+            v => panic!("expected a directive, got {:?}", v),
+        };
+        let atlas = directive(0);
+        assert_eq!(atlas.segments, ["assets", "atlas.png"]);
+        assert_eq!(atlas.call("region").map(<[Value]>::len), Some(4));
+        let enemy = directive(1);
+        assert_eq!(enemy.segments, ["enemy"]);
+        let queries: Vec<(&str, &Value)> = enemy.queries().collect();
+        assert_eq!(
+            queries,
+            [
+                ("variant", &Value::Ident("blue".into())),
+                ("hp", &Value::Integer(3))
+            ]
+        );
+
+        let err = |src: &str| parse(src).unwrap_err().message;
+        assert!(err("A: Gem { t: #a:f(1):b }").contains("segment 'b' after an argument"));
+        assert!(err("A: Gem { t: #a?=1 }").contains("query key"));
+    }
+
+    #[test]
+    fn if_else_chains_nest_in_else_blocks() {
+        let src = "extend Gem\nfn f() { if hp < 1 { die() } else if hp < 10 { if shield { x = 1 } } else { x = 2 } }";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let Stmt::If {
+            condition,
+            then_block,
+            else_block: Some(else_block),
+        } = &logic.functions[0].body.statements[0]
+        else {
+            panic!("expected an if with an else");
+        };
+        assert!(matches!(condition, Expr::BinaryOp { op, .. } if *op == BinOp::Less));
+        assert_eq!(then_block.statements.len(), 1);
+        let Stmt::If {
+            then_block,
+            else_block,
+            ..
+        } = &else_block.statements[0]
+        else {
+            panic!("expected else if to nest an if");
+        };
+        assert!(
+            matches!(&then_block.statements[0], Stmt::If { else_block, .. } if else_block.is_none())
+        );
+        let last = &else_block.as_ref().unwrap().statements[0];
+        assert!(matches!(last, Stmt::Assignment { target, .. } if target == "x"));
+
+        // Still names in scene files
+        let scene = parse("A: Gem { if: 1 else: 2 }").unwrap();
+        assert_eq!(scene.root.properties.len(), 2);
+        let src = "extend Gem\nfn f() { else { } }";
+        let err = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap_err();
+        assert!(err.message.contains("'else' without an 'if'"));
+    }
+
+    #[test]
+    fn for_loops_iterate_collections_and_ranges() {
+        let logic =
+            |src: &str| Parser::new(Lexer::new(src.to_string()).tokenize().unwrap()).parse_logic();
+        let src = "extend Gem\nfn f() { for i in 0..n + 1 { spawn(#scenes:enemy) } for child in get_children() { child.hide() } }";
+        let body = &logic(src).unwrap().functions[0].body;
+        let Stmt::For {
+            var,
+            iterable: Expr::Range { start, end },
+            body: loop_body,
+        } = &body.statements[0]
+        else {
+            panic!("expected a loop over a range");
+        };
+        assert_eq!(var, "i");
+        assert_eq!(**start, Expr::Integer(0));
+        assert!(matches!(**end, Expr::BinaryOp { op: BinOp::Add, .. }));
+        assert_eq!(loop_body.statements.len(), 1);
+        assert!(matches!(
+            &body.statements[1],
+            Stmt::For { var, iterable: Expr::Call { name, .. }, .. } if var == "child" && name == "get_children"
+        ));
+
+        let err = |src: &str| logic(src).unwrap_err().message;
+        assert!(err("extend Gem\nfn f() { for i of items { } }").contains("Expected 'in'"));
+        assert!(err("extend Gem\nfn f() { for in in items { } }").contains("reserved word"));
+        // Still names in scene files
+        let scene = parse("A: Gem { for: 1 in: 2 }").unwrap();
+        assert_eq!(scene.root.properties.len(), 2);
+    }
+
+    #[test]
+    fn property_chains_end_in_method_calls() {
+        let src = "extend Gem\nfn f() { weapon.muzzle.fire(2, speed) get_parent().get_name().len }";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let body = &logic.functions[0].body.statements;
+        let Stmt::ExprStmt(Expr::MethodCall {
+            object,
+            method,
+            args,
+        }) = &body[0]
+        else {
+            panic!("expected a method call");
+        };
+        assert_eq!(method, "fire");
+        assert_eq!(args.len(), 2);
+        assert!(matches!(&**object, Expr::PropertyAccess { property, .. } if property == "muzzle"));
+        let Stmt::ExprStmt(Expr::PropertyAccess { object, property }) = &body[1] else {
+            panic!("expected a property of a call's result");
+        };
+        assert_eq!(property, "len");
+        assert!(matches!(
+            &**object,
+            Expr::MethodCall { object, method, args }
+                if method == "get_name" && args.is_empty() && matches!(**object, Expr::Call { .. })
+        ));
+    }
+
+    #[test]
+    fn match_arms_take_literals_and_wildcards() {
+        let logic =
+            |src: &str| Parser::new(Lexer::new(src.to_string()).tokenize().unwrap()).parse_logic();
+        let src = "extend Gem\nfn f() { match state { \"idle\" => { speed = 0 }, -1 => { } 2.5 => { } _ => { speed = 1 } } }";
+        let body = &logic(src).unwrap().functions[0].body;
+        let Stmt::Match { value, arms } = &body.statements[0] else {
+            panic!("expected a match");
+        };
+        assert_eq!(*value, Expr::Ident("state".to_string()));
+        let patterns: Vec<_> = arms.iter().map(|a| &a.pattern).collect();
+        assert_eq!(
+            patterns,
+            [
+                &Pattern::Literal(Expr::String("idle".to_string())),
+                &Pattern::Literal(Expr::Integer(-1)),
+                &Pattern::Literal(Expr::Number(2.5)),
+                &Pattern::Wildcard,
+            ]
+        );
+        assert_eq!(arms[3].body.statements.len(), 1);
+
+        let err = |src: &str| logic(src).unwrap_err().message;
+        assert!(err("extend Gem\nfn f() { match x { 1 { } } }").contains("Expected '=>'"));
+        assert!(err("extend Gem\nfn f() { match x { y => { } } }").contains("as a match pattern"));
+    }
+
+    #[test]
+    fn return_takes_a_value_unless_the_block_ends() {
+        let src = "extend Gem\nfn speed(v) { if v < 0 { return } return v * 2 }";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let body = &logic.functions[0].body.statements;
+        assert!(
+            matches!(&body[0], Stmt::If { then_block, .. } if then_block.statements == [Stmt::Return(None)])
+        );
+        assert!(matches!(
+            &body[1],
+            Stmt::Return(Some(Expr::BinaryOp { op: BinOp::Mul, .. }))
+        ));
+
+        // Nor does one ending its line take the next line as its value
+        let src = "extend Gem\nfn stop() {\n    return\n    g(1)\n}";
+        let logic = Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+            .parse_logic()
+            .unwrap();
+        let body = &logic.functions[0].body.statements;
+        assert_eq!(body[0], Stmt::Return(None));
+        assert!(matches!(&body[1], Stmt::ExprStmt(Expr::Call { .. })));
+    }
+
+    #[test]
+    fn annotations_attach_to_the_next_function() {
+        let logic =
+            |src: &str| Parser::new(Lexer::new(src.to_string()).tokenize().unwrap()).parse_logic();
+        let file = logic(
+            "extend Gem\n@rpc(any_peer, call_local)\nfn sync_position(pos) {}\nfn plain() {}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            file.functions[0].annotations,
+            [Annotation {
+                name: "rpc".into(),
+                args: vec!["any_peer".into(), "call_local".into()],
+            }]
+        );
+        assert!(file.functions[1].annotations.is_empty());
+
+        let err = |src: &str| logic(src).unwrap_err().message;
+        assert!(err("extend Gem\n@rpc\nfn on_ready {}\n").contains("event handler on_ready"));
+        assert!(err("extend Gem\nfn a() {}\n@rpc\n").contains("followed by a function"));
+    }
+
+    #[test]
+    fn incremental_parses_reuse_untouched_children() {
+        let recovering = |src: &str| {
+            Parser::new(Lexer::new(src.to_string()).tokenize().unwrap())
+                .parse_scene_recovering()
+                .unwrap()
+        };
+        let src = "Hud: Gem {\n    title: \"{\" // }\n    Score: LabelGem { text: \"0\" }\n    \
+                   %Lives: LabelGem { hit: { a: 1 } Icon: SpriteGem {} }\n}";
+        let old = recovering(src);
+        let at = |needle: &str| src.find(needle).unwrap();
+        assert_eq!(
+            child_spans(src).unwrap(),
+            [
+                at("Score")..at(" }\n    %") + 2,
+                at("%Lives")..src.len() - 2
+            ]
+        );
+
+        // Each edit gives the tree a full parse of the edited source would
+        let check = |edits: &[TextEdit]| {
+            let new = parse_incremental(&old, src, edits).unwrap();
+            let text = apply_edits(src, edits, 0..src.len());
+            assert_eq!(new, recovering(&text), "{}", text);
+        };
+        let edit = |needle: &str, text: &str| TextEdit {
+            range: at(needle)..at(needle) + needle.len(),
+            text: text.to_string(),
+        };
+        check(&[edit("\"0\"", "\"10\"")]);
+        assert!(reparse_child(&old, src, &[edit("\"0\"", "\"10\"")]).is_some());
+        check(&[edit("a: 1", "a: 1 b: 2"), edit("SpriteGem", "LabelGem")]);
+        // Malformed properties are reported at the reparsed child
+        check(&[edit("\"0\"", "(1,")]);
+        // Splitting a child in two, or editing the root, reparses everything
+        check(&[edit("\"0\"", "\"0\" } Extra: Gem {")]);
+        check(&[edit("title", "subtitle")]);
+        assert!(reparse_child(&old, src, &[edit("\"0\"", "\"0\" } Extra: Gem {")]).is_none());
+
+        let overlapping = [edit("Score", "Points"), edit("Score:", "Points:")];
+        assert!(parse_incremental(&old, src, &overlapping).is_err());
     }
 }
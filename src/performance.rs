@@ -0,0 +1,122 @@
+//! Engine counters and the `Performance` singleton, so a game can draw its
+//! own performance HUD and tests can hold it to a budget. Frames are counted
+//! by `time::begin_frame` and draw calls by the renderer; the frame rate is
+//! the number of frames begun over the last whole second.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::autoload;
+use crate::gem;
+use crate::object::{self, ObjectInner, ObjectRef};
+use crate::value::Value;
+
+/// Monitors `get_monitor` knows, in the order its error lists them
+pub const MONITORS: [&str; 4] = ["fps", "draw_calls", "node_count", "memory_objects"];
+
+/// Clock reading when the current one-second window started
+static WINDOW_USEC: AtomicU64 = AtomicU64::new(0);
+static WINDOW_FRAMES: AtomicU64 = AtomicU64::new(0);
+/// Frames in the last whole window, scaled to one second (f64 bits)
+static FPS: AtomicU64 = AtomicU64::new(0);
+/// Draw calls issued so far in the frame being drawn
+static DRAWING: AtomicU64 = AtomicU64::new(0);
+/// Draw calls of the last frame drawn
+static DRAW_CALLS: AtomicU64 = AtomicU64::new(0);
+
+/// Count a frame begun at clock reading `now`
+pub fn record_frame(now: u64) {
+    let frames = WINDOW_FRAMES.fetch_add(1, Ordering::Relaxed) + 1;
+    let start = WINDOW_USEC.load(Ordering::Relaxed);
+    let elapsed = now.saturating_sub(start);
+    if elapsed >= 1_000_000 {
+        let fps = frames as f64 * 1_000_000.0 / elapsed as f64;
+        FPS.store(fps.to_bits(), Ordering::Relaxed);
+        WINDOW_USEC.store(now, Ordering::Relaxed);
+        WINDOW_FRAMES.store(0, Ordering::Relaxed);
+    }
+}
+
+pub fn fps() -> f64 {
+    f64::from_bits(FPS.load(Ordering::Relaxed))
+}
+
+/// Count one GL draw call
+pub fn count_draw_call() {
+    DRAWING.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Start drawing a frame; the previous one's draw calls become `draw_calls`
+pub fn begin_draw() {
+    DRAW_CALLS.store(DRAWING.swap(0, Ordering::Relaxed), Ordering::Relaxed);
+}
+
+pub fn draw_calls() -> u64 {
+    DRAW_CALLS.load(Ordering::Relaxed)
+}
+
+/// Nodes in the runtime tree, its root included
+pub fn node_count() -> u64 {
+    fn count(node: &ObjectRef) -> u64 {
+        1 + gem::children_of(node).iter().map(count).sum::<u64>()
+    }
+    count(&autoload::tree_root())
+}
+
+/// The current value of monitor `name`
+pub fn monitor(name: &str) -> Result<Value, String> {
+    Ok(match name {
+        "fps" => Value::Float(fps()),
+        "draw_calls" => Value::Int(draw_calls() as i64),
+        "node_count" => Value::Int(node_count() as i64),
+        "memory_objects" => Value::Int(object::live_objects() as i64),
+        _ => {
+            return Err(format!(
+                "Unknown monitor '{}', expected one of: {}",
+                name,
+                MONITORS.join(", ")
+            ));
+        }
+    })
+}
+
+/// The script-facing `Performance` singleton
+pub fn new_performance() -> ObjectRef {
+    let obj = ObjectInner::base("Performance");
+    // get_monitor(name): one of `MONITORS`
+    ObjectInner::insert_method(
+        &obj,
+        "get_monitor",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::String(name)) => monitor(name),
+            _ => Err("get_monitor expects a monitor name".to_string()),
+        }),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitors_follow_the_engine_counters() {
+        let performance = new_performance();
+        let get =
+            |name: &str| performance.call_method("get_monitor", &[Value::String(name.into())]);
+
+        // Twenty frames over a second; other tests may begin a few more
+        let start = WINDOW_USEC.load(Ordering::Relaxed) + 10_000_000;
+        WINDOW_USEC.store(start, Ordering::Relaxed);
+        WINDOW_FRAMES.store(0, Ordering::Relaxed);
+        for i in 1..=20 {
+            record_frame(start + i * 50_000);
+        }
+        assert!(matches!(get("fps"), Ok(Value::Float(f)) if (20.0..30.0).contains(&f)));
+
+        assert!(matches!(get("memory_objects"), Ok(Value::Int(n)) if n > 0));
+        assert!(matches!(get("node_count"), Ok(Value::Int(n)) if n >= 1));
+        assert!(matches!(get("draw_calls"), Ok(Value::Int(_))));
+        assert!(get("frame_time").unwrap_err().contains("fps, draw_calls"));
+    }
+}
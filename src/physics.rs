@@ -0,0 +1,309 @@
+//! PhysicsServer: point and ray queries against registered 2D collision shapes.
+//! Shapes are in world space and belong to a node; scripts reach the server as
+//! the `PhysicsServer` singleton and get hits back as maps with `node`, `point`
+//! and `normal` entries.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::gem;
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Rect { x: f64, y: f64, w: f64, h: f64 },
+    Circle { x: f64, y: f64, radius: f64 },
+}
+
+pub struct Hit {
+    pub node: ObjectRef,
+    pub point: (f64, f64),
+    /// Zero when the query started inside the shape
+    pub normal: (f64, f64),
+}
+
+static SHAPES: OnceLock<RwLock<Vec<(ObjectRef, Shape)>>> = OnceLock::new();
+
+fn shapes() -> &'static RwLock<Vec<(ObjectRef, Shape)>> {
+    SHAPES.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+pub fn add_shape(node: &ObjectRef, shape: Shape) {
+    shapes().write().unwrap().push((node.clone(), shape));
+}
+
+/// Drop every shape owned by `node`
+pub fn remove_shapes(node: &ObjectRef) {
+    shapes().write().unwrap().retain(|(n, _)| n != node);
+}
+
+impl Shape {
+    fn contains(&self, (px, py): (f64, f64)) -> bool {
+        match *self {
+            Shape::Rect { x, y, w, h } => px >= x && py >= y && px < x + w && py < y + h,
+            Shape::Circle { x, y, radius } => {
+                (px - x).powi(2) + (py - y).powi(2) <= radius * radius
+            }
+        }
+    }
+
+    /// First crossing of the segment `from + t * d` for t in [0, 1], with the
+    /// surface normal there
+    fn intersect_ray(&self, from: (f64, f64), d: (f64, f64)) -> Option<(f64, (f64, f64))> {
+        if self.contains(from) {
+            return Some((0.0, (0.0, 0.0)));
+        }
+        match *self {
+            Shape::Rect { x, y, w, h } => {
+                // Slab test: the entry is the latest of the per-axis entries
+                let mut t_enter = 0.0_f64;
+                let mut t_exit = 1.0_f64;
+                let mut normal = (0.0, 0.0);
+                for (origin, dir, lo, hi, axis) in [
+                    (from.0, d.0, x, x + w, (1.0, 0.0)),
+                    (from.1, d.1, y, y + h, (0.0, 1.0)),
+                ] {
+                    if dir == 0.0 {
+                        if origin < lo || origin > hi {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let (mut t0, mut t1) = ((lo - origin) / dir, (hi - origin) / dir);
+                    let mut n = (-axis.0, -axis.1);
+                    if t0 > t1 {
+                        std::mem::swap(&mut t0, &mut t1);
+                        n = axis;
+                    }
+                    if t0 > t_enter {
+                        t_enter = t0;
+                        normal = n;
+                    }
+                    t_exit = t_exit.min(t1);
+                    if t_enter > t_exit {
+                        return None;
+                    }
+                }
+                Some((t_enter, normal))
+            }
+            Shape::Circle { x, y, radius } => {
+                let (ox, oy) = (from.0 - x, from.1 - y);
+                let a = d.0 * d.0 + d.1 * d.1;
+                let b = 2.0 * (ox * d.0 + oy * d.1);
+                let c = ox * ox + oy * oy - radius * radius;
+                let disc = b * b - 4.0 * a * c;
+                if a == 0.0 || disc < 0.0 {
+                    return None;
+                }
+                let t = (-b - disc.sqrt()) / (2.0 * a);
+                if !(0.0..=1.0).contains(&t) {
+                    return None;
+                }
+                let (hx, hy) = (ox + d.0 * t, oy + d.1 * t);
+                Some((t, (hx / radius, hy / radius)))
+            }
+        }
+    }
+}
+
+/// Closest shape crossed by the segment `from`→`to`
+pub fn raycast(from: (f64, f64), to: (f64, f64)) -> Option<Hit> {
+    let d = (to.0 - from.0, to.1 - from.1);
+    let shapes = shapes().read().unwrap();
+    let (node, t, normal) = shapes
+        .iter()
+        .filter_map(|(node, shape)| {
+            shape
+                .intersect_ray(from, d)
+                .map(|(t, normal)| (node, t, normal))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+    Some(Hit {
+        node: node.clone(),
+        point: (from.0 + d.0 * t, from.1 + d.1 * t),
+        normal,
+    })
+}
+
+/// Every visible node with a shape containing `p`, most recently registered first
+pub fn intersect_point(p: (f64, f64)) -> Vec<Hit> {
+    let shapes = shapes().read().unwrap();
+    shapes
+        .iter()
+        .rev()
+        .filter(|(node, shape)| shape.contains(p) && gem::is_visible_in_tree(node))
+        .map(|(node, _)| Hit {
+            node: node.clone(),
+            point: p,
+            normal: (0.0, 0.0),
+        })
+        .collect()
+}
+
+impl Hit {
+    pub fn to_value(&self) -> Value {
+        Value::Map(HashMap::from([
+            ("node".to_string(), Value::Object(self.node.clone())),
+            ("point".to_string(), Value::vec2(self.point.0, self.point.1)),
+            (
+                "normal".to_string(),
+                Value::vec2(self.normal.0, self.normal.1),
+            ),
+        ]))
+    }
+}
+
+/// The script-facing `PhysicsServer` object
+pub fn new_physics_server() -> ObjectRef {
+    let obj = ObjectInner::base("PhysicsServer");
+    // raycast(from, to) -> hit map or Null
+    ObjectInner::insert_method(
+        &obj,
+        "raycast",
+        Arc::new(|_, args| {
+            let (Some(from), Some(to)) = (
+                args.first().and_then(Value::as_vec2),
+                args.get(1).and_then(Value::as_vec2),
+            ) else {
+                return Err("raycast expects (from, to) points".into());
+            };
+            Ok(raycast(from, to).map_or(Value::Null, |hit| hit.to_value()))
+        }),
+    );
+    // intersect_point(p) -> array of hit maps
+    ObjectInner::insert_method(
+        &obj,
+        "intersect_point",
+        Arc::new(|_, args| {
+            let p = args
+                .first()
+                .and_then(Value::as_vec2)
+                .ok_or("intersect_point expects a point")?;
+            Ok(Value::Array(
+                intersect_point(p).iter().map(Hit::to_value).collect(),
+            ))
+        }),
+    );
+    // add_rect(node, position, size), add_circle(node, center, radius), remove_shapes(node)
+    ObjectInner::insert_method(
+        &obj,
+        "add_rect",
+        Arc::new(|_, args| {
+            let (Some(Value::Object(node)), Some((x, y)), Some((w, h))) = (
+                args.first(),
+                args.get(1).and_then(Value::as_vec2),
+                args.get(2).and_then(Value::as_vec2),
+            ) else {
+                return Err("add_rect expects (node, position, size)".into());
+            };
+            add_shape(node, Shape::Rect { x, y, w, h });
+            Ok(Value::Null)
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "add_circle",
+        Arc::new(|_, args| {
+            let (Some(Value::Object(node)), Some((x, y)), Some(radius)) = (
+                args.first(),
+                args.get(1).and_then(Value::as_vec2),
+                args.get(2).and_then(Value::as_f64),
+            ) else {
+                return Err("add_circle expects (node, center, radius)".into());
+            };
+            add_shape(node, Shape::Circle { x, y, radius });
+            Ok(Value::Null)
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "remove_shapes",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::Object(node)) => {
+                remove_shapes(node);
+                Ok(Value::Null)
+            }
+            _ => Err("remove_shapes expects a node".into()),
+        }),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::init_object_class;
+    use crate::object::object_new;
+
+    fn point(x: f64, y: f64) -> Value {
+        Value::vec2(x, y)
+    }
+
+    #[test]
+    fn raycast_and_point_queries() {
+        init_object_class();
+        let wall = object_new("Object");
+        let ball = object_new("Object");
+        let server = new_physics_server();
+        server
+            .call_method(
+                "add_rect",
+                &[
+                    Value::Object(wall.clone()),
+                    point(100.0, 0.0),
+                    point(20.0, 50.0),
+                ],
+            )
+            .unwrap();
+        server
+            .call_method(
+                "add_circle",
+                &[
+                    Value::Object(ball.clone()),
+                    point(50.0, 25.0),
+                    Value::Int(10),
+                ],
+            )
+            .unwrap();
+
+        // Left to right along y = 25 meets the ball first, then the wall
+        let hit = raycast((0.0, 25.0), (200.0, 25.0)).unwrap();
+        assert_eq!(hit.node, ball);
+        assert_eq!(hit.point, (40.0, 25.0));
+        assert_eq!(hit.normal, (-1.0, 0.0));
+        let hit = raycast((0.0, 5.0), (200.0, 5.0)).unwrap();
+        assert_eq!(hit.node, wall);
+        assert_eq!(hit.point, (100.0, 5.0));
+        assert_eq!(hit.normal, (-1.0, 0.0));
+        // From above the wall, straight down
+        let hit = raycast((110.0, -10.0), (110.0, 10.0)).unwrap();
+        assert_eq!(hit.normal, (0.0, -1.0));
+        assert!(raycast((0.0, 100.0), (200.0, 100.0)).is_none());
+        // Too short to reach the wall
+        assert!(raycast((130.0, 5.0), (125.0, 5.0)).is_none());
+
+        let Value::Map(map) = server
+            .call_method("raycast", &[point(0.0, 25.0), point(200.0, 25.0)])
+            .unwrap()
+        else {
+            panic!("expected a hit map");
+        };
+        assert_eq!(map.get("node"), Some(&Value::Object(ball.clone())));
+        assert_eq!(map.get("point"), Some(&point(40.0, 25.0)));
+
+        let hits = intersect_point((105.0, 10.0));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].node, wall);
+        assert!(intersect_point((70.0, 70.0)).is_empty());
+        // Hidden nodes can't be picked
+        wall.set_property("visible", Value::Bool(false));
+        assert!(intersect_point((105.0, 10.0)).is_empty());
+        wall.set_property("visible", Value::Bool(true));
+
+        server
+            .call_method("remove_shapes", &[Value::Object(ball)])
+            .unwrap();
+        assert_eq!(raycast((0.0, 25.0), (200.0, 25.0)).unwrap().node, wall);
+    }
+}
@@ -1,10 +1,17 @@
 use crate::ast;
 use crate::codegen;
+use crate::commands;
+use crate::defines;
 use crate::ir::SceneIR;
 use crate::lexer::Lexer;
+use crate::log::{info, trace, verbose};
+use crate::multiplayer;
 use crate::parser::Parser;
+use crate::profile;
+use crate::resource;
+use crate::scope;
 use crate::token::Token;
-use crate::transformer::Transformer;
+use crate::transformer::{self, Transformer};
 use std::fs;
 
 pub struct SceneCompileResult {
@@ -13,9 +20,11 @@ pub struct SceneCompileResult {
     pub generated_path: String,
 }
 
+/// Lex `content` and expand it with the CLI's defines (`#if`, `$name`)
 pub fn lex_source(content: &str) -> Result<Vec<Token>, String> {
     let mut lexer = Lexer::new(content.to_string());
-    lexer.tokenize().map_err(|e| e.to_string())
+    let tokens = lexer.tokenize().map_err(|e| e.to_string())?;
+    defines::apply(&tokens)
 }
 
 pub fn parse_scene(tokens: Vec<Token>) -> Result<ast::GemFile, String> {
@@ -23,45 +32,120 @@ pub fn parse_scene(tokens: Vec<Token>) -> Result<ast::GemFile, String> {
     parser.parse_scene().map_err(|e| e.message)
 }
 
+/// Parse a scene, skipping malformed properties; they're listed in the
+/// file's `errors`
+pub fn parse_scene_recovering(tokens: Vec<Token>) -> Result<ast::GemFile, String> {
+    let mut parser = Parser::new(tokens);
+    parser.parse_scene_recovering().map_err(|e| e.message)
+}
+
+/// `Name: message` for each property skipped in `scene`
+pub fn describe_errors(scene: &ast::GemFile) -> Vec<String> {
+    scene
+        .errors
+        .iter()
+        .map(|e| match commands::find_node(&scene.root, &e.node) {
+            Some(decl) => format!("{}: {}", decl.name, e.message),
+            None => e.message.clone(),
+        })
+        .collect()
+}
+
+/// Parse directive text such as `#scenes:enemy?variant=blue`
+pub fn parse_directive(text: &str) -> Result<ast::Directive, String> {
+    let mut parser = Parser::new(lex_source(text)?);
+    parser.parse_standalone_directive().map_err(|e| e.message)
+}
+
 /// Parse a logic file from tokens.
 pub fn parse_logic(tokens: Vec<Token>) -> Result<ast::LogicFile, String> {
     let mut parser = Parser::new(tokens);
     parser.parse_logic().map_err(|e| e.message)
 }
 
+/// Parse a logic file and check that every directive it uses names an existing
+/// file and that its locals are declared before use.
+pub fn compile_logic(content: &str) -> Result<ast::LogicFile, String> {
+    let logic = parse_logic(lex_source(content)?)?;
+    let missing = resource::missing_refs(&logic);
+    if !missing.is_empty() {
+        return Err(missing.join("\n"));
+    }
+    multiplayer::rpc_configs(&logic)?;
+    scope::check(&logic)?;
+    Ok(logic)
+}
+
 /// Compile scene content end-to-end: lex -> parse -> transform -> codegen -> write file.
-/// Returns AST + IR + output path on success.
+/// Returns AST + IR + output path on success. Progress goes through `log`, so
+/// this prints nothing unless a level has been set.
 pub fn compile_scene(content: &str, output_path: &str) -> Result<SceneCompileResult, String> {
-    println!("\n=== Lexing ===");
+    verbose!("\n=== Lexing ===");
     let tokens = lex_source(content)?;
-    println!("[INFO] Lexed {} tokens", tokens.len());
+    verbose!("[INFO] Lexed {} tokens", tokens.len());
 
-    println!("\n=== Parsing ===");
+    verbose!("\n=== Parsing ===");
     let ast = parse_scene(tokens)?;
-    println!("[INFO] Parsed scene file successfully!");
+    verbose!("[INFO] Parsed scene file successfully!");
 
-    println!("\n=== Transforming to IR ===");
-    let transformer = Transformer::new();
+    verbose!("\n=== Transforming to IR ===");
+    let transformer = Transformer::new()
+        .with_overrides(profile::overrides())
+        .with_strict(transformer::strict());
     let ir = transformer.transform(ast.clone())?;
-    println!("[INFO] Transformed to IR: {} nodes", ir.nodes.len());
+    verbose!("[INFO] Transformed to IR: {} nodes", ir.nodes.len());
+    trace!("{}", ir.dump().trim_end());
 
-    println!("\n=== Generating Rust Code ===");
-    let codegen = codegen::RustCodegen::new();
+    verbose!("\n=== Generating Rust Code ===");
+    let codegen = codegen::RustCodegen::new().with_no_std(codegen::no_std());
     let rust_code = codegen.generate(&ir);
 
     std::fs::create_dir_all("build").ok();
     match fs::write(output_path, &rust_code) {
-        Ok(_) => println!("[INFO] Generated Rust code → {}", output_path),
+        Ok(_) => verbose!("[INFO] Generated Rust code → {}", output_path),
         Err(e) => return Err(format!("Failed to write {}: {}", output_path, e)),
     }
 
-    println!(
-        "\n[INFO] Compilation complete!\n      Generated: {}",
-        output_path
-    );
+    info!("[INFO] Compiled scene → {}", output_path);
     Ok(SceneCompileResult {
         ast,
         ir,
         generated_path: output_path.to_string(),
     })
 }
+
+/// The Rust code `compile_scene` generates for `content`, without writing it
+/// or printing progress
+pub fn scene_code(content: &str) -> Result<String, String> {
+    let ast = lex_source(content).and_then(parse_scene)?;
+    let ir = Transformer::new()
+        .with_overrides(profile::overrides())
+        .with_strict(transformer::strict())
+        .transform(ast)?;
+    Ok(codegen::RustCodegen::new()
+        .with_no_std(codegen::no_std())
+        .generate(&ir))
+}
+
+/// `compile_scene` for the preview. When the scene only fails on malformed
+/// properties, this is the rest of it, with the skipped ones in `ast.errors`;
+/// nothing is generated for such a scene, so `generated_path` is empty.
+pub fn compile_preview(content: &str, output_path: &str) -> Result<SceneCompileResult, String> {
+    let error = match compile_scene(content, output_path) {
+        Ok(result) => return Ok(result),
+        Err(e) => e,
+    };
+    let ast = match lex_source(content).and_then(parse_scene_recovering) {
+        Ok(ast) if !ast.errors.is_empty() => ast,
+        _ => return Err(error),
+    };
+    let ir = Transformer::new()
+        .with_overrides(profile::overrides())
+        .with_strict(transformer::strict())
+        .transform(ast.clone())?;
+    Ok(SceneCompileResult {
+        ast,
+        ir,
+        generated_path: String::new(),
+    })
+}
@@ -0,0 +1,457 @@
+//! PopupGem and DialogGem: windows drawn on the overlay layer, above the rest
+//! of the scene. Both start hidden. `popup()` shows one and gives it the
+//! input: while the most recently opened popup is visible, every input event
+//! goes to its subtree and no further (see `input::dispatch`). A PopupGem
+//! closes when clicked outside its rect, emitting `popup_hide`; a DialogGem
+//! stays open until `confirm()` (Enter) or `cancel()` (Escape) closes it and
+//! emits `confirmed` or `canceled`. `dim_background: true` darkens the scene
+//! behind the popup. A PopupMenuGem is a popup listing its `items` as rows,
+//! sized to fit them: hovering or the arrow keys move `focused_item`, and a
+//! click or Enter closes the menu and emits `item_selected(index)`.
+//! `popup_at(position)` opens one at a point, as for a right-click menu.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::gem;
+use crate::item_list;
+use crate::object::{MethodTable, ObjectRef, WeakObjectRef, define_method, register_class};
+use crate::scene_tree;
+use crate::value::Value;
+
+const SIZE_KEY: &str = "size";
+pub const DIM_BACKGROUND_KEY: &str = "dim_background";
+pub const ITEMS_KEY: &str = "items";
+/// The menu row under the pointer or moved to with the arrow keys, or -1
+pub const FOCUSED_ITEM_KEY: &str = "focused_item";
+
+/// Popups in the order they were opened; closed ones are dropped lazily
+static OPEN: Mutex<Vec<WeakObjectRef>> = Mutex::new(Vec::new());
+
+pub fn init_popup_classes() {
+    register_class("PopupGem", || new_popup("PopupGem", popup_methods()));
+    register_class("DialogGem", || new_popup("DialogGem", dialog_methods()));
+    register_class("PopupMenuGem", || {
+        let menu = new_popup("PopupMenuGem", popup_menu_methods());
+        menu.set_property(SIZE_KEY, Value::vec2(160.0, 0.0));
+        menu.set_property(ITEMS_KEY, Value::Array(Vec::new()));
+        menu.set_property(FOCUSED_ITEM_KEY, Value::Int(-1));
+        menu
+    });
+}
+
+fn new_popup(class_name: &str, methods: Arc<MethodTable>) -> ObjectRef {
+    let obj = gem::new_gem(class_name, methods);
+    obj.set_property("visible", Value::Bool(false));
+    obj.set_property(SIZE_KEY, Value::vec2(320.0, 180.0));
+    obj.set_property(DIM_BACKGROUND_KEY, Value::Bool(false));
+    obj
+}
+
+fn popup_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = build_popup_methods();
+            // _input(event): close on a click outside, and keep every event
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let event = event_arg(args)?;
+                    if event.class_name() == "InputEventMouseButton"
+                        && is_pressed(&event)
+                        && let Some(at) = event.get_property("position").and_then(|p| p.as_vec2())
+                        && !contains(this, at)
+                    {
+                        gem::set_visible(this, false);
+                        this.emit_signal("popup_hide", &[]);
+                    }
+                    event.call_method("accept_event", &[])
+                }),
+            );
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn dialog_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = build_popup_methods();
+            // confirm() / cancel(): close and emit `confirmed` / `canceled`
+            define_method(
+                &mut methods,
+                "confirm",
+                Arc::new(|this, _| {
+                    close(this, "confirmed");
+                    Ok(Value::Null)
+                }),
+            );
+            define_method(
+                &mut methods,
+                "cancel",
+                Arc::new(|this, _| {
+                    close(this, "canceled");
+                    Ok(Value::Null)
+                }),
+            );
+            // _input(event): Enter confirms, Escape cancels; every event is kept
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let event = event_arg(args)?;
+                    if event.class_name() == "InputEventKey" && is_pressed(&event) {
+                        match event.get_property("keycode") {
+                            Some(Value::String(key)) if key == "Enter" || key == "NumpadEnter" => {
+                                close(this, "confirmed")
+                            }
+                            Some(Value::String(key)) if key == "Escape" => close(this, "canceled"),
+                            _ => {}
+                        }
+                    }
+                    event.call_method("accept_event", &[])
+                }),
+            );
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn popup_menu_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| {
+            let mut methods = build_popup_methods();
+            // popup_at(position): open with the top-left corner at a global point
+            define_method(
+                &mut methods,
+                "popup_at",
+                Arc::new(|this, args| {
+                    let at = args
+                        .first()
+                        .and_then(Value::as_vec2)
+                        .ok_or("popup_at expects a position (x, y)")?;
+                    this.call_method("set_global_position", &[Value::vec2(at.0, at.1)])?;
+                    open(this);
+                    Ok(Value::Null)
+                }),
+            );
+            // add_item(text) -> index
+            define_method(
+                &mut methods,
+                "add_item",
+                Arc::new(|this, args| {
+                    let Some(Value::String(text)) = args.first() else {
+                        return Err("add_item expects a text string".into());
+                    };
+                    let mut items = menu_items(this);
+                    items.push(text.clone());
+                    set_menu_items(this, &items);
+                    Ok(Value::Int(items.len() as i64 - 1))
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_item_text",
+                Arc::new(|this, args| {
+                    let items = menu_items(this);
+                    match args.first() {
+                        Some(Value::Int(i)) => usize::try_from(*i)
+                            .ok()
+                            .and_then(|i| items.get(i))
+                            .map(|t| Value::String(t.clone()))
+                            .ok_or_else(|| {
+                                format!(
+                                    "get_item_text: {} is out of range for {} items",
+                                    i,
+                                    items.len()
+                                )
+                            }),
+                        _ => Err("get_item_text expects an Int index".into()),
+                    }
+                }),
+            );
+            define_method(
+                &mut methods,
+                "get_item_count",
+                Arc::new(|this, _| Ok(Value::Int(menu_items(this).len() as i64))),
+            );
+            define_method(
+                &mut methods,
+                "clear",
+                Arc::new(|this, _| {
+                    set_menu_items(this, &[]);
+                    Ok(Value::Null)
+                }),
+            );
+            // _input(event): hover and arrows focus a row, a click or Enter
+            // picks it; a click outside or Escape closes. Every event is kept.
+            define_method(
+                &mut methods,
+                "_input",
+                Arc::new(|this, args| {
+                    let event = event_arg(args)?;
+                    let at = event.get_property("position").and_then(|p| p.as_vec2());
+                    match event.class_name() {
+                        "InputEventMouseMotion" => {
+                            let row = at.and_then(|at| menu_row(this, at)).unwrap_or(-1);
+                            this.set_property(FOCUSED_ITEM_KEY, Value::Int(row));
+                        }
+                        "InputEventMouseButton" if is_pressed(&event) => {
+                            match at.map(|at| (at, menu_row(this, at))) {
+                                Some((_, Some(row))) => pick(this, row),
+                                Some((at, None)) if !contains(this, at) => {
+                                    close(this, "popup_hide")
+                                }
+                                _ => {}
+                            }
+                        }
+                        "InputEventKey" if is_pressed(&event) => {
+                            if let Some(Value::String(key)) = event.get_property("keycode") {
+                                menu_key(this, &key);
+                            }
+                        }
+                        _ => {}
+                    }
+                    event.call_method("accept_event", &[])
+                }),
+            );
+            // signals: item_selected(index), popup_hide
+            Arc::new(methods)
+        })
+        .clone()
+}
+
+fn menu_items(menu: &ObjectRef) -> Vec<String> {
+    match menu.get_property(ITEMS_KEY) {
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(t) => t,
+                _ => String::new(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Replace a menu's items; its height follows them
+pub fn set_menu_items(menu: &ObjectRef, items: &[String]) {
+    let values = items.iter().map(|t| Value::String(t.clone())).collect();
+    menu.set_property(ITEMS_KEY, Value::Array(values));
+    menu.set_property(FOCUSED_ITEM_KEY, Value::Int(-1));
+    fit_menu(menu);
+}
+
+fn fit_menu(menu: &ObjectRef) {
+    let height = menu_items(menu).len() as f64 * item_list::ROW_HEIGHT as f64;
+    menu.set_property(SIZE_KEY, Value::vec2(size_of(menu).0, height));
+}
+
+/// The row under a global point, if any
+fn menu_row(menu: &ObjectRef, at: (f64, f64)) -> Option<i64> {
+    if !contains(menu, at) {
+        return None;
+    }
+    let (_, y) = gem::to_local(menu, at)?;
+    let row = (y / item_list::ROW_HEIGHT as f64) as i64;
+    (row < menu_items(menu).len() as i64).then_some(row)
+}
+
+/// Arrows move the focus, wrapping; Enter picks it and Escape closes
+fn menu_key(menu: &ObjectRef, key: &str) {
+    let count = menu_items(menu).len() as i64;
+    let focused = match menu.get_property(FOCUSED_ITEM_KEY) {
+        Some(Value::Int(i)) if (0..count).contains(&i) => Some(i),
+        _ => None,
+    };
+    let next = match key {
+        "ArrowDown" if count > 0 => focused.map_or(0, |i| (i + 1) % count),
+        "ArrowUp" if count > 0 => focused.map_or(count - 1, |i| (i + count - 1) % count),
+        "Enter" | "NumpadEnter" => {
+            if let Some(i) = focused {
+                pick(menu, i);
+            }
+            return;
+        }
+        "Escape" => return close(menu, "popup_hide"),
+        _ => return,
+    };
+    menu.set_property(FOCUSED_ITEM_KEY, Value::Int(next));
+}
+
+/// Close the menu, then report the picked row
+fn pick(menu: &ObjectRef, index: i64) {
+    close(menu, "popup_hide");
+    menu.emit_signal("item_selected", &[Value::Int(index)]);
+}
+
+/// Gem's methods plus popup() and popup_centered(size?)
+fn build_popup_methods() -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+    define_method(
+        &mut methods,
+        "popup",
+        Arc::new(|this, _| {
+            open(this);
+            Ok(Value::Null)
+        }),
+    );
+    // popup_centered(size?): `size` replaces the popup's size when given
+    define_method(
+        &mut methods,
+        "popup_centered",
+        Arc::new(|this, args| {
+            if let Some(arg) = args.first() {
+                let (w, h) = arg
+                    .as_vec2()
+                    .ok_or("popup_centered expects a size (w, h)")?;
+                this.set_property(SIZE_KEY, Value::vec2(w, h));
+            }
+            let (w, h) = size_of(this);
+            let (vw, vh) = scene_tree::viewport_size();
+            this.call_method(
+                "set_global_position",
+                &[Value::vec2((vw - w) / 2.0, (vh - h) / 2.0)],
+            )?;
+            open(this);
+            Ok(Value::Null)
+        }),
+    );
+    methods
+}
+
+fn open(popup: &ObjectRef) {
+    if popup.class_name() == "PopupMenuGem" {
+        fit_menu(popup);
+    }
+    gem::set_visible(popup, true);
+    let mut open = OPEN.lock().unwrap();
+    open.retain(|p| p.upgrade().is_some_and(|p| p != *popup));
+    open.push(popup.downgrade());
+}
+
+fn close(popup: &ObjectRef, signal: &str) {
+    gem::set_visible(popup, false);
+    popup.emit_signal(signal, &[]);
+}
+
+/// The most recently opened popup that is still showing inside `root`'s subtree
+pub fn modal_within(root: &ObjectRef) -> Option<ObjectRef> {
+    let mut open = OPEN.lock().unwrap();
+    open.retain(|p| p.upgrade().is_some_and(|p| gem::is_visible(&p)));
+    open.iter()
+        .rev()
+        .filter_map(WeakObjectRef::upgrade)
+        .find(|p| gem::is_visible_in_tree(p) && is_within(p, root))
+}
+
+fn is_within(node: &ObjectRef, root: &ObjectRef) -> bool {
+    let mut cur = Some(node.clone());
+    while let Some(n) = cur {
+        if n == *root {
+            return true;
+        }
+        cur = gem::parent_of(&n);
+    }
+    false
+}
+
+fn event_arg(args: &[Value]) -> Result<ObjectRef, String> {
+    args.first()
+        .and_then(Value::as_object)
+        .ok_or_else(|| "_input expects an InputEvent".to_string())
+}
+
+fn is_pressed(event: &ObjectRef) -> bool {
+    matches!(event.get_property("pressed"), Some(Value::Bool(true)))
+}
+
+fn size_of(popup: &ObjectRef) -> (f64, f64) {
+    popup
+        .with_property(SIZE_KEY, |v| v.and_then(Value::as_vec2))
+        .unwrap_or((0.0, 0.0))
+}
+
+/// Whether the global point `at` is inside the popup's rect
+fn contains(popup: &ObjectRef, (x, y): (f64, f64)) -> bool {
+    let (px, py) = gem::global_transform(popup).origin;
+    let (w, h) = size_of(popup);
+    x >= px && y >= py && x < px + w && y < py + h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn dialogs_own_input_until_closed() {
+        scene_tree::init_builtin_classes();
+        let root = object_new("Gem");
+        let game = object_new("Gem");
+        let clicks = Arc::new(AtomicUsize::new(0));
+        let seen = clicks.clone();
+        crate::object::ObjectInner::insert_method(
+            &game,
+            "on_input",
+            Arc::new(move |_, _| {
+                seen.fetch_add(1, Ordering::SeqCst);
+                Ok(Value::Null)
+            }),
+        );
+        let dialog = object_new("DialogGem");
+        for child in [&game, &dialog] {
+            root.call_method("add_child", &[Value::Object(child.clone())])
+                .unwrap();
+        }
+        let canceled = Arc::new(AtomicUsize::new(0));
+        let seen = canceled.clone();
+        dialog.connect(
+            "canceled",
+            Arc::new(move |_| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        let click = || input::mouse_button_event("left", true, (1.0, 1.0));
+
+        assert!(!gem::is_visible(&dialog));
+        assert!(!input::dispatch(&root, &click()));
+        assert_eq!(clicks.load(Ordering::SeqCst), 1);
+
+        dialog
+            .call_method("popup_centered", &[Value::vec2(200.0, 100.0)])
+            .unwrap();
+        assert_eq!(
+            dialog.get_property("position"),
+            Some(Value::vec2(300.0, 250.0))
+        );
+        assert_eq!(modal_within(&root), Some(dialog.clone()));
+        // A click outside a dialog is swallowed, and doesn't close it
+        assert!(input::dispatch(&root, &click()));
+        assert_eq!(clicks.load(Ordering::SeqCst), 1);
+        assert!(gem::is_visible(&dialog));
+
+        assert!(input::dispatch(
+            &root,
+            &input::key_event("Escape", true, false)
+        ));
+        assert_eq!(canceled.load(Ordering::SeqCst), 1);
+        assert_eq!(modal_within(&root), None);
+        assert!(!input::dispatch(&root, &click()));
+        assert_eq!(clicks.load(Ordering::SeqCst), 2);
+
+        // Plain popups close when clicked outside
+        let menu = object_new("PopupGem");
+        root.call_method("add_child", &[Value::Object(menu.clone())])
+            .unwrap();
+        menu.set_property("position", Value::vec2(50.0, 50.0));
+        menu.call_method("popup", &[]).unwrap();
+        assert!(input::dispatch(&root, &click()));
+        assert!(!gem::is_visible(&menu));
+    }
+}
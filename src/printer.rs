@@ -0,0 +1,195 @@
+//! AST → .gem source printer.
+//! Round-trips with the parser: printing a parsed scene and parsing it again
+//! yields the same AST. Comments are not part of the AST and are not preserved.
+
+use crate::ast::{Directive, DirectiveArg, Easing, GemDecl, GemFile, Value};
+
+const INDENT: &str = "    ";
+
+pub fn print_scene(file: &GemFile) -> String {
+    let mut out = String::new();
+    print_decl(&file.root, 0, &mut out);
+    out
+}
+
+fn print_decl(decl: &GemDecl, depth: usize, out: &mut String) {
+    let pad = INDENT.repeat(depth);
+    let marker = if decl.unique { "%" } else { "" };
+    if decl.properties.is_empty() && decl.meta.is_empty() && decl.children.is_empty() {
+        out.push_str(&format!(
+            "{}{}{}: {} {{}}\n",
+            pad, marker, decl.name, decl.gem_type
+        ));
+        return;
+    }
+    out.push_str(&format!(
+        "{}{}{}: {} {{\n",
+        pad, marker, decl.name, decl.gem_type
+    ));
+    for p in &decl.properties {
+        out.push_str(&format!(
+            "{}{}{}: {}\n",
+            pad,
+            INDENT,
+            p.key,
+            print_value(&p.value)
+        ));
+    }
+    for m in &decl.meta {
+        out.push_str(&format!(
+            "{}{}meta:{}: {}\n",
+            pad,
+            INDENT,
+            m.key,
+            print_value(&m.value)
+        ));
+    }
+    for child in &decl.children {
+        print_decl(child, depth + 1, out);
+    }
+    out.push_str(&format!("{}}}\n", pad));
+}
+
+pub fn print_value(value: &Value) -> String {
+    match value {
+        Value::Integer(i) => i.to_string(),
+        // Keep a decimal point so the value lexes back as a float
+        Value::Number(n) if n.is_finite() && n.fract() == 0.0 => format!("{:.1}", n),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => format!("\"{}\"", escape(s)),
+        Value::Bool(b) => b.to_string(),
+        // A lone item keeps its comma, or it would read back as a group
+        Value::Tuple(vals) if vals.len() == 1 => format!("({},)", print_value(&vals[0])),
+        Value::Tuple(vals) => {
+            let items: Vec<String> = vals.iter().map(print_value).collect();
+            format!("({})", items.join(", "))
+        }
+        Value::Array(vals) => {
+            let items: Vec<String> = vals.iter().map(print_value).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Color([r, g, b, a]) => {
+            if *a == 255 {
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            } else {
+                format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+            }
+        }
+        Value::Directive(directive) => print_directive(directive),
+        Value::Ident(id) => id.clone(),
+        Value::Struct(fields) if fields.is_empty() => "{}".to_string(),
+        Value::Map(entries) if entries.is_empty() => "{}".to_string(),
+        Value::Struct(fields) => {
+            let items: Vec<String> = fields
+                .iter()
+                .map(|f| format!("{}: {}", f.key, print_value(&f.value)))
+                .collect();
+            format!("{{ {} }}", items.join(" "))
+        }
+        Value::Map(entries) => {
+            let items: Vec<String> = entries
+                .iter()
+                .map(|(key, v)| format!("\"{}\": {}", escape(key), print_value(v)))
+                .collect();
+            format!("{{ {} }}", items.join(", "))
+        }
+        Value::Curve(curve) => {
+            let points: Vec<String> = curve
+                .points
+                .iter()
+                .map(|p| {
+                    let (t, v) = (
+                        print_value(&Value::Number(p.t)),
+                        print_value(&Value::Number(p.value)),
+                    );
+                    match p.easing {
+                        Easing::Linear => format!("({}, {})", t, v),
+                        easing => format!("({}, {}, {})", t, v, easing.as_str()),
+                    }
+                })
+                .collect();
+            format!("Curve({})", points.join(", "))
+        }
+        Value::SizeExpr(expr) => expr.to_string(),
+    }
+}
+
+/// `#assets:atlas.png:region(0, 0, 32, 32)?key=value`
+pub fn print_directive(directive: &Directive) -> String {
+    let mut out = format!("#{}", directive.segments.join(":"));
+    let mut query = '?';
+    for arg in &directive.args {
+        match arg {
+            DirectiveArg::Call { name, values } => {
+                let values: Vec<String> = values.iter().map(print_value).collect();
+                out.push_str(&format!(":{}({})", name, values.join(", ")));
+            }
+            DirectiveArg::Query { key, value } => {
+                out.push_str(&format!("{}{}={}", query, key, print_value(value)));
+                query = '&';
+            }
+        }
+    }
+    out
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(src: &str) -> GemFile {
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        Parser::new(tokens).parse_scene().unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_the_parser() {
+        let ast = parse(
+            r#"Root: Gem {
+                title: "say \"hi\"\n"
+                position: (10, 2.5)
+                layers: (3,)
+                scale: 2.0
+                tint: #11223380
+                sprite: #assets:player.png
+                tile: #assets:atlas.svg:region(0, 0, 32, 32)
+                enemy: #scenes:enemy?variant=blue&hp=3
+                mode: additive
+                shape: { type: "circle", radius: 16 offset: (0, { y: 4 }) }
+                empty: {}
+                fade: Curve((0, 1), (0.5, 0.8, ease_out), (1, 0))
+                Child: LabelGem { visible: false }
+                Empty: Gem {}
+            }"#,
+        );
+        let printed = print_scene(&ast);
+        assert!(printed.contains("    scale: 2.0\n"));
+        assert!(printed.contains("    layers: (3,)\n"));
+        assert!(printed.contains("    enemy: #scenes:enemy?variant=blue&hp=3\n"));
+        assert!(printed.contains("    Empty: Gem {}\n"));
+        assert!(
+            printed.contains("    shape: { type: \"circle\" radius: 16 offset: (0, { y: 4 }) }\n")
+        );
+        assert!(
+            printed.contains("    fade: Curve((0.0, 1.0), (0.5, 0.8, ease_out), (1.0, 0.0))\n")
+        );
+        assert_eq!(parse(&printed), ast);
+    }
+}
@@ -0,0 +1,243 @@
+//! Build profiles from the project manifest. A `profiles` block holds one
+//! block per profile; `--profile name` picks one (`debug` by default):
+//!
+//! ```text
+//! profiles {
+//!     debug {
+//!         verbose: true
+//!         MainScene/HUD/Fps: { visible: true }
+//!     }
+//!     release {
+//!         MainScene: { debug_overlay: false }
+//!     }
+//! }
+//! ```
+//!
+//! `name: literal` sets a define (see `defines`), and `Root/Child: { ... }`
+//! overrides properties of the node at that path of names from a scene's
+//! root, a bare root name meaning the root itself. The transformer applies the
+//! overrides, so they reach the IR and generated code.
+
+use std::sync::RwLock;
+
+use crate::ast;
+use crate::defines::Defines;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Token;
+
+/// The profile used without `--profile`
+pub const DEFAULT: &str = "debug";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub defines: Defines,
+    pub overrides: Vec<Override>,
+}
+
+/// Properties set on the node at `path`, over the scene's own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub path: Vec<String>,
+    pub properties: Vec<ast::Property>,
+}
+
+static OVERRIDES: RwLock<Vec<Override>> = RwLock::new(Vec::new());
+
+/// Apply `overrides` to every scene compiled from now on
+pub fn set_overrides(overrides: Vec<Override>) {
+    *OVERRIDES.write().unwrap() = overrides;
+}
+
+pub fn overrides() -> Vec<Override> {
+    OVERRIDES.read().unwrap().clone()
+}
+
+/// Remove `--profile name` from `args`, returning the name
+pub fn take_flag(args: &mut Vec<String>) -> Result<String, String> {
+    let Some(at) = args.iter().position(|a| a == "--profile") else {
+        return Ok(DEFAULT.to_string());
+    };
+    if at + 1 >= args.len() {
+        return Err("--profile expects a profile name".into());
+    }
+    let name = args.remove(at + 1);
+    args.remove(at);
+    Ok(name)
+}
+
+/// The profile `name` from `manifest`, if it declares one
+pub fn parse(manifest: &str, name: &str) -> Result<Option<Profile>, String> {
+    let tokens = Lexer::new(manifest.to_string())
+        .tokenize()
+        .map_err(|e| e.to_string())?;
+    let Some(start) = tokens
+        .windows(2)
+        .position(|w| matches!(w, [Token::Ident(p), Token::LBrace] if p == "profiles"))
+    else {
+        return Ok(None);
+    };
+    let mut i = start + 2;
+    while tokens.get(i) != Some(&Token::RBrace) {
+        let (Some(Token::Ident(profile)), Some(Token::LBrace)) = (tokens.get(i), tokens.get(i + 1))
+        else {
+            return Err("Expected `name { ... }` in profiles".into());
+        };
+        let close = block_end(&tokens, i + 1)?;
+        if profile == name {
+            return parse_entries(name, &tokens[i + 2..close]).map(Some);
+        }
+        i = close + 1;
+    }
+    Ok(None)
+}
+
+fn parse_entries(name: &str, tokens: &[Token]) -> Result<Profile, String> {
+    let mut profile = Profile {
+        defines: Defines::new(),
+        overrides: Vec::new(),
+    };
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut path = Vec::new();
+        while let Some(Token::Ident(segment)) = tokens.get(i) {
+            path.push(segment.clone());
+            i += 1;
+            if tokens.get(i) != Some(&Token::Divide) {
+                break;
+            }
+            i += 1;
+        }
+        if path.is_empty() || tokens.get(i) != Some(&Token::Colon) {
+            return Err(format!(
+                "profile {}: expected `Node/Path: {{ ... }}` or `define: value`",
+                name
+            ));
+        }
+        i += 1;
+        match tokens.get(i) {
+            Some(Token::LBrace) => {
+                let close = block_end(tokens, i)?;
+                // Read the block as the body of a node to reuse property parsing
+                let mut body = vec![
+                    Token::Ident("Override".into()),
+                    Token::Colon,
+                    Token::Ident("Gem".into()),
+                ];
+                body.extend_from_slice(&tokens[i..=close]);
+                let decl = Parser::new(body)
+                    .parse_scene()
+                    .map_err(|e| format!("profile {} {}: {}", name, path.join("/"), e.message))?
+                    .root;
+                if !decl.children.is_empty() || !decl.meta.is_empty() {
+                    return Err(format!(
+                        "profile {} {}: overrides take only properties",
+                        name,
+                        path.join("/")
+                    ));
+                }
+                profile.overrides.push(Override {
+                    path,
+                    properties: decl.properties,
+                });
+                i = close + 1;
+            }
+            Some(
+                value @ (Token::Bool(_) | Token::Integer(_) | Token::Float(_) | Token::String(_)),
+            ) if path.len() == 1 => {
+                profile.defines.insert(path.remove(0), value.clone());
+                i += 1;
+            }
+            Some(Token::Ident(word)) if path.len() == 1 => {
+                profile
+                    .defines
+                    .insert(path.remove(0), Token::String(word.clone()));
+                i += 1;
+            }
+            _ => {
+                return Err(format!(
+                    "profile {}: {} takes {{ properties }}, or a literal as a define",
+                    name,
+                    path.join("/")
+                ));
+            }
+        }
+        if tokens.get(i) == Some(&Token::Comma) {
+            i += 1;
+        }
+    }
+    Ok(profile)
+}
+
+fn block_end(tokens: &[Token], open: usize) -> Result<usize, String> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LBrace => depth += 1,
+            Token::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("Unclosed block in profiles".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::PropValue;
+    use crate::pipeline;
+    use crate::transformer::Transformer;
+
+    #[test]
+    fn profiles_set_defines_and_override_properties() {
+        let manifest = r#"Scenes {
+            entry: main_menu
+            profiles {
+                debug {
+                    verbose: true, platform: web
+                    Menu/Hud/Fps: { visible: true color: #00ff00 }
+                    Menu: { title: "Menu (debug)" }
+                    Menu/Missing: { visible: true }
+                }
+                release { Menu/Hud/Fps: { visible: false } }
+            }
+        }"#;
+        let debug = parse(manifest, "debug").unwrap().unwrap();
+        assert_eq!(debug.defines["verbose"], Token::Bool(true));
+        assert_eq!(debug.defines["platform"], Token::String("web".into()));
+        assert_eq!(debug.overrides.len(), 3);
+        assert_eq!(parse(manifest, "profiling").unwrap(), None);
+        assert_eq!(parse("Scenes { entry: a }", "debug").unwrap(), None);
+        assert!(parse("profiles { debug { Menu: { Child: Gem {} } } }", "debug").is_err());
+
+        let scene = pipeline::lex_source(
+            "Menu: Gem { title: \"Menu\" Hud: Gem { Fps: LabelGem { visible: false } } }",
+        )
+        .and_then(pipeline::parse_scene)
+        .unwrap();
+        let ir = Transformer::new()
+            .with_overrides(debug.overrides)
+            .transform(scene)
+            .unwrap();
+        let property = |name: &str, key: &str| {
+            let node = ir.nodes.values().find(|n| n.name == name).unwrap();
+            node.properties[key].value.clone()
+        };
+        assert_eq!(property("Fps", "visible"), PropValue::Bool(true));
+        assert_eq!(property("Fps", "color"), PropValue::Color([0, 255, 0, 255]));
+        assert_eq!(
+            property("Menu", "title"),
+            PropValue::Str("Menu (debug)".into())
+        );
+
+        let mut args = vec!["gem".to_string(), "--profile".into(), "release".into()];
+        assert_eq!(take_flag(&mut args).unwrap(), "release");
+        assert_eq!(take_flag(&mut args).unwrap(), DEFAULT);
+        assert_eq!(args, ["gem"]);
+    }
+}
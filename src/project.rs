@@ -0,0 +1,298 @@
+//! Project-wide reference graph.
+//! Starting from the registry, follows every directive: the scenes and
+//! autoload scripts the registry names, then the directives in each scene's
+//! properties and each logic file's code. Files nothing reaches are reported
+//! as unused, and directives whose file doesn't exist as missing.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::pipeline;
+use crate::resource;
+use crate::texture;
+use crate::vfs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileKind {
+    Registry,
+    Scene,
+    Logic,
+    Asset,
+}
+
+impl FileKind {
+    /// What `path` holds: `.gem` files starting with `extend` are logic
+    pub fn of(path: &Path, content: Option<&str>) -> Self {
+        if path.extension().is_none_or(|e| e != "gem") {
+            FileKind::Asset
+        } else if content.is_some_and(|c| c.trim_start().starts_with("extend")) {
+            FileKind::Logic
+        } else {
+            FileKind::Scene
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileKind::Registry => "registry",
+            FileKind::Scene => "scene",
+            FileKind::Logic => "logic",
+            FileKind::Asset => "asset",
+        }
+    }
+}
+
+/// A directive in `from` naming a file that doesn't exist
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingRef {
+    pub from: PathBuf,
+    pub directive: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default)]
+pub struct ProjectGraph {
+    /// Every existing file reachable from the registry
+    pub files: BTreeMap<PathBuf, FileKind>,
+    /// (referencing file, referenced file)
+    pub edges: BTreeSet<(PathBuf, PathBuf)>,
+    pub missing: Vec<MissingRef>,
+    /// Files that were reached but couldn't be read or parsed
+    pub errors: Vec<String>,
+}
+
+/// Follow every directive reachable from the registry at `registry`
+pub fn build(registry: &str) -> Result<ProjectGraph, String> {
+    let content = vfs::read_to_string(registry)?;
+    let registry = PathBuf::from(registry);
+    let mut graph = ProjectGraph::default();
+    graph.files.insert(registry.clone(), FileKind::Registry);
+    let mut queue = VecDeque::new();
+    graph.follow(&registry, registry_refs(&content), &mut queue);
+
+    while let Some(path) = queue.pop_front() {
+        let name = path.to_string_lossy();
+        let content = match FileKind::of(&path, None) {
+            FileKind::Asset => None,
+            _ => match vfs::read_to_string(&name) {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    graph.errors.push(e);
+                    None
+                }
+            },
+        };
+        let kind = FileKind::of(&path, content.as_deref());
+        graph.files.insert(path.clone(), kind);
+        // An asset's import file is used along with it
+        let import = texture::import_path(&path);
+        if kind == FileKind::Asset && vfs::is_file(&import.to_string_lossy()) {
+            graph.edges.insert((path.clone(), import.clone()));
+            graph.files.insert(import, FileKind::Asset);
+        }
+        let Some(content) = content else {
+            continue;
+        };
+        match file_refs(kind, &content) {
+            Ok(refs) => graph.follow(&path, refs, &mut queue),
+            Err(e) => graph.errors.push(format!("{}: {}", name, e)),
+        }
+    }
+    Ok(graph)
+}
+
+impl ProjectGraph {
+    /// Record the edges from `from` and queue files not seen yet
+    fn follow(&mut self, from: &Path, refs: Vec<Vec<String>>, queue: &mut VecDeque<PathBuf>) {
+        for parts in refs {
+            let path = resource::resolve(&parts);
+            if !vfs::is_file(&path.to_string_lossy()) {
+                self.missing.push(MissingRef {
+                    from: from.to_path_buf(),
+                    directive: format!("#{}", parts.join(":")),
+                    path,
+                });
+                continue;
+            }
+            self.edges.insert((from.to_path_buf(), path.clone()));
+            if !self.files.contains_key(&path) && !queue.contains(&path) {
+                queue.push_back(path);
+            }
+        }
+    }
+
+    /// Files in the project's folders that nothing reaches, with their kind.
+    /// The folders are those at the registry's own depth (`example` and
+    /// `assets` for `example/scenes.registry.gem`) holding a reachable file;
+    /// hidden files and folders (such as `.gen`) are skipped.
+    pub fn unreferenced(&self) -> Vec<(PathBuf, FileKind)> {
+        let depth = self
+            .files
+            .iter()
+            .find(|(_, kind)| **kind == FileKind::Registry)
+            .map_or(1, |(path, _)| path.components().count().max(2) - 1);
+        let folders: BTreeSet<PathBuf> = self
+            .files
+            .keys()
+            .filter(|f| f.components().count() > depth)
+            .map(|f| f.components().take(depth).collect())
+            .collect();
+        let mut unused = Vec::new();
+        for folder in folders {
+            for file in vfs::list(&folder.to_string_lossy()).unwrap_or_default() {
+                let hidden = file
+                    .components()
+                    .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+                if hidden || self.files.contains_key(&file) {
+                    continue;
+                }
+                let content = vfs::read_to_string(&file.to_string_lossy()).ok();
+                let kind = FileKind::of(&file, content.as_deref());
+                unused.push((file, kind));
+            }
+        }
+        unused
+    }
+}
+
+/// `#folder:file` directives on the registry's lines
+fn registry_refs(content: &str) -> Vec<Vec<String>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("//"))
+        .flat_map(|line| line.split_whitespace())
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|d| d.trim_end_matches(','))
+        .filter(|d| !d.is_empty())
+        .map(|d| d.split(':').map(str::to_string).collect())
+        .collect()
+}
+
+fn file_refs(kind: FileKind, content: &str) -> Result<Vec<Vec<String>>, String> {
+    let tokens = pipeline::lex_source(content)?;
+    let mut refs = Vec::new();
+    match kind {
+        FileKind::Logic => refs = resource::logic_refs(&pipeline::parse_logic(tokens)?),
+        _ => {
+            let scene = pipeline::parse_scene(tokens)?;
+            let mut stack = vec![&scene.root];
+            while let Some(decl) = stack.pop() {
+                for p in decl.properties.iter().chain(&decl.meta) {
+                    resource::value_refs(&p.value, &mut refs);
+                }
+                stack.extend(decl.children.iter().rev());
+            }
+        }
+    }
+    Ok(refs)
+}
+
+/// The `gem check` report
+pub struct Report<'g> {
+    pub graph: &'g ProjectGraph,
+    pub unreferenced: Vec<(PathBuf, FileKind)>,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Reachable files: {}", self.graph.files.len())?;
+        let scenes: Vec<_> = self
+            .unreferenced
+            .iter()
+            .filter(|(_, kind)| *kind != FileKind::Asset)
+            .collect();
+        let assets: Vec<_> = self
+            .unreferenced
+            .iter()
+            .filter(|(_, kind)| *kind == FileKind::Asset)
+            .collect();
+        for (title, files) in [
+            ("Unused scenes and scripts", scenes),
+            ("Unused assets", assets),
+        ] {
+            writeln!(f, "{}: {}", title, files.len())?;
+            for (path, kind) in files {
+                writeln!(f, "  {} ({})", path.display(), kind.as_str())?;
+            }
+        }
+        writeln!(f, "Missing files: {}", self.graph.missing.len())?;
+        for m in &self.graph.missing {
+            writeln!(
+                f,
+                "  {} in {} -> {}",
+                m.directive,
+                m.from.display(),
+                m.path.display()
+            )?;
+        }
+        for e in &self.graph.errors {
+            writeln!(f, "Error: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn finds_unused_and_missing_files() {
+        let dir = Path::new("target").join("gem_project_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("logic")).unwrap();
+        fs::create_dir_all(dir.join(".gen")).unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.join(name), content).unwrap();
+        let d = "target:gem_project_test";
+        write(
+            "scenes.registry.gem",
+            &format!("Scenes {{\n    entry: menu\n    menu: #{d}:menu.gem\n}}"),
+        );
+        write(
+            "menu.gem",
+            &format!(
+                "Menu: Gem {{ Icon: PanelGem {{ texture: #{d}:icon.svg }} \
+                 Play: ButtonGem {{ link: #{d}:logic:play }} }}"
+            ),
+        );
+        write(
+            "logic/play.gem",
+            &format!("extend ButtonGem\nfn on_pressed {{\n  change_scene(#{d}:level)\n}}\n"),
+        );
+        write("icon.svg", "<svg/>");
+        write("icon.svg.import", "Import: Gem { filter: nearest }");
+        write("old_menu.gem", "Old: Gem {}");
+        write("unused.png", "");
+        write(".gen/menu.rs", "");
+
+        let graph = build("target/gem_project_test/scenes.registry.gem").unwrap();
+        assert!(graph.errors.is_empty(), "{:?}", graph.errors);
+        let base = |name: &str| dir.join(name);
+        assert_eq!(graph.files.len(), 5);
+        assert_eq!(graph.files[&base("logic/play.gem")], FileKind::Logic);
+        assert!(
+            graph
+                .edges
+                .contains(&(base("menu.gem"), base("logic/play.gem")))
+        );
+        assert!(
+            graph
+                .edges
+                .contains(&(base("icon.svg"), base("icon.svg.import")))
+        );
+        assert_eq!(graph.missing.len(), 1);
+        assert_eq!(graph.missing[0].from, base("logic/play.gem"));
+        assert_eq!(graph.missing[0].path, base("level.gem"));
+        assert_eq!(
+            graph.unreferenced(),
+            [
+                (base("old_menu.gem"), FileKind::Scene),
+                (base("unused.png"), FileKind::Asset),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,6 @@
-//! Property type inference from literal values
+//! Property types of IR values and their Rust types in generated code
+
+use crate::ir::PropValue;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PropertyType {
@@ -7,68 +9,53 @@ pub enum PropertyType {
     Float,
     Bool,
     Vec2,     // (x, y)
-    Vec3,     // (x, y, z)
-    Color,    // (r, g, b, a) or hex
+    Vec3,     // (x, y, z); values are three-number lists
+    Color,    // #rrggbb or #rrggbbaa
     SceneRef, // #path:to:scene
+    List,     // any other tuple; codegen emits a Rust tuple of its element types
+    Array,    // [a, b, ...]; codegen emits a slice of the elements' type
+    Struct,   // { key: value, ... }; codegen names a struct per property
+    Map,      // { "key": value, ... }; codegen emits a slice of (key, value) pairs
+    Curve,    // Curve((t, value, easing), ...)
+    SizeExpr, // parent.width - 20; generated code keeps the source text
 }
 
 impl PropertyType {
-    /// Infer type from literal value string
-    pub fn infer(value: &str) -> Self {
-        let trimmed = value.trim();
-
-        // Scene reference
-        if trimmed.starts_with('#') {
-            return PropertyType::SceneRef;
-        }
-
-        // Tuple literals
-        if trimmed.starts_with('(') && trimmed.ends_with(')') {
-            let inner = &trimmed[1..trimmed.len() - 1];
-            let parts: Vec<&str> = inner.split(',').map(|s| s.trim()).collect();
-            return match parts.len() {
-                2 => PropertyType::Vec2,
-                3 => PropertyType::Vec3,
-                4 => PropertyType::Color,
-                _ => PropertyType::String,
-            };
+    pub fn of(value: &PropValue) -> Self {
+        match value {
+            PropValue::Int(_) => PropertyType::Int,
+            PropValue::Float(_) => PropertyType::Float,
+            PropValue::Bool(_) => PropertyType::Bool,
+            PropValue::Str(_) => PropertyType::String,
+            PropValue::Vec2(..) => PropertyType::Vec2,
+            PropValue::Color(_) => PropertyType::Color,
+            PropValue::SceneRef(_) => PropertyType::SceneRef,
+            PropValue::List(_) => PropertyType::List,
+            PropValue::Array(_) => PropertyType::Array,
+            PropValue::Struct(_) => PropertyType::Struct,
+            PropValue::Map(_) => PropertyType::Map,
+            PropValue::Curve(_) => PropertyType::Curve,
+            PropValue::SizeExpr(_) => PropertyType::SizeExpr,
         }
-
-        // String literals
-        if trimmed.starts_with('"') && trimmed.ends_with('"') {
-            return PropertyType::String;
-        }
-
-        // Boolean
-        if trimmed == "true" || trimmed == "false" {
-            return PropertyType::Bool;
-        }
-
-        // Float (contains decimal point)
-        if trimmed.contains('.') && trimmed.parse::<f64>().is_ok() {
-            return PropertyType::Float;
-        }
-
-        // Int
-        if trimmed.parse::<i64>().is_ok() {
-            return PropertyType::Int;
-        }
-
-        // Default to string
-        PropertyType::String
     }
 
     /// Get Rust type string for static const
     pub fn to_rust_type(&self) -> &'static str {
         match self {
             PropertyType::String => "&'static str",
-            PropertyType::Int => "i32",
-            PropertyType::Float => "f32",
+            PropertyType::Int => "i64",
+            PropertyType::Float => "f64",
             PropertyType::Bool => "bool",
-            PropertyType::Vec2 => "(i32, i32)",
-            PropertyType::Vec3 => "(i32, i32, i32)",
+            PropertyType::Vec2 => "(f64, f64)",
+            PropertyType::Vec3 => "(f64, f64, f64)",
             PropertyType::Color => "(u8, u8, u8, u8)",
             PropertyType::SceneRef => "&'static str",
+            // Depend on the value's elements; codegen spells these out
+            PropertyType::List | PropertyType::Array | PropertyType::Map => "()",
+            PropertyType::Struct => "struct",
+            // (t, value, easing name) per point
+            PropertyType::Curve => "&'static [(f64, f64, &'static str)]",
+            PropertyType::SizeExpr => "&'static str",
         }
     }
 
@@ -80,58 +67,42 @@ impl PropertyType {
             _ => self.to_rust_type(), // primitives stay the same
         }
     }
-
-    /// Convert literal string to Rust const value
-    pub fn parse_to_rust_const(&self, value: &str) -> String {
-        let trimmed = value.trim();
-        match self {
-            PropertyType::String => {
-                // Keep quotes for &'static str
-                if trimmed.starts_with('"') && trimmed.ends_with('"') {
-                    trimmed.to_string()
-                } else {
-                    format!("\"{}\"", trimmed)
-                }
-            }
-            PropertyType::SceneRef => {
-                // Already has quotes typically
-                if trimmed.starts_with('"') {
-                    trimmed.to_string()
-                } else {
-                    format!("\"{}\"", trimmed)
-                }
-            }
-            PropertyType::Vec2 | PropertyType::Vec3 => {
-                // Already in tuple form: (x, y) or (x, y, z)
-                trimmed.to_string()
-            }
-            PropertyType::Color => {
-                // Parse (r, g, b, a) tuple
-                trimmed.to_string()
-            }
-            PropertyType::Bool | PropertyType::Int | PropertyType::Float => trimmed.to_string(),
-        }
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::Directive;
 
     #[test]
-    fn test_type_inference() {
-        assert_eq!(PropertyType::infer("\"hello\""), PropertyType::String);
-        assert_eq!(PropertyType::infer("42"), PropertyType::Int);
-        assert_eq!(PropertyType::infer("3.14"), PropertyType::Float);
-        assert_eq!(PropertyType::infer("true"), PropertyType::Bool);
-        assert_eq!(PropertyType::infer("(100, 200)"), PropertyType::Vec2);
-        assert_eq!(PropertyType::infer("(1, 2, 3)"), PropertyType::Vec3);
+    fn test_type_of_values() {
+        assert_eq!(
+            PropertyType::of(&PropValue::Str("hello".into())),
+            PropertyType::String
+        );
+        assert_eq!(PropertyType::of(&PropValue::Int(42)), PropertyType::Int);
+        assert_eq!(
+            PropertyType::of(&PropValue::Float(2.5)),
+            PropertyType::Float
+        );
+        assert_eq!(PropertyType::of(&PropValue::Bool(true)), PropertyType::Bool);
+        assert_eq!(
+            PropertyType::of(&PropValue::Vec2(100.0, 200.0)),
+            PropertyType::Vec2
+        );
+        assert_eq!(
+            PropertyType::of(&PropValue::List(vec![PropValue::Int(1); 3])),
+            PropertyType::List
+        );
         assert_eq!(
-            PropertyType::infer("(255, 128, 0, 255)"),
+            PropertyType::of(&PropValue::Color([255, 128, 0, 255])),
             PropertyType::Color
         );
         assert_eq!(
-            PropertyType::infer("#example:scene"),
+            PropertyType::of(&PropValue::SceneRef(Directive::new(vec![
+                "example".into(),
+                "scene".into()
+            ]))),
             PropertyType::SceneRef
         );
     }
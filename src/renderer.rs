@@ -1,13 +1,273 @@
+use crate::batch::{self, Batch};
+use crate::bitmap_font;
+use crate::container;
 use crate::display::GemDisplay;
+use crate::draw_list::{DrawCmd, DrawList, LightCmd, OccluderCmd, TextCmd};
+use crate::font::{self, FaceId, FontRegistry};
+use crate::log::verbose;
+use crate::mesh::{self, Mesh, MeshRef};
+use crate::performance;
+use crate::svg::{self, SvgImage};
+use crate::text::{LaidOut, TextLayout};
+use crate::texture::{Filter, Texture, TextureOptions};
+use crate::vfs;
 use glutin::display::GetGlDisplay;
 use glutin::prelude::GlDisplay;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::path::{Path, PathBuf};
+
+/// How a drawable composites onto what is already in the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    /// Parse a `blend_mode` property value (e.g. `blend_mode: add`)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(BlendMode::Normal),
+            "add" => Some(BlendMode::Add),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            _ => None,
+        }
+    }
+
+    fn gl_factors(self) -> (gl::types::GLenum, gl::types::GLenum) {
+        match self {
+            BlendMode::Normal => (gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Add => (gl::SRC_ALPHA, gl::ONE),
+            // The shader premultiplies in these modes (see `premultiplies`)
+            BlendMode::Multiply => (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Screen => (gl::ONE, gl::ONE_MINUS_SRC_COLOR),
+        }
+    }
+
+    /// Whether the shader multiplies its color by its alpha first. Multiply
+    /// blends as `src * dst + dst * (1 - src_alpha)` and screen as
+    /// `src + dst * (1 - src)`, which only fade out transparent pixels when
+    /// `src` is premultiplied, and sprites and textures are straight alpha.
+    fn premultiplies(self) -> bool {
+        matches!(self, BlendMode::Multiply | BlendMode::Screen)
+    }
+}
+
+/// Maximum number of color stops the gradient shader path accepts
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GradientMode {
+    #[default]
+    Linear,
+    Radial,
+}
+
+impl GradientMode {
+    /// Parse a `gradient_mode` property value
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(GradientMode::Linear),
+            "radial" => Some(GradientMode::Radial),
+            _ => None,
+        }
+    }
+}
+
+/// A multi-stop gradient fill, e.g. `gradient: ((0, #112233), (1, #334455))`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub mode: GradientMode,
+    /// Direction of linear gradients in degrees; 90 runs top to bottom
+    pub angle: f32,
+    /// (offset in 0..1, rgba) pairs, sorted by offset
+    pub stops: Vec<(f32, [f32; 4])>,
+}
+
+/// Fragment shading path selected per draw via uniforms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shading {
+    Flat,
+    Textured,
+    RadialLight,
+    Gradient(GradientMode),
+}
+
+/// A mesh's vertices uploaded for the 3D pass
+struct GpuMesh {
+    vao: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+    vertex_count: i32,
+}
+
+/// Offscreen target the lighting pass accumulates lights into
+struct LightBuffer {
+    fbo: gl::types::GLuint,
+    texture: gl::types::GLuint,
+    depth_stencil: gl::types::GLuint,
+    width: u32,
+    height: u32,
+}
+
+/// Built-in quad shaders; `shaders/quad.vert` and `shaders/quad.frag` in the
+/// project override them
+const VERTEX_SHADER_SRC: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec4 aColor;
+
+out vec2 TexCoord;
+out vec4 Color;
+
+uniform mat4 projection;
+
+void main() {
+    gl_Position = projection * vec4(aPos, 0.0, 1.0);
+    TexCoord = aTexCoord;
+    Color = aColor;
+}
+"#;
+
+/// Vertex shader for instanced runs: the unit quad is placed by each
+/// instance's NDC rect (center, size), with its own texture coordinates
+/// (u0, v0, u1, v1) and color. It feeds the same fragment shader.
+const INSTANCED_VERTEX_SHADER_SRC: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 3) in vec4 iRect;
+layout (location = 4) in vec4 iUv;
+layout (location = 5) in vec4 iColor;
+
+out vec2 TexCoord;
+out vec4 Color;
+
+void main() {
+    gl_Position = vec4(iRect.xy + aPos * iRect.zw, 0.0, 1.0);
+    TexCoord = mix(iUv.xy, iUv.zw, aTexCoord);
+    Color = iColor;
+}
+"#;
+
+/// Floats per instance: rect, uv and color
+const INSTANCE_FLOATS: usize = 12;
+
+/// Mesh shaders for the 3D pass: flat color lit by one light from above
+/// and a fixed ambient term
+const MESH_VERTEX_SHADER_SRC: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+
+out vec3 Normal;
+
+uniform mat4 model;
+uniform mat4 viewProjection;
+
+void main() {
+    gl_Position = viewProjection * model * vec4(aPos, 1.0);
+    Normal = mat3(model) * aNormal;
+}
+"#;
+
+const MESH_FRAGMENT_SHADER_SRC: &str = r#"
+#version 330 core
+in vec3 Normal;
+out vec4 FragColor;
+
+uniform vec4 color;
+
+void main() {
+    vec3 toLight = normalize(vec3(0.4, 1.0, 0.6));
+    float diffuse = max(dot(normalize(Normal), toLight), 0.0);
+    FragColor = vec4(color.rgb * (0.35 + 0.65 * diffuse), color.a);
+}
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+#version 330 core
+in vec2 TexCoord;
+in vec4 Color;
+out vec4 FragColor;
+
+uniform sampler2D texture1;
+uniform bool useTexture;
+uniform bool radialLight;
+uniform int gradientMode; // 0 = none, 1 = linear, 2 = radial
+uniform vec2 gradientDir;
+uniform int stopCount;
+uniform float stopOffsets[8];
+uniform vec4 stopColors[8];
+uniform bool premultiply;
+
+vec4 sampleGradient(float t) {
+    t = clamp(t, 0.0, 1.0);
+    vec4 c = stopColors[0];
+    for (int i = 1; i < 8; i++) {
+        if (i >= stopCount) break;
+        float a = stopOffsets[i - 1];
+        float b = stopOffsets[i];
+        if (t >= a) {
+            float k = b > a ? clamp((t - a) / (b - a), 0.0, 1.0) : 1.0;
+            c = mix(stopColors[i - 1], stopColors[i], k);
+        }
+    }
+    return c;
+}
+
+void main() {
+    if (gradientMode == 1) {
+        float t = dot(TexCoord - vec2(0.5), gradientDir) + 0.5;
+        FragColor = sampleGradient(t) * Color;
+    } else if (gradientMode == 2) {
+        float t = length(TexCoord - vec2(0.5)) * 2.0;
+        FragColor = sampleGradient(t) * Color;
+    } else if (radialLight) {
+        float d = length(TexCoord - vec2(0.5)) * 2.0;
+        float falloff = clamp(1.0 - d, 0.0, 1.0);
+        FragColor = vec4(Color.rgb * falloff * falloff, 1.0);
+    } else if (useTexture) {
+        FragColor = texture(texture1, TexCoord) * Color;
+    } else {
+        FragColor = Color;
+    }
+    if (premultiply) {
+        FragColor.rgb *= FragColor.a;
+    }
+}
+"#;
 
 pub struct GemRenderer {
     program: gl::types::GLuint,
     vao: gl::types::GLuint,
     vbo: gl::types::GLuint,
     ebo: gl::types::GLuint,
+    /// Program and buffers for instanced runs of quads (see `batch`)
+    instanced_program: gl::types::GLuint,
+    instance_vao: gl::types::GLuint,
+    instance_quad_vbo: gl::types::GLuint,
+    instance_vbo: gl::types::GLuint,
+    blend_mode: BlendMode,
+    viewport: (u32, u32),
+    light_buffer: Option<LightBuffer>,
+    font_atlas: gl::types::GLuint,
+    /// Uploaded glyph pages per (face, pixel size) with the generation they hold
+    glyph_pages: HashMap<(FaceId, u32), (gl::types::GLuint, u32)>,
+    /// Parsed SVG documents; `None` remembers files that failed to load
+    svg_images: HashMap<PathBuf, Option<SvgImage>>,
+    /// Rasterized SVGs per (path, pixel width, pixel height, sampling)
+    svg_textures: HashMap<(PathBuf, u32, u32, TextureOptions), gl::types::GLuint>,
+    /// Physical pixels per layout pixel (the window's DPI scale)
+    pixel_ratio: f64,
+    mesh_program: gl::types::GLuint,
+    /// Uploaded meshes; `None` remembers files that failed to load
+    meshes: HashMap<MeshRef, Option<GpuMesh>>,
 }
 
 impl GemRenderer {
@@ -21,21 +281,47 @@ impl GemRenderer {
                 .cast()
         });
 
-        println!("[GemRenderer] OpenGL loaded");
+        verbose!("[GemRenderer] OpenGL loaded");
 
         let version = unsafe {
             let data = gl::GetString(gl::VERSION) as *const i8;
             std::ffi::CStr::from_ptr(data).to_str().unwrap().to_string()
         };
-        println!("[GemRenderer] OpenGL version: {}", version);
+        verbose!("[GemRenderer] OpenGL version: {}", version);
 
-        let program = unsafe { Self::create_shader_program() };
+        let program = unsafe {
+            Self::create_shader_program(VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC)
+                .expect("Built-in shaders failed to compile")
+        };
 
         let (vao, vbo, ebo) = unsafe { Self::create_quad_buffers() };
+        let instanced_program = unsafe {
+            Self::create_shader_program(INSTANCED_VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC)
+                .expect("Built-in shaders failed to compile")
+        };
+        let (instance_vao, instance_quad_vbo, instance_vbo) =
+            unsafe { Self::create_instance_buffers(ebo) };
+        let mesh_program = unsafe {
+            Self::create_shader_program(MESH_VERTEX_SHADER_SRC, MESH_FRAGMENT_SHADER_SRC)
+                .expect("Built-in shaders failed to compile")
+        };
+
+        let font_atlas = unsafe {
+            Self::upload_texture(
+                bitmap_font::ATLAS_W as u32,
+                bitmap_font::ATLAS_H as u32,
+                &bitmap_font::atlas_rgba(),
+                TextureOptions {
+                    filter: Filter::Nearest,
+                    ..TextureOptions::default()
+                },
+            )
+        };
 
         unsafe {
             gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            let (src, dst) = BlendMode::Normal.gl_factors();
+            gl::BlendFunc(src, dst);
             gl::ClearColor(0.1, 0.1, 0.15, 1.0);
         }
 
@@ -44,48 +330,36 @@ impl GemRenderer {
             vao,
             vbo,
             ebo,
+            instanced_program,
+            instance_vao,
+            instance_quad_vbo,
+            instance_vbo,
+            blend_mode: BlendMode::Normal,
+            viewport: (800, 600),
+            light_buffer: None,
+            font_atlas,
+            glyph_pages: HashMap::new(),
+            svg_images: HashMap::new(),
+            svg_textures: HashMap::new(),
+            pixel_ratio: 1.0,
+            mesh_program,
+            meshes: HashMap::new(),
         }
     }
 
-    unsafe fn create_shader_program() -> gl::types::GLuint {
-        let vertex_src = r#"
-            #version 330 core
-            layout (location = 0) in vec2 aPos;
-            layout (location = 1) in vec2 aTexCoord;
-            layout (location = 2) in vec4 aColor;
-            
-            out vec2 TexCoord;
-            out vec4 Color;
-            
-            uniform mat4 projection;
-            
-            void main() {
-                gl_Position = projection * vec4(aPos, 0.0, 1.0);
-                TexCoord = aTexCoord;
-                Color = aColor;
-            }
-        "#;
-
-        let fragment_src = r#"
-            #version 330 core
-            in vec2 TexCoord;
-            in vec4 Color;
-            out vec4 FragColor;
-            
-            uniform sampler2D texture1;
-            uniform bool useTexture;
-            
-            void main() {
-                if (useTexture) {
-                    FragColor = texture(texture1, TexCoord) * Color;
-                } else {
-                    FragColor = Color;
+    unsafe fn create_shader_program(
+        vertex_src: &str,
+        fragment_src: &str,
+    ) -> Result<gl::types::GLuint, String> {
+        let vertex_shader = unsafe { Self::compile_shader(vertex_src, gl::VERTEX_SHADER)? };
+        let fragment_shader =
+            match unsafe { Self::compile_shader(fragment_src, gl::FRAGMENT_SHADER) } {
+                Ok(shader) => shader,
+                Err(e) => {
+                    unsafe { gl::DeleteShader(vertex_shader) };
+                    return Err(e);
                 }
-            }
-        "#;
-
-        let vertex_shader = unsafe { Self::compile_shader(vertex_src, gl::VERTEX_SHADER) };
-        let fragment_shader = unsafe { Self::compile_shader(fragment_src, gl::FRAGMENT_SHADER) };
+            };
 
         let program = unsafe { gl::CreateProgram() };
         unsafe {
@@ -108,10 +382,15 @@ impl GemRenderer {
             unsafe {
                 gl::GetProgramInfoLog(program, len, &mut len, buffer.as_mut_ptr() as *mut i8);
             }
-            panic!(
+            unsafe {
+                gl::DeleteShader(vertex_shader);
+                gl::DeleteShader(fragment_shader);
+                gl::DeleteProgram(program);
+            }
+            return Err(format!(
                 "Program linking failed: {}",
                 String::from_utf8_lossy(&buffer)
-            );
+            ));
         }
 
         unsafe {
@@ -119,12 +398,15 @@ impl GemRenderer {
             gl::DeleteShader(fragment_shader);
         }
 
-        println!("[GemRenderer] Shader program created");
+        verbose!("[GemRenderer] Shader program created");
 
-        program
+        Ok(program)
     }
 
-    unsafe fn compile_shader(src: &str, shader_type: gl::types::GLenum) -> gl::types::GLuint {
+    unsafe fn compile_shader(
+        src: &str,
+        shader_type: gl::types::GLenum,
+    ) -> Result<gl::types::GLuint, String> {
         let shader = unsafe { gl::CreateShader(shader_type) };
         let c_str = CString::new(src.as_bytes()).unwrap();
         unsafe {
@@ -146,13 +428,14 @@ impl GemRenderer {
             unsafe {
                 gl::GetShaderInfoLog(shader, len, &mut len, buffer.as_mut_ptr() as *mut i8);
             }
-            panic!(
+            unsafe { gl::DeleteShader(shader) };
+            return Err(format!(
                 "Shader compilation failed: {}",
                 String::from_utf8_lossy(&buffer)
-            );
+            ));
         }
 
-        shader
+        Ok(shader)
     }
 
     unsafe fn create_quad_buffers() -> (gl::types::GLuint, gl::types::GLuint, gl::types::GLuint) {
@@ -226,34 +509,731 @@ impl GemRenderer {
             gl::BindVertexArray(0);
         }
 
-        println!(
+        verbose!(
             "[GemRenderer] Quad buffers created (VAO: {}, VBO: {}, EBO: {})",
-            vao, vbo, ebo
+            vao,
+            vbo,
+            ebo
         );
 
         (vao, vbo, ebo)
     }
 
-    pub fn begin_frame(&self) {
+    /// A unit quad (texcoords 0..1 from its top-left corner) sharing the
+    /// quad index buffer, plus an empty per-instance buffer filled per run
+    unsafe fn create_instance_buffers(
+        ebo: gl::types::GLuint,
+    ) -> (gl::types::GLuint, gl::types::GLuint, gl::types::GLuint) {
+        #[rustfmt::skip]
+        let vertices: [f32; 16] = [
+            // positions   // texcoords
+            -0.5,  0.5,    0.0, 0.0,  // top-left
+             0.5,  0.5,    1.0, 0.0,  // top-right
+             0.5, -0.5,    1.0, 1.0,  // bottom-right
+            -0.5, -0.5,    0.0, 1.0,  // bottom-left
+        ];
+        let float = std::mem::size_of::<f32>();
+
+        let mut vao = 0;
+        let mut quad_vbo = 0;
+        let mut instance_vbo = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut quad_vbo);
+            gl::GenBuffers(1, &mut instance_vbo);
+            gl::BindVertexArray(vao);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, quad_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&vertices) as isize,
+                vertices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            let stride = 4 * float as i32;
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (2 * float) as *const _);
+            gl::EnableVertexAttribArray(1);
+
+            // Rect, uv and color advance once per instance
+            gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
+            let stride = (INSTANCE_FLOATS * float) as i32;
+            for (i, location) in (3..6).enumerate() {
+                gl::VertexAttribPointer(
+                    location,
+                    4,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    (i * 4 * float) as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+            gl::BindVertexArray(0);
+        }
+        (vao, quad_vbo, instance_vbo)
+    }
+
+    pub fn begin_frame(&mut self) {
+        performance::begin_draw();
         unsafe {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
+        self.set_blend_mode(BlendMode::Normal);
+    }
+
+    /// Switch the blend function for subsequent quads. Consecutive draws with
+    /// the same mode share GL state, so a batch is only split on a mode change.
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        if self.blend_mode == mode {
+            return;
+        }
+        let (src, dst) = mode.gl_factors();
+        unsafe {
+            gl::BlendFunc(src, dst);
+        }
+        self.blend_mode = mode;
     }
 
     pub fn render_quad(&self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.render_quad_shaded(x, y, width, height, color, Shading::Flat);
+    }
+
+    fn render_quad_shaded(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [f32; 4],
+        shading: Shading,
+    ) {
+        let mut model = [0.0f32; 16];
+        model[0] = width;
+        model[5] = height;
+        model[10] = 1.0;
+        model[12] = x;
+        model[13] = y;
+        model[15] = 1.0;
+
+        let projection = Self::ortho_matrix(-1.0, 1.0, -1.0, 1.0);
+        let mvp = Self::multiply_matrices(&projection, &model);
+
+        #[rustfmt::skip]
+        let vertices: [f32; 32] = [
+            // positions   // texcoords  // colors
+            -0.5,  0.5,    0.0, 1.0,     color[0], color[1], color[2], color[3],
+             0.5,  0.5,    1.0, 1.0,     color[0], color[1], color[2], color[3],
+             0.5, -0.5,    1.0, 0.0,     color[0], color[1], color[2], color[3],
+            -0.5, -0.5,    0.0, 0.0,     color[0], color[1], color[2], color[3],
+        ];
+
+        self.draw_vertices(&vertices, &mvp, shading);
+    }
+
+    /// Draw a textured quad given as NDC (center x, center y, width, height).
+    /// `uv` is (u0, v0, u1, v1) with (u0, v0) mapped to the top-left corner.
+    pub fn render_textured_quad(
+        &self,
+        ndc_rect: (f32, f32, f32, f32),
+        uv: [f32; 4],
+        color: [f32; 4],
+        texture: gl::types::GLuint,
+    ) {
+        let (x, y, width, height) = ndc_rect;
+        let mut model = [0.0f32; 16];
+        model[0] = width;
+        model[5] = height;
+        model[10] = 1.0;
+        model[12] = x;
+        model[13] = y;
+        model[15] = 1.0;
+
+        let projection = Self::ortho_matrix(-1.0, 1.0, -1.0, 1.0);
+        let mvp = Self::multiply_matrices(&projection, &model);
+        let [u0, v0, u1, v1] = uv;
+
+        #[rustfmt::skip]
+        let vertices: [f32; 32] = [
+            // positions   // texcoords  // colors
+            -0.5,  0.5,    u0, v0,       color[0], color[1], color[2], color[3],
+             0.5,  0.5,    u1, v0,       color[0], color[1], color[2], color[3],
+             0.5, -0.5,    u1, v1,       color[0], color[1], color[2], color[3],
+            -0.5, -0.5,    u0, v1,       color[0], color[1], color[2], color[3],
+        ];
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        self.draw_vertices(&vertices, &mvp, Shading::Textured);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Draw a whole scene: meshes, then quads in order, then text, then the
+    /// lighting pass
+    pub fn render_draw_list(&mut self, list: &DrawList) {
+        if !list.meshes.is_empty() {
+            self.render_meshes(list);
+        }
+        let (quads_from, texts_from) = list.overlay_start();
+        if list.views.is_empty() {
+            self.render_layer(list, &list.quads[..quads_from], &list.texts[..texts_from]);
+            if !list.lights.is_empty() {
+                self.render_lighting(&list.lights, &list.occluders, list.ambient);
+            }
+        } else {
+            self.render_views(list);
+        }
+
+        // Canvas layers and popups go over the lit scene, the popups behind
+        // an optional dimming tint
+        if let Some(overlay) = list.overlay {
+            let (popup_quads, popup_texts) = (overlay.popup_quads_from, overlay.popup_texts_from);
+            self.render_layer(
+                list,
+                &list.quads[quads_from..popup_quads],
+                &list.texts[texts_from..popup_texts],
+            );
+            if let Some(dim) = overlay.dim {
+                self.set_blend_mode(BlendMode::Normal);
+                let (fb_w, fb_h) = (self.viewport.0 as f32, self.viewport.1 as f32);
+                self.render_px_quad(0.0, 0.0, fb_w, fb_h, dim);
+            }
+            self.render_layer(list, &list.quads[popup_quads..], &list.texts[popup_texts..]);
+        }
+    }
+
+    /// The 3D pass, under everything 2D: meshes hide each other by depth
+    fn render_meshes(&mut self, list: &DrawList) {
+        let uniform = |name: &str| unsafe {
+            gl::GetUniformLocation(self.mesh_program, CString::new(name).unwrap().as_ptr())
+        };
+        let (model, view_projection, color) = (
+            uniform("model"),
+            uniform("viewProjection"),
+            uniform("color"),
+        );
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::UseProgram(self.mesh_program);
+            gl::UniformMatrix4fv(
+                view_projection,
+                1,
+                gl::FALSE,
+                list.view_projection.0.as_ptr(),
+            );
+        }
+        for cmd in &list.meshes {
+            let Some((vao, vertex_count)) = self.mesh(&cmd.mesh) else {
+                continue;
+            };
+            unsafe {
+                gl::UniformMatrix4fv(model, 1, gl::FALSE, cmd.model.0.as_ptr());
+                gl::Uniform4fv(color, 1, cmd.color.as_ptr());
+                gl::BindVertexArray(vao);
+                gl::DrawArrays(gl::TRIANGLES, 0, vertex_count);
+            }
+            performance::count_draw_call();
+        }
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::Disable(gl::DEPTH_TEST);
+        }
+    }
+
+    /// The VAO and vertex count of `source`, uploaded on first use
+    fn mesh(&mut self, source: &MeshRef) -> Option<(gl::types::GLuint, i32)> {
+        let mesh = self.meshes.entry(source.clone()).or_insert_with(|| {
+            mesh::load(source)
+                .map_err(|e| eprintln!("[WARN] Failed to load mesh {}", e))
+                .ok()
+                .map(|m| unsafe { Self::upload_mesh(&m) })
+        });
+        mesh.as_ref().map(|m| (m.vao, m.vertex_count))
+    }
+
+    /// Interleaved position and normal per vertex
+    unsafe fn upload_mesh(mesh: &Mesh) -> GpuMesh {
+        let data: Vec<f32> = mesh
+            .vertices
+            .iter()
+            .flat_map(|v| v.position.into_iter().chain(v.normal))
+            .collect();
+        let float = std::mem::size_of::<f32>();
+        let (mut vao, mut vbo) = (0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (data.len() * float) as isize,
+                data.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            let stride = 6 * float as i32;
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, (3 * float) as *const _);
+            gl::EnableVertexAttribArray(1);
+            gl::BindVertexArray(0);
+        }
+        GpuMesh {
+            vao,
+            vbo,
+            vertex_count: mesh.vertices.len() as i32,
+        }
+    }
+
+    /// Canvas layers behind the world, then each split-screen view clipped to
+    /// its rect and lit by its own lights
+    fn render_views(&mut self, list: &DrawList) {
+        let first = &list.views[0];
+        self.render_layer(
+            list,
+            &list.quads[..first.quads.start],
+            &list.texts[..first.texts.start],
+        );
+        for view in &list.views {
+            self.set_clip(Some(view.rect));
+            let quads = &list.quads[view.quads.clone()];
+            self.render_layer(list, quads, &list.texts[view.texts.clone()]);
+            if !view.lights.is_empty() {
+                let lights = &list.lights[view.lights.clone()];
+                self.render_lighting(
+                    lights,
+                    &list.occluders[view.occluders.clone()],
+                    list.ambient,
+                );
+            }
+        }
+        self.set_clip(None);
+    }
+
+    /// Only draw inside a pixel rect of the window, or anywhere again
+    fn set_clip(&self, rect: Option<container::Rect>) {
+        let Some(((x, y), (w, h))) = rect else {
+            unsafe { gl::Disable(gl::SCISSOR_TEST) };
+            return;
+        };
+        // GL counts rows from the bottom
+        let bottom = self.viewport.1 as f32 - (y + h);
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x as i32, bottom as i32, w as i32, h as i32);
+        }
+    }
+
+    fn render_layer(&mut self, list: &DrawList, quads: &[DrawCmd], texts: &[TextCmd]) {
+        for b in batch::batches(quads) {
+            match b {
+                Batch::Single(i) => self.render_cmd(&quads[i]),
+                Batch::Instanced(range) => self.render_instanced(&quads[range]),
+            }
+        }
+
+        // Text has no blend mode of its own, so it mustn't inherit the last
+        // batch's
+        self.set_blend_mode(BlendMode::Normal);
+        for t in texts {
+            self.render_text((t.x, t.y), &t.layout, &list.fonts);
+        }
+    }
+
+    fn render_cmd(&mut self, d: &DrawCmd) {
+        self.set_blend_mode(d.blend);
+        match (&d.texture, &d.gradient) {
+            (Some(texture), _) => self.render_svg(d.x, d.y, d.w, d.h, texture, [1.0; 4]),
+            (None, Some(g)) => {
+                let (cx, cy, w, h) = self.px_rect_to_ndc(d.x, d.y, d.w, d.h);
+                self.render_gradient_quad(cx, cy, w, h, g);
+            }
+            (None, None) => self.render_px_quad(d.x, d.y, d.w, d.h, d.color),
+        }
+    }
+
+    /// Draw a run of quads that `batch` found to share their state with one
+    /// instanced call
+    fn render_instanced(&mut self, quads: &[DrawCmd]) {
+        let first = &quads[0];
+        self.set_blend_mode(first.blend);
+        let texture = match &first.texture {
+            Some(t) => match self.svg_texture(t, first.w, first.h) {
+                Some(found) => Some(found),
+                None => return,
+            },
+            None => None,
+        };
+        let mut instances = Vec::with_capacity(quads.len() * INSTANCE_FLOATS);
+        for q in quads {
+            let (cx, cy, w, h) = self.px_rect_to_ndc(q.x, q.y, q.w, q.h);
+            let (uv, color) = match (&q.texture, texture) {
+                (Some(t), Some((_, size))) => (Self::svg_uv(t, size, q.w, q.h), [1.0; 4]),
+                _ => ([0.0, 0.0, 1.0, 1.0], q.color),
+            };
+            instances.extend_from_slice(&[cx, cy, w, h]);
+            instances.extend_from_slice(&uv);
+            instances.extend_from_slice(&color);
+        }
+        unsafe {
+            gl::UseProgram(self.instanced_program);
+            let uniform = |name: &str| {
+                gl::GetUniformLocation(self.instanced_program, CString::new(name).unwrap().as_ptr())
+            };
+            gl::Uniform1i(uniform("useTexture"), texture.is_some() as i32);
+            gl::Uniform1i(uniform("radialLight"), 0);
+            gl::Uniform1i(uniform("gradientMode"), 0);
+            gl::Uniform1i(
+                uniform("premultiply"),
+                self.blend_mode.premultiplies() as i32,
+            );
+            if let Some((gl_texture, _)) = texture {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, gl_texture);
+            }
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(instances.as_slice()) as isize,
+                instances.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            gl::BindVertexArray(self.instance_vao);
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                6,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+                quads.len() as i32,
+            );
+            performance::count_draw_call();
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Line segment `width` pixels thick between two pixel-space points
+    pub fn render_line(&self, from: (f32, f32), to: (f32, f32), width: f32, color: [f32; 4]) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 {
+            return;
+        }
+        let (nx, ny) = (-dy / len * width * 0.5, dx / len * width * 0.5);
+        let (fb_w, fb_h) = (self.viewport.0 as f32, self.viewport.1 as f32);
+        let ndc = |x: f32, y: f32| (x / fb_w * 2.0 - 1.0, 1.0 - y / fb_h * 2.0);
+        self.render_ndc_quad(
+            [
+                ndc(from.0 + nx, from.1 + ny),
+                ndc(to.0 + nx, to.1 + ny),
+                ndc(to.0 - nx, to.1 - ny),
+                ndc(from.0 - nx, from.1 - ny),
+            ],
+            color,
+        );
+    }
+
+    /// Flat quad from a pixel-space rect (top-left origin)
+    pub fn render_px_quad(&self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        let (cx, cy, nw, nh) = self.px_rect_to_ndc(x, y, w, h);
+        self.render_quad(cx, cy, nw, nh, color);
+    }
+
+    /// Convert a pixel-space rect (top-left origin) into NDC center + size
+    fn px_rect_to_ndc(&self, x: f32, y: f32, w: f32, h: f32) -> (f32, f32, f32, f32) {
+        let (fb_w, fb_h) = (self.viewport.0 as f32, self.viewport.1 as f32);
+        let cx = ((x + w * 0.5) / fb_w) * 2.0 - 1.0;
+        let cy = -(((y + h * 0.5) / fb_h) * 2.0 - 1.0);
+        (cx, cy, w / fb_w * 2.0, h / fb_h * 2.0)
+    }
+
+    /// Draw a laid-out text block with its top-left corner at `origin` (pixels)
+    pub fn render_text(&mut self, origin: (f32, f32), layout: &TextLayout, fonts: &FontRegistry) {
+        for item in &layout.items {
+            match item {
+                LaidOut::Glyph {
+                    ch,
+                    x,
+                    y,
+                    font_size,
+                    bold,
+                    color,
+                    face,
+                } => {
+                    if ch.is_whitespace() {
+                        continue;
+                    }
+                    let passes = if *bold { 2 } else { 1 };
+                    if let Some(face) = face {
+                        let Some(slot) = fonts.glyph(*face, *ch, *font_size) else {
+                            continue;
+                        };
+                        let texture = self.glyph_page(fonts, *face, *font_size);
+                        for pass in 0..passes {
+                            let rect = self.px_rect_to_ndc(
+                                origin.0 + x + slot.offset.0 + pass as f32,
+                                origin.1 + y + slot.offset.1,
+                                slot.size.0,
+                                slot.size.1,
+                            );
+                            self.render_textured_quad(rect, slot.uv, *color, texture);
+                        }
+                        continue;
+                    }
+                    let scale = bitmap_font::BitmapFont::scale(*font_size);
+                    let w = bitmap_font::CELL_W as f32 * scale;
+                    let h = bitmap_font::CELL_H as f32 * scale;
+                    let uv = bitmap_font::glyph_uv(*ch);
+                    // Faux bold: overdraw one font pixel to the right
+                    for pass in 0..passes {
+                        let dx = pass as f32 * scale;
+                        let rect = self.px_rect_to_ndc(origin.0 + x + dx, origin.1 + y, w, h);
+                        self.render_textured_quad(rect, uv, *color, self.font_atlas);
+                    }
+                }
+                LaidOut::Underline { x, y, w, h, color } => {
+                    self.render_px_quad(origin.0 + x, origin.1 + y, *w, *h, *color);
+                }
+                LaidOut::Image { x, y, w, h, .. } => {
+                    // No image decoding yet: reserve the slot with a neutral box
+                    self.render_px_quad(origin.0 + x, origin.1 + y, *w, *h, [0.5, 0.5, 0.5, 0.8]);
+                }
+            }
+        }
+    }
+
+    /// Texture for a glyph page, re-uploaded when new glyphs were rasterized into it
+    fn glyph_page(
+        &mut self,
+        fonts: &FontRegistry,
+        face: FaceId,
+        font_size: f32,
+    ) -> gl::types::GLuint {
+        let key = (face, font::size_key(font_size));
+        let current = self.glyph_pages.get(&key).copied();
+        let uploaded = fonts.with_page(face, font_size, |page| match current {
+            Some((texture, generation)) if generation == page.generation => (texture, generation),
+            _ => unsafe {
+                if let Some((old, _)) = current {
+                    gl::DeleteTextures(1, &old);
+                }
+                let texture = Self::upload_texture(
+                    font::PAGE_SIZE,
+                    font::PAGE_SIZE,
+                    &page.pixels,
+                    TextureOptions::default(),
+                );
+                (texture, page.generation)
+            },
+        });
+        match uploaded {
+            Some(entry) => {
+                self.glyph_pages.insert(key, entry);
+                entry.0
+            }
+            None => 0,
+        }
+    }
+
+    /// Draw an SVG over a pixel-space rect. With default options it's
+    /// stretched, rasterized and cached per display size, so resized nodes and
+    /// DPI changes re-rasterize it; other options rasterize it once at its own
+    /// size and let the GPU filter, tile and mipmap it.
+    pub fn render_svg(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        texture: &Texture,
+        color: [f32; 4],
+    ) {
+        let Some((gl_texture, size)) = self.svg_texture(texture, w, h) else {
+            return;
+        };
+        let rect = self.px_rect_to_ndc(x, y, w, h);
+        let uv = Self::svg_uv(texture, size, w, h);
+        self.render_textured_quad(rect, uv, color, gl_texture);
+    }
+
+    /// The GL texture for an SVG drawn at `w`x`h`, rasterized on first use,
+    /// with the image's own size
+    fn svg_texture(
+        &mut self,
+        texture: &Texture,
+        w: f32,
+        h: f32,
+    ) -> Option<(gl::types::GLuint, (f32, f32))> {
+        let Texture { path, options, .. } = texture;
+        let image = self.svg_images.entry(path.clone()).or_insert_with(|| {
+            svg::load(path)
+                .map_err(|e| eprintln!("[WARN] Failed to load svg {}", e))
+                .ok()
+        });
+        let image = image.as_ref()?;
+        let (_, _, vw, vh) = image.view_box;
+        let (size_w, size_h) = if texture.rasterized_at_display_size() {
+            (w, h)
+        } else {
+            (vw, vh)
+        };
+        let px_w = (size_w as f64 * self.pixel_ratio).ceil() as u32;
+        let px_h = (size_h as f64 * self.pixel_ratio).ceil() as u32;
+        if px_w == 0 || px_h == 0 {
+            return None;
+        }
+        let key = (path.clone(), px_w, px_h, *options);
+        let gl_texture = match self.svg_textures.get(&key) {
+            Some(gl_texture) => *gl_texture,
+            None => {
+                let pixels = image.rasterize(px_w, px_h)?;
+                let gl_texture = unsafe { Self::upload_texture(px_w, px_h, &pixels, *options) };
+                self.svg_textures.insert(key, gl_texture);
+                gl_texture
+            }
+        };
+        Some((gl_texture, (vw, vh)))
+    }
+
+    /// A region picks part of the image; repeating textures tile at the
+    /// image's own size
+    fn svg_uv(texture: &Texture, (vw, vh): (f32, f32), w: f32, h: f32) -> [f32; 4] {
+        if let Some([rx, ry, rw, rh]) = texture.region {
+            [rx / vw, ry / vh, (rx + rw) / vw, (ry + rh) / vh]
+        } else if texture.options.repeat {
+            [0.0, 0.0, w / vw, h / vh]
+        } else {
+            [0.0, 0.0, 1.0, 1.0]
+        }
+    }
+
+    /// Update the DPI scale; cached SVG rasters are dropped when it changes
+    pub fn set_pixel_ratio(&mut self, ratio: f64) {
+        if ratio == self.pixel_ratio {
+            return;
+        }
+        self.pixel_ratio = ratio;
+        self.delete_svg_textures();
+    }
+
+    fn delete_svg_textures(&mut self) {
+        for (_, texture) in self.svg_textures.drain() {
+            unsafe { gl::DeleteTextures(1, &texture) };
+        }
+    }
+
+    unsafe fn upload_texture(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        options: TextureOptions,
+    ) -> gl::types::GLuint {
+        let (mag_filter, min_filter) = match (options.filter, options.mipmaps) {
+            (Filter::Linear, false) => (gl::LINEAR, gl::LINEAR),
+            (Filter::Linear, true) => (gl::LINEAR, gl::LINEAR_MIPMAP_LINEAR),
+            (Filter::Nearest, false) => (gl::NEAREST, gl::NEAREST),
+            (Filter::Nearest, true) => (gl::NEAREST, gl::NEAREST_MIPMAP_NEAREST),
+        };
+        let wrap = if options.repeat {
+            gl::REPEAT
+        } else {
+            gl::CLAMP_TO_EDGE
+        } as i32;
+        let mut texture = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap);
+            if options.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        texture
+    }
+
+    /// Draw a quad filled with a gradient instead of a flat color
+    pub fn render_gradient_quad(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        gradient: &Gradient,
+    ) {
+        let count = gradient.stops.len().min(MAX_GRADIENT_STOPS);
+        if count == 0 {
+            return;
+        }
+        let mut offsets = [0.0f32; MAX_GRADIENT_STOPS];
+        let mut colors = [0.0f32; MAX_GRADIENT_STOPS * 4];
+        for (i, (offset, color)) in gradient.stops.iter().take(count).enumerate() {
+            offsets[i] = *offset;
+            colors[i * 4..i * 4 + 4].copy_from_slice(color);
+        }
+        // Texture v grows upwards, so flip the screen-space angle
+        let rad = gradient.angle.to_radians();
+        let dir = [rad.cos(), -rad.sin()];
+
         unsafe {
             gl::UseProgram(self.program);
+            let loc = |name: &str| {
+                let c = CString::new(name).unwrap();
+                gl::GetUniformLocation(self.program, c.as_ptr())
+            };
+            gl::Uniform1i(loc("stopCount"), count as i32);
+            gl::Uniform1fv(loc("stopOffsets"), count as i32, offsets.as_ptr());
+            gl::Uniform4fv(loc("stopColors"), count as i32, colors.as_ptr());
+            gl::Uniform2f(loc("gradientDir"), dir[0], dir[1]);
+        }
 
-            let mut model = [0.0f32; 16];
-            model[0] = width;
-            model[5] = height;
-            model[10] = 1.0;
-            model[12] = x;
-            model[13] = y;
-            model[15] = 1.0;
+        self.render_quad_shaded(
+            x,
+            y,
+            width,
+            height,
+            [1.0; 4],
+            Shading::Gradient(gradient.mode),
+        );
+    }
 
-            let projection = Self::ortho_matrix(-1.0, 1.0, -1.0, 1.0);
-            let mvp = Self::multiply_matrices(&projection, &model);
+    /// Draw an arbitrary convex quad given in NDC corners (used for shadow volumes)
+    fn render_ndc_quad(&self, corners: [(f32, f32); 4], color: [f32; 4]) {
+        let mut vertices = [0.0f32; 32];
+        for (i, (x, y)) in corners.iter().enumerate() {
+            vertices[i * 8..i * 8 + 8]
+                .copy_from_slice(&[*x, *y, 0.0, 0.0, color[0], color[1], color[2], color[3]]);
+        }
+        let projection = Self::ortho_matrix(-1.0, 1.0, -1.0, 1.0);
+        self.draw_vertices(&vertices, &projection, Shading::Flat);
+    }
+
+    fn draw_vertices(&self, vertices: &[f32; 32], mvp: &[f32; 16], shading: Shading) {
+        unsafe {
+            gl::UseProgram(self.program);
 
             let proj_loc =
                 gl::GetUniformLocation(self.program, CString::new("projection").unwrap().as_ptr());
@@ -261,31 +1241,238 @@ impl GemRenderer {
 
             let use_texture_loc =
                 gl::GetUniformLocation(self.program, CString::new("useTexture").unwrap().as_ptr());
-            gl::Uniform1i(use_texture_loc, 0);
-
-            #[rustfmt::skip]
-            let vertices: [f32; 32] = [
-                // positions   // texcoords  // colors
-                -0.5,  0.5,    0.0, 1.0,     color[0], color[1], color[2], color[3],
-                 0.5,  0.5,    1.0, 1.0,     color[0], color[1], color[2], color[3],
-                 0.5, -0.5,    1.0, 0.0,     color[0], color[1], color[2], color[3],
-                -0.5, -0.5,    0.0, 0.0,     color[0], color[1], color[2], color[3],
-            ];
+            gl::Uniform1i(use_texture_loc, (shading == Shading::Textured) as i32);
+
+            let radial_loc =
+                gl::GetUniformLocation(self.program, CString::new("radialLight").unwrap().as_ptr());
+            gl::Uniform1i(radial_loc, (shading == Shading::RadialLight) as i32);
+
+            let gradient_loc = gl::GetUniformLocation(
+                self.program,
+                CString::new("gradientMode").unwrap().as_ptr(),
+            );
+            let gradient_mode = match shading {
+                Shading::Gradient(GradientMode::Linear) => 1,
+                Shading::Gradient(GradientMode::Radial) => 2,
+                _ => 0,
+            };
+            gl::Uniform1i(gradient_loc, gradient_mode);
+
+            let premultiply_loc =
+                gl::GetUniformLocation(self.program, CString::new("premultiply").unwrap().as_ptr());
+            gl::Uniform1i(premultiply_loc, self.blend_mode.premultiplies() as i32);
 
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
             gl::BufferSubData(
                 gl::ARRAY_BUFFER,
                 0,
-                (vertices.len() * std::mem::size_of::<f32>()) as isize,
+                std::mem::size_of_val(vertices) as isize,
                 vertices.as_ptr() as *const _,
             );
 
             gl::BindVertexArray(self.vao);
             gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null());
+            performance::count_draw_call();
             gl::BindVertexArray(0);
         }
     }
 
+    /// Lighting pass: accumulate every light (minus the shadows cast by the
+    /// occluders) into an offscreen texture cleared to `ambient`, then multiply
+    /// that texture over whatever has been drawn so far this frame.
+    pub fn render_lighting(
+        &mut self,
+        lights: &[LightCmd],
+        occluders: &[OccluderCmd],
+        ambient: [f32; 4],
+    ) {
+        let (fb_w, fb_h) = (self.viewport.0 as f32, self.viewport.1 as f32);
+        let to_ndc = |x: f32, y: f32| ((x / fb_w) * 2.0 - 1.0, -((y / fb_h) * 2.0 - 1.0));
+        let fbo = self.ensure_light_buffer();
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::ClearColor(ambient[0], ambient[1], ambient[2], 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gl::Enable(gl::STENCIL_TEST);
+        }
+
+        for light in lights {
+            unsafe {
+                gl::Clear(gl::STENCIL_BUFFER_BIT);
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+            }
+
+            // Extrude each occluder edge away from the light to mark its shadow
+            for occ in occluders {
+                let corners = [
+                    (occ.x, occ.y),
+                    (occ.x + occ.w, occ.y),
+                    (occ.x + occ.w, occ.y + occ.h),
+                    (occ.x, occ.y + occ.h),
+                ];
+                for i in 0..4 {
+                    let a = corners[i];
+                    let b = corners[(i + 1) % 4];
+                    let far = |p: (f32, f32)| {
+                        let (dx, dy) = (p.0 - light.x, p.1 - light.y);
+                        let len = (dx * dx + dy * dy).sqrt().max(0.001);
+                        let reach = (fb_w + fb_h) * 4.0;
+                        (p.0 + dx / len * reach, p.1 + dy / len * reach)
+                    };
+                    let (af, bf) = (far(a), far(b));
+                    self.render_ndc_quad(
+                        [
+                            to_ndc(a.0, a.1),
+                            to_ndc(b.0, b.1),
+                            to_ndc(bf.0, bf.1),
+                            to_ndc(af.0, af.1),
+                        ],
+                        [0.0, 0.0, 0.0, 1.0],
+                    );
+                }
+            }
+
+            unsafe {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                gl::StencilFunc(gl::EQUAL, 0, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                gl::BlendFunc(gl::ONE, gl::ONE);
+            }
+
+            let (cx, cy) = to_ndc(light.x, light.y);
+            let color = [
+                light.color[0] * light.color[3] * light.energy,
+                light.color[1] * light.color[3] * light.energy,
+                light.color[2] * light.color[3] * light.energy,
+                1.0,
+            ];
+            self.render_quad_shaded(
+                cx,
+                cy,
+                light.radius * 2.0 / fb_w * 2.0,
+                light.radius * 2.0 / fb_h * 2.0,
+                color,
+                Shading::RadialLight,
+            );
+        }
+
+        unsafe {
+            gl::Disable(gl::STENCIL_TEST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::ClearColor(0.1, 0.1, 0.15, 1.0);
+
+            // Composite: scene * light
+            gl::BlendFunc(gl::DST_COLOR, gl::ZERO);
+            if let Some(lb) = &self.light_buffer {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, lb.texture);
+            }
+        }
+        self.render_quad_shaded(0.0, 0.0, 2.0, 2.0, [1.0; 4], Shading::Textured);
+
+        // Restore whatever blend mode the draw list was using
+        let (src, dst) = self.blend_mode.gl_factors();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BlendFunc(src, dst);
+        }
+    }
+
+    /// (Re)create the light accumulation target when the viewport size changes
+    fn ensure_light_buffer(&mut self) -> gl::types::GLuint {
+        let (w, h) = self.viewport;
+        if let Some(lb) = &self.light_buffer
+            && lb.width == w
+            && lb.height == h
+        {
+            return lb.fbo;
+        }
+        if let Some(old) = self.light_buffer.take() {
+            unsafe { Self::delete_light_buffer(&old) };
+        }
+        let lb = unsafe { Self::create_light_buffer(w, h) };
+        let fbo = lb.fbo;
+        self.light_buffer = Some(lb);
+        fbo
+    }
+
+    unsafe fn create_light_buffer(width: u32, height: u32) -> LightBuffer {
+        let mut fbo = 0;
+        let mut texture = 0;
+        let mut depth_stencil = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture,
+                0,
+            );
+
+            gl::GenRenderbuffers(1, &mut depth_stencil);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_stencil);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_stencil,
+            );
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                eprintln!("[GemRenderer] Light buffer framebuffer is incomplete");
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        verbose!("[GemRenderer] Light buffer created ({}x{})", width, height);
+
+        LightBuffer {
+            fbo,
+            texture,
+            depth_stencil,
+            width,
+            height,
+        }
+    }
+
+    unsafe fn delete_light_buffer(lb: &LightBuffer) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &lb.fbo);
+            gl::DeleteTextures(1, &lb.texture);
+            gl::DeleteRenderbuffers(1, &lb.depth_stencil);
+        }
+    }
+
     fn ortho_matrix(left: f32, right: f32, bottom: f32, top: f32) -> [f32; 16] {
         let mut matrix = [0.0f32; 16];
         matrix[0] = 2.0 / (right - left);
@@ -310,7 +1497,44 @@ impl GemRenderer {
         result
     }
 
-    pub fn set_viewport(&self, width: u32, height: u32) {
+    /// Recompile the quad shaders, preferring `quad.vert` / `quad.frag` from
+    /// `dir` when present; instanced runs use `quad.frag` too. On failure the previous program stays active.
+    pub fn reload_shaders(&mut self, dir: &Path) -> Result<(), String> {
+        let read = |name: &str, builtin: &str| -> Result<String, String> {
+            let path = dir.join(name).to_string_lossy().into_owned();
+            if vfs::is_file(&path) {
+                vfs::read_to_string(&path)
+            } else {
+                Ok(builtin.to_string())
+            }
+        };
+        let vertex_src = read("quad.vert", VERTEX_SHADER_SRC)?;
+        let fragment_src = read("quad.frag", FRAGMENT_SHADER_SRC)?;
+        let program = unsafe { Self::create_shader_program(&vertex_src, &fragment_src)? };
+        let instanced_program = match unsafe {
+            Self::create_shader_program(INSTANCED_VERTEX_SHADER_SRC, &fragment_src)
+        } {
+            Ok(instanced_program) => instanced_program,
+            Err(e) => {
+                unsafe { gl::DeleteProgram(program) };
+                return Err(e);
+            }
+        };
+        unsafe {
+            gl::DeleteProgram(self.program);
+            gl::DeleteProgram(self.instanced_program);
+        }
+        self.program = program;
+        self.instanced_program = instanced_program;
+        Ok(())
+    }
+
+    pub fn viewport(&self) -> (u32, u32) {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width, height);
         unsafe {
             gl::Viewport(0, 0, width as i32, height as i32);
         }
@@ -319,11 +1543,55 @@ impl GemRenderer {
 
 impl Drop for GemRenderer {
     fn drop(&mut self) {
+        if let Some(lb) = self.light_buffer.take() {
+            unsafe { Self::delete_light_buffer(&lb) };
+        }
+        self.delete_svg_textures();
         unsafe {
+            gl::DeleteTextures(1, &self.font_atlas);
+            for (texture, _) in self.glyph_pages.values() {
+                gl::DeleteTextures(1, texture);
+            }
             gl::DeleteProgram(self.program);
             gl::DeleteVertexArrays(1, &self.vao);
             gl::DeleteBuffers(1, &self.vbo);
             gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteProgram(self.instanced_program);
+            gl::DeleteVertexArrays(1, &self.instance_vao);
+            gl::DeleteBuffers(1, &self.instance_quad_vbo);
+            gl::DeleteBuffers(1, &self.instance_vbo);
+            gl::DeleteProgram(self.mesh_program);
+            for mesh in self.meshes.values().flatten() {
+                gl::DeleteVertexArrays(1, &mesh.vao);
+                gl::DeleteBuffers(1, &mesh.vbo);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_modes_weigh_the_source_by_its_alpha() {
+        let modes = [
+            (
+                BlendMode::Normal,
+                (gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+                false,
+            ),
+            (BlendMode::Add, (gl::SRC_ALPHA, gl::ONE), false),
+            (
+                BlendMode::Multiply,
+                (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+                true,
+            ),
+            (BlendMode::Screen, (gl::ONE, gl::ONE_MINUS_SRC_COLOR), true),
+        ];
+        for (mode, factors, premultiplies) in modes {
+            assert_eq!(mode.gl_factors(), factors, "{:?}", mode);
+            assert_eq!(mode.premultiplies(), premultiplies, "{:?}", mode);
         }
     }
 }
@@ -0,0 +1,327 @@
+//! Resources referenced by directives (`#folder:file`).
+//! A directive names a `res://` file, relative to the project root: the last segment
+//! gets `.gem` when it has no extension. Evaluating one in logic yields a
+//! shared handle, loaded once per path: scene files become `PackedScene`s that
+//! `spawn` and `SceneTree.change_scene` accept, as do glTF models through
+//! `importers::gltf`, scripts become `Script`s, and any other file a
+//! `Resource` carrying its `path`. Logic files are checked
+//! when compiled so a reference to a missing file fails early.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+use crate::ast::{self, Block, Expr, LogicFile, Stmt};
+use crate::importers::gltf;
+use crate::object::{ObjectInner, ObjectRef};
+use crate::pipeline;
+use crate::value::Value;
+use crate::vfs;
+
+static LOADED: OnceLock<RwLock<HashMap<PathBuf, ObjectRef>>> = OnceLock::new();
+
+fn loaded() -> &'static RwLock<HashMap<PathBuf, ObjectRef>> {
+    LOADED.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// `["scenes", "level"]` -> `scenes/level.gem`
+pub fn resolve(parts: &[String]) -> PathBuf {
+    let mut path: PathBuf = parts.iter().collect();
+    if path.extension().is_none() {
+        path.set_extension("gem");
+    }
+    path
+}
+
+/// What a directive evaluates to in logic
+pub fn eval_directive(parts: &[String]) -> Result<Value, String> {
+    load(&resolve(parts)).map(Value::Object)
+}
+
+/// The handle for `path`, loading it on first use
+pub fn load(path: &Path) -> Result<ObjectRef, String> {
+    if let Some(handle) = loaded().read().unwrap().get(path) {
+        return Ok(handle.clone());
+    }
+    let handle = load_uncached(path)?;
+    // Another thread may have loaded it meanwhile; keep the first handle
+    let mut cache = loaded().write().unwrap();
+    Ok(cache.entry(path.to_path_buf()).or_insert(handle).clone())
+}
+
+fn load_uncached(path: &Path) -> Result<ObjectRef, String> {
+    if !vfs::is_file(&path.to_string_lossy()) {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let path_value = Value::String(path.display().to_string());
+    if path.extension().is_some_and(|e| e == "gem") {
+        let content = vfs::read_to_string(&path.to_string_lossy())?;
+        let tokens =
+            pipeline::lex_source(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if content.trim_start().starts_with("extend") {
+            pipeline::parse_logic(tokens).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let script = ObjectInner::base("Script");
+            script.set_property("path", path_value);
+            return Ok(script);
+        }
+        let file =
+            pipeline::parse_scene(tokens).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(packed(path_value, file));
+    }
+    if gltf::is_model(path) {
+        return Ok(packed(path_value, gltf::import(path)?));
+    }
+    let resource = ObjectInner::base("Resource");
+    resource.set_property("path", path_value);
+    Ok(resource)
+}
+
+fn packed(path: Value, file: ast::GemFile) -> ObjectRef {
+    let scene = ObjectInner::base("PackedScene");
+    scene.set_property("path", path);
+    scene
+        .extension::<OnceLock<ast::GemFile>>()
+        .get_or_init(|| file);
+    scene
+}
+
+/// The scene held by a `PackedScene` handle
+pub fn packed_scene(handle: &ObjectRef) -> Option<&ast::GemFile> {
+    if handle.class_name() != "PackedScene" {
+        return None;
+    }
+    handle.extension::<OnceLock<ast::GemFile>>().get()
+}
+
+/// Scene argument of `spawn` and `change_scene`: a PackedScene handle, or a
+/// directive value kept as text (`enemy: #scenes:enemy` in a scene file).
+/// A directive's query sets properties on the scene's root, so
+/// `#scenes:enemy?variant=blue` is the enemy with `variant: blue`.
+pub fn scene_arg(args: &[Value], method: &str) -> Result<ast::GemFile, String> {
+    let mut queries = Vec::new();
+    let arg = match args.first() {
+        Some(Value::String(text)) if text.starts_with('#') => {
+            let directive =
+                pipeline::parse_directive(text).map_err(|e| format!("{}: {}", method, e))?;
+            if let Some(arg) = directive
+                .args
+                .iter()
+                .find(|a| matches!(a, ast::DirectiveArg::Call { .. }))
+            {
+                return Err(format!("{}: scenes take no {:?} argument", method, arg));
+            }
+            queries = directive
+                .queries()
+                .map(|(key, value)| ast::Property {
+                    key: key.to_string(),
+                    value: value.clone(),
+                })
+                .collect();
+            Some(eval_directive(&directive.segments)?)
+        }
+        arg => arg.cloned(),
+    };
+    let Some(Value::Object(handle)) = arg else {
+        return Err(format!("{} expects a scene such as #scenes:level", method));
+    };
+    let mut scene = packed_scene(&handle)
+        .cloned()
+        .ok_or_else(|| format!("{} expects a scene, got a {}", method, handle.class_name()))?;
+    let properties = &mut scene.root.properties;
+    for query in queries {
+        match properties.iter_mut().find(|p| p.key == query.key) {
+            Some(p) => *p = query,
+            None => properties.push(query),
+        }
+    }
+    Ok(scene)
+}
+
+/// Every directive in `logic`, in order
+pub fn logic_refs(logic: &LogicFile) -> Vec<Vec<String>> {
+    let mut refs = Vec::new();
+    for body in logic
+        .events
+        .iter()
+        .map(|e| &e.body)
+        .chain(logic.functions.iter().map(|f| &f.body))
+    {
+        block_refs(body, &mut refs);
+    }
+    refs
+}
+
+/// Every directive in `logic` whose file doesn't exist, as error messages
+pub fn missing_refs(logic: &LogicFile) -> Vec<String> {
+    logic_refs(logic)
+        .into_iter()
+        .filter_map(|parts| {
+            let path = resolve(&parts);
+            (!vfs::is_file(&path.to_string_lossy())).then(|| {
+                format!(
+                    "#{} refers to {}, which does not exist",
+                    parts.join(":"),
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+fn block_refs(block: &Block, out: &mut Vec<Vec<String>>) {
+    for stmt in &block.statements {
+        match stmt {
+            Stmt::Let { value, .. } | Stmt::Assignment { value, .. } => expr_refs(value, out),
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                expr_refs(condition, out);
+                block_refs(then_block, out);
+                if let Some(block) = else_block {
+                    block_refs(block, out);
+                }
+            }
+            Stmt::For { iterable, body, .. } => {
+                expr_refs(iterable, out);
+                block_refs(body, out);
+            }
+            Stmt::Match { value, arms } => {
+                expr_refs(value, out);
+                arms.iter().for_each(|a| block_refs(&a.body, out));
+            }
+            Stmt::Call { args, .. } => args.iter().for_each(|a| expr_refs(a, out)),
+            Stmt::Spawn { properties, .. } => {
+                for p in properties {
+                    value_refs(&p.value, out);
+                }
+            }
+            Stmt::Return(Some(expr)) | Stmt::ExprStmt(expr) => expr_refs(expr, out),
+            Stmt::Return(None) => {}
+        }
+    }
+}
+
+fn expr_refs(expr: &Expr, out: &mut Vec<Vec<String>>) {
+    match expr {
+        Expr::Directive(directive) => out.push(directive.segments.clone()),
+        Expr::Tuple(items) | Expr::Call { args: items, .. } => {
+            items.iter().for_each(|e| expr_refs(e, out))
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            expr_refs(left, out);
+            expr_refs(right, out);
+        }
+        Expr::Range { start, end } => {
+            expr_refs(start, out);
+            expr_refs(end, out);
+        }
+        Expr::UnaryOp { expr, .. } => expr_refs(expr, out),
+        Expr::PropertyAccess { object, .. } => expr_refs(object, out),
+        Expr::MethodCall { object, args, .. } => {
+            expr_refs(object, out);
+            args.iter().for_each(|e| expr_refs(e, out));
+        }
+        Expr::Number(_) | Expr::Integer(_) | Expr::String(_) | Expr::Bool(_) | Expr::Ident(_) => {}
+    }
+}
+
+/// Directives in a property value, including nested tuples, structs and maps
+pub fn value_refs(value: &ast::Value, out: &mut Vec<Vec<String>>) {
+    match value {
+        ast::Value::Directive(directive) => out.push(directive.segments.clone()),
+        ast::Value::Tuple(items) | ast::Value::Array(items) => {
+            items.iter().for_each(|v| value_refs(v, out))
+        }
+        ast::Value::Struct(fields) => fields.iter().for_each(|f| value_refs(&f.value, out)),
+        ast::Value::Map(entries) => entries.iter().for_each(|(_, v)| value_refs(v, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem;
+    use crate::scene_tree;
+    use std::fs;
+
+    #[test]
+    fn directives_load_shared_handles_and_are_checked() {
+        let dir = Path::new("target").join("gem_resource_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("bat.gem"),
+            "Bat: SpriteGem { speed: 3 Wing: Gem {} }",
+        )
+        .unwrap();
+        fs::write(dir.join("bat.png"), "").unwrap();
+        let parts = |file: &str| {
+            vec![
+                "target".to_string(),
+                "gem_resource_test".to_string(),
+                file.to_string(),
+            ]
+        };
+
+        let Value::Object(bat) = eval_directive(&parts("bat")).unwrap() else {
+            panic!("expected a handle");
+        };
+        assert_eq!(bat.class_name(), "PackedScene");
+        assert_eq!(load(&resolve(&parts("bat"))).unwrap(), bat);
+        let Value::Object(png) = eval_directive(&parts("bat.png")).unwrap() else {
+            panic!("expected a handle");
+        };
+        assert_eq!(png.class_name(), "Resource");
+        assert!(eval_directive(&parts("missing")).is_err());
+
+        // Spawning the scene under a node instantiates a fresh copy each time
+        scene_tree::init_builtin_classes();
+        let cave = crate::object::object_new("Gem");
+        let Value::Object(first) = cave
+            .call_method("spawn", &[Value::Object(bat.clone())])
+            .unwrap()
+        else {
+            panic!("expected the spawned node");
+        };
+        // A query overrides properties of the spawned root
+        let Value::Object(second) = cave
+            .call_method(
+                "spawn",
+                &[Value::String(
+                    "#target:gem_resource_test:bat?speed=5&tint=blue".into(),
+                )],
+            )
+            .unwrap()
+        else {
+            panic!("expected the spawned node");
+        };
+        assert_eq!(first.class_name(), "SpriteGem");
+        assert_eq!(first.get_property("speed"), Some(Value::Int(3)));
+        assert_eq!(second.get_property("speed"), Some(Value::Int(5)));
+        assert_eq!(
+            second.get_property("tint"),
+            Some(Value::String("blue".into()))
+        );
+        assert_eq!(gem::children_of(&cave).len(), 2);
+        let err = cave
+            .call_method("spawn", &[Value::Object(png)])
+            .unwrap_err();
+        assert!(err.contains("got a Resource"));
+
+        let logic = pipeline::lex_source(
+            "extend Gem\nfn on_ready {\n  spawn(#target:gem_resource_test:bat)\n  \
+             next = #target:gem_resource_test:gone\n}\n",
+        )
+        .and_then(pipeline::parse_logic)
+        .unwrap();
+        assert_eq!(
+            missing_refs(&logic),
+            [format!(
+                "#target:gem_resource_test:gone refers to {}, which does not exist",
+                dir.join("gone.gem").display()
+            )]
+        );
+    }
+}
@@ -0,0 +1,902 @@
+//! SceneTree: frame dispatch over the runtime tree, and the pause state.
+//! Each frame it walks the tree parent-first and gives every node that may
+//! process its built-in hook (`_process(dt)`, e.g. timers and state machines)
+//! followed by its script's `on_update(dt)`; physics steps call
+//! `on_physics_update(dt)` the same way. Whether a node may process depends on
+//! `SceneTree.paused` and the node's effective `process_mode`.
+//!
+//! It also converts between scene files and live nodes: `instantiate` builds a
+//! node tree from a scene AST, with the scene root as every other node's
+//! `owner`, `pack` turns an owned tree back into an AST, and `live_scene` lays
+//! what scripts changed in a running tree over the AST it came from, for
+//! drawing. Runtime values have no directive or identifier types, so those
+//! are kept as their `#`-prefixed (or bare) source text; colors become
+//! `Value::Color` and struct values maps.
+//! `change_scene` swaps the scene under the tree root for a new instance, as
+//! the editor preview does on reload and scripts do with a `PackedScene`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::accessibility;
+use crate::ast;
+use crate::autoload;
+use crate::check_box;
+use crate::color::Color;
+use crate::color_picker;
+use crate::commands;
+use crate::crash;
+use crate::curve;
+use crate::draw_list;
+use crate::file_access;
+use crate::gem;
+use crate::item_list;
+use crate::line_edit;
+use crate::object::{self, ObjectInner, ObjectRef};
+use crate::option_button;
+use crate::pipeline;
+use crate::popup;
+use crate::printer;
+use crate::resource;
+use crate::skeleton;
+use crate::state_machine;
+use crate::tab_container;
+use crate::timer;
+use crate::value::Value;
+use crate::websocket;
+
+const PAUSED_KEY: &str = "paused";
+/// Window size in pixels, kept up to date by the main loop
+const VIEWPORT_SIZE_KEY: &str = "viewport_size";
+pub const PROCESS_MODE_KEY: &str = "process_mode";
+/// The project's `pixel_snap:` setting
+const PIXEL_SNAP_KEY: &str = "pixel_snap";
+/// Physics steps per second, whatever the frame rate
+pub const PHYSICS_TICKS_PER_SECOND: f64 = 60.0;
+/// Steps one frame may catch up on; lag beyond that is dropped
+const MAX_PHYSICS_STEPS: u32 = 8;
+
+/// Frame time not yet simulated, in seconds (f64 bits). Kept out of the
+/// SceneTree's properties, which would count every frame as a change.
+static PHYSICS_LAG: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessMode {
+    /// Use the parent's mode; at the top of the tree, process unless paused
+    Inherit,
+    /// Process whether or not the tree is paused
+    Always,
+    /// Process only while the tree is paused (e.g. a pause menu)
+    WhenPaused,
+    /// Never process
+    Disabled,
+}
+
+impl ProcessMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "inherit" => Some(Self::Inherit),
+            "always" => Some(Self::Always),
+            "when_paused" => Some(Self::WhenPaused),
+            "disabled" => Some(Self::Disabled),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Inherit => "inherit",
+            Self::Always => "always",
+            Self::WhenPaused => "when_paused",
+            Self::Disabled => "disabled",
+        }
+    }
+
+    /// The mode a node runs with, given what it inherits from its parent
+    pub(crate) fn resolve(self, inherited: ProcessMode) -> ProcessMode {
+        match self {
+            Self::Inherit => inherited,
+            mode => mode,
+        }
+    }
+
+    pub(crate) fn can_process(self, paused: bool) -> bool {
+        match self {
+            Self::Inherit => !paused,
+            Self::Always => true,
+            Self::WhenPaused => paused,
+            Self::Disabled => false,
+        }
+    }
+}
+
+/// Register every engine-provided class; safe to call repeatedly
+pub fn init_builtin_classes() {
+    object::init_object_class();
+    gem::init_gem_class();
+    state_machine::init_state_machine_class();
+    timer::init_timer_class();
+    skeleton::init_skeleton_classes();
+    popup::init_popup_classes();
+    option_button::init_option_button_class();
+    line_edit::init_text_edit_classes();
+    check_box::init_check_box_classes();
+    color_picker::init_color_picker_class();
+    tab_container::init_tab_classes();
+    item_list::init_item_list_classes();
+    websocket::init_websocket_class();
+    file_access::init_file_access_class();
+    object::set_draw_filter(draws_change);
+}
+
+/// Whether setting `key` on `object` can change what the current scene draws
+fn draws_change(object: &ObjectRef, key: &str) -> bool {
+    if !gem::is_gem(object) || !draw_list::draws_property(object.class_name(), key) {
+        return false;
+    }
+    match key {
+        "visible" => is_drawn_if_visible(object),
+        _ => is_drawn(object),
+    }
+}
+
+/// The script-facing `SceneTree` singleton; its `paused` property is the pause state
+pub fn scene_tree() -> ObjectRef {
+    static TREE: OnceLock<ObjectRef> = OnceLock::new();
+    TREE.get_or_init(new_scene_tree).clone()
+}
+
+fn new_scene_tree() -> ObjectRef {
+    let obj = ObjectInner::base("SceneTree");
+    obj.set_property(PAUSED_KEY, Value::Bool(false));
+    obj.set_property(VIEWPORT_SIZE_KEY, Value::vec2(800.0, 600.0));
+    // set_paused(bool), is_paused(), get_root()
+    ObjectInner::insert_method(
+        &obj,
+        "set_paused",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::Bool(b)) => {
+                set_paused(*b);
+                Ok(Value::Null)
+            }
+            _ => Err("set_paused expects Bool".into()),
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "is_paused",
+        Arc::new(|_, _| Ok(Value::Bool(is_paused()))),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "get_root",
+        Arc::new(|_, _| Ok(Value::Object(autoload::tree_root()))),
+    );
+    // pack(root) -> .gem source text
+    ObjectInner::insert_method(
+        &obj,
+        "pack",
+        Arc::new(|_, args| match args.first() {
+            Some(Value::Object(root)) => Ok(Value::String(printer::print_scene(&pack(root)))),
+            _ => Err("pack expects a root node".into()),
+        }),
+    );
+    // change_scene(scene), get_current_scene()
+    ObjectInner::insert_method(
+        &obj,
+        "change_scene",
+        Arc::new(|_, args| {
+            change_scene(&resource::scene_arg(args, "change_scene")?).map(Value::Object)
+        }),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "get_current_scene",
+        Arc::new(|_, _| Ok(current_scene().map(Value::Object).unwrap_or(Value::Null))),
+    );
+    // get_accessibility_tree() -> { nodes: [...], focus: id } for the whole tree
+    ObjectInner::insert_method(
+        &obj,
+        "get_accessibility_tree",
+        Arc::new(|_, _| {
+            let tree = accessibility::tree_update(&autoload::tree_root());
+            Ok(Value::Map(HashMap::from([
+                (
+                    "nodes".to_string(),
+                    Value::Array(tree.nodes.iter().map(|n| n.to_value()).collect()),
+                ),
+                ("focus".to_string(), Value::Int(tree.focus as i64)),
+            ])))
+        }),
+    );
+    obj
+}
+
+/// Held by tests that change the current scene and then look at it: it's
+/// global, so another test's `change_scene` would swap it under them
+#[cfg(test)]
+pub(crate) static CURRENT_SCENE_TESTS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn current_scene_slot() -> &'static RwLock<Option<ObjectRef>> {
+    static CURRENT: OnceLock<RwLock<Option<ObjectRef>>> = OnceLock::new();
+    CURRENT.get_or_init(|| RwLock::new(None))
+}
+
+/// The scene last entered through `change_scene`
+pub fn current_scene() -> Option<ObjectRef> {
+    current_scene_slot().read().unwrap().clone()
+}
+
+/// Whether the draw list shows `node`: it's visible and so are its
+/// ancestors, up to and including the current scene
+pub fn is_drawn(node: &ObjectRef) -> bool {
+    gem::is_visible(node) && is_drawn_if_visible(node)
+}
+
+/// Whether the draw list would show `node` if it were visible itself: it's
+/// the current scene or under a drawn parent
+pub fn is_drawn_if_visible(node: &ObjectRef) -> bool {
+    current_scene().as_ref() == Some(node) || gem::parent_of(node).is_some_and(|p| is_drawn(&p))
+}
+
+/// Replace the current scene under the tree root with a fresh instance of
+/// `file`. On error the current scene stays in place.
+pub fn change_scene(file: &ast::GemFile) -> Result<ObjectRef, String> {
+    let node = instantiate(file)?;
+    let root = autoload::tree_root();
+    let previous = current_scene_slot().write().unwrap().replace(node.clone());
+    if let Some(previous) = previous {
+        root.call_method("remove_child", &[Value::Object(previous)])?;
+    }
+    root.call_method("add_child", &[Value::Object(node.clone())])?;
+    gem::propagate_ready(&node);
+    Ok(node)
+}
+
+/// Live nodes for a scene file. Types without a registered class become plain
+/// Gems that keep the declared type as their class name.
+pub fn instantiate(file: &ast::GemFile) -> Result<ObjectRef, String> {
+    init_builtin_classes();
+    instantiate_decl(&file.root, None)
+}
+
+fn instantiate_decl(decl: &ast::GemDecl, owner: Option<&ObjectRef>) -> Result<ObjectRef, String> {
+    let node = if object::is_class_registered(&decl.gem_type) {
+        object::object_new(&decl.gem_type)
+    } else {
+        gem::new_gem(&decl.gem_type, gem::gem_methods())
+    };
+    node.set_property("name", Value::String(decl.name.clone()));
+    for p in &decl.properties {
+        node.set_property(&p.key, from_ast(&p.value));
+    }
+    for m in &decl.meta {
+        node.set_meta(&m.key, from_ast(&m.value));
+    }
+    gem::set_owner(&node, owner)?;
+    gem::set_unique_name_in_owner(&node, decl.unique)?;
+    let owner = owner.unwrap_or(&node);
+    for child in &decl.children {
+        let child = instantiate_decl(child, Some(owner))?;
+        node.call_method("add_child", &[Value::Object(child)])?;
+    }
+    Ok(node)
+}
+
+/// The scene AST for `root` and the descendants it owns, directly or through
+/// nested scenes it owns. Nodes without such an owner (e.g. added by a script
+/// and never given one) are left out, as are properties still at their class
+/// default.
+pub fn pack(root: &ObjectRef) -> ast::GemFile {
+    ast::GemFile {
+        root: pack_decl(root, root),
+        errors: Vec::new(),
+    }
+}
+
+fn pack_decl(node: &ObjectRef, root: &ObjectRef) -> ast::GemDecl {
+    let defaults = object::is_class_registered(node.class_name())
+        .then(|| object::object_new(node.class_name()));
+    let mut keys = node.property_keys();
+    keys.sort();
+    let mut properties = Vec::new();
+    for key in keys {
+        let Some(value) = node.get_property(&key) else {
+            continue;
+        };
+        if key == "name"
+            || defaults.as_ref().and_then(|d| d.get_property(&key)) == Some(value.clone())
+        {
+            continue;
+        }
+        match to_ast(&value) {
+            Some(value) => properties.push(ast::Property { key, value }),
+            None if value == Value::Null => {}
+            None => eprintln!(
+                "[WARN] pack: {}.{} has no scene file form, skipping",
+                node.class_name(),
+                key
+            ),
+        }
+    }
+    let mut meta = Vec::new();
+    for key in node.meta_keys() {
+        let Some(value) = node.get_meta(&key) else {
+            continue;
+        };
+        match to_ast(&value) {
+            Some(value) => meta.push(ast::Property { key, value }),
+            None => eprintln!(
+                "[WARN] pack: {} metadata '{}' has no scene file form, skipping",
+                node.class_name(),
+                key
+            ),
+        }
+    }
+    let name = match node.get_property("name") {
+        Some(Value::String(s)) => s,
+        _ => node.class_name().to_string(),
+    };
+    ast::GemDecl {
+        name,
+        // Unique within a nested scene only; the packed file is one flat scene
+        unique: gem::is_unique_name_in_owner(node) && gem::owner_of(node).as_ref() == Some(root),
+        gem_type: node.class_name().to_string(),
+        properties,
+        meta,
+        children: gem::children_of(node)
+            .iter()
+            .filter(|child| is_owned_by(child, root))
+            .map(|child| pack_decl(child, root))
+            .collect(),
+    }
+}
+
+/// Whether `node`'s owner chain reaches `root`
+fn is_owned_by(node: &ObjectRef, root: &ObjectRef) -> bool {
+    let mut owner = gem::owner_of(node);
+    while let Some(o) = owner {
+        if &o == root {
+            return true;
+        }
+        owner = gem::owner_of(&o);
+    }
+    false
+}
+
+/// `scene` as the running game shows it. Each node's properties that a
+/// script has changed on its live counterpart under `node` since `entered`
+/// was instantiated there take their live value, its `position` blended
+/// `alpha` of the way from the pose before the latest physics step; the
+/// rest, like edits made in the editor, keep `scene`'s. Nodes a script has
+/// freed are hidden, so the editor's node paths still hold. Live nodes are
+/// matched to declarations by name.
+pub fn live_scene(
+    scene: &ast::GemFile,
+    entered: &ast::GemFile,
+    node: &ObjectRef,
+    alpha: f64,
+) -> ast::GemFile {
+    ast::GemFile {
+        root: live_decl(&scene.root, Some(&entered.root), node, alpha),
+        errors: scene.errors.clone(),
+    }
+}
+
+fn live_decl(
+    decl: &ast::GemDecl,
+    entered: Option<&ast::GemDecl>,
+    node: &ObjectRef,
+    alpha: f64,
+) -> ast::GemDecl {
+    let default = class_default(node.class_name());
+    // What the node held when it was instantiated
+    let initial = |key: &str| {
+        entered
+            .and_then(|e| commands::get_property(e, key))
+            .map(from_ast)
+            .or_else(|| default.as_ref()?.get_property(key))
+    };
+    let mut out = ast::GemDecl {
+        children: Vec::new(),
+        ..decl.clone()
+    };
+    for key in node.property_keys() {
+        let Some(value) = node.get_property(&key) else {
+            continue;
+        };
+        if key == "name" || initial(&key) == Some(value.clone()) {
+            continue;
+        }
+        if let Some(value) = to_ast(&value) {
+            commands::set_property(&mut out, &key, Some(value));
+        }
+    }
+    let position = gem::interpolated_transform(node, alpha).origin;
+    let live = node.get_property("position").and_then(|p| p.as_vec2());
+    if live.is_some_and(|p| p != position) {
+        let value = ast::Value::Tuple(vec![
+            ast::Value::Number(position.0),
+            ast::Value::Number(position.1),
+        ]);
+        commands::set_property(&mut out, "position", Some(value));
+    }
+
+    let children = gem::children_of(node);
+    let named = |name: &str| {
+        children
+            .iter()
+            .find(|c| c.get_property("name") == Some(Value::String(name.to_string())))
+    };
+    for child in &decl.children {
+        let entered_child = entered.and_then(|e| e.children.iter().find(|c| c.name == child.name));
+        match named(&child.name) {
+            Some(live) => {
+                let child = live_decl(child, entered_child, live, alpha);
+                out.children.push(child);
+            }
+            // Freed by a script, unless the editor added it
+            None if entered_child.is_some() => {
+                let mut freed = child.clone();
+                commands::set_property(&mut freed, "visible", Some(ast::Value::Bool(false)));
+                out.children.push(freed);
+            }
+            None => out.children.push(child.clone()),
+        }
+    }
+    out
+}
+
+/// A new `class` object, whose properties are the class's defaults; made
+/// once per class, as `live_scene` runs on every frame that changes
+fn class_default(class: &str) -> Option<ObjectRef> {
+    static DEFAULTS: OnceLock<RwLock<HashMap<String, Option<ObjectRef>>>> = OnceLock::new();
+    let defaults = DEFAULTS.get_or_init(Default::default);
+    if let Some(default) = defaults.read().unwrap().get(class) {
+        return default.clone();
+    }
+    let default = object::is_class_registered(class).then(|| object::object_new(class));
+    let mut defaults = defaults.write().unwrap();
+    defaults.entry(class.to_string()).or_insert(default).clone()
+}
+
+fn from_ast(value: &ast::Value) -> Value {
+    match value {
+        ast::Value::Number(n) => Value::Float(*n),
+        ast::Value::Integer(i) => Value::Int(*i),
+        ast::Value::String(s) => Value::String(s.clone()),
+        ast::Value::Bool(b) => Value::Bool(*b),
+        ast::Value::Tuple(items) | ast::Value::Array(items) => {
+            Value::Array(items.iter().map(from_ast).collect())
+        }
+        ast::Value::Struct(fields) => Value::Map(
+            fields
+                .iter()
+                .map(|f| (f.key.clone(), from_ast(&f.value)))
+                .collect(),
+        ),
+        ast::Value::Map(entries) => Value::Map(
+            entries
+                .iter()
+                .map(|(key, v)| (key.clone(), from_ast(v)))
+                .collect(),
+        ),
+        ast::Value::Color(c) => Value::Color(Color::from_rgba8(*c)),
+        ast::Value::Directive(_) | ast::Value::Ident(_) | ast::Value::SizeExpr(_) => {
+            Value::String(printer::print_value(value))
+        }
+        ast::Value::Curve(c) => Value::Object(curve::handle(c.clone())),
+    }
+}
+
+/// `None` for values a scene file can't hold (objects, null)
+fn to_ast(value: &Value) -> Option<ast::Value> {
+    Some(match value {
+        Value::Bool(b) => ast::Value::Bool(*b),
+        Value::Int(i) => ast::Value::Integer(*i),
+        Value::Float(f) => ast::Value::Number(*f),
+        Value::String(s) => hash_value(s).unwrap_or_else(|| ast::Value::String(s.clone())),
+        Value::Array(items) => ast::Value::Tuple(items.iter().map(to_ast).collect::<Option<_>>()?),
+        Value::Map(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let entries: Vec<(String, ast::Value)> = keys
+                .into_iter()
+                .map(|key| Some((key.clone(), to_ast(&map[key])?)))
+                .collect::<Option<_>>()?;
+            // Keys a struct field can't spell keep their quotes, and `{}` is
+            // an empty map
+            if entries.is_empty() || !entries.iter().all(|(key, _)| is_field_name(key)) {
+                return Some(ast::Value::Map(entries));
+            }
+            ast::Value::Struct(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| ast::Property { key, value })
+                    .collect(),
+            )
+        }
+        Value::Object(o) => ast::Value::Curve(curve::of(o)?.clone()),
+        Value::Color(c) => ast::Value::Color(c.to_rgba8()),
+        Value::Null => return None,
+    })
+}
+
+/// `hit_box`, but not `Hit Box` or `2d`
+fn is_field_name(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_lowercase() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// A color or directive kept as its `#` source text
+fn hash_value(s: &str) -> Option<ast::Value> {
+    s.strip_prefix('#')?;
+    if let Some(color) = Color::from_hex(s) {
+        return Some(ast::Value::Color(color.to_rgba8()));
+    }
+    pipeline::parse_directive(s).ok().map(ast::Value::Directive)
+}
+
+pub fn is_paused() -> bool {
+    scene_tree().with_property(PAUSED_KEY, |v| matches!(v, Some(Value::Bool(true))))
+}
+
+pub fn viewport_size() -> (f64, f64) {
+    scene_tree()
+        .with_property(VIEWPORT_SIZE_KEY, |v| v.and_then(Value::as_vec2))
+        .unwrap_or((800.0, 600.0))
+}
+
+pub fn set_viewport_size(width: f64, height: f64) {
+    scene_tree().set_property(VIEWPORT_SIZE_KEY, Value::vec2(width, height));
+}
+
+pub fn is_pixel_snap() -> bool {
+    scene_tree().with_property(PIXEL_SNAP_KEY, |v| matches!(v, Some(Value::Bool(true))))
+}
+
+pub fn set_pixel_snap(snap: bool) {
+    scene_tree().set_property(PIXEL_SNAP_KEY, Value::Bool(snap));
+}
+
+/// Changing the state broadcasts `NOTIFICATION_PAUSED` or `NOTIFICATION_UNPAUSED`
+pub fn set_paused(paused: bool) {
+    if is_paused() == paused {
+        return;
+    }
+    scene_tree().set_property(PAUSED_KEY, Value::Bool(paused));
+    let code = if paused {
+        object::NOTIFICATION_PAUSED
+    } else {
+        object::NOTIFICATION_UNPAUSED
+    };
+    gem::propagate_notification(&autoload::tree_root(), code);
+}
+
+/// The node's own `process_mode`; missing or unknown values inherit
+pub fn process_mode(node: &ObjectRef) -> ProcessMode {
+    node.with_property(PROCESS_MODE_KEY, |v| match v {
+        Some(Value::String(s)) => ProcessMode::parse(s),
+        _ => None,
+    })
+    .unwrap_or(ProcessMode::Inherit)
+}
+
+/// The mode after resolving `inherit` through the node's ancestors
+pub fn effective_process_mode(node: &ObjectRef) -> ProcessMode {
+    let inherited = gem::parent_of(node)
+        .map(|p| effective_process_mode(&p))
+        .unwrap_or(ProcessMode::Inherit);
+    process_mode(node).resolve(inherited)
+}
+
+pub fn can_process(node: &ObjectRef) -> bool {
+    effective_process_mode(node).can_process(is_paused())
+}
+
+/// Run one frame's `_process(dt)` and `on_update(dt)` over `root`'s subtree
+pub fn process_frame(root: &ObjectRef, dt: f64) {
+    dispatch(root, &["_process", "on_update"], dt);
+}
+
+/// Advance physics by a frame of `dt` seconds in fixed steps of
+/// `1 / PHYSICS_TICKS_PER_SECOND`, recording every node's pose before each
+/// step; returns how many steps ran
+pub fn step_physics(root: &ObjectRef, dt: f64) -> u32 {
+    let step = 1.0 / PHYSICS_TICKS_PER_SECOND;
+    let mut lag = f64::from_bits(PHYSICS_LAG.load(Ordering::Relaxed)) + dt;
+    let mut steps = 0;
+    while lag >= step {
+        if steps == MAX_PHYSICS_STEPS {
+            lag %= step;
+            break;
+        }
+        gem::record_poses(root);
+        physics_frame(root, step);
+        lag -= step;
+        steps += 1;
+    }
+    PHYSICS_LAG.store(lag.to_bits(), Ordering::Relaxed);
+    steps
+}
+
+/// How far the frame is from the latest physics step towards the next, 0 to
+/// 1, for blending the poses on either side of it
+pub fn physics_interpolation_fraction() -> f64 {
+    f64::from_bits(PHYSICS_LAG.load(Ordering::Relaxed)) * PHYSICS_TICKS_PER_SECOND
+}
+
+/// Run one physics step's `on_physics_update(dt)` over `root`'s subtree
+pub fn physics_frame(root: &ObjectRef, dt: f64) {
+    dispatch(root, &["on_physics_update"], dt);
+}
+
+fn dispatch(root: &ObjectRef, hooks: &[&str], dt: f64) {
+    let inherited = gem::parent_of(root)
+        .map(|p| effective_process_mode(&p))
+        .unwrap_or(ProcessMode::Inherit);
+    dispatch_recursive(root, inherited, is_paused(), hooks, &[Value::Float(dt)]);
+}
+
+fn dispatch_recursive(
+    node: &ObjectRef,
+    inherited: ProcessMode,
+    paused: bool,
+    hooks: &[&str],
+    args: &[Value],
+) {
+    let mode = process_mode(node).resolve(inherited);
+    if mode.can_process(paused) {
+        for hook in hooks {
+            if node.has_method(hook)
+                && let Err(e) = crash::running(node, || node.call_method(hook, args))
+            {
+                eprintln!("[ERR] {}.{}: {}", node.class_name(), hook, e);
+            }
+        }
+    }
+    // Children are visited even under a node that can't process, since an
+    // `always` or `when_paused` child overrides what it inherits
+    for child in gem::children_of(node) {
+        dispatch_recursive(&child, mode, paused, hooks, args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{init_object_class, object_new};
+    use std::sync::Mutex;
+
+    #[test]
+    fn pause_freezes_gameplay_but_not_the_menu() {
+        init_object_class();
+        gem::init_gem_class();
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let root = object_new("Gem");
+        let mut nodes = Vec::new();
+        for (name, mode) in [
+            ("Player", None),
+            ("Menu", Some("when_paused")),
+            ("Hud", Some("always")),
+            ("Ghost", Some("disabled")),
+        ] {
+            let node = object_new("Gem");
+            if let Some(mode) = mode {
+                node.set_property(PROCESS_MODE_KEY, Value::String(mode.into()));
+            }
+            let log = log.clone();
+            ObjectInner::insert_method(
+                &node,
+                "on_update",
+                Arc::new(move |_, _| {
+                    log.lock().unwrap().push(name);
+                    Ok(Value::Null)
+                }),
+            );
+            root.call_method("add_child", &[Value::Object(node.clone())])
+                .unwrap();
+            nodes.push(node);
+        }
+        // A child of the menu inherits when_paused
+        let button = object_new("Gem");
+        nodes[1]
+            .call_method("add_child", &[Value::Object(button.clone())])
+            .unwrap();
+        assert_eq!(effective_process_mode(&button), ProcessMode::WhenPaused);
+
+        // The tree is shared with other tests, so drive the modes directly
+        let frame = |paused: bool| {
+            log.lock().unwrap().clear();
+            dispatch_recursive(
+                &root,
+                ProcessMode::Inherit,
+                paused,
+                &["on_update"],
+                &[Value::Float(0.1)],
+            );
+            log.lock().unwrap().clone()
+        };
+        assert_eq!(frame(false), ["Player", "Hud"]);
+        assert_eq!(frame(true), ["Menu", "Hud"]);
+
+        let tree = scene_tree();
+        assert!(tree.call_method("set_paused", &[Value::Int(1)]).is_err());
+        assert!(tree.has_method("get_root"));
+    }
+
+    #[test]
+    fn fixed_physics_steps_blend_poses_in_between() {
+        init_object_class();
+        gem::init_gem_class();
+
+        let root = object_new("Gem");
+        let mut movers = Vec::new();
+        for interpolated in [true, false] {
+            let node = object_new("Gem");
+            node.set_property("position", Value::vec2(0.0, 0.0));
+            node.set_property("physics_interpolation", Value::Bool(interpolated));
+            ObjectInner::insert_method(
+                &node,
+                "on_physics_update",
+                Arc::new(|this, _| {
+                    let (x, y) = this.get_property("position").unwrap().as_vec2().unwrap();
+                    this.set_property("position", Value::vec2(x + 10.0, y));
+                    Ok(Value::Null)
+                }),
+            );
+            root.call_method("add_child", &[Value::Object(node.clone())])
+                .unwrap();
+            movers.push(node);
+        }
+
+        let step = 1.0 / PHYSICS_TICKS_PER_SECOND;
+        PHYSICS_LAG.store(0.0f64.to_bits(), Ordering::Relaxed);
+        assert_eq!(step_physics(&root, step * 0.5), 0);
+        assert_eq!(step_physics(&root, step * 2.0), 2);
+        let alpha = physics_interpolation_fraction();
+        assert!((alpha - 0.5).abs() < 1e-9);
+        let x = |node| gem::interpolated_global_transform(node, alpha).origin.0;
+        assert!((x(&movers[0]) - 15.0).abs() < 1e-9);
+        assert_eq!(x(&movers[1]), 20.0);
+        // A long hitch runs at most MAX_PHYSICS_STEPS steps
+        assert_eq!(step_physics(&root, 1.0), MAX_PHYSICS_STEPS);
+        assert!(physics_interpolation_fraction() < 1.0);
+    }
+
+    #[test]
+    fn running_scenes_draw_as_scripts_leave_them() {
+        use crate::draw_list;
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let src = "Hud: Gem {
+            Ball: PanelGem { position: (0, 0) size: (10, 10) }
+            Menu: PanelGem { visible: false Item: PanelGem {} }
+            Badge: PanelGem { position: (0, 100) }
+            Tip: PanelGem {}
+        }";
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let file = Parser::new(tokens).parse_scene().unwrap();
+        let hud = instantiate(&file).unwrap();
+        let children = gem::children_of(&hud);
+        // A physics step moves the ball 10 across; half a step on it's drawn
+        // halfway there
+        gem::record_poses(&hud);
+        children[0].set_property("position", Value::vec2(10.0, 0.0));
+        // Scripts show the menu, and its item with it, hide the badge and
+        // free the tip
+        gem::set_visible(&children[1], true);
+        gem::set_visible(&children[2], false);
+        hud.call_method("remove_child", &[Value::Object(children[3].clone())])
+            .unwrap();
+        // The editor's edits to what scripts leave alone still show
+        let mut edited = file.clone();
+        commands::set_property(
+            &mut edited.root.children[0],
+            "size",
+            Some(ast::Value::Tuple(vec![
+                ast::Value::Integer(20),
+                ast::Value::Integer(20),
+            ])),
+        );
+
+        let list = draw_list::build(&live_scene(&edited, &file, &hud, 0.5));
+        let drawn: Vec<&[usize]> = list.quads.iter().map(|q| q.node.as_slice()).collect();
+        assert_eq!(drawn, [&[0][..], &[1], &[1, 0]]);
+        assert_eq!((list.quads[0].x, list.quads[0].w), (5.0, 20.0));
+    }
+
+    #[test]
+    fn only_drawn_changes_move_the_draw_revision() {
+        let _scene = CURRENT_SCENE_TESTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let src = "Level: Gem {
+            Clock: TimerGem { wait_time: 2 }
+            Hud: PanelGem { visible: false Score: LabelGem {} }
+        }";
+        let file = pipeline::lex_source(src)
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let level = change_scene(&file).unwrap();
+        let [clock, hud] = gem::children_of(&level).try_into().unwrap();
+        let score = gem::children_of(&hud)[0].clone();
+        // A running timer's countdown and a script's own values aren't drawn
+        assert!(draws_change(&clock, "position"));
+        assert!(!draws_change(&clock, "time_left"));
+        assert!(!draws_change(&level, "hp"));
+        // Under a hidden node only the `visible` that shows it counts
+        assert!(!draws_change(&score, "text"));
+        assert!(draws_change(&hud, "visible"));
+        // Nor does anything outside the current scene, like an autoload
+        let spare = instantiate(&file).unwrap();
+        assert!(!draws_change(&spare, "position"));
+
+        let before = object::draw_revision();
+        level
+            .call_method("add_child", &[Value::Object(spare)])
+            .unwrap();
+        assert!(object::draw_revision() > before);
+        // Every rebuild compares against the same default objects
+        assert_eq!(class_default("TimerGem"), class_default("TimerGem"));
+    }
+
+    #[test]
+    fn instantiated_scenes_pack_back_to_source() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let src = r#"Menu: PanelGem {
+    color: #334455
+    size: (200, 100)
+    %Title: LabelGem {
+        font: #assets:fonts:ui.ttf
+        text: "Paused"
+        meta:tab_order: 1
+    }
+    Tick: TimerGem {
+        wait_time: 0.5
+    }
+}
+"#;
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let file = Parser::new(tokens).parse_scene().unwrap();
+        let menu = instantiate(&file).unwrap();
+        let children = gem::children_of(&menu);
+        assert_eq!(gem::owner_of(&children[0]), Some(menu.clone()));
+        assert_eq!(children[1].class_name(), "TimerGem");
+        assert_eq!(children[0].get_meta("tab_order"), Some(Value::Int(1)));
+        assert_eq!(children[0].get_property("tab_order"), None);
+        assert_eq!(
+            children[1].call_method("get_node", &[Value::String("%Title".into())]),
+            Ok(Value::Object(children[0].clone()))
+        );
+        // Class defaults (one_shot, time_left) stay out of the packed scene
+        assert_eq!(printer::print_scene(&pack(&menu)), src);
+
+        // A runtime node is saved only once it's owned by the scene
+        let hint = object::object_new("Gem");
+        hint.set_property("name", Value::String("Hint".into()));
+        hint.set_property("align", Value::String("center".into()));
+        children[0]
+            .call_method("add_child", &[Value::Object(hint.clone())])
+            .unwrap();
+        children[0].set_property("text", Value::String("Menu".into()));
+        let packed = pack(&menu);
+        assert!(packed.root.children[0].children.is_empty());
+        gem::set_owner(&hint, Some(&menu)).unwrap();
+        let packed = pack(&menu);
+        let title = &packed.root.children[0];
+        assert_eq!(title.properties[1].value, ast::Value::String("Menu".into()));
+        assert_eq!(title.children[0].name, "Hint");
+        assert_eq!(
+            scene_tree()
+                .call_method("pack", &[Value::Object(menu)])
+                .unwrap(),
+            Value::String(printer::print_scene(&packed))
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Local variable scopes in logic files, checked by `pipeline::compile_logic`.
+//! `let name = value` declares a local that lives until the end of its block,
+//! as do a function's or event's parameters for its body and a `for` loop's
+//! variable for the loop's. An inner block may shadow an outer local, but a
+//! block can't declare the same name twice. A name no `let` declares is one
+//! of the node's properties or functions, or a singleton, so the error caught
+//! here is using a local before its `let`.
+
+use crate::ast::{Block, Expr, LogicFile, Stmt};
+
+/// The first misused local in `logic`, as `function: message`
+pub fn check(logic: &LogicFile) -> Result<(), String> {
+    let bodies = logic
+        .events
+        .iter()
+        .map(|e| (&e.name, &e.params, &e.body))
+        .chain(
+            logic
+                .functions
+                .iter()
+                .map(|f| (&f.name, &f.params, &f.body)),
+        );
+    for (name, params, body) in bodies {
+        Scopes::default()
+            .block(body, params)
+            .map_err(|e| format!("{}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Scopes<'a> {
+    /// Names declared so far in each enclosing block, innermost last
+    declared: Vec<Vec<&'a str>>,
+    /// Names each enclosing block declares later on
+    pending: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Scopes<'a> {
+    fn block(&mut self, block: &'a Block, locals: &'a [String]) -> Result<(), String> {
+        self.declared
+            .push(locals.iter().map(String::as_str).collect());
+        self.pending.push(
+            block
+                .statements
+                .iter()
+                .filter_map(|s| match s {
+                    Stmt::Let { name, .. } => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        );
+        for stmt in &block.statements {
+            self.stmt(stmt)?;
+        }
+        self.declared.pop();
+        self.pending.pop();
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Let { name, value } => {
+                // The value sees the name as it was before, so `let x = x - 1`
+                // can shadow an outer `x`
+                let pending = self.pending.last_mut().expect("inside a block");
+                if let Some(i) = pending.iter().position(|p| p == name) {
+                    pending.remove(i);
+                }
+                self.expr(value)?;
+                let current = self.declared.last_mut().expect("inside a block");
+                if current.contains(&name.as_str()) {
+                    return Err(format!("'{}' is already declared in this block", name));
+                }
+                current.push(name);
+            }
+            Stmt::Assignment { target, value } => {
+                self.expr(value)?;
+                self.name(target)?;
+            }
+            Stmt::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition)?;
+                self.block(then_block, &[])?;
+                if let Some(block) = else_block {
+                    self.block(block, &[])?;
+                }
+            }
+            Stmt::For {
+                var,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable)?;
+                self.block(body, std::slice::from_ref(var))?;
+            }
+            Stmt::Match { value, arms } => {
+                self.expr(value)?;
+                for arm in arms {
+                    self.block(&arm.body, &[])?;
+                }
+            }
+            Stmt::Call { args, .. } => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+            }
+            Stmt::Return(Some(expr)) | Stmt::ExprStmt(expr) => self.expr(expr)?,
+            Stmt::Spawn { .. } | Stmt::Return(None) => {}
+        }
+        Ok(())
+    }
+
+    fn expr(&self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Ident(name) => self.name(name),
+            Expr::Tuple(items) | Expr::Call { args: items, .. } => {
+                items.iter().try_for_each(|e| self.expr(e))
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            Expr::Range { start, end } => {
+                self.expr(start)?;
+                self.expr(end)
+            }
+            Expr::UnaryOp { expr, .. } => self.expr(expr),
+            Expr::PropertyAccess { object, .. } => self.expr(object),
+            Expr::MethodCall { object, args, .. } => {
+                self.expr(object)?;
+                args.iter().try_for_each(|e| self.expr(e))
+            }
+            Expr::Number(_)
+            | Expr::Integer(_)
+            | Expr::String(_)
+            | Expr::Bool(_)
+            | Expr::Directive(_) => Ok(()),
+        }
+    }
+
+    /// A local declared in an enclosing block, or a name no `let` declares
+    fn name(&self, name: &str) -> Result<(), String> {
+        // The innermost block that knows the name decides
+        for (declared, pending) in self.declared.iter().zip(&self.pending).rev() {
+            if declared.contains(&name) {
+                return Ok(());
+            }
+            if pending.contains(&name) {
+                return Err(format!("'{}' is used before its 'let'", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    fn check_src(body: &str) -> Result<(), String> {
+        let src = format!("extend Gem\nfn f(speed) {{ {} }}", body);
+        check(&pipeline::parse_logic(pipeline::lex_source(&src)?)?)
+    }
+
+    #[test]
+    fn locals_are_declared_before_use() {
+        assert_eq!(
+            check_src("let x = speed * 2 if x > 1 { let x = x - 1 position = (x, 0) } hp = x"),
+            Ok(())
+        );
+        // Undeclared names are properties; loop variables are locals
+        assert_eq!(check_src("hp = hp + 1 for i in 0..3 { let y = i }"), Ok(()));
+        // Each match arm is a block of its own
+        assert_eq!(
+            check_src("match hp { 1 => { let y = 1 } _ => { let y = 2 } }"),
+            Ok(())
+        );
+
+        let err = |body| check_src(body).unwrap_err();
+        assert_eq!(err("hp = x let x = 1"), "f: 'x' is used before its 'let'");
+        assert!(err("if hp { x = 2 } let x = 1").contains("before its 'let'"));
+        assert!(err("let x = 1 let x = 2").contains("already declared"));
+        assert!(err("let 5 = 1").contains("variable name"));
+        assert!(err("let x 1").contains("Expected '=' after 'let x'"));
+    }
+}
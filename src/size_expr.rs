@@ -0,0 +1,226 @@
+//! Size expressions: `size: (parent.width - 20, 40)`.
+//! A size component may be arithmetic (`+ - * /`, parentheses) over numbers
+//! and the `width` / `height` of `parent`, the enclosing node's size, or
+//! `viewport`, the window's. The layout pass in `draw_list` resolves them on
+//! every build, so they follow window resizes.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    Width,
+    Height,
+}
+
+impl Axis {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "width" => Some(Axis::Width),
+            "height" => Some(Axis::Height),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Axis::Width => "width",
+            Axis::Height => "height",
+        }
+    }
+
+    fn of(self, size: (f32, f32)) -> f32 {
+        match self {
+            Axis::Width => size.0,
+            Axis::Height => size.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Op::Add | Op::Sub => 1,
+            Op::Mul | Op::Div => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeExpr {
+    Integer(i64),
+    Number(f64),
+    /// `parent.width` / `parent.height`
+    Parent(Axis),
+    /// `viewport.width` / `viewport.height`
+    Viewport(Axis),
+    Neg(Box<SizeExpr>),
+    Op(Op, Box<SizeExpr>, Box<SizeExpr>),
+}
+
+impl SizeExpr {
+    /// The value with the given parent and viewport sizes
+    pub fn eval(&self, parent: (f32, f32), viewport: (f32, f32)) -> f32 {
+        match self {
+            SizeExpr::Integer(i) => *i as f32,
+            SizeExpr::Number(n) => *n as f32,
+            SizeExpr::Parent(axis) => axis.of(parent),
+            SizeExpr::Viewport(axis) => axis.of(viewport),
+            SizeExpr::Neg(e) => -e.eval(parent, viewport),
+            SizeExpr::Op(op, a, b) => {
+                let (a, b) = (a.eval(parent, viewport), b.eval(parent, viewport));
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    Op::Div => a / b,
+                }
+            }
+        }
+    }
+
+    /// The value, when it refers to neither `parent` nor `viewport`
+    pub fn constant(&self) -> Option<f64> {
+        match self {
+            SizeExpr::Integer(i) => Some(*i as f64),
+            SizeExpr::Number(n) => Some(*n),
+            SizeExpr::Parent(_) | SizeExpr::Viewport(_) => None,
+            SizeExpr::Neg(e) => Some(-e.constant()?),
+            SizeExpr::Op(op, a, b) => {
+                let (a, b) = (a.constant()?, b.constant()?);
+                Some(match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    Op::Div => a / b,
+                })
+            }
+        }
+    }
+
+    /// The value, when it is made of integers only and every division in it
+    /// comes out even
+    pub fn integer(&self) -> Option<i64> {
+        match self {
+            SizeExpr::Integer(i) => Some(*i),
+            SizeExpr::Number(_) | SizeExpr::Parent(_) | SizeExpr::Viewport(_) => None,
+            SizeExpr::Neg(e) => e.integer()?.checked_neg(),
+            SizeExpr::Op(op, a, b) => {
+                let (a, b) = (a.integer()?, b.integer()?);
+                match op {
+                    Op::Add => a.checked_add(b),
+                    Op::Sub => a.checked_sub(b),
+                    Op::Mul => a.checked_mul(b),
+                    Op::Div if b != 0 && a % b == 0 => a.checked_div(b),
+                    Op::Div => None,
+                }
+            }
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            SizeExpr::Op(op, ..) => op.precedence(),
+            _ => 3,
+        }
+    }
+}
+
+/// Scene-file spelling, with only the parentheses precedence needs
+impl fmt::Display for SizeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeExpr::Integer(i) => write!(f, "{}", i),
+            SizeExpr::Number(n) => write!(f, "{}", n),
+            SizeExpr::Parent(axis) => write!(f, "parent.{}", axis.as_str()),
+            SizeExpr::Viewport(axis) => write!(f, "viewport.{}", axis.as_str()),
+            SizeExpr::Neg(e) if e.precedence() < 3 => write!(f, "-({})", e),
+            SizeExpr::Neg(e) => write!(f, "-{}", e),
+            SizeExpr::Op(op, a, b) => {
+                let p = op.precedence();
+                if a.precedence() < p {
+                    write!(f, "({})", a)?;
+                } else {
+                    write!(f, "{}", a)?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                // `a - (b - c)` and `a / (b * c)` keep their grouping
+                if b.precedence() < p || (b.precedence() == p && matches!(op, Op::Sub | Op::Div)) {
+                    write!(f, "({})", b)
+                } else {
+                    write!(f, "{}", b)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Value;
+    use crate::pipeline;
+
+    fn size_of(src: &str) -> Value {
+        let scene = pipeline::lex_source(&format!("Main: PanelGem {{ size: {} }}", src))
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        scene.root.properties[0].value.clone()
+    }
+
+    #[test]
+    fn parses_evaluates_and_prints() {
+        let Value::Tuple(items) = size_of("(parent.width - 20, viewport.height * 0.5 + 4)") else {
+            panic!("expected a tuple");
+        };
+        let [Value::SizeExpr(w), Value::SizeExpr(h)] = items.as_slice() else {
+            panic!("expected two expressions, got {:?}", items);
+        };
+        assert_eq!(w.eval((300.0, 100.0), (800.0, 600.0)), 280.0);
+        assert_eq!(h.eval((300.0, 100.0), (800.0, 600.0)), 304.0);
+        assert_eq!(w.to_string(), "parent.width - 20");
+
+        let Value::Tuple(items) = size_of("(viewport.width / (2 + 2), -(parent.height - 10))")
+        else {
+            panic!("expected a tuple");
+        };
+        let printed: Vec<String> = items.iter().map(crate::printer::print_value).collect();
+        assert_eq!(
+            printed,
+            ["viewport.width / (2 + 2)", "-(parent.height - 10)"]
+        );
+
+        // Constant arithmetic and negative literals fold to plain numbers,
+        // integers while every operand is one and divisions come out even
+        assert_eq!(
+            size_of("(-5, 2 * 10, -(3), 7 / 2, 1.5 * 2)"),
+            Value::Tuple(vec![
+                Value::Integer(-5),
+                Value::Integer(20),
+                Value::Integer(-3),
+                Value::Number(3.5),
+                Value::Number(3.0),
+            ])
+        );
+        assert!(
+            pipeline::lex_source("Main: PanelGem { size: (parent.depth, 1) }")
+                .and_then(pipeline::parse_scene)
+                .is_err()
+        );
+    }
+}
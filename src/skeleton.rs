@@ -0,0 +1,460 @@
+//! SkeletonGem: plays the animations of the glTF `model` it was imported from
+//! on the nodes below it. `play("walk")` starts a clip from the beginning, and
+//! `play("run", 0.3)` crossfades to it from the playing one over 0.3 seconds.
+//! Each frame `_process(dt)` samples the clips and sets the animated nodes'
+//! `position`, `rotation` and `scale`. Clips loop unless `looping` is false;
+//! then they hold their last pose and emit `animation_finished(name)`.
+//! BoneAttachmentGem children follow the node their `bone` names, wherever it
+//! is below the skeleton, so a prop can ride a hand without editing the
+//! imported tree.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::animation::{self, Clip, Target};
+use crate::gem;
+use crate::importers::gltf;
+use crate::object::{MethodTable, ObjectRef, define_method, register_class};
+use crate::pipeline;
+use crate::transform3d::{self, NO_ROTATION, Rotation};
+use crate::value::Value;
+
+const MODEL_KEY: &str = "model";
+const LOOPING_KEY: &str = "looping";
+/// The clip playing, or Null
+const CURRENT_KEY: &str = "current_animation";
+const TIME_KEY: &str = "animation_time";
+/// The clip fading out during a crossfade, its time and the fade's length
+/// and seconds left
+const BLEND_FROM_KEY: &str = "blend_from";
+const BLEND_FROM_TIME_KEY: &str = "blend_from_time";
+const BLEND_TIME_KEY: &str = "blend_time";
+const BLEND_LEFT_KEY: &str = "blend_left";
+const BONE_KEY: &str = "bone";
+const ATTACHMENT_TYPE: &str = "BoneAttachmentGem";
+
+/// Clips by model directive, read once
+static CLIPS: OnceLock<RwLock<HashMap<String, Arc<Vec<Clip>>>>> = OnceLock::new();
+
+pub fn init_skeleton_classes() {
+    register_class("SkeletonGem", || {
+        let obj = gem::new_gem("SkeletonGem", skeleton_methods());
+        obj.set_property(LOOPING_KEY, Value::Bool(true));
+        obj.set_property(CURRENT_KEY, Value::Null);
+        obj
+    });
+    register_class(ATTACHMENT_TYPE, || {
+        gem::new_gem(ATTACHMENT_TYPE, gem::gem_methods())
+    });
+}
+
+fn skeleton_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_skeleton_methods()))
+        .clone()
+}
+
+fn build_skeleton_methods() -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+
+    // play(name, blend?): crossfade over `blend` seconds from what is playing
+    define_method(
+        &mut methods,
+        "play",
+        Arc::new(|this, args| {
+            let Some(Value::String(name)) = args.first() else {
+                return Err("play expects an animation name".into());
+            };
+            if find(&clips(this)?, name).is_none() {
+                return Err(format!(
+                    "{} has no animation named {}",
+                    node_name(this),
+                    name
+                ));
+            }
+            let blend = args.get(1).and_then(Value::as_f64).unwrap_or(0.0);
+            match this.get_property(CURRENT_KEY) {
+                Some(Value::String(from)) if blend > 0.0 => {
+                    this.set_property(BLEND_FROM_KEY, Value::String(from));
+                    this.set_property(
+                        BLEND_FROM_TIME_KEY,
+                        Value::Float(f64_property(this, TIME_KEY)),
+                    );
+                    this.set_property(BLEND_TIME_KEY, Value::Float(blend));
+                    this.set_property(BLEND_LEFT_KEY, Value::Float(blend));
+                }
+                _ => this.set_property(BLEND_FROM_KEY, Value::Null),
+            }
+            this.set_property(CURRENT_KEY, Value::String(name.clone()));
+            this.set_property(TIME_KEY, Value::Float(0.0));
+            Ok(Value::Null)
+        }),
+    );
+    // stop(): the nodes keep their current pose
+    define_method(
+        &mut methods,
+        "stop",
+        Arc::new(|this, _| {
+            this.set_property(CURRENT_KEY, Value::Null);
+            this.set_property(BLEND_FROM_KEY, Value::Null);
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "is_playing",
+        Arc::new(|this, _| {
+            Ok(Value::Bool(matches!(
+                this.get_property(CURRENT_KEY),
+                Some(Value::String(_))
+            )))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_current_animation",
+        Arc::new(|this, _| Ok(this.get_property(CURRENT_KEY).unwrap_or(Value::Null))),
+    );
+    define_method(
+        &mut methods,
+        "get_animation_list",
+        Arc::new(|this, _| {
+            Ok(Value::Array(
+                clips(this)?
+                    .iter()
+                    .map(|c| Value::String(c.name.clone()))
+                    .collect(),
+            ))
+        }),
+    );
+    // _process(dt), called by the scene tree
+    define_method(
+        &mut methods,
+        "_process",
+        Arc::new(|this, args| {
+            let dt = args
+                .first()
+                .and_then(Value::as_f64)
+                .ok_or("_process expects dt")?;
+            advance(this, dt)?;
+            Ok(Value::Null)
+        }),
+    );
+
+    // signals: animation_finished(name)
+    methods
+}
+
+fn f64_property(node: &ObjectRef, key: &str) -> f64 {
+    node.with_property(key, |v| v.and_then(Value::as_f64))
+        .unwrap_or(0.0)
+}
+
+fn node_name(node: &ObjectRef) -> String {
+    match node.get_property("name") {
+        Some(Value::String(s)) => s,
+        _ => node.class_name().to_string(),
+    }
+}
+
+/// The clips of the skeleton's `model`
+fn clips(skeleton: &ObjectRef) -> Result<Arc<Vec<Clip>>, String> {
+    let Some(Value::String(model)) = skeleton.get_property(MODEL_KEY) else {
+        return Err(format!("{} has no model", node_name(skeleton)));
+    };
+    let cache = CLIPS.get_or_init(Default::default);
+    if let Some(clips) = cache.read().unwrap().get(&model) {
+        return Ok(clips.clone());
+    }
+    let directive = pipeline::parse_directive(&model)?;
+    let path: PathBuf = directive.segments.iter().collect();
+    let clips = Arc::new(gltf::animations(&path)?);
+    let mut cache = cache.write().unwrap();
+    Ok(cache.entry(model).or_insert(clips).clone())
+}
+
+fn find<'a>(clips: &'a [Clip], name: &str) -> Option<&'a Clip> {
+    clips.iter().find(|c| c.name == name)
+}
+
+/// `time` after `dt` more seconds of `clip`, wrapped when looping
+fn step(clip: &Clip, time: f64, dt: f64, looping: bool) -> f64 {
+    let duration = clip.duration();
+    let time = time + dt;
+    if time < duration || duration <= 0.0 {
+        time
+    } else if looping {
+        time % duration
+    } else {
+        duration
+    }
+}
+
+fn advance(skeleton: &ObjectRef, dt: f64) -> Result<(), String> {
+    let Some(Value::String(name)) = skeleton.get_property(CURRENT_KEY) else {
+        return Ok(());
+    };
+    let clips = clips(skeleton)?;
+    let Some(clip) = find(&clips, &name) else {
+        return Ok(());
+    };
+    let looping = !matches!(skeleton.get_property(LOOPING_KEY), Some(Value::Bool(false)));
+    let before = f64_property(skeleton, TIME_KEY);
+    let time = step(clip, before, dt, looping);
+    skeleton.set_property(TIME_KEY, Value::Float(time));
+    let mut pose = clip.pose(time);
+
+    let from = match skeleton.get_property(BLEND_FROM_KEY) {
+        Some(Value::String(from)) => find(&clips, &from),
+        _ => None,
+    };
+    if let Some(from) = from {
+        let left = f64_property(skeleton, BLEND_LEFT_KEY) - dt;
+        if left <= 0.0 {
+            skeleton.set_property(BLEND_FROM_KEY, Value::Null);
+        } else {
+            let from_time = step(
+                from,
+                f64_property(skeleton, BLEND_FROM_TIME_KEY),
+                dt,
+                looping,
+            );
+            skeleton.set_property(BLEND_FROM_TIME_KEY, Value::Float(from_time));
+            skeleton.set_property(BLEND_LEFT_KEY, Value::Float(left));
+            let weight = 1.0 - left / f64_property(skeleton, BLEND_TIME_KEY);
+            pose = animation::blend(&from.pose(from_time), &pose, weight);
+        }
+    }
+
+    for (path, target, value) in pose {
+        let Some(node) = node_at(skeleton, &path) else {
+            continue;
+        };
+        let value = match target {
+            Target::Rotation => transform3d::angles(&transform3d::quaternion_rotation(value)),
+            _ => [value[0], value[1], value[2]],
+        };
+        node.set_property(target.key(), vec3_value(value));
+    }
+    attach(skeleton);
+
+    if !looping && before < clip.duration() && time >= clip.duration() {
+        skeleton.emit_signal("animation_finished", &[Value::String(name)]);
+    }
+    Ok(())
+}
+
+fn node_at(skeleton: &ObjectRef, path: &str) -> Option<ObjectRef> {
+    if path.is_empty() {
+        return Some(skeleton.clone());
+    }
+    skeleton
+        .call_method("get_node", &[Value::String(path.to_string())])
+        .ok()?
+        .as_object()
+}
+
+fn vec3_value(v: [f64; 3]) -> Value {
+    Value::Array(v.into_iter().map(Value::Float).collect())
+}
+
+fn vec3_property(node: &ObjectRef, key: &str, default: f64) -> [f64; 3] {
+    match node.get_property(key) {
+        Some(Value::Array(items)) if items.len() == 3 => {
+            std::array::from_fn(|i| items[i].as_f64().unwrap_or(default))
+        }
+        _ => [default; 3],
+    }
+}
+
+/// Place each BoneAttachmentGem child where its bone is, relative to the
+/// skeleton
+fn attach(skeleton: &ObjectRef) {
+    for attachment in gem::children_of(skeleton) {
+        if attachment.class_name() != ATTACHMENT_TYPE {
+            continue;
+        }
+        let Some(Value::String(bone)) = attachment.get_property(BONE_KEY) else {
+            continue;
+        };
+        let Some(node) = find_bone(skeleton, &bone) else {
+            eprintln!(
+                "[WARN] {}: {} has no bone named {}",
+                node_name(&attachment),
+                node_name(skeleton),
+                bone
+            );
+            continue;
+        };
+        let (position, rotation, scale) = relative_transform(skeleton, &node);
+        attachment.set_property("position", vec3_value(position));
+        attachment.set_property("rotation", vec3_value(transform3d::angles(&rotation)));
+        attachment.set_property("scale", vec3_value(scale));
+    }
+}
+
+/// The first node below `skeleton` named `bone`, skipping attachments
+fn find_bone(skeleton: &ObjectRef, bone: &str) -> Option<ObjectRef> {
+    let mut todo = gem::children_of(skeleton);
+    todo.reverse();
+    while let Some(node) = todo.pop() {
+        if node.class_name() == ATTACHMENT_TYPE {
+            continue;
+        }
+        if node_name(&node) == bone {
+            return Some(node);
+        }
+        let mut children = gem::children_of(&node);
+        children.reverse();
+        todo.extend(children);
+    }
+    None
+}
+
+/// `node`'s position, rotation and scale in `skeleton`'s space; scales
+/// multiply per axis, which is exact unless a rotated parent scales unevenly
+fn relative_transform(skeleton: &ObjectRef, node: &ObjectRef) -> ([f64; 3], Rotation, [f64; 3]) {
+    let mut chain = Vec::new();
+    let mut at = Some(node.clone());
+    while let Some(n) = at.filter(|n| n != skeleton) {
+        at = gem::parent_of(&n);
+        chain.push(n);
+    }
+    let (mut position, mut rotation, mut scale) = ([0.0; 3], NO_ROTATION, [1.0; 3]);
+    for n in chain.iter().rev() {
+        let local = vec3_property(n, "position", 0.0);
+        let offset = transform3d::rotate(&rotation, std::array::from_fn(|i| local[i] * scale[i]));
+        position = std::array::from_fn(|i| position[i] + offset[i]);
+        rotation = transform3d::compose(
+            &rotation,
+            &transform3d::rotation_of(vec3_property(n, "rotation", 0.0)),
+        );
+        let local_scale = vec3_property(n, "scale", 1.0);
+        scale = std::array::from_fn(|i| scale[i] * local_scale[i]);
+    }
+    (position, rotation, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource;
+    use crate::scene_tree;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    #[test]
+    fn skeletons_play_and_crossfade_model_animations() {
+        let dir = Path::new("target").join("gem_skeleton_test");
+        fs::create_dir_all(&dir).unwrap();
+        // Key times 0 and 1, then Arm positions (0, 0, 0) and (2, 0, 0), then
+        // Hand's yaw keys: none and a quarter turn
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let floats = [
+            0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, half, 0.0, half,
+        ];
+        let bytes: Vec<u8> = floats.iter().flat_map(|f: &f32| f.to_le_bytes()).collect();
+        fs::write(dir.join("hero.bin"), bytes).unwrap();
+        fs::write(
+            dir.join("hero.gltf"),
+            r#"{
+                "asset": {"version": "2.0"},
+                "scenes": [{"nodes": [0]}],
+                "nodes": [
+                    {"name": "Arm", "children": [1]},
+                    {"name": "Hand", "translation": [0, 1, 0]}
+                ],
+                "animations": [
+                    {"name": "reach", "samplers": [{"input": 0, "output": 1}], "channels": [{"sampler": 0, "target": {"node": 0, "path": "translation"}}]},
+                    {"name": "wave", "samplers": [{"input": 0, "output": 2, "interpolation": "STEP"}], "channels": [{"sampler": 0, "target": {"node": 1, "path": "rotation"}}]}
+                ],
+                "accessors": [
+                    {"bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR"},
+                    {"bufferView": 0, "byteOffset": 8, "componentType": 5126, "count": 2, "type": "VEC3"},
+                    {"bufferView": 0, "byteOffset": 32, "componentType": 5126, "count": 2, "type": "VEC4"}
+                ],
+                "bufferViews": [{"buffer": 0, "byteLength": 64}],
+                "buffers": [{"uri": "hero.bin", "byteLength": 64}]
+            }"#,
+        )
+        .unwrap();
+
+        scene_tree::init_builtin_classes();
+        let model = resource::load(&dir.join("hero.gltf")).unwrap();
+        let hero = scene_tree::instantiate(resource::packed_scene(&model).unwrap()).unwrap();
+        assert_eq!(hero.class_name(), "SkeletonGem");
+        let arm = gem::children_of(&hero)[0].clone();
+        let hand = gem::children_of(&arm)[0].clone();
+        let sword = crate::object::object_new(ATTACHMENT_TYPE);
+        sword.set_property(BONE_KEY, Value::String("Hand".into()));
+        hero.call_method("add_child", &[Value::Object(sword.clone())])
+            .unwrap();
+        assert_eq!(
+            hero.call_method("get_animation_list", &[]).unwrap(),
+            Value::Array(vec![
+                Value::String("reach".into()),
+                Value::String("wave".into())
+            ])
+        );
+
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let seen = finished.clone();
+        hero.connect(
+            "animation_finished",
+            Arc::new(move |args| seen.lock().unwrap().push(args[0].clone())),
+        );
+        let step = |dt: f64| {
+            hero.call_method("_process", &[Value::Float(dt)]).unwrap();
+        };
+        hero.call_method("play", &[Value::String("reach".into())])
+            .unwrap();
+        step(0.25);
+        assert_eq!(
+            arm.get_property("position"),
+            Some(vec3_value([0.5, 0.0, 0.0]))
+        );
+        // The sword rides the hand: the arm's position plus the hand's
+        assert_eq!(
+            sword.get_property("position"),
+            Some(vec3_value([0.5, 1.0, 0.0]))
+        );
+        // Looping wraps around
+        step(1.0);
+        assert_eq!(
+            arm.get_property("position"),
+            Some(vec3_value([0.5, 0.0, 0.0]))
+        );
+
+        // Halfway through a crossfade, each clip holds half the pose
+        hero.call_method("play", &[Value::String("wave".into()), Value::Float(0.5)])
+            .unwrap();
+        step(0.25);
+        assert_eq!(
+            arm.get_property("position"),
+            Some(vec3_value([1.0, 0.0, 0.0]))
+        );
+        // Once the fade is over, the arm keeps its last pose; wave holds its
+        // first key until the second
+        step(0.5);
+        assert_eq!(
+            arm.get_property("position"),
+            Some(vec3_value([1.0, 0.0, 0.0]))
+        );
+        assert_eq!(vec3_property(&hand, "rotation", 0.0), [0.0; 3]);
+
+        // Without looping, the clip stops on its last key and says so once
+        hero.set_property(LOOPING_KEY, Value::Bool(false));
+        step(0.5);
+        let yaw = vec3_property(&hand, "rotation", 0.0)[1];
+        assert!((yaw - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        step(1.0);
+        assert_eq!(*finished.lock().unwrap(), [Value::String("wave".into())]);
+        let err = hero
+            .call_method("play", &[Value::String("run".into())])
+            .unwrap_err();
+        assert!(err.contains("has no animation named run"));
+    }
+}
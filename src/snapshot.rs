@@ -0,0 +1,193 @@
+//! Golden tests of generated code. Each scene's Rust code is kept in a
+//! snapshot under the project's `.snapshots` folder, mirroring `.gen`, and
+//! `gem verify-codegen` compiles the scenes and compares their code against
+//! it, printing a line diff for each that changed. `--update` rewrites the
+//! snapshots once a change is intended.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::pipeline;
+use crate::project::{self, FileKind};
+
+pub const DIR: &str = ".snapshots";
+
+/// Unchanged lines shown around each change in a diff
+const CONTEXT: usize = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Matches,
+    /// The snapshot and a diff from it to the generated code
+    Differs(PathBuf, String),
+    Missing(PathBuf),
+}
+
+/// `<root>/.snapshots/<relative>.rs` for `scene`
+pub fn path(root: &Path, scene: &Path) -> PathBuf {
+    let mut out = root.join(DIR).join(mirrored(root, scene));
+    out.set_extension("rs");
+    out
+}
+
+/// Where `scene` goes under a folder of `root` that mirrors it, such as
+/// `.gen`: its path below `root`, or just its file name for a path outside
+/// it that joining would escape
+pub fn mirrored(root: &Path, scene: &Path) -> PathBuf {
+    let relative = scene.strip_prefix(root).unwrap_or(scene);
+    if relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return relative.to_path_buf();
+    }
+    scene.file_name().map_or_else(PathBuf::new, PathBuf::from)
+}
+
+/// Every scene the project behind `registry` reaches
+pub fn project_scenes(registry: &str) -> Result<Vec<PathBuf>, String> {
+    let graph = project::build(registry)?;
+    Ok(graph
+        .files
+        .into_iter()
+        .filter(|(_, kind)| *kind == FileKind::Scene)
+        .map(|(file, _)| file)
+        .collect())
+}
+
+/// Compare `scene`'s generated code with its snapshot
+pub fn verify(root: &Path, scene: &Path) -> Result<Outcome, String> {
+    let code = code_of(scene)?;
+    let snapshot = path(root, scene);
+    let Ok(expected) = fs::read_to_string(&snapshot) else {
+        return Ok(Outcome::Missing(snapshot));
+    };
+    if expected == code {
+        return Ok(Outcome::Matches);
+    }
+    Ok(Outcome::Differs(snapshot, diff(&expected, &code)))
+}
+
+/// Write `scene`'s generated code as its snapshot, returning the snapshot
+pub fn update(root: &Path, scene: &Path) -> Result<PathBuf, String> {
+    let code = code_of(scene)?;
+    let snapshot = path(root, scene);
+    if let Some(parent) = snapshot.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    fs::write(&snapshot, code).map_err(|e| format!("{}: {}", snapshot.display(), e))?;
+    Ok(snapshot)
+}
+
+fn code_of(scene: &Path) -> Result<String, String> {
+    // Scenes named on the command line are paths on disk, not res:// paths
+    let content = fs::read_to_string(scene).map_err(|e| format!("{}: {}", scene.display(), e))?;
+    pipeline::scene_code(&content).map_err(|e| format!("{}: {}", scene.display(), e))
+}
+
+/// `-` for lines only in `old` and `+` for lines only in `new`, each run of
+/// changes under an `@@ line N` header with a few lines around it
+pub fn diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    // (marker, old line number, text)
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', i, old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', i, old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', i, new[j]));
+            j += 1;
+        }
+    }
+
+    let near_change = |at: usize| {
+        let from = at.saturating_sub(CONTEXT);
+        let to = (at + CONTEXT + 1).min(lines.len());
+        lines[from..to].iter().any(|(marker, ..)| *marker != ' ')
+    };
+    let mut out = String::new();
+    let mut in_hunk = false;
+    for (at, (marker, line, text)) in lines.iter().enumerate() {
+        if !near_change(at) {
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            out.push_str(&format!("@@ line {}\n", line + 1));
+            in_hunk = true;
+        }
+        out.push_str(&format!("{} {}\n", marker, text));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformer::Transformer;
+
+    #[test]
+    fn example_scenes_match_their_snapshots() {
+        let root = Path::new("example");
+        let scenes = project_scenes("example/scenes.registry.gem").unwrap();
+        assert!(scenes.len() >= 3);
+        for scene in scenes {
+            match verify(root, &scene).unwrap() {
+                Outcome::Matches => {}
+                Outcome::Differs(snapshot, diff) => panic!(
+                    "{} no longer matches {} (`gem verify-codegen --update` to accept):\n{}",
+                    scene.display(),
+                    snapshot.display(),
+                    diff
+                ),
+                Outcome::Missing(snapshot) => panic!("{} is missing", snapshot.display()),
+            }
+        }
+
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        assert_eq!(
+            diff(old, new),
+            "@@ line 2\n  b\n  c\n- d\n+ D\n  e\n  f\n@@ line 11\n  k\n  l\n+ m\n"
+        );
+        assert_eq!(
+            path(root, Path::new("example/menus/pause.gem")),
+            PathBuf::from("example/.snapshots/menus/pause.rs")
+        );
+        // Paths outside the project can't escape the snapshot folder
+        assert_eq!(
+            path(root, Path::new("/tmp/pause.gem")),
+            PathBuf::from("example/.snapshots/pause.rs")
+        );
+    }
+
+    #[test]
+    fn example_scenes_pass_strict_mode() {
+        for scene in project_scenes("example/scenes.registry.gem").unwrap() {
+            let content = fs::read_to_string(&scene).unwrap();
+            let ast = pipeline::lex_source(&content)
+                .and_then(pipeline::parse_scene)
+                .unwrap();
+            if let Err(e) = Transformer::new().with_strict(true).transform(ast) {
+                panic!("{}: {}", scene.display(), e);
+            }
+        }
+    }
+}
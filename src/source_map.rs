@@ -0,0 +1,156 @@
+//! Maps the nodes of a scene file back to the lines that declare them.
+//! Tokens don't carry positions, so the map is rebuilt from the source text:
+//! declarations (`Name: Type {`) appear in the same depth-first order as the
+//! parsed tree, which pairs each one with its node. Runtime errors use it to
+//! point at `file:line`.
+
+use std::collections::HashMap;
+
+use crate::ast;
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    file: String,
+    /// Line (1-based) of each node, keyed by the names from the scene root down
+    lines: HashMap<Vec<String>, usize>,
+}
+
+impl SourceMap {
+    /// Map `scene`, parsed from `source`; the map stays empty if the two
+    /// don't line up
+    pub fn new(file: &str, source: &str, scene: &ast::GemFile) -> Self {
+        let mut paths = Vec::new();
+        collect_paths(&scene.root, &mut Vec::new(), &mut paths);
+        let lines = declaration_lines(source);
+        let lines = if lines.len() == paths.len() {
+            paths.into_iter().zip(lines).collect()
+        } else {
+            HashMap::new()
+        };
+        Self {
+            file: file.to_string(),
+            lines,
+        }
+    }
+
+    /// `file:line` of the node at `names`, starting with the scene root's name
+    pub fn locate(&self, names: &[String]) -> Option<String> {
+        let line = self.lines.get(names)?;
+        Some(format!("{}:{}", self.file, line))
+    }
+}
+
+fn collect_paths(decl: &ast::GemDecl, path: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    path.push(decl.name.clone());
+    out.push(path.clone());
+    for child in &decl.children {
+        collect_paths(child, path, out);
+    }
+    path.pop();
+}
+
+/// The line of every `Name: Type {` (or `%Name: Type {`), skipping strings
+/// and comments
+fn declaration_lines(source: &str) -> Vec<usize> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lines = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let ident_end = |mut j: usize| {
+        while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+            j += 1;
+        }
+        j
+    };
+    // Uppercase, or a letter without case (CJK)
+    let starts_name = |c: char| c.is_alphabetic() && !c.is_lowercase();
+    let skip_spaces = |mut j: usize| {
+        while j < chars.len() && matches!(chars[j], ' ' | '\t') {
+            j += 1;
+        }
+        j
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    } else if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if next == Some('#') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '#' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            c if c == '%' || starts_name(c) => {
+                let start = if c == '%' { i + 1 } else { i };
+                let name_end = ident_end(start);
+                let starts_ident = chars.get(start).is_some_and(|&c| starts_name(c));
+                // A name must not continue an identifier, e.g. the `P` in `myP`
+                let fresh = i == 0 || !(chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+                let mut j = skip_spaces(name_end);
+                if starts_ident && fresh && chars.get(j) == Some(&':') {
+                    j = skip_spaces(j + 1);
+                    let type_end = ident_end(j);
+                    if type_end > j && chars.get(skip_spaces(type_end)) == Some(&'{') {
+                        lines.push(line);
+                    }
+                }
+                i = name_end.max(i + 1);
+            }
+            _ => i += 1,
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    #[test]
+    fn nodes_map_to_their_declaration_lines() {
+        let source = r#"// Menu: Gem { commented out }
+Main: Gem {
+    title: "Fake: Gem {"
+    Title: LabelGem { text: "Hi" }
+    %Hud: Gem { Score: LabelGem {} }
+}"#;
+        let scene = pipeline::lex_source(source)
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let map = SourceMap::new("main.gem", source, &scene);
+        let at = |path: &[&str]| {
+            let names: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+            map.locate(&names)
+        };
+        assert_eq!(at(&["Main"]).as_deref(), Some("main.gem:2"));
+        assert_eq!(at(&["Main", "Title"]).as_deref(), Some("main.gem:4"));
+        assert_eq!(at(&["Main", "Hud", "Score"]).as_deref(), Some("main.gem:5"));
+        assert_eq!(at(&["Main", "Missing"]), None);
+    }
+}
@@ -0,0 +1,215 @@
+//! StateMachineGem: a Gem whose children are its states.
+//! One child is active at a time. `transition_to(name)` runs the old state's
+//! `on_exit` and the new state's `on_enter`, and `update(dt)` forwards
+//! `on_state_update(dt)` to the active state; the scene tree calls it every
+//! frame the machine may process. Handlers are methods on the state
+//! node, which is where its script installs them; missing ones are skipped.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+const CURRENT_KEY: &str = "current_state";
+/// State entered by the first `update` when nothing is active; defaults to the first child
+const INITIAL_KEY: &str = "initial_state";
+
+pub fn init_state_machine_class() {
+    register_class("StateMachineGem", || {
+        let obj = gem::new_gem("StateMachineGem", state_machine_methods());
+        obj.set_property(CURRENT_KEY, Value::Null);
+        obj
+    });
+}
+
+fn state_machine_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_state_machine_methods()))
+        .clone()
+}
+
+/// Gem's methods plus the state machine's own
+fn build_state_machine_methods() -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+
+    // transition_to(name)
+    define_method(
+        &mut methods,
+        "transition_to",
+        Arc::new(|this, args| {
+            let name = match args.first() {
+                Some(Value::String(s)) => s.clone(),
+                _ => return Err("transition_to expects a state name".into()),
+            };
+            transition(this, &name)?;
+            Ok(Value::Null)
+        }),
+    );
+    // get_current_state() -> name or Null
+    define_method(
+        &mut methods,
+        "get_current_state",
+        Arc::new(|this, _| Ok(this.get_property(CURRENT_KEY).unwrap_or(Value::Null))),
+    );
+    // update(dt)
+    define_method(
+        &mut methods,
+        "update",
+        Arc::new(|this, args| {
+            let dt = match args.first() {
+                Some(Value::Float(f)) => Value::Float(*f),
+                Some(Value::Int(i)) => Value::Float(*i as f64),
+                _ => return Err("update expects dt".into()),
+            };
+            if current(this).is_none() {
+                let initial = match this.get_property(INITIAL_KEY) {
+                    Some(Value::String(s)) => Some(s),
+                    _ => states(this).first().and_then(state_name),
+                };
+                match initial {
+                    Some(name) => transition(this, &name)?,
+                    None => return Ok(Value::Null),
+                }
+            }
+            if let Some(state) = current(this) {
+                notify(&state, "on_state_update", &[dt])?;
+            }
+            Ok(Value::Null)
+        }),
+    );
+
+    // _process(dt): the scene tree drives the machine once per frame
+    define_method(
+        &mut methods,
+        "_process",
+        Arc::new(|this, args| this.call_method("update", args)),
+    );
+
+    // signals: state_changed(from, to)
+    methods
+}
+
+fn states(machine: &ObjectRef) -> Vec<ObjectRef> {
+    match machine.call_method("get_children", &[]) {
+        Ok(Value::Array(children)) => children.iter().filter_map(Value::as_object).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn state_name(state: &ObjectRef) -> Option<String> {
+    match state.get_property("name") {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn state_named(machine: &ObjectRef, name: &str) -> Option<ObjectRef> {
+    states(machine)
+        .into_iter()
+        .find(|s| state_name(s).as_deref() == Some(name))
+}
+
+fn current(machine: &ObjectRef) -> Option<ObjectRef> {
+    match machine.get_property(CURRENT_KEY) {
+        Some(Value::String(name)) => state_named(machine, &name),
+        _ => None,
+    }
+}
+
+/// Run a state's handler if its script defines one
+fn notify(state: &ObjectRef, event: &str, args: &[Value]) -> Result<(), String> {
+    if state.has_method(event) {
+        state.call_method(event, args)?;
+    }
+    Ok(())
+}
+
+fn transition(machine: &ObjectRef, name: &str) -> Result<(), String> {
+    let next = state_named(machine, name).ok_or_else(|| {
+        let machine_name = state_name(machine).unwrap_or_default();
+        format!("{} has no state named {}", machine_name, name)
+    })?;
+    let from = machine.get_property(CURRENT_KEY).unwrap_or(Value::Null);
+    if let Some(prev) = current(machine) {
+        notify(&prev, "on_exit", &[])?;
+    }
+    let to = Value::String(name.to_string());
+    machine.set_property(CURRENT_KEY, to.clone());
+    notify(&next, "on_enter", std::slice::from_ref(&from))?;
+    machine.emit_signal("state_changed", &[from, to]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{ObjectInner, init_object_class, object_new};
+    use std::sync::Mutex;
+
+    #[test]
+    fn transitions_run_enter_exit_and_update() {
+        init_object_class();
+        gem::init_gem_class();
+        init_state_machine_class();
+
+        let log = Arc::new(Mutex::new(Vec::<String>::new()));
+        let machine = object_new("StateMachineGem");
+        for name in ["Idle", "Chase"] {
+            let state = object_new("Gem");
+            state.set_property("name", Value::String(name.into()));
+            for event in ["on_enter", "on_exit", "on_state_update"] {
+                let log = log.clone();
+                ObjectInner::insert_method(
+                    &state,
+                    event,
+                    Arc::new(move |_, args| {
+                        log.lock()
+                            .unwrap()
+                            .push(format!("{}.{}{:?}", name, event, args));
+                        Ok(Value::Null)
+                    }),
+                );
+            }
+            machine
+                .call_method("add_child", &[Value::Object(state)])
+                .unwrap();
+        }
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        machine.connect(
+            "state_changed",
+            Arc::new(move |args| seen.lock().unwrap().push(args.to_vec())),
+        );
+
+        // The first update enters the first child
+        machine.call_method("update", &[Value::Float(0.5)]).unwrap();
+        machine
+            .call_method("transition_to", &[Value::String("Chase".into())])
+            .unwrap();
+        assert_eq!(
+            machine.call_method("get_current_state", &[]).unwrap(),
+            Value::String("Chase".into())
+        );
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "Idle.on_enter[Null]",
+                "Idle.on_state_update[Float(0.5)]",
+                "Idle.on_exit[]",
+                "Chase.on_enter[String(\"Idle\")]",
+            ]
+        );
+        assert_eq!(changes.lock().unwrap().len(), 2);
+
+        let err = machine
+            .call_method("transition_to", &[Value::String("Flee".into())])
+            .unwrap_err();
+        assert!(err.contains("no state named Flee"));
+        assert_eq!(
+            machine.call_method("get_current_state", &[]).unwrap(),
+            Value::String("Chase".into())
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Scene statistics for `gem stats <scene>`.
+//! Counts what a scene declares (nodes by class, depth, properties, assets)
+//! and what it costs to draw, and flags the usual performance traps: very
+//! deep trees, huge texture files and textures drawn at sizes no GPU takes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::ast;
+use crate::batch::{self, Batch};
+use crate::draw_list::{self, DrawList};
+use crate::font;
+use crate::resource;
+use crate::text::LaidOut;
+use crate::vfs;
+
+/// Trees deeper than this get a warning
+pub const MAX_DEPTH: usize = 16;
+/// Asset files bigger than this get a warning
+pub const MAX_ASSET_BYTES: usize = 1024 * 1024;
+/// Textures are rasterized at display size; beyond this the upload fails on
+/// many GPUs
+pub const MAX_TEXTURE_PX: f32 = 4096.0;
+
+#[derive(Debug, Default)]
+pub struct SceneStats {
+    pub nodes: usize,
+    pub by_class: BTreeMap<String, usize>,
+    /// Levels below the root; a lone root has depth 0
+    pub depth: usize,
+    pub properties: usize,
+    /// Every directive in a property value, with its size when the file exists
+    pub assets: BTreeMap<String, Option<usize>>,
+    /// One call per quad, glyph and light, as the renderer issues them now;
+    /// an instanced run of quads (see `batch`) is one call
+    pub draw_calls: usize,
+    /// Calls left if consecutive draws sharing state were merged
+    pub batched_draw_calls: usize,
+    pub issues: Vec<String>,
+}
+
+pub fn collect(scene: &ast::GemFile) -> SceneStats {
+    let mut stats = SceneStats::default();
+    visit(&scene.root, 0, &mut stats);
+    if stats.depth > MAX_DEPTH {
+        stats.issues.push(format!(
+            "tree is {} levels deep (more than {}); deep trees make every transform and dispatch slower",
+            stats.depth, MAX_DEPTH
+        ));
+    }
+    for (path, size) in &stats.assets {
+        match size {
+            None => stats.issues.push(format!("{} is missing", path)),
+            Some(bytes) if *bytes > MAX_ASSET_BYTES => stats.issues.push(format!(
+                "{} is {} KiB (more than {} KiB)",
+                path,
+                bytes / 1024,
+                MAX_ASSET_BYTES / 1024
+            )),
+            _ => {}
+        }
+    }
+
+    let list = draw_list::build(scene);
+    for q in list.quads.iter().filter(|q| q.texture.is_some()) {
+        if q.w.max(q.h) > MAX_TEXTURE_PX {
+            stats.issues.push(format!(
+                "a texture is drawn at {}x{} px (more than {} px on a side)",
+                q.w, q.h, MAX_TEXTURE_PX
+            ));
+        }
+    }
+    (stats.draw_calls, stats.batched_draw_calls) = draw_calls(&list);
+    stats
+}
+
+fn visit(decl: &ast::GemDecl, depth: usize, stats: &mut SceneStats) {
+    stats.nodes += 1;
+    *stats.by_class.entry(decl.gem_type.clone()).or_default() += 1;
+    stats.depth = stats.depth.max(depth);
+    stats.properties += decl.properties.len();
+    for p in &decl.properties {
+        directives(&p.value, &mut stats.assets);
+    }
+    for child in &decl.children {
+        visit(child, depth + 1, stats);
+    }
+}
+
+fn directives(v: &ast::Value, out: &mut BTreeMap<String, Option<usize>>) {
+    match v {
+        ast::Value::Directive(directive) => {
+            let path = resource::resolve(&directive.segments)
+                .to_string_lossy()
+                .into_owned();
+            out.entry(path)
+                .or_insert_with_key(|path| vfs::read(path).ok().map(|data| data.len()));
+        }
+        ast::Value::Tuple(vals) | ast::Value::Array(vals) => {
+            vals.iter().for_each(|v| directives(v, out))
+        }
+        ast::Value::Struct(fields) => fields.iter().for_each(|f| directives(&f.value, out)),
+        ast::Value::Map(entries) => entries.iter().for_each(|(_, v)| directives(v, out)),
+        _ => {}
+    }
+}
+
+/// What a draw binds: flat quads and glyphs from one atlas can share a call
+#[derive(PartialEq)]
+enum DrawState {
+    Flat,
+    Gradient,
+    Texture(String),
+    /// Glyph atlas page: a face (or the bitmap font) at a pixel size
+    Glyphs(Option<usize>, u32),
+}
+
+/// (calls as issued, calls after merging runs with the same state and blend)
+fn draw_calls(list: &DrawList) -> (usize, usize) {
+    let mut states = Vec::new();
+    let (quads_from, texts_from) = list.overlay_start();
+    let layers = [
+        (&list.quads[..quads_from], &list.texts[..texts_from]),
+        (&list.quads[quads_from..], &list.texts[texts_from..]),
+    ];
+    for (quads, texts) in layers {
+        for b in batch::batches(quads) {
+            let q = match b {
+                Batch::Single(i) => &quads[i],
+                Batch::Instanced(range) => &quads[range.start],
+            };
+            let state = match (&q.texture, &q.gradient) {
+                (Some(texture), _) => DrawState::Texture(texture.path.display().to_string()),
+                (None, Some(_)) => DrawState::Gradient,
+                (None, None) => DrawState::Flat,
+            };
+            states.push((state, Some(q.blend)));
+        }
+        for t in texts {
+            for item in &t.layout.items {
+                let state = match item {
+                    LaidOut::Glyph {
+                        face, font_size, ..
+                    } => DrawState::Glyphs(*face, font::size_key(*font_size)),
+                    LaidOut::Underline { .. } => DrawState::Flat,
+                    LaidOut::Image { path, .. } => DrawState::Texture(path.clone()),
+                };
+                states.push((state, None));
+            }
+        }
+    }
+    let mut batched = 0;
+    for (i, state) in states.iter().enumerate() {
+        // Gradients carry their stops as uniforms, so each needs its own call
+        let merges = i > 0 && states[i - 1] == *state && state.0 != DrawState::Gradient;
+        if !merges {
+            batched += 1;
+        }
+    }
+    let lights = list.lights.len();
+    (states.len() + lights, batched + lights)
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Nodes: {} (depth {})", self.nodes, self.depth)?;
+        let mut classes: Vec<_> = self.by_class.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (class, count) in classes {
+            writeln!(f, "  {:<20} {}", class, count)?;
+        }
+        writeln!(f, "Properties: {}", self.properties)?;
+        writeln!(f, "Assets: {}", self.assets.len())?;
+        for (path, size) in &self.assets {
+            match size {
+                Some(bytes) => writeln!(f, "  {} ({} bytes)", path, bytes)?,
+                None => writeln!(f, "  {} (missing)", path)?,
+            }
+        }
+        writeln!(
+            f,
+            "Draw calls: {} ({} after batching)",
+            self.draw_calls, self.batched_draw_calls
+        )?;
+        let unique: BTreeSet<&String> = self.issues.iter().collect();
+        if unique.is_empty() {
+            writeln!(f, "Issues: none")
+        } else {
+            writeln!(f, "Issues: {}", unique.len())?;
+            for issue in unique {
+                writeln!(f, "  - {}", issue)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    fn stats_for(src: &str) -> SceneStats {
+        collect(
+            &pipeline::lex_source(src)
+                .and_then(pipeline::parse_scene)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn counts_nodes_assets_and_batches() {
+        let stats = stats_for(
+            r#"Root: Gem {
+                A: PanelGem { color: #ff0000 }
+                B: PanelGem { color: #00ff00 }
+                Icon: PanelGem { texture: #assets:missing.svg size: (5000, 10) }
+                Box: ContainerGem { Title: LabelGem { text: "ab" } }
+            }"#,
+        );
+        assert_eq!(stats.nodes, 6);
+        assert_eq!(stats.by_class["PanelGem"], 3);
+        assert_eq!(stats.depth, 2);
+        assert_eq!(stats.properties, 5);
+        assert_eq!(stats.assets.get("assets/missing.svg"), Some(&None));
+        // A, B, Icon, Box and two glyphs; the panels and the glyphs merge
+        assert_eq!(stats.draw_calls, 6);
+        assert_eq!(stats.batched_draw_calls, 4);
+        assert!(
+            stats
+                .issues
+                .iter()
+                .any(|i| i.contains("missing.svg is missing"))
+        );
+        assert!(stats.issues.iter().any(|i| i.contains("5000x10")));
+    }
+
+    #[test]
+    fn deep_trees_are_flagged() {
+        let mut src = String::new();
+        for i in 0..=MAX_DEPTH + 1 {
+            src.push_str(&format!("N{}: Gem {{ ", i));
+        }
+        src.push_str(&"}".repeat(MAX_DEPTH + 2));
+        let stats = stats_for(&src);
+        assert_eq!(stats.depth, MAX_DEPTH + 1);
+        assert!(stats.issues[0].contains("levels deep"));
+    }
+}
@@ -0,0 +1,702 @@
+//! SVG rasterizer for UI icons.
+//! Covers the subset icon sets use in practice: `rect`, `circle`, `ellipse`,
+//! `line`, `polyline`, `polygon` and `path` (including arcs) inside nested
+//! `g` groups, with fill/stroke presentation attributes, `style`, opacity and
+//! transforms. Text, gradients, masks and filters are ignored.
+
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use crate::text;
+use crate::vfs;
+
+/// A parsed document, ready to rasterize at any size
+pub struct SvgImage {
+    /// Coordinate box of the drawing (x, y, w, h)
+    pub view_box: (f32, f32, f32, f32),
+    shapes: Vec<Shape>,
+}
+
+struct Shape {
+    path: tiny_skia::Path,
+    fill: Option<[u8; 4]>,
+    stroke: Option<([u8; 4], f32)>,
+    fill_rule: FillRule,
+    transform: Transform,
+}
+
+/// Presentation attributes inherited from enclosing groups
+#[derive(Clone)]
+struct Style {
+    fill: Option<[u8; 4]>,
+    stroke: Option<[u8; 4]>,
+    stroke_width: f32,
+    opacity: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    fill_rule: FillRule,
+    transform: Transform,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some([0, 0, 0, 255]),
+            stroke: None,
+            stroke_width: 1.0,
+            opacity: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            fill_rule: FillRule::Winding,
+            transform: Transform::identity(),
+        }
+    }
+}
+
+impl Style {
+    fn apply(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        match key {
+            "fill" => self.fill = parse_paint(value).unwrap_or(self.fill),
+            "stroke" => self.stroke = parse_paint(value).unwrap_or(self.stroke),
+            "stroke-width" => self.stroke_width = parse_length(value).unwrap_or(self.stroke_width),
+            // Group opacity is approximated by multiplying it into the children
+            "opacity" => self.opacity *= parse_length(value).unwrap_or(1.0),
+            "fill-opacity" => self.fill_opacity = parse_length(value).unwrap_or(1.0),
+            "stroke-opacity" => self.stroke_opacity = parse_length(value).unwrap_or(1.0),
+            "fill-rule" => {
+                self.fill_rule = match value {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::Winding,
+                }
+            }
+            "transform" => self.transform = self.transform.pre_concat(parse_transform(value)),
+            "style" => {
+                for decl in value.split(';') {
+                    if let Some((k, v)) = decl.split_once(':') {
+                        self.apply(k.trim(), v);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn with_alpha(color: Option<[u8; 4]>, alpha: f32) -> Option<[u8; 4]> {
+        let mut c = color?;
+        c[3] = (c[3] as f32 * alpha.clamp(0.0, 1.0)).round() as u8;
+        Some(c)
+    }
+}
+
+/// `Ok(None)` for `none`, `Err` for values we don't understand
+fn parse_paint(value: &str) -> Result<Option<[u8; 4]>, ()> {
+    match value {
+        "none" | "transparent" => Ok(None),
+        // No CSS `color` cascade: currentColor renders black like the initial value
+        "currentColor" => Ok(Some([0, 0, 0, 255])),
+        v => parse_color(v).map(Some).ok_or(()),
+    }
+}
+
+fn parse_color(value: &str) -> Option<[u8; 4]> {
+    if let Some(hex) = value.strip_prefix('#')
+        && hex.len() == 3
+    {
+        let ch = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+        return Some([ch(0)?, ch(1)?, ch(2)?, 255]);
+    }
+    if let Some(args) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let c: Vec<f32> = args
+            .split(',')
+            .map(|s| {
+                let s = s.trim();
+                match s.strip_suffix('%') {
+                    Some(p) => p.parse::<f32>().ok().map(|p| p * 2.55),
+                    None => s.parse::<f32>().ok(),
+                }
+            })
+            .collect::<Option<_>>()?;
+        if c.len() != 3 {
+            return None;
+        }
+        return Some([c[0] as u8, c[1] as u8, c[2] as u8, 255]);
+    }
+    text::parse_color_name(value).map(|c| c.map(|ch| (ch * 255.0).round() as u8))
+}
+
+/// Numbers with an optional `px` unit; other units are taken as-is
+fn parse_length(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches("px").parse().ok()
+}
+
+fn parse_transform(value: &str) -> Transform {
+    let mut t = Transform::identity();
+    for item in value.split_terminator(')') {
+        let Some((name, args)) = item.split_once('(') else {
+            continue;
+        };
+        let a = numbers(args);
+        let arg = |i: usize| a.get(i).copied().unwrap_or(0.0);
+        let step = match (name.trim().trim_start_matches(','), a.len()) {
+            ("matrix", 6) => Transform::from_row(a[0], a[1], a[2], a[3], a[4], a[5]),
+            ("translate", _) => Transform::from_translate(arg(0), arg(1)),
+            ("scale", 1) => Transform::from_scale(a[0], a[0]),
+            ("scale", _) => Transform::from_scale(arg(0), arg(1)),
+            ("rotate", 3) => Transform::from_translate(a[1], a[2])
+                .pre_concat(Transform::from_rotate(a[0]))
+                .pre_translate(-a[1], -a[2]),
+            ("rotate", _) => Transform::from_rotate(arg(0)),
+            ("skewX", _) => Transform::from_skew(arg(0).to_radians().tan(), 0.0),
+            ("skewY", _) => Transform::from_skew(0.0, arg(0).to_radians().tan()),
+            _ => Transform::identity(),
+        };
+        t = t.pre_concat(step);
+    }
+    t
+}
+
+/// Split a list of numbers separated by whitespace and/or commas
+fn numbers(s: &str) -> Vec<f32> {
+    let mut tokens = PathTokens::new(s);
+    let mut out = Vec::new();
+    while let Some(n) = tokens.number() {
+        out.push(n);
+    }
+    out
+}
+
+/// Tokenizer for path data: handles `1.5.5`, `10-20` and exponents
+struct PathTokens<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PathTokens<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src: src.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self.pos < self.src.len()
+            && (self.src[self.pos].is_ascii_whitespace() || self.src[self.pos] == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let c = *self.src.get(self.pos)?;
+        if c.is_ascii_alphabetic() && c != b'e' && c != b'E' {
+            self.pos += 1;
+            return Some(c);
+        }
+        None
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.pos;
+        let mut seen_dot = false;
+        let mut seen_exp = false;
+        while let Some(&c) = self.src.get(self.pos) {
+            let at_start = self.pos == start;
+            let after_exp = self.pos > start && matches!(self.src[self.pos - 1], b'e' | b'E');
+            match c {
+                b'0'..=b'9' => {}
+                b'+' | b'-' if at_start || after_exp => {}
+                b'.' if !seen_dot && !seen_exp => seen_dot = true,
+                b'e' | b'E' if !seen_exp && !at_start => seen_exp = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let n = std::str::from_utf8(&self.src[start..self.pos])
+            .ok()?
+            .parse()
+            .ok();
+        if n.is_none() {
+            self.pos = start;
+        }
+        n
+    }
+
+    /// Arc flags may be written without separators (`a1 1 0 011 1`)
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let c = *self.src.get(self.pos)?;
+        self.pos += 1;
+        match c {
+            b'0' => Some(false),
+            b'1' => Some(true),
+            _ => None,
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.src.len()
+    }
+}
+
+fn parse_path_data(d: &str) -> Option<tiny_skia::Path> {
+    let mut pb = PathBuilder::new();
+    let mut t = PathTokens::new(d);
+    let (mut cur, mut start) = ((0.0f32, 0.0f32), (0.0f32, 0.0f32));
+    // Reflected control point for S/T
+    let mut last_ctrl: Option<(u8, (f32, f32))> = None;
+    let mut cmd = t.command()?;
+
+    loop {
+        let rel = cmd.is_ascii_lowercase();
+        let (ox, oy) = if rel { cur } else { (0.0, 0.0) };
+        let mut ctrl = None;
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                let p = (t.number()? + ox, t.number()? + oy);
+                pb.move_to(p.0, p.1);
+                cur = p;
+                start = p;
+                // Extra pairs after a moveto are implicit linetos
+                cmd = if rel { b'l' } else { b'L' };
+                if t.at_end() {
+                    break;
+                }
+                if let Some(next) = t.command() {
+                    cmd = next;
+                }
+                last_ctrl = None;
+                continue;
+            }
+            b'L' => {
+                cur = (t.number()? + ox, t.number()? + oy);
+                pb.line_to(cur.0, cur.1);
+            }
+            b'H' => {
+                cur.0 = t.number()? + ox;
+                pb.line_to(cur.0, cur.1);
+            }
+            b'V' => {
+                cur.1 = t.number()? + oy;
+                pb.line_to(cur.0, cur.1);
+            }
+            b'C' | b'S' => {
+                let c1 = if cmd.eq_ignore_ascii_case(&b'C') {
+                    (t.number()? + ox, t.number()? + oy)
+                } else {
+                    reflect(last_ctrl, b'C', cur)
+                };
+                let c2 = (t.number()? + ox, t.number()? + oy);
+                let p = (t.number()? + ox, t.number()? + oy);
+                pb.cubic_to(c1.0, c1.1, c2.0, c2.1, p.0, p.1);
+                ctrl = Some((b'C', c2));
+                cur = p;
+            }
+            b'Q' | b'T' => {
+                let c = if cmd.eq_ignore_ascii_case(&b'Q') {
+                    (t.number()? + ox, t.number()? + oy)
+                } else {
+                    reflect(last_ctrl, b'Q', cur)
+                };
+                let p = (t.number()? + ox, t.number()? + oy);
+                pb.quad_to(c.0, c.1, p.0, p.1);
+                ctrl = Some((b'Q', c));
+                cur = p;
+            }
+            b'A' => {
+                let (rx, ry, rot) = (t.number()?, t.number()?, t.number()?);
+                let (large, sweep) = (t.flag()?, t.flag()?);
+                let p = (t.number()? + ox, t.number()? + oy);
+                arc_to(&mut pb, cur, (rx, ry), rot, large, sweep, p);
+                cur = p;
+            }
+            b'Z' => {
+                pb.close();
+                cur = start;
+            }
+            _ => return None,
+        }
+        last_ctrl = ctrl;
+
+        if t.at_end() {
+            break;
+        }
+        // Without a new command letter the previous one repeats
+        if let Some(next) = t.command() {
+            cmd = next;
+        } else if cmd.eq_ignore_ascii_case(&b'Z') {
+            return None;
+        }
+    }
+    pb.finish()
+}
+
+fn reflect(last: Option<(u8, (f32, f32))>, kind: u8, cur: (f32, f32)) -> (f32, f32) {
+    match last {
+        Some((k, c)) if k == kind => (2.0 * cur.0 - c.0, 2.0 * cur.1 - c.1),
+        _ => cur,
+    }
+}
+
+/// Endpoint-parameterized elliptical arc, converted to cubic segments of at
+/// most 90 degrees (SVG 1.1 implementation notes, F.6.5)
+fn arc_to(
+    pb: &mut PathBuilder,
+    from: (f32, f32),
+    radii: (f32, f32),
+    x_rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: (f32, f32),
+) {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if rx == 0.0 || ry == 0.0 || from == to {
+        pb.line_to(to.0, to.1);
+        return;
+    }
+    let (sin_phi, cos_phi) = x_rotation.to_radians().sin_cos();
+    let dx = (from.0 - to.0) / 2.0;
+    let dy = (from.1 - to.1) / 2.0;
+    let x1 = cos_phi * dx + sin_phi * dy;
+    let y1 = -sin_phi * dx + cos_phi * dy;
+
+    // Scale radii up if the endpoints can't be reached
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = -coef * ry * x1 / rx;
+    let cx = cos_phi * cx1 - sin_phi * cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin_phi * cx1 + cos_phi * cy1 + (from.1 + to.1) / 2.0;
+
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| {
+        let a = (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+        if a.is_nan() { 0.0 } else { a }
+    };
+    let theta1 = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle(
+        (x1 - cx1) / rx,
+        (y1 - cy1) / ry,
+        (-x1 - cx1) / rx,
+        (-y1 - cy1) / ry,
+    );
+    if !sweep && delta > 0.0 {
+        delta -= std::f32::consts::TAU;
+    } else if sweep && delta < 0.0 {
+        delta += std::f32::consts::TAU;
+    }
+
+    let segments = (delta.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = delta / segments as f32;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |t: f32| {
+        let (s, c) = t.sin_cos();
+        (
+            cx + rx * c * cos_phi - ry * s * sin_phi,
+            cy + rx * c * sin_phi + ry * s * cos_phi,
+        )
+    };
+    let deriv = |t: f32| {
+        let (s, c) = t.sin_cos();
+        (
+            -rx * s * cos_phi - ry * c * sin_phi,
+            -rx * s * sin_phi + ry * c * cos_phi,
+        )
+    };
+    for i in 0..segments {
+        let t0 = theta1 + step * i as f32;
+        let t1 = t0 + step;
+        let (p0, d0) = (point(t0), deriv(t0));
+        let (p1, d1) = (point(t1), deriv(t1));
+        pb.cubic_to(
+            p0.0 + k * d0.0,
+            p0.1 + k * d0.1,
+            p1.0 - k * d1.0,
+            p1.1 - k * d1.1,
+            p1.0,
+            p1.1,
+        );
+    }
+}
+
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, rx: f32, ry: f32) -> Option<tiny_skia::Path> {
+    let (rx, ry) = (rx.min(w / 2.0), ry.min(h / 2.0));
+    if rx <= 0.0 || ry <= 0.0 {
+        return Some(PathBuilder::from_rect(Rect::from_xywh(x, y, w, h)?));
+    }
+    // Control point distance for a quarter ellipse
+    const K: f32 = 0.552_284_8;
+    let (kx, ky) = (rx * K, ry * K);
+    let (r, b) = (x + w, y + h);
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + rx, y);
+    pb.line_to(r - rx, y);
+    pb.cubic_to(r - rx + kx, y, r, y + ry - ky, r, y + ry);
+    pb.line_to(r, b - ry);
+    pb.cubic_to(r, b - ry + ky, r - rx + kx, b, r - rx, b);
+    pb.line_to(x + rx, b);
+    pb.cubic_to(x + rx - kx, b, x, b - ry + ky, x, b - ry);
+    pb.line_to(x, y + ry);
+    pb.cubic_to(x, y + ry - ky, x + rx - kx, y, x + rx, y);
+    pb.close();
+    pb.finish()
+}
+
+fn points_path(points: &str, close: bool) -> Option<tiny_skia::Path> {
+    let n = numbers(points);
+    let mut pairs = n.chunks_exact(2);
+    let first = pairs.next()?;
+    let mut pb = PathBuilder::new();
+    pb.move_to(first[0], first[1]);
+    for p in pairs {
+        pb.line_to(p[0], p[1]);
+    }
+    if close {
+        pb.close();
+    }
+    pb.finish()
+}
+
+fn attrs(e: &BytesStart) -> Vec<(String, String)> {
+    e.attributes()
+        .flatten()
+        .map(|a| {
+            let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
+            let value = a
+                .unescape_value()
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            (key, value)
+        })
+        .collect()
+}
+
+fn shape_path(tag: &str, attrs: &[(String, String)]) -> Option<tiny_skia::Path> {
+    let get = |k: &str| {
+        attrs
+            .iter()
+            .find(|(key, _)| key == k)
+            .map(|(_, v)| v.as_str())
+    };
+    let num = |k: &str| get(k).and_then(parse_length).unwrap_or(0.0);
+    match tag {
+        "rect" => {
+            let (rx, ry) = (
+                get("rx").and_then(parse_length),
+                get("ry").and_then(parse_length),
+            );
+            let (rx, ry) = (rx.or(ry).unwrap_or(0.0), ry.or(rx).unwrap_or(0.0));
+            rounded_rect(num("x"), num("y"), num("width"), num("height"), rx, ry)
+        }
+        "circle" => PathBuilder::from_circle(num("cx"), num("cy"), num("r")),
+        "ellipse" => {
+            let (rx, ry) = (num("rx"), num("ry"));
+            PathBuilder::from_oval(Rect::from_xywh(
+                num("cx") - rx,
+                num("cy") - ry,
+                rx * 2.0,
+                ry * 2.0,
+            )?)
+        }
+        "line" => {
+            let mut pb = PathBuilder::new();
+            pb.move_to(num("x1"), num("y1"));
+            pb.line_to(num("x2"), num("y2"));
+            pb.finish()
+        }
+        "polyline" => points_path(get("points")?, false),
+        "polygon" => points_path(get("points")?, true),
+        "path" => parse_path_data(get("d")?),
+        _ => None,
+    }
+}
+
+/// Parse an SVG document
+pub fn parse(src: &str) -> Result<SvgImage, String> {
+    let mut reader = quick_xml::Reader::from_str(src);
+    let mut styles = vec![Style::default()];
+    let mut view_box = None;
+    let mut size = (None, None);
+    let mut shapes = Vec::new();
+    // Depth inside elements whose content is never rendered (defs, clipPath, ...)
+    let mut hidden = 0usize;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("at byte {}: {}", reader.buffer_position(), e))?;
+        let (e, is_empty) = match &event {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(_) => {
+                if hidden > 0 {
+                    hidden -= 1;
+                } else if styles.len() > 1 {
+                    styles.pop();
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let tag = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+        let attrs = attrs(e);
+
+        if hidden > 0
+            || matches!(
+                tag.as_str(),
+                "defs" | "clipPath" | "mask" | "symbol" | "title"
+            )
+        {
+            if !is_empty {
+                hidden += 1;
+            }
+            continue;
+        }
+
+        let mut style = styles.last().cloned().unwrap_or_default();
+        for (k, v) in &attrs {
+            style.apply(k, v);
+        }
+
+        if tag == "svg" && view_box.is_none() {
+            for (k, v) in &attrs {
+                match k.as_str() {
+                    "viewBox" => {
+                        if let [x, y, w, h] = numbers(v)[..] {
+                            view_box = Some((x, y, w, h));
+                        }
+                    }
+                    "width" => size.0 = parse_length(v),
+                    "height" => size.1 = parse_length(v),
+                    _ => {}
+                }
+            }
+        } else if let Some(path) = shape_path(&tag, &attrs) {
+            let fill = Style::with_alpha(style.fill, style.opacity * style.fill_opacity);
+            let stroke = Style::with_alpha(style.stroke, style.opacity * style.stroke_opacity)
+                .map(|c| (c, style.stroke_width));
+            shapes.push(Shape {
+                path,
+                fill,
+                stroke,
+                fill_rule: style.fill_rule,
+                transform: style.transform,
+            });
+        }
+
+        if !is_empty {
+            styles.push(style);
+        }
+    }
+
+    let view_box = match (view_box, size) {
+        (Some(vb), _) => vb,
+        (None, (Some(w), Some(h))) => (0.0, 0.0, w, h),
+        _ => return Err("svg needs a viewBox or width/height".to_string()),
+    };
+    if view_box.2 <= 0.0 || view_box.3 <= 0.0 {
+        return Err("svg has an empty viewBox".to_string());
+    }
+    Ok(SvgImage { view_box, shapes })
+}
+
+pub fn load(path: &Path) -> Result<SvgImage, String> {
+    let src = vfs::read_to_string(&path.to_string_lossy())?;
+    parse(&src).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl SvgImage {
+    /// Render into straight-alpha RGBA8 at `width` x `height` pixels, fitting
+    /// the view box centered with its aspect ratio preserved
+    pub fn rasterize(&self, width: u32, height: u32) -> Option<Vec<u8>> {
+        let mut pixmap = Pixmap::new(width, height)?;
+        let (vx, vy, vw, vh) = self.view_box;
+        let scale = (width as f32 / vw).min(height as f32 / vh);
+        let fit = Transform::from_translate(
+            (width as f32 - vw * scale) / 2.0,
+            (height as f32 - vh * scale) / 2.0,
+        )
+        .pre_scale(scale, scale)
+        .pre_translate(-vx, -vy);
+
+        for shape in &self.shapes {
+            let transform = fit.pre_concat(shape.transform);
+            let mut paint = Paint {
+                anti_alias: true,
+                ..Default::default()
+            };
+            if let Some([r, g, b, a]) = shape.fill {
+                paint.set_color_rgba8(r, g, b, a);
+                pixmap.fill_path(&shape.path, &paint, shape.fill_rule, transform, None);
+            }
+            if let Some(([r, g, b, a], width)) = shape.stroke {
+                paint.set_color_rgba8(r, g, b, a);
+                let stroke = Stroke {
+                    width,
+                    ..Default::default()
+                };
+                pixmap.stroke_path(&shape.path, &paint, &stroke, transform, None);
+            }
+        }
+
+        let mut out = Vec::with_capacity((width * height * 4) as usize);
+        for px in pixmap.pixels() {
+            let c = px.demultiply();
+            out.extend_from_slice(&[c.red(), c.green(), c.blue(), c.alpha()]);
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgba: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * width + x) * 4) as usize;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    }
+
+    #[test]
+    fn rasterizes_shapes_at_any_size() {
+        let svg = parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+                <rect width="5" height="10" fill="#f00"/>
+                <g transform="translate(5 0)" style="fill: rgb(0, 0, 255)">
+                    <path d="M0 0h5v10H0z"/>
+                </g>
+                <defs><rect width="10" height="10" fill="green"/></defs>
+            </svg>"##,
+        )
+        .unwrap();
+        for size in [10, 40] {
+            let img = svg.rasterize(size, size).unwrap();
+            assert_eq!(pixel(&img, size, size / 4, size / 2), [255, 0, 0, 255]);
+            assert_eq!(pixel(&img, size, size * 3 / 4, size / 2), [0, 0, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn path_data_tokens() {
+        assert_eq!(numbers("1.5.5-2e1,3"), vec![1.5, 0.5, -20.0, 3.0]);
+        assert!(parse_path_data("M0 0 L10 0 A5 5 0 0110 10 Q5 15 0 10 T0 0z").is_some());
+        assert!(parse_path_data("M0 0 X").is_none());
+        assert!(parse("<svg/>").is_err());
+    }
+}
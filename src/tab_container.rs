@@ -0,0 +1,301 @@
+//! TabContainerGem and AccordionGem: Gems whose children are pages, each
+//! titled by its `tab_title` property or else its name. A TabContainerGem
+//! draws a bar of equal-width tabs across its top and shows only the page
+//! at `current_tab` below it; clicking a tab selects it. An AccordionGem
+//! stacks a header per page and opens the current page under its header;
+//! clicking the open header collapses it, leaving `current_tab` at -1.
+//! Selecting another page emits `tab_changed(index)`. The layout functions
+//! here are shared by the runtime's hit tests and the preview's draw list.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{self, MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+pub const CURRENT_TAB_KEY: &str = "current_tab";
+pub const TITLE_KEY: &str = "tab_title";
+/// Height of the tab bar and of each accordion header, in px
+pub const BAR_HEIGHT: f32 = 28.0;
+
+pub fn init_tab_classes() {
+    register_class("TabContainerGem", || {
+        new_tabs("TabContainerGem", tab_container_methods())
+    });
+    register_class("AccordionGem", || {
+        new_tabs("AccordionGem", accordion_methods())
+    });
+}
+
+fn new_tabs(class_name: &str, methods: Arc<MethodTable>) -> ObjectRef {
+    let obj = gem::new_gem(class_name, methods);
+    obj.set_property(CURRENT_TAB_KEY, Value::Int(0));
+    obj
+}
+
+/// The x and width of each of `count` tabs across a `width`-wide bar
+pub fn tab_spans(width: f32, count: usize) -> Vec<(f32, f32)> {
+    let w = width / count.max(1) as f32;
+    (0..count).map(|i| (i as f32 * w, w)).collect()
+}
+
+/// An accordion `height` px tall: the top of each of `count` headers, and
+/// the top and height of the open page's content
+pub fn accordion_layout(
+    height: f32,
+    count: usize,
+    current: Option<usize>,
+) -> (Vec<f32>, Option<(f32, f32)>) {
+    let open = current.filter(|&c| c < count);
+    let content = (height - count as f32 * BAR_HEIGHT).max(0.0);
+    let tops = (0..count)
+        .map(|i| {
+            let below_open = open.is_some_and(|c| i > c);
+            i as f32 * BAR_HEIGHT + if below_open { content } else { 0.0 }
+        })
+        .collect();
+    let page = open.map(|c| ((c + 1) as f32 * BAR_HEIGHT, content));
+    (tops, page)
+}
+
+fn tab_container_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_tab_methods(false)))
+        .clone()
+}
+
+fn accordion_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_tab_methods(true)))
+        .clone()
+}
+
+/// Gem's methods plus the tab methods; an accordion may have no page open
+fn build_tab_methods(accordion: bool) -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+    define_method(
+        &mut methods,
+        "get_tab_count",
+        Arc::new(|this, _| Ok(Value::Int(gem::children_of(this).len() as i64))),
+    );
+    // get_tab_title(index)
+    define_method(
+        &mut methods,
+        "get_tab_title",
+        Arc::new(|this, args| {
+            let page = match args.first() {
+                Some(Value::Int(i)) => usize::try_from(*i)
+                    .ok()
+                    .and_then(|i| gem::children_of(this).into_iter().nth(i)),
+                _ => return Err("get_tab_title expects an Int index".into()),
+            };
+            let page = page.ok_or("get_tab_title: no tab at that index")?;
+            Ok(Value::String(title(&page)))
+        }),
+    );
+    define_method(
+        &mut methods,
+        "get_current_tab",
+        Arc::new(|this, _| Ok(Value::Int(current(this)))),
+    );
+    // set_current_tab(index): -1 collapses an accordion
+    define_method(
+        &mut methods,
+        "set_current_tab",
+        Arc::new(move |this, args| {
+            let index = match args.first() {
+                Some(Value::Int(i)) => *i,
+                _ => return Err("set_current_tab expects an Int index".into()),
+            };
+            let count = gem::children_of(this).len() as i64;
+            let lowest = if accordion { -1 } else { 0 };
+            if index < lowest || index >= count {
+                return Err(format!(
+                    "set_current_tab: {} is out of range for {} tabs",
+                    index, count
+                ));
+            }
+            select(this, index);
+            Ok(Value::Null)
+        }),
+    );
+    // _notification(code): show only the current page once the pages are in
+    define_method(
+        &mut methods,
+        "_notification",
+        Arc::new(|this, args| {
+            if let Some(Value::Int(object::NOTIFICATION_READY)) = args.first() {
+                show_current(this);
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // _input(event): a click on a tab or header selects its page
+    define_method(
+        &mut methods,
+        "_input",
+        Arc::new(move |this, args| {
+            let Some(event) = args.first().and_then(Value::as_object) else {
+                return Err("_input expects an InputEvent".into());
+            };
+            if event.class_name() != "InputEventMouseButton"
+                || !matches!(event.get_property("pressed"), Some(Value::Bool(true)))
+            {
+                return Ok(Value::Null);
+            }
+            let Some((x, y)) = event
+                .get_property("position")
+                .and_then(|p| p.as_vec2())
+                .and_then(|p| gem::to_local(this, p))
+            else {
+                return Ok(Value::Null);
+            };
+            if let Some(index) = hit(this, (x as f32, y as f32), accordion) {
+                let index = if accordion && index == current(this) {
+                    -1
+                } else {
+                    index
+                };
+                select(this, index);
+                event.call_method("accept_event", &[])?;
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // signals: tab_changed(index)
+    methods
+}
+
+fn current(tabs: &ObjectRef) -> i64 {
+    match tabs.get_property(CURRENT_TAB_KEY) {
+        Some(Value::Int(i)) => i,
+        _ => 0,
+    }
+}
+
+fn title(page: &ObjectRef) -> String {
+    match (page.get_property(TITLE_KEY), page.get_property("name")) {
+        (Some(Value::String(t)), _) | (_, Some(Value::String(t))) => t,
+        _ => page.class_name().to_string(),
+    }
+}
+
+fn select(tabs: &ObjectRef, index: i64) {
+    let changed = current(tabs) != index;
+    tabs.set_property(CURRENT_TAB_KEY, Value::Int(index));
+    show_current(tabs);
+    if changed {
+        tabs.emit_signal("tab_changed", &[Value::Int(index)]);
+    }
+}
+
+fn show_current(tabs: &ObjectRef) {
+    let index = current(tabs);
+    for (i, page) in gem::children_of(tabs).iter().enumerate() {
+        gem::set_visible(page, i as i64 == index);
+    }
+}
+
+/// The tab or header under a point in the container's local space
+fn hit(tabs: &ObjectRef, (x, y): (f32, f32), accordion: bool) -> Option<i64> {
+    let (w, h) = tabs
+        .get_property("size")
+        .and_then(|s| s.as_vec2())
+        .unwrap_or((0.0, 0.0));
+    let (w, h) = (w as f32, h as f32);
+    let count = gem::children_of(tabs).len();
+    if x < 0.0 || x >= w {
+        return None;
+    }
+    let index = if accordion {
+        let open = usize::try_from(current(tabs)).ok();
+        let (tops, _) = accordion_layout(h, count, open);
+        tops.iter()
+            .position(|&top| y >= top && y < top + BAR_HEIGHT)
+    } else if (0.0..BAR_HEIGHT).contains(&y) {
+        tab_spans(w, count)
+            .iter()
+            .position(|&(left, width)| x >= left && x < left + width)
+    } else {
+        None
+    };
+    index.map(|i| i as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use crate::object::object_new;
+    use crate::scene_tree;
+    use std::sync::Mutex;
+
+    fn tabs_with_pages(class_name: &str) -> (ObjectRef, Vec<ObjectRef>) {
+        scene_tree::init_builtin_classes();
+        let tabs = object_new(class_name);
+        tabs.set_property("size", Value::vec2(300.0, 200.0));
+        let pages: Vec<ObjectRef> = ["Video", "Audio", "Keys"]
+            .iter()
+            .map(|name| {
+                let page = object_new("Gem");
+                page.set_property("name", Value::String(name.to_string()));
+                tabs.call_method("add_child", &[Value::Object(page.clone())])
+                    .unwrap();
+                page
+            })
+            .collect();
+        pages[2].set_property(TITLE_KEY, Value::String("Controls".into()));
+        gem::propagate_ready(&tabs);
+        (tabs, pages)
+    }
+
+    #[test]
+    fn tabs_switch_pages_on_click() {
+        let (tabs, pages) = tabs_with_pages("TabContainerGem");
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        tabs.connect(
+            "tab_changed",
+            Arc::new(move |args| seen.lock().unwrap().push(args[0].clone())),
+        );
+        assert_eq!(
+            tabs.call_method("get_tab_title", &[Value::Int(2)]),
+            Ok(Value::String("Controls".into()))
+        );
+        let visible = || pages.iter().map(gem::is_visible).collect::<Vec<_>>();
+        assert_eq!(visible(), [true, false, false]);
+
+        // Tabs are 100px wide; the third starts at x = 200
+        let click = |x: f64, y: f64| input::mouse_button_event("left", true, (x, y));
+        assert!(input::dispatch(&tabs, &click(250.0, 10.0)));
+        assert_eq!(tabs.call_method("get_current_tab", &[]), Ok(Value::Int(2)));
+        assert_eq!(visible(), [false, false, true]);
+        // Below the bar is page content, not a tab
+        assert!(!input::dispatch(&tabs, &click(10.0, 100.0)));
+        assert!(
+            tabs.call_method("set_current_tab", &[Value::Int(3)])
+                .is_err()
+        );
+        assert_eq!(*changes.lock().unwrap(), [Value::Int(2)]);
+    }
+
+    #[test]
+    fn accordion_headers_open_and_collapse() {
+        let (accordion, pages) = tabs_with_pages("AccordionGem");
+        // 200px tall with three 28px headers leaves 116px for the open page
+        assert_eq!(
+            accordion_layout(200.0, 3, Some(0)),
+            (vec![0.0, 144.0, 172.0], Some((28.0, 116.0)))
+        );
+        let click = |y: f64| input::mouse_button_event("left", true, (10.0, y));
+        assert!(input::dispatch(&accordion, &click(150.0)));
+        assert_eq!(current(&accordion), 1);
+        assert!(gem::is_visible(&pages[1]));
+        // Header 1 now sits right under header 0
+        assert!(input::dispatch(&accordion, &click(30.0)));
+        assert_eq!(current(&accordion), -1);
+        assert!(pages.iter().all(|p| !gem::is_visible(p)));
+    }
+}
@@ -0,0 +1,668 @@
+//! Text layout engine
+//! Turns plain or marked-up text into positioned glyphs, underlines and inline
+//! images relative to the top-left corner of a text box.
+
+use crate::font::FaceId;
+
+/// Measures glyphs; implemented by every font source the engine can draw with.
+pub trait GlyphMetrics {
+    /// Horizontal advance of `ch` at `font_size` pixels
+    fn advance(&self, ch: char, font_size: f32) -> f32;
+    /// Distance between the tops of two consecutive lines at `font_size`
+    fn line_height(&self, font_size: f32) -> f32;
+    /// Loaded face that draws `ch`, or `None` for the built-in bitmap font
+    fn face(&self, _ch: char) -> Option<FaceId> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub underline: bool,
+    pub color: [f32; 4],
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            underline: false,
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// A run of uniformly styled text or an inline image
+#[derive(Debug, Clone, PartialEq)]
+pub enum Span {
+    Text {
+        text: String,
+        style: TextStyle,
+    },
+    Image {
+        path: String,
+        size: Option<(f32, f32)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretch inter-word spacing so wrapped lines fill the box width
+    Justify,
+}
+
+impl Align {
+    /// Parse an `align` property value
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Align::Left),
+            "center" => Some(Align::Center),
+            "right" => Some(Align::Right),
+            "justify" => Some(Align::Justify),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutParams {
+    pub font_size: f32,
+    /// Wrap width in pixels; `None` lays everything out on explicit lines only
+    pub max_width: Option<f32>,
+    pub align: Align,
+    /// Truncate to this many lines, ending the last one with an ellipsis
+    pub max_lines: Option<usize>,
+    /// Multiplier applied to the font's line height between lines
+    pub line_spacing: f32,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            font_size: 16.0,
+            max_width: None,
+            align: Align::Left,
+            max_lines: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaidOut {
+    Glyph {
+        ch: char,
+        x: f32,
+        y: f32,
+        font_size: f32,
+        bold: bool,
+        color: [f32; 4],
+        face: Option<FaceId>,
+    },
+    Underline {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: [f32; 4],
+    },
+    Image {
+        path: String,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    },
+}
+
+impl LaidOut {
+    fn shift_x(&mut self, dx: f32) {
+        match self {
+            LaidOut::Glyph { x, .. } | LaidOut::Underline { x, .. } | LaidOut::Image { x, .. } => {
+                *x += dx
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub items: Vec<LaidOut>,
+    pub width: f32,
+    pub height: f32,
+    pub line_count: usize,
+}
+
+/// Parse the BBCode-like subset used by `RichLabelGem`:
+/// `[b]`, `[u]`, `[color=red]` / `[color=#rrggbb]` and `[img]path[/img]`
+/// (optionally `[img=32x32]`). Unknown or malformed tags are kept as text.
+pub fn parse_markup(src: &str, base: &TextStyle) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut stack: Vec<(String, TextStyle)> = Vec::new();
+    let mut style = base.clone();
+    let mut text = String::new();
+    let mut rest = src;
+
+    fn flush(spans: &mut Vec<Span>, text: &mut String, style: &TextStyle) {
+        if !text.is_empty() {
+            spans.push(Span::Text {
+                text: std::mem::take(text),
+                style: style.clone(),
+            });
+        }
+    }
+
+    while let Some(open) = rest.find('[') {
+        text.push_str(&rest[..open]);
+        let after = &rest[open..];
+        let Some(close) = after.find(']') else {
+            text.push_str(after);
+            rest = "";
+            break;
+        };
+        let tag = &after[1..close];
+        let consumed = &after[..=close];
+        rest = &after[close + 1..];
+
+        let (name, arg) = match tag.split_once('=') {
+            Some((n, a)) => (n, Some(a)),
+            None => (tag, None),
+        };
+        match (name, arg) {
+            ("b", None) | ("u", None) => {
+                flush(&mut spans, &mut text, &style);
+                stack.push((name.to_string(), style.clone()));
+                if name == "b" {
+                    style.bold = true;
+                } else {
+                    style.underline = true;
+                }
+            }
+            ("color", Some(c)) if parse_color_name(c).is_some() => {
+                flush(&mut spans, &mut text, &style);
+                stack.push((name.to_string(), style.clone()));
+                style.color = parse_color_name(c).unwrap();
+            }
+            ("img", size) => {
+                let Some(end) = rest.find("[/img]") else {
+                    text.push_str(consumed);
+                    continue;
+                };
+                flush(&mut spans, &mut text, &style);
+                let path = rest[..end].trim().to_string();
+                let size = size.and_then(|s| {
+                    let (w, h) = s.split_once('x')?;
+                    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+                });
+                spans.push(Span::Image { path, size });
+                rest = &rest[end + "[/img]".len()..];
+            }
+            (closing, None) if closing.starts_with('/') => {
+                let closing = &closing[1..];
+                if stack.last().map(|(n, _)| n.as_str()) == Some(closing) {
+                    flush(&mut spans, &mut text, &style);
+                    style = stack.pop().unwrap().1;
+                } else {
+                    text.push_str(consumed);
+                }
+            }
+            _ => text.push_str(consumed),
+        }
+    }
+    text.push_str(rest);
+    flush(&mut spans, &mut text, &style);
+    spans
+}
+
+/// Named colors accepted by `[color=...]`, plus `#rrggbb` / `#rrggbbaa`
+pub fn parse_color_name(name: &str) -> Option<[f32; 4]> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 && hex.len() != 8 {
+            return None;
+        }
+        let ch = |i: usize| {
+            u8::from_str_radix(hex.get(i..i + 2)?, 16)
+                .ok()
+                .map(|v| v as f32 / 255.0)
+        };
+        let a = if hex.len() == 8 { ch(6)? } else { 1.0 };
+        return Some([ch(0)?, ch(2)?, ch(4)?, a]);
+    }
+    let rgb = match name {
+        "white" => [1.0, 1.0, 1.0],
+        "black" => [0.0, 0.0, 0.0],
+        "red" => [1.0, 0.0, 0.0],
+        "green" => [0.0, 1.0, 0.0],
+        "blue" => [0.0, 0.0, 1.0],
+        "yellow" => [1.0, 1.0, 0.0],
+        "cyan" => [0.0, 1.0, 1.0],
+        "magenta" => [1.0, 0.0, 1.0],
+        "orange" => [1.0, 0.65, 0.0],
+        "purple" => [0.5, 0.0, 0.5],
+        "gray" | "grey" => [0.5, 0.5, 0.5],
+        _ => return None,
+    };
+    Some([rgb[0], rgb[1], rgb[2], 1.0])
+}
+
+/// One piece the line breaker places as a unit
+enum Piece<'a> {
+    Word(&'a str, &'a TextStyle),
+    Space(&'a str, &'a TextStyle),
+    Newline,
+    Image(&'a str, Option<(f32, f32)>),
+}
+
+fn pieces(spans: &[Span]) -> Vec<Piece<'_>> {
+    let mut out = Vec::new();
+    for span in spans {
+        match span {
+            Span::Text { text, style } => {
+                let mut start = 0;
+                let mut in_space = None;
+                for (i, c) in text.char_indices() {
+                    if c == '\n' {
+                        if start < i {
+                            out.push(make_piece(&text[start..i], style, in_space == Some(true)));
+                        }
+                        out.push(Piece::Newline);
+                        start = i + 1;
+                        in_space = None;
+                        continue;
+                    }
+                    let is_space = c.is_whitespace();
+                    if in_space.is_some_and(|s| s != is_space) {
+                        out.push(make_piece(&text[start..i], style, !is_space));
+                        start = i;
+                    }
+                    in_space = Some(is_space);
+                }
+                if start < text.len() {
+                    out.push(make_piece(&text[start..], style, in_space == Some(true)));
+                }
+            }
+            Span::Image { path, size } => out.push(Piece::Image(path, *size)),
+        }
+    }
+    out
+}
+
+fn make_piece<'a>(s: &'a str, style: &'a TextStyle, space: bool) -> Piece<'a> {
+    if space {
+        Piece::Space(s, style)
+    } else {
+        Piece::Word(s, style)
+    }
+}
+
+/// A finished line: the item range it owns plus what alignment needs
+struct LineInfo {
+    start: usize,
+    end: usize,
+    y: f32,
+    h: f32,
+    width: f32,
+    /// Item indices where inter-word space was placed (used by justify)
+    gaps: Vec<usize>,
+    /// Ended by an explicit newline or the end of the text
+    hard_break: bool,
+}
+
+struct LineBuilder<'m> {
+    metrics: &'m dyn GlyphMetrics,
+    params: LayoutParams,
+    layout: TextLayout,
+    line_start: usize,
+    cursor_x: f32,
+    /// Width up to the last non-space piece, so trailing spaces don't count
+    content_w: f32,
+    line_y: f32,
+    line_h: f32,
+    gaps: Vec<usize>,
+    lines: Vec<LineInfo>,
+}
+
+impl<'m> LineBuilder<'m> {
+    fn new(metrics: &'m dyn GlyphMetrics, params: LayoutParams) -> Self {
+        let line_h = metrics.line_height(params.font_size);
+        Self {
+            metrics,
+            params,
+            layout: TextLayout::default(),
+            line_start: 0,
+            cursor_x: 0.0,
+            content_w: 0.0,
+            line_y: 0.0,
+            line_h,
+            gaps: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn text_width(&self, s: &str) -> f32 {
+        s.chars()
+            .map(|c| self.metrics.advance(c, self.params.font_size))
+            .sum()
+    }
+
+    fn fits(&self, w: f32) -> bool {
+        match self.params.max_width {
+            Some(max) => self.cursor_x == 0.0 || self.cursor_x + w <= max,
+            None => true,
+        }
+    }
+
+    fn newline(&mut self, hard_break: bool) {
+        let end = self.layout.items.len();
+        self.lines.push(LineInfo {
+            start: self.line_start,
+            end,
+            y: self.line_y,
+            h: self.line_h,
+            width: self.content_w,
+            gaps: std::mem::take(&mut self.gaps),
+            hard_break,
+        });
+        self.line_start = end;
+        self.line_y += self.line_h * self.params.line_spacing;
+        self.line_h = self.metrics.line_height(self.params.font_size);
+        self.cursor_x = 0.0;
+        self.content_w = 0.0;
+    }
+
+    fn place_text(&mut self, s: &str, style: &TextStyle, visible: bool) {
+        let size = self.params.font_size;
+        let start_x = self.cursor_x;
+        if !visible {
+            self.gaps.push(self.layout.items.len());
+        }
+        for ch in s.chars() {
+            if visible {
+                self.layout.items.push(LaidOut::Glyph {
+                    ch,
+                    x: self.cursor_x,
+                    y: self.line_y,
+                    font_size: size,
+                    bold: style.bold,
+                    color: style.color,
+                    face: self.metrics.face(ch),
+                });
+            }
+            self.cursor_x += self.metrics.advance(ch, size);
+        }
+        if style.underline {
+            let thickness = (size / 16.0).max(1.0);
+            self.layout.items.push(LaidOut::Underline {
+                x: start_x,
+                y: self.line_y + self.metrics.line_height(size) - thickness,
+                w: self.cursor_x - start_x,
+                h: thickness,
+                color: style.color,
+            });
+        }
+        if visible {
+            self.content_w = self.cursor_x;
+        }
+    }
+
+    fn right_edge(&self, item: &LaidOut) -> f32 {
+        match item {
+            LaidOut::Glyph {
+                ch, x, font_size, ..
+            } => x + self.metrics.advance(*ch, *font_size),
+            LaidOut::Underline { x, w, .. } | LaidOut::Image { x, w, .. } => x + w,
+        }
+    }
+
+    /// Drop lines past `max_lines` and end the last kept line with "..."
+    fn truncate(&mut self, max_lines: usize) {
+        let max_lines = max_lines.max(1);
+        if self.lines.len() <= max_lines {
+            return;
+        }
+        self.lines.truncate(max_lines);
+        let last = self.lines.last().unwrap();
+        let (start, line_y) = (last.start, last.y);
+        self.layout.items.truncate(last.end);
+
+        const ELLIPSIS: &str = "...";
+        let ellipsis_w = self.text_width(ELLIPSIS);
+        let limit = self.params.max_width.map(|m| m - ellipsis_w);
+        while self.layout.items.len() > start {
+            let edge = self.right_edge(self.layout.items.last().unwrap());
+            if limit.is_none_or(|l| edge <= l) {
+                break;
+            }
+            self.layout.items.pop();
+        }
+
+        // Continue in the style of the last surviving glyph
+        let (mut x, mut bold, mut color) = (0.0, false, TextStyle::default().color);
+        for item in &self.layout.items[start..] {
+            x = f32::max(x, self.right_edge(item));
+            if let LaidOut::Glyph {
+                bold: b, color: c, ..
+            } = item
+            {
+                bold = *b;
+                color = *c;
+            }
+        }
+        for ch in ELLIPSIS.chars() {
+            self.layout.items.push(LaidOut::Glyph {
+                ch,
+                x,
+                y: line_y,
+                font_size: self.params.font_size,
+                bold,
+                color,
+                face: self.metrics.face(ch),
+            });
+            x += self.metrics.advance(ch, self.params.font_size);
+        }
+
+        let end = self.layout.items.len();
+        let last = self.lines.last_mut().unwrap();
+        last.end = end;
+        last.width = x;
+        last.gaps.retain(|g| *g < end);
+        last.hard_break = true;
+    }
+
+    fn finish(mut self) -> TextLayout {
+        self.newline(true);
+        if let Some(max) = self.params.max_lines {
+            self.truncate(max);
+        }
+        let box_w = self
+            .params
+            .max_width
+            .unwrap_or_else(|| self.lines.iter().map(|l| l.width).fold(0.0, f32::max));
+        for line in &self.lines {
+            let slack = box_w - line.width;
+            let items = &mut self.layout.items[line.start..line.end];
+            match self.params.align {
+                Align::Justify if !line.hard_break => {
+                    // Only gaps between words count; leading/trailing ones don't
+                    let gaps: Vec<usize> = line
+                        .gaps
+                        .iter()
+                        .copied()
+                        .filter(|g| *g > line.start && *g < line.end)
+                        .collect();
+                    if gaps.is_empty() || slack <= 0.0 {
+                        continue;
+                    }
+                    let per_gap = slack / gaps.len() as f32;
+                    for (offset, item) in items.iter_mut().enumerate() {
+                        let idx = line.start + offset;
+                        let n = gaps.iter().filter(|g| **g <= idx).count();
+                        item.shift_x(per_gap * n as f32);
+                    }
+                }
+                align => {
+                    let factor = match align {
+                        Align::Center => 0.5,
+                        Align::Right => 1.0,
+                        Align::Left | Align::Justify => 0.0,
+                    };
+                    let dx = slack * factor;
+                    if dx != 0.0 {
+                        for item in items {
+                            item.shift_x(dx);
+                        }
+                    }
+                }
+            }
+        }
+        self.layout.width = box_w;
+        self.layout.height = self.lines.last().map(|l| l.y + l.h).unwrap_or(0.0);
+        self.layout.line_count = self.lines.len();
+        self.layout
+    }
+}
+
+/// Lay out spans into lines, wrapping on word boundaries at `max_width`.
+pub fn layout(spans: &[Span], params: &LayoutParams, metrics: &dyn GlyphMetrics) -> TextLayout {
+    let mut b = LineBuilder::new(metrics, params.clone());
+    for piece in pieces(spans) {
+        match piece {
+            Piece::Newline => b.newline(true),
+            Piece::Space(s, style) => {
+                // Spaces that would start a wrapped line are dropped
+                if b.cursor_x > 0.0 {
+                    b.place_text(s, style, false);
+                }
+            }
+            Piece::Word(s, style) => {
+                let w = b.text_width(s);
+                if !b.fits(w) {
+                    b.newline(false);
+                }
+                b.place_text(s, style, true);
+            }
+            Piece::Image(path, size) => {
+                let (w, h) = size.unwrap_or((b.line_h, b.line_h));
+                if !b.fits(w) {
+                    b.newline(false);
+                }
+                b.layout.items.push(LaidOut::Image {
+                    path: path.to_string(),
+                    x: b.cursor_x,
+                    y: b.line_y,
+                    w,
+                    h,
+                });
+                b.cursor_x += w;
+                b.content_w = b.cursor_x;
+                b.line_h = b.line_h.max(h);
+            }
+        }
+    }
+    b.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every glyph is 10px wide and lines are 20px tall
+    struct Fixed;
+    impl GlyphMetrics for Fixed {
+        fn advance(&self, _ch: char, _font_size: f32) -> f32 {
+            10.0
+        }
+        fn line_height(&self, _font_size: f32) -> f32 {
+            20.0
+        }
+    }
+
+    #[test]
+    fn markup_runs() {
+        let spans = parse_markup(
+            "a [b]bold[/b] [color=red]red[/color][img=8x8]#assets:icon[/img] [x]",
+            &TextStyle::default(),
+        );
+        assert_eq!(spans.len(), 6);
+        match &spans[1] {
+            Span::Text { text, style } => {
+                assert_eq!(text, "bold");
+                assert!(style.bold);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &spans[3] {
+            Span::Text { style, .. } => assert_eq!(style.color, [1.0, 0.0, 0.0, 1.0]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            spans[4],
+            Span::Image {
+                path: "#assets:icon".into(),
+                size: Some((8.0, 8.0))
+            }
+        );
+        // unknown tags survive as literal text
+        match &spans[5] {
+            Span::Text { text, .. } => assert_eq!(text, " [x]"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wraps_and_aligns() {
+        let spans = parse_markup("aaa bbb cc", &TextStyle::default());
+        let params = LayoutParams {
+            max_width: Some(75.0),
+            align: Align::Right,
+            ..Default::default()
+        };
+        let l = layout(&spans, &params, &Fixed);
+        assert_eq!(l.line_count, 2);
+        assert_eq!(l.height, 40.0);
+        // second line "cc" is 20px wide, right-aligned in 75px
+        let last = l.items.last().unwrap();
+        match last {
+            LaidOut::Glyph { ch, x, y, .. } => {
+                assert_eq!(*ch, 'c');
+                assert_eq!(*x, 65.0);
+                assert_eq!(*y, 20.0);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn justify_ellipsis_and_spacing() {
+        let spans = parse_markup("aa b cc dd ee", &TextStyle::default());
+        let params = LayoutParams {
+            max_width: Some(60.0),
+            align: Align::Justify,
+            max_lines: Some(2),
+            line_spacing: 1.5,
+            ..Default::default()
+        };
+        let l = layout(&spans, &params, &Fixed);
+        // "aa b" / "cc dd" / "ee" truncated to two lines
+        assert_eq!(l.line_count, 2);
+        assert_eq!(l.height, 30.0 + 20.0);
+        let glyphs: Vec<(char, f32, f32)> = l
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                LaidOut::Glyph { ch, x, y, .. } => Some((*ch, *x, *y)),
+                _ => None,
+            })
+            .collect();
+        // first line is justified: "b" pushed to the right edge
+        assert_eq!(glyphs[2], ('b', 50.0, 0.0));
+        // second line "cc dd" (50px) can't also fit "..." in 60px: "dd" is dropped
+        let second: String = glyphs.iter().filter(|g| g.2 == 30.0).map(|g| g.0).collect();
+        assert_eq!(second, "cc...");
+    }
+}
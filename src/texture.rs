@@ -0,0 +1,203 @@
+//! Texture sampling options: `filter: nearest | linear`, `repeat: enabled |
+//! disabled` and `mipmaps: true | false`. An asset's import file, next to it
+//! with `.import` appended (`hero.svg.import`), sets them for every use:
+//!
+//! ```text
+//! Import: Gem { filter: nearest }
+//! ```
+//!
+//! and a node overrides them with the same properties beside its `texture`.
+//! The defaults (linear, clamped, no mipmaps) rasterize an SVG at its
+//! display size; any other option rasterizes it at its own size and leaves
+//! scaling, tiling and mipmapping to the GPU, so `nearest` keeps pixel art
+//! sharp and `repeat` tiles the image across the node.
+//!
+//! A `region(x, y, w, h)` argument on the directive
+//! (`#assets:atlas.svg:region(0, 0, 32, 32)`) draws only that part of the
+//! image, in its own units, so one atlas can serve many nodes.
+
+use std::path::{Path, PathBuf};
+
+use crate::ast;
+use crate::pipeline;
+use crate::vfs;
+
+pub const IMPORT_EXTENSION: &str = "import";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Filter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "linear" => Some(Filter::Linear),
+            "nearest" => Some(Filter::Nearest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    pub filter: Filter,
+    /// Tile the image at its own size instead of stretching it
+    pub repeat: bool,
+    pub mipmaps: bool,
+}
+
+impl TextureOptions {
+    /// Take `filter`, `repeat` and `mipmaps` from `properties`, warning
+    /// (as `owner`) about values that aren't understood
+    pub fn apply(&mut self, properties: &[ast::Property], owner: &str) {
+        for p in properties {
+            let word = match &p.value {
+                ast::Value::Ident(s) | ast::Value::String(s) => Some(s.as_str()),
+                ast::Value::Bool(true) => Some("enabled"),
+                ast::Value::Bool(false) => Some("disabled"),
+                _ => None,
+            };
+            let understood = match (p.key.as_str(), word) {
+                ("filter", Some(w)) => Filter::parse(w).map(|f| self.filter = f).is_some(),
+                ("repeat", Some(w)) => on_off(w).map(|on| self.repeat = on).is_some(),
+                ("mipmaps", Some(w)) => on_off(w).map(|on| self.mipmaps = on).is_some(),
+                ("filter" | "repeat" | "mipmaps", None) => false,
+                _ => true,
+            };
+            if !understood {
+                eprintln!(
+                    "[WARN] {}: unknown {} {:?}, ignoring it",
+                    owner, p.key, p.value
+                );
+            }
+        }
+    }
+
+    /// The options the GPU has nothing to do for
+    pub fn is_default(&self) -> bool {
+        *self == TextureOptions::default()
+    }
+}
+
+fn on_off(word: &str) -> Option<bool> {
+    match word {
+        "enabled" => Some(true),
+        "disabled" => Some(false),
+        _ => None,
+    }
+}
+
+/// A texture and how it's sampled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    pub path: PathBuf,
+    pub options: TextureOptions,
+    /// The part of the image drawn: x, y, width, height in image units
+    pub region: Option<[f32; 4]>,
+}
+
+impl Texture {
+    /// Whether the image is rasterized at the size it's drawn, rather than
+    /// at its own size for the GPU to sample
+    pub fn rasterized_at_display_size(&self) -> bool {
+        self.options.is_default() && self.region.is_none()
+    }
+}
+
+/// The `region(x, y, w, h)` of a texture directive, warning (as `owner`)
+/// about any other argument
+pub fn region(directive: &ast::Directive, owner: &str) -> Option<[f32; 4]> {
+    for arg in &directive.args {
+        if !matches!(arg, ast::DirectiveArg::Call { name, .. } if name == "region") {
+            eprintln!(
+                "[WARN] {}: texture directives take region(x, y, w, h), ignoring {:?}",
+                owner, arg
+            );
+        }
+    }
+    let values = directive.call("region")?;
+    let numbers: Option<Vec<f32>> = values
+        .iter()
+        .map(|v| match v {
+            ast::Value::Integer(i) => Some(*i as f32),
+            ast::Value::Number(n) => Some(*n as f32),
+            _ => None,
+        })
+        .collect();
+    let region = numbers.and_then(|n| <[f32; 4]>::try_from(n).ok());
+    if region.is_none() {
+        eprintln!(
+            "[WARN] {}: region takes four numbers, got {:?}",
+            owner, values
+        );
+    }
+    region
+}
+
+/// `hero.svg.import` for `hero.svg`
+pub fn import_path(asset: &Path) -> PathBuf {
+    let mut name = asset.as_os_str().to_owned();
+    name.push(".");
+    name.push(IMPORT_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// The options `asset`'s import file sets, or the defaults without one
+pub fn import_options(asset: &Path) -> TextureOptions {
+    let mut options = TextureOptions::default();
+    let path = import_path(asset);
+    let name = path.to_string_lossy();
+    if !vfs::is_file(&name) {
+        return options;
+    }
+    match vfs::read_to_string(&name)
+        .and_then(|src| pipeline::lex_source(&src))
+        .and_then(pipeline::parse_scene)
+    {
+        Ok(import) => options.apply(&import.root.properties, &name),
+        Err(e) => eprintln!("[WARN] {}: {}; using default options", name, e),
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options_of(src: &str) -> TextureOptions {
+        let scene = pipeline::lex_source(src)
+            .and_then(pipeline::parse_scene)
+            .unwrap();
+        let mut options = TextureOptions::default();
+        options.apply(&scene.root.properties, "test");
+        options
+    }
+
+    #[test]
+    fn parses_filter_repeat_and_mipmaps() {
+        assert!(options_of("Import: Gem {}").is_default());
+        assert_eq!(
+            options_of("Import: Gem { filter: nearest repeat: enabled mipmaps: true }"),
+            TextureOptions {
+                filter: Filter::Nearest,
+                repeat: true,
+                mipmaps: true,
+            }
+        );
+        // Unknown values are skipped, leaving the rest
+        assert_eq!(
+            options_of("Import: Gem { filter: cubic repeat: true }"),
+            TextureOptions {
+                repeat: true,
+                ..TextureOptions::default()
+            }
+        );
+        assert_eq!(
+            import_path(Path::new("assets/hero.svg")),
+            PathBuf::from("assets/hero.svg.import")
+        );
+    }
+}
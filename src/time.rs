@@ -0,0 +1,182 @@
+//! The engine clock and the `Time` singleton.
+//! All frame timing comes from one monotonic clock counted in whole
+//! microseconds since startup. `begin_frame` reads it once per frame and
+//! returns the difference from the previous reading as that frame's `dt`.
+//! Everything stepped by `_process(dt)`, such as timers, therefore advances
+//! by exactly the clock's elapsed time, without rounding drift piling up.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::object::{ObjectInner, ObjectRef};
+use crate::performance;
+use crate::value::Value;
+
+static START: OnceLock<Instant> = OnceLock::new();
+/// Clock reading at the start of the current frame
+static FRAME_USEC: AtomicU64 = AtomicU64::new(0);
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+
+/// Microseconds since the engine started
+pub fn ticks_usec() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+pub fn ticks_msec() -> u64 {
+    ticks_usec() / 1000
+}
+
+/// Frames begun so far
+pub fn frames() -> u64 {
+    FRAMES.load(Ordering::Relaxed)
+}
+
+/// Start a frame: count it and return the seconds since the last one
+pub fn begin_frame() -> f64 {
+    let now = ticks_usec();
+    let last = FRAME_USEC.swap(now, Ordering::Relaxed);
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+    performance::record_frame(now);
+    now.saturating_sub(last) as f64 / 1_000_000.0
+}
+
+/// Seconds since 1970-01-01 UTC
+pub fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    /// 1-12
+    pub month: u32,
+    /// 1-31
+    pub day: u32,
+    /// 0 is Sunday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// The UTC date and time `secs` seconds after the Unix epoch
+    pub fn from_unix(secs: i64) -> DateTime {
+        let days = secs.div_euclid(86_400);
+        let of_day = secs.rem_euclid(86_400) as u32;
+        // Civil date from a day count (Howard Hinnant's days_from_civil, inverted)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        DateTime {
+            year,
+            month,
+            day,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            hour: of_day / 3600,
+            minute: of_day / 60 % 60,
+            second: of_day % 60,
+        }
+    }
+
+    pub fn to_value(self) -> Value {
+        Value::Map(HashMap::from([
+            ("year".to_string(), Value::Int(self.year)),
+            ("month".to_string(), Value::Int(self.month.into())),
+            ("day".to_string(), Value::Int(self.day.into())),
+            ("weekday".to_string(), Value::Int(self.weekday.into())),
+            ("hour".to_string(), Value::Int(self.hour.into())),
+            ("minute".to_string(), Value::Int(self.minute.into())),
+            ("second".to_string(), Value::Int(self.second.into())),
+        ]))
+    }
+}
+
+/// The script-facing `Time` singleton
+pub fn new_time() -> ObjectRef {
+    let obj = ObjectInner::base("Time");
+    // get_ticks_msec(), get_ticks_usec(): since the engine started
+    ObjectInner::insert_method(
+        &obj,
+        "get_ticks_msec",
+        Arc::new(|_, _| Ok(Value::Int(ticks_msec() as i64))),
+    );
+    ObjectInner::insert_method(
+        &obj,
+        "get_ticks_usec",
+        Arc::new(|_, _| Ok(Value::Int(ticks_usec() as i64))),
+    );
+    // get_unix_time() -> Float seconds
+    ObjectInner::insert_method(
+        &obj,
+        "get_unix_time",
+        Arc::new(|_, _| Ok(Value::Float(unix_time()))),
+    );
+    // get_datetime_dict(unix_time?): UTC, now by default
+    ObjectInner::insert_method(
+        &obj,
+        "get_datetime_dict",
+        Arc::new(|_, args| {
+            let secs = match args.first() {
+                None => unix_time(),
+                Some(v) => v.as_f64().ok_or("get_datetime_dict expects a unix time")?,
+            };
+            Ok(DateTime::from_unix(secs.floor() as i64).to_value())
+        }),
+    );
+    // get_frames(): frames begun since startup
+    ObjectInner::insert_method(
+        &obj,
+        "get_frames",
+        Arc::new(|_, _| Ok(Value::Int(frames() as i64))),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_follow_the_calendar() {
+        let date = |secs| {
+            let d = DateTime::from_unix(secs);
+            (
+                d.year, d.month, d.day, d.weekday, d.hour, d.minute, d.second,
+            )
+        };
+        assert_eq!(date(0), (1970, 1, 1, 4, 0, 0, 0));
+        // Leap day, and the second before the epoch
+        assert_eq!(date(951_827_696), (2000, 2, 29, 2, 12, 34, 56));
+        assert_eq!(date(-1), (1969, 12, 31, 3, 23, 59, 59));
+    }
+
+    #[test]
+    fn frame_deltas_add_up_to_the_clock() {
+        let first = ticks_usec();
+        let frames_before = frames();
+        begin_frame();
+        let start = FRAME_USEC.load(Ordering::Relaxed);
+        let mut total = 0.0;
+        for _ in 0..5 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            total += begin_frame();
+        }
+        let end = FRAME_USEC.load(Ordering::Relaxed);
+        assert!(start >= first);
+        assert_eq!(frames() - frames_before, 6);
+        assert!((total - (end - start) as f64 / 1_000_000.0).abs() < 1e-9);
+        assert!(total >= 0.01);
+    }
+}
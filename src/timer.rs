@@ -0,0 +1,163 @@
+//! TimerGem: counts down `wait_time` seconds and emits `timeout`.
+//! The scene tree ticks it through `_process(dt)`, so a timer stops counting
+//! whenever its node can't process (e.g. gameplay timers while paused).
+//! Non-`one_shot` timers restart after each timeout.
+
+use std::sync::{Arc, OnceLock};
+
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, define_method, register_class};
+use crate::value::Value;
+
+const WAIT_TIME_KEY: &str = "wait_time";
+const ONE_SHOT_KEY: &str = "one_shot";
+/// Seconds until the next timeout; zero while stopped
+const TIME_LEFT_KEY: &str = "time_left";
+
+pub fn init_timer_class() {
+    register_class("TimerGem", || {
+        let obj = gem::new_gem("TimerGem", timer_methods());
+        obj.set_property(WAIT_TIME_KEY, Value::Float(1.0));
+        obj.set_property(ONE_SHOT_KEY, Value::Bool(false));
+        obj.set_property(TIME_LEFT_KEY, Value::Float(0.0));
+        obj
+    });
+}
+
+fn timer_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_timer_methods()))
+        .clone()
+}
+
+fn build_timer_methods() -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+
+    // start(time?): `time` replaces wait_time when given
+    define_method(
+        &mut methods,
+        "start",
+        Arc::new(|this, args| {
+            if let Some(arg) = args.first() {
+                let time = arg.as_f64().ok_or("start expects a time in seconds")?;
+                this.set_property(WAIT_TIME_KEY, Value::Float(time));
+            }
+            this.set_property(
+                TIME_LEFT_KEY,
+                Value::Float(f64_property(this, WAIT_TIME_KEY)),
+            );
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "stop",
+        Arc::new(|this, _| {
+            this.set_property(TIME_LEFT_KEY, Value::Float(0.0));
+            Ok(Value::Null)
+        }),
+    );
+    define_method(
+        &mut methods,
+        "is_stopped",
+        Arc::new(|this, _| Ok(Value::Bool(f64_property(this, TIME_LEFT_KEY) <= 0.0))),
+    );
+    define_method(
+        &mut methods,
+        "get_time_left",
+        Arc::new(|this, _| Ok(Value::Float(f64_property(this, TIME_LEFT_KEY)))),
+    );
+    // _process(dt), called by the scene tree
+    define_method(
+        &mut methods,
+        "_process",
+        Arc::new(|this, args| {
+            let dt = args
+                .first()
+                .and_then(Value::as_f64)
+                .ok_or("_process expects dt")?;
+            tick(this, dt);
+            Ok(Value::Null)
+        }),
+    );
+
+    // signals: timeout
+    methods
+}
+
+fn f64_property(node: &ObjectRef, key: &str) -> f64 {
+    node.with_property(key, |v| v.and_then(Value::as_f64))
+        .unwrap_or(0.0)
+}
+
+fn tick(timer: &ObjectRef, dt: f64) {
+    let left = f64_property(timer, TIME_LEFT_KEY);
+    if left <= 0.0 {
+        return;
+    }
+    let mut left = left - dt;
+    if left <= 0.0 {
+        let wait = f64_property(timer, WAIT_TIME_KEY);
+        let one_shot = matches!(timer.get_property(ONE_SHOT_KEY), Some(Value::Bool(true)));
+        // Carry the overshoot into the next period so repeats don't drift
+        left = if one_shot || wait <= 0.0 {
+            0.0
+        } else {
+            (left + wait).max(f64::MIN_POSITIVE)
+        };
+        timer.set_property(TIME_LEFT_KEY, Value::Float(left));
+        timer.emit_signal("timeout", &[]);
+        return;
+    }
+    timer.set_property(TIME_LEFT_KEY, Value::Float(left));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{init_object_class, object_new};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn timers_count_down_and_repeat() {
+        init_object_class();
+        gem::init_gem_class();
+        init_timer_class();
+
+        let timer = object_new("TimerGem");
+        let fired = Arc::new(AtomicUsize::new(0));
+        let seen = fired.clone();
+        timer.connect(
+            "timeout",
+            Arc::new(move |_| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+        let step = |dt: f64| {
+            timer.call_method("_process", &[Value::Float(dt)]).unwrap();
+        };
+
+        // Stopped timers don't count
+        step(5.0);
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+        timer.call_method("start", &[Value::Float(0.5)]).unwrap();
+        step(0.25);
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+        step(0.5);
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        // Restarted with the 0.25s overshoot already spent
+        assert_eq!(
+            timer.call_method("get_time_left", &[]).unwrap(),
+            Value::Float(0.25)
+        );
+
+        timer.set_property(ONE_SHOT_KEY, Value::Bool(true));
+        step(0.25);
+        assert_eq!(fired.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            timer.call_method("is_stopped", &[]).unwrap(),
+            Value::Bool(true)
+        );
+    }
+}
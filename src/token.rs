@@ -6,19 +6,35 @@ pub enum Token {
     Float(f64),
     String(String),
     Bool(bool),
+    Color([u8; 4]), // hex literal: #rrggbb or #rrggbbaa
 
     // Keywords for logic
     On,      // 'on' event handler keyword
     Spawn,   // 'spawn' to create Gem instances
     Extend,  // 'extend' header in logic files
     Fn,      // 'fn' function declaration keyword
+    If,      // 'if' branch
+    Else,    // 'else' after an 'if' block
+    For,     // 'for' loop
+    In,      // 'in' between a loop variable and what it iterates
+    Return,  // 'return' from a function
+    Let,     // 'let' declares a local variable
+    Match,   // 'match' picks the arm whose pattern equals a value
     
     Hash,               // '#'
+    Percent,            // '%' marks a scene-unique node name
+    At,                 // '@' starts an annotation (`@rpc`)
+    Question,           // '?' starts a directive's query (`#scenes:enemy?variant=blue`)
+    Amp,                // '&' joins query parameters
+    Dollar,             // '$' reads a define (`$platform`)
     DocComment(String), // collected from lines starting with '///'
     Eq,
+    FatArrow, // '=>' between a match pattern and its block
     Semi,
     LParen,
     RParen,
+    LBracket,
+    RBracket,
     LBrace,
     RBrace,
 
@@ -34,12 +50,33 @@ pub enum Token {
 
     Not,
 
-    Comma, // ,
-    Colon, // :
-    Dot,   // .
+    Comma,  // ,
+    Colon,  // :
+    Dot,    // .
+    DotDot, // .. between a range's bounds
 
     Less,      // <
     Greater,   // >
     LessEq,    // <=
     GreaterEq, // >=
 }
+
+impl Token {
+    /// The word a keyword token was lexed from
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            Token::On => Some("on"),
+            Token::Spawn => Some("spawn"),
+            Token::Extend => Some("extend"),
+            Token::Fn => Some("fn"),
+            Token::If => Some("if"),
+            Token::Else => Some("else"),
+            Token::For => Some("for"),
+            Token::In => Some("in"),
+            Token::Return => Some("return"),
+            Token::Let => Some("let"),
+            Token::Match => Some("match"),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! 2D affine transforms for runtime nodes.
+//! A node's local transform comes from its `position`, `rotation` (radians)
+//! and `scale` properties; its global transform is the parent's global
+//! transform times the local one.
+
+use std::f64::consts::{PI, TAU};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2D {
+    /// Columns of the linear part (where local x and y axes land)
+    pub x_axis: (f64, f64),
+    pub y_axis: (f64, f64),
+    pub origin: (f64, f64),
+}
+
+/// A node's `position`, `rotation` and `scale`, before they become a matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub position: (f64, f64),
+    pub rotation: f64,
+    pub scale: (f64, f64),
+}
+
+impl Pose {
+    pub fn transform(&self) -> Transform2D {
+        Transform2D::from_parts(self.position, self.rotation, self.scale)
+    }
+
+    /// `t` (0 to 1) of the way from `self` to `to`, turning the short way round
+    pub fn lerp(&self, to: &Pose, t: f64) -> Pose {
+        let mix = |a: f64, b: f64| a + (b - a) * t;
+        let turn = (to.rotation - self.rotation + PI).rem_euclid(TAU) - PI;
+        Pose {
+            position: (
+                mix(self.position.0, to.position.0),
+                mix(self.position.1, to.position.1),
+            ),
+            rotation: self.rotation + turn * t,
+            scale: (mix(self.scale.0, to.scale.0), mix(self.scale.1, to.scale.1)),
+        }
+    }
+}
+
+impl Transform2D {
+    /// Scale, then rotate, then translate
+    pub fn from_parts(position: (f64, f64), rotation: f64, scale: (f64, f64)) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+        Self {
+            x_axis: (cos * scale.0, sin * scale.0),
+            y_axis: (-sin * scale.1, cos * scale.1),
+            origin: position,
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.x_axis.0 * x + self.y_axis.0 * y + self.origin.0,
+            self.x_axis.1 * x + self.y_axis.1 * y + self.origin.1,
+        )
+    }
+
+    /// `self * local`: apply `local` first, as a child inside this transform
+    pub fn compose(&self, local: &Transform2D) -> Transform2D {
+        let linear = |(x, y): (f64, f64)| {
+            (
+                self.x_axis.0 * x + self.y_axis.0 * y,
+                self.x_axis.1 * x + self.y_axis.1 * y,
+            )
+        };
+        Transform2D {
+            x_axis: linear(local.x_axis),
+            y_axis: linear(local.y_axis),
+            origin: self.apply(local.origin),
+        }
+    }
+
+    /// `None` when the transform collapses space (a zero scale)
+    pub fn inverse(&self) -> Option<Transform2D> {
+        let det = self.x_axis.0 * self.y_axis.1 - self.y_axis.0 * self.x_axis.1;
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+        let inv = Transform2D {
+            x_axis: (self.y_axis.1 / det, -self.x_axis.1 / det),
+            y_axis: (-self.y_axis.0 / det, self.x_axis.0 / det),
+            origin: (0.0, 0.0),
+        };
+        let (ox, oy) = inv.apply(self.origin);
+        Some(Transform2D {
+            origin: (-ox, -oy),
+            ..inv
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn poses_blend_the_short_way_round() {
+        let from = Pose {
+            position: (0.0, 10.0),
+            rotation: 3.0,
+            scale: (1.0, 1.0),
+        };
+        let to = Pose {
+            position: (20.0, 30.0),
+            rotation: -3.0,
+            scale: (3.0, 1.0),
+        };
+        let mid = from.lerp(&to, 0.5);
+        assert!(close(mid.position, (10.0, 20.0)));
+        assert!(close(mid.scale, (2.0, 1.0)));
+        // Through pi, not back through zero
+        assert!((mid.rotation - std::f64::consts::PI).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compose_and_invert() {
+        let parent =
+            Transform2D::from_parts((100.0, 50.0), std::f64::consts::FRAC_PI_2, (2.0, 2.0));
+        let child = Transform2D::from_parts((10.0, 0.0), 0.0, (1.0, 1.0));
+        let global = parent.compose(&child);
+        // The child's offset is scaled by 2 and turned to point down
+        assert!(close(global.apply((0.0, 0.0)), (100.0, 70.0)));
+        let inv = global.inverse().unwrap();
+        assert!(close(inv.apply((100.0, 70.0)), (0.0, 0.0)));
+        assert!(close(inv.apply(global.apply((3.0, -4.0))), (3.0, -4.0)));
+        assert!(
+            Transform2D::from_parts((0.0, 0.0), 0.0, (0.0, 1.0))
+                .inverse()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! 4x4 matrices for the 3D path: a MeshGem's model matrix from its
+//! `position`, `rotation` (radians about x, y and z) and `scale`, and a
+//! Camera3DGem's perspective view of the world. Column-major, as GL takes them.
+//! Rotations imported or animated in f64 convert to and from `rotation`
+//! angles through `Rotation` matrices.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4(pub [f32; 16]);
+
+impl Mat4 {
+    #[rustfmt::skip]
+    pub const IDENTITY: Mat4 = Mat4([
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ]);
+
+    pub fn translation([x, y, z]: [f32; 3]) -> Mat4 {
+        let mut m = Mat4::IDENTITY;
+        m.0[12..15].copy_from_slice(&[x, y, z]);
+        m
+    }
+
+    pub fn scaling([x, y, z]: [f32; 3]) -> Mat4 {
+        let mut m = Mat4::IDENTITY;
+        (m.0[0], m.0[5], m.0[10]) = (x, y, z);
+        m
+    }
+
+    /// Roll about z, then pitch about x, then yaw about y
+    pub fn rotation([x, y, z]: [f32; 3]) -> Mat4 {
+        let about = |angle: f32, (a, b): (usize, usize)| {
+            let (sin, cos) = angle.sin_cos();
+            let mut m = Mat4::IDENTITY;
+            (m.0[a * 4 + a], m.0[a * 4 + b]) = (cos, sin);
+            (m.0[b * 4 + a], m.0[b * 4 + b]) = (-sin, cos);
+            m
+        };
+        about(y, (2, 0)) * about(x, (1, 2)) * about(z, (0, 1))
+    }
+
+    /// Scale, then rotate, then translate
+    pub fn from_parts(position: [f32; 3], rotation: [f32; 3], scale: [f32; 3]) -> Mat4 {
+        Mat4::translation(position) * Mat4::rotation(rotation) * Mat4::scaling(scale)
+    }
+
+    /// `fov_y` (radians) high, looking down -z; depth runs from `near` to `far`
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let f = 1.0 / (fov_y / 2.0).tan();
+        let mut m = Mat4([0.0; 16]);
+        m.0[0] = f / aspect;
+        m.0[5] = f;
+        m.0[10] = (far + near) / (near - far);
+        m.0[11] = -1.0;
+        m.0[14] = 2.0 * far * near / (near - far);
+        m
+    }
+
+    /// The inverse of a camera placed by `from_parts` without scale
+    pub fn view(position: [f32; 3], rotation: [f32; 3]) -> Mat4 {
+        let r = Mat4::rotation(rotation);
+        // A rotation's inverse is its transpose
+        let mut inverse = Mat4::IDENTITY;
+        for col in 0..3 {
+            for row in 0..3 {
+                inverse.0[col * 4 + row] = r.0[row * 4 + col];
+            }
+        }
+        let [x, y, z] = position;
+        inverse * Mat4::translation([-x, -y, -z])
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Mat4 {
+        Mat4::IDENTITY
+    }
+}
+
+impl std::ops::Mul for Mat4 {
+    type Output = Mat4;
+
+    /// `self * rhs`: apply `rhs` first
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let (a, b) = (&self.0, &rhs.0);
+        Mat4(std::array::from_fn(|i| {
+            let (col, row) = (i / 4, i % 4);
+            (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum()
+        }))
+    }
+}
+
+/// A 3x3 rotation matrix, `r[row][col]`
+pub type Rotation = [[f64; 3]; 3];
+
+pub const NO_ROTATION: Rotation = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// The rotation `Mat4::rotation` makes from the same angles
+pub fn rotation_of([x, y, z]: [f64; 3]) -> Rotation {
+    let ((sx, cx), (sy, cy), (sz, cz)) = (x.sin_cos(), y.sin_cos(), z.sin_cos());
+    [
+        [cy * cz + sy * sx * sz, sy * sx * cz - cy * sz, sy * cx],
+        [cx * sz, cx * cz, -sx],
+        [cy * sx * sz - sy * cz, sy * sz + cy * sx * cz, cy * cx],
+    ]
+}
+
+/// The `rotation` angles of `r`, the inverse of `rotation_of`
+pub fn angles(r: &Rotation) -> [f64; 3] {
+    let x = (-r[1][2]).clamp(-1.0, 1.0).asin();
+    if r[1][2].abs() < 0.999_999 {
+        [x, r[0][2].atan2(r[2][2]), r[1][0].atan2(r[1][1])]
+    } else {
+        // Facing straight up or down, y and z turn about the same axis
+        [x, (-r[2][0]).atan2(r[0][0]), 0.0]
+    }
+}
+
+/// A unit quaternion `[x, y, z, w]` as a matrix
+pub fn quaternion_rotation([x, y, z, w]: [f64; 4]) -> Rotation {
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+/// `a * b`: rotate by `b`, then by `a`
+pub fn compose(a: &Rotation, b: &Rotation) -> Rotation {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|col| (0..3).map(|k| a[row][k] * b[k][col]).sum())
+    })
+}
+
+pub fn rotate(r: &Rotation, v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|row| (0..3).map(|k| r[row][k] * v[k]).sum())
+}
+
+/// Translation, rotation and scale of a column-major 4x4 matrix without shear
+pub fn decompose(m: &[f64; 16]) -> ([f64; 3], Rotation, [f64; 3]) {
+    let scale: [f64; 3] = std::array::from_fn(|col| {
+        (0..3)
+            .map(|row| m[col * 4 + row].powi(2))
+            .sum::<f64>()
+            .sqrt()
+    });
+    let rotation = std::array::from_fn(|row| {
+        std::array::from_fn(|col| {
+            if scale[col] == 0.0 {
+                0.0
+            } else {
+                m[col * 4 + row] / scale[col]
+            }
+        })
+    });
+    ([m[12], m[13], m[14]], rotation, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    impl Mat4 {
+        fn apply(&self, [x, y, z]: [f32; 3]) -> [f32; 4] {
+            let m = &self.0;
+            std::array::from_fn(|row| m[row] * x + m[4 + row] * y + m[8 + row] * z + m[12 + row])
+        }
+    }
+
+    fn close(a: [f32; 4], b: [f32; 4]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn models_and_cameras_place_points() {
+        let model = Mat4::from_parts([1.0, 2.0, 3.0], [0.0, FRAC_PI_2, 0.0], [2.0, 2.0, 2.0]);
+        // A quarter turn about y takes x to -z
+        assert!(close(model.apply([1.0, 0.0, 0.0]), [1.0, 2.0, 1.0, 1.0]));
+
+        // A camera turned the same way sees that point straight ahead
+        let view = Mat4::view([1.0, 2.0, 3.0], [0.0, FRAC_PI_2, 0.0]);
+        assert!(close(view.apply([-1.0, 2.0, 3.0]), [0.0, 0.0, -2.0, 1.0]));
+
+        // The near plane's center lands on the near end of clip space
+        let projection = Mat4::perspective(FRAC_PI_2, 1.0, 0.5, 100.0);
+        let [x, y, z, w] = projection.apply([0.0, 0.0, -0.5]);
+        assert!(close([x / w, y / w, z / w, w], [0.0, 0.0, -1.0, 0.5]));
+    }
+
+    #[test]
+    fn rotations_convert_to_and_from_angles() {
+        let turn = [0.3, -1.2, 2.0];
+        let r = rotation_of(turn);
+        let back = angles(&r);
+        assert!(turn.iter().zip(back).all(|(a, b)| (a - b).abs() < 1e-9));
+        // Same as the f32 matrix
+        let m = Mat4::rotation([0.3, -1.2, 2.0]);
+        assert!((0..3).all(|row| (0..3).all(|col| (m.0[col * 4 + row] as f64 - r[row][col]).abs() < 1e-6)));
+
+        // A quarter turn about y
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let q = quaternion_rotation([0.0, half, 0.0, half]);
+        assert!((angles(&q)[1] - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+        let v = rotate(&compose(&q, &q), [1.0, 0.0, 0.0]);
+        assert!((v[0] + 1.0).abs() < 1e-9);
+    }
+}
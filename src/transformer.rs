@@ -1,23 +1,84 @@
 //! AST → IR Transformer
 //! Converts parsed GemFile (AST) into SceneIR (compile-time representation)
 
-use crate::ast::{GemDecl, GemFile, Value};
-use crate::ir::{NodeId, SceneIR, TypedProperty};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::ast::{GemDecl, GemFile, Property, Value};
+use crate::class_defs;
+use crate::ir::{NodeId, PropValue, SceneIR, TypedProperty};
+use crate::profile::Override;
 use crate::property_type::PropertyType;
+use crate::uid;
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Make what the class definitions reject (unknown types and properties,
+/// values of the wrong type) errors rather than warnings in every scene
+/// compiled from now on
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+pub fn strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+/// Remove `--strict` from `args`, returning whether it was there
+pub fn take_flag(args: &mut Vec<String>) -> bool {
+    let before = args.len();
+    args.retain(|a| a != "--strict");
+    args.len() != before
+}
 
 pub struct Transformer {
     scene: SceneIR,
+    overrides: Vec<Override>,
+    strict: bool,
+    /// Names from the root to the node being transformed
+    path: Vec<String>,
+    /// Properties and types the class definitions don't know
+    issues: Vec<String>,
 }
 
 impl Transformer {
     pub fn new() -> Self {
         Self {
             scene: SceneIR::new(),
+            overrides: Vec::new(),
+            strict: false,
+            path: Vec::new(),
+            issues: Vec::new(),
         }
     }
 
+    /// Set a build profile's properties over the scene's own
+    pub fn with_overrides(mut self, overrides: Vec<Override>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Fail on what the class definitions don't know instead of warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     pub fn transform(mut self, ast: GemFile) -> Result<SceneIR, String> {
         self.transform_gem_decl(&ast.root, None)?; // root becomes scene.root implicitly
+        for o in &self.overrides {
+            if o.path[0] == ast.root.name && !has_path(&ast.root, &o.path[1..]) {
+                eprintln!(
+                    "[WARN] Profile override for missing node {}",
+                    o.path.join("/")
+                );
+            }
+        }
+        if self.strict && !self.issues.is_empty() {
+            return Err(format!("strict mode: {}", self.issues.join("; ")));
+        }
+        for issue in &self.issues {
+            eprintln!("[WARN] {}", issue);
+        }
         Ok(self.scene)
     }
 
@@ -27,12 +88,48 @@ impl Transformer {
         parent: Option<NodeId>,
     ) -> Result<NodeId, String> {
         let node_id = self.scene.add_node(&decl.name, &decl.gem_type);
+        if decl.unique {
+            if parent.is_none() {
+                return Err(format!(
+                    "%{}: the scene root can't be scene-unique",
+                    decl.name
+                ));
+            }
+            self.scene.set_unique(node_id)?;
+        }
 
-        // Add properties with type inference
+        // Add typed properties
         for prop in &decl.properties {
-            let value_str = self.value_to_string(&prop.value);
-            let prop_type = PropertyType::infer(&value_str);
-            self.scene.set_typed_property(node_id, &prop.key, value_str, prop_type);
+            let value = self.checked_value(decl, prop)?;
+            self.scene.set_property(node_id, &prop.key, value);
+        }
+        self.path.push(decl.name.clone());
+        let overridden: Vec<(String, PropValue)> = self
+            .overrides
+            .iter()
+            .filter(|o| o.path == self.path)
+            .flat_map(|o| &o.properties)
+            .map(|p| Ok((p.key.clone(), self.checked_value(decl, p)?)))
+            .collect::<Result<_, String>>()?;
+        for (key, value) in overridden {
+            self.scene.set_property(node_id, key, value);
+        }
+        self.apply_class(node_id, decl);
+        for entry in &decl.meta {
+            // `meta:uid` is the node's stable identifier rather than metadata
+            if entry.key == uid::META {
+                let Value::String(uid) = &entry.value else {
+                    return Err(format!(
+                        "{}: meta:{} must be a string",
+                        decl.name,
+                        uid::META
+                    ));
+                };
+                self.scene.set_uid(node_id, uid)?;
+                continue;
+            }
+            let value = self.checked_value(decl, entry)?;
+            self.scene.set_meta(node_id, &entry.key, value);
         }
 
         // Attach to parent if provided
@@ -44,28 +141,174 @@ impl Transformer {
         for child in &decl.children {
             self.transform_gem_decl(child, Some(node_id))?;
         }
+        self.path.pop();
 
         Ok(node_id)
     }
 
-    fn value_to_string(&self, value: &Value) -> String {
+    /// Check the node's properties against its class, converting values to
+    /// the property's type and filling in defaults for those it doesn't set
+    fn apply_class(&mut self, node_id: NodeId, decl: &GemDecl) {
+        let Some(class) = class_defs::find(&decl.gem_type) else {
+            self.issues
+                .push(format!("{}: unknown Gem type {}", decl.name, decl.gem_type));
+            return;
+        };
+        let node = self.scene.nodes.get_mut(&node_id).unwrap();
+        let mut properties: Vec<_> = node.properties.iter_mut().collect();
+        properties.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, property) in properties {
+            let Some(def) = class.property(key) else {
+                self.issues.push(format!(
+                    "{}: {} has no property {} (use meta:{} for custom data)",
+                    decl.name, decl.gem_type, key, key
+                ));
+                continue;
+            };
+            match def.coerce(&property.value) {
+                Some(value) => *property = TypedProperty::new(value),
+                None => self.issues.push(format!(
+                    "{}: {} takes {:?}, got {:?}",
+                    decl.name, key, def.types[0], property.prop_type
+                )),
+            }
+        }
+        for def in class.all_properties() {
+            if let Some(default) = def.default
+                && !node.properties.contains_key(def.name)
+            {
+                let value = TypedProperty::new(default.to_prop_value());
+                node.properties.insert(def.name.to_string(), value);
+            }
+        }
+    }
+
+    /// `prop`'s typed value, which codegen must be able to give a type
+    fn checked_value(&self, decl: &GemDecl, prop: &Property) -> Result<PropValue, String> {
+        let value = self.prop_value(&prop.value);
+        if let Some(what) = mixed_collection(&value) {
+            return Err(format!(
+                "{}: {}: {} must all have the same type",
+                decl.name, prop.key, what
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Typed IR value straight from the AST; two-number tuples are vectors
+    fn prop_value(&self, value: &Value) -> PropValue {
         match value {
-            Value::Number(n) => n.to_string(),
-            Value::Integer(i) => i.to_string(),
-            Value::String(s) => format!("\"{}\"", s.replace('\"', "\\\"")),
-            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => PropValue::Float(*n),
+            Value::Integer(i) => PropValue::Int(*i),
+            Value::String(s) | Value::Ident(s) => PropValue::Str(s.clone()),
+            Value::Bool(b) => PropValue::Bool(*b),
             Value::Tuple(vals) => {
-                let items: Vec<String> = vals.iter().map(|v| self.value_to_string(v)).collect();
-                format!("({})", items.join(", "))
+                if let [x, y] = vals.as_slice()
+                    && let (Some(x), Some(y)) = (number(x), number(y))
+                {
+                    return PropValue::Vec2(x, y);
+                }
+                PropValue::List(vals.iter().map(|v| self.prop_value(v)).collect())
+            }
+            Value::Array(vals) => {
+                let mut items: Vec<PropValue> = vals.iter().map(|v| self.prop_value(v)).collect();
+                promote_ints(items.iter_mut());
+                PropValue::Array(items)
+            }
+            Value::Map(entries) => {
+                let mut entries: Vec<(String, PropValue)> = entries
+                    .iter()
+                    .map(|(key, v)| (key.clone(), self.prop_value(v)))
+                    .collect();
+                promote_ints(entries.iter_mut().map(|(_, v)| v));
+                PropValue::Map(entries)
             }
-            Value::Directive(parts) => {
-                format!("#{}", parts.join(":"))
+            Value::Color(c) => PropValue::Color(*c),
+            Value::Directive(directive) => PropValue::SceneRef(directive.clone()),
+            Value::Curve(curve) => PropValue::Curve(curve.clone()),
+            Value::SizeExpr(expr) => PropValue::SizeExpr(expr.clone()),
+            Value::Struct(fields) => PropValue::Struct(
+                fields
+                    .iter()
+                    .map(|f| (f.key.clone(), self.prop_value(&f.value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Integers among floats are floats
+fn promote_ints<'a>(values: impl Iterator<Item = &'a mut PropValue>) {
+    let mut values: Vec<&mut PropValue> = values.collect();
+    if values.iter().any(|v| matches!(v, PropValue::Float(_))) {
+        for value in &mut values {
+            if let PropValue::Int(i) = **value {
+                **value = PropValue::Float(i as f64);
             }
-            Value::Ident(id) => id.clone(),
         }
     }
 }
 
+/// What within `value` mixes types: array elements or map values, which
+/// must have one shape for codegen to give them a slice type
+fn mixed_collection(value: &PropValue) -> Option<&'static str> {
+    let (inner, what): (Vec<&PropValue>, _) = match value {
+        PropValue::Array(items) => (items.iter().collect(), Some("array elements")),
+        PropValue::Map(entries) => (entries.iter().map(|(_, v)| v).collect(), Some("map values")),
+        PropValue::List(items) => (items.iter().collect(), None),
+        PropValue::Struct(fields) => (fields.iter().map(|(_, v)| v).collect(), None),
+        _ => return None,
+    };
+    if let Some(mixed) = inner.iter().find_map(|v| mixed_collection(v)) {
+        return Some(mixed);
+    }
+    what.filter(|_| !inner.windows(2).all(|pair| same_shape(pair[0], pair[1])))
+}
+
+/// Whether `a` and `b` have the same Rust type in generated code
+fn same_shape(a: &PropValue, b: &PropValue) -> bool {
+    match (a, b) {
+        (PropValue::List(x), PropValue::List(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| same_shape(x, y))
+        }
+        (PropValue::Struct(x), PropValue::Struct(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .zip(y)
+                    .all(|((kx, x), (ky, y))| kx == ky && same_shape(x, y))
+        }
+        // Arrays and maps of any length share a type
+        (PropValue::Array(x), PropValue::Array(y)) => match (x.first(), y.first()) {
+            (Some(x), Some(y)) => same_shape(x, y),
+            _ => true,
+        },
+        (PropValue::Map(x), PropValue::Map(y)) => match (x.first(), y.first()) {
+            (Some((_, x)), Some((_, y))) => same_shape(x, y),
+            _ => true,
+        },
+        _ => PropertyType::of(a) == PropertyType::of(b),
+    }
+}
+
+/// Whether `decl` has a descendant at `path` of child names
+fn has_path(decl: &GemDecl, path: &[String]) -> bool {
+    match path.split_first() {
+        None => true,
+        Some((name, rest)) => decl
+            .children
+            .iter()
+            .any(|c| c.name == *name && has_path(c, rest)),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => Some(*n),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,18 +319,23 @@ mod tests {
         let ast = GemFile {
             root: GemDecl {
                 name: "Root".to_string(),
+                unique: false,
                 gem_type: "Gem".to_string(),
                 properties: vec![Property {
                     key: "position".to_string(),
                     value: Value::Tuple(vec![Value::Integer(0), Value::Integer(0)]),
                 }],
+                meta: vec![],
                 children: vec![GemDecl {
                     name: "Child".to_string(),
+                    unique: false,
                     gem_type: "Sprite".to_string(),
                     properties: vec![],
+                    meta: vec![],
                     children: vec![],
                 }],
             },
+            errors: vec![],
         };
 
         let transformer = Transformer::new();
@@ -107,4 +355,114 @@ mod tests {
         assert_eq!(child.name, "Child");
         assert_eq!(child.class_name, "Sprite");
     }
+
+    #[test]
+    fn dump_lists_nodes_and_properties() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let src = "Hud: Gem { color: #102030 %Score: LabelGem { text: \"0\" position: (4, 8) } }";
+        let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse_scene().unwrap();
+        let scene = Transformer::new().transform(ast).unwrap();
+        assert_eq!(
+            scene.dump(),
+            "Hud (Gem) color=#102030\n  %Score (LabelGem) color=#b333cc position=(4.0, 8.0) \
+             size=(260.0, 40.0) text=\"0\"\n"
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_what_class_definitions_dont_know() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let transform = |src: &str, strict: bool| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse_scene().unwrap();
+            Transformer::new().with_strict(strict).transform(ast)
+        };
+        let src = "Hud: Gem { speed: 2 Score: Scoreboard {} Tick: TimerGem { one_shot: 1 } }";
+        assert!(transform(src, false).is_ok());
+        let err = transform(src, true).unwrap_err();
+        assert!(err.contains("Hud: Gem has no property speed"));
+        assert!(err.contains("Score: unknown Gem type Scoreboard"));
+        assert!(err.contains("one_shot takes Bool"));
+        assert!(transform("Hud: Gem { meta:speed: 2 }", true).is_ok());
+
+        let mut args = vec!["gem".to_string(), "--strict".into(), "check".into()];
+        assert!(take_flag(&mut args));
+        assert!(!take_flag(&mut args));
+        assert_eq!(args, ["gem", "check"]);
+    }
+
+    #[test]
+    fn unique_names_are_registered_once_per_scene() {
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let transform = |src: &str| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse_scene().unwrap();
+            Transformer::new().transform(ast)
+        };
+        let scene =
+            transform("Hud: Gem { Top: Gem { %Score: LabelGem {} } Lives: Gem {} }").unwrap();
+        let score = scene.unique_names["Score"];
+        assert_eq!(scene.nodes[&score].class_name, "LabelGem");
+        assert_eq!(scene.unique_names.len(), 1);
+
+        let err = transform("Hud: Gem { %Score: Gem {} Top: Gem { %Score: Gem {} } }").unwrap_err();
+        assert!(err.contains("%Score is used more than once"));
+        assert!(
+            transform("%Hud: Gem {}")
+                .unwrap_err()
+                .contains("scene root")
+        );
+    }
+
+    #[test]
+    fn uids_survive_inserts_and_renames_in_diffs() {
+        use crate::ir::{NodeChange, diff};
+        use crate::lexer::Lexer;
+        use crate::parser::Parser;
+
+        let transform = |src: &str| {
+            let tokens = Lexer::new(src.to_string()).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse_scene().unwrap();
+            Transformer::new().transform(ast)
+        };
+        let old = transform(r#"Main: Gem { Player: Gem { speed: 1 meta:uid: "p1" } Hud: Gem {} }"#)
+            .unwrap();
+        let player = old.uids["p1"];
+        assert_eq!(old.nodes[&player].uid.as_deref(), Some("p1"));
+        assert!(old.nodes[&player].meta.is_empty());
+
+        // A node inserted first renumbers Player, and Player is renamed
+        let new = transform(
+            r#"Main: Gem { Sky: Gem {} Hero: Gem { speed: 2 meta:uid: "p1" } Hud: Gem {} }"#,
+        )
+        .unwrap();
+        assert_ne!(new.uids["p1"], player);
+        assert_eq!(
+            diff(&old, &new),
+            [
+                NodeChange::Moved {
+                    from: "/Main/Player".into(),
+                    to: "/Main/Hero".into()
+                },
+                NodeChange::Changed {
+                    path: "/Main/Hero".into(),
+                    keys: vec!["speed".into()]
+                },
+                NodeChange::Added {
+                    path: "/Main/Sky".into()
+                },
+            ]
+        );
+
+        let err = transform(r#"Main: Gem { meta:uid: "a" B: Gem { meta:uid: "a" } }"#).unwrap_err();
+        assert!(err.contains("uid \"a\" is used by both Main and B"));
+        assert!(transform("Main: Gem { meta:uid: 3 }").is_err());
+    }
 }
@@ -0,0 +1,116 @@
+//! Stable node identifiers.
+//! A node's uid is written in the scene as `meta:uid: "5f0c9a1e27b34d68"`.
+//! `NodeId`s count declarations, so adding a node renumbers the ones after
+//! it; a uid stays with its node through inserts, renames and moves, which
+//! lets generated code, the hot-reload differ and the editor keep pointing
+//! at the same node. `gem uids scene.gem` gives every node without one a
+//! fresh uid and writes the scene back.
+
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::ast::{GemDecl, GemFile, Property, Value};
+use crate::draw_list::NodePath;
+
+/// Metadata key holding a node's uid
+pub const META: &str = "uid";
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A new random uid: 16 hex digits
+pub fn generate() -> String {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}", RandomState::new().hash_one(n))
+}
+
+/// The uid declared on `decl`
+pub fn of(decl: &GemDecl) -> Option<&str> {
+    decl.meta.iter().find_map(|m| match &m.value {
+        Value::String(uid) if m.key == META => Some(uid.as_str()),
+        _ => None,
+    })
+}
+
+/// Drop `decl`'s uid and its descendants', e.g. on a copy that must not
+/// share them
+pub fn clear(decl: &mut GemDecl) {
+    decl.meta.retain(|m| m.key != META);
+    decl.children.iter_mut().for_each(clear);
+}
+
+/// Give every node in `file` without a uid a new one. Returns how many were
+/// added.
+pub fn assign(file: &mut GemFile) -> usize {
+    let mut used = HashSet::new();
+    collect(&file.root, &mut used);
+    assign_decl(&mut file.root, &mut used)
+}
+
+fn collect(decl: &GemDecl, used: &mut HashSet<String>) {
+    if let Some(uid) = of(decl) {
+        used.insert(uid.to_string());
+    }
+    decl.children.iter().for_each(|c| collect(c, used));
+}
+
+fn assign_decl(decl: &mut GemDecl, used: &mut HashSet<String>) -> usize {
+    let mut added = 0;
+    if of(decl).is_none() {
+        let uid = loop {
+            let uid = generate();
+            if used.insert(uid.clone()) {
+                break uid;
+            }
+        };
+        decl.meta.push(Property {
+            key: META.to_string(),
+            value: Value::String(uid),
+        });
+        added += 1;
+    }
+    for child in &mut decl.children {
+        added += assign_decl(child, used);
+    }
+    added
+}
+
+/// Child-index path of the node with `uid` under `root`
+pub fn find(root: &GemDecl, uid: &str) -> Option<NodePath> {
+    if of(root) == Some(uid) {
+        return Some(Vec::new());
+    }
+    root.children.iter().enumerate().find_map(|(i, child)| {
+        let mut path = find(child, uid)?;
+        path.insert(0, i);
+        Some(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline;
+
+    #[test]
+    fn uids_are_assigned_once_and_found() {
+        let mut scene = pipeline::lex_source(
+            r#"Main: Gem { A: Gem { meta:uid: "aaaa" } B: Gem { C: Gem {} } }"#,
+        )
+        .and_then(pipeline::parse_scene)
+        .unwrap();
+        assert_eq!(assign(&mut scene), 3);
+        assert_eq!(assign(&mut scene), 0);
+        assert_eq!(of(&scene.root.children[0]), Some("aaaa"));
+        let c = of(&scene.root.children[1].children[0]).unwrap().to_string();
+        assert_eq!(c.len(), 16);
+        assert_eq!(find(&scene.root, &c), Some(vec![1, 0]));
+        assert_eq!(find(&scene.root, "aaaa"), Some(vec![0]));
+        assert_eq!(find(&scene.root, "none"), None);
+
+        clear(&mut scene.root.children[1]);
+        assert_eq!(find(&scene.root, &c), None);
+        assert!(of(&scene.root).is_some());
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fmt;
 
+use crate::color::Color;
 use crate::object::ObjectRef; // forward reference (object.rs will declare ObjectRef)
 
 #[derive(Clone, PartialEq)]
@@ -13,6 +14,7 @@ pub enum Value {
     Array(Vec<Value>),
     Map(HashMap<String, Value>),
     Object(ObjectRef),
+    Color(Color),
 }
 
 impl fmt::Debug for Value {
@@ -26,6 +28,7 @@ impl fmt::Debug for Value {
             Value::Array(arr) => write!(f, "Array(len={})", arr.len()),
             Value::Map(map) => write!(f, "Map(len={})", map.len()),
             Value::Object(obj) => write!(f, "Object(class={}, id={})", obj.class_name(), obj.id()),
+            Value::Color(c) => write!(f, "{:?}", c),
         }
     }
 }
@@ -37,6 +40,33 @@ impl Value {
             _ => None,
         }
     }
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+    /// A two-element numeric array, the runtime's point/size shape
+    pub fn as_vec2(&self) -> Option<(f64, f64)> {
+        match self {
+            Value::Array(items) if items.len() == 2 => {
+                Some((items[0].as_f64()?, items[1].as_f64()?))
+            }
+            _ => None,
+        }
+    }
+    /// A color, or a `#rrggbb`/`#rrggbbaa` string
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            Value::Color(c) => Some(*c),
+            Value::String(s) => Color::from_hex(s),
+            _ => None,
+        }
+    }
+    pub fn vec2(x: f64, y: f64) -> Value {
+        Value::Array(vec![Value::Float(x), Value::Float(y)])
+    }
     pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
         match self {
             Value::Array(v) => Some(v),
@@ -0,0 +1,347 @@
+//! Virtual filesystem behind every engine file access.
+//! Paths use two schemes: `res://` for the project's files, read-only, and
+//! `user://` for the per-user data directory (saves, settings). A path
+//! without a scheme is a `res://` path relative to the project root, which is
+//! how directives and the registry name files. Paths are checked component by
+//! component: `..` and absolute paths are refused, so nothing can reach
+//! outside its root.
+//!
+//! `res://` is served by a stack of sources, most recently mounted first. The
+//! project directory and then the engine's built-in files (see `defaults`)
+//! are always at the bottom; a `.gempack` archive opened from disk or embedded
+//! in the executable can be mounted over them to ship a game without loose
+//! files. `user://` is always a plain directory.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::defaults;
+
+/// A place `res://` files can come from; paths are relative to the project root
+pub trait Source: Send + Sync {
+    fn read(&self, path: &Path) -> Option<Vec<u8>>;
+    fn is_file(&self, path: &Path) -> bool;
+    /// Every file below `dir`, recursively
+    fn list(&self, dir: &Path) -> Vec<PathBuf>;
+}
+
+/// Files in a directory on disk
+pub struct DirSource {
+    root: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Source for DirSource {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        fs::read(self.root.join(path)).ok()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        // A missing directory just has no files
+        let _ = collect_files(&self.root.join(dir), &mut files);
+        files
+            .into_iter()
+            .map(|f| match f.strip_prefix(&self.root) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => f,
+            })
+            .collect()
+    }
+}
+
+const PACK_MAGIC: &[u8; 8] = b"GEMPACK\0";
+
+/// Files stored in a pack: the magic, a little-endian u32 file count, then
+/// for each file its `/`-separated path (u32 length + UTF-8) and its data
+/// (u64 length + bytes)
+pub struct PackSource {
+    data: Cow<'static, [u8]>,
+    /// Byte range of each file's data
+    files: HashMap<PathBuf, (usize, usize)>,
+}
+
+impl PackSource {
+    /// A pack in memory, owned or embedded with `include_bytes!`
+    pub fn from_bytes(data: impl Into<Cow<'static, [u8]>>) -> Result<Self, String> {
+        let data = data.into();
+        let files = parse_pack(&data).ok_or("not a valid .gempack")?;
+        Ok(Self { data, files })
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_bytes(data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl Source for PackSource {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        let (start, end) = *self.files.get(path)?;
+        Some(self.data[start..end].to_vec())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter(|f| f.starts_with(dir))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+fn parse_pack(data: &[u8]) -> Option<HashMap<PathBuf, (usize, usize)>> {
+    let mut at = PACK_MAGIC.len();
+    if data.get(..at)? != PACK_MAGIC {
+        return None;
+    }
+    let mut take = |n: usize| {
+        let range = (at, at.checked_add(n)?);
+        at = range.1;
+        data.get(range.0..range.1).map(|_| range)
+    };
+    let int = |(start, end): (usize, usize)| {
+        data[start..end]
+            .iter()
+            .rev()
+            .fold(0usize, |n, b| n << 8 | *b as usize)
+    };
+    let count = int(take(4)?);
+    let mut files = HashMap::new();
+    for _ in 0..count {
+        let len = int(take(4)?);
+        let (start, end) = take(len)?;
+        let path = std::str::from_utf8(&data[start..end]).ok()?;
+        let len = int(take(8)?);
+        files.insert(PathBuf::from(path), take(len)?);
+    }
+    Some(files)
+}
+
+/// Pack every file under `dirs` (relative to the project root) into `out`
+pub fn write_pack(dirs: &[PathBuf], out: &Path) -> Result<usize, String> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        collect_files(dir, &mut files).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let mut pack = PACK_MAGIC.to_vec();
+    pack.extend((files.len() as u32).to_le_bytes());
+    for path in &files {
+        let name = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        pack.extend((name.len() as u32).to_le_bytes());
+        pack.extend(name.as_bytes());
+        pack.extend((data.len() as u64).to_le_bytes());
+        pack.extend(data);
+    }
+    fs::write(out, pack).map_err(|e| format!("{}: {}", out.display(), e))?;
+    Ok(files.len())
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else {
+            out.push(path.strip_prefix(".").unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+static SOURCES: RwLock<Vec<Arc<dyn Source>>> = RwLock::new(Vec::new());
+/// Replaces the platform data directory in tests
+static USER_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Serve `res://` files from `source` ahead of everything mounted before it
+pub fn mount(source: Arc<dyn Source>) {
+    SOURCES.write().unwrap().push(source);
+}
+
+/// Mounted sources, most recent first, then the project directory and the
+/// engine's built-in files
+fn sources() -> Vec<Arc<dyn Source>> {
+    let mut sources = SOURCES.read().unwrap().clone();
+    sources.reverse();
+    sources.push(Arc::new(DirSource::new(".")));
+    sources.push(Arc::new(defaults::EmbeddedSource));
+    sources
+}
+
+#[cfg(test)]
+pub(crate) fn set_user_dir(dir: Option<PathBuf>) {
+    *USER_DIR.write().unwrap() = dir;
+}
+
+/// Where `user://` points on disk
+pub fn user_dir() -> PathBuf {
+    if let Some(dir) = USER_DIR.read().unwrap().clone() {
+        return dir;
+    }
+    let env = |var| std::env::var_os(var).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|h| h.join("Library/Application Support"))
+    } else {
+        env("XDG_DATA_HOME").or_else(|| env("HOME").map(|h| h.join(".local/share")))
+    };
+    base.unwrap_or_else(std::env::temp_dir).join("gem")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VPath {
+    /// Relative to the project root
+    Res(PathBuf),
+    /// Relative to the user data directory
+    User(PathBuf),
+}
+
+/// Split `path` into its root and a checked relative path
+pub fn resolve(path: &str) -> Result<VPath, String> {
+    let (rest, user) = if let Some(rest) = path.strip_prefix("res://") {
+        (rest, false)
+    } else if let Some(rest) = path.strip_prefix("user://") {
+        (rest, true)
+    } else if path.contains("://") {
+        return Err(format!("{}: paths must start with res:// or user://", path));
+    } else {
+        (path, false)
+    };
+    let mut relative = PathBuf::new();
+    for component in Path::new(rest).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("{}: leaves its root directory", path)),
+        }
+    }
+    Ok(if user {
+        VPath::User(relative)
+    } else {
+        VPath::Res(relative)
+    })
+}
+
+/// The file on disk behind a `user://` path
+pub fn user_file(path: &str) -> Result<PathBuf, String> {
+    match resolve(path)? {
+        VPath::User(relative) => Ok(user_dir().join(relative)),
+        VPath::Res(_) => Err(format!("{}: res:// is read-only", path)),
+    }
+}
+
+pub fn read(path: &str) -> Result<Vec<u8>, String> {
+    let found = match resolve(path)? {
+        VPath::Res(relative) => sources().iter().find_map(|s| s.read(&relative)),
+        VPath::User(relative) => fs::read(user_dir().join(relative)).ok(),
+    };
+    found.ok_or_else(|| format!("{} does not exist", path))
+}
+
+pub fn read_to_string(path: &str) -> Result<String, String> {
+    String::from_utf8(read(path)?).map_err(|_| format!("{} is not UTF-8 text", path))
+}
+
+/// Every `res://` file below the directory `path`, from all sources, sorted
+pub fn list(path: &str) -> Result<Vec<PathBuf>, String> {
+    let VPath::Res(dir) = resolve(path)? else {
+        return Err(format!("{}: only res:// directories can be listed", path));
+    };
+    let mut files: Vec<PathBuf> = sources().iter().flat_map(|s| s.list(&dir)).collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+pub fn is_file(path: &str) -> bool {
+    match resolve(path) {
+        Ok(VPath::Res(relative)) => sources().iter().any(|s| s.is_file(&relative)),
+        Ok(VPath::User(relative)) => user_dir().join(relative).is_file(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_override_the_project_directory() {
+        let dir = Path::new("target").join("gem_vfs_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("levels")).unwrap();
+        fs::write(dir.join("levels/one.gem"), "loose").unwrap();
+        fs::write(dir.join("readme.txt"), "only on disk").unwrap();
+        assert_eq!(
+            read_to_string("res://target/gem_vfs_test/levels/one.gem").unwrap(),
+            "loose"
+        );
+
+        let pack = dir.join("game.gempack");
+        assert_eq!(write_pack(&[dir.join("levels")], &pack).unwrap(), 1);
+        fs::write(dir.join("levels/one.gem"), "changed after packing").unwrap();
+        let pack = PackSource::open(&pack).unwrap();
+        assert!(pack.is_file(Path::new("target/gem_vfs_test/levels/one.gem")));
+        mount(Arc::new(pack));
+        // A bare path is a res:// path; both see the packed copy first
+        assert_eq!(
+            read_to_string("target/gem_vfs_test/levels/one.gem").unwrap(),
+            "loose"
+        );
+        assert!(is_file("res://target/gem_vfs_test/readme.txt"));
+        assert!(!is_file("res://target/gem_vfs_test/missing.txt"));
+        assert_eq!(
+            list("target/gem_vfs_test").unwrap(),
+            [
+                Path::new("target/gem_vfs_test/game.gempack"),
+                Path::new("target/gem_vfs_test/levels/one.gem"),
+                Path::new("target/gem_vfs_test/readme.txt"),
+            ]
+        );
+        assert!(PackSource::from_bytes(b"GEMPACK\0\x05\0\0\0".as_slice()).is_err());
+
+        for escape in [
+            "res://../x",
+            "user://../x",
+            "/etc/passwd",
+            "ftp://x",
+            "a/../../b",
+        ] {
+            assert!(resolve(escape).is_err(), "{} was allowed", escape);
+        }
+        assert_eq!(
+            resolve("user://saves/./a.json"),
+            Ok(VPath::User(PathBuf::from("saves/a.json")))
+        );
+        assert!(user_file("res://target/gem_vfs_test/x").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,615 @@
+//! WebSocketGem: a WebSocket client (RFC 6455) for lobbies and live data.
+//! `connect_to(url)` opens a `ws://` connection on a background thread, so
+//! the main loop never waits on the network. What arrives is queued and
+//! emitted from the node's `_process`, on the main thread: `connected`,
+//! `message_received(data)` (a String for text, an Array of byte Ints for
+//! binary) and `closed(code, reason)`. `send(data)` takes the same two
+//! shapes, and `close(code?)` ends the connection. `state` is `"closed"`,
+//! `"connecting"` or `"open"`. `wss://` needs TLS, which isn't supported yet.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use crate::gem;
+use crate::object::{MethodTable, ObjectRef, WeakObjectRef, define_method, register_class};
+use crate::value::Value;
+
+const STATE_KEY: &str = "state";
+/// Appended to the client's key to derive the server's accept value
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Sent when `close()` is called without a code
+const CLOSE_NORMAL: i64 = 1000;
+/// Reported when the connection ends without a close frame
+const CLOSE_ABNORMAL: i64 = 1006;
+
+pub fn init_websocket_class() {
+    register_class("WebSocketGem", || {
+        let obj = gem::new_gem("WebSocketGem", websocket_methods());
+        obj.set_property(STATE_KEY, Value::String("closed".into()));
+        obj
+    });
+}
+
+/// The open stream, if any, and which `connect_to` call it belongs to; a
+/// reader thread whose connection was replaced stays quiet
+#[derive(Default)]
+struct Connection {
+    stream: Option<TcpStream>,
+    generation: u64,
+    /// Signals waiting for the next `_process`
+    inbox: Vec<(&'static str, Vec<Value>)>,
+}
+
+/// Shared by the node and its reader thread
+type Socket = Arc<Mutex<Connection>>;
+
+fn socket(node: &ObjectRef) -> Socket {
+    gem::class_state(node)
+}
+
+fn websocket_methods() -> Arc<MethodTable> {
+    static METHODS: OnceLock<Arc<MethodTable>> = OnceLock::new();
+    METHODS
+        .get_or_init(|| Arc::new(build_websocket_methods()))
+        .clone()
+}
+
+fn build_websocket_methods() -> MethodTable {
+    let mut methods = (*gem::gem_methods()).clone();
+
+    // connect_to(url): starts connecting; `connected` or `closed` follows
+    define_method(
+        &mut methods,
+        "connect_to",
+        Arc::new(|this, args| {
+            let Some(Value::String(url)) = args.first() else {
+                return Err("connect_to expects a ws:// URL".into());
+            };
+            let target = Target::parse(url)?;
+            let s = socket(this);
+            close_socket(&s, CLOSE_NORMAL);
+            let generation = {
+                let mut connection = s.lock().unwrap();
+                connection.generation += 1;
+                connection.generation
+            };
+            this.set_property(STATE_KEY, Value::String("connecting".into()));
+            let node = this.downgrade();
+            thread::spawn(move || run(target, node, s, generation));
+            Ok(Value::Null)
+        }),
+    );
+    // send(text | bytes)
+    define_method(
+        &mut methods,
+        "send",
+        Arc::new(|this, args| {
+            let (opcode, payload) = match args.first() {
+                Some(Value::String(text)) => (OP_TEXT, text.as_bytes().to_vec()),
+                Some(Value::Array(bytes)) => (OP_BINARY, to_bytes(bytes)?),
+                _ => return Err("send expects a String or an Array of bytes".into()),
+            };
+            let s = socket(this);
+            let mut connection = s.lock().unwrap();
+            let stream = connection
+                .stream
+                .as_mut()
+                .ok_or("send: the socket isn't open")?;
+            write_frame(stream, opcode, &payload, true).map_err(|e| format!("send: {}", e))?;
+            Ok(Value::Null)
+        }),
+    );
+    // _process(dt): emit what the reader thread received since the last frame
+    define_method(
+        &mut methods,
+        "_process",
+        Arc::new(|this, _| {
+            let inbox = std::mem::take(&mut socket(this).lock().unwrap().inbox);
+            for (signal, args) in inbox {
+                this.emit_signal(signal, &args);
+            }
+            Ok(Value::Null)
+        }),
+    );
+    // close(code?)
+    define_method(
+        &mut methods,
+        "close",
+        Arc::new(|this, args| {
+            let code = match args.first() {
+                Some(Value::Int(code)) => *code,
+                _ => CLOSE_NORMAL,
+            };
+            close_socket(&socket(this), code);
+            Ok(Value::Null)
+        }),
+    );
+
+    // signals: connected, message_received(data), closed(code, reason)
+    methods
+}
+
+/// Send a close frame and stop the reader; it reports `closed`
+fn close_socket(socket: &Socket, code: i64) {
+    if let Some(mut stream) = socket.lock().unwrap().stream.take() {
+        let _ = write_frame(&mut stream, OP_CLOSE, &(code as u16).to_be_bytes(), true);
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn to_bytes(values: &[Value]) -> Result<Vec<u8>, String> {
+    values
+        .iter()
+        .map(|v| match v {
+            Value::Int(b) if (0..=255).contains(b) => Ok(*b as u8),
+            _ => Err("send expects bytes from 0 to 255".to_string()),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Target {
+    fn parse(url: &str) -> Result<Target, String> {
+        if url.starts_with("wss://") {
+            return Err("wss:// needs TLS, which isn't supported yet".into());
+        }
+        let rest = url
+            .strip_prefix("ws://")
+            .ok_or_else(|| format!("{} isn't a ws:// URL", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("{}: bad port {}", url, port))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("{} has no host", url));
+        }
+        Ok(Target {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// The connection thread: handshake, then read until the socket closes
+fn run(target: Target, node: WeakObjectRef, socket: Socket, generation: u64) {
+    let emit = |signal: &'static str, args: &[Value]| {
+        let mut connection = socket.lock().unwrap();
+        if connection.generation == generation {
+            connection.inbox.push((signal, args.to_vec()));
+        }
+    };
+    let set_state = |state: &str| {
+        if socket.lock().unwrap().generation == generation
+            && let Some(node) = node.upgrade()
+        {
+            node.set_property(STATE_KEY, Value::String(state.into()));
+        }
+    };
+    let opened = handshake(&target)
+        .and_then(|stream| Ok((stream.try_clone().map_err(|e| e.to_string())?, stream)));
+    let reader = match opened {
+        Ok((reader, stream)) => {
+            let mut connection = socket.lock().unwrap();
+            if connection.generation != generation {
+                return;
+            }
+            connection.stream = Some(stream);
+            reader
+        }
+        Err(e) => {
+            set_state("closed");
+            emit("closed", &[Value::Int(CLOSE_ABNORMAL), Value::String(e)]);
+            return;
+        }
+    };
+    set_state("open");
+    emit("connected", &[]);
+
+    let (code, reason) = read_messages(reader, &socket, generation, |opcode, payload| {
+        let data = if opcode == OP_TEXT {
+            Value::String(String::from_utf8_lossy(&payload).into_owned())
+        } else {
+            Value::Array(payload.into_iter().map(|b| Value::Int(b as i64)).collect())
+        };
+        emit("message_received", &[data]);
+    });
+    {
+        let mut connection = socket.lock().unwrap();
+        if connection.generation != generation {
+            return;
+        }
+        connection.stream = None;
+    }
+    set_state("closed");
+    emit("closed", &[Value::Int(code), Value::String(reason)]);
+}
+
+fn handshake(target: &Target) -> Result<TcpStream, String> {
+    let mut stream = TcpStream::connect((target.host.as_str(), target.port))
+        .map_err(|e| format!("{}:{}: {}", target.host, target.port, e))?;
+    let key = base64(&random_bytes::<16>());
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        target.path, target.host, target.port, key
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Read the response headers byte by byte so no frame data is consumed
+    let headers = stream.try_clone().map_err(|e| e.to_string())?.take(8192);
+    let mut reader = BufReader::with_capacity(1, headers);
+    let mut status = String::new();
+    reader.read_line(&mut status).map_err(|e| e.to_string())?;
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(format!("handshake refused: {}", status.trim()));
+    }
+    let mut accept = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("handshake cut short".into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("sec-websocket-accept")
+        {
+            accept = Some(value.trim().to_string());
+        }
+    }
+    if accept == Some(accept_key(&key)) {
+        Ok(stream)
+    } else {
+        Err("handshake failed: bad Sec-WebSocket-Accept".into())
+    }
+}
+
+/// Deliver messages until the connection ends; returns the close code and reason
+fn read_messages(
+    mut reader: TcpStream,
+    socket: &Socket,
+    generation: u64,
+    mut on_message: impl FnMut(u8, Vec<u8>),
+) -> (i64, String) {
+    let mut message: Option<(u8, Vec<u8>)> = None;
+    loop {
+        let (fin, opcode, payload) = match read_frame(&mut reader) {
+            Ok(frame) => frame,
+            Err(e) => return (CLOSE_ABNORMAL, e.to_string()),
+        };
+        match opcode {
+            OP_TEXT | OP_BINARY => message = Some((opcode, payload)),
+            OP_CONTINUATION => match &mut message {
+                Some((_, data)) => data.extend(payload),
+                None => return (CLOSE_ABNORMAL, "continuation without a message".into()),
+            },
+            OP_PING => {
+                let mut connection = socket.lock().unwrap();
+                if connection.generation == generation
+                    && let Some(stream) = connection.stream.as_mut()
+                {
+                    let _ = write_frame(stream, OP_PONG, &payload, true);
+                }
+                continue;
+            }
+            OP_PONG => continue,
+            OP_CLOSE => {
+                let code = match payload.get(..2) {
+                    Some(&[hi, lo]) => u16::from_be_bytes([hi, lo]) as i64,
+                    _ => CLOSE_NORMAL,
+                };
+                let reason = String::from_utf8_lossy(payload.get(2..).unwrap_or(&[])).into_owned();
+                // Echo the close unless we started it (the stream is gone then)
+                let mut connection = socket.lock().unwrap();
+                if connection.generation == generation
+                    && let Some(mut stream) = connection.stream.take()
+                {
+                    let _ = write_frame(
+                        &mut stream,
+                        OP_CLOSE,
+                        &payload[..payload.len().min(2)],
+                        true,
+                    );
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+                return (code, reason);
+            }
+            other => return (CLOSE_ABNORMAL, format!("unknown opcode {}", other)),
+        }
+        if fin && let Some((opcode, data)) = message.take() {
+            on_message(opcode, data);
+        }
+    }
+}
+
+/// One frame as (fin, opcode, unmasked payload)
+fn read_frame(reader: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut b = [0u8; 2];
+            reader.read_exact(&mut b)?;
+            u16::from_be_bytes(b) as u64
+        }
+        127 => {
+            let mut b = [0u8; 8];
+            reader.read_exact(&mut b)?;
+            u64::from_be_bytes(b)
+        }
+        n => n as u64,
+    };
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        payload
+            .iter_mut()
+            .zip(mask.iter().cycle())
+            .for_each(|(b, m)| *b ^= m);
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Clients mask every frame they send; servers don't
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8], mask: bool) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let mask_bit = if mask { 0x80 } else { 0 };
+    match payload.len() {
+        n if n < 126 => frame.push(mask_bit | n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend((n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(mask_bit | 127);
+            frame.extend((n as u64).to_be_bytes());
+        }
+    }
+    if mask {
+        let key = random_bytes::<4>();
+        frame.extend(key);
+        frame.extend(payload.iter().zip(key.iter().cycle()).map(|(b, m)| b ^ m));
+    } else {
+        frame.extend(payload);
+    }
+    stream.write_all(&frame)
+}
+
+/// Unpredictable enough for handshake keys and frame masks
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut out = [0u8; N];
+    for chunk in out.chunks_mut(8) {
+        let r = RandomState::new()
+            .hash_one(thread::current().id())
+            .to_le_bytes();
+        chunk.copy_from_slice(&r[..chunk.len()]);
+    }
+    out
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend((data.len() as u64 * 8).to_be_bytes());
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (hi, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *hi = hi.wrapping_add(v);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (chunk, v) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&v.to_be_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::object_new;
+    use crate::scene_tree;
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn handshake_key_matches_the_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert!(Target::parse("wss://example.com").is_err());
+        assert_eq!(
+            Target::parse("ws://localhost:9000"),
+            Ok(Target {
+                host: "localhost".into(),
+                port: 9000,
+                path: "/".into()
+            })
+        );
+    }
+
+    #[test]
+    fn messages_round_trip_through_an_echo_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut key = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(k) = line.strip_prefix("Sec-WebSocket-Key: ") {
+                    key = k.trim().to_string();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            )
+            .unwrap();
+            // Echo one message split in two frames, then close
+            let (_, opcode, payload) = read_frame(&mut reader).unwrap();
+            let (head, tail) = payload.split_at(1);
+            stream.write_all(&[opcode, head.len() as u8]).unwrap();
+            stream.write_all(head).unwrap();
+            write_frame(&mut stream, OP_CONTINUATION, tail, false).unwrap();
+            write_frame(
+                &mut stream,
+                OP_CLOSE,
+                &[0x03, 0xE8, b'b', b'y', b'e'],
+                false,
+            )
+            .unwrap();
+            read_frame(&mut reader).unwrap()
+        });
+
+        scene_tree::init_builtin_classes();
+        let ws = object_new("WebSocketGem");
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for signal in ["connected", "message_received", "closed"] {
+            let log = log.clone();
+            ws.connect(
+                signal,
+                Arc::new(move |args| log.lock().unwrap().push((signal, args.to_vec()))),
+            );
+        }
+        ws.call_method(
+            "connect_to",
+            &[Value::String(format!("ws://127.0.0.1:{}/chat", port))],
+        )
+        .unwrap();
+        let wait_for = |count: usize| {
+            let start = Instant::now();
+            while log.lock().unwrap().len() < count {
+                assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+                ws.call_method("_process", &[Value::Float(0.0)]).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+        wait_for(1);
+        assert_eq!(
+            ws.get_property(STATE_KEY),
+            Some(Value::String("open".into()))
+        );
+        ws.call_method("send", &[Value::String("hello".into())])
+            .unwrap();
+        wait_for(3);
+
+        let (_, opcode, payload) = server.join().unwrap();
+        assert_eq!((opcode, payload), (OP_CLOSE, vec![0x03, 0xE8]));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                ("connected", vec![]),
+                ("message_received", vec![Value::String("hello".into())]),
+                (
+                    "closed",
+                    vec![Value::Int(1000), Value::String("bye".into())]
+                ),
+            ]
+        );
+        assert_eq!(
+            ws.get_property(STATE_KEY),
+            Some(Value::String("closed".into()))
+        );
+        assert!(
+            ws.call_method("send", &[Value::String("late".into())])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! The `WorkerPool` singleton: runs pure functions on a few background
+//! threads and reports each result with a `completed(job_id, result)` signal
+//! from the main loop (`failed(job_id, error)` if it returns an error or
+//! panics), so pathfinding, procedural generation or file parsing doesn't
+//! hitch the frame. A job only sees its arguments, never the scene tree.
+//! Functions are run by name from those `register`ed with the pool, which so
+//! far are the engine's own (`find_path`): logic files are only parsed, so
+//! their `fn`s can't be registered yet.
+
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::thread;
+
+use crate::navigation;
+use crate::object::{ObjectInner, ObjectRef};
+use crate::value::Value;
+
+pub type JobFn = dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static;
+
+/// At most this many worker threads, fewer on smaller machines
+const MAX_THREADS: usize = 4;
+
+static FUNCTIONS: RwLock<BTreeMap<String, Arc<JobFn>>> = RwLock::new(BTreeMap::new());
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+static QUEUE: OnceLock<Sender<Job>> = OnceLock::new();
+
+struct Job {
+    id: i64,
+    function: Arc<JobFn>,
+    args: Vec<Value>,
+    /// Emits the job's result
+    pool: ObjectRef,
+}
+
+/// Make `f` runnable as `WorkerPool.run(name, args)`
+pub fn register(name: &str, f: Arc<JobFn>) {
+    FUNCTIONS.write().unwrap().insert(name.to_string(), f);
+}
+
+/// The functions the engine provides to run as jobs
+fn register_builtins() {
+    register("find_path", Arc::new(navigation::find_path_job));
+}
+
+/// The job queue, starting the workers on first use
+fn queue() -> &'static Sender<Job> {
+    QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = thread::available_parallelism().map_or(1, |n| n.get().min(MAX_THREADS));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("gem-worker-{}", i))
+                .spawn(move || {
+                    loop {
+                        // Let go of the queue before running the job
+                        let job = receiver.lock().unwrap().recv();
+                        let Ok(job) = job else { break };
+                        run_job(job);
+                    }
+                })
+                .expect("failed to start a worker thread");
+        }
+        sender
+    })
+}
+
+fn run_job(job: Job) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| (job.function)(&job.args)));
+    let id = Value::Int(job.id);
+    match result {
+        Ok(Ok(value)) => job.pool.emit_signal_deferred("completed", &[id, value]),
+        Ok(Err(e)) => job
+            .pool
+            .emit_signal_deferred("failed", &[id, Value::String(e)]),
+        Err(_) => {
+            let message = Value::String("the job panicked".into());
+            job.pool.emit_signal_deferred("failed", &[id, message]);
+        }
+    }
+}
+
+/// The script-facing `WorkerPool` singleton
+pub fn new_worker_pool() -> ObjectRef {
+    register_builtins();
+    let obj = ObjectInner::base("WorkerPool");
+    // run(name, args?) -> Int job id, matching the later `completed` signal
+    ObjectInner::insert_method(
+        &obj,
+        "run",
+        Arc::new(|this, args| {
+            let Some(Value::String(name)) = args.first() else {
+                return Err("run expects a function name".into());
+            };
+            let args = match args.get(1) {
+                None => Vec::new(),
+                Some(Value::Array(items)) => items.clone(),
+                Some(_) => return Err("run expects its arguments as an Array".into()),
+            };
+            let Some(function) = FUNCTIONS.read().unwrap().get(name).cloned() else {
+                return Err(format!("No function '{}' is registered to run", name));
+            };
+            let id = NEXT_JOB.fetch_add(1, Ordering::Relaxed) as i64;
+            let job = Job {
+                id,
+                function,
+                args,
+                pool: this.clone(),
+            };
+            queue()
+                .send(job)
+                .map_err(|_| "the worker threads have stopped".to_string())?;
+            Ok(Value::Int(id))
+        }),
+    );
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn results_arrive_as_signals_on_the_flush() {
+        let _queue = object::DEFERRED_TESTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        register(
+            "test_sum",
+            Arc::new(|args| {
+                let sum = args.iter().filter_map(Value::as_f64).sum();
+                Ok(Value::Float(sum))
+            }),
+        );
+        register("test_fail", Arc::new(|_| Err("no path".into())));
+        let pool = new_worker_pool();
+        let results = Arc::new(Mutex::new(Vec::new()));
+        for signal in ["completed", "failed"] {
+            let results = results.clone();
+            pool.connect(
+                signal,
+                Arc::new(move |args| results.lock().unwrap().push(args.to_vec())),
+            );
+        }
+
+        let nums = Value::Array(vec![Value::Int(2), Value::Float(0.5)]);
+        let sum = pool.call_method("run", &[Value::String("test_sum".into()), nums]);
+        let fail = pool.call_method("run", &[Value::String("test_fail".into())]);
+        // The engine's own jobs are registered with the pool
+        let rows = Value::Array(vec![Value::String(".#".into()), Value::String("..".into())]);
+        let path_args = vec![
+            rows,
+            Value::Float(10.0),
+            Value::vec2(5.0, 5.0),
+            Value::vec2(15.0, 15.0),
+        ];
+        let path = pool.call_method(
+            "run",
+            &[Value::String("find_path".into()), Value::Array(path_args)],
+        );
+        let (Ok(sum), Ok(fail), Ok(path)) = (sum, fail, path) else {
+            panic!("jobs should start");
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while results.lock().unwrap().len() < 3 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+            object::flush_deferred_signals();
+        }
+        let results = results.lock().unwrap();
+        assert!(results.contains(&vec![sum, Value::Float(2.5)]));
+        assert!(results.contains(&vec![fail, Value::String("no path".into())]));
+        let points = vec![
+            Value::vec2(5.0, 5.0),
+            Value::vec2(5.0, 15.0),
+            Value::vec2(15.0, 15.0),
+        ];
+        assert!(results.contains(&vec![path, Value::Array(points)]));
+
+        let missing = pool.call_method("run", &[Value::String("nope".into())]);
+        assert!(missing.unwrap_err().contains("'nope'"));
+    }
+}